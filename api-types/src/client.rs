@@ -0,0 +1,289 @@
+//! Typed client of the V2 HTTP API, so Rust services consume the API structs instead of the hand-written JSON.
+
+use primitive_types::H256;
+use reqwest::{header::ACCEPT, RequestBuilder, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use std::fmt::{self, Display};
+
+use crate::{
+	Block, DataQuery, DataRangeQuery, DataRangeResponse, DataResponse, FieldsQueryParameter,
+	Header, Status, SubmitResponse, SyncProgress, Transaction, TransactionStatusMessage, Version,
+};
+
+/// Error response of the API, with the response status and the error message
+#[derive(Debug)]
+pub struct ResponseError {
+	pub status: StatusCode,
+	pub message: String,
+}
+
+impl Display for ResponseError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"API responded with status {}: {}",
+			self.status, self.message
+		)
+	}
+}
+
+impl std::error::Error for ResponseError {}
+
+/// Error of the API client
+#[derive(Debug)]
+pub enum Error {
+	/// API URL is invalid, or its scheme is not supported
+	InvalidUrl(String),
+	/// Request failed, or its response cannot be decoded
+	Request(reqwest::Error),
+	/// API responded with the error status
+	Response(ResponseError),
+}
+
+impl Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::InvalidUrl(message) => write!(f, "{message}"),
+			Error::Request(error) => write!(f, "API request failed: {error}"),
+			Error::Response(error) => write!(f, "{error}"),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Error::InvalidUrl(_) => None,
+			Error::Request(error) => Some(error),
+			Error::Response(error) => Some(error),
+		}
+	}
+}
+
+impl From<reqwest::Error> for Error {
+	fn from(error: reqwest::Error) -> Self {
+		Error::Request(error)
+	}
+}
+
+/// Client of the light client V2 HTTP API
+#[derive(Clone, Debug)]
+pub struct Client {
+	http: reqwest::Client,
+	url: String,
+	api_key: Option<String>,
+}
+
+fn query(parameters: &[(&str, Option<String>)]) -> String {
+	let parameters = parameters
+		.iter()
+		.filter_map(|(name, value)| value.as_ref().map(|value| format!("{name}={value}")))
+		.collect::<Vec<_>>();
+	match parameters.is_empty() {
+		true => String::new(),
+		false => format!("?{}", parameters.join("&")),
+	}
+}
+
+fn fields(fields: &Option<FieldsQueryParameter>) -> Option<String> {
+	fields.as_ref().map(|FieldsQueryParameter(fields)| {
+		let mut fields = fields
+			.iter()
+			.filter_map(|field| serde_json::to_value(field).ok()?.as_str().map(String::from))
+			.collect::<Vec<_>>();
+		fields.sort();
+		fields.join(",")
+	})
+}
+
+fn data_query(data_query: &DataQuery) -> String {
+	query(&[
+		("fields", fields(&data_query.fields)),
+		("offset", data_query.offset.map(|offset| offset.to_string())),
+		("limit", data_query.limit.map(|limit| limit.to_string())),
+	])
+}
+
+impl Client {
+	/// Creates the client of the API at the given URL (e.g. `http://127.0.0.1:7007`, or `https://light.example.com`).
+	pub fn new(url: &str) -> Result<Self, Error> {
+		let parsed =
+			Url::parse(url).map_err(|_| Error::InvalidUrl(format!("Invalid API URL {url}")))?;
+		if !matches!(parsed.scheme(), "http" | "https") {
+			return Err(Error::InvalidUrl(format!(
+				"API URL {url} is not supported, only HTTP and HTTPS endpoints are supported"
+			)));
+		}
+		Ok(Client {
+			http: reqwest::Client::new(),
+			url: url.trim_end_matches('/').to_string(),
+			api_key: None,
+		})
+	}
+
+	/// Authenticates the requests with the given API key.
+	pub fn with_api_key(mut self, api_key: &str) -> Self {
+		self.api_key = Some(api_key.to_string());
+		self
+	}
+
+	async fn request<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, Error> {
+		let mut request = request.header(ACCEPT, "application/json");
+		if let Some(api_key) = &self.api_key {
+			request = request.bearer_auth(api_key);
+		}
+
+		let response = request.send().await?;
+		let status = response.status();
+		if !status.is_success() {
+			let message = response.text().await?;
+			return Err(Error::Response(ResponseError { status, message }));
+		}
+		Ok(response.json().await?)
+	}
+
+	async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+		self.request(self.http.get(format!("{}{path}", self.url)))
+			.await
+	}
+
+	/// Returns the version of the light client and of the network.
+	pub async fn version(&self) -> Result<Version, Error> {
+		self.get("/v2/version").await
+	}
+
+	/// Returns the status of the light client.
+	pub async fn status(&self) -> Result<Status, Error> {
+		self.get("/v2/status").await
+	}
+
+	/// Returns the status and the confidence of the block.
+	pub async fn block(&self, block_number: u32) -> Result<Block, Error> {
+		self.get(&format!("/v2/blocks/{block_number}")).await
+	}
+
+	/// Returns the verified header of the block.
+	pub async fn block_header(&self, block_number: u32) -> Result<Header, Error> {
+		self.get(&format!("/v2/blocks/{block_number}/header")).await
+	}
+
+	/// Returns the page of the block data transactions of the followed application.
+	pub async fn block_data(
+		&self,
+		block_number: u32,
+		query: &DataQuery,
+	) -> Result<DataResponse, Error> {
+		let query = data_query(query);
+		self.get(&format!("/v2/blocks/{block_number}/data{query}"))
+			.await
+	}
+
+	/// Returns the page of the stored application data of the block.
+	pub async fn app_data(
+		&self,
+		app_id: u32,
+		block_number: u32,
+		query: &DataQuery,
+	) -> Result<DataResponse, Error> {
+		let query = data_query(query);
+		self.get(&format!("/v2/apps/{app_id}/data/{block_number}{query}"))
+			.await
+	}
+
+	/// Returns the page of the stored application data of the block range.
+	pub async fn app_data_range(
+		&self,
+		app_id: u32,
+		range_query: &DataRangeQuery,
+	) -> Result<DataRangeResponse, Error> {
+		let query = query(&[
+			("from_block", Some(range_query.from_block.to_string())),
+			("to_block", Some(range_query.to_block.to_string())),
+			("page", range_query.page.map(String::from)),
+			("fields", fields(&range_query.fields)),
+		]);
+		self.get(&format!("/v2/apps/{app_id}/data{query}")).await
+	}
+
+	/// Submits the data transaction, or the signed extrinsic.
+	pub async fn submit(&self, transaction: &Transaction) -> Result<SubmitResponse, Error> {
+		let url = format!("{}/v2/submit", self.url);
+		self.request(self.http.post(url).json(transaction)).await
+	}
+
+	/// Returns the status of the submitted transaction.
+	pub async fn transaction_status(&self, hash: H256) -> Result<TransactionStatusMessage, Error> {
+		self.get(&format!("/v2/submit/{hash:?}/status")).await
+	}
+
+	/// Returns the progress of the historical sync.
+	pub async fn sync_progress(&self) -> Result<SyncProgress, Error> {
+		self.get("/v2/sync").await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use reqwest::StatusCode;
+	use std::collections::HashSet;
+	use test_case::test_case;
+	use warp::Filter;
+
+	use super::{data_query, Client, Error};
+	use crate::{DataField, DataQuery, FieldsQueryParameter, Version};
+
+	#[test_case("http://127.0.0.1:7007" => true ; "http")]
+	#[test_case("https://127.0.0.1:7007" => true ; "https")]
+	#[test_case("ws://127.0.0.1:7007" => false ; "unsupported scheme")]
+	#[test_case("not a url" => false ; "invalid url")]
+	fn test_new(url: &str) -> bool {
+		Client::new(url).is_ok()
+	}
+
+	#[test]
+	fn data_query_parameters() {
+		let fields = HashSet::from([DataField::Extrinsic, DataField::Data]);
+		let query = DataQuery {
+			fields: Some(FieldsQueryParameter(fields)),
+			offset: None,
+			limit: Some(10),
+		};
+		assert_eq!(data_query(&query), "?fields=data,extrinsic&limit=10");
+
+		let query = DataQuery {
+			fields: None,
+			offset: None,
+			limit: None,
+		};
+		assert_eq!(data_query(&query), "");
+	}
+
+	#[tokio::test]
+	async fn decodes_responses_and_errors() {
+		let version = warp::path!("v2" / "version")
+			.and(warp::header::<String>("authorization"))
+			.map(|authorization: String| {
+				warp::reply::json(&Version {
+					version: authorization,
+					network_version: "1.0".to_string(),
+				})
+			});
+		let not_found = warp::path!("v2" / "blocks" / u32)
+			.map(|_| warp::reply::with_status("Not Found", warp::http::StatusCode::NOT_FOUND));
+		let (address, server) =
+			warp::serve(version.or(not_found)).bind_ephemeral(([127, 0, 0, 1], 0));
+		tokio::spawn(server);
+
+		let client = Client::new(&format!("http://{address}/"))
+			.unwrap()
+			.with_api_key("key");
+		let version = client.version().await.unwrap();
+		assert_eq!(version.version, "Bearer key");
+
+		let Error::Response(error) = client.block(1).await.err().unwrap() else {
+			panic!("Error response is expected");
+		};
+		assert_eq!(error.status, StatusCode::NOT_FOUND);
+		assert_eq!(error.message, "Not Found");
+	}
+}
@@ -0,0 +1,577 @@
+//! Request and response types of the Avail light client HTTP API, shared by the API server and its clients.
+
+use base64::{engine::general_purpose, DecodeError, Engine};
+#[cfg(feature = "codec")]
+use codec::{Decode, Encode};
+use primitive_types::H256;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+	collections::HashSet,
+	fmt::{self, Display},
+};
+
+#[cfg(feature = "client")]
+pub mod client;
+
+/// Size of the KZG commitment, in bytes
+pub const COMMITMENT_SIZE: usize = 48;
+
+#[cfg(feature = "warp")]
+macro_rules! json_reply {
+	($($reply:ty),*) => {
+		$(
+			impl warp::Reply for $reply {
+				fn into_response(self) -> warp::reply::Response {
+					warp::Reply::into_response(warp::reply::json(&self))
+				}
+			}
+		)*
+	};
+}
+
+#[cfg(feature = "warp")]
+json_reply!(
+	Version,
+	FollowedApps,
+	SyncProgress,
+	Status,
+	SubmitResponse,
+	TransactionStatusMessage,
+	Block,
+	BlockByHash,
+	Confidence,
+	SampledCells,
+	Header,
+	DataResponse,
+	DataRangeResponse
+);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Version {
+	pub version: String,
+	pub network_version: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlockRange {
+	pub first: u32,
+	pub last: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HistoricalSync {
+	pub synced: bool,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub available: Option<BlockRange>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub app_data: Option<BlockRange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct FollowedApps {
+	pub app_ids: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SyncProgress {
+	pub start: u32,
+	pub end: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub lowest_verified: Option<u32>,
+	pub verified: u32,
+	pub total: u32,
+	pub percentage: f64,
+	/// Estimated time until the sync is completed, in seconds
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub eta: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Blocks {
+	pub latest: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub available: Option<BlockRange>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub app_data: Option<BlockRange>,
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub missed: Vec<BlockRange>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub historical_sync: Option<HistoricalSync>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Status {
+	pub modes: Vec<Mode>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub app_id: Option<u32>,
+	pub genesis_hash: String,
+	pub network: String,
+	pub blocks: Blocks,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub partition: Option<Partition>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub reachability: Option<Reachability>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+	Light,
+	App,
+	Partition,
+}
+
+/// Partition of the block matrix fetched by the fat client, in the `{number}/{fraction}` format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Partition {
+	pub number: u8,
+	pub fraction: u8,
+}
+
+impl TryFrom<String> for Partition {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		let (number, fraction) = value
+			.split_once('/')
+			.ok_or_else(|| format!("Invalid partition {value}"))?;
+		Ok(Partition {
+			number: number
+				.parse()
+				.map_err(|_| format!("Invalid partition number {number}"))?,
+			fraction: fraction
+				.parse()
+				.map_err(|_| format!("Invalid partition fraction {fraction}"))?,
+		})
+	}
+}
+
+impl From<Partition> for String {
+	fn from(value: Partition) -> Self {
+		format!("{}/{}", value.number, value.fraction)
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NatStatus {
+	#[default]
+	Unknown,
+	Public,
+	Private,
+}
+
+/// Reachability of the light client, as observed by the AutoNAT probes
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Reachability {
+	pub nat_status: NatStatus,
+	/// Public address confirmed by the AutoNAT servers
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub public_address: Option<String>,
+	/// Light client is listening on the relay circuit, which makes it dialable behind the NAT
+	pub relayed: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Base64(pub Vec<u8>);
+
+impl From<Base64> for Vec<u8> {
+	fn from(val: Base64) -> Self {
+		val.0
+	}
+}
+
+impl TryFrom<String> for Base64 {
+	type Error = DecodeError;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		general_purpose::STANDARD.decode(value).map(Base64)
+	}
+}
+
+impl From<Base64> for String {
+	fn from(value: Base64) -> Self {
+		general_purpose::STANDARD.encode(value.0)
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transaction {
+	Data(Base64),
+	Extrinsic(Base64),
+}
+
+impl Transaction {
+	pub fn is_empty(&self) -> bool {
+		match self {
+			Transaction::Data(data) => data.0.is_empty(),
+			Transaction::Extrinsic(data) => data.0.is_empty(),
+		}
+	}
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubmitResponse {
+	pub block_number: u32,
+	pub block_hash: H256,
+	pub hash: H256,
+	pub index: u32,
+}
+
+/// Status of the submitted transaction, as it moves through the pipeline.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "codec", derive(Decode, Encode))]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum TransactionStatus {
+	/// Transaction is in the transaction pool, waiting to be included in a block
+	InPool,
+	/// Transaction is included in a block, which is not finalized yet
+	InBlock { block_hash: H256 },
+	/// Transaction is included in a finalized block
+	Finalized {
+		block_number: u32,
+		block_hash: H256,
+		index: u32,
+	},
+	/// Transaction is dropped, invalid, or failed to execute
+	Failed { reason: String },
+}
+
+/// Status of the submitted transaction, identified by its hash.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransactionStatusMessage {
+	pub hash: H256,
+	#[serde(flatten)]
+	pub status: TransactionStatus,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlockStatus {
+	Unavailable,
+	Pending,
+	VerifyingHeader,
+	VerifyingConfidence,
+	VerifyingData,
+	Finished,
+	BestHead,
+}
+
+/// Source from which the sampled cell was fetched.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "codec", derive(Decode, Encode))]
+#[serde(rename_all = "lowercase")]
+pub enum CellSource {
+	Dht,
+	Rpc,
+	/// Cell verified earlier, reused from the cell cache
+	Cache,
+	/// Cell reconstructed from the verified cells of the same column
+	Reconstructed,
+}
+
+impl Display for CellSource {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CellSource::Dht => write!(f, "DHT"),
+			CellSource::Rpc => write!(f, "RPC"),
+			CellSource::Cache => write!(f, "cache"),
+			CellSource::Reconstructed => write!(f, "reconstruction"),
+		}
+	}
+}
+
+/// Sampled cell position, with the fetch source and verification outcome.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "codec", derive(Decode, Encode))]
+pub struct SampledCell {
+	pub row: u32,
+	pub col: u16,
+	/// Source of the fetched cell, omitted if cell is not fetched
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub source: Option<CellSource>,
+	pub verified: bool,
+}
+
+/// Sampling timings, in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "codec", derive(Decode, Encode))]
+pub struct SamplingTimings {
+	pub dht_fetch: u64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub rpc_fetch: Option<u64>,
+	pub proof_verification: u64,
+	pub total: u64,
+}
+
+/// Sampling outcome of the block, used for debugging low confidence.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "codec", derive(Decode, Encode))]
+pub struct BlockSamplingStats {
+	pub cells: Vec<SampledCell>,
+	pub timings: SamplingTimings,
+}
+
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct Block {
+	pub status: BlockStatus,
+	pub confidence: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub sampling: Option<BlockSamplingStats>,
+}
+
+impl Block {
+	pub fn new(
+		status: BlockStatus,
+		confidence: Option<f64>,
+		sampling: Option<BlockSamplingStats>,
+	) -> Self {
+		Self {
+			status,
+			confidence,
+			sampling,
+		}
+	}
+}
+
+/// Block looked up by the block hash, with the resolved block number.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct BlockByHash {
+	pub block_number: u32,
+	#[serde(flatten)]
+	pub block: Block,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct ConfidenceQuery {
+	pub confidence: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Confidence {
+	pub requested: f64,
+	pub required_cell_count: u32,
+	pub max_cell_count: u32,
+	pub cell_count: u32,
+	pub achievable: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CellPosition {
+	pub row: u32,
+	pub col: u16,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SampledCells {
+	pub block_number: u32,
+	pub block_hash: H256,
+	pub cells: Vec<CellPosition>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Header {
+	pub hash: H256,
+	pub parent_hash: H256,
+	pub number: u32,
+	pub state_root: H256,
+	pub extrinsics_root: H256,
+	pub extension: Extension,
+}
+
+#[derive(Debug, Clone)]
+pub struct Commitment(pub [u8; COMMITMENT_SIZE]);
+
+impl Serialize for Commitment {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		let hex_string = format!("0x{}", hex::encode(self.0));
+		serializer.serialize_str(&hex_string)
+	}
+}
+
+impl<'de> Deserialize<'de> for Commitment {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		const PREFIX_0X_LEN: usize = 2;
+		const HEX_ENCODED_BYTE_LEN: usize = 2;
+		const LEN: usize = (COMMITMENT_SIZE * HEX_ENCODED_BYTE_LEN) + PREFIX_0X_LEN;
+
+		let s = String::deserialize(deserializer)?;
+
+		if !s.starts_with("0x") || s.len() != LEN {
+			let message = "Expected a hex string of correct length with 0x prefix";
+			return Err(de::Error::custom(message));
+		}
+
+		let decoded = hex::decode(&s[2..]).map_err(de::Error::custom)?;
+		let decoded_len = decoded.len();
+		let bytes: [u8; COMMITMENT_SIZE] = decoded
+			.try_into()
+			.map_err(|_| de::Error::invalid_length(decoded_len, &"Expected vector of 48 bytes"))?;
+
+		Ok(Commitment(bytes))
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Extension {
+	pub rows: u16,
+	pub cols: u16,
+	pub data_root: H256,
+	pub commitments: Vec<Commitment>,
+	pub app_lookup: DataLookup,
+}
+
+/// Compact lookup of the application data in the block matrix
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataLookup {
+	/// Number of the data cells in the block matrix
+	pub size: u32,
+	/// Applications with data in the block, with the index of their first data cell
+	pub index: Vec<DataLookupItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataLookupItem {
+	pub app_id: u32,
+	pub start: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum DataField {
+	Data,
+	Extrinsic,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FieldsQueryParameter(pub HashSet<DataField>);
+
+impl TryFrom<String> for FieldsQueryParameter {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value
+			.split(',')
+			.map(|part| format!(r#""{part}""#))
+			.map(|part| {
+				serde_json::from_str(&part)
+					.map_err(|error| format!("Cannot deserialize field: {error}"))
+			})
+			.collect::<Result<HashSet<_>, _>>()
+			.map(FieldsQueryParameter)
+	}
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DataQuery {
+	pub fields: Option<FieldsQueryParameter>,
+	/// Index of the first data transaction in the response
+	pub offset: Option<u32>,
+	/// Maximum number of data transactions in the response, capped by the configured page size
+	pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataResponse {
+	pub block_number: u32,
+	pub data_transactions: Vec<DataTransaction>,
+	/// Offset of the next page, if there are more data transactions
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub next_offset: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DataRangeQuery {
+	pub fields: Option<FieldsQueryParameter>,
+	/// First block of the range
+	pub from_block: u32,
+	/// Last block of the range, inclusive
+	pub to_block: u32,
+	/// Cursor of the page, returned as `next_page` of the previous page
+	pub page: Option<DataCursor>,
+}
+
+/// Position of the first data transaction of the page, in the `{block_number}-{offset}` format
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DataCursor {
+	pub block_number: u32,
+	pub offset: u32,
+}
+
+impl TryFrom<String> for DataCursor {
+	type Error = String;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		let (block_number, offset) = value
+			.split_once('-')
+			.ok_or_else(|| format!("Invalid page cursor {value}"))?;
+		Ok(DataCursor {
+			block_number: block_number
+				.parse()
+				.map_err(|_| format!("Invalid cursor block number {block_number}"))?,
+			offset: offset
+				.parse()
+				.map_err(|_| format!("Invalid cursor offset {offset}"))?,
+		})
+	}
+}
+
+impl From<DataCursor> for String {
+	fn from(value: DataCursor) -> Self {
+		format!("{}-{}", value.block_number, value.offset)
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataRangeResponse {
+	pub blocks: Vec<DataResponse>,
+	/// Cursor of the next page, if there are more blocks or data transactions in the range
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub next_page: Option<DataCursor>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DataTransaction {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub data: Option<Base64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub extrinsic: Option<Base64>,
+}
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+
+	use super::{DataCursor, Partition};
+
+	#[test_case("1/10" => Some((1, 10)) ; "Partition")]
+	#[test_case("1-10" => None ; "Invalid separator")]
+	#[test_case("a/10" => None ; "Invalid number")]
+	fn partition_from_string(value: &str) -> Option<(u8, u8)> {
+		Partition::try_from(value.to_string())
+			.ok()
+			.map(|Partition { number, fraction }| (number, fraction))
+	}
+
+	#[test]
+	fn data_cursor_round_trip() {
+		let cursor = DataCursor {
+			block_number: 10,
+			offset: 2,
+		};
+		let value = String::from(cursor);
+		assert_eq!(value, "10-2");
+		assert_eq!(DataCursor::try_from(value).unwrap(), cursor);
+	}
+}
@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+	// Vendored protoc is used, so the build doesn't depend on the system protobuf compiler
+	std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+	tonic_build::compile_protos("proto/light_client.proto")?;
+	Ok(())
+}
@@ -0,0 +1,149 @@
+//! Admin HTTP API for the light client operators, enabled with the `admin_api_enable` parameter.
+//!
+//! # Endpoints
+//!
+//! * `GET /admin/log-levels` - returns default log level and per-module log level overrides
+//! * `PUT /admin/log-levels` - sets per-module log levels, override is removed if the level is `null`
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+use tracing::{info, Level};
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+use crate::logs::LogFilter;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct LogLevels {
+	pub default: String,
+	pub overrides: BTreeMap<String, String>,
+}
+
+impl From<&LogFilter> for LogLevels {
+	fn from(filter: &LogFilter) -> Self {
+		LogLevels {
+			default: filter.default_level().to_string().to_lowercase(),
+			overrides: filter
+				.overrides()
+				.into_iter()
+				.map(|(target, level)| (target, level.to_string().to_lowercase()))
+				.collect(),
+		}
+	}
+}
+
+impl Reply for LogLevels {
+	fn into_response(self) -> warp::reply::Response {
+		warp::reply::json(&self).into_response()
+	}
+}
+
+fn with_log_filter(
+	log_filter: Option<Arc<LogFilter>>,
+) -> impl Filter<Extract = (Arc<LogFilter>,), Error = Rejection> + Clone {
+	warp::any().and_then(move || {
+		let log_filter = log_filter.clone();
+		async move { log_filter.ok_or_else(warp::reject::not_found) }
+	})
+}
+
+fn update_log_levels(
+	levels: BTreeMap<String, Option<String>>,
+	log_filter: Arc<LogFilter>,
+) -> warp::reply::Response {
+	let levels = levels
+		.into_iter()
+		.map(
+			|(target, level)| match level.map(|level| Level::from_str(&level)) {
+				Some(Err(_)) => Err(format!("Invalid log level for target {target}")),
+				Some(Ok(level)) => Ok((target, Some(level))),
+				None => Ok((target, None)),
+			},
+		)
+		.collect::<Result<BTreeMap<_, _>, _>>();
+
+	let result = levels.and_then(|levels| {
+		log_filter
+			.update(levels)
+			.map_err(|error| format!("{error:#}"))
+	});
+	match result {
+		Ok(()) => {
+			let levels = LogLevels::from(log_filter.as_ref());
+			info!(overrides = ?levels.overrides, "Log levels updated");
+			levels.into_response()
+		},
+		Err(message) => warp::reply::with_status(message, StatusCode::BAD_REQUEST).into_response(),
+	}
+}
+
+pub fn routes(
+	log_filter: Option<Arc<LogFilter>>,
+	max_body_size: u64,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+	let get_log_levels = warp::get()
+		.and(warp::path!("admin" / "log-levels"))
+		.and(with_log_filter(log_filter.clone()))
+		.map(|log_filter: Arc<LogFilter>| LogLevels::from(log_filter.as_ref()).into_response());
+
+	let put_log_levels = warp::put()
+		.and(warp::path!("admin" / "log-levels"))
+		.and(warp::body::content_length_limit(max_body_size))
+		.and(warp::body::json())
+		.and(with_log_filter(log_filter))
+		.map(update_log_levels);
+
+	get_log_levels.or(put_log_levels)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use tracing::Level;
+	use tracing_subscriber::reload;
+
+	use crate::logs::{env_filter, LogFilter};
+
+	#[tokio::test]
+	async fn log_levels_disabled() {
+		let route = super::routes(None, 1024);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/admin/log-levels")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), 404);
+	}
+
+	#[tokio::test]
+	async fn update_log_levels() {
+		let (_layer, handle) = reload::Layer::new(env_filter(Level::INFO));
+		let route = super::routes(Some(Arc::new(LogFilter::new(handle, Level::INFO))), 1024);
+
+		let response = warp::test::request()
+			.method("PUT")
+			.path("/admin/log-levels")
+			.body(r#"{"avail_light::network":"debug"}"#)
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), 200);
+
+		let response = warp::test::request()
+			.method("GET")
+			.path("/admin/log-levels")
+			.reply(&route)
+			.await;
+		assert_eq!(
+			response.body(),
+			r#"{"default":"info","overrides":{"avail_light::network":"debug"}}"#
+		);
+
+		let response = warp::test::request()
+			.method("PUT")
+			.path("/admin/log-levels")
+			.body(r#"{"avail_light::network":null,"libp2p":"verbose"}"#)
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), 400);
+		assert_eq!(response.body(), "Invalid log level for target libp2p");
+	}
+}
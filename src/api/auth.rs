@@ -0,0 +1,246 @@
+//! Authentication and authorization of the API requests, with the `read`, `submit` and `admin` roles of the API keys.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use warp::{
+	http::{Method, StatusCode},
+	path::FullPath,
+	reject::Reject,
+	Filter, Rejection, Reply,
+};
+
+use crate::types::RuntimeConfig;
+
+/// Role of the API key, roles are ordered by the access they grant.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+	Read,
+	Submit,
+	Admin,
+}
+
+/// API key with its role.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ApiKey {
+	pub key: String,
+	pub role: Role,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+	/// API key is missing or unknown
+	Unauthenticated,
+	/// API key role doesn't grant access to the endpoint
+	Forbidden,
+}
+
+impl Reject for AuthError {}
+
+/// Authorization of the API callers (see [RuntimeConfig] for details)
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+	keys: Arc<HashMap<String, Role>>,
+	public_read: bool,
+}
+
+impl From<&RuntimeConfig> for Auth {
+	fn from(val: &RuntimeConfig) -> Self {
+		let keys = val
+			.api_keys
+			.iter()
+			.map(|ApiKey { key, role }| (key.clone(), *role))
+			.collect();
+		Self {
+			keys: Arc::new(keys),
+			public_read: val.api_public_read,
+		}
+	}
+}
+
+/// Returns role required by the request, or `None` if endpoint is public.
+pub fn required_role(method: &Method, path: &str) -> Option<Role> {
+	match (method, path) {
+		(_, "/health" | "/ready") => None,
+		(_, path) if path.starts_with("/admin/") => Some(Role::Admin),
+		(&Method::PUT | &Method::DELETE, path) if path.starts_with("/v2/apps/") => {
+			Some(Role::Admin)
+		},
+		(&Method::POST, "/v2/submit") => Some(Role::Submit),
+		_ => Some(Role::Read),
+	}
+}
+
+impl Auth {
+	pub fn is_enabled(&self) -> bool {
+		!self.keys.is_empty()
+	}
+
+	/// Returns role of the caller, given the `Authorization` and `X-API-Key` header values.
+	/// All callers are admins if authentication is disabled.
+	pub fn role(
+		&self,
+		authorization: Option<&str>,
+		api_key: Option<&str>,
+	) -> Result<Role, AuthError> {
+		if !self.is_enabled() {
+			return Ok(Role::Admin);
+		}
+		let bearer = authorization.and_then(|value| value.strip_prefix("Bearer "));
+		match bearer.or(api_key).map(str::trim) {
+			Some(key) => self
+				.keys
+				.get(key)
+				.copied()
+				.ok_or(AuthError::Unauthenticated),
+			None if self.public_read => Ok(Role::Read),
+			None => Err(AuthError::Unauthenticated),
+		}
+	}
+
+	/// Checks if the caller has the required role.
+	pub fn authorize(
+		&self,
+		authorization: Option<&str>,
+		api_key: Option<&str>,
+		required: Role,
+	) -> Result<Role, AuthError> {
+		let role = self.role(authorization, api_key)?;
+		if role < required {
+			return Err(AuthError::Forbidden);
+		}
+		Ok(role)
+	}
+}
+
+fn headers() -> impl Filter<Extract = (Option<String>, Option<String>), Error = Rejection> + Clone {
+	warp::header::optional::<String>("authorization")
+		.and(warp::header::optional::<String>("x-api-key"))
+}
+
+/// Rejects requests whose caller doesn't have the role required by the endpoint.
+pub fn filter(auth: Auth) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+	warp::method()
+		.and(warp::path::full())
+		.and(headers())
+		.and_then(
+			move |method: Method,
+			      path: FullPath,
+			      authorization: Option<String>,
+			      api_key: Option<String>| {
+				let auth = auth.clone();
+				async move {
+					let Some(required) = required_role(&method, path.as_str()) else {
+						return Ok(());
+					};
+					auth.authorize(authorization.as_deref(), api_key.as_deref(), required)
+						.map(|_| ())
+						.map_err(warp::reject::custom)
+				}
+			},
+		)
+		.untuple_one()
+}
+
+/// Extracts role of the caller, or `None` if caller is not authenticated.
+pub fn caller_role(
+	auth: Auth,
+) -> impl Filter<Extract = (Option<Role>,), Error = Rejection> + Clone {
+	headers().map(
+		move |authorization: Option<String>, api_key: Option<String>| {
+			auth.role(authorization.as_deref(), api_key.as_deref()).ok()
+		},
+	)
+}
+
+pub async fn handle_rejection(error: Rejection) -> Result<impl Reply, Rejection> {
+	match error.find::<AuthError>() {
+		Some(AuthError::Unauthenticated) => Ok(warp::reply::with_header(
+			warp::reply::with_status("Unauthorized", StatusCode::UNAUTHORIZED),
+			"www-authenticate",
+			"Bearer",
+		)
+		.into_response()),
+		Some(AuthError::Forbidden) => {
+			Ok(warp::reply::with_status("Forbidden", StatusCode::FORBIDDEN).into_response())
+		},
+		None => Err(error),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+	use warp::Filter;
+
+	use super::{filter, handle_rejection, ApiKey, Auth, Role};
+	use crate::types::RuntimeConfig;
+
+	fn auth(public_read: bool) -> Auth {
+		let config = RuntimeConfig {
+			api_keys: vec![
+				ApiKey {
+					key: "read-key".to_string(),
+					role: Role::Read,
+				},
+				ApiKey {
+					key: "submit-key".to_string(),
+					role: Role::Submit,
+				},
+				ApiKey {
+					key: "admin-key".to_string(),
+					role: Role::Admin,
+				},
+			],
+			api_public_read: public_read,
+			..Default::default()
+		};
+		(&config).into()
+	}
+
+	async fn request(auth: Auth, method: &str, path: &str, header: Option<(&str, &str)>) -> u16 {
+		let route = filter(auth).map(warp::reply).recover(handle_rejection);
+		let mut request = warp::test::request().method(method).path(path);
+		if let Some((name, value)) = header {
+			request = request.header(name, value);
+		}
+		request.reply(&route).await.status().as_u16()
+	}
+
+	#[test]
+	fn roles_are_ordered() {
+		assert!(Role::Read < Role::Submit && Role::Submit < Role::Admin);
+	}
+
+	#[test_case("GET", "/health", None => 200 ; "Health is public")]
+	#[test_case("GET", "/ready", None => 200 ; "Readiness is public")]
+	#[test_case("GET", "/v2/status", None => 401 ; "Missing key")]
+	#[test_case("GET", "/v2/status", Some(("authorization", "Bearer unknown")) => 401 ; "Unknown key")]
+	#[test_case("GET", "/v2/status", Some(("authorization", "Bearer read-key")) => 200 ; "Read with bearer token")]
+	#[test_case("GET", "/v2/status", Some(("x-api-key", "read-key")) => 200 ; "Read with API key header")]
+	#[test_case("POST", "/v2/submit", Some(("x-api-key", "read-key")) => 403 ; "Submit with read key")]
+	#[test_case("POST", "/v2/submit", Some(("x-api-key", "submit-key")) => 200 ; "Submit with submit key")]
+	#[test_case("PUT", "/v2/apps/1", Some(("x-api-key", "submit-key")) => 403 ; "Follow app with submit key")]
+	#[test_case("GET", "/v2/apps", Some(("x-api-key", "submit-key")) => 200 ; "List apps with submit key")]
+	#[test_case("PUT", "/admin/log-levels", Some(("x-api-key", "submit-key")) => 403 ; "Admin with submit key")]
+	#[test_case("PUT", "/admin/log-levels", Some(("x-api-key", "admin-key")) => 200 ; "Admin with admin key")]
+	#[tokio::test]
+	async fn test_filter(method: &str, path: &str, header: Option<(&str, &str)>) -> u16 {
+		request(auth(false), method, path, header).await
+	}
+
+	#[tokio::test]
+	async fn public_read() {
+		assert_eq!(request(auth(true), "GET", "/v2/status", None).await, 200);
+		assert_eq!(request(auth(true), "POST", "/v2/submit", None).await, 403);
+		let header = Some(("x-api-key", "unknown"));
+		assert_eq!(request(auth(true), "GET", "/v2/status", header).await, 401);
+	}
+
+	#[tokio::test]
+	async fn disabled_auth() {
+		let auth = Auth::default();
+		assert!(!auth.is_enabled());
+		assert_eq!(request(auth, "PUT", "/admin/log-levels", None).await, 200);
+	}
+}
@@ -0,0 +1,454 @@
+//! gRPC server for block status, confidence, app data retrieval and submission, served by the HTTP API version 2 handlers.
+
+use color_eyre::{eyre::WrapErr, Result};
+use futures::FutureExt;
+use std::sync::{Arc, Mutex};
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+
+use self::proto::{
+	light_client_server::{LightClient, LightClientServer},
+	submit_request,
+};
+use crate::{
+	api::{
+		auth::{Auth, AuthError, Role},
+		v2::{
+			handlers,
+			transactions::{self, Submit},
+			types::{
+				Base64, Block, BlockStatus, Confidence, ConfidenceQuery, DataField, DataQuery,
+				DataResponse, DataTransaction, Error, ErrorCode, FieldsQueryParameter,
+				SubmitResponse, Transaction, WsClients,
+			},
+		},
+	},
+	data::Database,
+	network::rpc,
+	shutdown::Controller,
+	types::{IdentityConfig, RuntimeConfig, State},
+};
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+pub mod proto {
+	tonic::include_proto!("avail.light.v2");
+}
+
+impl From<Error> for Status {
+	fn from(error: Error) -> Self {
+		match error.error_code {
+			ErrorCode::NotFound => Status::not_found(error.message),
+			ErrorCode::BadRequest => Status::invalid_argument(error.message),
+			ErrorCode::InternalServerError => {
+				if let Some(cause) = error.cause {
+					error!("{}: {cause:#}", error.message);
+				}
+				Status::internal(error.message)
+			},
+		}
+	}
+}
+
+impl From<Block> for proto::Block {
+	fn from(block: Block) -> Self {
+		let status = match block.status {
+			BlockStatus::Unavailable => proto::BlockStatus::Unavailable,
+			BlockStatus::Pending => proto::BlockStatus::Pending,
+			BlockStatus::VerifyingHeader => proto::BlockStatus::VerifyingHeader,
+			BlockStatus::VerifyingConfidence => proto::BlockStatus::VerifyingConfidence,
+			BlockStatus::VerifyingData => proto::BlockStatus::VerifyingData,
+			BlockStatus::Finished => proto::BlockStatus::Finished,
+			BlockStatus::BestHead => proto::BlockStatus::BestHead,
+		};
+		proto::Block {
+			status: status.into(),
+			confidence: block.confidence,
+		}
+	}
+}
+
+impl From<Confidence> for proto::Confidence {
+	fn from(confidence: Confidence) -> Self {
+		proto::Confidence {
+			requested: confidence.requested,
+			required_cell_count: confidence.required_cell_count,
+			max_cell_count: confidence.max_cell_count,
+			cell_count: confidence.cell_count,
+			achievable: confidence.achievable,
+		}
+	}
+}
+
+impl From<DataTransaction> for proto::DataTransaction {
+	fn from(transaction: DataTransaction) -> Self {
+		proto::DataTransaction {
+			data: transaction.data.map(|Base64(data)| data),
+			extrinsic: transaction.extrinsic.map(|Base64(extrinsic)| extrinsic),
+		}
+	}
+}
+
+impl From<DataResponse> for proto::BlockData {
+	fn from(response: DataResponse) -> Self {
+		proto::BlockData {
+			block_number: response.block_number,
+			data_transactions: response
+				.data_transactions
+				.into_iter()
+				.map(From::from)
+				.collect(),
+			next_offset: response.next_offset,
+		}
+	}
+}
+
+impl From<SubmitResponse> for proto::SubmitResponse {
+	fn from(response: SubmitResponse) -> Self {
+		proto::SubmitResponse {
+			block_number: response.block_number,
+			block_hash: response.block_hash.as_bytes().to_vec(),
+			hash: response.hash.as_bytes().to_vec(),
+			index: response.index,
+		}
+	}
+}
+
+/// Converts requested fields into the query, all fields are returned if none is requested.
+fn data_query(fields: &[i32]) -> Result<DataQuery, Status> {
+	if fields.is_empty() {
+		return Ok(DataQuery {
+			fields: None,
+			offset: None,
+			limit: None,
+		});
+	}
+
+	let fields = fields
+		.iter()
+		.map(|&field| match proto::DataField::from_i32(field) {
+			Some(proto::DataField::Data) => Ok(DataField::Data),
+			Some(proto::DataField::Extrinsic) => Ok(DataField::Extrinsic),
+			_ => Err(Status::invalid_argument(format!(
+				"Invalid data field {field}"
+			))),
+		})
+		.collect::<Result<_, _>>()?;
+
+	Ok(DataQuery {
+		fields: Some(FieldsQueryParameter(fields)),
+		offset: None,
+		limit: None,
+	})
+}
+
+pub struct Service<T: Database, S: Submit> {
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: T,
+	submitter: Arc<S>,
+	auth: Auth,
+}
+
+impl<T: Database, S: Submit> Service<T, S> {
+	fn authorize<R>(&self, request: &Request<R>, required: Role) -> Result<(), Status> {
+		let metadata = |key: &str| {
+			request
+				.metadata()
+				.get(key)
+				.and_then(|value| value.to_str().ok())
+		};
+		match self
+			.auth
+			.authorize(metadata("authorization"), metadata("x-api-key"), required)
+		{
+			Ok(_) => Ok(()),
+			Err(AuthError::Unauthenticated) => {
+				Err(Status::unauthenticated("API key is missing or invalid"))
+			},
+			Err(AuthError::Forbidden) => Err(Status::permission_denied(
+				"API key is not allowed to access the method",
+			)),
+		}
+	}
+}
+
+#[tonic::async_trait]
+impl<T, S> LightClient for Service<T, S>
+where
+	T: Database + Clone + Send + Sync + 'static,
+	S: Submit + Send + Sync + 'static,
+{
+	async fn get_block(
+		&self,
+		request: Request<proto::BlockRequest>,
+	) -> Result<Response<proto::Block>, Status> {
+		self.authorize(&request, Role::Read)?;
+		let block_number = request.into_inner().block_number;
+		let block = handlers::block(
+			block_number,
+			self.config.clone(),
+			self.state.clone(),
+			self.db.clone(),
+		)
+		.await?;
+		Ok(Response::new(block.into()))
+	}
+
+	async fn get_confidence(
+		&self,
+		request: Request<proto::ConfidenceRequest>,
+	) -> Result<Response<proto::Confidence>, Status> {
+		self.authorize(&request, Role::Read)?;
+		let query = ConfidenceQuery {
+			confidence: request.into_inner().confidence,
+		};
+		let confidence = handlers::confidence(query, self.config.clone());
+		Ok(Response::new(confidence.into()))
+	}
+
+	async fn get_block_data(
+		&self,
+		request: Request<proto::BlockDataRequest>,
+	) -> Result<Response<proto::BlockData>, Status> {
+		self.authorize(&request, Role::Read)?;
+		let request = request.into_inner();
+		let query = DataQuery {
+			offset: request.offset,
+			limit: request.limit,
+			..data_query(&request.fields)?
+		};
+		let data = handlers::block_data(
+			request.block_number,
+			query,
+			self.config.clone(),
+			self.state.clone(),
+			self.db.clone(),
+		)
+		.await?;
+		Ok(Response::new(data.into()))
+	}
+
+	async fn submit(
+		&self,
+		request: Request<proto::SubmitRequest>,
+	) -> Result<Response<proto::SubmitResponse>, Status> {
+		self.authorize(&request, Role::Submit)?;
+		let transaction = match request.into_inner().transaction {
+			Some(submit_request::Transaction::Data(data)) => Transaction::Data(Base64(data)),
+			Some(submit_request::Transaction::Extrinsic(extrinsic)) => {
+				Transaction::Extrinsic(Base64(extrinsic))
+			},
+			None => return Err(Status::invalid_argument("Transaction is empty")),
+		};
+		if transaction.is_empty() {
+			return Err(Status::invalid_argument("Transaction is empty"));
+		}
+
+		let response = handlers::submit(self.submitter.clone(), transaction).await?;
+		Ok(Response::new(response.into()))
+	}
+}
+
+pub struct Server<T: Database> {
+	pub db: T,
+	pub cfg: RuntimeConfig,
+	pub identity_cfg: IdentityConfig,
+	pub state: Arc<Mutex<State>>,
+	pub node_client: rpc::Client,
+	pub ws_clients: WsClients,
+	pub shutdown: Controller<String>,
+}
+
+impl<T: Database + Clone + Send + Sync + 'static> Server<T> {
+	async fn serve(self, port: u16) -> Result<()> {
+		let addr = self
+			.cfg
+			.http_socket_address(port)
+			.wrap_err("Unable to parse gRPC host address from config")?;
+
+		// Signed extrinsics can be submitted even if app mode is not active
+		let submitter = transactions::Submitter::new(
+			self.node_client,
+			self.cfg.app_id,
+			self.identity_cfg,
+			self.db.clone(),
+			self.ws_clients,
+		);
+
+		let service = Service {
+			auth: (&self.cfg).into(),
+			config: self.cfg,
+			state: self.state,
+			db: self.db,
+			submitter: Arc::new(submitter),
+		};
+
+		info!("gRPC running on http://{addr}");
+		let shutdown_signal = self.shutdown.triggered_shutdown().map(|_| ());
+		tonic::transport::Server::builder()
+			.add_service(LightClientServer::new(service))
+			.serve_with_shutdown(addr, shutdown_signal)
+			.await
+			.wrap_err("gRPC server failed")
+	}
+
+	/// Runs gRPC server on the given port, until the shutdown is triggered
+	pub async fn bind(self, port: u16) {
+		if let Err(error) = self.serve(port).await {
+			error!("{error:#}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use async_trait::async_trait;
+	use color_eyre::Result;
+	use sp_core::H256;
+	use std::sync::{Arc, Mutex};
+	use test_case::test_case;
+	use tonic::{Code, Request};
+
+	use super::{
+		data_query,
+		proto::{self, light_client_server::LightClient, submit_request},
+		Service,
+	};
+	use crate::{
+		api::{
+			auth::{ApiKey, Auth, Role},
+			v2::{
+				transactions::Submit,
+				types::{DataField, SubmitResponse, Transaction},
+			},
+		},
+		data::{mem_db::MemoryDB, Database, Key},
+		types::{BlockRange, RuntimeConfig, State},
+	};
+
+	struct MockSubmitter {}
+
+	#[async_trait]
+	impl Submit for MockSubmitter {
+		async fn submit(&self, _: Transaction) -> Result<SubmitResponse> {
+			Ok(SubmitResponse {
+				block_number: 1,
+				block_hash: H256::repeat_byte(1),
+				hash: H256::repeat_byte(2),
+				index: 0,
+			})
+		}
+
+		fn is_data_enabled(&self) -> bool {
+			false
+		}
+	}
+
+	fn service(state: State, db: MemoryDB) -> Service<MemoryDB, MockSubmitter> {
+		Service {
+			config: RuntimeConfig::default(),
+			state: Arc::new(Mutex::new(state)),
+			db,
+			submitter: Arc::new(MockSubmitter {}),
+			auth: Auth::default(),
+		}
+	}
+
+	#[tokio::test]
+	async fn get_block() {
+		let state = State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(10)),
+			confidence_achieved: Some(BlockRange::init(10)),
+			..Default::default()
+		};
+		let db = MemoryDB::default();
+		db.put(Key::CanonicalHash(10), H256::repeat_byte(1))
+			.unwrap();
+		db.put(Key::VerifiedCellCount(10, H256::repeat_byte(1)), 4)
+			.unwrap();
+
+		let request = Request::new(proto::BlockRequest { block_number: 10 });
+		let block = service(state, db)
+			.get_block(request)
+			.await
+			.unwrap()
+			.into_inner();
+
+		assert_eq!(block.status(), proto::BlockStatus::VerifyingData);
+		assert_eq!(block.confidence, Some(93.75));
+	}
+
+	#[tokio::test]
+	async fn get_block_not_found() {
+		let request = Request::new(proto::BlockRequest { block_number: 10 });
+		let status = service(State::default(), MemoryDB::default())
+			.get_block(request)
+			.await
+			.unwrap_err();
+		assert_eq!(status.code(), Code::NotFound);
+	}
+
+	#[test_case(Some(submit_request::Transaction::Data(vec![1])), Code::NotFound ; "Data is not enabled")]
+	#[test_case(Some(submit_request::Transaction::Extrinsic(vec![])), Code::InvalidArgument ; "Empty extrinsic")]
+	#[test_case(None, Code::InvalidArgument ; "Empty transaction")]
+	#[tokio::test]
+	async fn submit_error(transaction: Option<submit_request::Transaction>, expected: Code) {
+		let request = Request::new(proto::SubmitRequest { transaction });
+		let status = service(State::default(), MemoryDB::default())
+			.submit(request)
+			.await
+			.unwrap_err();
+		assert_eq!(status.code(), expected);
+	}
+
+	#[tokio::test]
+	async fn submit_extrinsic() {
+		let transaction = Some(submit_request::Transaction::Extrinsic(vec![1]));
+		let request = Request::new(proto::SubmitRequest { transaction });
+		let response = service(State::default(), MemoryDB::default())
+			.submit(request)
+			.await
+			.unwrap()
+			.into_inner();
+		assert_eq!(response.block_number, 1);
+		assert_eq!(response.hash, H256::repeat_byte(2).as_bytes());
+	}
+
+	#[test_case(None => Code::Unauthenticated ; "Missing key")]
+	#[test_case(Some("Bearer read-key") => Code::PermissionDenied ; "Read key")]
+	#[tokio::test]
+	async fn submit_unauthorized(authorization: Option<&str>) -> Code {
+		let mut service = service(State::default(), MemoryDB::default());
+		service.auth = (&RuntimeConfig {
+			api_keys: vec![ApiKey {
+				key: "read-key".to_string(),
+				role: Role::Read,
+			}],
+			..Default::default()
+		})
+			.into();
+
+		let transaction = Some(submit_request::Transaction::Extrinsic(vec![1]));
+		let mut request = Request::new(proto::SubmitRequest { transaction });
+		if let Some(authorization) = authorization {
+			request
+				.metadata_mut()
+				.insert("authorization", authorization.parse().unwrap());
+		}
+		service.submit(request).await.unwrap_err().code()
+	}
+
+	#[test]
+	fn test_data_query() {
+		assert!(data_query(&[]).unwrap().fields.is_none());
+
+		let query = data_query(&[proto::DataField::Extrinsic.into()]).unwrap();
+		let fields = query.fields.unwrap().0;
+		assert_eq!(fields.len(), 1);
+		assert!(fields.contains(&DataField::Extrinsic));
+
+		let status = data_query(&[proto::DataField::Unspecified.into()]).unwrap_err();
+		assert_eq!(status.code(), Code::InvalidArgument);
+	}
+}
@@ -0,0 +1,336 @@
+//! Health and readiness endpoints, for the Kubernetes probes and load balancers.
+//!
+//! # Endpoints
+//!
+//! * `/health` - returns `200 OK` while the process is alive
+//! * `/ready` - returns `200 OK` if the client is ready, or `503 Service Unavailable` with the failed checks
+//! * `/diagnostics` - returns `200 OK` with the diagnostics report, or `503 Service Unavailable` if any check failed (see [diagnostics])
+
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::time::timeout;
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+	data::{Database, Key},
+	diagnostics::{self, DiagnosticsConfig},
+	network::{p2p, rpc},
+	types::{RuntimeConfig, State},
+	utils::unix_timestamp,
+};
+
+/// Maximum duration of the RPC and P2P checks
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Readiness configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct ReadinessConfig {
+	pub max_sync_lag: u32,
+	pub is_fat_client: bool,
+}
+
+impl From<&RuntimeConfig> for ReadinessConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		ReadinessConfig {
+			max_sync_lag: val.ready_max_sync_lag,
+			is_fat_client: val.is_fat_client(),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+	Alive,
+	Ready,
+	NotReady,
+}
+
+/// Outcome of the single dependency check.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+	pub ok: bool,
+	pub message: String,
+}
+
+impl From<Result<String>> for Check {
+	fn from(result: Result<String>) -> Self {
+		match result {
+			Ok(message) => Check { ok: true, message },
+			Err(error) => Check {
+				ok: false,
+				message: format!("{error:#}"),
+			},
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Checks {
+	pub rpc: Check,
+	pub p2p: Check,
+	pub database: Check,
+	pub sync: Check,
+}
+
+impl Checks {
+	fn is_ready(&self) -> bool {
+		[&self.rpc, &self.p2p, &self.database, &self.sync]
+			.iter()
+			.all(|check| check.ok)
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+	pub status: Status,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub checks: Option<Checks>,
+}
+
+impl Reply for Response {
+	fn into_response(self) -> warp::reply::Response {
+		let status_code = match self.status {
+			Status::Alive | Status::Ready => StatusCode::OK,
+			Status::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+		};
+		warp::reply::with_status(warp::reply::json(&self), status_code).into_response()
+	}
+}
+
+impl From<Checks> for Response {
+	fn from(checks: Checks) -> Self {
+		let status = match checks.is_ready() {
+			true => Status::Ready,
+			false => Status::NotReady,
+		};
+		Response {
+			status,
+			checks: Some(checks),
+		}
+	}
+}
+
+impl Reply for diagnostics::Report {
+	fn into_response(self) -> warp::reply::Response {
+		let status_code = match self.passed {
+			true => StatusCode::OK,
+			false => StatusCode::SERVICE_UNAVAILABLE,
+		};
+		warp::reply::with_status(warp::reply::json(&self), status_code).into_response()
+	}
+}
+
+pub(crate) async fn rpc_check(client: &rpc::Client, host: String) -> Result<String> {
+	timeout(CHECK_TIMEOUT, client.get_finalized_head_hash())
+		.await
+		.wrap_err_with(|| format!("Request to {host} timed out"))?
+		.wrap_err_with(|| format!("Request to {host} failed"))?;
+	Ok(format!("Connected to {host}"))
+}
+
+async fn p2p_check(client: &p2p::Client) -> Result<String> {
+	let peers = timeout(CHECK_TIMEOUT, client.get_routing_table_peers())
+		.await
+		.wrap_err("Routing table query timed out")??
+		.len();
+	if peers == 0 {
+		return Err(eyre!(
+			"Routing table is empty, P2P network is not bootstrapped"
+		));
+	}
+	Ok(format!("{peers} peers in the routing table"))
+}
+
+/// Checks if the database is writable, by writing and reading back the current timestamp.
+pub(crate) fn database_check(db: &impl Database) -> Result<String> {
+	let now = unix_timestamp();
+	db.put(Key::HealthProbe, now)
+		.wrap_err("Database is not writable")?;
+	match db.get::<u64>(Key::HealthProbe)? {
+		Some(stored) if stored == now => Ok("Writable".to_string()),
+		_ => Err(eyre!("Written value cannot be read back")),
+	}
+}
+
+/// Checks if the latest processed block (sampled, or header verified for the fat client)
+/// lags behind the latest finalized block by at most the maximum lag.
+fn sync_check(state: &State, cfg: &ReadinessConfig) -> Result<String> {
+	let latest = state.latest;
+	let Some(processed) = state.latest_processed(cfg.is_fat_client) else {
+		return Err(eyre!("No blocks are processed, latest block is {latest}"));
+	};
+	let lag = latest.saturating_sub(processed);
+	let message =
+		format!("Processed block {processed} lags {lag} blocks behind latest block {latest}");
+	if lag > cfg.max_sync_lag {
+		return Err(eyre!("{message}, maximum lag is {}", cfg.max_sync_lag));
+	}
+	Ok(message)
+}
+
+async fn ready(
+	cfg: ReadinessConfig,
+	db: impl Database,
+	state: Arc<Mutex<State>>,
+	node_client: rpc::Client,
+	p2p_client: p2p::Client,
+) -> Response {
+	let (host, sync) = match state.lock() {
+		Ok(state) => (state.connected_node.host.clone(), sync_check(&state, &cfg)),
+		Err(error) => (
+			String::new(),
+			Err(eyre!("State mutex is poisoned: {error:#}")),
+		),
+	};
+	let (rpc, p2p) = tokio::join!(rpc_check(&node_client, host), p2p_check(&p2p_client));
+	Checks {
+		rpc: rpc.into(),
+		p2p: p2p.into(),
+		database: database_check(&db).into(),
+		sync: sync.into(),
+	}
+	.into()
+}
+
+async fn diagnostics(
+	cfg: DiagnosticsConfig,
+	db: impl Database,
+	state: Arc<Mutex<State>>,
+	node_client: rpc::Client,
+	p2p_client: p2p::Client,
+) -> diagnostics::Report {
+	let node = match state.lock() {
+		Ok(state) => state.connected_node.clone(),
+		Err(error) => {
+			let error = Err(eyre!("State mutex is poisoned: {error:#}"));
+			return vec![diagnostics::Diagnostic::new("rpc", error)].into();
+		},
+	};
+	let (mut checks, p2p) = tokio::join!(
+		diagnostics::check_node(&cfg, &node_client, &node),
+		diagnostics::check_p2p(&cfg, &p2p_client)
+	);
+	checks.push(p2p);
+	checks.extend(diagnostics::check_local(&cfg, Ok(&db)));
+	checks.into()
+}
+
+pub fn routes<T: Database + Clone + Send + Sync + 'static>(
+	cfg: ReadinessConfig,
+	diagnostics_cfg: DiagnosticsConfig,
+	db: T,
+	state: Arc<Mutex<State>>,
+	node_client: rpc::Client,
+	p2p_client: p2p::Client,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+	let health = warp::head()
+		.or(warp::get())
+		.unify()
+		.and(warp::path("health"))
+		.and(warp::path::end())
+		.map(|| Response {
+			status: Status::Alive,
+			checks: None,
+		});
+
+	let ready = warp::head()
+		.or(warp::get())
+		.unify()
+		.and(warp::path("ready"))
+		.and(warp::path::end())
+		.then({
+			let (db, state) = (db.clone(), state.clone());
+			let (node_client, p2p_client) = (node_client.clone(), p2p_client.clone());
+			move || {
+				ready(
+					cfg.clone(),
+					db.clone(),
+					state.clone(),
+					node_client.clone(),
+					p2p_client.clone(),
+				)
+			}
+		});
+
+	let diagnostics = warp::get()
+		.and(warp::path("diagnostics"))
+		.and(warp::path::end())
+		.then(move || {
+			diagnostics(
+				diagnostics_cfg.clone(),
+				db.clone(),
+				state.clone(),
+				node_client.clone(),
+				p2p_client.clone(),
+			)
+		});
+
+	health.or(ready).or(diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+	use warp::Reply;
+
+	use super::{database_check, sync_check, Check, Checks, ReadinessConfig, Response, Status};
+	use crate::{
+		data::mem_db::MemoryDB,
+		types::{BlockRange, State},
+	};
+
+	fn check(ok: bool) -> Check {
+		Check {
+			ok,
+			message: String::new(),
+		}
+	}
+
+	#[test_case(false, None, None => false ; "No processed blocks")]
+	#[test_case(false, Some(95), None => true ; "Lag within maximum")]
+	#[test_case(false, Some(80), None => false ; "Lag above maximum")]
+	#[test_case(true, Some(80), Some(99) => true ; "Fat client uses verified headers")]
+	fn test_sync_check(
+		is_fat_client: bool,
+		confidence_achieved: Option<u32>,
+		header_verified: Option<u32>,
+	) -> bool {
+		let state = State {
+			latest: 100,
+			confidence_achieved: confidence_achieved.map(BlockRange::init),
+			header_verified: header_verified.map(BlockRange::init),
+			..Default::default()
+		};
+		let cfg = ReadinessConfig {
+			max_sync_lag: 10,
+			is_fat_client,
+		};
+		sync_check(&state, &cfg).is_ok()
+	}
+
+	#[test]
+	fn test_database_check() {
+		assert!(database_check(&MemoryDB::default()).is_ok());
+	}
+
+	#[test_case(true, true => (Status::Ready, 200) ; "All checks passed")]
+	#[test_case(true, false => (Status::NotReady, 503) ; "Check failed")]
+	fn test_response(rpc: bool, sync: bool) -> (Status, u16) {
+		let response: Response = Checks {
+			rpc: check(rpc),
+			p2p: check(true),
+			database: check(true),
+			sync: check(sync),
+		}
+		.into();
+		(response.status, response.into_response().status().as_u16())
+	}
+}
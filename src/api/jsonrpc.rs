@@ -0,0 +1,342 @@
+//! JSON-RPC endpoint compatible with a subset of the Substrate node RPC.
+//!
+//! # Methods
+//!
+//! * `chain_getHeader(hash?)` - header of the block, or of the latest processed block if hash is omitted
+//! * `chain_getBlockHash(number?)` - hash of the block, or of the latest processed block if number is omitted
+//! * `chain_getFinalizedHead()` - hash of the latest processed block
+//! * `system_health()` - number of the connected peers, and whether historical sync is in progress
+//! * `kate_blockConfidence(hash?)` - number and confidence of the block, or of the latest processed block if hash is omitted
+
+use async_trait::async_trait;
+use avail_subxt::primitives::Header;
+use codec::Encode;
+use color_eyre::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sp_core::{blake2_256, H256};
+use std::sync::{Arc, Mutex};
+use warp::{hyper::body::Bytes, Filter, Rejection, Reply};
+
+use crate::{
+	data::{self, Database, Key},
+	network::p2p,
+	types::State,
+	utils::calculate_confidence,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Network status used by the `system_health` method.
+#[async_trait]
+pub trait Network {
+	async fn connected_peers(&self) -> Result<usize>;
+}
+
+#[async_trait]
+impl Network for p2p::Client {
+	async fn connected_peers(&self) -> Result<usize> {
+		Ok(self.list_connected_peers().await?.len())
+	}
+}
+
+#[derive(Deserialize)]
+struct Request {
+	jsonrpc: String,
+	#[serde(default)]
+	id: Value,
+	method: String,
+	#[serde(default)]
+	params: Value,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Error {
+	pub code: i64,
+	pub message: String,
+}
+
+impl Error {
+	fn new(code: i64, message: impl Into<String>) -> Self {
+		Error {
+			code,
+			message: message.into(),
+		}
+	}
+
+	fn internal(error: color_eyre::Report) -> Self {
+		Error::new(INTERNAL_ERROR, format!("{error:#}"))
+	}
+}
+
+#[derive(Serialize)]
+struct Response {
+	jsonrpc: &'static str,
+	id: Value,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	result: Option<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<Error>,
+}
+
+impl Response {
+	fn new(id: Value, result: Result<Value, Error>) -> Self {
+		let (result, error) = match result {
+			Ok(result) => (Some(result), None),
+			Err(error) => (None, Some(error)),
+		};
+		Response {
+			jsonrpc: "2.0",
+			id,
+			result,
+			error,
+		}
+	}
+}
+
+/// Returns optional positional parameter, parameter is omitted if it is missing or `null`.
+fn param<T: DeserializeOwned>(params: &Value, index: usize) -> Result<Option<T>, Error> {
+	let param = match params {
+		Value::Null => return Ok(None),
+		Value::Array(params) => params.get(index).cloned().unwrap_or_default(),
+		_ => return Err(Error::new(INVALID_PARAMS, "Parameters must be an array")),
+	};
+	if param.is_null() {
+		return Ok(None);
+	}
+	serde_json::from_value(param).map(Some).map_err(|error| {
+		Error::new(
+			INVALID_PARAMS,
+			format!("Invalid parameter {index}: {error}"),
+		)
+	})
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, Error> {
+	serde_json::to_value(value).map_err(|error| Error::internal(error.into()))
+}
+
+#[derive(Clone)]
+pub struct JsonRpc<T, N> {
+	pub db: T,
+	pub state: Arc<Mutex<State>>,
+	pub is_fat_client: bool,
+	pub network: Arc<N>,
+}
+
+impl<T: Database, N: Network> JsonRpc<T, N> {
+	fn state<R>(&self, f: impl FnOnce(&State) -> R) -> Result<R, Error> {
+		let state = self
+			.state
+			.lock()
+			.map_err(|_| Error::new(INTERNAL_ERROR, "State mutex is poisoned"))?;
+		Ok(f(&state))
+	}
+
+	fn latest_processed(&self) -> Result<Option<u32>, Error> {
+		self.state(|state| state.latest_processed(self.is_fat_client))
+	}
+
+	/// Returns number of the processed block, or of the latest processed block if hash is omitted.
+	fn block_number(&self, block_hash: Option<H256>) -> Result<Option<u32>, Error> {
+		match block_hash {
+			Some(block_hash) => self
+				.db
+				.get(Key::BlockNumber(block_hash))
+				.map_err(Error::internal),
+			None => self.latest_processed(),
+		}
+	}
+
+	fn header(&self, block_number: Option<u32>) -> Result<Option<Header>, Error> {
+		let Some(block_number) = block_number else {
+			return Ok(None);
+		};
+		data::get_canonical(&self.db, block_number, Key::BlockHeader).map_err(Error::internal)
+	}
+
+	fn block_hash(&self, block_number: Option<u32>) -> Result<Option<H256>, Error> {
+		let header = self.header(block_number)?;
+		Ok(header.map(|header| Encode::using_encoded(&header, blake2_256).into()))
+	}
+
+	async fn call(&self, method: &str, params: &Value) -> Result<Value, Error> {
+		match method {
+			"chain_getHeader" => {
+				let block_number = self.block_number(param(params, 0)?)?;
+				self.header(block_number).and_then(to_value)
+			},
+			"chain_getBlockHash" => {
+				let block_number = match param::<u32>(params, 0)? {
+					Some(block_number) => Some(block_number),
+					None => self.latest_processed()?,
+				};
+				to_value(self.block_hash(block_number)?)
+			},
+			"chain_getFinalizedHead" => to_value(self.block_hash(self.latest_processed()?)?),
+			"system_health" => {
+				let peers = self
+					.network
+					.connected_peers()
+					.await
+					.map_err(Error::internal)?;
+				let is_syncing = self.state(|state| state.synced == Some(false))?;
+				Ok(json!({
+					"peers": peers,
+					"isSyncing": is_syncing,
+					"shouldHavePeers": true,
+				}))
+			},
+			"kate_blockConfidence" => {
+				let Some(block_number) = self.block_number(param(params, 0)?)? else {
+					return Ok(Value::Null);
+				};
+				let confidence =
+					data::get_canonical(&self.db, block_number, Key::VerifiedCellCount)
+						.map_err(Error::internal)?
+						.map(calculate_confidence);
+				Ok(json!({ "blockNumber": block_number, "confidence": confidence }))
+			},
+			method => Err(Error::new(
+				METHOD_NOT_FOUND,
+				format!("Method {method} not found"),
+			)),
+		}
+	}
+
+	async fn handle(&self, request: Value) -> Response {
+		let request = match serde_json::from_value::<Request>(request) {
+			Ok(request) if request.jsonrpc == "2.0" => request,
+			_ => {
+				let error = Error::new(INVALID_REQUEST, "Invalid request");
+				return Response::new(Value::Null, Err(error));
+			},
+		};
+		let result = self.call(&request.method, &request.params).await;
+		Response::new(request.id, result)
+	}
+
+	/// Handles single or batch request, requests in the batch are handled in order.
+	async fn handle_body(&self, body: Bytes) -> Value {
+		let into_value = |response: Response| serde_json::to_value(response).unwrap_or_default();
+		match serde_json::from_slice::<Value>(&body) {
+			Ok(Value::Array(requests)) if !requests.is_empty() => {
+				let mut responses = vec![];
+				for request in requests {
+					responses.push(into_value(self.handle(request).await));
+				}
+				Value::Array(responses)
+			},
+			Ok(Value::Array(_)) => into_value(Response::new(
+				Value::Null,
+				Err(Error::new(INVALID_REQUEST, "Empty batch")),
+			)),
+			Ok(request) => into_value(self.handle(request).await),
+			Err(error) => into_value(Response::new(
+				Value::Null,
+				Err(Error::new(PARSE_ERROR, format!("Parse error: {error}"))),
+			)),
+		}
+	}
+}
+
+pub fn routes<T, N>(
+	json_rpc: JsonRpc<T, N>,
+	max_body_size: u64,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone
+where
+	T: Database + Clone + Send + Sync + 'static,
+	N: Network + Send + Sync + 'static,
+{
+	warp::post()
+		.and(warp::path("rpc"))
+		.and(warp::path::end())
+		.and(warp::body::content_length_limit(max_body_size))
+		.and(warp::body::bytes())
+		.then(move |body: Bytes| {
+			let json_rpc = json_rpc.clone();
+			async move { warp::reply::json(&json_rpc.handle_body(body).await) }
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use async_trait::async_trait;
+	use serde_json::{json, Value};
+	use sp_core::H256;
+	use std::sync::{Arc, Mutex};
+	use test_case::test_case;
+
+	use super::{routes, JsonRpc, Network};
+	use crate::{
+		data::{mem_db::MemoryDB, Database, Key},
+		types::{BlockRange, State},
+	};
+
+	struct MockNetwork;
+
+	#[async_trait]
+	impl Network for MockNetwork {
+		async fn connected_peers(&self) -> color_eyre::Result<usize> {
+			Ok(3)
+		}
+	}
+
+	async fn request(body: &str) -> Value {
+		let db = MemoryDB::default();
+		db.put(Key::BlockNumber(H256::repeat_byte(1)), 10u32)
+			.unwrap();
+		db.put(Key::CanonicalHash(10), H256::repeat_byte(1))
+			.unwrap();
+		db.put(Key::VerifiedCellCount(10, H256::repeat_byte(1)), 4u32)
+			.unwrap();
+		let state = State {
+			latest: 10,
+			confidence_achieved: Some(BlockRange::init(10)),
+			synced: Some(false),
+			..Default::default()
+		};
+		let json_rpc = JsonRpc {
+			db,
+			state: Arc::new(Mutex::new(state)),
+			is_fat_client: false,
+			network: Arc::new(MockNetwork),
+		};
+		let response = warp::test::request()
+			.method("POST")
+			.path("/rpc")
+			.body(body)
+			.reply(&routes(json_rpc, 1024))
+			.await;
+		serde_json::from_slice(response.body()).unwrap()
+	}
+
+	#[test_case(r#"{"jsonrpc":"2.0","id":1,"method":"system_health"}"# => json!({"jsonrpc":"2.0","id":1,"result":{"peers":3,"isSyncing":true,"shouldHavePeers":true}}) ; "System health")]
+	#[test_case(r#"{"jsonrpc":"2.0","id":1,"method":"kate_blockConfidence","params":["0x0101010101010101010101010101010101010101010101010101010101010101"]}"# => json!({"jsonrpc":"2.0","id":1,"result":{"blockNumber":10,"confidence":93.75}}) ; "Confidence by hash")]
+	#[test_case(r#"{"jsonrpc":"2.0","id":1,"method":"kate_blockConfidence","params":["0x0202020202020202020202020202020202020202020202020202020202020202"]}"# => json!({"jsonrpc":"2.0","id":1,"result":null}) ; "Unknown block hash")]
+	#[test_case(r#"{"jsonrpc":"2.0","id":1,"method":"chain_getHeader","params":[]}"# => json!({"jsonrpc":"2.0","id":1,"result":null}) ; "Header is not stored")]
+	#[test_case(r#"{"jsonrpc":"2.0","id":1,"method":"chain_getHeader","params":"0x01"}"# => json!({"jsonrpc":"2.0","id":1,"error":{"code":-32602,"message":"Parameters must be an array"}}) ; "Invalid parameters")]
+	#[test_case(r#"{"jsonrpc":"2.0","id":1,"method":"author_submitExtrinsic"}"# => json!({"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"Method author_submitExtrinsic not found"}}) ; "Unknown method")]
+	#[test_case(r#"{"id":1,"method":"system_health"}"# => json!({"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"Invalid request"}}) ; "Invalid request")]
+	#[tokio::test]
+	async fn test_request(body: &str) -> Value {
+		request(body).await
+	}
+
+	#[tokio::test]
+	async fn batch_and_parse_error() {
+		let response = request(
+			r#"[{"jsonrpc":"2.0","id":1,"method":"system_health"},{"jsonrpc":"2.0","id":2,"method":"chain_getFinalizedHead"}]"#,
+		)
+		.await;
+		assert_eq!(response[0]["id"], 1);
+		assert_eq!(response[1], json!({"jsonrpc":"2.0","id":2,"result":null}));
+
+		let response = request("{").await;
+		assert_eq!(response["error"]["code"], -32700);
+	}
+}
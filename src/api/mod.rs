@@ -1,3 +1,8 @@
+pub mod admin;
+pub mod auth;
+pub mod grpc;
+pub mod health;
+pub mod jsonrpc;
 pub mod server;
 mod v1;
 pub mod v2;
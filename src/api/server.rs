@@ -7,24 +7,32 @@
 //! * `/v1/latest_block` - returns latest processed block
 //! * `/v1/confidence/{block_number}` - returns calculated confidence for a given block number
 //! * `/v1/appdata/{block_number}` - returns decoded extrinsic data for configured app_id and given block number
+//! * `/health` - returns `200 OK` while the process is alive
+//! * `/ready` - returns readiness of the client, with the dependency checks (see [health])
+//! * `/diagnostics` - returns self-diagnostics report of the client
+//! * `/rpc` - JSON-RPC endpoint compatible with a subset of the Substrate node RPC (see [jsonrpc])
+//! * `/metrics` - returns Prometheus metrics, if enabled
+//! * `/admin/log-levels` - returns and updates per-module log levels, if admin API is enabled
+//!
+//! Requests are authorized by the API key roles, if API keys are configured (see [auth]).
 
-use crate::api::v2;
+use crate::api::{admin, auth, health, jsonrpc, v2};
+use crate::app_client::AppClients;
 use crate::data::Database;
+use crate::logs::LogFilter;
 use crate::shutdown::Controller;
+use crate::telemetry::prometheus;
 use crate::types::IdentityConfig;
 use crate::{
 	api::v1,
-	network::rpc::{self},
+	network::{p2p, rpc},
 	types::{RuntimeConfig, State},
 };
 use color_eyre::eyre::WrapErr;
 use futures::{Future, FutureExt};
-use std::{
-	net::SocketAddr,
-	str::FromStr,
-	sync::{Arc, Mutex},
-};
-use tracing::info;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+use url::{Origin, Url};
 use warp::{Filter, Reply};
 
 pub struct Server<T: Database> {
@@ -35,27 +43,86 @@ pub struct Server<T: Database> {
 	pub version: String,
 	pub network_version: String,
 	pub node_client: rpc::Client,
+	pub p2p_client: p2p::Client,
 	pub ws_clients: v2::types::WsClients,
+	pub app_clients: Arc<AppClients<T>>,
+	pub prometheus_metrics: Option<Arc<prometheus::Metrics>>,
+	/// Log filter changed by the admin API, admin API is disabled if not set
+	pub log_filter: Option<Arc<LogFilter>>,
 	pub shutdown: Controller<String>,
 }
 
-fn health_route() -> impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone {
-	warp::head()
-		.or(warp::get())
-		.and(warp::path("health"))
-		.map(|_| warp::reply::with_status("", warp::http::StatusCode::OK))
+fn metrics_route(
+	metrics: Option<Arc<prometheus::Metrics>>,
+) -> impl Filter<Extract = impl Reply, Error = warp::Rejection> + Clone {
+	warp::get()
+		.and(warp::path("metrics"))
+		.and(warp::path::end())
+		.and_then(move || {
+			let metrics = metrics.clone();
+			async move { metrics.ok_or_else(warp::reject::not_found) }
+		})
+		.map(|metrics: Arc<prometheus::Metrics>| match metrics.encode() {
+			Ok(encoded) => warp::reply::with_header(
+				encoded,
+				"content-type",
+				"application/openmetrics-text; version=1.0.0; charset=utf-8",
+			)
+			.into_response(),
+			Err(error) => {
+				error!("{error:#}");
+				warp::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
+			},
+		})
+}
+
+/// Creates CORS filter allowing the configured origins, origins which are not valid URLs are skipped.
+fn cors_filter(origins: &[String]) -> warp::cors::Builder {
+	let cors = warp::cors()
+		.allow_headers(vec!["content-type", "authorization", "x-api-key"])
+		.allow_methods(vec!["GET", "POST", "PUT", "DELETE"]);
+	if origins.iter().any(|origin| origin == "*") {
+		return cors.allow_any_origin();
+	}
+	let origins = origins
+		.iter()
+		.filter_map(|origin| match Url::parse(origin).map(|url| url.origin()) {
+			Ok(origin @ Origin::Tuple(..)) => Some(origin.ascii_serialization()),
+			_ => {
+				warn!("Invalid CORS origin {origin}, skipping");
+				None
+			},
+		})
+		.collect::<Vec<_>>();
+	cors.allow_origins(origins.iter().map(String::as_str))
 }
 
 impl<T: Database + Clone + Send + Sync + 'static> Server<T> {
 	/// Creates a HTTP server that needs to be spawned into a runtime
 	pub fn bind(self) -> impl Future<Output = ()> {
 		let RuntimeConfig {
-			http_server_host: host,
 			http_server_port: port,
 			app_id,
 			..
 		} = self.cfg.clone();
+		let auth: auth::Auth = (&self.cfg).into();
+		let cors = cors_filter(&self.cfg.api_cors_origins);
+		let max_body_size = self.cfg.api_max_body_size;
 
+		let health_api = health::routes(
+			(&self.cfg).into(),
+			(&self.cfg).into(),
+			self.db.clone(),
+			self.state.clone(),
+			self.node_client.clone(),
+			self.p2p_client.clone(),
+		);
+		let json_rpc = jsonrpc::JsonRpc {
+			db: self.db.clone(),
+			state: self.state.clone(),
+			is_fat_client: self.cfg.is_fat_client(),
+			network: Arc::new(self.p2p_client),
+		};
 		let v1_api = v1::routes(self.db.clone(), app_id, self.state.clone());
 		let v2_api = v2::routes(
 			self.version.clone(),
@@ -66,19 +133,27 @@ impl<T: Database + Clone + Send + Sync + 'static> Server<T> {
 			self.node_client.clone(),
 			self.ws_clients.clone(),
 			self.db.clone(),
+			self.app_clients,
 		);
 
-		let cors = warp::cors()
-			.allow_any_origin()
-			.allow_header("content-type")
-			.allow_methods(vec!["GET", "POST", "DELETE"]);
-
-		let routes = health_route().or(v1_api).or(v2_api).with(cors);
+		let routes = auth::filter(auth)
+			.and(
+				health_api
+					.or(jsonrpc::routes(json_rpc, max_body_size))
+					.or(metrics_route(self.prometheus_metrics))
+					.or(admin::routes(self.log_filter, max_body_size))
+					.or(v1_api)
+					.or(v2_api),
+			)
+			.recover(auth::handle_rejection)
+			.with(cors);
 
-		let addr = SocketAddr::from_str(format!("{host}:{port}").as_str())
+		let addr = self
+			.cfg
+			.http_socket_address(port)
 			.wrap_err("Unable to parse host address from config")
 			.unwrap();
-		info!("RPC running on http://{host}:{port}");
+		info!("RPC running on http://{addr}");
 		// warp graceful shutdown expects a signal that is [`Future<Output = ()>`]
 		let shutdown_signal = self.shutdown.triggered_shutdown().map(|_| ());
 		let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(addr, shutdown_signal);
@@ -1,7 +1,7 @@
 use super::types::{AppDataQuery, ClientResponse, ConfidenceResponse, LatestBlockResponse, Status};
 use crate::{
 	api::v1::types::{Extrinsics, ExtrinsicsDataResponse},
-	data::{Database, Key},
+	data::{self, Database, Key},
 	types::{Mode, OptionBlockRange, State},
 	utils::calculate_confidence,
 };
@@ -33,7 +33,7 @@ pub fn confidence(
 	state: Arc<Mutex<State>>,
 ) -> ClientResponse<ConfidenceResponse> {
 	info!("Got request for confidence for block {block_num}");
-	let res = match db.get(Key::VerifiedCellCount(block_num)) {
+	let res = match data::get_canonical(&db, block_num, Key::VerifiedCellCount) {
 		Ok(Some(count)) => {
 			let confidence = calculate_confidence(count);
 			let serialised_confidence = serialised_confidence(block_num, confidence);
@@ -71,7 +71,7 @@ pub fn status(
 	let Some(last) = state.confidence_achieved.last() else {
 		return ClientResponse::NotFound;
 	};
-	let res = match db.get(Key::VerifiedCellCount(last)) {
+	let res = match data::get_canonical(&db, last, Key::VerifiedCellCount) {
 		Ok(Some(count)) => {
 			let confidence = calculate_confidence(count);
 			ClientResponse::Normal(Status {
@@ -129,9 +129,11 @@ pub fn appdata(
 	let state = state.lock().unwrap();
 	let last = state.confidence_achieved.last();
 	let decode = query.decode.unwrap_or(false);
-	let res = match decode_app_data_to_extrinsics(
-		db.get(Key::AppData(app_id.unwrap_or(0u32), block_num)),
-	) {
+	let res = match decode_app_data_to_extrinsics(data::get_canonical(
+		&db,
+		block_num,
+		|block_num, block_hash| Key::AppData(app_id.unwrap_or(0u32), block_num, block_hash),
+	)) {
 		Ok(Some(data)) => {
 			if !decode {
 				ClientResponse::Normal(ExtrinsicsDataResponse {
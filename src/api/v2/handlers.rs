@@ -1,27 +1,41 @@
 use super::{
 	transactions,
 	types::{
-		block_status, filter_fields, Block, BlockStatus, DataQuery, DataResponse, DataTransaction,
-		Error, FieldsQueryParameter, Header, Status, SubmitResponse, Subscription, SubscriptionId,
-		Transaction, Version, WsClients,
+		self, block_status, filter_fields, paginate, Block, BlockByHash, BlockStatus, CellPosition,
+		Confidence, ConfidenceQuery, DataCursor, DataQuery, DataRangeQuery, DataRangeResponse,
+		DataResponse, DataTransaction, Error, FieldsQueryParameter, FollowedApps, Header,
+		PeerScores, SampledCells, SubmitResponse, Subscription, SubscriptionId, SyncProgress,
+		Transaction, TransactionStatusMessage, Version, WsClients,
 	},
 	ws,
 };
 use crate::{
 	api::v2::types::{ErrorCode, InternalServerError},
-	data::Database,
-	data::Key,
-	types::{RuntimeConfig, State},
-	utils::calculate_confidence,
+	app_client::Follow,
+	attestation::Attestation,
+	data::{self, BlockSamplingStats, Database, Key},
+	data_proof::{leaf_hash, DataProof},
+	network::rpc,
+	types::{OptionBlockRange, RuntimeConfig, State},
+	utils::{calculate_confidence, decode_app_data, extract_app_ids, extract_kate, unix_timestamp},
+	verification::CommitmentMismatch,
 };
+use async_trait::async_trait;
+use avail_core::AppId;
 use avail_subxt::primitives;
+use codec::Encode;
 use color_eyre::{eyre::eyre, Result};
-use hyper::StatusCode;
+use futures::stream;
+use hyper::{
+	header::{HeaderValue, CONTENT_TYPE},
+	Body, StatusCode,
+};
+use sp_core::{blake2_256, H256};
 use std::{
 	convert::Infallible,
 	sync::{Arc, Mutex},
 };
-use tracing::error;
+use tracing::{debug, error};
 use uuid::Uuid;
 use warp::{ws::Ws, Rejection, Reply};
 
@@ -38,12 +52,68 @@ pub async fn submit(
 	submitter: Arc<impl transactions::Submit>,
 	transaction: Transaction,
 ) -> Result<SubmitResponse, Error> {
+	if matches!(transaction, Transaction::Data(_)) && !submitter.is_data_enabled() {
+		return Err(Error::not_found());
+	}
+
 	submitter
 		.submit(transaction)
 		.await
 		.map_err(Error::internal_server_error)
 }
 
+pub async fn transaction_status(
+	hash: H256,
+	db: impl Database,
+) -> Result<TransactionStatusMessage, Error> {
+	db.get(Key::TransactionStatus(hash))
+		.map_err(Error::internal_server_error)?
+		.map(|status| TransactionStatusMessage { hash, status })
+		.ok_or_else(Error::not_found)
+}
+
+pub async fn block_attestation(block_number: u32, db: impl Database) -> Result<Attestation, Error> {
+	db.get(Key::Attestation(block_number))
+		.map_err(Error::internal_server_error)?
+		.ok_or_else(Error::not_found)
+}
+
+pub async fn apps(app_clients: Arc<impl Follow>) -> Result<FollowedApps, Infallible> {
+	let app_ids = app_clients.app_ids();
+	Ok(FollowedApps { app_ids })
+}
+
+pub async fn follow_app(app_id: u32, app_clients: Arc<impl Follow>) -> Result<impl Reply, Error> {
+	let is_followed = app_clients
+		.follow(AppId(app_id))
+		.map_err(Error::internal_server_error)?;
+	let status = if is_followed {
+		StatusCode::CREATED
+	} else {
+		StatusCode::OK
+	};
+	let app_ids = app_clients.app_ids();
+	Ok(warp::reply::with_status(FollowedApps { app_ids }, status))
+}
+
+pub async fn unfollow_app(
+	app_id: u32,
+	app_clients: Arc<impl Follow>,
+) -> Result<FollowedApps, Error> {
+	if !app_clients.unfollow(AppId(app_id)) {
+		return Err(Error::not_found());
+	}
+	let app_ids = app_clients.app_ids();
+	Ok(FollowedApps { app_ids })
+}
+
+pub async fn sync_progress(db: impl Database) -> Result<SyncProgress, Error> {
+	db.get(Key::SyncProgress)
+		.map_err(Error::internal_server_error)?
+		.map(|progress| types::sync_progress(&progress, unix_timestamp()))
+		.ok_or_else(Error::not_found)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn ws(
 	subscription_id: String,
@@ -57,6 +127,7 @@ pub async fn ws(
 	if !clients.has_subscription(&subscription_id).await {
 		return Err(warp::reject::not_found());
 	}
+	let ws = ws.max_message_size(config.api_max_body_size as usize);
 	// NOTE: Multiple connections to the same client are currently allowed
 	Ok(ws.on_upgrade(move |web_socket| {
 		ws::connect(
@@ -73,7 +144,29 @@ pub async fn ws(
 
 pub fn status(config: RuntimeConfig, state: Arc<Mutex<State>>) -> impl Reply {
 	let state = state.lock().expect("Lock should be acquired");
-	Status::new(&config, &state)
+	types::status(&config, &state)
+}
+
+pub fn peer_scores(state: Arc<Mutex<State>>) -> impl Reply {
+	let state = state.lock().expect("Lock should be acquired");
+	PeerScores {
+		peers: state.peer_scores.clone(),
+	}
+}
+
+pub fn peers(state: Arc<Mutex<State>>) -> impl Reply {
+	let state = state.lock().expect("Lock should be acquired");
+	state.peers.clone()
+}
+
+pub fn bandwidth(state: Arc<Mutex<State>>) -> impl Reply {
+	let state = state.lock().expect("Lock should be acquired");
+	state.bandwidth.clone()
+}
+
+pub fn confidence(query: ConfidenceQuery, config: RuntimeConfig) -> Confidence {
+	let requested = query.confidence.unwrap_or(config.confidence);
+	types::confidence(requested, config.max_cell_count)
 }
 
 pub fn log_internal_server_error(result: Result<impl Reply, Error>) -> Result<impl Reply, Error> {
@@ -94,19 +187,87 @@ pub async fn block(
 	config: RuntimeConfig,
 	state: Arc<Mutex<State>>,
 	db: impl Database,
-) -> Result<impl Reply, Error> {
+) -> Result<Block, Error> {
+	block_of_hash(block_number, None, config, state, db)
+}
+
+/// Returns the block at the given height, canonical block is used unless the block hash is given.
+fn block_of_hash(
+	block_number: u32,
+	block_hash: Option<H256>,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database,
+) -> Result<Block, Error> {
 	let state = state.lock().expect("Lock should be acquired");
 
-	let Some(block_status) = block_status(&config.sync_start_block, &state, block_number) else {
-		return Err(Error::not_found());
+	let Some(mut block_status) = block_status(&config.sync_start_block, &state, block_number)
+	else {
+		// Unfinalized blocks are returned with the confidence of the sampled best head, if any
+		return state
+			.best_heads
+			.get(&block_number)
+			.filter(|best_head| block_hash.map_or(true, |hash| hash == best_head.hash))
+			.map(|best_head| Block::new(BlockStatus::BestHead, best_head.confidence, None))
+			.ok_or_else(Error::not_found);
 	};
 
-	let confidence = db
-		.get(Key::VerifiedCellCount(block_number))
+	// Blocks whose header extension does not match the sampled data are unavailable
+	let mismatch = db
+		.get::<CommitmentMismatch>(Key::CommitmentMismatch(block_number))
+		.map_err(Error::internal_server_error)?;
+	if mismatch.is_some() {
+		block_status = BlockStatus::Unavailable;
+	}
+
+	let confidence = data::get_block(&db, block_number, block_hash, Key::VerifiedCellCount)
 		.map_err(Error::internal_server_error)?
 		.map(calculate_confidence);
 
-	Ok(Block::new(block_status, confidence))
+	let sampling = db
+		.get(Key::BlockSamplingStats(block_number))
+		.map_err(Error::internal_server_error)?;
+
+	Ok(Block::new(block_status, confidence, sampling))
+}
+
+/// Resolves block numbers of the block hashes which are not found in the store.
+#[async_trait]
+pub trait BlockNumbers {
+	async fn block_number(&self, block_hash: H256) -> Result<Option<u32>>;
+}
+
+#[async_trait]
+impl BlockNumbers for rpc::Client {
+	async fn block_number(&self, block_hash: H256) -> Result<Option<u32>> {
+		self.get_block_number(block_hash).await
+	}
+}
+
+pub async fn block_by_hash(
+	block_hash: H256,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database,
+	block_numbers: Arc<impl BlockNumbers>,
+) -> Result<BlockByHash, Error> {
+	let stored = db
+		.get::<u32>(Key::BlockNumber(block_hash))
+		.map_err(Error::internal_server_error)?;
+	let block_number = match stored {
+		Some(block_number) => block_number,
+		None => block_numbers
+			.block_number(block_hash)
+			.await
+			.map_err(Error::internal_server_error)?
+			.ok_or_else(Error::not_found)?,
+	};
+
+	let block = block_of_hash(block_number, Some(block_hash), config, state, db)?;
+	Ok(BlockByHash {
+		block_number,
+		block,
+	})
 }
 
 pub async fn block_header(
@@ -128,12 +289,63 @@ pub async fn block_header(
 		return Err(Error::bad_request_unknown("Block header is not available"));
 	};
 
-	db.get::<primitives::Header>(Key::BlockHeader(block_number))
+	data::get_canonical::<primitives::Header>(&db, block_number, Key::BlockHeader)
 		.and_then(|header| header.ok_or_else(|| eyre!("Header not found")))
-		.and_then(|header| header.try_into())
+		.and_then(types::header)
 		.map_err(Error::internal_server_error)
 }
 
+pub async fn block_sampled_cells(
+	block_number: u32,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database,
+) -> Result<SampledCells, Error> {
+	if config.sampling_seed.is_none() {
+		return Err(Error::not_found());
+	};
+
+	let state = state.lock().expect("Lock should be acquired");
+
+	let Some(block_status) = block_status(&config.sync_start_block, &state, block_number) else {
+		return Err(Error::not_found());
+	};
+
+	if matches!(
+		block_status,
+		BlockStatus::Unavailable | BlockStatus::Pending | BlockStatus::VerifyingHeader
+	) {
+		return Err(Error::bad_request_unknown("Block header is not available"));
+	};
+
+	let header = data::get_canonical::<primitives::Header>(&db, block_number, Key::BlockHeader)
+		.and_then(|header| header.ok_or_else(|| eyre!("Header not found")))
+		.map_err(Error::internal_server_error)?;
+
+	let block_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+	// Sampled positions are stored, since the sampled cell count changes with the adaptive confidence and config reload
+	let stats = db
+		.get::<BlockSamplingStats>(Key::BlockSamplingStats(block_number))
+		.map_err(Error::internal_server_error)?;
+	let Some(stats) = stats else {
+		return Err(Error::not_found());
+	};
+	let cells = stats
+		.cells
+		.into_iter()
+		.map(|cell| CellPosition {
+			row: cell.row,
+			col: cell.col,
+		})
+		.collect();
+
+	Ok(SampledCells {
+		block_number,
+		block_hash,
+		cells,
+	})
+}
+
 pub async fn block_data(
 	block_number: u32,
 	query: DataQuery,
@@ -141,47 +353,295 @@ pub async fn block_data(
 	state: Arc<Mutex<State>>,
 	db: impl Database,
 ) -> Result<DataResponse, Error> {
-	let state = state.lock().expect("Lock should be acquired");
-
 	let Some(app_id) = config.app_id else {
 		return Err(Error::not_found());
 	};
 
-	let Some(block_status) = block_status(&config.sync_start_block, &state, block_number) else {
+	app_data(app_id, block_number, query, config, state, db).await
+}
+
+/// Returns stored data of the block, if the data of the application is verified.
+fn verified_app_data(
+	app_id: u32,
+	block_number: u32,
+	config: &RuntimeConfig,
+	state: &State,
+	db: &impl Database,
+) -> Result<Vec<Vec<u8>>, Error> {
+	let Some(block_status) = block_status(&config.sync_start_block, state, block_number) else {
 		return Err(Error::not_found());
 	};
 
-	if block_status != BlockStatus::Finished {
+	// Block status reflects the configured application, additional applications are tracked separately
+	let is_data_verified = match state.apps.get(&app_id) {
+		_ if config.app_id == Some(app_id) => block_status == BlockStatus::Finished,
+		Some(app) => {
+			app.data_verified.contains(block_number)
+				|| app.sync_data_verified.contains(block_number)
+		},
+		None => return Err(Error::not_found()),
+	};
+
+	if !is_data_verified {
 		return Err(Error::bad_request_unknown("Block data is not available"));
 	};
 
-	let data = db
-		.get::<Vec<Vec<u8>>>(Key::AppData(app_id, block_number))
-		.map_err(Error::internal_server_error)?;
+	let data = data::get_canonical::<Vec<Vec<u8>>>(db, block_number, |block_number, block_hash| {
+		Key::AppData(app_id, block_number, block_hash)
+	})
+	.map_err(Error::internal_server_error)?;
 
-	let Some(data) = data else {
-		return Ok(DataResponse {
-			block_number,
-			data_transactions: vec![],
-		});
-	};
+	Ok(data.unwrap_or_default())
+}
 
+fn data_transactions(
+	data: Vec<Vec<u8>>,
+	fields: &Option<FieldsQueryParameter>,
+) -> Result<Vec<DataTransaction>, Error> {
 	let mut data_transactions: Vec<DataTransaction> = data
 		.into_iter()
-		.map(DataTransaction::try_from)
+		.map(types::data_transaction)
 		.collect::<Result<_>>()
 		.map_err(Error::internal_server_error)?;
 
-	if let Some(FieldsQueryParameter(fields)) = &query.fields {
+	if let Some(FieldsQueryParameter(fields)) = fields {
 		filter_fields(&mut data_transactions, fields);
 	}
+	Ok(data_transactions)
+}
+
+/// Returns data reconstructed and decoded by the app client, only followed applications are available.
+pub async fn app_data(
+	app_id: u32,
+	block_number: u32,
+	query: DataQuery,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database,
+) -> Result<DataResponse, Error> {
+	let state = state.lock().expect("Lock should be acquired");
+	let data = verified_app_data(app_id, block_number, &config, &state, &db)?;
+
+	// Only the requested page is decoded
+	let (data, next_offset) = paginate(data, query.offset, query.limit, config.api_max_page_size);
 
 	Ok(DataResponse {
 		block_number,
-		data_transactions,
+		data_transactions: data_transactions(data, &query.fields)?,
+		next_offset,
 	})
 }
 
+/// Returns data of the application in the block range, paginated with the page cursor,
+/// or streamed as newline delimited JSON (one block per line) if `application/x-ndjson` is accepted.
+pub async fn app_data_range(
+	app_id: u32,
+	query: DataRangeQuery,
+	accept: Option<String>,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send + Sync + 'static,
+) -> Result<warp::reply::Response, Error> {
+	if query.to_block < query.from_block {
+		return Err(Error::bad_request_unknown("Invalid block range"));
+	}
+	let cursor = query.page.unwrap_or(DataCursor {
+		block_number: query.from_block,
+		offset: 0,
+	});
+	if !(query.from_block..=query.to_block).contains(&cursor.block_number) {
+		return Err(Error::bad_request_unknown("Page is out of the block range"));
+	}
+
+	if accept.is_some_and(|accept| accept.contains(NDJSON)) {
+		return Ok(stream_app_data(
+			app_id,
+			cursor,
+			query.to_block,
+			query.fields,
+			config,
+			state,
+			db,
+		));
+	}
+
+	let max_page_size = config.api_max_page_size.max(1);
+	let mut remaining = max_page_size;
+	let mut blocks = vec![];
+	let mut next_page = None;
+
+	for block_number in cursor.block_number..=query.to_block {
+		// Page is limited by the number of data transactions, and by the number of blocks
+		if remaining == 0 || blocks.len() >= max_page_size as usize {
+			next_page = Some(DataCursor {
+				block_number,
+				offset: 0,
+			});
+			break;
+		}
+
+		let data = {
+			let state = state.lock().expect("Lock should be acquired");
+			verified_app_data(app_id, block_number, &config, &state, &db)
+		};
+		let data = match data {
+			Ok(data) => data,
+			Err(error) if blocks.is_empty() => return Err(error),
+			// Page ends with the first block whose data is not available yet
+			Err(_) => {
+				next_page = Some(DataCursor {
+					block_number,
+					offset: 0,
+				});
+				break;
+			},
+		};
+
+		let offset = (block_number == cursor.block_number).then_some(cursor.offset);
+		let (data, next_offset) = paginate(data, offset, Some(remaining), max_page_size);
+		remaining -= data.len() as u32;
+		blocks.push(DataResponse {
+			block_number,
+			data_transactions: data_transactions(data, &query.fields)?,
+			next_offset: None,
+		});
+
+		if let Some(offset) = next_offset {
+			next_page = Some(DataCursor {
+				block_number,
+				offset,
+			});
+			break;
+		}
+	}
+
+	Ok(DataRangeResponse { blocks, next_page }.into_response())
+}
+
+const NDJSON: &str = "application/x-ndjson";
+
+/// Streams data of the blocks one by one, stream ends with the last block of the range,
+/// or with the first block whose data is not available.
+fn stream_app_data(
+	app_id: u32,
+	cursor: DataCursor,
+	to_block: u32,
+	fields: Option<FieldsQueryParameter>,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send + Sync + 'static,
+) -> warp::reply::Response {
+	let fields = Arc::new(fields);
+	let lines = stream::unfold(Some(cursor), move |cursor| {
+		let (fields, config, state, db) =
+			(fields.clone(), config.clone(), state.clone(), db.clone());
+		async move {
+			let cursor = cursor.filter(|cursor| cursor.block_number <= to_block)?;
+			let block_number = cursor.block_number;
+			let data = {
+				let state = state.lock().expect("Lock should be acquired");
+				verified_app_data(app_id, block_number, &config, &state, &db)
+			};
+			let data_transactions = data.and_then(|data| {
+				let data = data.into_iter().skip(cursor.offset as usize).collect();
+				data_transactions(data, &fields)
+			});
+			let response = match data_transactions {
+				Ok(data_transactions) => DataResponse {
+					block_number,
+					data_transactions,
+					next_offset: None,
+				},
+				Err(Error { cause, message, .. }) => {
+					match cause {
+						Some(cause) => error!(block_number, "{message}: {cause:#}"),
+						None => debug!(block_number, "Data stream ended: {message}"),
+					}
+					return None;
+				},
+			};
+			let mut line = match serde_json::to_vec(&response) {
+				Ok(line) => line,
+				Err(error) => {
+					error!(block_number, "Cannot serialize block data: {error}");
+					return None;
+				},
+			};
+			line.push(b'\n');
+			let next = block_number.checked_add(1).map(|block_number| DataCursor {
+				block_number,
+				offset: 0,
+			});
+			Some((Ok::<_, Infallible>(line), next))
+		}
+	});
+
+	let mut response = warp::reply::Response::new(Body::wrap_stream(lines));
+	response
+		.headers_mut()
+		.insert(CONTENT_TYPE, HeaderValue::from_static(NDJSON));
+	response
+}
+
+/// Returns inclusion proof of the data transaction on the given index in the block data root.
+/// Proof is computed from the reconstructed data, so data of all applications in the block must be available.
+pub async fn block_data_proof(
+	block_number: u32,
+	index: u32,
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database,
+) -> Result<DataProof, Error> {
+	let state = state.lock().expect("Lock should be acquired");
+
+	let Some(block_status) = block_status(&config.sync_start_block, &state, block_number) else {
+		return Err(Error::not_found());
+	};
+
+	if matches!(
+		block_status,
+		BlockStatus::Unavailable | BlockStatus::Pending | BlockStatus::VerifyingHeader
+	) {
+		return Err(Error::bad_request_unknown("Block header is not available"));
+	};
+
+	let header = data::get_canonical::<primitives::Header>(&db, block_number, Key::BlockHeader)
+		.and_then(|header| header.ok_or_else(|| eyre!("Header not found")))
+		.map_err(Error::internal_server_error)?;
+
+	// Data is keyed by the hash of the returned header, so it belongs to the same block
+	let block_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+	let mut leaves = vec![];
+	for app_id in extract_app_ids(&header.extension) {
+		let Some(extrinsics) = db
+			.get::<Vec<Vec<u8>>>(Key::AppData(app_id, block_number, block_hash))
+			.map_err(Error::internal_server_error)?
+		else {
+			let message = format!("Data of the application {app_id} is not available");
+			return Err(Error::bad_request_unknown(&message));
+		};
+		for extrinsic in extrinsics {
+			if let Some(data) = decode_app_data(&extrinsic).map_err(Error::internal_server_error)? {
+				leaves.push(leaf_hash(&data));
+			}
+		}
+	}
+
+	let Some(proof) = DataProof::new(&leaves, index) else {
+		return Err(Error::not_found());
+	};
+
+	let (_, _, data_root, _) = extract_kate(&header.extension);
+	if proof.data_root != data_root {
+		return Err(Error::internal_server_error(eyre!(
+			"Computed data root {:?} does not match header data root {data_root:?}",
+			proof.data_root
+		)));
+	}
+
+	Ok(proof)
+}
+
 pub async fn handle_rejection(error: Rejection) -> Result<impl Reply, Rejection> {
 	if error.find::<InternalServerError>().is_some() {
 		return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
@@ -1,29 +1,31 @@
-use avail_subxt::AvailConfig;
-use sp_core::sr25519::Pair;
+use sp_core::H256;
 use std::{
 	convert::Infallible,
 	fmt::Display,
 	sync::{Arc, Mutex},
 };
-use subxt::tx::PairSigner;
-use tokio::sync::broadcast;
-use tracing::{debug, error, info};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, error, info, warn};
 use warp::{Filter, Rejection, Reply};
 
 use self::{
 	handlers::{handle_rejection, log_internal_server_error},
-	types::{DataQuery, PublishMessage, Version, WsClients},
+	types::{ConfidenceQuery, DataQuery, DataRangeQuery, PublishMessage, Version, WsClients},
 };
 
 use crate::{
-	api::v2::types::Topic,
+	api::{
+		auth::{self, Auth, Role},
+		v2::types::Topic,
+	},
+	app_client::Follow,
 	data::Database,
 	network::rpc::Client,
 	types::{IdentityConfig, RuntimeConfig, State},
 };
 
-mod handlers;
-mod transactions;
+pub(crate) mod handlers;
+pub(crate) mod transactions;
 pub mod types;
 mod ws;
 
@@ -65,6 +67,43 @@ fn status_route(
 		.map(handlers::status)
 }
 
+fn peer_scores_route(
+	state: Arc<Mutex<State>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "p2p" / "peer_scores")
+		.and(warp::get())
+		.and(warp::any().map(move || state.clone()))
+		.map(handlers::peer_scores)
+}
+
+fn peers_route(
+	state: Arc<Mutex<State>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "p2p" / "peers")
+		.and(warp::get())
+		.and(warp::any().map(move || state.clone()))
+		.map(handlers::peers)
+}
+
+fn bandwidth_route(
+	state: Arc<Mutex<State>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "p2p" / "bandwidth")
+		.and(warp::get())
+		.and(warp::any().map(move || state.clone()))
+		.map(handlers::bandwidth)
+}
+
+fn confidence_route(
+	config: RuntimeConfig,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "confidence")
+		.and(warp::get())
+		.and(warp::query::<ConfidenceQuery>())
+		.and(warp::any().map(move || config.clone()))
+		.map(handlers::confidence)
+}
+
 fn block_route(
 	config: RuntimeConfig,
 	state: Arc<Mutex<State>>,
@@ -79,6 +118,22 @@ fn block_route(
 		.map(log_internal_server_error)
 }
 
+fn block_by_hash_route(
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send,
+	block_numbers: Arc<impl handlers::BlockNumbers + Send + Sync>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "blocks" / H256)
+		.and(warp::get())
+		.and(warp::any().map(move || config.clone()))
+		.and(warp::any().map(move || state.clone()))
+		.and(with_db(db))
+		.and(warp::any().map(move || block_numbers.clone()))
+		.then(handlers::block_by_hash)
+		.map(log_internal_server_error)
+}
+
 fn block_header_route(
 	config: RuntimeConfig,
 	state: Arc<Mutex<State>>,
@@ -93,6 +148,20 @@ fn block_header_route(
 		.map(log_internal_server_error)
 }
 
+fn block_sampled_cells_route(
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "blocks" / u32 / "sampled_cells")
+		.and(warp::get())
+		.and(warp::any().map(move || config.clone()))
+		.and(warp::any().map(move || state.clone()))
+		.and(with_db(db))
+		.then(handlers::block_sampled_cells)
+		.map(log_internal_server_error)
+}
+
 fn block_data_route(
 	config: RuntimeConfig,
 	state: Arc<Mutex<State>>,
@@ -108,22 +177,130 @@ fn block_data_route(
 		.map(log_internal_server_error)
 }
 
+fn block_data_proof_route(
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "blocks" / u32 / "data" / u32 / "proof")
+		.and(warp::get())
+		.and(warp::any().map(move || config.clone()))
+		.and(warp::any().map(move || state.clone()))
+		.and(with_db(db))
+		.then(handlers::block_data_proof)
+		.map(log_internal_server_error)
+}
+
+fn app_data_route(
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "apps" / u32 / "data" / u32)
+		.and(warp::get())
+		.and(warp::query::<DataQuery>())
+		.and(warp::any().map(move || config.clone()))
+		.and(warp::any().map(move || state.clone()))
+		.and(with_db(db))
+		.then(handlers::app_data)
+		.map(log_internal_server_error)
+}
+
+fn app_data_range_route(
+	config: RuntimeConfig,
+	state: Arc<Mutex<State>>,
+	db: impl Database + Clone + Send + Sync + 'static,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "apps" / u32 / "data")
+		.and(warp::get())
+		.and(warp::query::<DataRangeQuery>())
+		.and(warp::header::optional::<String>("accept"))
+		.and(warp::any().map(move || config.clone()))
+		.and(warp::any().map(move || state.clone()))
+		.and(with_db(db))
+		.then(handlers::app_data_range)
+		.map(log_internal_server_error)
+}
+
+fn apps_route(
+	app_clients: Arc<impl Follow + Send + Sync>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "apps")
+		.and(warp::get())
+		.and(warp::any().map(move || app_clients.clone()))
+		.and_then(handlers::apps)
+}
+
+fn follow_app_route(
+	app_clients: Arc<impl Follow + Send + Sync>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "apps" / u32)
+		.and(warp::put())
+		.and(warp::any().map(move || app_clients.clone()))
+		.then(handlers::follow_app)
+		.map(log_internal_server_error)
+}
+
+fn unfollow_app_route(
+	app_clients: Arc<impl Follow + Send + Sync>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "apps" / u32)
+		.and(warp::delete())
+		.and(warp::any().map(move || app_clients.clone()))
+		.then(handlers::unfollow_app)
+		.map(log_internal_server_error)
+}
+
 fn submit_route(
 	submitter: Option<Arc<impl transactions::Submit + Clone + Send + Sync>>,
+	max_body_size: u64,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
 	warp::path!("v2" / "submit")
 		.and(warp::post())
 		.and_then(move || optionally(submitter.clone()))
+		.and(warp::body::content_length_limit(max_body_size))
 		.and(warp::body::json())
 		.then(handlers::submit)
 		.map(log_internal_server_error)
 }
 
+fn transaction_status_route(
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "submit" / H256 / "status")
+		.and(warp::get())
+		.and(with_db(db))
+		.then(handlers::transaction_status)
+		.map(log_internal_server_error)
+}
+
+fn block_attestation_route(
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "blocks" / u32 / "attestation")
+		.and(warp::get())
+		.and(with_db(db))
+		.then(handlers::block_attestation)
+		.map(log_internal_server_error)
+}
+
+fn sync_progress_route(
+	db: impl Database + Clone + Send,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+	warp::path!("v2" / "sync")
+		.and(warp::get())
+		.and(with_db(db))
+		.then(handlers::sync_progress)
+		.map(log_internal_server_error)
+}
+
 fn subscriptions_route(
 	clients: WsClients,
+	max_body_size: u64,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
 	warp::path!("v2" / "subscriptions")
 		.and(warp::post())
+		.and(warp::body::content_length_limit(max_body_size))
 		.and(warp::body::json())
 		.and(with_ws_clients(clients))
 		.and_then(handlers::subscriptions)
@@ -135,13 +312,17 @@ fn ws_route(
 	config: RuntimeConfig,
 	submitter: Option<Arc<impl transactions::Submit + Clone + Send + Sync + 'static>>,
 	state: Arc<Mutex<State>>,
+	auth: Auth,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
 	warp::path!("v2" / "ws" / String)
 		.and(warp::ws())
 		.and(with_ws_clients(clients))
 		.and(warp::any().map(move || version.clone()))
 		.and(warp::any().map(move || config.clone()))
-		.and(warp::any().map(move || submitter.clone()))
+		// Transactions can be submitted over WebSocket only by the callers with the submit role
+		.and(auth::caller_role(auth).map(move |role: Option<Role>| {
+			submitter.clone().filter(|_| role >= Some(Role::Submit))
+		}))
 		.and(warp::any().map(move || state.clone()))
 		.and_then(handlers::ws)
 }
@@ -156,6 +337,13 @@ pub async fn publish<T: Clone + TryInto<PublishMessage>>(
 	loop {
 		let message = match receiver.recv().await {
 			Ok(value) => value,
+			Err(RecvError::Lagged(skipped)) => {
+				warn!(
+					?topic,
+					"Publishing is lagging behind, {skipped} messages are skipped"
+				);
+				continue;
+			},
 			Err(error) => {
 				error!(?topic, "Cannot receive message: {error}");
 				return;
@@ -193,63 +381,118 @@ pub fn routes(
 	identity_config: IdentityConfig,
 	rpc_client: Client,
 	ws_clients: WsClients,
-	db: impl Database + Clone + Send,
+	db: impl Database + Clone + Send + Sync + 'static,
+	app_clients: Arc<impl Follow + Send + Sync + 'static>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
 	let version = Version {
 		version,
 		network_version,
 	};
+	let auth = Auth::from(&config);
 
-	let app_id = config.app_id.as_ref();
-	let pair_signer = <PairSigner<AvailConfig, Pair>>::new(identity_config.avail_key_pair);
+	let block_numbers = Arc::new(rpc_client.clone());
 
-	let submitter = app_id.map(|&app_id| {
-		Arc::new(transactions::Submitter {
-			rpc_client,
-			app_id,
-			pair_signer,
-		})
-	});
+	// Signed extrinsics can be submitted even if app mode is not active
+	let submitter = Some(Arc::new(transactions::Submitter::new(
+		rpc_client,
+		config.app_id,
+		identity_config,
+		db.clone(),
+		ws_clients.clone(),
+	)));
 
 	version_route(version.clone())
 		.or(status_route(config.clone(), state.clone()))
+		.or(peer_scores_route(state.clone()))
+		.or(peers_route(state.clone()))
+		.or(bandwidth_route(state.clone()))
+		.or(confidence_route(config.clone()))
 		.or(block_route(config.clone(), state.clone(), db.clone()))
+		.or(block_by_hash_route(
+			config.clone(),
+			state.clone(),
+			db.clone(),
+			block_numbers,
+		))
 		.or(block_header_route(
 			config.clone(),
 			state.clone(),
 			db.clone(),
 		))
+		.or(block_sampled_cells_route(
+			config.clone(),
+			state.clone(),
+			db.clone(),
+		))
+		.or(block_attestation_route(db.clone()))
 		.or(block_data_route(config.clone(), state.clone(), db.clone()))
-		.or(subscriptions_route(ws_clients.clone()))
-		.or(submit_route(submitter.clone()))
-		.or(ws_route(ws_clients, version, config, submitter, state))
+		.or(block_data_proof_route(
+			config.clone(),
+			state.clone(),
+			db.clone(),
+		))
+		.or(app_data_route(config.clone(), state.clone(), db.clone()))
+		.or(app_data_range_route(
+			config.clone(),
+			state.clone(),
+			db.clone(),
+		))
+		.or(apps_route(app_clients.clone()))
+		.or(follow_app_route(app_clients.clone()))
+		.or(unfollow_app_route(app_clients))
+		.or(subscriptions_route(
+			ws_clients.clone(),
+			config.api_max_body_size,
+		))
+		.or(submit_route(submitter.clone(), config.api_max_body_size))
+		.or(transaction_status_route(db.clone()))
+		.or(sync_progress_route(db))
+		.or(ws_route(
+			ws_clients, version, config, submitter, state, auth,
+		))
 		.recover(handle_rejection)
 }
 
 #[cfg(test)]
 mod tests {
-	use super::{transactions, types::Transaction};
+	use super::{handlers, transactions, types::Transaction, Auth};
+	use crate::api::auth::{ApiKey, Role};
 	use crate::{
 		api::v2::types::{
-			DataField, ErrorCode, SubmitResponse, Subscription, SubscriptionId, Topic, Version,
-			WsClients, WsError, WsResponse,
+			DataField, DataRangeResponse, DataResponse, ErrorCode, SampledCells, SubmitResponse,
+			Subscription, SubscriptionId, Topic, Version, WsClients, WsError, WsResponse,
 		},
+		app_client::MockFollow,
+		attestation::{Attestation, KeyType, Signer},
+		best_heads::BestHead,
 		data::Key,
-		data::{mem_db, Database},
-		types::{BlockRange, OptionBlockRange, RuntimeConfig, State},
+		data::{
+			mem_db, Batch, BlockSamplingStats, CellSource, Database, SampledCell, SamplingTimings,
+			SyncProgress, TransactionStatus,
+		},
+		data_proof::{leaf_hash, DataProof},
+		network::p2p::{
+			BandwidthStats, Direction, NatStatus, PeerInfo, PeerScore, Peers, Reachability,
+			RoutingTableStats, Traffic, Transport,
+		},
+		types::{AppState, BlockRange, OptionBlockRange, RuntimeConfig, State},
+		verification::{CommitmentMismatch, MismatchReason},
 	};
 	use async_trait::async_trait;
 	use avail_subxt::utils::H256;
 	use avail_subxt::{
 		api::runtime_types::avail_core::{
-			data_lookup::compact::CompactDataLookup,
+			data_lookup::compact::{CompactDataLookup, DataLookupItem},
 			header::extension::{v3, HeaderExtension},
 			kate_commitment::v3::KateCommitment,
+			AppId,
 		},
 		primitives::Header as DaHeader,
 	};
+	use codec::Encode;
 	use hyper::StatusCode;
 	use kate_recovery::matrix::Partition;
+	use sp_core::blake2_256;
 	use std::{
 		collections::HashSet,
 		str::FromStr,
@@ -342,6 +585,119 @@ mod tests {
 		assert_eq!(response.body(), &expected);
 	}
 
+	#[tokio::test]
+	async fn status_route_reachability() {
+		let state = Arc::new(Mutex::new(State::default()));
+		state.lock().unwrap().reachability = Some(Reachability {
+			nat_status: NatStatus::Private,
+			public_address: None,
+			relayed: true,
+		});
+
+		let route = super::status_route(RuntimeConfig::default(), state);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/status")
+			.reply(&route)
+			.await;
+
+		let gen_hash = H256::default();
+		let expected = format!(
+			r#"{{"modes":["light"],"genesis_hash":"{:x?}","network":"{NETWORK}","blocks":{{"latest":0}},"reachability":{{"nat_status":"private","relayed":true}}}}"#,
+			gen_hash
+		);
+		assert_eq!(response.body(), &expected);
+	}
+
+	#[tokio::test]
+	async fn peer_scores_route() {
+		let state = Arc::new(Mutex::new(State::default()));
+		state.lock().unwrap().peer_scores = vec![PeerScore {
+			peer_id: "12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz".to_string(),
+			score: -100,
+			served: 2,
+			invalid: 5,
+			timeouts: 2,
+			banned_for: Some(3600),
+		}];
+
+		let route = super::peer_scores_route(state);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/p2p/peer_scores")
+			.reply(&route)
+			.await;
+
+		assert_eq!(
+			response.body(),
+			r#"{"peers":[{"peer_id":"12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz","score":-100,"served":2,"invalid":5,"timeouts":2,"banned_for":3600}]}"#
+		);
+	}
+
+	#[tokio::test]
+	async fn peers_route() {
+		let state = Arc::new(Mutex::new(State::default()));
+		state.lock().unwrap().peers = Peers {
+			peers: vec![PeerInfo {
+				peer_id: "12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz".to_string(),
+				agent_version: Some(
+					"avail-light-client/light-client/1.9.0/rust-client".to_string(),
+				),
+				latency: Some(42),
+				direction: Direction::Outbound,
+				transport: Transport::Tcp,
+				served: 12,
+				invalid: 1,
+			}],
+			routing_table: RoutingTableStats {
+				peers: 20,
+				buckets: 3,
+			},
+		};
+
+		let route = super::peers_route(state);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/p2p/peers")
+			.reply(&route)
+			.await;
+
+		assert_eq!(
+			response.body(),
+			r#"{"peers":[{"peer_id":"12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz","agent_version":"avail-light-client/light-client/1.9.0/rust-client","latency":42,"direction":"outbound","transport":"tcp","served":12,"invalid":1}],"routing_table":{"peers":20,"buckets":3}}"#
+		);
+	}
+
+	#[tokio::test]
+	async fn bandwidth_route() {
+		let state = Arc::new(Mutex::new(State::default()));
+		state.lock().unwrap().bandwidth = BandwidthStats {
+			protocols: [(
+				"kad".to_string(),
+				Traffic {
+					uploaded: 2000,
+					downloaded: 80,
+				},
+			)]
+			.into(),
+			hourly_upload: 1000,
+			daily_upload: 2000,
+			throttled: true,
+		};
+
+		let route = super::bandwidth_route(state);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/p2p/bandwidth")
+			.reply(&route)
+			.await;
+
+		assert_eq!(
+			response.body(),
+			r#"{"protocols":{"kad":{"uploaded":2000,"downloaded":80}},"hourly_upload":1000,"daily_upload":2000,"throttled":true}"#
+		);
+	}
+
 	#[test_case(1, 2)]
 	#[test_case(10, 11)]
 	#[test_case(10, 20)]
@@ -375,7 +731,8 @@ mod tests {
 			state.data_verified.set(10);
 		}
 		let db = mem_db::MemoryDB::default();
-		_ = db.put(Key::VerifiedCellCount(10), 4);
+		_ = db.put(Key::CanonicalHash(10), H256::repeat_byte(1));
+		_ = db.put(Key::VerifiedCellCount(10, H256::repeat_byte(1)), 4);
 		let route = super::block_route(config, state, db);
 		let response = warp::test::request()
 			.method("GET")
@@ -390,6 +747,153 @@ mod tests {
 		);
 	}
 
+	#[tokio::test]
+	async fn block_route_best_head() {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State::default()));
+		{
+			let mut state = state.lock().unwrap();
+			state.latest = 10;
+			let best_head = BestHead {
+				hash: H256::repeat_byte(11),
+				confidence: Some(93.75),
+			};
+			state.best_heads.insert(11, best_head);
+		}
+		let db = mem_db::MemoryDB::default();
+		let route = super::block_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/11")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			r#"{"status":"best-head","confidence":93.75}"#
+		);
+	}
+
+	#[tokio::test]
+	async fn block_route_commitment_mismatch() {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State::default()));
+		{
+			let mut state = state.lock().unwrap();
+			state.latest = 10;
+			state.header_verified.set(10);
+		}
+		let db = mem_db::MemoryDB::default();
+		let mismatch = CommitmentMismatch {
+			block_number: 10,
+			block_hash: H256::zero(),
+			reason: MismatchReason::UnverifiedCells { cells: 1 },
+		};
+		_ = db.put(Key::CommitmentMismatch(10), mismatch);
+		let route = super::block_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/10")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			r#"{"status":"unavailable","confidence":null}"#
+		);
+	}
+
+	struct MockBlockNumbers(Option<u32>);
+
+	#[async_trait]
+	impl handlers::BlockNumbers for MockBlockNumbers {
+		async fn block_number(&self, _: H256) -> color_eyre::Result<Option<u32>> {
+			Ok(self.0)
+		}
+	}
+
+	#[test_case(Some(10), None => StatusCode::OK ; "Block number from the store")]
+	#[test_case(None, Some(10) => StatusCode::OK ; "Block number from the RPC")]
+	#[test_case(None, None => StatusCode::NOT_FOUND ; "Unknown block hash")]
+	#[tokio::test]
+	async fn block_by_hash_route(stored: Option<u32>, resolved: Option<u32>) -> StatusCode {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(10)),
+			data_verified: Some(BlockRange::init(10)),
+			..Default::default()
+		}));
+		let block_hash = H256::repeat_byte(1);
+		let db = mem_db::MemoryDB::default();
+		_ = db.put(Key::VerifiedCellCount(10, block_hash), 4);
+		if let Some(block_number) = stored {
+			_ = db.put(Key::BlockNumber(block_hash), block_number);
+		}
+		let block_numbers = Arc::new(MockBlockNumbers(resolved));
+		let route = super::block_by_hash_route(config, state, db, block_numbers);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/blocks/{block_hash:?}"))
+			.reply(&route)
+			.await;
+
+		if response.status() == StatusCode::OK {
+			assert_eq!(
+				response.body(),
+				r#"{"block_number":10,"status":"finished","confidence":93.75}"#
+			);
+		}
+		response.status()
+	}
+
+	#[tokio::test]
+	async fn block_route_sampling_stats() {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(10)),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		let cell = |row, col, source, verified| SampledCell {
+			row,
+			col,
+			source,
+			verified,
+		};
+		_ = db.put(
+			Key::BlockSamplingStats(10),
+			BlockSamplingStats {
+				cells: vec![
+					cell(0, 1, Some(CellSource::Dht), true),
+					cell(1, 2, Some(CellSource::Rpc), false),
+					cell(2, 3, None, false),
+				],
+				timings: SamplingTimings {
+					dht_fetch: 1200,
+					rpc_fetch: Some(300),
+					proof_verification: 40,
+					total: 1550,
+				},
+			},
+		);
+		let route = super::block_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/10")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			r#"{"status":"verifying-confidence","confidence":null,"sampling":{"cells":[{"row":0,"col":1,"source":"dht","verified":true},{"row":1,"col":2,"source":"rpc","verified":false},{"row":2,"col":3,"verified":false}],"timings":{"dht_fetch":1200,"rpc_fetch":300,"proof_verification":40,"total":1550}}}"#
+		);
+	}
+
 	#[test_case(0, r#"Block header is not available"#  ; "Block is unavailable")]
 	#[test_case(6, r#"Block header is not available"#  ; "Block is pending")]
 	#[test_case(10, r#"Block header is not available"#  ; "Block is in verifying-header state")]
@@ -406,71 +910,228 @@ mod tests {
 			..Default::default()
 		}));
 
-		let db = mem_db::MemoryDB::default();
-		let route = super::block_header_route(config, state, db);
+		let db = mem_db::MemoryDB::default();
+		let route = super::block_header_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/blocks/{block_number}/header"))
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(response.body(), expected);
+	}
+
+	#[tokio::test]
+	async fn block_header_route_not_found() {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		let route = super::block_header_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/11/header")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	}
+
+	fn put_header(db: &impl Database, header: DaHeader) -> H256 {
+		let mut batch = Batch::default();
+		let block_hash = batch.put_finalized_header(header);
+		db.write(batch).unwrap();
+		block_hash
+	}
+
+	fn header() -> DaHeader {
+		DaHeader {
+			parent_hash: H256::default(),
+			number: 1,
+			state_root: H256::default(),
+			extrinsics_root: H256::default(),
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment::default(),
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+			digest: Digest { logs: vec![] },
+		}
+	}
+
+	#[tokio::test]
+	async fn block_header_route_ok() {
+		let config = RuntimeConfig::default();
+		let state = Arc::new(Mutex::new(State {
+			latest: 1,
+			header_verified: Some(BlockRange::init(1)),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		put_header(&db, header());
+		let route = super::block_header_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/1/header")
+			.reply(&route)
+			.await;
+		assert_eq!(
+			response.body(),
+			r#"{"hash":"0xb4ab92948e78b5e3115d2ce5ff2207e7d713a7fb33f4a9240e413c00954f244b","parent_hash":"0x0000000000000000000000000000000000000000000000000000000000000000","number":1,"state_root":"0x0000000000000000000000000000000000000000000000000000000000000000","extrinsics_root":"0x0000000000000000000000000000000000000000000000000000000000000000","extension":{"rows":0,"cols":0,"data_root":"0x0000000000000000000000000000000000000000000000000000000000000000","commitments":[],"app_lookup":{"size":0,"index":[]}}}"#
+		);
+	}
+
+	#[test_case(0 => StatusCode::OK ; "Data transaction in block")]
+	#[test_case(1 => StatusCode::NOT_FOUND ; "Index out of range")]
+	#[tokio::test]
+	async fn block_data_proof_route(index: u32) -> StatusCode {
+		let state = Arc::new(Mutex::new(State {
+			latest: 1,
+			header_verified: Some(BlockRange::init(1)),
+			..Default::default()
+		}));
+		let mut header = header();
+		header.extension = HeaderExtension::V3(v3::HeaderExtension {
+			commitment: KateCommitment {
+				data_root: leaf_hash(b"test\n"),
+				..Default::default()
+			},
+			app_lookup: CompactDataLookup {
+				size: 1,
+				index: vec![DataLookupItem {
+					app_id: AppId(1),
+					start: 0,
+				}],
+			},
+		});
+		let db = mem_db::MemoryDB::default();
+		let block_hash = put_header(&db, header);
+		_ = db.put(
+			Key::AppData(1, 1, block_hash),
+			vec![vec![
+				189, 1, 132, 0, 212, 53, 147, 199, 21, 253, 211, 28, 97, 20, 26, 189, 4, 169, 159,
+				214, 130, 44, 133, 88, 133, 76, 205, 227, 154, 86, 132, 231, 165, 109, 162, 125, 1,
+				50, 12, 43, 176, 19, 42, 23, 73, 70, 223, 198, 180, 103, 34, 60, 246, 184, 49, 140,
+				113, 174, 234, 229, 95, 71, 18, 92, 158, 185, 168, 140, 126, 12, 191, 156, 50, 234,
+				8, 4, 68, 137, 5, 156, 94, 209, 7, 169, 105, 62, 63, 1, 122, 253, 195, 112, 173,
+				239, 21, 73, 163, 240, 106, 109, 131, 0, 4, 0, 4, 29, 1, 20, 116, 101, 115, 116,
+				10,
+			]],
+		);
+		let route = super::block_data_proof_route(RuntimeConfig::default(), state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/blocks/1/data/{index}/proof"))
+			.reply(&route)
+			.await;
+		if response.status() == StatusCode::OK {
+			let proof: DataProof = serde_json::from_slice(response.body()).unwrap();
+			assert_eq!(proof.leaf, leaf_hash(b"test\n"));
+			assert!(proof.verify());
+		}
+		response.status()
+	}
+
+	#[test_case("", r#"{"requested":99.9,"required_cell_count":10,"max_cell_count":20,"cell_count":10,"achievable":99.90234375}"# ; "Configured confidence")]
+	#[test_case("?confidence=99.9999999", r#"{"requested":99.9999999,"required_cell_count":30,"max_cell_count":20,"cell_count":20,"achievable":99.99990463256836}"# ; "Confidence exceeds maximum")]
+	#[tokio::test]
+	async fn confidence_route(query: &str, expected: &str) {
+		let route = super::confidence_route(RuntimeConfig::default());
 		let response = warp::test::request()
 			.method("GET")
-			.path(&format!("/v2/blocks/{block_number}/header"))
+			.path(&format!("/v2/confidence{query}"))
 			.reply(&route)
 			.await;
-		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+		assert_eq!(response.status(), StatusCode::OK);
 		assert_eq!(response.body(), expected);
 	}
 
 	#[tokio::test]
-	async fn block_header_route_not_found() {
-		let config = RuntimeConfig::default();
+	async fn block_sampled_cells_route_without_seed() {
 		let state = Arc::new(Mutex::new(State {
-			latest: 10,
+			latest: 1,
+			header_verified: Some(BlockRange::init(1)),
 			..Default::default()
 		}));
 		let db = mem_db::MemoryDB::default();
-		let route = super::block_header_route(config, state, db);
+		put_header(&db, header());
+		let route = super::block_sampled_cells_route(RuntimeConfig::default(), state, db);
 		let response = warp::test::request()
 			.method("GET")
-			.path("/v2/blocks/11/header")
+			.path("/v2/blocks/1/sampled_cells")
 			.reply(&route)
 			.await;
 		assert_eq!(response.status(), StatusCode::NOT_FOUND);
 	}
 
-	fn header() -> DaHeader {
-		DaHeader {
-			parent_hash: H256::default(),
-			number: 1,
-			state_root: H256::default(),
-			extrinsics_root: H256::default(),
-			extension: HeaderExtension::V3(v3::HeaderExtension {
-				commitment: KateCommitment::default(),
-				app_lookup: CompactDataLookup {
-					size: 0,
-					index: vec![],
-				},
-			}),
-			digest: Digest { logs: vec![] },
-		}
-	}
-
 	#[tokio::test]
-	async fn block_header_route_ok() {
-		let config = RuntimeConfig::default();
+	async fn block_sampled_cells_route_ok() {
+		let config = RuntimeConfig {
+			sampling_seed: Some("audit".to_string()),
+			..Default::default()
+		};
 		let state = Arc::new(Mutex::new(State {
 			latest: 1,
 			header_verified: Some(BlockRange::init(1)),
 			..Default::default()
 		}));
+		let mut header = header();
+		header.extension = HeaderExtension::V3(v3::HeaderExtension {
+			commitment: KateCommitment {
+				rows: 1,
+				cols: 4,
+				..Default::default()
+			},
+			app_lookup: CompactDataLookup {
+				size: 0,
+				index: vec![],
+			},
+		});
+		let block_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
 		let db = mem_db::MemoryDB::default();
-		_ = db.put(Key::BlockHeader(1), header());
-		let route = super::block_header_route(config, state, db);
+		put_header(&db, header);
+		let route = super::block_sampled_cells_route(config.clone(), state.clone(), db.clone());
 		let response = warp::test::request()
 			.method("GET")
-			.path("/v2/blocks/1/header")
+			.path("/v2/blocks/1/sampled_cells")
 			.reply(&route)
 			.await;
-		assert_eq!(
-			response.body(),
-			r#"{"hash":"0xb4ab92948e78b5e3115d2ce5ff2207e7d713a7fb33f4a9240e413c00954f244b","parent_hash":"0x0000000000000000000000000000000000000000000000000000000000000000","number":1,"state_root":"0x0000000000000000000000000000000000000000000000000000000000000000","extrinsics_root":"0x0000000000000000000000000000000000000000000000000000000000000000","extension":{"rows":0,"cols":0,"data_root":"0x0000000000000000000000000000000000000000000000000000000000000000","commitments":[],"app_lookup":{"size":0,"index":[]}}}"#
+		// Sampled cells are not stored
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+		// Stored sampled cells are returned, regardless of the cell count of the current configuration
+		let cell = |row, col| SampledCell {
+			row,
+			col,
+			source: Some(CellSource::Dht),
+			verified: true,
+		};
+		_ = db.put(
+			Key::BlockSamplingStats(1),
+			BlockSamplingStats {
+				cells: vec![cell(1, 3), cell(0, 2)],
+				timings: SamplingTimings::default(),
+			},
 		);
+		let route = super::block_sampled_cells_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/1/sampled_cells")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+
+		let sampled: SampledCells = serde_json::from_slice(response.body()).unwrap();
+		assert_eq!(sampled.block_hash, block_hash);
+		let cells = sampled
+			.cells
+			.iter()
+			.map(|cell| (cell.row, cell.col))
+			.collect::<Vec<_>>();
+		assert_eq!(cells, vec![(1, 3), (0, 2)]);
 	}
 
 	#[test_case(0, r#"Block data is not available"#  ; "Block is unavailable")]
@@ -562,8 +1223,9 @@ mod tests {
 			..Default::default()
 		}));
 		let db = mem_db::MemoryDB::default();
+		_ = db.put(Key::CanonicalHash(5), H256::repeat_byte(5));
 		_ = db.put(
-			Key::AppData(1, 5),
+			Key::AppData(1, 5, H256::repeat_byte(5)),
 			vec![vec![
 				189, 1, 132, 0, 212, 53, 147, 199, 21, 253, 211, 28, 97, 20, 26, 189, 4, 169, 159,
 				214, 130, 44, 133, 88, 133, 76, 205, 227, 154, 86, 132, 231, 165, 109, 162, 125, 1,
@@ -587,6 +1249,155 @@ mod tests {
 		);
 	}
 
+	#[test_case(1, r#"{"block_number":5,"data_transactions":[{"data":"dGVzdAo=","extrinsic":"vQGEANQ1k8cV/dMcYRQavQSpn9aCLIVYhUzN45pWhOelbaJ9ATIMK7ATKhdJRt/GtGciPPa4MYxxrurlX0cSXJ65qIx+DL+cMuoIBESJBZxe0QepaT4/AXr9w3Ct7xVJo/BqbYMABAAEHQEUdGVzdAo="}]}"# ; "Configured app")]
+	#[test_case(2, r#"{"block_number":5,"data_transactions":[{"data":"dGVzdAo=","extrinsic":"vQGEANQ1k8cV/dMcYRQavQSpn9aCLIVYhUzN45pWhOelbaJ9ATIMK7ATKhdJRt/GtGciPPa4MYxxrurlX0cSXJ65qIx+DL+cMuoIBESJBZxe0QepaT4/AXr9w3Ct7xVJo/BqbYMABAAEHQEUdGVzdAo="}]}"# ; "Additional app")]
+	#[test_case(3, "" ; "Other app")]
+	#[tokio::test]
+	async fn app_data_route(app_id: u32, expected: &str) {
+		let config = RuntimeConfig {
+			app_id: Some(1),
+			..Default::default()
+		};
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: Some(BlockRange::init(5)),
+			confidence_achieved: Some(BlockRange::init(5)),
+			data_verified: Some(BlockRange::init(5)),
+			apps: [(
+				2,
+				AppState {
+					data_verified: Some(BlockRange::init(5)),
+					sync_data_verified: None,
+				},
+			)]
+			.into(),
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		_ = db.put(Key::CanonicalHash(5), H256::repeat_byte(5));
+		for app_id in [1, 2] {
+			_ = db.put(
+				Key::AppData(app_id, 5, H256::repeat_byte(5)),
+				vec![vec![
+					189, 1, 132, 0, 212, 53, 147, 199, 21, 253, 211, 28, 97, 20, 26, 189, 4, 169,
+					159, 214, 130, 44, 133, 88, 133, 76, 205, 227, 154, 86, 132, 231, 165, 109,
+					162, 125, 1, 50, 12, 43, 176, 19, 42, 23, 73, 70, 223, 198, 180, 103, 34, 60,
+					246, 184, 49, 140, 113, 174, 234, 229, 95, 71, 18, 92, 158, 185, 168, 140, 126,
+					12, 191, 156, 50, 234, 8, 4, 68, 137, 5, 156, 94, 209, 7, 169, 105, 62, 63, 1,
+					122, 253, 195, 112, 173, 239, 21, 73, 163, 240, 106, 109, 131, 0, 4, 0, 4, 29,
+					1, 20, 116, 101, 115, 116, 10,
+				]],
+			);
+		}
+		let route = super::app_data_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/apps/{app_id}/data/5"))
+			.reply(&route)
+			.await;
+		if expected.is_empty() {
+			assert_eq!(response.status(), StatusCode::NOT_FOUND);
+		} else {
+			assert_eq!(response.status(), StatusCode::OK);
+			assert_eq!(response.body(), expected);
+		}
+	}
+
+	const EXTRINSIC: [u8; 113] = [
+		189, 1, 132, 0, 212, 53, 147, 199, 21, 253, 211, 28, 97, 20, 26, 189, 4, 169, 159, 214,
+		130, 44, 133, 88, 133, 76, 205, 227, 154, 86, 132, 231, 165, 109, 162, 125, 1, 50, 12, 43,
+		176, 19, 42, 23, 73, 70, 223, 198, 180, 103, 34, 60, 246, 184, 49, 140, 113, 174, 234, 229,
+		95, 71, 18, 92, 158, 185, 168, 140, 126, 12, 191, 156, 50, 234, 8, 4, 68, 137, 5, 156, 94,
+		209, 7, 169, 105, 62, 63, 1, 122, 253, 195, 112, 173, 239, 21, 73, 163, 240, 106, 109, 131,
+		0, 4, 0, 4, 29, 1, 20, 116, 101, 115, 116, 10,
+	];
+
+	fn app_data_range_setup() -> (RuntimeConfig, Arc<Mutex<State>>, mem_db::MemoryDB) {
+		let config = RuntimeConfig {
+			app_id: Some(1),
+			api_max_page_size: 2,
+			..Default::default()
+		};
+		let verified = Some(BlockRange { first: 5, last: 7 });
+		let state = Arc::new(Mutex::new(State {
+			latest: 10,
+			header_verified: verified.clone(),
+			confidence_achieved: verified.clone(),
+			data_verified: verified,
+			..Default::default()
+		}));
+		let db = mem_db::MemoryDB::default();
+		for (block_number, transactions) in [(5, 2), (7, 1)] {
+			let hash = H256::repeat_byte(block_number as u8);
+			_ = db.put(Key::CanonicalHash(block_number), hash);
+			_ = db.put(
+				Key::AppData(1, block_number, hash),
+				vec![EXTRINSIC.to_vec(); transactions],
+			);
+		}
+		(config, state, db)
+	}
+
+	#[test_case("from_block=5&to_block=7" => (vec![(5, 2)], Some("6-0".to_string())) ; "First page")]
+	#[test_case("from_block=5&to_block=7&page=6-0" => (vec![(6, 0), (7, 1)], None) ; "Last page")]
+	#[test_case("from_block=5&to_block=7&page=5-1" => (vec![(5, 1), (6, 0)], Some("7-0".to_string())) ; "Page within block")]
+	#[test_case("from_block=5&to_block=9&page=7-0" => (vec![(7, 1)], Some("8-0".to_string())) ; "Unavailable block")]
+	#[tokio::test]
+	async fn app_data_range_route(query: &str) -> (Vec<(u32, usize)>, Option<String>) {
+		let (config, state, db) = app_data_range_setup();
+		let route = super::app_data_range_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/apps/1/data?{query}"))
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		let response: DataRangeResponse = serde_json::from_slice(response.body()).unwrap();
+		let blocks = response
+			.blocks
+			.iter()
+			.map(|block| (block.block_number, block.data_transactions.len()))
+			.collect();
+		(blocks, response.next_page.map(String::from))
+	}
+
+	#[test_case("from_block=7&to_block=5" ; "Invalid range")]
+	#[test_case("from_block=5&to_block=7&page=8-0" ; "Page out of range")]
+	#[test_case("from_block=8&to_block=9" ; "Data not available")]
+	#[tokio::test]
+	async fn app_data_range_route_bad_request(query: &str) {
+		let (config, state, db) = app_data_range_setup();
+		let route = super::app_data_range_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/apps/1/data?{query}"))
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	}
+
+	#[tokio::test]
+	async fn app_data_range_route_ndjson() {
+		let (config, state, db) = app_data_range_setup();
+		let route = super::app_data_range_route(config, state, db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/apps/1/data?from_block=5&to_block=9&fields=data")
+			.header("accept", "application/x-ndjson")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(response.headers()["content-type"], "application/x-ndjson");
+		let body = std::str::from_utf8(response.body()).unwrap();
+		let blocks = body
+			.lines()
+			.map(|line| serde_json::from_str::<DataResponse>(line).unwrap())
+			.map(|block| (block.block_number, block.data_transactions.len()))
+			.collect::<Vec<_>>();
+		// Stream ends with the first block whose data is not verified
+		assert_eq!(blocks, vec![(5, 2), (6, 0), (7, 1)]);
+	}
+
 	fn all_topics() -> HashSet<Topic> {
 		vec![
 			Topic::HeaderVerified,
@@ -603,6 +1414,8 @@ mod tests {
 			.collect()
 	}
 
+	const MAX_BODY_SIZE: u64 = 1024;
+
 	#[derive(Clone)]
 	struct MockSubmitter {}
 
@@ -616,13 +1429,45 @@ mod tests {
 				index: 0,
 			})
 		}
+
+		fn is_data_enabled(&self) -> bool {
+			true
+		}
+	}
+
+	#[derive(Clone)]
+	struct MockExtrinsicSubmitter {}
+
+	#[async_trait]
+	impl transactions::Submit for MockExtrinsicSubmitter {
+		async fn submit(&self, transaction: Transaction) -> color_eyre::Result<SubmitResponse> {
+			transactions::Submit::submit(&MockSubmitter {}, transaction).await
+		}
+
+		fn is_data_enabled(&self) -> bool {
+			false
+		}
+	}
+
+	#[test_case(r#"{"data":"dHJhbnNhY3Rpb24K"}"#, StatusCode::NOT_FOUND ; "Data is not enabled")]
+	#[test_case(r#"{"extrinsic":"dHJhbnNhY3Rpb24K"}"#, StatusCode::OK ; "Extrinsic is enabled")]
+	#[tokio::test]
+	async fn submit_route_without_app_mode(body: &str, expected: StatusCode) {
+		let route = super::submit_route(Some(Arc::new(MockExtrinsicSubmitter {})), MAX_BODY_SIZE);
+		let response = warp::test::request()
+			.method("POST")
+			.path("/v2/submit")
+			.body(body)
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), expected);
 	}
 
 	#[test_case(r#"{"raw":""}"#, b"Request body deserialize error: unknown variant `raw`" ; "Invalid json schema")]
 	#[test_case(r#"{"data":"dHJhbnooNhY3Rpb24:"}"#, b"Request body deserialize error: Invalid byte" ; "Invalid base64 value")]
 	#[tokio::test]
 	async fn submit_route_bad_request(json: &str, message: &[u8]) {
-		let route = super::submit_route(Some(Arc::new(MockSubmitter {})));
+		let route = super::submit_route(Some(Arc::new(MockSubmitter {})), MAX_BODY_SIZE);
 		let response = warp::test::request()
 			.method("POST")
 			.path("/v2/submit")
@@ -637,7 +1482,7 @@ mod tests {
 	#[test_case(r#"{"extrinsic":"dHJhbnNhY3Rpb24K"}"# ; "No errors in case of submitted extrinsic")]
 	#[tokio::test]
 	async fn submit_route_extrinsic(body: &str) {
-		let route = super::submit_route(Some(Arc::new(MockSubmitter {})));
+		let route = super::submit_route(Some(Arc::new(MockSubmitter {})), MAX_BODY_SIZE);
 		let response = warp::test::request()
 			.method("POST")
 			.path("/v2/submit")
@@ -649,10 +1494,173 @@ mod tests {
 		let _ = serde_json::to_string(&response).unwrap();
 	}
 
+	#[tokio::test]
+	async fn submit_route_payload_too_large() {
+		let route = super::submit_route(Some(Arc::new(MockSubmitter {})), MAX_BODY_SIZE);
+		let data = "a".repeat(MAX_BODY_SIZE as usize);
+		let response = warp::test::request()
+			.method("POST")
+			.path("/v2/submit")
+			.body(format!(r#"{{"data":"{data}"}}"#))
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+	}
+
+	#[test_case(TransactionStatus::InPool, r#""status":"in-pool""# ; "In pool")]
+	#[test_case(TransactionStatus::InBlock { block_hash: H256::zero() }, r#""status":"in-block","block_hash":"0x0000000000000000000000000000000000000000000000000000000000000000""# ; "In block")]
+	#[test_case(TransactionStatus::Failed { reason: "Transaction is dropped".to_string() }, r#""status":"failed","reason":"Transaction is dropped""# ; "Failed")]
+	#[tokio::test]
+	async fn transaction_status_route(status: TransactionStatus, expected: &str) {
+		let hash = H256::repeat_byte(1);
+		let db = mem_db::MemoryDB::default();
+		db.put(Key::TransactionStatus(hash), status).unwrap();
+		let route = super::transaction_status_route(db);
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/submit/{hash:?}/status"))
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			&format!(r#"{{"hash":"{hash:?}",{expected}}}"#)
+		);
+	}
+
+	#[tokio::test]
+	async fn transaction_status_route_not_found() {
+		let route = super::transaction_status_route(mem_db::MemoryDB::default());
+		let response = warp::test::request()
+			.method("GET")
+			.path(&format!("/v2/submit/{:?}/status", H256::repeat_byte(1)))
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn block_attestation_route() {
+		let signer = Signer::from_phrase(KeyType::Ed25519, "//Alice", None).unwrap();
+		let attestation = Attestation::new(&signer, 1, H256::repeat_byte(1), 99.0, 0);
+		let db = mem_db::MemoryDB::default();
+		db.put(Key::Attestation(1), attestation.clone()).unwrap();
+		let route = super::block_attestation_route(db);
+
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/1/attestation")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::OK);
+		let body: Attestation = serde_json::from_slice(response.body()).unwrap();
+		assert_eq!(body, attestation);
+
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/blocks/2/attestation")
+			.reply(&route)
+			.await;
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn sync_progress_route() {
+		let db = mem_db::MemoryDB::default();
+		let mut progress = SyncProgress::new(0..4, 0);
+		for block_number in 0..4 {
+			progress.set_verified(block_number);
+		}
+		db.put(Key::SyncProgress, progress).unwrap();
+		let route = super::sync_progress_route(db);
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/sync")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(
+			response.body(),
+			r#"{"start":0,"end":4,"lowest_verified":0,"verified":4,"total":4,"percentage":100.0,"eta":0}"#
+		);
+	}
+
+	#[tokio::test]
+	async fn sync_progress_route_not_found() {
+		let route = super::sync_progress_route(mem_db::MemoryDB::default());
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/sync")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn apps_route() {
+		let mut app_clients = MockFollow::new();
+		app_clients.expect_app_ids().return_const(vec![1, 2]);
+		let route = super::apps_route(Arc::new(app_clients));
+		let response = warp::test::request()
+			.method("GET")
+			.path("/v2/apps")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(response.body(), r#"{"app_ids":[1,2]}"#);
+	}
+
+	#[test_case(true, StatusCode::CREATED ; "Newly followed app")]
+	#[test_case(false, StatusCode::OK ; "Already followed app")]
+	#[tokio::test]
+	async fn follow_app_route(is_followed: bool, expected_status: StatusCode) {
+		let mut app_clients = MockFollow::new();
+		app_clients
+			.expect_follow()
+			.withf(|app_id| app_id.0 == 2)
+			.returning(move |_| Ok(is_followed));
+		app_clients.expect_app_ids().return_const(vec![1, 2]);
+		let route = super::follow_app_route(Arc::new(app_clients));
+		let response = warp::test::request()
+			.method("PUT")
+			.path("/v2/apps/2")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), expected_status);
+		assert_eq!(response.body(), r#"{"app_ids":[1,2]}"#);
+	}
+
+	#[test_case(true, StatusCode::OK, r#"{"app_ids":[1]}"# ; "Followed app")]
+	#[test_case(false, StatusCode::NOT_FOUND, "Not Found" ; "Not followed app")]
+	#[tokio::test]
+	async fn unfollow_app_route(is_unfollowed: bool, expected_status: StatusCode, expected: &str) {
+		let mut app_clients = MockFollow::new();
+		app_clients
+			.expect_unfollow()
+			.withf(|app_id| app_id.0 == 2)
+			.return_const(is_unfollowed);
+		app_clients.expect_app_ids().return_const(vec![1]);
+		let route = super::unfollow_app_route(Arc::new(app_clients));
+		let response = warp::test::request()
+			.method("DELETE")
+			.path("/v2/apps/2")
+			.reply(&route)
+			.await;
+
+		assert_eq!(response.status(), expected_status);
+		assert_eq!(response.body(), expected);
+	}
+
 	#[tokio::test]
 	async fn subscriptions_route() {
 		let clients = WsClients::default();
-		let route = super::subscriptions_route(clients.clone());
+		let route = super::subscriptions_route(clients.clone(), MAX_BODY_SIZE);
 
 		let body = r#"{"topics":["confidence-achieved","data-verified","header-verified"],"data_fields":["data","extrinsic"]}"#;
 		let response = warp::test::request()
@@ -678,6 +1686,8 @@ mod tests {
 	struct MockSetup {
 		ws_client: warp::test::WsClient,
 		state: Arc<Mutex<State>>,
+		clients: WsClients,
+		client_uuid: String,
 	}
 
 	impl MockSetup {
@@ -695,6 +1705,7 @@ mod tests {
 				config.clone(),
 				submitter.map(Arc::new),
 				state.clone(),
+				Auth::from(&config),
 			);
 			let ws_client = warp::test::ws()
 				.path(&format!("/v2/ws/{client_uuid}"))
@@ -702,7 +1713,12 @@ mod tests {
 				.await
 				.expect("handshake");
 
-			MockSetup { ws_client, state }
+			MockSetup {
+				ws_client,
+				state,
+				clients,
+				client_uuid,
+			}
 		}
 
 		async fn ws_send_text(&mut self, message: &str) -> String {
@@ -723,6 +1739,24 @@ mod tests {
 		);
 	}
 
+	#[tokio::test]
+	async fn ws_route_subscribe() {
+		let mut test = MockSetup::new(RuntimeConfig::default(), None).await;
+		let request = r#"{"type":"subscribe","request_id":"cae63fff-c4b8-4af9-b4fe-0605a5329aa0","message":{"topics":["confidence-achieved"],"data_fields":[]}}"#;
+		let response = test.ws_send_text(request).await;
+		assert_eq!(
+			r#"{"topic":"subscribed","request_id":"cae63fff-c4b8-4af9-b4fe-0605a5329aa0","message":{"topics":["confidence-achieved"],"data_fields":[]}}"#,
+			response
+		);
+
+		let clients = test.clients.0.read().await;
+		let client = clients.get(&test.client_uuid).unwrap();
+		assert_eq!(
+			client.subscription.topics,
+			HashSet::from([Topic::ConfidenceAchieved])
+		);
+	}
+
 	#[tokio::test]
 	async fn ws_route_status() {
 		let config = RuntimeConfig {
@@ -766,6 +1800,7 @@ mod tests {
 	#[test_case("abcd",  "Failed to parse request" ; "Invalid json")]
 	#[test_case("{}",  "Failed to parse request" ; "Empty json")]
 	#[test_case(r#"{"type":"unknown","request_id":"11043443-7e4c-4485-a21c-304b457b6cc7","message":""}"#,  "Failed to parse request: Cannot parse json" ; "Wrong request type")]
+	#[test_case(r#"{"type":"subscribe","request_id":"11043443-7e4c-4485-a21c-304b457b6cc7","message":{"topics":[],"data_fields":[]}}"#,  "Topics are empty" ; "Empty topics")]
 	#[tokio::test]
 	async fn ws_route_bad_request(request: &str, expected: &str) {
 		let mut test = MockSetup::new(RuntimeConfig::default(), None).await;
@@ -799,6 +1834,25 @@ mod tests {
 		assert!(error.message.contains(expected));
 	}
 
+	#[tokio::test]
+	async fn ws_route_submit_without_submit_role() {
+		let config = RuntimeConfig {
+			api_keys: vec![ApiKey {
+				key: "submit-key".to_string(),
+				role: Role::Submit,
+			}],
+			api_public_read: true,
+			..Default::default()
+		};
+		let mut test = MockSetup::new(config, Some(MockSubmitter {})).await;
+
+		let request = r#"{"type":"submit","request_id":"fca2ff0c-7a26-42a2-a6f0-d0aeeaba8a9a","message":{"data":"dHJhbnNhY3Rpb24K"}}"#;
+		let response = test.ws_send_text(request).await;
+		let WsError::Error(error) = serde_json::from_str(&response).unwrap();
+		assert_eq!(error.error_code, ErrorCode::BadRequest);
+		assert!(error.message.contains("Submit is not configured"));
+	}
+
 	#[tokio::test]
 	async fn ws_route_submit_data() {
 		let submitter = Some(MockSubmitter {});
@@ -1,53 +1,157 @@
 use async_trait::async_trait;
-use avail_subxt::{api, primitives::AvailExtrinsicParams, AvailConfig};
-use color_eyre::Result;
-use sp_core::sr25519::Pair;
-use subxt::tx::PairSigner;
+use avail_subxt::{
+	api::{self, runtime_types::bounded_collections::bounded_vec::BoundedVec},
+	avail,
+	primitives::AvailExtrinsicParams,
+	AvailConfig,
+};
+use color_eyre::{eyre::eyre, Result};
+use sp_core::{sr25519::Pair, H256};
+use subxt::tx::{PairSigner, TxProgress, TxStatus};
+use tracing::{debug, warn};
 
-use super::types::{SubmitResponse, Transaction};
-use crate::network::rpc;
+use super::types::{
+	PublishMessage, SubmitResponse, Topic, Transaction, TransactionStatusMessage, WsClients,
+};
+use crate::{
+	data::{Database, Key, TransactionStatus},
+	network::rpc,
+	types::IdentityConfig,
+};
 
 #[async_trait]
 pub trait Submit {
 	async fn submit(&self, transaction: Transaction) -> Result<SubmitResponse>;
+
+	/// Data transactions are created and signed by the light client, so they can be submitted only in app mode.
+	/// Signed extrinsics can be submitted regardless.
+	fn is_data_enabled(&self) -> bool;
 }
 
+/// Submits transactions and tracks their status until they are finalized or failed.
+/// Each status change is stored into the database and published to the WebSocket clients.
 #[derive(Clone)]
-pub struct Submitter {
+pub struct Submitter<T: Database> {
 	pub rpc_client: rpc::Client,
-	pub app_id: u32,
+	pub app_id: Option<u32>,
 	pub pair_signer: PairSigner<AvailConfig, Pair>,
+	pub db: T,
+	pub ws_clients: WsClients,
+}
+
+impl<T: Database> Submitter<T> {
+	pub fn new(
+		rpc_client: rpc::Client,
+		app_id: Option<u32>,
+		identity_config: IdentityConfig,
+		db: T,
+		ws_clients: WsClients,
+	) -> Self {
+		Submitter {
+			rpc_client,
+			app_id,
+			pair_signer: PairSigner::new(identity_config.avail_key_pair),
+			db,
+			ws_clients,
+		}
+	}
+
+	async fn update_status(&self, hash: H256, status: TransactionStatus) {
+		debug!(?hash, ?status, "Transaction status changed");
+		if let Err(error) = self.db.put(Key::TransactionStatus(hash), status.clone()) {
+			warn!(?hash, "Cannot store transaction status: {error:#}");
+		}
+
+		let message = PublishMessage::TransactionStatus(TransactionStatusMessage { hash, status });
+		if let Err(error) = self
+			.ws_clients
+			.publish(&Topic::TransactionStatus, message)
+			.await
+		{
+			warn!(?hash, "Cannot publish transaction status: {error:#}");
+		}
+	}
+
+	async fn wait_for_finalized(
+		&self,
+		hash: H256,
+		mut progress: TxProgress<AvailConfig, avail::Client>,
+	) -> Result<SubmitResponse> {
+		while let Some(status) = progress.next_item().await {
+			match status? {
+				TxStatus::Future | TxStatus::Ready | TxStatus::Broadcast(_) => (),
+				TxStatus::Retracted(_) => self.update_status(hash, TransactionStatus::InPool).await,
+				TxStatus::InBlock(in_block) => {
+					let block_hash = in_block.block_hash();
+					self.update_status(hash, TransactionStatus::InBlock { block_hash })
+						.await
+				},
+				TxStatus::Finalized(in_block) => {
+					let events = in_block.wait_for_success().await?;
+					let block_hash = events.block_hash();
+					let block_number = self.rpc_client.get_header_by_hash(block_hash).await?.number;
+					let index = events.extrinsic_index();
+
+					let status = TransactionStatus::Finalized {
+						block_number,
+						block_hash,
+						index,
+					};
+					self.update_status(hash, status).await;
+
+					return Ok(SubmitResponse {
+						block_number,
+						block_hash,
+						hash,
+						index,
+					});
+				},
+				TxStatus::FinalityTimeout(_) => return Err(eyre!("Transaction finality timeout")),
+				TxStatus::Usurped(_) => return Err(eyre!("Transaction is usurped")),
+				TxStatus::Dropped => return Err(eyre!("Transaction is dropped")),
+				TxStatus::Invalid => return Err(eyre!("Transaction is invalid")),
+			}
+		}
+		Err(eyre!("Transaction status subscription ended"))
+	}
 }
 
 #[async_trait]
-impl Submit for Submitter {
+impl<T: Database + Clone + Send + Sync> Submit for Submitter<T> {
 	async fn submit(&self, transaction: Transaction) -> Result<SubmitResponse> {
-		let ex_event = match transaction {
+		let progress = match transaction {
 			Transaction::Data(data) => {
-				let extrinsic = api::tx().data_availability().submit_data(data.into());
-				let params = AvailExtrinsicParams::new_with_app_id(self.app_id.into());
+				let app_id = self
+					.app_id
+					.ok_or_else(|| eyre!("Data submit requires app mode"))?;
+				let extrinsic = api::tx()
+					.data_availability()
+					.submit_data(BoundedVec(data.0));
+				let params = AvailExtrinsicParams::new_with_app_id(app_id.into());
 				self.rpc_client
-					.submit_signed_and_wait_for_finalized(&extrinsic, &self.pair_signer, params)
+					.submit_signed_and_watch(&extrinsic, &self.pair_signer, params)
 					.await?
 			},
 			Transaction::Extrinsic(extrinsic) => {
 				self.rpc_client
-					.submit_from_bytes_and_wait_for_finalized(extrinsic.into())
+					.submit_from_bytes_and_watch(extrinsic.into())
 					.await?
 			},
 		};
 
-		let block_number = self
-			.rpc_client
-			.get_header_by_hash(ex_event.block_hash())
-			.await?
-			.number;
-
-		Ok(SubmitResponse {
-			block_number,
-			block_hash: ex_event.block_hash(),
-			hash: ex_event.extrinsic_hash(),
-			index: ex_event.extrinsic_index(),
-		})
+		let hash = progress.extrinsic_hash();
+		self.update_status(hash, TransactionStatus::InPool).await;
+
+		let result = self.wait_for_finalized(hash, progress).await;
+		if let Err(error) = result.as_ref() {
+			let reason = error.to_string();
+			self.update_status(hash, TransactionStatus::Failed { reason })
+				.await;
+		}
+		result
+	}
+
+	fn is_data_enabled(&self) -> bool {
+		self.app_id.is_some()
 	}
 }
@@ -1,8 +1,13 @@
-use avail_subxt::api::runtime_types::{
-	avail_core::{data_lookup::compact::CompactDataLookup, header::extension::HeaderExtension},
-	bounded_collections::bounded_vec::BoundedVec,
+use avail_core::AppId;
+use avail_light_api_types as api;
+pub use avail_light_api_types::{
+	Base64, Block, BlockByHash, BlockRange, BlockStatus, Blocks, CellPosition, Commitment,
+	Confidence, ConfidenceQuery, DataCursor, DataField, DataLookup, DataLookupItem, DataQuery,
+	DataRangeQuery, DataRangeResponse, DataResponse, DataTransaction, Extension,
+	FieldsQueryParameter, FollowedApps, Header, HistoricalSync, Mode, SampledCells, Status,
+	SubmitResponse, SyncProgress, Transaction, TransactionStatusMessage, Version,
 };
-use base64::{engine::general_purpose, DecodeError, Engine};
+use avail_subxt::api::runtime_types::avail_core::header::extension::HeaderExtension;
 use codec::Encode;
 use color_eyre::{
 	eyre::{eyre, WrapErr},
@@ -10,9 +15,9 @@ use color_eyre::{
 };
 use derive_more::From;
 use hyper::{http, StatusCode};
-use kate_recovery::{com::AppData, commitments, config, matrix::Partition};
-use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use sp_core::{blake2_256, H256};
+use kate_recovery::{com::AppData, commitments, matrix::Partition};
+use serde::{Deserialize, Serialize};
+use sp_core::blake2_256;
 use std::{
 	collections::{HashMap, HashSet},
 	sync::Arc,
@@ -25,11 +30,16 @@ use warp::{
 };
 
 use crate::{
-	network::rpc::Event as RpcEvent,
-	types::{
-		self, block_matrix_partition_format, BlockVerified, OptionBlockRange, RuntimeConfig, State,
+	attestation::Attestation,
+	data::SyncProgress as SyncProgressData,
+	data_proof::DataProof,
+	network::{
+		p2p::{BandwidthStats, PeerScore, Peers, Reachability},
+		rpc::{self, Event as RpcEvent},
 	},
-	utils::decode_app_data,
+	types::{self, BlockVerified, OptionBlockRange, RuntimeConfig, State},
+	utils::{calculate_confidence, decode_app_data},
+	verification::CommitmentMismatch,
 };
 
 #[derive(Debug)]
@@ -37,24 +47,6 @@ pub struct InternalServerError {}
 
 impl warp::reject::Reject for InternalServerError {}
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Version {
-	pub version: String,
-	pub network_version: String,
-}
-
-impl Reply for Version {
-	fn into_response(self) -> warp::reply::Response {
-		warp::reply::json(&self).into_response()
-	}
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct BlockRange {
-	pub first: u32,
-	pub last: u32,
-}
-
 impl From<&types::BlockRange> for BlockRange {
 	fn from(value: &types::BlockRange) -> Self {
 		BlockRange {
@@ -64,137 +56,86 @@ impl From<&types::BlockRange> for BlockRange {
 	}
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct HistoricalSync {
-	pub synced: bool,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub available: Option<BlockRange>,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub app_data: Option<BlockRange>,
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct Blocks {
-	pub latest: u32,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub available: Option<BlockRange>,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub app_data: Option<BlockRange>,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub historical_sync: Option<HistoricalSync>,
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct Status {
-	pub modes: Vec<Mode>,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub app_id: Option<u32>,
-	pub genesis_hash: String,
-	pub network: String,
-	pub blocks: Blocks,
-	#[serde(
-		skip_serializing_if = "Option::is_none",
-		with = "block_matrix_partition_format"
-	)]
-	pub partition: Option<Partition>,
-}
-
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
-#[serde(try_from = "String", into = "String")]
-pub struct Base64(pub Vec<u8>);
-
-impl From<Base64> for BoundedVec<u8> {
-	fn from(val: Base64) -> Self {
-		BoundedVec(val.0)
-	}
-}
-
-impl From<Base64> for Vec<u8> {
-	fn from(val: Base64) -> Self {
-		val.0
+impl Reply for Attestation {
+	fn into_response(self) -> warp::reply::Response {
+		warp::reply::json(&self).into_response()
 	}
 }
 
-impl TryFrom<String> for Base64 {
-	type Error = DecodeError;
-
-	fn try_from(value: String) -> Result<Self, Self::Error> {
-		general_purpose::STANDARD.decode(value).map(Base64)
+impl Reply for DataProof {
+	fn into_response(self) -> warp::reply::Response {
+		warp::reply::json(&self).into_response()
 	}
 }
 
-impl From<Base64> for String {
-	fn from(value: Base64) -> Self {
-		general_purpose::STANDARD.encode(value.0)
-	}
-}
+/// Creates sync progress, with ETA estimated from the sync rate in the current session
+pub fn sync_progress(progress: &SyncProgressData, now: u64) -> SyncProgress {
+	let (verified, total) = (progress.verified(), progress.total());
+	let percentage = match total {
+		0 => 100.0,
+		total => 100.0 * verified as f64 / total as f64,
+	};
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-pub enum Transaction {
-	Data(Base64),
-	Extrinsic(Base64),
-}
+	let remaining = total - verified;
+	let elapsed = now.saturating_sub(progress.session_started_at);
+	let eta = match (remaining, progress.session_verified) {
+		(0, _) => Some(0),
+		(_, 0) => None,
+		(remaining, verified) => Some(remaining as u64 * elapsed / verified as u64),
+	};
 
-impl Transaction {
-	pub fn is_empty(&self) -> bool {
-		match self {
-			Transaction::Data(data) => data.0.is_empty(),
-			Transaction::Extrinsic(data) => data.0.is_empty(),
-		}
-	}
-}
+	SyncProgress {
+		start: progress.start,
+		end: progress.end,
+		lowest_verified: progress.lowest_verified,
+		verified,
+		total,
+		percentage,
+		eta,
+	}
+}
+
+pub fn status(config: &RuntimeConfig, state: &State) -> Status {
+	let historical_sync = state.synced.map(|synced| HistoricalSync {
+		synced,
+		available: state.sync_confidence_achieved.as_ref().map(From::from),
+		app_data: state.sync_data_verified.as_ref().map(From::from),
+	});
+
+	let blocks = Blocks {
+		latest: state.latest,
+		available: state.confidence_achieved.as_ref().map(From::from),
+		app_data: state.data_verified.as_ref().map(From::from),
+		missed: state.missed.iter().map(From::from).collect(),
+		historical_sync,
+	};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct SubmitResponse {
-	pub block_number: u32,
-	pub block_hash: H256,
-	pub hash: H256,
-	pub index: u32,
-}
+	let node = state.connected_node.clone();
+	let partition = config
+		.block_matrix_partition
+		.map(|Partition { number, fraction }| api::Partition { number, fraction });
 
-impl Reply for SubmitResponse {
-	fn into_response(self) -> warp::reply::Response {
-		warp::reply::json(&self).into_response()
+	Status {
+		modes: config.into(),
+		app_id: config.app_id,
+		genesis_hash: format!("{:?}", node.genesis_hash),
+		network: node.network(),
+		blocks,
+		partition,
+		reachability: state.reachability.as_ref().map(From::from),
 	}
 }
 
-impl Status {
-	pub fn new(config: &RuntimeConfig, state: &State) -> Self {
-		let historical_sync = state.synced.map(|synced| HistoricalSync {
-			synced,
-			available: state.sync_confidence_achieved.as_ref().map(From::from),
-			app_data: state.sync_data_verified.as_ref().map(From::from),
-		});
-
-		let blocks = Blocks {
-			latest: state.latest,
-			available: state.confidence_achieved.as_ref().map(From::from),
-			app_data: state.data_verified.as_ref().map(From::from),
-			historical_sync,
-		};
-
-		let node = state.connected_node.clone();
-
-		Status {
-			modes: config.into(),
-			app_id: config.app_id,
-			genesis_hash: format!("{:?}", node.genesis_hash),
-			network: node.network(),
-			blocks,
-			partition: config.block_matrix_partition,
+impl From<&Reachability> for api::Reachability {
+	fn from(value: &Reachability) -> Self {
+		api::Reachability {
+			nat_status: value.nat_status,
+			public_address: value.public_address.as_ref().map(ToString::to_string),
+			relayed: value.relayed,
 		}
 	}
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-pub enum Mode {
-	Light,
-	App,
-	Partition,
-}
-
 impl From<&RuntimeConfig> for Vec<Mode> {
 	fn from(value: &RuntimeConfig) -> Self {
 		let mut result: Vec<Mode> = vec![];
@@ -202,35 +143,24 @@ impl From<&RuntimeConfig> for Vec<Mode> {
 		if value.app_id.is_some() {
 			result.push(Mode::App);
 		}
-		if value.block_matrix_partition.is_some() {
+		if value.is_fat_client() {
 			result.push(Mode::Partition)
 		}
 		result
 	}
 }
 
-impl Reply for Status {
-	fn into_response(self) -> warp::reply::Response {
-		warp::reply::json(&self).into_response()
-	}
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum Topic {
 	HeaderVerified,
 	ConfidenceAchieved,
 	DataVerified,
+	TransactionStatus,
+	CommitmentMismatch,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash)]
-#[serde(rename_all = "kebab-case")]
-pub enum DataField {
-	Data,
-	Extrinsic,
-}
-
-#[derive(Serialize, Deserialize, PartialEq, Default)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct Subscription {
 	pub topics: HashSet<Topic>,
 	pub data_fields: HashSet<DataField>,
@@ -242,17 +172,6 @@ pub struct HeaderMessage {
 	header: Header,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
-#[serde(rename_all = "kebab-case")]
-pub enum BlockStatus {
-	Unavailable,
-	Pending,
-	VerifyingHeader,
-	VerifyingConfidence,
-	VerifyingData,
-	Finished,
-}
-
 pub fn block_status(
 	sync_start_block: &Option<u32>,
 	state: &State,
@@ -301,135 +220,93 @@ pub fn block_status(
 	Some(BlockStatus::Pending)
 }
 
-#[derive(Serialize, Deserialize, PartialEq)]
-pub struct Block {
-	pub status: BlockStatus,
-	pub confidence: Option<f64>,
+pub fn confidence(requested: f64, max_cell_count: u32) -> Confidence {
+	let required_cell_count = rpc::required_cell_count(requested);
+	let cell_count = required_cell_count.min(max_cell_count);
+	Confidence {
+		requested,
+		required_cell_count,
+		max_cell_count,
+		cell_count,
+		achievable: calculate_confidence(cell_count),
+	}
 }
 
-impl Block {
-	pub fn new(status: BlockStatus, confidence: Option<f64>) -> Self {
-		Self { status, confidence }
-	}
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeerScores {
+	pub peers: Vec<PeerScore>,
 }
 
-impl Reply for Block {
+impl Reply for PeerScores {
 	fn into_response(self) -> warp::reply::Response {
 		warp::reply::json(&self).into_response()
 	}
 }
 
-impl TryFrom<avail_subxt::primitives::Header> for HeaderMessage {
-	type Error = Report;
-
-	fn try_from(header: avail_subxt::primitives::Header) -> Result<Self, Self::Error> {
-		let header: Header = header.try_into()?;
-		Ok(Self {
-			block_number: header.number,
-			header,
-		})
-	}
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Header {
-	hash: H256,
-	parent_hash: H256,
-	pub number: u32,
-	state_root: H256,
-	extrinsics_root: H256,
-	extension: Extension,
-}
-
-impl Reply for Header {
+impl Reply for Peers {
 	fn into_response(self) -> warp::reply::Response {
 		warp::reply::json(&self).into_response()
 	}
 }
 
-#[derive(Debug, Clone)]
-struct Commitment([u8; config::COMMITMENT_SIZE]);
-
-impl Serialize for Commitment {
-	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-	where
-		S: Serializer,
-	{
-		let hex_string = format!("0x{}", hex::encode(self.0));
-		serializer.serialize_str(&hex_string)
-	}
-}
-
-impl<'de> Deserialize<'de> for Commitment {
-	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-	where
-		D: Deserializer<'de>,
-	{
-		const PREFIX_0X_LEN: usize = 2;
-		const HEX_ENCODED_BYTE_LEN: usize = 2;
-		const LEN: usize = (config::COMMITMENT_SIZE * HEX_ENCODED_BYTE_LEN) + PREFIX_0X_LEN;
-
-		let s = String::deserialize(deserializer)?;
-
-		if !s.starts_with("0x") || s.len() != LEN {
-			let message = "Expected a hex string of correct length with 0x prefix";
-			return Err(de::Error::custom(message));
-		}
-
-		let decoded = hex::decode(&s[2..]).map_err(de::Error::custom)?;
-		let decoded_len = decoded.len();
-		let bytes: [u8; config::COMMITMENT_SIZE] = decoded
-			.try_into()
-			.map_err(|_| de::Error::invalid_length(decoded_len, &"Expected vector of 48 bytes"))?;
-
-		Ok(Commitment(bytes))
+impl Reply for BandwidthStats {
+	fn into_response(self) -> warp::reply::Response {
+		warp::reply::json(&self).into_response()
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Extension {
-	rows: u16,
-	cols: u16,
-	data_root: H256,
-	commitments: Vec<Commitment>,
-	app_lookup: CompactDataLookup,
-}
-
-impl TryFrom<avail_subxt::primitives::Header> for Header {
+impl TryFrom<avail_subxt::primitives::Header> for HeaderMessage {
 	type Error = Report;
 
-	fn try_from(header: avail_subxt::primitives::Header) -> Result<Self> {
-		Ok(Header {
-			hash: Encode::using_encoded(&header, blake2_256).into(),
-			parent_hash: header.parent_hash,
-			number: header.number,
-			state_root: header.state_root,
-			extrinsics_root: header.extrinsics_root,
-			extension: header.extension.try_into()?,
+	fn try_from(header: avail_subxt::primitives::Header) -> Result<Self, Self::Error> {
+		let header = self::header(header)?;
+		Ok(Self {
+			block_number: header.number,
+			header,
 		})
 	}
 }
 
-impl TryFrom<HeaderExtension> for Extension {
-	type Error = Report;
+pub fn header(header: avail_subxt::primitives::Header) -> Result<Header> {
+	Ok(Header {
+		hash: Encode::using_encoded(&header, blake2_256).into(),
+		parent_hash: header.parent_hash,
+		number: header.number,
+		state_root: header.state_root,
+		extrinsics_root: header.extrinsics_root,
+		extension: extension(header.extension)?,
+	})
+}
 
-	fn try_from(value: HeaderExtension) -> Result<Self, Self::Error> {
-		match value {
-			HeaderExtension::V3(v3) => {
-				let commitments = commitments::from_slice(&v3.commitment.commitment)?
+fn extension(value: HeaderExtension) -> Result<Extension> {
+	match value {
+		HeaderExtension::V3(v3) => {
+			let commitments = commitments::from_slice(&v3.commitment.commitment)?
+				.into_iter()
+				.map(Commitment)
+				.collect::<Vec<_>>();
+
+			let app_lookup = DataLookup {
+				size: v3.app_lookup.size,
+				index: v3
+					.app_lookup
+					.index
 					.into_iter()
-					.map(Commitment)
-					.collect::<Vec<_>>();
-
-				Ok(Extension {
-					rows: v3.commitment.rows,
-					cols: v3.commitment.cols,
-					data_root: v3.commitment.data_root,
-					commitments,
-					app_lookup: v3.app_lookup,
-				})
-			},
-		}
+					.map(|item| DataLookupItem {
+						app_id: item.app_id.0,
+						start: item.start,
+					})
+					.collect(),
+			};
+
+			Ok(Extension {
+				rows: v3.commitment.rows,
+				cols: v3.commitment.cols,
+				data_root: v3.commitment.data_root,
+				commitments,
+				app_lookup,
+			})
+		},
 	}
 }
 
@@ -442,6 +319,9 @@ impl TryFrom<RpcEvent> for PublishMessage {
 				.try_into()
 				.map(Box::new)
 				.map(PublishMessage::HeaderVerified),
+			RpcEvent::ConnectionLost { .. } | RpcEvent::ConnectionRestored { .. } => {
+				Err(eyre!("Connection events are not published"))
+			},
 		}
 	}
 }
@@ -464,63 +344,43 @@ impl TryFrom<BlockVerified> for PublishMessage {
 	}
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(try_from = "String")]
-pub struct FieldsQueryParameter(pub HashSet<DataField>);
-
-impl TryFrom<String> for FieldsQueryParameter {
+impl TryFrom<CommitmentMismatch> for PublishMessage {
 	type Error = Report;
 
-	fn try_from(value: String) -> Result<Self, Self::Error> {
-		value
-			.split(',')
-			.map(|part| format!(r#""{part}""#))
-			.map(|part| serde_json::from_str(&part).wrap_err("Cannot deserialize field"))
-			.collect::<Result<HashSet<_>>>()
-			.map(FieldsQueryParameter)
-	}
-}
-
-#[derive(Serialize, Deserialize)]
-pub struct DataQuery {
-	pub fields: Option<FieldsQueryParameter>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct DataResponse {
-	pub block_number: u32,
-	pub data_transactions: Vec<DataTransaction>,
-}
-
-impl Reply for DataResponse {
-	fn into_response(self) -> warp::reply::Response {
-		warp::reply::json(&self).into_response()
+	fn try_from(value: CommitmentMismatch) -> Result<Self, Self::Error> {
+		Ok(PublishMessage::CommitmentMismatch(value))
 	}
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DataMessage {
+	app_id: u32,
 	block_number: u32,
 	data_transactions: Vec<DataTransaction>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct DataTransaction {
-	#[serde(skip_serializing_if = "Option::is_none")]
-	data: Option<Base64>,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	extrinsic: Option<Base64>,
+pub fn data_transaction(value: Vec<u8>) -> Result<DataTransaction> {
+	Ok(DataTransaction {
+		data: decode_app_data(&value)?.map(Base64),
+		extrinsic: Some(Base64(value)),
+	})
 }
 
-impl TryFrom<Vec<u8>> for DataTransaction {
-	type Error = Report;
-
-	fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
-		Ok(DataTransaction {
-			data: decode_app_data(&value)?.map(Base64),
-			extrinsic: Some(Base64(value)),
-		})
-	}
+/// Returns the page of the items, and offset of the next page if there are more items.
+/// Page size is limited to the maximum page size, which is also used if limit is not set.
+pub fn paginate<T>(
+	items: Vec<T>,
+	offset: Option<u32>,
+	limit: Option<u32>,
+	max_page_size: u32,
+) -> (Vec<T>, Option<u32>) {
+	let offset = offset.unwrap_or(0) as usize;
+	let max_page_size = max_page_size.max(1);
+	let limit = limit.unwrap_or(max_page_size).clamp(1, max_page_size) as usize;
+	let end = offset.saturating_add(limit);
+	let next_offset = (end < items.len()).then_some(end as u32);
+	let page = items.into_iter().skip(offset).take(limit).collect();
+	(page, next_offset)
 }
 
 pub fn filter_fields(data_transactions: &mut [DataTransaction], fields: &HashSet<DataField>) {
@@ -536,15 +396,18 @@ pub fn filter_fields(data_transactions: &mut [DataTransaction], fields: &HashSet
 	}
 }
 
-impl TryFrom<(u32, AppData)> for PublishMessage {
+impl TryFrom<(AppId, u32, AppData)> for PublishMessage {
 	type Error = Report;
 
-	fn try_from((block_number, app_data): (u32, AppData)) -> Result<Self, Self::Error> {
+	fn try_from(
+		(app_id, block_number, app_data): (AppId, u32, AppData),
+	) -> Result<Self, Self::Error> {
 		let data_transactions = app_data
 			.into_iter()
 			.map(TryFrom::try_from)
 			.collect::<Result<Vec<_>>>()?;
 		Ok(PublishMessage::DataVerified(DataMessage {
+			app_id: app_id.0,
 			block_number,
 			data_transactions,
 		}))
@@ -557,6 +420,8 @@ pub enum PublishMessage {
 	HeaderVerified(Box<HeaderMessage>),
 	ConfidenceAchieved(ConfidenceMessage),
 	DataVerified(DataMessage),
+	TransactionStatus(TransactionStatusMessage),
+	CommitmentMismatch(CommitmentMismatch),
 }
 
 impl PublishMessage {
@@ -564,6 +429,8 @@ impl PublishMessage {
 		match self {
 			PublishMessage::HeaderVerified(_) => (),
 			PublishMessage::ConfidenceAchieved(_) => (),
+			PublishMessage::TransactionStatus(_) => (),
+			PublishMessage::CommitmentMismatch(_) => (),
 			PublishMessage::DataVerified(data) => {
 				filter_fields(&mut data.data_transactions, fields)
 			},
@@ -619,6 +486,20 @@ impl WsClients {
 		Ok(())
 	}
 
+	/// Replaces topic filters of the existing subscription.
+	pub async fn set_subscription(
+		&self,
+		subscription_id: &str,
+		subscription: Subscription,
+	) -> Result<()> {
+		let mut clients = self.0.write().await;
+		let Some(client) = clients.get_mut(subscription_id) else {
+			return Err(eyre!("Client is not subscribed"));
+		};
+		client.subscription = subscription;
+		Ok(())
+	}
+
 	pub async fn has_subscription(&self, subscription_id: &str) -> bool {
 		self.0.read().await.contains_key(subscription_id)
 	}
@@ -671,6 +552,7 @@ pub enum Payload {
 	Version,
 	Status,
 	Submit(Transaction),
+	Subscribe(Subscription),
 }
 
 #[derive(Deserialize)]
@@ -788,6 +670,7 @@ pub enum WsResponse {
 	Version(Response<Version>),
 	Status(Response<Status>),
 	DataTransactionSubmitted(Response<SubmitResponse>),
+	Subscribed(Response<Subscription>),
 }
 
 #[derive(Serialize, Deserialize, From)]
@@ -800,20 +683,38 @@ pub enum WsError {
 mod tests {
 	use std::time::Duration;
 
-	use avail_subxt::api::runtime_types::avail_core::data_lookup::compact::CompactDataLookup;
 	use sp_core::H256;
+	use test_case::test_case;
 	use tokio::sync::mpsc;
 
 	use crate::{
 		api::v2::types::{BlockStatus, Header, HeaderMessage, PublishMessage},
+		data,
 		types::{OptionBlockRange, State},
 	};
 
 	use super::{
-		block_status, Base64, ConfidenceMessage, DataField, DataMessage, DataTransaction,
-		Subscription, Topic, WsClients,
+		block_status, paginate, sync_progress, Base64, ConfidenceMessage, DataField, DataLookup,
+		DataMessage, DataTransaction, Extension, Subscription, Topic, WsClients,
 	};
 
+	#[test]
+	fn sync_progress_eta() {
+		let mut progress = data::SyncProgress::new(0..10, 100);
+		let status = sync_progress(&progress, 110);
+		assert_eq!(status.percentage, 0.0);
+		assert_eq!(status.eta, None);
+
+		progress.set_verified(9);
+		progress.set_verified(8);
+		let status = sync_progress(&progress, 110);
+		assert_eq!(status.verified, 2);
+		assert_eq!(status.percentage, 20.0);
+		assert_eq!(status.lowest_verified, Some(8));
+		// 2 blocks are verified in 10 seconds, so remaining 8 blocks are verified in 40 seconds
+		assert_eq!(status.eta, Some(40));
+	}
+
 	fn subscription(topics: Vec<Topic>, fields: Vec<DataField>) -> Subscription {
 		Subscription {
 			topics: topics.into_iter().collect(),
@@ -830,12 +731,12 @@ mod tests {
 				number: 1,
 				state_root: H256::default(),
 				extrinsics_root: H256::default(),
-				extension: super::Extension {
+				extension: Extension {
 					rows: 1,
 					cols: 1,
 					data_root: H256::default(),
 					commitments: vec![],
-					app_lookup: CompactDataLookup {
+					app_lookup: DataLookup {
 						size: 0,
 						index: vec![],
 					},
@@ -857,6 +758,7 @@ mod tests {
 
 	fn data_verified() -> PublishMessage {
 		PublishMessage::DataVerified(DataMessage {
+			app_id: 1,
 			block_number: 1,
 			data_transactions: vec![DataTransaction {
 				data: transaction_data(),
@@ -1076,4 +978,13 @@ mod tests {
 		assert_eq!(block_status(&Some(1), &state, 5), finished);
 		assert_ne!(block_status(&Some(1), &state, 6), finished);
 	}
+
+	#[test_case(None, None => (vec![0, 1, 2], Some(3)) ; "Default page size")]
+	#[test_case(Some(3), Some(2) => (vec![3, 4], None) ; "Last page")]
+	#[test_case(Some(1), Some(10) => (vec![1, 2, 3], Some(4)) ; "Limit is capped")]
+	#[test_case(Some(1), Some(0) => (vec![1], Some(2)) ; "Zero limit")]
+	#[test_case(Some(10), None => (vec![], None) ; "Offset out of range")]
+	fn test_paginate(offset: Option<u32>, limit: Option<u32>) -> (Vec<u32>, Option<u32>) {
+		paginate(vec![0, 1, 2, 3, 4], offset, limit, 3)
+	}
 }
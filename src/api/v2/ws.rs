@@ -1,6 +1,8 @@
 use super::{
 	transactions,
-	types::{Payload, Request, Response, Status, Version, WsClients, WsError, WsResponse},
+	types::{
+		self, Payload, Request, Response, Transaction, Version, WsClients, WsError, WsResponse,
+	},
 };
 use crate::{
 	api::v2::types::{Error, Sender},
@@ -63,7 +65,17 @@ pub async fn connect(
 		let submitter = submitter.clone();
 		let state = state.clone();
 
-		let send_result = match handle_request(message, &version, &config, submitter, state).await {
+		let send_result = match handle_request(
+			message,
+			&subscription_id,
+			&clients,
+			&version,
+			&config,
+			submitter,
+			state,
+		)
+		.await
+		{
 			Ok(response) => send(sender.clone(), response),
 			Err(error) => {
 				if let Some(cause) = error.cause.as_ref() {
@@ -81,6 +93,8 @@ pub async fn connect(
 
 async fn handle_request(
 	message: Message,
+	subscription_id: &str,
+	clients: &WsClients,
 	version: &Version,
 	config: &RuntimeConfig,
 	submitter: Option<Arc<impl transactions::Submit>>,
@@ -95,7 +109,7 @@ async fn handle_request(
 		Payload::Version => Ok(Response::new(request_id, version.clone()).into()),
 		Payload::Status => {
 			let state = state.lock().expect("State lock can be acquired");
-			let status = Status::new(config, &state);
+			let status = types::status(config, &state);
 			Ok(Response::new(request_id, status).into())
 		},
 		Payload::Submit(transaction) => {
@@ -105,6 +119,12 @@ async fn handle_request(
 			if transaction.is_empty() {
 				return Err(Error::bad_request(request_id, "Transaction is empty."));
 			}
+			if matches!(transaction, Transaction::Data(_)) && !submitter.is_data_enabled() {
+				return Err(Error::bad_request(
+					request_id,
+					"Data submit is not configured.",
+				));
+			}
 
 			submitter
 				.submit(transaction)
@@ -112,5 +132,16 @@ async fn handle_request(
 				.map(|response| Response::new(request_id, response).into())
 				.map_err(Error::internal_server_error)
 		},
+		Payload::Subscribe(subscription) => {
+			if subscription.topics.is_empty() {
+				return Err(Error::bad_request(request_id, "Topics are empty."));
+			}
+
+			clients
+				.set_subscription(subscription_id, subscription.clone())
+				.await
+				.map(|_| Response::new(request_id, subscription).into())
+				.map_err(Error::internal_server_error)
+		},
 	}
 }
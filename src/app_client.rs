@@ -6,14 +6,16 @@
 //!
 //! Get app data rows from node
 //! Verify commitment equality for each row
-//! Decode app data and store it into local database under the `app_id:block_number` key
+//! Decode app data, filter it by the configured data filter of the application (see [`app_filter`](crate::app_filter)), and store it into local database under the `app_id:block_number` key
+//!
+//! Multiple applications can be followed, each one with the separate app client task (see [`AppClients`]).
 //!
 //! # Notes
 //!
 //! If application client fails to run or stops its execution, error is logged, and other tasks continue with execution.
 use async_trait::async_trait;
 use avail_core::AppId;
-use avail_subxt::utils::H256;
+use avail_subxt::{primitives::Header as DaHeader, utils::H256};
 use color_eyre::{
 	eyre::{eyre, WrapErr},
 	Result,
@@ -37,15 +39,21 @@ use std::{
 	ops::Range,
 	sync::{Arc, Mutex},
 };
-use tokio::sync::broadcast;
-use tracing::{debug, error, info, instrument};
+use tokio::{
+	sync::broadcast::{self, error::RecvError},
+	task::AbortHandle,
+};
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::{
-	data::{Database, Key},
+	app_filter::DataFilter,
+	config_reload::ConfigUpdate,
+	data::{self, cell_cache::CellCache, Database, Key},
 	network::{p2p::Client as P2pClient, rpc::Client as RpcClient},
-	proof,
+	proof::{self, Lane},
 	shutdown::Controller,
-	types::{AppClientConfig, BlockVerified, OptionBlockRange, State},
+	types::{AppClientConfig, AppState, BlockVerified, OptionBlockRange, State},
+	utils::calculate_confidence,
 };
 
 #[async_trait]
@@ -79,6 +87,7 @@ trait Client {
 struct AppClient {
 	p2p_client: P2pClient,
 	rpc_client: RpcClient,
+	cell_cache: CellCache,
 }
 
 #[async_trait]
@@ -105,6 +114,7 @@ impl Client for AppClient {
 		let (fetched, unfetched) = fetch_verified(
 			pp.clone(),
 			&self.p2p_client,
+			&self.cell_cache,
 			block_number,
 			dimensions,
 			commitments,
@@ -125,6 +135,7 @@ impl Client for AppClient {
 		let (missing_fetched, _) = fetch_verified(
 			pp,
 			&self.p2p_client,
+			&self.cell_cache,
 			block_number,
 			dimensions,
 			commitments,
@@ -250,25 +261,41 @@ fn data_cell(
 		.ok_or_else(|| eyre!("Data cell not found"))
 }
 
+/// Fetches cells which are not cached from the DHT, and caches the verified ones.
 async fn fetch_verified(
 	pp: Arc<PublicParameters>,
 	p2p_client: &P2pClient,
+	cell_cache: &CellCache,
 	block_number: u32,
 	dimensions: Dimensions,
 	commitments: &[[u8; config::COMMITMENT_SIZE]],
 	positions: &[Position],
 ) -> Result<(Vec<Cell>, Vec<Position>)> {
-	let (mut fetched, mut unfetched) = p2p_client
-		.fetch_cells_from_dht(block_number, positions)
-		.await;
+	let (mut cached, positions) = cell_cache.get(block_number, positions);
+	if positions.is_empty() {
+		return Ok((cached, vec![]));
+	}
 
-	let (verified, mut unverified) =
-		proof::verify(block_number, dimensions, &fetched, commitments, pp)
-			.await
-			.wrap_err("Failed to verify fetched cells")?;
+	let (mut fetched, mut unfetched, peers) = p2p_client
+		.fetch_cells_from_dht(block_number, &positions)
+		.await;
 
+	let (verified, mut unverified) = proof::verify(
+		Lane::Head,
+		block_number,
+		dimensions,
+		&fetched,
+		commitments,
+		pp,
+	)
+	.await
+	.wrap_err("Failed to verify fetched cells")?;
+
+	p2p_client.report_verified_cells(peers, &verified);
 	fetched.retain(|cell| verified.contains(&cell.position));
+	cell_cache.insert(block_number, &fetched);
 	unfetched.append(&mut unverified);
+	fetched.append(&mut cached);
 
 	Ok((fetched, unfetched))
 }
@@ -281,6 +308,7 @@ async fn process_block(
 	app_id: AppId,
 	block: &BlockVerified,
 	pp: Arc<PublicParameters>,
+	filter: Option<&DataFilter>,
 ) -> Result<AppData> {
 	let lookup = &block.lookup;
 	let block_number = block.block_num;
@@ -390,11 +418,28 @@ async fn process_block(
 	let data = decode_app_extrinsics(lookup, dimensions, data_cells, app_id)
 		.wrap_err("Failed to decode app extrinsics")?;
 
+	let data = match filter {
+		Some(filter) => {
+			let decoded_count = data.len();
+			let data = filter.apply(data);
+			debug!(
+				block_number,
+				"Filtered {} of {decoded_count} app extrinsics",
+				decoded_count - data.len()
+			);
+			data
+		},
+		None => data,
+	};
+
 	debug!(block_number, "Storing data into database");
 
 	// store encoded App Data into the database
-	db.put(Key::AppData(app_id.0, block_number), data.clone())
-		.wrap_err("App Client failed to store App Data into database")?;
+	db.put(
+		Key::AppData(app_id.0, block_number, block.header_hash),
+		data.clone(),
+	)
+	.wrap_err("App Client failed to store App Data into database")?;
 
 	let bytes_count = data.iter().fold(0usize, |acc, x| acc + x.len());
 	debug!(block_number, "Stored {bytes_count} bytes into database");
@@ -410,8 +455,10 @@ async fn process_block(
 /// * `db` - Database to store data inot DB
 /// * `network_client` - Reference to a libp2p custom network client
 /// * `rpc_client` - Node's RPC subxt client for fetching data unavailable in DHT (if configured)
+/// * `cell_cache` - Cache of the verified cells, reused instead of fetching them from DHT
 /// * `app_id` - Application ID
 /// * `block_receive` - Channel used to receive header of verified block
+/// * `backfill` - Previously verified blocks, processed before the received blocks
 /// * `pp` - Public parameters (i.e. SRS) needed for proof verification
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
@@ -419,38 +466,81 @@ pub async fn run(
 	db: impl Database + Clone + Sync,
 	network_client: P2pClient,
 	rpc_client: RpcClient,
+	cell_cache: CellCache,
 	app_id: AppId,
 	mut block_receive: broadcast::Receiver<BlockVerified>,
+	backfill: Vec<BlockVerified>,
 	pp: Arc<PublicParameters>,
 	state: Arc<Mutex<State>>,
 	sync_range: Range<u32>,
-	data_verified_sender: broadcast::Sender<(u32, AppData)>,
+	data_verified_sender: broadcast::Sender<(AppId, u32, AppData)>,
 	shutdown: Controller<String>,
 ) {
 	info!("Starting for app {app_id}...");
 
+	let is_configured_app = cfg.app_id == Some(app_id.0);
+
+	let filter = match cfg
+		.data_filters
+		.iter()
+		.find(|filter| filter.app_id == app_id.0)
+		.map(|filter| filter.parse())
+		.transpose()
+	{
+		Ok(filter) => filter,
+		Err(error) => {
+			error!("Cannot parse data filter of app {app_id}: {error:#}");
+			let _ = shutdown.trigger_shutdown(format!("Cannot parse data filter: {error:#}"));
+			return;
+		},
+	};
+
 	fn set_data_verified_state(
 		state: Arc<Mutex<State>>,
 		sync_range: &Range<u32>,
+		app_id: AppId,
+		is_configured_app: bool,
 		block_number: u32,
 	) {
 		let mut state = state.lock().expect("State lock can be acquired");
+		// Additional applications are not affecting the block status and sync state
+		if !is_configured_app {
+			if let Some(app) = state.apps.get_mut(&app_id.0) {
+				match sync_range.contains(&block_number) {
+					true => app.sync_data_verified.extend(block_number),
+					false => app.data_verified.set(block_number),
+				}
+			}
+			return;
+		}
 		match sync_range.contains(&block_number) {
-			true => state.sync_data_verified.set(block_number),
+			true => state.sync_data_verified.extend(block_number),
 			false => state.data_verified.set(block_number),
 		}
-		if state.synced == Some(false) && sync_range.clone().last() == Some(block_number) {
+		// Historical blocks are synced from the latest, so the sync is completed with the first block
+		if state.synced == Some(false) && sync_range.start == block_number {
 			state.synced.replace(true);
 		};
 	}
 
+	// Backfilled blocks are processed first, and skipped if received again
+	let backfilled = backfill
+		.iter()
+		.map(|block| block.block_num)
+		.collect::<HashSet<_>>();
+	let mut backfill = backfill.into_iter();
+
 	loop {
-		let block = match block_receive.recv().await {
-			Ok(block) => block,
-			Err(error) => {
-				error!("Cannot receive message: {error}");
-				let _ = shutdown.trigger_shutdown(format!("Cannot receive message: {error:#}"));
-				return;
+		let block = match backfill.next() {
+			Some(block) => block,
+			None => match block_receive.recv().await {
+				Ok(block) if backfilled.contains(&block.block_num) => continue,
+				Ok(block) => block,
+				Err(error) => {
+					error!("Cannot receive message: {error}");
+					let _ = shutdown.trigger_shutdown(format!("Cannot receive message: {error:#}"));
+					return;
+				},
 			},
 		};
 
@@ -464,25 +554,47 @@ pub async fn run(
 				block_number,
 				"Skipping block with no cells for app {app_id}"
 			);
-			set_data_verified_state(state.clone(), &sync_range, block_number);
+			set_data_verified_state(
+				state.clone(),
+				&sync_range,
+				app_id,
+				is_configured_app,
+				block_number,
+			);
 			continue;
 		}
 
 		let app_client = AppClient {
 			p2p_client: network_client.clone(),
 			rpc_client: rpc_client.clone(),
+			cell_cache: cell_cache.clone(),
 		};
-		let data =
-			match process_block(app_client, db.clone(), &cfg, app_id, &block, pp.clone()).await {
-				Ok(data) => data,
-				Err(error) => {
-					error!(block_number, "Cannot process block: {error}");
-					let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
-					return;
-				},
-			};
-		set_data_verified_state(state.clone(), &sync_range, block_number);
-		if let Err(error) = data_verified_sender.send((block_number, data)) {
+		let data = match process_block(
+			app_client,
+			db.clone(),
+			&cfg,
+			app_id,
+			&block,
+			pp.clone(),
+			filter.as_ref(),
+		)
+		.await
+		{
+			Ok(data) => data,
+			Err(error) => {
+				error!(block_number, "Cannot process block: {error}");
+				let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
+				return;
+			},
+		};
+		set_data_verified_state(
+			state.clone(),
+			&sync_range,
+			app_id,
+			is_configured_app,
+			block_number,
+		);
+		if let Err(error) = data_verified_sender.send((app_id, block_number, data)) {
 			error!("Cannot send data verified message: {error}");
 			let _ =
 				shutdown.trigger_shutdown(format!("Cannot send data verified message: {error:#}"));
@@ -492,6 +604,206 @@ pub async fn run(
 	}
 }
 
+/// App clients of the followed applications, which can be added or removed at runtime.
+#[derive(Clone)]
+pub struct AppClients<T: Database> {
+	cfg: AppClientConfig,
+	db: T,
+	p2p_client: P2pClient,
+	rpc_client: RpcClient,
+	cell_cache: CellCache,
+	block_sender: broadcast::Sender<BlockVerified>,
+	pp: Arc<PublicParameters>,
+	state: Arc<Mutex<State>>,
+	sync_range: Range<u32>,
+	data_verified_sender: broadcast::Sender<(AppId, u32, AppData)>,
+	shutdown: Controller<String>,
+	running: Arc<Mutex<HashMap<u32, AbortHandle>>>,
+}
+
+impl<T: Database + Clone + Send + Sync + 'static> AppClients<T> {
+	#[allow(clippy::too_many_arguments)]
+	pub fn new(
+		cfg: AppClientConfig,
+		db: T,
+		p2p_client: P2pClient,
+		rpc_client: RpcClient,
+		cell_cache: CellCache,
+		block_sender: broadcast::Sender<BlockVerified>,
+		pp: Arc<PublicParameters>,
+		state: Arc<Mutex<State>>,
+		sync_range: Range<u32>,
+		data_verified_sender: broadcast::Sender<(AppId, u32, AppData)>,
+		shutdown: Controller<String>,
+	) -> Self {
+		AppClients {
+			cfg,
+			db,
+			p2p_client,
+			rpc_client,
+			cell_cache,
+			block_sender,
+			pp,
+			state,
+			sync_range,
+			data_verified_sender,
+			shutdown,
+			running: Default::default(),
+		}
+	}
+
+	/// Starts app client for the given application, returns `false` if application is already followed.
+	pub fn add(&self, app_id: AppId) -> bool {
+		self.start(app_id, vec![])
+	}
+
+	/// Returns latest verified blocks to backfill, oldest first.
+	fn backfill_blocks(&self) -> Result<Vec<BlockVerified>> {
+		let state = self.state.lock().expect("State lock can be acquired");
+		let Some(range) = state.confidence_achieved.clone() else {
+			return Ok(vec![]);
+		};
+		drop(state);
+		let first = range
+			.first
+			.max((range.last + 1).saturating_sub(self.cfg.backfill_blocks));
+
+		let mut blocks = vec![];
+		for block_number in first..=range.last {
+			let header = data::get_canonical::<DaHeader>(&self.db, block_number, Key::BlockHeader)
+				.wrap_err("App Client failed to get Block Header")?;
+			let count = data::get_canonical::<u32>(&self.db, block_number, Key::VerifiedCellCount)
+				.wrap_err("App Client failed to get Confidence Factor")?;
+			let (Some(header), Some(count)) = (header, count) else {
+				debug!(
+					block_number,
+					"Skipping backfill of block without header or confidence"
+				);
+				continue;
+			};
+			let confidence = Some(calculate_confidence(count));
+			blocks.push(BlockVerified::try_from((header, confidence))?);
+		}
+		Ok(blocks)
+	}
+
+	fn start(&self, app_id: AppId, backfill: Vec<BlockVerified>) -> bool {
+		let mut running = self.running.lock().expect("Lock can be acquired");
+		if running.contains_key(&app_id.0) {
+			return false;
+		}
+
+		if self.cfg.app_id != Some(app_id.0) {
+			let mut state = self.state.lock().expect("State lock can be acquired");
+			state.apps.insert(app_id.0, AppState::default());
+		}
+
+		let app_client = run(
+			self.cfg.clone(),
+			self.db.clone(),
+			self.p2p_client.clone(),
+			self.rpc_client.clone(),
+			self.cell_cache.clone(),
+			app_id,
+			self.block_sender.subscribe(),
+			backfill,
+			self.pp.clone(),
+			self.state.clone(),
+			self.sync_range.clone(),
+			self.data_verified_sender.clone(),
+			self.shutdown.clone(),
+		);
+		let handle = tokio::spawn(self.shutdown.with_cancel(app_client));
+		running.insert(app_id.0, handle.abort_handle());
+		true
+	}
+
+	/// Stops app client for the given application, returns `false` if application is not followed.
+	pub fn remove(&self, app_id: AppId) -> bool {
+		let mut running = self.running.lock().expect("Lock can be acquired");
+		let Some(handle) = running.remove(&app_id.0) else {
+			return false;
+		};
+		handle.abort();
+		self.state
+			.lock()
+			.expect("State lock can be acquired")
+			.apps
+			.remove(&app_id.0);
+		info!("Stopped app client for app {app_id}");
+		true
+	}
+}
+
+/// Follows applications at runtime.
+#[automock]
+pub trait Follow {
+	/// Starts following the application, with latest verified blocks backfilled.
+	/// Returns `false` if application is already followed.
+	fn follow(&self, app_id: AppId) -> Result<bool>;
+
+	/// Stops following the application, returns `false` if application is not followed.
+	fn unfollow(&self, app_id: AppId) -> bool;
+
+	/// Returns sorted IDs of the followed applications.
+	fn app_ids(&self) -> Vec<u32>;
+}
+
+impl<T: Database + Clone + Send + Sync + 'static> Follow for AppClients<T> {
+	fn follow(&self, app_id: AppId) -> Result<bool> {
+		if self.app_ids().contains(&app_id.0) {
+			return Ok(false);
+		}
+		let backfill = self.backfill_blocks()?;
+		info!(
+			"Following app {app_id}, backfilling {} blocks",
+			backfill.len()
+		);
+		Ok(self.start(app_id, backfill))
+	}
+
+	fn unfollow(&self, app_id: AppId) -> bool {
+		self.remove(app_id)
+	}
+
+	fn app_ids(&self) -> Vec<u32> {
+		let running = self.running.lock().expect("Lock can be acquired");
+		let mut app_ids = running.keys().cloned().collect::<Vec<_>>();
+		app_ids.sort();
+		app_ids
+	}
+}
+
+/// Follows and unfollows applications when configured application list is changed on configuration reload.
+/// Only the difference from the previously configured list is applied,
+/// so applications followed using the API are not affected.
+pub async fn apply_updates(
+	app_clients: Arc<impl Follow>,
+	mut previous: Vec<u32>,
+	mut receiver: broadcast::Receiver<ConfigUpdate>,
+) {
+	loop {
+		let app_ids = match receiver.recv().await {
+			Ok(ConfigUpdate::AppIds(app_ids)) => app_ids,
+			Ok(_) | Err(RecvError::Lagged(_)) => continue,
+			Err(RecvError::Closed) => return,
+		};
+		for app_id in previous.iter().filter(|&id| !app_ids.contains(id)) {
+			if !app_clients.unfollow(AppId(*app_id)) {
+				debug!("App {app_id} is not followed");
+			}
+		}
+		for app_id in app_ids.iter().filter(|&id| !previous.contains(id)) {
+			match app_clients.follow(AppId(*app_id)) {
+				Ok(true) => (),
+				Ok(false) => debug!("App {app_id} is already followed"),
+				Err(error) => warn!("Cannot follow app {app_id}: {error:#}"),
+			}
+		}
+		previous = app_ids;
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -553,7 +865,7 @@ mod tests {
 			.expect_reconstruct_rows_from_dht()
 			.returning(|_, _, _, _, _| Box::pin(async move { Ok(vec![]) }));
 
-		process_block(mock_client, db, &cfg, AppId(1), &block, pp)
+		process_block(mock_client, db, &cfg, AppId(1), &block, pp, None)
 			.await
 			.unwrap();
 	}
@@ -615,8 +927,28 @@ mod tests {
 			.expect_reconstruct_rows_from_dht()
 			.returning(|_, _, _, _, _| Box::pin(async move { Ok(vec![]) }));
 
-		process_block(mock_client, db, &cfg, AppId(1), &block, pp)
+		process_block(mock_client, db, &cfg, AppId(1), &block, pp, None)
 			.await
 			.unwrap();
 	}
+
+	#[tokio::test]
+	async fn apply_app_ids_update() {
+		let mut app_clients = MockFollow::new();
+		app_clients
+			.expect_unfollow()
+			.withf(|app_id| app_id.0 == 1)
+			.times(1)
+			.returning(|_| true);
+		app_clients
+			.expect_follow()
+			.withf(|app_id| app_id.0 == 3)
+			.times(1)
+			.returning(|_| Ok(true));
+
+		let (sender, receiver) = broadcast::channel(1);
+		sender.send(ConfigUpdate::AppIds(vec![2, 3])).unwrap();
+		drop(sender);
+		apply_updates(Arc::new(app_clients), vec![1, 2], receiver).await;
+	}
 }
@@ -0,0 +1,160 @@
+//! Filters of the application data, so app clients following high-volume applications store only the relevant extrinsics.
+
+use avail_core::AppId;
+use avail_subxt::{
+	api::runtime_types::{da_control::pallet::Call, da_runtime::RuntimeCall},
+	primitives::AppUncheckedExtrinsic,
+};
+use codec::Decode;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use kate_recovery::com::AppData;
+use serde::{Deserialize, Serialize};
+use sp_core::crypto::{AccountId32, Ss58Codec};
+use subxt::utils::MultiAddress;
+
+/// Configured filter of the application data (see [RuntimeConfig](crate::types::RuntimeConfig) for details)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppDataFilter {
+	/// ID of the filtered application
+	pub app_id: u32,
+	/// SS58 addresses of the submitters whose extrinsics are stored, extrinsics of all submitters are stored if empty
+	#[serde(default)]
+	pub submitters: Vec<String>,
+	/// Hex encoded prefixes of the submitted data which is stored, data with any prefix is stored if empty
+	#[serde(default)]
+	pub data_prefixes: Vec<String>,
+}
+
+impl AppDataFilter {
+	/// Parses the configured submitters and data prefixes.
+	pub fn parse(&self) -> Result<DataFilter> {
+		let submitters = self
+			.submitters
+			.iter()
+			.map(|address| {
+				AccountId32::from_ss58check(address)
+					.map(|account| *account.as_ref())
+					.map_err(|error| eyre!("Invalid submitter address {address}: {error:?}"))
+			})
+			.collect::<Result<Vec<[u8; 32]>>>()?;
+		let data_prefixes = self
+			.data_prefixes
+			.iter()
+			.map(|prefix| {
+				hex::decode(prefix.trim_start_matches("0x"))
+					.wrap_err_with(|| format!("Invalid data prefix {prefix}"))
+			})
+			.collect::<Result<Vec<_>>>()?;
+		Ok(DataFilter {
+			app_id: AppId(self.app_id),
+			submitters,
+			data_prefixes,
+		})
+	}
+}
+
+/// Checks configured filters, so the invalid filters are rejected once the configuration is loaded.
+pub fn check(filters: &[AppDataFilter]) -> Result<()> {
+	for filter in filters {
+		filter
+			.parse()
+			.wrap_err_with(|| format!("Invalid data filter of app {}", filter.app_id))?;
+	}
+	Ok(())
+}
+
+/// Parsed filter of the application data
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataFilter {
+	pub app_id: AppId,
+	submitters: Vec<[u8; 32]>,
+	data_prefixes: Vec<Vec<u8>>,
+}
+
+impl DataFilter {
+	fn matches_parts(&self, submitter: Option<&[u8; 32]>, data: Option<&[u8]>) -> bool {
+		let submitter_matches = self.submitters.is_empty()
+			|| submitter.map_or(false, |submitter| self.submitters.contains(submitter));
+		let data_matches = self.data_prefixes.is_empty()
+			|| data.map_or(false, |data| {
+				self.data_prefixes
+					.iter()
+					.any(|prefix| data.starts_with(prefix))
+			});
+		submitter_matches && data_matches
+	}
+
+	/// Returns `true` if the encoded app extrinsic matches the filter.
+	pub fn matches(&self, extrinsic: &[u8]) -> bool {
+		let Ok(extrinsic) = AppUncheckedExtrinsic::decode(&mut &extrinsic[..]) else {
+			return false;
+		};
+		let submitter = match &extrinsic.signature {
+			Some((MultiAddress::Id(account), ..)) => Some(&account.0),
+			_ => None,
+		};
+		let data = match &extrinsic.function {
+			RuntimeCall::DataAvailability(Call::submit_data { data, .. }) => Some(&data.0[..]),
+			_ => None,
+		};
+		self.matches_parts(submitter, data)
+	}
+
+	/// Keeps only the app extrinsics which match the filter.
+	pub fn apply(&self, mut data: AppData) -> AppData {
+		data.retain(|extrinsic| self.matches(extrinsic));
+		data
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+
+	use super::{check, AppDataFilter};
+
+	const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+	const BOB: &str = "5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty";
+
+	fn filter(submitters: &[&str], data_prefixes: &[&str]) -> AppDataFilter {
+		AppDataFilter {
+			app_id: 1,
+			submitters: submitters
+				.iter()
+				.map(|address| address.to_string())
+				.collect(),
+			data_prefixes: data_prefixes
+				.iter()
+				.map(|prefix| prefix.to_string())
+				.collect(),
+		}
+	}
+
+	#[test_case(&[], &[] => true ; "no filters")]
+	#[test_case(&[ALICE, "invalid"], &[] => false ; "invalid address")]
+	#[test_case(&[], &["0x01", "zz"] => false ; "invalid prefix")]
+	#[test_case(&[ALICE], &["0x0102", "abcd"] => true ; "valid filters")]
+	fn test_check(submitters: &[&str], data_prefixes: &[&str]) -> bool {
+		check(&[filter(submitters, data_prefixes)]).is_ok()
+	}
+
+	#[test]
+	fn extrinsics_match_submitter_and_data_prefix() {
+		let bob = filter(&[BOB], &[]).parse().unwrap().submitters[0];
+		let data_filter = filter(&[ALICE], &["0x0102"]).parse().unwrap();
+		let alice = &data_filter.submitters[0];
+
+		assert!(data_filter.matches_parts(Some(alice), Some(&[1, 2, 3][..])));
+		assert!(!data_filter.matches_parts(Some(&bob), Some(&[1, 2, 3][..])));
+		assert!(!data_filter.matches_parts(Some(alice), Some(&[2, 1][..])));
+		assert!(!data_filter.matches_parts(None, Some(&[1, 2][..])));
+		assert!(!data_filter.matches_parts(Some(alice), None));
+
+		let unfiltered = AppDataFilter::default().parse().unwrap();
+		assert!(unfiltered.matches_parts(None, None));
+		assert!(!unfiltered.matches(&[0xff]));
+	}
+}
@@ -0,0 +1,272 @@
+//! Proof-of-availability attestations, signed by the light client.
+
+use codec::{Decode, Encode, Error as CodecError, Input, Output};
+use color_eyre::{eyre::WrapErr, Result};
+use serde::{Deserialize, Serialize};
+use sp_core::{ed25519, sr25519, Pair, H256, H512};
+use tokio::sync::broadcast;
+use tracing::{debug, error, info};
+
+use crate::{
+	data::{Database, Key},
+	network::p2p,
+	types::BlockVerified,
+	utils::unix_timestamp,
+};
+
+/// Gossipsub topic on which the attestations are published
+pub const ATTESTATIONS_TOPIC: &str = "/avail/light/attestations/1";
+
+/// Domain separator of the signed attestation payload
+const SIGNING_CONTEXT: &[u8] = b"avail-light-attestation";
+
+/// Type of the attestation signing key.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyType {
+	#[default]
+	Sr25519,
+	Ed25519,
+}
+
+/// Key pair used to sign the attestations.
+#[derive(Clone)]
+pub enum Signer {
+	Sr25519(sr25519::Pair),
+	Ed25519(ed25519::Pair),
+}
+
+impl Signer {
+	/// Derives key pair of the given type from the secret seed phrase (or secret URI).
+	pub fn from_phrase(key_type: KeyType, phrase: &str, password: Option<&str>) -> Result<Self> {
+		let signer = match key_type {
+			KeyType::Sr25519 => Signer::Sr25519(sr25519::Pair::from_string(phrase, password)?),
+			KeyType::Ed25519 => Signer::Ed25519(ed25519::Pair::from_string(phrase, password)?),
+		};
+		Ok(signer)
+	}
+
+	pub fn key_type(&self) -> KeyType {
+		match self {
+			Signer::Sr25519(_) => KeyType::Sr25519,
+			Signer::Ed25519(_) => KeyType::Ed25519,
+		}
+	}
+
+	pub fn public_key(&self) -> H256 {
+		match self {
+			Signer::Sr25519(pair) => H256::from_slice(pair.public().as_ref()),
+			Signer::Ed25519(pair) => H256::from_slice(pair.public().as_ref()),
+		}
+	}
+
+	fn sign(&self, message: &[u8]) -> H512 {
+		match self {
+			Signer::Sr25519(pair) => H512::from_slice(pair.sign(message).as_ref()),
+			Signer::Ed25519(pair) => H512::from_slice(pair.sign(message).as_ref()),
+		}
+	}
+}
+
+/// Signed attestation that the block is available with the given confidence.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Attestation {
+	pub block_number: u32,
+	pub block_hash: H256,
+	pub confidence: f64,
+	/// Unix timestamp in seconds, at which the attestation is signed
+	pub timestamp: u64,
+	pub key_type: KeyType,
+	pub public_key: H256,
+	pub signature: H512,
+}
+
+impl Attestation {
+	pub fn new(
+		signer: &Signer,
+		block_number: u32,
+		block_hash: H256,
+		confidence: f64,
+		timestamp: u64,
+	) -> Self {
+		let payload = payload(block_number, block_hash, confidence, timestamp);
+		Attestation {
+			block_number,
+			block_hash,
+			confidence,
+			timestamp,
+			key_type: signer.key_type(),
+			public_key: signer.public_key(),
+			signature: signer.sign(&payload),
+		}
+	}
+
+	/// Verifies the signature of the attestation against its public key.
+	pub fn verify(&self) -> bool {
+		let payload = payload(
+			self.block_number,
+			self.block_hash,
+			self.confidence,
+			self.timestamp,
+		);
+		match self.key_type {
+			KeyType::Sr25519 => <sr25519::Pair as Pair>::verify(
+				&sr25519::Signature::from_raw(self.signature.0),
+				&payload,
+				&sr25519::Public::from_raw(self.public_key.0),
+			),
+			KeyType::Ed25519 => <ed25519::Pair as Pair>::verify(
+				&ed25519::Signature::from_raw(self.signature.0),
+				&payload,
+				&ed25519::Public::from_raw(self.public_key.0),
+			),
+		}
+	}
+}
+
+/// Signed payload is SCALE encoded, with the confidence encoded as its IEEE 754 bit representation.
+fn payload(block_number: u32, block_hash: H256, confidence: f64, timestamp: u64) -> Vec<u8> {
+	(
+		SIGNING_CONTEXT,
+		block_number,
+		block_hash,
+		confidence.to_bits(),
+		timestamp,
+	)
+		.encode()
+}
+
+// Confidence is not supported by SCALE codec, so it is encoded as its bit representation
+impl Encode for Attestation {
+	fn encode_to<T: Output + ?Sized>(&self, dest: &mut T) {
+		(
+			self.block_number,
+			self.block_hash,
+			self.confidence.to_bits(),
+			self.timestamp,
+			self.key_type,
+			self.public_key,
+			self.signature,
+		)
+			.encode_to(dest)
+	}
+}
+
+impl Decode for Attestation {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, CodecError> {
+		let (block_number, block_hash, confidence, timestamp, key_type, public_key, signature) =
+			<(u32, H256, u64, u64, KeyType, H256, H512)>::decode(input)?;
+		Ok(Attestation {
+			block_number,
+			block_hash,
+			confidence: f64::from_bits(confidence),
+			timestamp,
+			key_type,
+			public_key,
+			signature,
+		})
+	}
+}
+
+/// Signs and stores attestations of the blocks which achieved confidence, publishing them if gossip client is provided.
+pub async fn run(
+	db: impl Database,
+	signer: Signer,
+	gossip_client: Option<p2p::Client>,
+	mut block_receiver: broadcast::Receiver<BlockVerified>,
+) {
+	info!(
+		"Signing availability attestations with {:?} key {:?}",
+		signer.key_type(),
+		signer.public_key()
+	);
+
+	loop {
+		let block = match block_receiver.recv().await {
+			Ok(block) => block,
+			Err(broadcast::error::RecvError::Lagged(skipped)) => {
+				debug!("Attestations are skipped for {skipped} blocks");
+				continue;
+			},
+			Err(error) => {
+				error!("Cannot receive verified block: {error}");
+				return;
+			},
+		};
+		let Some(confidence) = block.confidence else {
+			continue;
+		};
+
+		let attestation = Attestation::new(
+			&signer,
+			block.block_num,
+			block.header_hash,
+			confidence,
+			unix_timestamp(),
+		);
+		if let Err(error) = store_and_publish(&db, gossip_client.as_ref(), attestation).await {
+			error!(block.block_num, "Cannot attest block: {error:#}");
+		}
+	}
+}
+
+async fn store_and_publish(
+	db: &impl Database,
+	gossip_client: Option<&p2p::Client>,
+	attestation: Attestation,
+) -> Result<()> {
+	let block_number = attestation.block_number;
+	db.put(Key::Attestation(block_number), attestation.clone())
+		.wrap_err("Failed to store attestation")?;
+
+	let Some(client) = gossip_client else {
+		return Ok(());
+	};
+	// Publishing fails if no peers are subscribed to the topic, which is expected on small networks
+	if let Err(error) = client
+		.publish(ATTESTATIONS_TOPIC, attestation.encode())
+		.await
+	{
+		debug!(block_number, "Attestation is not published: {error:#}");
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use codec::{Decode, Encode};
+	use sp_core::H256;
+	use test_case::test_case;
+
+	use super::{Attestation, KeyType, Signer};
+
+	const PHRASE: &str =
+		"bottom drive obey lake curtain smoke basket hold race lonely fit walk//attestation";
+
+	fn attestation(key_type: KeyType) -> Attestation {
+		let signer = Signer::from_phrase(key_type, PHRASE, None).unwrap();
+		Attestation::new(&signer, 10, H256::repeat_byte(1), 99.9, 1_700_000_000)
+	}
+
+	#[test_case(KeyType::Sr25519 ; "Sr25519 key")]
+	#[test_case(KeyType::Ed25519 ; "Ed25519 key")]
+	fn signed_attestation_is_verified(key_type: KeyType) {
+		let attestation = attestation(key_type);
+		assert_eq!(attestation.key_type, key_type);
+		assert!(attestation.verify());
+
+		let decoded = Attestation::decode(&mut &attestation.encode()[..]).unwrap();
+		assert_eq!(decoded, attestation);
+		assert!(decoded.verify());
+	}
+
+	#[test_case(|attestation| attestation.confidence = 50.0 ; "Changed confidence")]
+	#[test_case(|attestation| attestation.block_hash = H256::repeat_byte(2) ; "Changed block hash")]
+	#[test_case(|attestation| attestation.timestamp += 1 ; "Changed timestamp")]
+	#[test_case(|attestation| attestation.key_type = KeyType::Ed25519 ; "Changed key type")]
+	fn tampered_attestation_is_not_verified(tamper: fn(&mut Attestation)) {
+		let mut attestation = attestation(KeyType::Sr25519);
+		tamper(&mut attestation);
+		assert!(!attestation.verify());
+	}
+}
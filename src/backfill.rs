@@ -0,0 +1,192 @@
+//! Backfill of the blocks missed by the light client, sampled from the oldest gap with the sync priority.
+
+use std::{
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::{
+	data::Database,
+	dedup::BlockDedup,
+	network::{self, rpc},
+	scheduler::{Priority, Scheduler},
+	telemetry::Metrics,
+	types::{BlockRange, BlockVerified, LightClientConfig, State},
+	verification::CommitmentMismatch,
+	watchdog,
+};
+
+/// Adds the missed blocks to the gaps, merging overlapping and adjacent ranges.
+pub fn insert(gaps: &mut Vec<BlockRange>, missed: BlockRange) {
+	let (mut first, mut last) = (missed.first, missed.last);
+	gaps.retain(|gap| {
+		let is_merged = gap.first <= last.saturating_add(1) && first <= gap.last.saturating_add(1);
+		if is_merged {
+			first = first.min(gap.first);
+			last = last.max(gap.last);
+		}
+		!is_merged
+	});
+	let position = gaps.partition_point(|gap| gap.first < first);
+	gaps.insert(position, BlockRange { first, last });
+}
+
+/// Removes the block from the gaps, splitting the gap which contains it.
+pub fn remove(gaps: &mut Vec<BlockRange>, block_number: u32) {
+	let Some(position) = gaps.iter().position(|gap| gap.contains(block_number)) else {
+		return;
+	};
+	let gap = gaps.remove(position);
+	if block_number < gap.last {
+		gaps.insert(
+			position,
+			BlockRange {
+				first: block_number + 1,
+				last: gap.last,
+			},
+		);
+	}
+	if gap.first < block_number {
+		gaps.insert(
+			position,
+			BlockRange {
+				first: gap.first,
+				last: block_number - 1,
+			},
+		);
+	}
+}
+
+/// Runs backfill of the missed blocks.
+///
+/// # Arguments
+///
+/// * `network_client` - Network client used to fetch the missed cells
+/// * `rpc_client` - RPC client used to fetch the missed headers
+/// * `cfg` - Light client configuration
+/// * `interval` - Interval between the backfills
+/// * `scheduler` - Sampling scheduler shared with the light and sync clients
+/// * `dedup` - Processed blocks shared with the light and sync clients
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+	db: impl Database + Clone,
+	network_client: impl network::Client,
+	rpc_client: rpc::Client,
+	cfg: LightClientConfig,
+	metrics: Arc<impl Metrics>,
+	state: Arc<Mutex<State>>,
+	block_sender: broadcast::Sender<BlockVerified>,
+	commitment_mismatch_sender: broadcast::Sender<CommitmentMismatch>,
+	interval: Duration,
+	scheduler: Scheduler,
+	dedup: BlockDedup,
+) {
+	info!("Starting backfill...");
+	let mut interval = tokio::time::interval(interval);
+
+	loop {
+		interval.tick().await;
+
+		// Blocks which fail again are scheduled for the next interval
+		let gaps = state.lock().unwrap().missed.clone();
+		let missed_blocks = gaps.into_iter().flat_map(|gap| gap.first..=gap.last);
+		for block_number in missed_blocks {
+			let (header, header_hash) =
+				match rpc_client.get_header_by_block_number(block_number).await {
+					Ok(header) => header,
+					Err(error) => {
+						warn!(block_number, "Cannot fetch missed header: {error:#}");
+						break;
+					},
+				};
+
+			remove(&mut state.lock().unwrap().missed, block_number);
+			let claim = match dedup.claim(header_hash) {
+				Ok(claim) => claim,
+				Err(duplicate) => {
+					info!(block_number, "Skipping missed block, it is {duplicate}");
+					continue;
+				},
+			};
+			let cell_count = rpc::bounded_cell_count(cfg.confidence, cfg.max_cell_count);
+			let slot = scheduler.acquire(Priority::Sync).await;
+			let result = watchdog::process_block(
+				db.clone(),
+				&network_client,
+				&metrics,
+				&cfg,
+				cell_count,
+				header.clone(),
+				Instant::now(),
+				state.clone(),
+				&commitment_mismatch_sender,
+			)
+			.await;
+			drop(slot);
+
+			let confidence = match result {
+				Ok(confidence) => {
+					claim.complete();
+					confidence
+				},
+				Err(error) => {
+					error!(block_number, "Cannot process missed block: {error:#}");
+					let gap = BlockRange {
+						first: block_number,
+						last: block_number,
+					};
+					insert(&mut state.lock().unwrap().missed, gap);
+					break;
+				},
+			};
+			info!(block_number, "Missed block is processed");
+
+			match BlockVerified::try_from((header, confidence)) {
+				Ok(block) => {
+					if let Err(error) = block_sender.send(block) {
+						error!("Cannot send block verified message: {error}");
+					}
+				},
+				Err(error) => error!("Cannot create message from header: {error}"),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+
+	use super::{insert, remove};
+	use crate::types::BlockRange;
+
+	fn ranges(ranges: &[(u32, u32)]) -> Vec<BlockRange> {
+		ranges
+			.iter()
+			.map(|&(first, last)| BlockRange { first, last })
+			.collect()
+	}
+
+	#[test_case(&[], (5, 7) => ranges(&[(5, 7)]) ; "First gap")]
+	#[test_case(&[(1, 2), (10, 12)], (5, 7) => ranges(&[(1, 2), (5, 7), (10, 12)]) ; "Separate gap")]
+	#[test_case(&[(1, 2), (10, 12)], (3, 9) => ranges(&[(1, 12)]) ; "Adjacent gaps")]
+	#[test_case(&[(1, 5), (8, 12)], (4, 9) => ranges(&[(1, 12)]) ; "Overlapping gaps")]
+	fn test_insert(gaps: &[(u32, u32)], (first, last): (u32, u32)) -> Vec<BlockRange> {
+		let mut gaps = ranges(gaps);
+		insert(&mut gaps, BlockRange { first, last });
+		gaps
+	}
+
+	#[test_case(&[(5, 7)], 5 => ranges(&[(6, 7)]) ; "First block")]
+	#[test_case(&[(5, 7)], 7 => ranges(&[(5, 6)]) ; "Last block")]
+	#[test_case(&[(5, 7)], 6 => ranges(&[(5, 5), (7, 7)]) ; "Split gap")]
+	#[test_case(&[(5, 5), (8, 9)], 5 => ranges(&[(8, 9)]) ; "Filled gap")]
+	#[test_case(&[(5, 7)], 9 => ranges(&[(5, 7)]) ; "Block outside gaps")]
+	fn test_remove(gaps: &[(u32, u32)], block_number: u32) -> Vec<BlockRange> {
+		let mut gaps = ranges(gaps);
+		remove(&mut gaps, block_number);
+		gaps
+	}
+}
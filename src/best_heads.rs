@@ -0,0 +1,402 @@
+//! Sampling of the best (unfinalized) heads, so the cells of the new blocks are sampled before the blocks are finalized.
+
+use avail_subxt::{primitives::Header, utils::H256};
+use codec::Encode;
+use color_eyre::Result;
+use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
+use futures::StreamExt;
+use kate_recovery::{commitments, data::Cell, matrix::Dimensions};
+use sp_core::blake2_256;
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+	data::cell_cache::CellCache,
+	network::rpc::{self, Event},
+	proof::{self, Lane},
+	types::{LightClientConfig, State},
+	utils::{calculate_confidence, extract_kate},
+};
+
+/// Maximum number of the tracked best heads, the lowest heads are dropped first
+const MAX_TRACKED_HEADS: usize = 64;
+
+/// Best head which is sampled, but not finalized yet
+struct SampledHead {
+	number: u32,
+	parent_hash: H256,
+	confidence: Option<f64>,
+	cells: Vec<Cell>,
+}
+
+/// Latest sampled best head at the unfinalized height, as it is exposed over the API
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BestHead {
+	pub hash: H256,
+	pub confidence: Option<f64>,
+}
+
+/// Sampled best head, confirmed by the finalized block
+#[derive(Debug)]
+pub struct Confirmed {
+	pub number: u32,
+	pub hash: H256,
+	pub confidence: Option<f64>,
+	pub cells: Vec<Cell>,
+}
+
+/// Result of the reconciliation of the sampled best heads with the finalized block.
+#[derive(Debug, Default)]
+pub struct Reconciled {
+	/// Sampled heads on the finalized chain
+	pub confirmed: Vec<Confirmed>,
+	/// Sampled heads of the discarded forks, as block number and hash
+	pub discarded: Vec<(u32, H256)>,
+	/// Whether the finalized block differs from the block sampled at its height
+	pub reorged: bool,
+}
+
+/// Sampled best heads, tracked until their height is finalized.
+#[derive(Default)]
+pub struct BestHeads {
+	heads: HashMap<H256, SampledHead>,
+	finalized: u32,
+}
+
+impl BestHeads {
+	/// Returns `true` if the head is above the latest finalized block and is not tracked yet.
+	pub fn is_new(&self, number: u32, hash: &H256) -> bool {
+		number > self.finalized && !self.heads.contains_key(hash)
+	}
+
+	/// Tracks the sampled head, and returns hashes of the other tracked heads at the same height, which are on another fork.
+	/// Returns `None` if the height of the head is finalized in the meantime.
+	pub fn insert(
+		&mut self,
+		header: &Header,
+		hash: H256,
+		confidence: Option<f64>,
+		cells: Vec<Cell>,
+	) -> Option<Vec<H256>> {
+		if header.number <= self.finalized {
+			return None;
+		}
+		let forks = self
+			.heads
+			.iter()
+			.filter(|(other, head)| head.number == header.number && **other != hash)
+			.map(|(other, _)| *other)
+			.collect();
+		self.heads.insert(
+			hash,
+			SampledHead {
+				number: header.number,
+				parent_hash: header.parent_hash,
+				confidence,
+				cells,
+			},
+		);
+		while self.heads.len() > MAX_TRACKED_HEADS {
+			let Some(lowest) = self
+				.heads
+				.iter()
+				.min_by_key(|(_, head)| head.number)
+				.map(|(hash, _)| *hash)
+			else {
+				break;
+			};
+			self.heads.remove(&lowest);
+		}
+		Some(forks)
+	}
+
+	/// Reconciles the tracked heads with the finalized block, and stops tracking heads at or below its height.
+	/// Heads reachable from the finalized block by the parent hashes are confirmed, other heads are discarded.
+	pub fn finalize(&mut self, number: u32, hash: H256) -> Reconciled {
+		self.finalized = self.finalized.max(number);
+
+		let mut reconciled = Reconciled::default();
+		let mut canonical = Some(hash);
+		while let Some(hash) = canonical {
+			let Some(head) = self.heads.remove(&hash) else {
+				break;
+			};
+			canonical = Some(head.parent_hash);
+			reconciled.confirmed.push(Confirmed {
+				number: head.number,
+				hash,
+				confidence: head.confidence,
+				cells: head.cells,
+			});
+		}
+
+		let finalized = self.finalized;
+		self.heads.retain(|hash, head| {
+			if head.number > finalized {
+				return true;
+			}
+			reconciled.reorged |= head.number == number;
+			reconciled.discarded.push((head.number, *hash));
+			false
+		});
+		reconciled.discarded.sort();
+		reconciled
+	}
+}
+
+/// Samples the best head, returning the confidence if all cells are verified, and the verified cells.
+async fn sample(
+	rpc_client: &rpc::Client,
+	pp: Arc<PublicParameters>,
+	cfg: &LightClientConfig,
+	header: &Header,
+	hash: H256,
+) -> Result<Option<(Option<f64>, Vec<Cell>)>> {
+	let (rows, cols, _, commitment) = extract_kate(&header.extension);
+	let Some(dimensions) = Dimensions::new(rows, cols).filter(|d| d.cols().get() > 2) else {
+		debug!(
+			block_number = header.number,
+			"Skipping best head with invalid dimensions {rows}x{cols}"
+		);
+		return Ok(None);
+	};
+	let commitments = commitments::from_slice(&commitment)?;
+
+	let cell_count = rpc::bounded_cell_count(cfg.confidence, cfg.max_cell_count);
+	let positions = rpc::generate_cells(
+		dimensions,
+		cell_count,
+		cfg.sampling_seed.as_deref(),
+		hash,
+		cfg.sampling_strategy,
+	);
+	let mut cells = rpc_client.request_kate_proof(hash, &positions).await?;
+	let (verified, _) = proof::verify(
+		Lane::Head,
+		header.number,
+		dimensions,
+		&cells,
+		&commitments,
+		pp,
+	)
+	.await?;
+	cells.retain(|cell| verified.contains(&cell.position));
+
+	let confidence =
+		(cells.len() >= positions.len()).then(|| calculate_confidence(cells.len() as u32));
+	Ok(Some((confidence, cells)))
+}
+
+fn reconcile(
+	best_heads: &mut BestHeads,
+	cell_cache: &CellCache,
+	state: &Mutex<State>,
+	header: &Header,
+) {
+	let hash: H256 = Encode::using_encoded(header, blake2_256).into();
+	let reconciled = best_heads.finalize(header.number, hash);
+	state
+		.lock()
+		.expect("Lock should be acquired")
+		.best_heads
+		.retain(|&number, _| number > header.number);
+
+	if reconciled.reorged {
+		warn!(
+			block_number = header.number,
+			"Finalized block differs from the sampled best block, block is verified again"
+		);
+	}
+	for (block_number, hash) in reconciled.discarded {
+		info!(
+			block_number,
+			"Sampled best head {hash:?} is discarded by the finalized fork"
+		);
+	}
+	for confirmed in reconciled.confirmed {
+		debug!(
+			block_number = confirmed.number,
+			confidence = confirmed.confidence,
+			"Sampled best head {:?} is finalized",
+			confirmed.hash
+		);
+		cell_cache.insert(confirmed.number, &confirmed.cells);
+	}
+}
+
+/// Runs sampling of the best heads, until the finalized headers channel is closed.
+///
+/// # Arguments
+///
+/// * `rpc_client` - RPC client, used to subscribe to the best headers and to fetch the cells
+/// * `pp` - Public parameters used for the proof verification
+/// * `cfg` - Light client configuration, so the best heads are sampled on the same positions
+/// * `cell_cache` - Cache of the verified cells, shared with the light client
+/// * `state` - Shared state, into which the latest sampled heads are published
+/// * `rpc_events` - Finalized headers, used to reconcile the sampled heads
+pub async fn run(
+	rpc_client: rpc::Client,
+	pp: Arc<PublicParameters>,
+	cfg: LightClientConfig,
+	cell_cache: CellCache,
+	state: Arc<Mutex<State>>,
+	mut rpc_events: broadcast::Receiver<Event>,
+) {
+	info!("Starting best heads sampling...");
+
+	let best_headers = rpc_client.clone().best_header_stream();
+	futures::pin_mut!(best_headers);
+	let mut best_heads = BestHeads::default();
+
+	loop {
+		tokio::select! {
+			event = rpc_events.recv() => match event {
+				Ok(Event::HeaderUpdate { header, .. }) => {
+					reconcile(&mut best_heads, &cell_cache, &state, &header)
+				},
+				Ok(Event::ConnectionLost { .. } | Event::ConnectionRestored { .. }) => (),
+				Err(RecvError::Lagged(skipped)) => {
+					warn!("Best heads sampling is lagging behind, {skipped} finalized headers are skipped")
+				},
+				Err(RecvError::Closed) => {
+					error!("Cannot receive message: channel is closed");
+					return;
+				},
+			},
+			Some(header) = best_headers.next() => {
+				let hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+				if !best_heads.is_new(header.number, &hash) {
+					continue;
+				}
+				let (confidence, cells) =
+					match sample(&rpc_client, pp.clone(), &cfg, &header, hash).await {
+						Ok(Some(sampled)) => sampled,
+						Ok(None) => continue,
+						Err(error) => {
+							warn!(block_number = header.number, "Cannot sample best head: {error:#}");
+							continue;
+						},
+					};
+				info!(
+					block_number = header.number,
+					confidence,
+					"Sampled best head {hash:?}"
+				);
+				match best_heads.insert(&header, hash, confidence, cells) {
+					None => debug!(
+						block_number = header.number,
+						"Best head is finalized before it is sampled"
+					),
+					Some(forks) => {
+						if !forks.is_empty() {
+							info!(
+								block_number = header.number,
+								"Best head {hash:?} is on another fork than {forks:?}"
+							);
+						}
+						let best_head = BestHead { hash, confidence };
+						let mut state = state.lock().expect("Lock should be acquired");
+						state.best_heads.insert(header.number, best_head);
+					},
+				}
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3, HeaderExtension},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::Digest,
+		primitives::Header,
+	};
+	use sp_core::H256;
+
+	use super::BestHeads;
+
+	fn header(number: u32, parent_hash: H256) -> Header {
+		Header {
+			parent_hash,
+			number,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs: vec![] },
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 1,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+		}
+	}
+
+	fn hash(byte: u8) -> H256 {
+		H256::repeat_byte(byte)
+	}
+
+	#[test]
+	fn finalized_chain_is_confirmed() {
+		let mut best_heads = BestHeads::default();
+		best_heads.insert(&header(1, hash(0)), hash(1), Some(99.0), vec![]);
+		best_heads.insert(&header(2, hash(1)), hash(2), Some(99.0), vec![]);
+		best_heads.insert(&header(3, hash(2)), hash(3), None, vec![]);
+
+		let reconciled = best_heads.finalize(2, hash(2));
+		let confirmed = reconciled
+			.confirmed
+			.iter()
+			.map(|head| head.number)
+			.collect::<Vec<_>>();
+		assert_eq!(confirmed, vec![2, 1]);
+		assert!(reconciled.discarded.is_empty());
+		assert!(!reconciled.reorged);
+
+		assert!(!best_heads.is_new(2, &hash(22)));
+		assert!(!best_heads.is_new(3, &hash(3)));
+		assert!(best_heads.is_new(4, &hash(4)));
+	}
+
+	#[test]
+	fn forks_are_discarded_on_finalization() {
+		let mut best_heads = BestHeads::default();
+		best_heads.insert(&header(1, hash(0)), hash(1), Some(99.0), vec![]);
+		assert_eq!(
+			best_heads.insert(&header(2, hash(1)), hash(2), Some(99.0), vec![]),
+			Some(vec![])
+		);
+		// Reorg of the best chain at block 2
+		assert_eq!(
+			best_heads.insert(&header(2, hash(1)), hash(22), Some(99.0), vec![]),
+			Some(vec![hash(2)])
+		);
+		best_heads.insert(&header(3, hash(22)), hash(33), Some(99.0), vec![]);
+		assert_eq!(best_heads.finalize(1, hash(1)).confirmed.len(), 1);
+
+		// Finalized block 2 was not sampled, so the blocks sampled at its height are discarded
+		let reconciled = best_heads.finalize(2, hash(222));
+		assert!(reconciled.confirmed.is_empty());
+		assert_eq!(reconciled.discarded, vec![(2, hash(2)), (2, hash(22))]);
+		assert!(reconciled.reorged);
+
+		// Heads finalized in the meantime are not tracked
+		assert_eq!(
+			best_heads.insert(&header(2, hash(1)), hash(2), None, vec![]),
+			None
+		);
+	}
+}
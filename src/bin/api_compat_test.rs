@@ -1,7 +1,9 @@
 use avail_light::{
-	data::rocks_db::RocksDB,
-	network::rpc,
-	types::{ExponentialConfig, RetryConfig, State},
+	consts::ExpectedNodeVariant,
+	data::rocks_db::{RocksDB, RocksDBConfig},
+	network::rpc::{self, CircuitBreakerConfig, NodePolicy, RateLimitConfig, TransportConfig},
+	telemetry,
+	types::{ExponentialConfig, HealthCheckConfig, ParallelFetchConfig, RetryConfig, State},
 };
 use clap::Parser;
 use color_eyre::{eyre::Context, Result};
@@ -21,7 +23,7 @@ async fn main() -> Result<()> {
 	let command_args = CommandArgs::parse();
 	println!("Using URL: {}", command_args.url);
 	println!("Using Path: {}", command_args.avail_path);
-	let db = RocksDB::open(&command_args.avail_path)
+	let db = RocksDB::open(&command_args.avail_path, &RocksDBConfig::default())
 		.wrap_err("API Compatibility Test could not initialize database")?;
 
 	let state = Arc::new(Mutex::new(State::default()));
@@ -31,8 +33,23 @@ async fn main() -> Result<()> {
 		retries: 4,
 	});
 
-	let (rpc_client, _, subscriptions) =
-		rpc::init(db, state, &[command_args.url], "DEV", retry_cfg).await?;
+	let (rpc_client, _, subscriptions) = rpc::init(
+		db,
+		state,
+		&[command_args.url],
+		"DEV",
+		ExpectedNodeVariant::new(),
+		retry_cfg,
+		NodePolicy::default(),
+		CircuitBreakerConfig::default(),
+		RateLimitConfig::default(),
+		TransportConfig::default(),
+		ParallelFetchConfig::default(),
+		HealthCheckConfig::default(),
+		1,
+		Arc::new(telemetry::Registry::default()),
+	)
+	.await?;
 	tokio::spawn(subscriptions.run());
 
 	let mut correct: bool = true;
@@ -0,0 +1,178 @@
+use codec::Encode;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use hyper::{body, Client, Uri};
+use serde::{Deserialize, Serialize};
+use sp_core::{ed25519, Pair, H256};
+use tracing::info;
+
+use crate::{data::FinalitySyncCheckpoint, types::RuntimeConfig};
+
+/// Trusted state from which finality is synced, instead of syncing it from the genesis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TrustedCheckpoint {
+	pub block_hash: H256,
+	pub number: u32,
+	pub set_id: u64,
+	/// GRANDPA authorities (SS58 encoded ed25519 public keys) active at the checkpoint block
+	pub validator_set: Vec<ed25519::Public>,
+}
+
+impl TrustedCheckpoint {
+	/// Message signed by the checkpoint provider.
+	fn signing_payload(&self) -> Vec<u8> {
+		(
+			self.block_hash,
+			self.number,
+			self.set_id,
+			&self.validator_set,
+		)
+			.encode()
+	}
+}
+
+impl From<TrustedCheckpoint> for FinalitySyncCheckpoint {
+	fn from(checkpoint: TrustedCheckpoint) -> Self {
+		FinalitySyncCheckpoint {
+			number: checkpoint.number,
+			set_id: checkpoint.set_id,
+			validator_set: checkpoint.validator_set,
+		}
+	}
+}
+
+/// Checkpoint response of the checkpoint provider.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignedCheckpoint {
+	pub checkpoint: TrustedCheckpoint,
+	pub signature: ed25519::Signature,
+}
+
+impl SignedCheckpoint {
+	pub fn verify(self, public_key: &ed25519::Public) -> Result<TrustedCheckpoint> {
+		let payload = self.checkpoint.signing_payload();
+		if !<ed25519::Pair as Pair>::verify(&self.signature, payload, public_key) {
+			return Err(eyre!("Invalid checkpoint signature"));
+		}
+		Ok(self.checkpoint)
+	}
+}
+
+/// Checkpoint sync configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug, Default)]
+pub struct CheckpointConfig {
+	pub trusted_checkpoint: Option<TrustedCheckpoint>,
+	pub provider_url: Option<String>,
+	pub provider_public_key: Option<String>,
+}
+
+impl From<&RuntimeConfig> for CheckpointConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		CheckpointConfig {
+			trusted_checkpoint: val.trusted_checkpoint.clone(),
+			provider_url: val.checkpoint_provider_url.clone(),
+			provider_public_key: val.checkpoint_provider_public_key.clone(),
+		}
+	}
+}
+
+fn parse_public_key(public_key: &str) -> Result<ed25519::Public> {
+	let bytes = hex::decode(public_key.trim_start_matches("0x"))
+		.wrap_err("Checkpoint provider public key is not valid hex")?;
+	let raw: [u8; 32] = bytes
+		.try_into()
+		.map_err(|_| eyre!("Checkpoint provider public key must be 32 bytes long"))?;
+	Ok(ed25519::Public::from_raw(raw))
+}
+
+async fn fetch_signed_checkpoint(url: &str) -> Result<SignedCheckpoint> {
+	let uri: Uri = url.parse().wrap_err("Invalid checkpoint provider URL")?;
+	let response = Client::new()
+		.get(uri)
+		.await
+		.wrap_err("Checkpoint provider request failed")?;
+	if !response.status().is_success() {
+		return Err(eyre!(
+			"Checkpoint provider responded with status {}",
+			response.status()
+		));
+	}
+	let bytes = body::to_bytes(response.into_body())
+		.await
+		.wrap_err("Cannot read checkpoint provider response")?;
+	serde_json::from_slice(&bytes).wrap_err("Cannot decode checkpoint provider response")
+}
+
+/// Resolves trusted checkpoint, either from configuration or from the checkpoint provider.
+/// Checkpoint from configuration takes precedence, fetched checkpoint must be signed by the configured provider key.
+pub async fn resolve(cfg: &CheckpointConfig) -> Result<Option<TrustedCheckpoint>> {
+	if let Some(checkpoint) = &cfg.trusted_checkpoint {
+		info!("Using trusted checkpoint at block {}", checkpoint.number);
+		return Ok(Some(checkpoint.clone()));
+	}
+
+	let Some(url) = &cfg.provider_url else {
+		return Ok(None);
+	};
+
+	let public_key = cfg
+		.provider_public_key
+		.as_deref()
+		.ok_or_else(|| eyre!("Checkpoint provider public key is not configured"))
+		.and_then(parse_public_key)?;
+
+	let checkpoint = fetch_signed_checkpoint(url).await?.verify(&public_key)?;
+	info!(
+		"Fetched trusted checkpoint at block {} from {url}",
+		checkpoint.number
+	);
+	Ok(Some(checkpoint))
+}
+
+#[cfg(test)]
+mod tests {
+	use sp_core::{ed25519, Pair};
+
+	use super::{parse_public_key, SignedCheckpoint, TrustedCheckpoint};
+
+	fn checkpoint() -> TrustedCheckpoint {
+		TrustedCheckpoint {
+			block_hash: [1u8; 32].into(),
+			number: 100,
+			set_id: 3,
+			validator_set: vec![ed25519::Public::from_raw([2u8; 32])],
+		}
+	}
+
+	#[test]
+	fn verify_signed_checkpoint() {
+		let provider = ed25519::Pair::from_seed(&[7u8; 32]);
+		let checkpoint = checkpoint();
+		let signed = SignedCheckpoint {
+			signature: provider.sign(&checkpoint.signing_payload()),
+			checkpoint: checkpoint.clone(),
+		};
+		assert_eq!(signed.verify(&provider.public()).unwrap(), checkpoint);
+	}
+
+	#[test]
+	fn reject_tampered_checkpoint() {
+		let provider = ed25519::Pair::from_seed(&[7u8; 32]);
+		let mut signed = SignedCheckpoint {
+			signature: provider.sign(&checkpoint().signing_payload()),
+			checkpoint: checkpoint(),
+		};
+		signed.checkpoint.set_id = 4;
+		assert!(signed.verify(&provider.public()).is_err());
+	}
+
+	#[test]
+	fn parse_provider_public_key() {
+		let public_key = ed25519::Pair::from_seed(&[7u8; 32]).public();
+		let encoded = format!("0x{}", hex::encode(public_key.0));
+		assert_eq!(parse_public_key(&encoded).unwrap(), public_key);
+		assert!(parse_public_key("0x1234").is_err());
+	}
+}
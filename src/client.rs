@@ -0,0 +1,972 @@
+//! Light client which can be embedded into other Rust services, instead of running the `avail-light` binary.
+
+use avail_core::AppId;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use futures::FutureExt;
+use kate_recovery::com::AppData;
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use std::{
+	net::IpAddr,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tracing::{error, info, trace, warn};
+
+#[cfg(feature = "network-analysis")]
+use crate::network::p2p::analyzer;
+use crate::{
+	api::{
+		self,
+		v2::{
+			transactions::{Submit, Submitter},
+			types::{SubmitResponse, Transaction},
+		},
+	},
+	app_client::{self, AppClients},
+	checkpoint,
+	config_reload::{self, ConfigUpdate},
+	consts::{ExpectedNodeVariant, BLOCK_TIME_SECS, EXPECTED_SYSTEM_VERSION},
+	data::{self, cell_cache::CellCache, journal, BlockSamplingStats, Database, Key, Store},
+	dedup::BlockDedup,
+	event_bridge,
+	logs::{self, LogFilter},
+	maintenance::StaticConfigParams,
+	network::{self, p2p, rpc, tuning::BatchSize},
+	platform, proof,
+	retention::RetentionConfig,
+	scheduler::Scheduler,
+	shutdown::Controller,
+	supervisor,
+	sync_client::SyncClient,
+	sync_finality::SyncFinality,
+	telemetry::{
+		self,
+		otlp::MetricAttributes,
+		push::{PushConfig, PushLabels},
+		reporter::{ClientInfo, ReporterConfig},
+	},
+	types::{
+		BlockVerified, BootstrapConfig, ClientChannels, IdentityConfig, LibP2PConfig,
+		RuntimeConfig, State,
+	},
+	utils::calculate_confidence,
+	verification::CommitmentMismatch,
+	webhooks,
+};
+
+const CLIENT_ROLE: &str = if cfg!(feature = "crawl") {
+	"crawler"
+} else {
+	"lightnode"
+};
+
+/// Shutdown reason of the client started with [`LightClientBuilder::exit_after_sync`], after the historical blocks are synced
+pub const SYNC_FINISHED: &str = "Historical sync finished";
+
+type ConfigLoader = Box<dyn Fn() -> Result<RuntimeConfig> + Send + Sync>;
+
+/// Event emitted by the running light client.
+#[derive(Debug, Clone)]
+pub enum Event {
+	/// Block is sampled, with the confidence if it is achieved
+	BlockVerified(BlockVerified),
+	/// Data of the followed application is reconstructed
+	DataVerified {
+		app_id: u32,
+		block_number: u32,
+		data: AppData,
+	},
+	/// Header extension of the block does not match the sampled data
+	CommitmentMismatch(CommitmentMismatch),
+}
+
+/// Builder of the [`LightClient`].
+pub struct LightClientBuilder {
+	cfg: RuntimeConfig,
+	identity_cfg: IdentityConfig,
+	shutdown: Controller<String>,
+	log_filter: Option<Arc<LogFilter>>,
+	exit_after_sync: bool,
+	config_reload: Option<(PathBuf, ConfigLoader)>,
+}
+
+impl LightClientBuilder {
+	/// Uses the given shutdown controller, so the client is stopped together with the embedding service.
+	pub fn shutdown(mut self, shutdown: Controller<String>) -> Self {
+		self.shutdown = shutdown;
+		self
+	}
+
+	/// Reloadable log filter of the embedding service, used by the admin API and configuration reload.
+	pub fn log_filter(mut self, log_filter: Arc<LogFilter>) -> Self {
+		self.log_filter = Some(log_filter);
+		self
+	}
+
+	/// Triggers the shutdown with the [`SYNC_FINISHED`] reason once the historical blocks are synced.
+	pub fn exit_after_sync(mut self, exit_after_sync: bool) -> Self {
+		self.exit_after_sync = exit_after_sync;
+		self
+	}
+
+	/// Watches the configuration file for changes, and applies the reloadable options loaded by `load`.
+	pub fn config_reload(
+		mut self,
+		path: PathBuf,
+		load: impl Fn() -> Result<RuntimeConfig> + Send + Sync + 'static,
+	) -> Self {
+		self.config_reload = Some((path, Box::new(load)));
+		self
+	}
+
+	/// Starts the light client subsystems, and returns once the first finalized header is received.
+	pub async fn start(self) -> Result<LightClient> {
+		let LightClientBuilder {
+			mut cfg,
+			identity_cfg,
+			shutdown,
+			log_filter,
+			exit_after_sync,
+			config_reload,
+		} = self;
+
+		let client_role = if cfg.is_fat_client() {
+			info!("Fat client mode");
+			"fatnode"
+		} else {
+			CLIENT_ROLE
+		};
+
+		let version = clap::crate_version!();
+		info!("Running Avail light client version: {version}. Role: {client_role}.");
+		info!("Using config: {cfg:?}");
+		info!("Avail address is: {}", &identity_cfg.avail_address);
+
+		if !cfg.has_bootstrap_sources() {
+			Err(eyre!("Bootstrap sources must not be empty. Either use a '--network' flag or add a list of bootstrap nodes in the configuration file"))?
+		}
+
+		// Browser storage is opened asynchronously
+		#[cfg(not(target_arch = "wasm32"))]
+		let db = Store::open(&(&cfg).into());
+		#[cfg(target_arch = "wasm32")]
+		let db = Store::open(&(&cfg).into()).await;
+		let db = db.wrap_err("Avail Light could not initialize database")?;
+		let repair = journal::repair(&db).wrap_err("Database consistency repair failed")?;
+		if !repair.is_empty() {
+			warn!(
+				"Removed verification results of the interrupted blocks {:?}, and confidence of the blocks without header {:?}",
+				repair.interrupted, repair.orphaned
+			);
+		}
+
+		let cfg_libp2p: LibP2PConfig = (&cfg).into();
+		let (id_keys, peer_id) = p2p::keypair(&cfg_libp2p, &db)?;
+
+		let metric_attributes = MetricAttributes {
+			role: client_role.into(),
+			peer_id,
+			ip: RwLock::new("".to_string()),
+			multiaddress: RwLock::new("".to_string()), // Default value is empty until first processed block triggers an update,
+			origin: cfg.origin.clone(),
+			avail_address: identity_cfg.avail_address.clone(),
+			operating_mode: cfg.operation_mode.to_string(),
+			partition_size: cfg
+				.block_matrix_partition
+				.map(|partition| format!("{}/{}", partition.number, partition.fraction))
+				.unwrap_or("n/a".to_string()),
+		};
+
+		let ot_metrics =
+			telemetry::otlp::initialize(cfg.ot_collector_endpoint.clone(), metric_attributes)
+				.wrap_err("Unable to initialize OpenTelemetry service")?;
+		let mut metrics = telemetry::Registry::default().with_backend(Arc::new(ot_metrics));
+
+		let push_cfg: PushConfig = (&cfg).into();
+		let prometheus_metrics = (cfg.prometheus_metrics_enable || push_cfg.endpoint.is_some())
+			.then(|| Arc::new(telemetry::prometheus::Metrics::new()));
+		if let Some(prometheus_metrics) = prometheus_metrics.as_ref() {
+			metrics = metrics.with_backend(prometheus_metrics.clone());
+		}
+		if let (Some(prometheus_metrics), Some(_)) =
+			(prometheus_metrics.as_ref(), &push_cfg.endpoint)
+		{
+			let mut network = cfg.genesis_hash.trim_start_matches("0x").to_string();
+			network.truncate(6);
+			let labels = PushLabels {
+				node_id: peer_id.to_string(),
+				network,
+				role: client_role.to_string(),
+			};
+			platform::spawn(shutdown.with_cancel(telemetry::push::run(
+				prometheus_metrics.clone(),
+				push_cfg,
+				labels,
+			)));
+		}
+		let reporter_cfg: ReporterConfig = (&cfg).into();
+		if reporter_cfg.endpoint.is_some() {
+			let stats = Arc::new(telemetry::reporter::Stats::new(cfg.confidence));
+			metrics = metrics.with_backend(stats.clone());
+			let client_info = ClientInfo {
+				version: version.to_string(),
+				role: client_role.to_string(),
+				genesis_hash: cfg.genesis_hash.clone(),
+			};
+			platform::spawn(shutdown.with_cancel(async move {
+				if let Err(error) = telemetry::reporter::run(stats, reporter_cfg, client_info).await
+				{
+					error!("Telemetry reporter failed: {error:#}");
+				}
+			}));
+		}
+		let metrics = Arc::new(metrics);
+
+		proof::init((&cfg).into())?;
+		let pp = Arc::new(kate_recovery::couscous::public_params());
+		let raw_pp = pp.to_raw_var_bytes();
+		let public_params_hash = hex::encode(sp_core::blake2_128(&raw_pp));
+		let public_params_len = hex::encode(raw_pp).len();
+		trace!("Public params ({public_params_len}): hash: {public_params_hash}");
+
+		// Create sender channel for P2P event loop commands
+		let (p2p_event_loop_sender, p2p_event_loop_receiver) = mpsc::unbounded_channel();
+		let p2p_event_loop_receiver = Arc::new(tokio::sync::Mutex::new(p2p_event_loop_receiver));
+		// Gossip messages are received through the shared sender, so the subscriptions survive the event loop restarts
+		let (gossip_tx, _) = broadcast::channel::<p2p::GossipMessage>(1 << 7);
+		// Commitments of the blocks are received through the shared receiver, used for the DHT record validation
+		let (commitments_sender, commitments_receiver) = mpsc::unbounded_channel();
+		let commitments_receiver = Arc::new(tokio::sync::Mutex::new(commitments_receiver));
+
+		if cfg.proxy.is_some() && cfg.quic_transport_enable {
+			warn!("QUIC connections cannot be proxied, QUIC transport is disabled");
+		}
+
+		let mut listen_addresses = vec![];
+		for &ip in &cfg.listen_ips {
+			if cfg.ws_transport_enable || cfg.tcp_transport_enable {
+				listen_addresses.push((
+					"TCP",
+					construct_multiaddress(ip, cfg.ws_transport_enable, cfg.port),
+				));
+			}
+			if !cfg.ws_transport_enable && cfg.quic_transport_enable && cfg.proxy.is_none() {
+				listen_addresses.push(("QUIC", construct_quic_multiaddress(ip, cfg.port)));
+			}
+		}
+
+		// Event loop disconnects the peers on shutdown, and delays the shutdown until it is stopped.
+		// If it fails, it is restarted with the new swarm listening on the same addresses,
+		// and the routing table is bootstrapped again by the bootstrap retries.
+		let (is_fat_client, ws_transport_enable) = (cfg.is_fat_client(), cfg.ws_transport_enable);
+		let event_loop_shutdown = shutdown.clone();
+		let event_loop_metrics = metrics.clone();
+		let event_loop_gossip_tx = gossip_tx.clone();
+		let event_loop_pp = pp.clone();
+		tokio::spawn(supervisor::supervise(
+			"p2p_event_loop",
+			cfg.subsystem_restart_policy.clone(),
+			shutdown.clone(),
+			metrics.clone(),
+			move || {
+				let (cfg_libp2p, id_keys) = (cfg_libp2p.clone(), id_keys.clone());
+				let listen_addresses = listen_addresses.clone();
+				let shutdown = event_loop_shutdown.clone();
+				let (metrics, receiver) =
+					(event_loop_metrics.clone(), p2p_event_loop_receiver.clone());
+				let gossip_sender = event_loop_gossip_tx.clone();
+				let (pp, commitments_receiver) =
+					(event_loop_pp.clone(), commitments_receiver.clone());
+				async move {
+					let mut event_loop = p2p::EventLoop::new(
+						cfg_libp2p,
+						&id_keys,
+						is_fat_client,
+						ws_transport_enable,
+						gossip_sender,
+						pp,
+						shutdown,
+					)
+					.await;
+					for (transport, addr) in listen_addresses {
+						event_loop
+							.listen_on(addr.clone())
+							.wrap_err_with(|| format!("Listening on {transport} not to fail."))?;
+						info!("{transport} listener started on {addr}");
+					}
+					event_loop
+						.run(metrics, receiver, commitments_receiver)
+						.await;
+					Ok(())
+				}
+			},
+		));
+
+		let p2p_client = p2p::Client::new(
+			p2p_event_loop_sender,
+			BatchSize::new(
+				"DHT parallelization",
+				cfg.dht_parallelization_limit,
+				cfg.fetch_tuning(cfg.dht_parallelization_min, cfg.dht_parallelization_max),
+			),
+			cfg.dht_record_ttl(),
+			cfg.dht_put_quorum(),
+			cfg.retry_policies.dht.clone(),
+		);
+
+		let (config_update_tx, _) = broadcast::channel::<ConfigUpdate>(16);
+
+		let p2p_clone = p2p_client.to_owned();
+		let bootstrap_cfg: BootstrapConfig = (&cfg).into();
+		let bootstrap_update_receiver = config_update_tx.subscribe();
+		let db_clone = db.clone();
+		tokio::spawn(shutdown.with_cancel(async move {
+			match p2p::restore_peers(&p2p_clone, &db_clone).await {
+				Ok(restored) => info!("Restored {restored} peers from the peer store"),
+				Err(e) => warn!("Unable to restore peers from the peer store: {e:#}"),
+			}
+
+			info!("Bootstraping the DHT with bootstrap nodes...");
+			let bootstrap_peers = p2p::bootstrap_peers(&bootstrap_cfg).await;
+			let bs_result = p2p_clone.bootstrap_on_startup(bootstrap_peers).await;
+			match bs_result {
+				Ok(_) => {
+					info!("Bootstrap done.");
+				},
+				Err(e) => {
+					warn!("Bootstrap process: {e:?}.");
+				},
+			}
+
+			p2p::retry_bootstrap(p2p_clone, bootstrap_cfg, bootstrap_update_receiver).await;
+		}));
+
+		#[cfg(feature = "network-analysis")]
+		platform::spawn(shutdown.with_cancel(analyzer::start_traffic_analyzer(cfg.port, 10)));
+
+		let state = Arc::new(Mutex::new(State::default()));
+		let (rpc_client, rpc_events, rpc_subscriptions) = rpc::init(
+			db.clone(),
+			state.clone(),
+			&cfg.full_node_ws,
+			&cfg.genesis_hash,
+			ExpectedNodeVariant {
+				allow_mismatch: cfg.allow_version_mismatch,
+				version_requirement: cfg.node_version_requirement.clone(),
+				..ExpectedNodeVariant::new()
+			},
+			cfg.retry_config.clone(),
+			(&cfg).into(),
+			(&cfg).into(),
+			(&cfg).into(),
+			(&cfg).into(),
+			(&cfg).into(),
+			(&cfg).into(),
+			cfg.rpc_connect_race_nodes,
+			cfg.rpc_event_buffer_size,
+			metrics.clone(),
+		)
+		.await?;
+
+		if cfg.full_node_dns_resolution_enable {
+			let refresh_interval = Duration::from_secs(cfg.full_node_dns_refresh_interval.max(1));
+			platform::spawn(
+				shutdown.with_cancel(rpc_client.clone().resolve_nodes(refresh_interval)),
+			);
+		}
+
+		// Subscribing to RPC events before first event is published
+		let publish_rpc_event_receiver = rpc_events.subscribe();
+		let first_header_rpc_event_receiver = rpc_events.subscribe();
+		let client_rpc_event_receiver = rpc_events.subscribe();
+		let best_heads_rpc_event_receiver = cfg
+			.best_head_sampling_enable
+			.then(|| rpc_events.subscribe());
+		#[cfg(feature = "crawl")]
+		let crawler_rpc_event_receiver = rpc_events.subscribe();
+
+		if cfg.record_validation {
+			let max_blocks = (cfg.dht_record_ttl() / BLOCK_TIME_SECS) as usize;
+			tokio::spawn(shutdown.with_cancel(p2p::forward_commitments(
+				db.clone(),
+				max_blocks,
+				rpc_events.subscribe(),
+				commitments_sender,
+			)));
+		}
+
+		// spawn the RPC Network task for Event Loop to run in the background
+		// and shut it down, without delays
+		let rpc_subscriptions_handle = tokio::spawn(shutdown.with_cancel(shutdown.with_trigger(
+			"Subscription loop failure triggered shutdown".to_string(),
+			async {
+				let result = rpc_subscriptions.run().await;
+				if let Err(ref err) = result {
+					error!(%err, "Subscription loop ended with error");
+				};
+				result
+			},
+		)));
+
+		info!("Waiting for first finalized header...");
+		let block_header = match shutdown
+			.with_cancel(rpc::wait_for_finalized_header(
+				first_header_rpc_event_receiver,
+				360,
+			))
+			.await
+		{
+			Ok(Err(report)) => {
+				if !rpc_subscriptions_handle.is_finished() {
+					return Err(report);
+				}
+				let Ok(Ok(Err(subscriptions_error))) = rpc_subscriptions_handle.await else {
+					return Err(report);
+				};
+				return Err(eyre!(subscriptions_error));
+			},
+			Ok(Ok(num)) => num,
+			Err(shutdown_reason) => {
+				if !rpc_subscriptions_handle.is_finished() {
+					return Err(eyre!(shutdown_reason));
+				}
+				let Ok(Ok(Err(event_loop_error))) = rpc_subscriptions_handle.await else {
+					return Err(eyre!(shutdown_reason));
+				};
+				return Err(eyre!(event_loop_error));
+			},
+		};
+
+		state.lock().unwrap().latest = block_header.number;
+		let sync_range = cfg.sync_range(block_header.number);
+		// Sync depth is resolved into the starting block, relative to the latest finalized block
+		if cfg.sync_start_block.is_none() && !sync_range.is_empty() {
+			cfg.sync_start_block = Some(sync_range.start);
+		}
+
+		let ws_clients = api::v2::types::WsClients::default();
+
+		if let Some(port) = cfg.grpc_server_port {
+			let grpc_server = api::grpc::Server {
+				db: db.clone(),
+				cfg: cfg.clone(),
+				identity_cfg: identity_cfg.clone(),
+				state: state.clone(),
+				node_client: rpc_client.clone(),
+				ws_clients: ws_clients.clone(),
+				shutdown: shutdown.clone(),
+			};
+			// Servers stop gracefully on shutdown, completing the requests in progress
+			platform::spawn(shutdown.with_delay(grpc_server.bind(port))?);
+		}
+
+		let (block_tx, block_rx) = broadcast::channel::<BlockVerified>(1 << 7);
+
+		let (data_tx, data_rx) = broadcast::channel::<(AppId, u32, AppData)>(1 << 7);
+		let (commitment_mismatch_tx, _) = broadcast::channel::<CommitmentMismatch>(1 << 7);
+		let cell_cache = CellCache::new(cfg.cell_cache_size);
+		let app_clients = Arc::new(AppClients::new(
+			(&cfg).into(),
+			db.clone(),
+			p2p_client.clone(),
+			rpc_client.clone(),
+			cell_cache.clone(),
+			block_tx.clone(),
+			pp.clone(),
+			state.clone(),
+			sync_range.clone(),
+			data_tx.clone(),
+			shutdown.clone(),
+		));
+		for app_id in cfg.followed_app_ids() {
+			app_clients.add(AppId(app_id));
+		}
+
+		if let Some(signer) = identity_cfg.attestation_signer.clone() {
+			let gossip_client = cfg.attestation_gossip.then(|| p2p_client.clone());
+			platform::spawn(shutdown.with_cancel(crate::attestation::run(
+				db.clone(),
+				signer,
+				gossip_client,
+				block_tx.subscribe(),
+			)));
+		} else if cfg.attestation_gossip {
+			warn!("Attestation signing key is not configured, attestations are not published");
+		}
+
+		// Received gossip messages are propagated only after they are validated
+		if cfg.sampling_gossip || cfg.attestation_gossip {
+			// Fat client doesn't sample blocks, so it has no sampling summaries to share
+			let publish_summaries = cfg.sampling_gossip && !cfg.is_fat_client();
+			platform::spawn(shutdown.with_cancel(crate::gossip::run(
+				db.clone(),
+				p2p_client.clone(),
+				metrics.clone(),
+				publish_summaries,
+				block_tx.subscribe(),
+				gossip_tx.subscribe(),
+			)));
+		}
+
+		// Signed extrinsics can be submitted even if app mode is not active
+		let submitter = Submitter::new(
+			rpc_client.clone(),
+			cfg.app_id,
+			identity_cfg.clone(),
+			db.clone(),
+			ws_clients.clone(),
+		);
+
+		// Spawn tokio task which runs one http server for handling RPC
+		let server = api::server::Server {
+			db: db.clone(),
+			cfg: cfg.clone(),
+			identity_cfg,
+			state: state.clone(),
+			version: format!("v{}", clap::crate_version!()),
+			network_version: EXPECTED_SYSTEM_VERSION[0].to_string(),
+			node_client: rpc_client.clone(),
+			p2p_client: p2p_client.clone(),
+			ws_clients: ws_clients.clone(),
+			app_clients: app_clients.clone(),
+			prometheus_metrics: prometheus_metrics.filter(|_| cfg.prometheus_metrics_enable),
+			log_filter: log_filter.clone().filter(|_| cfg.admin_api_enable),
+			shutdown: shutdown.clone(),
+		};
+		platform::spawn(shutdown.with_delay(server.bind())?);
+
+		platform::spawn(shutdown.with_cancel(api::v2::publish(
+			api::v2::types::Topic::HeaderVerified,
+			publish_rpc_event_receiver,
+			ws_clients.clone(),
+		)));
+
+		platform::spawn(shutdown.with_cancel(api::v2::publish(
+			api::v2::types::Topic::ConfidenceAchieved,
+			block_tx.subscribe(),
+			ws_clients.clone(),
+		)));
+
+		platform::spawn(shutdown.with_cancel(api::v2::publish(
+			api::v2::types::Topic::CommitmentMismatch,
+			commitment_mismatch_tx.subscribe(),
+			ws_clients.clone(),
+		)));
+
+		platform::spawn(shutdown.with_cancel(api::v2::publish(
+			api::v2::types::Topic::DataVerified,
+			data_rx,
+			ws_clients,
+		)));
+
+		platform::spawn(shutdown.with_cancel(webhooks::run(
+			(&cfg).into(),
+			block_tx.clone(),
+			data_tx.clone(),
+		)));
+
+		platform::spawn(shutdown.with_cancel(event_bridge::run(
+			(&cfg).into(),
+			block_tx.clone(),
+			commitment_mismatch_tx.clone(),
+			data_tx.clone(),
+		)));
+
+		#[cfg(feature = "crawl")]
+		if cfg.crawl.crawl_block {
+			let partition = cfg.crawl.crawl_block_matrix_partition;
+			platform::spawn(shutdown.with_cancel(crate::crawl_client::run(
+				crawler_rpc_event_receiver,
+				p2p_client.clone(),
+				cfg.crawl.crawl_block_delay,
+				metrics.clone(),
+				cfg.crawl.crawl_block_mode,
+				partition.unwrap_or(crate::crawl_client::ENTIRE_BLOCK),
+			)));
+		}
+
+		// Sampling slots are shared by the light and sync clients
+		let scheduler = Scheduler::new(&(&cfg).into());
+		// Blocks received by more than one client are processed once
+		let dedup = BlockDedup::default();
+
+		if cfg.sync_start_block.is_some() {
+			state.lock().unwrap().synced.replace(false);
+			let sync_client = SyncClient::new(db.clone(), rpc_client.clone());
+			let (p2p_client, rpc_client) = (p2p_client.clone(), rpc_client.clone());
+			let (pp, cell_cache, block_tx) = (pp.clone(), cell_cache.clone(), block_tx.clone());
+			let (state, sync_shutdown) = (state.clone(), shutdown.clone());
+			let (sync_scheduler, sync_dedup) = (scheduler.clone(), dedup.clone());
+			let sync_cfg = cfg.clone();
+			// Sync is restarted on failure, and continues from the stored sync progress
+			let sync = supervisor::supervise(
+				"sync_client",
+				cfg.subsystem_restart_policy.clone(),
+				shutdown.clone(),
+				metrics.clone(),
+				move || {
+					let sync_network_client = network::new(
+						p2p_client.clone(),
+						rpc_client.clone(),
+						pp.clone(),
+						sync_cfg.disable_rpc,
+						cell_cache.clone(),
+						sync_cfg.retry_policies.cell_fetch.clone(),
+						sync_cfg.cell_reconstruction_enable,
+						proof::Lane::Sync,
+					);
+					crate::sync_client::run(
+						sync_client.clone(),
+						sync_network_client,
+						(&sync_cfg).into(),
+						sync_range.clone(),
+						block_tx.clone(),
+						state.clone(),
+						sync_scheduler.clone(),
+						sync_dedup.clone(),
+						sync_shutdown.clone(),
+					)
+					.map(Ok)
+				},
+			);
+			// Sync stops on shutdown after the processed block results are stored
+			if exit_after_sync {
+				platform::spawn(shutdown.with_trigger(SYNC_FINISHED.to_string(), sync));
+			} else {
+				platform::spawn(sync);
+			}
+		} else if exit_after_sync {
+			return Err(eyre!(
+				"There are no blocks to sync, sync start block or depth has to be set"
+			));
+		}
+
+		if cfg.sync_finality_enable {
+			let sync_finality = SyncFinality::new(db.clone(), rpc_client.clone());
+			if let Some(checkpoint) = checkpoint::resolve(&(&cfg).into()).await? {
+				crate::sync_finality::import_checkpoint(&sync_finality, checkpoint).await?;
+			}
+			platform::spawn(shutdown.with_cancel(crate::sync_finality::run(
+				sync_finality,
+				shutdown.clone(),
+				state.clone(),
+				block_header.clone(),
+				cfg.retry_policies.header_query.clone(),
+			)));
+		} else {
+			let mut s = state
+				.lock()
+				.map_err(|e| eyre!("State mutex is poisoned: {e:#}"))?;
+			warn!("Finality sync is disabled! Implicitly, blocks before LC startup will be considered verified as final");
+			s.finality_synced = true;
+		}
+
+		let static_config_params = StaticConfigParams {
+			block_confidence_treshold: cfg.confidence,
+			replication_factor: cfg.replication_factor,
+			query_timeout: cfg.query_timeout,
+			pruning_interval: cfg.store_pruning_interval,
+		};
+
+		platform::spawn(shutdown.with_cancel(crate::maintenance::run(
+			p2p_client.clone(),
+			db.clone(),
+			metrics.clone(),
+			block_rx,
+			static_config_params,
+			state.clone(),
+			shutdown.clone(),
+		)));
+
+		if let Some(blocks) = cfg.retention_blocks() {
+			let retention_cfg = RetentionConfig {
+				blocks,
+				interval: Duration::from_secs(cfg.retention_interval),
+				avail_path: cfg.avail_path.clone(),
+			};
+			platform::spawn(shutdown.with_cancel(crate::retention::run(
+				db.clone(),
+				retention_cfg,
+				state.clone(),
+				app_clients.clone(),
+			)));
+		}
+
+		if let Some(log_filter) = log_filter {
+			platform::spawn(shutdown.with_cancel(logs::apply_updates(
+				log_filter,
+				config_update_tx.subscribe(),
+			)));
+		}
+		platform::spawn(shutdown.with_cancel(app_client::apply_updates(
+			app_clients.clone(),
+			cfg.app_ids.clone(),
+			config_update_tx.subscribe(),
+		)));
+
+		let channels = ClientChannels {
+			block_sender: block_tx.clone(),
+			commitment_mismatch_sender: commitment_mismatch_tx.clone(),
+			rpc_event_receiver: client_rpc_event_receiver,
+			config_update_receiver: config_update_tx.subscribe(),
+		};
+
+		if cfg.is_fat_client() {
+			let fat_client = crate::fat_client::new(p2p_client.clone(), rpc_client.clone());
+
+			platform::spawn(shutdown.with_cancel(crate::fat_client::run(
+				fat_client,
+				db.clone(),
+				(&cfg).into(),
+				metrics.clone(),
+				state.clone(),
+				channels,
+				pp,
+				shutdown.clone(),
+			)));
+		} else {
+			let backfill_network_client = network::new(
+				p2p_client.clone(),
+				rpc_client.clone(),
+				pp.clone(),
+				cfg.disable_rpc,
+				cell_cache.clone(),
+				cfg.retry_policies.cell_fetch.clone(),
+				cfg.cell_reconstruction_enable,
+				proof::Lane::Sync,
+			);
+			platform::spawn(shutdown.with_cancel(crate::backfill::run(
+				db.clone(),
+				backfill_network_client,
+				rpc_client.clone(),
+				(&cfg).into(),
+				metrics.clone(),
+				state.clone(),
+				block_tx.clone(),
+				commitment_mismatch_tx.clone(),
+				Duration::from_secs(cfg.backfill_interval.max(1)),
+				scheduler.clone(),
+				dedup.clone(),
+			)));
+
+			if let Some(rpc_event_receiver) = best_heads_rpc_event_receiver {
+				if cfg.disable_rpc {
+					warn!("Best heads sampling is disabled, since it requires RPC");
+				} else {
+					platform::spawn(shutdown.with_cancel(crate::best_heads::run(
+						rpc_client.clone(),
+						pp.clone(),
+						(&cfg).into(),
+						cell_cache.clone(),
+						state.clone(),
+						rpc_event_receiver,
+					)));
+				}
+			}
+
+			let light_network_client = network::new(
+				p2p_client,
+				rpc_client.clone(),
+				pp,
+				cfg.disable_rpc,
+				cell_cache,
+				cfg.retry_policies.cell_fetch.clone(),
+				cfg.cell_reconstruction_enable,
+				proof::Lane::Head,
+			);
+
+			platform::spawn(shutdown.with_cancel(crate::light_client::run(
+				db.clone(),
+				light_network_client,
+				rpc_client,
+				(&cfg).into(),
+				metrics,
+				state.clone(),
+				channels,
+				scheduler,
+				dedup,
+				shutdown.clone(),
+			)));
+		}
+
+		if let Some((config_path, load)) = config_reload {
+			platform::spawn(shutdown.with_cancel(config_reload::run(
+				config_path,
+				Duration::from_secs(cfg.config_reload_interval),
+				load,
+				cfg.clone(),
+				config_update_tx,
+			)));
+		}
+
+		Ok(LightClient {
+			db,
+			state,
+			submitter,
+			shutdown_timeout: Duration::from_secs(cfg.shutdown_timeout),
+			block_sender: block_tx,
+			data_sender: data_tx,
+			commitment_mismatch_sender: commitment_mismatch_tx,
+			shutdown,
+		})
+	}
+}
+
+/// Running light client, with the access to its sampling results.
+pub struct LightClient {
+	db: Store,
+	state: Arc<Mutex<State>>,
+	submitter: Submitter<Store>,
+	shutdown_timeout: Duration,
+	block_sender: broadcast::Sender<BlockVerified>,
+	data_sender: broadcast::Sender<(AppId, u32, AppData)>,
+	commitment_mismatch_sender: broadcast::Sender<CommitmentMismatch>,
+	shutdown: Controller<String>,
+}
+
+impl LightClient {
+	pub fn builder(cfg: RuntimeConfig, identity_cfg: IdentityConfig) -> LightClientBuilder {
+		LightClientBuilder {
+			cfg,
+			identity_cfg,
+			shutdown: Controller::new(),
+			log_filter: None,
+			exit_after_sync: false,
+			config_reload: None,
+		}
+	}
+
+	/// Returns stream of the client events, emitted after the stream is created.
+	/// Events are skipped if the stream is not polled fast enough.
+	pub fn events(&self) -> impl Stream<Item = Event> + Send + Unpin {
+		let blocks = BroadcastStream::new(self.block_sender.subscribe())
+			.filter_map(|block| block.ok().map(Event::BlockVerified));
+		let data = BroadcastStream::new(self.data_sender.subscribe()).filter_map(|data| {
+			data.ok()
+				.map(|(app_id, block_number, data)| Event::DataVerified {
+					app_id: app_id.0,
+					block_number,
+					data,
+				})
+		});
+		let mismatches = BroadcastStream::new(self.commitment_mismatch_sender.subscribe())
+			.filter_map(|mismatch| mismatch.ok().map(Event::CommitmentMismatch));
+		blocks.merge(data).merge(mismatches)
+	}
+
+	/// Latest finalized block number.
+	pub fn latest_block(&self) -> u32 {
+		self.state.lock().expect("Lock should be acquired").latest
+	}
+
+	/// Returns confidence of the sampled block, or `None` if the block is not sampled.
+	pub fn confidence(&self, block_number: u32) -> Result<Option<f64>> {
+		let count = data::get_canonical::<u32>(&self.db, block_number, Key::VerifiedCellCount)?;
+		Ok(count.map(calculate_confidence))
+	}
+
+	/// Returns sampled cells of the block, with their source and verification result.
+	pub fn sampling_stats(&self, block_number: u32) -> Result<Option<BlockSamplingStats>> {
+		self.db.get(Key::BlockSamplingStats(block_number))
+	}
+
+	/// Returns reconstructed data of the followed application, or `None` if the data is not verified.
+	pub fn app_data(&self, app_id: u32, block_number: u32) -> Result<Option<AppData>> {
+		data::get_canonical(&self.db, block_number, |block_number, block_hash| {
+			Key::AppData(app_id, block_number, block_hash)
+		})
+	}
+
+	/// Submits the data transaction of the followed application, or the signed extrinsic.
+	/// Data transactions are signed with the Avail account key, so they can be submitted only in app mode.
+	pub async fn submit(&self, transaction: Transaction) -> Result<SubmitResponse> {
+		if matches!(transaction, Transaction::Data(_)) && !self.submitter.is_data_enabled() {
+			return Err(eyre!("Data transactions can be submitted only in app mode"));
+		}
+		self.submitter.submit(transaction).await
+	}
+
+	/// Waits until the shutdown is triggered, either by the subsystem failure or by the embedding service.
+	/// Subsystems are given up to the shutdown timeout to stop, and pending store writes are flushed.
+	/// Returns the shutdown reason.
+	pub async fn stopped(self) -> String {
+		let reason = self.shutdown.triggered_shutdown().await;
+		info!(
+			"Shutdown triggered: {reason}, waiting up to {:?} for the subsystems to stop",
+			self.shutdown_timeout
+		);
+		if tokio::time::timeout(self.shutdown_timeout, self.shutdown.completed_shutdown())
+			.await
+			.is_err()
+		{
+			warn!(
+				"Subsystems did not stop in {:?}, exiting",
+				self.shutdown_timeout
+			);
+		}
+		match self.db.flush() {
+			Ok(()) => info!("Pending store writes flushed"),
+			Err(error) => error!("Cannot flush pending store writes: {error:#}"),
+		}
+		reason
+	}
+
+	/// Triggers the shutdown, and waits until the client is stopped.
+	/// If the shutdown is already triggered, its original reason is returned.
+	pub async fn shutdown(self, reason: impl Into<String>) -> String {
+		// Shutdown may already be started by the subsystem failure
+		let _ = self.shutdown.trigger_shutdown(reason.into());
+		self.stopped().await
+	}
+}
+
+fn construct_multiaddress(ip: IpAddr, is_websocket: bool, port: u16) -> Multiaddr {
+	let tcp_multiaddress = Multiaddr::empty()
+		.with(Protocol::from(ip))
+		.with(Protocol::Tcp(port));
+
+	if is_websocket {
+		return tcp_multiaddress.with(Protocol::Ws(std::borrow::Cow::Borrowed("avail-light")));
+	}
+
+	tcp_multiaddress
+}
+
+fn construct_quic_multiaddress(ip: IpAddr, port: u16) -> Multiaddr {
+	Multiaddr::empty()
+		.with(Protocol::from(ip))
+		.with(Protocol::Udp(port))
+		.with(Protocol::QuicV1)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+	use test_case::test_case;
+
+	use super::{construct_multiaddress, construct_quic_multiaddress};
+
+	#[test_case(false => "/ip4/0.0.0.0/tcp/37000" ; "TCP")]
+	#[test_case(true => "/ip4/0.0.0.0/tcp/37000/x-parity-ws/avail-light" ; "WebSocket")]
+	fn test_construct_multiaddress(is_websocket: bool) -> String {
+		construct_multiaddress(Ipv4Addr::UNSPECIFIED.into(), is_websocket, 37000).to_string()
+	}
+
+	#[test_case(false => "/ip6/::/tcp/37000" ; "TCP")]
+	#[test_case(true => "/ip6/::/tcp/37000/x-parity-ws/avail-light" ; "WebSocket")]
+	fn test_construct_ipv6_multiaddress(is_websocket: bool) -> String {
+		construct_multiaddress(Ipv6Addr::UNSPECIFIED.into(), is_websocket, 37000).to_string()
+	}
+
+	#[test_case(IpAddr::V4(Ipv4Addr::UNSPECIFIED) => "/ip4/0.0.0.0/udp/37000/quic-v1" ; "IPv4")]
+	#[test_case(IpAddr::V6(Ipv6Addr::UNSPECIFIED) => "/ip6/::/udp/37000/quic-v1" ; "IPv6")]
+	fn test_construct_quic_multiaddress(ip: IpAddr) -> String {
+		construct_quic_multiaddress(ip, 37000).to_string()
+	}
+}
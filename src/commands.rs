@@ -0,0 +1,196 @@
+//! Commands executed on the local database, without connecting to the network.
+//!
+//! Output of the commands is returned as pretty printed JSON, so it can be processed by other tools.
+
+use avail_subxt::primitives::Header;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+	data::{
+		self, rocks_db::RocksDB, BlockSamplingStats, Database, Key, StorageBackend, Store,
+		StoreConfig, SyncProgress,
+	},
+	types::{DbCommand, QueryCommand},
+	utils::calculate_confidence,
+};
+
+fn to_json(value: impl Serialize) -> Result<String> {
+	serde_json::to_string_pretty(&value).wrap_err("Failed to serialize output")
+}
+
+/// Executes the query on the database, returning the JSON output.
+pub fn query(db: &impl Database, command: QueryCommand) -> Result<String> {
+	match command {
+		QueryCommand::Header {
+			block_number,
+			block_hash,
+		} => {
+			let header = data::get_block::<Header>(db, block_number, block_hash, Key::BlockHeader)?
+				.ok_or_else(|| eyre!("Header of the block {block_number} is not found"))?;
+			to_json(header)
+		},
+		QueryCommand::Confidence {
+			block_number,
+			block_hash,
+		} => {
+			let cell_count =
+				data::get_block::<u32>(db, block_number, block_hash, Key::VerifiedCellCount)?
+					.ok_or_else(|| eyre!("Confidence of the block {block_number} is not found"))?;
+			to_json(json!({
+				"block_number": block_number,
+				"cell_count": cell_count,
+				"confidence": calculate_confidence(cell_count),
+			}))
+		},
+		QueryCommand::AppData {
+			app_id,
+			block_number,
+			block_hash,
+		} => {
+			let data = data::get_block::<Vec<Vec<u8>>>(
+				db,
+				block_number,
+				block_hash,
+				|block_number, block_hash| Key::AppData(app_id, block_number, block_hash),
+			)?
+			.ok_or_else(|| {
+				eyre!("Data of the app {app_id} in the block {block_number} is not found")
+			})?;
+			let data = data
+				.iter()
+				.map(|data| format!("0x{}", hex::encode(data)))
+				.collect::<Vec<_>>();
+			to_json(json!({
+				"block_number": block_number,
+				"app_id": app_id,
+				"data": data,
+			}))
+		},
+		QueryCommand::SamplingStats { block_number } => {
+			let stats = db
+				.get::<BlockSamplingStats>(Key::BlockSamplingStats(block_number))?
+				.ok_or_else(|| eyre!("Sampling stats of the block {block_number} are not found"))?;
+			to_json(stats)
+		},
+		QueryCommand::SyncProgress => {
+			let progress = db
+				.get::<SyncProgress>(Key::SyncProgress)?
+				.ok_or_else(|| eyre!("Sync progress is not found"))?;
+			to_json(json!({
+				"start": progress.start,
+				"end": progress.end,
+				"lowest_verified": progress.lowest_verified,
+				"verified": progress.verified(),
+				"total": progress.total(),
+			}))
+		},
+	}
+}
+
+/// Executes the database maintenance command, returning the JSON output.
+/// Schema version, repair and stats are supported only by the RocksDB backend.
+pub fn db(cfg: &StoreConfig, command: DbCommand) -> Result<String> {
+	let path = cfg.path.as_str();
+	match (command, cfg.backend) {
+		(DbCommand::Version, StorageBackend::RocksDB) => {
+			let version = RocksDB::schema_version(path)?;
+			to_json(json!({ "schema_version": version }))
+		},
+		(DbCommand::Repair, StorageBackend::RocksDB) => {
+			RocksDB::repair(path)?;
+			to_json(json!({ "repaired": path }))
+		},
+		(DbCommand::Stats, StorageBackend::RocksDB) => {
+			let stats = RocksDB::stats(path)?;
+			to_json(json!({ "column_families": stats }))
+		},
+		(DbCommand::Compact, _) => {
+			Store::open(cfg)?.compact()?;
+			to_json(json!({ "compacted": path }))
+		},
+		(command, backend) => Err(eyre!(
+			"Command {command:?} is not supported by the {backend:?} storage backend"
+		)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sp_core::H256;
+	use test_case::test_case;
+
+	use super::{db, query};
+	use crate::{
+		data::{mem_db::MemoryDB, Database, Key, StorageBackend, StoreConfig, SyncProgress},
+		types::{DbCommand, QueryCommand, RuntimeConfig},
+	};
+
+	#[test]
+	fn query_confidence() {
+		let db = MemoryDB::default();
+		let (canonical, fork) = (H256::repeat_byte(1), H256::repeat_byte(2));
+		db.put(Key::CanonicalHash(5), canonical).unwrap();
+		db.put(Key::VerifiedCellCount(5, canonical), 4u32).unwrap();
+		db.put(Key::VerifiedCellCount(5, fork), 1u32).unwrap();
+
+		let confidence = |block_number, block_hash| {
+			let command = QueryCommand::Confidence {
+				block_number,
+				block_hash,
+			};
+			query(&db, command)
+				.map(|output| serde_json::from_str::<serde_json::Value>(&output).unwrap())
+		};
+		let output = confidence(5, None).unwrap();
+		assert_eq!(output["cell_count"], 4);
+		assert_eq!(output["confidence"], 93.75);
+		assert_eq!(confidence(5, Some(fork)).unwrap()["cell_count"], 1);
+
+		assert!(confidence(6, None).is_err());
+	}
+
+	#[test]
+	fn query_app_data_and_sync_progress() {
+		let db = MemoryDB::default();
+		db.put(Key::CanonicalHash(5), H256::zero()).unwrap();
+		db.put(Key::AppData(1, 5, H256::zero()), vec![vec![1u8, 2]])
+			.unwrap();
+		let mut progress = SyncProgress::new(1..5, 0);
+		progress.set_verified(4);
+		db.put(Key::SyncProgress, progress).unwrap();
+
+		let output = query(
+			&db,
+			QueryCommand::AppData {
+				app_id: 1,
+				block_number: 5,
+				block_hash: None,
+			},
+		)
+		.unwrap();
+		let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+		assert_eq!(output["data"][0], "0x0102");
+
+		let output = query(&db, QueryCommand::SyncProgress).unwrap();
+		let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+		assert_eq!(output["verified"], 1);
+		assert_eq!(output["total"], 4);
+		assert_eq!(output["lowest_verified"], 4);
+	}
+
+	#[test_case(DbCommand::Version ; "Schema version")]
+	#[test_case(DbCommand::Repair ; "Repair")]
+	#[test_case(DbCommand::Stats ; "Stats")]
+	fn unsupported_db_command(command: DbCommand) {
+		let cfg = StoreConfig {
+			backend: StorageBackend::Memory,
+			..StoreConfig::from(&RuntimeConfig::default())
+		};
+		assert!(db(&cfg, command).is_err());
+	}
+}
@@ -0,0 +1,132 @@
+//! Overrides of the configuration parameters, set by the environment variables and command line flags.
+
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use serde_json::{Map, Value};
+
+use crate::types::RuntimeConfig;
+
+/// Prefix of the environment variables which override configuration parameters
+pub const ENV_PREFIX: &str = "AVAIL_";
+
+fn to_map(cfg: &RuntimeConfig) -> Result<Map<String, Value>> {
+	match serde_json::to_value(cfg).wrap_err("Failed to serialize configuration")? {
+		Value::Object(map) => Ok(map),
+		_ => Err(eyre!("Configuration is not serialized as a map")),
+	}
+}
+
+fn parse_value(current: &Value, value: &str) -> Value {
+	match current {
+		Value::String(_) => Value::String(value.to_string()),
+		_ => serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())),
+	}
+}
+
+fn apply(
+	cfg: &mut RuntimeConfig,
+	overrides: impl IntoIterator<Item = (String, String)>,
+	ignore_unknown: bool,
+) -> Result<()> {
+	let mut map = to_map(cfg)?;
+	for (key, value) in overrides {
+		let Some(current) = map.get(&key) else {
+			if ignore_unknown {
+				continue;
+			}
+			return Err(eyre!("Unknown configuration parameter {key}"));
+		};
+		map.insert(key.clone(), parse_value(current, &value));
+		// Deserialized on each parameter, so the invalid one can be reported
+		*cfg = serde_json::from_value(Value::Object(map.clone()))
+			.wrap_err_with(|| format!("Invalid value of the configuration parameter {key}"))?;
+	}
+	Ok(())
+}
+
+/// Overrides configuration parameters with the `AVAIL_` prefixed environment variables.
+/// Variable name is upper case parameter name (e.g. `AVAIL_LOG_LEVEL` overrides `log_level`).
+pub fn apply_env(
+	cfg: &mut RuntimeConfig,
+	vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<()> {
+	let overrides = vars.into_iter().filter_map(|(name, value)| {
+		name.strip_prefix(ENV_PREFIX)
+			.map(|key| (key.to_lowercase(), value))
+	});
+	apply(cfg, overrides, true).wrap_err("Failed to apply environment variables")
+}
+
+/// Overrides configuration parameters with the `KEY=VALUE` pairs given on the command line.
+pub fn apply_values(cfg: &mut RuntimeConfig, values: &[(String, String)]) -> Result<()> {
+	apply(cfg, values.iter().cloned(), false).wrap_err("Failed to apply --set flags")
+}
+
+/// Parses `KEY=VALUE` pair given with the `--set` flag.
+pub fn parse_key_value(value: &str) -> Result<(String, String), String> {
+	match value.split_once('=') {
+		Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+		_ => Err(format!("Expected KEY=VALUE, found {value}")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+
+	use super::{apply_env, apply_values, parse_key_value};
+	use crate::types::RuntimeConfig;
+
+	fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+		vars.iter()
+			.map(|(name, value)| (name.to_string(), value.to_string()))
+			.collect()
+	}
+
+	#[test]
+	fn env_overrides() {
+		let mut cfg = RuntimeConfig::default();
+		let env = vars(&[
+			("AVAIL_HTTP_SERVER_PORT", "7001"),
+			("AVAIL_APP_IDS", "[1, 2]"),
+			("AVAIL_LOG_LEVEL", "debug"),
+			("AVAIL_GENESIS_HASH", "1234"),
+			("AVAIL_SYNC_START_BLOCK", "100"),
+			("AVAIL_PASSPHRASE", "ignored"),
+			("HTTP_SERVER_PORT", "7002"),
+		]);
+		apply_env(&mut cfg, env).unwrap();
+		assert_eq!(cfg.http_server_port, 7001);
+		assert_eq!(cfg.app_ids, vec![1, 2]);
+		assert_eq!(cfg.log_level, "debug");
+		assert_eq!(cfg.genesis_hash, "1234");
+		assert_eq!(cfg.sync_start_block, Some(100));
+	}
+
+	#[test]
+	fn invalid_env_value() {
+		let mut cfg = RuntimeConfig::default();
+		let env = vars(&[("AVAIL_HTTP_SERVER_PORT", "port")]);
+		assert!(apply_env(&mut cfg, env).is_err());
+	}
+
+	#[test]
+	fn cli_values_override_env() {
+		let mut cfg = RuntimeConfig::default();
+		apply_env(&mut cfg, vars(&[("AVAIL_CONFIDENCE", "95")])).unwrap();
+		apply_values(&mut cfg, &vars(&[("confidence", "99.5")])).unwrap();
+		assert_eq!(cfg.confidence, 99.5);
+
+		assert!(apply_values(&mut cfg, &vars(&[("unknown", "1")])).is_err());
+	}
+
+	#[test_case("port=37000" => Ok(("port".to_string(), "37000".to_string())) ; "Key and value")]
+	#[test_case("log_level=" => Ok(("log_level".to_string(), "".to_string())) ; "Empty value")]
+	#[test_case("=1" => matches Err(_) ; "Empty key")]
+	#[test_case("port" => matches Err(_) ; "Missing value")]
+	fn test_parse_key_value(value: &str) -> Result<(String, String), String> {
+		parse_key_value(value)
+	}
+}
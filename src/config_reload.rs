@@ -0,0 +1,215 @@
+//! Reload of the configuration file on SIGHUP or on change, without restarting the light client.
+
+use color_eyre::{eyre::WrapErr, Result};
+use serde_json::Value;
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
+use tokio::{
+	sync::{broadcast, mpsc},
+	time,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::types::{MultiaddrConfig, RuntimeConfig};
+
+/// Change of the tunable configuration parameter.
+#[derive(Clone, Debug)]
+pub enum ConfigUpdate {
+	/// Confidence threshold, applied to the next processed block
+	Confidence(f64),
+	/// Default log level
+	LogLevel(String),
+	/// Bootstrap nodes, DHT is bootstrapped with the new nodes
+	Bootstraps(Vec<MultiaddrConfig>),
+	/// Additional applications to follow
+	AppIds(Vec<u32>),
+}
+
+/// Compares configurations, returning updates of the tunable parameters,
+/// and names of the changed parameters which require restart.
+pub fn diff(
+	current: &RuntimeConfig,
+	new: &RuntimeConfig,
+) -> Result<(Vec<ConfigUpdate>, Vec<String>)> {
+	let to_map = |cfg: &RuntimeConfig| match serde_json::to_value(cfg) {
+		Ok(Value::Object(map)) => Ok(map),
+		Ok(_) => Ok(Default::default()),
+		Err(error) => Err(error),
+	};
+	let current_map = to_map(current).wrap_err("Failed to serialize configuration")?;
+	let new_map = to_map(new).wrap_err("Failed to serialize configuration")?;
+
+	let mut updates = vec![];
+	let mut rejected = vec![];
+	for (name, value) in new_map {
+		if current_map.get(&name) == Some(&value) {
+			continue;
+		}
+		match name.as_str() {
+			"confidence" => updates.push(ConfigUpdate::Confidence(new.confidence)),
+			"log_level" => updates.push(ConfigUpdate::LogLevel(new.log_level.clone())),
+			"bootstraps" => updates.push(ConfigUpdate::Bootstraps(new.bootstraps.clone())),
+			"app_ids" => updates.push(ConfigUpdate::AppIds(new.app_ids.clone())),
+			_ => rejected.push(name),
+		}
+	}
+	Ok((updates, rejected))
+}
+
+fn apply(current: &mut RuntimeConfig, update: &ConfigUpdate) {
+	match update {
+		ConfigUpdate::Confidence(confidence) => current.confidence = *confidence,
+		ConfigUpdate::LogLevel(log_level) => current.log_level = log_level.clone(),
+		ConfigUpdate::Bootstraps(bootstraps) => current.bootstraps = bootstraps.clone(),
+		ConfigUpdate::AppIds(app_ids) => current.app_ids = app_ids.clone(),
+	}
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path)
+		.and_then(|metadata| metadata.modified())
+		.ok()
+}
+
+/// Sends reload request on each SIGHUP signal.
+#[cfg(unix)]
+async fn forward_hangup(sender: mpsc::Sender<()>) {
+	use tokio::signal::unix::{signal, SignalKind};
+
+	let mut hangup = match signal(SignalKind::hangup()) {
+		Ok(hangup) => hangup,
+		Err(error) => {
+			warn!("Cannot listen for SIGHUP signal: {error}");
+			return;
+		},
+	};
+	while hangup.recv().await.is_some() {
+		if sender.send(()).await.is_err() {
+			return;
+		}
+	}
+}
+
+/// Reloads the configuration on SIGHUP, or when the configuration file is modified.
+/// Configuration file is checked for modifications on the given interval.
+pub async fn run(
+	path: PathBuf,
+	interval: Duration,
+	load: impl Fn() -> Result<RuntimeConfig>,
+	mut current: RuntimeConfig,
+	sender: broadcast::Sender<ConfigUpdate>,
+) {
+	info!(
+		"Watching configuration file {} for changes...",
+		path.display()
+	);
+
+	let (reload_sender, mut reload_receiver) = mpsc::channel(1);
+	#[cfg(unix)]
+	tokio::spawn(forward_hangup(reload_sender));
+	#[cfg(not(unix))]
+	drop(reload_sender);
+
+	let mut modified = modified_at(&path);
+	let mut interval = time::interval(interval);
+	loop {
+		tokio::select! {
+			_ = interval.tick() => {
+				let modified_now = modified_at(&path);
+				if modified_now == modified {
+					continue;
+				}
+				modified = modified_now;
+				info!("Configuration file is modified, reloading...");
+			},
+			Some(()) = reload_receiver.recv() => info!("Received SIGHUP, reloading configuration..."),
+		}
+
+		let new = match load() {
+			Ok(new) => new,
+			Err(error) => {
+				error!("Cannot reload configuration: {error:#}");
+				continue;
+			},
+		};
+
+		let (updates, rejected) = match diff(&current, &new) {
+			Ok(diff) => diff,
+			Err(error) => {
+				error!("Cannot compare configurations: {error:#}");
+				continue;
+			},
+		};
+
+		if !rejected.is_empty() {
+			warn!(
+				"Changes of the parameters {} require restart, they are not applied",
+				rejected.join(", ")
+			);
+		}
+		if updates.is_empty() {
+			debug!("There are no changes of the tunable parameters");
+		}
+		for update in updates {
+			info!(?update, "Applying configuration change");
+			apply(&mut current, &update);
+			// Sending fails only if there are no subscribers
+			let _ = sender.send(update);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{apply, diff, ConfigUpdate};
+	use crate::types::RuntimeConfig;
+
+	#[test]
+	fn diff_tunable_parameters() {
+		let current = RuntimeConfig::default();
+		let mut new = current.clone();
+		new.confidence = 99.0;
+		new.app_ids = vec![1, 2];
+		new.log_level = "DEBUG".to_string();
+
+		let (updates, rejected) = diff(&current, &new).unwrap();
+		assert!(rejected.is_empty());
+		assert_eq!(updates.len(), 3);
+		assert!(updates.iter().any(
+			|update| matches!(update, ConfigUpdate::Confidence(confidence) if *confidence == 99.0)
+		));
+		assert!(updates
+			.iter()
+			.any(|update| matches!(update, ConfigUpdate::AppIds(app_ids) if app_ids == &[1, 2])));
+	}
+
+	#[test]
+	fn diff_rejects_restart_parameters() {
+		let mut current = RuntimeConfig::default();
+		let mut new = current.clone();
+		new.port = current.port + 1;
+		new.http_server_port = current.http_server_port + 1;
+		new.confidence = 95.0;
+
+		let (updates, mut rejected) = diff(&current, &new).unwrap();
+		rejected.sort();
+		assert_eq!(rejected, vec!["http_server_port", "port"]);
+		assert_eq!(updates.len(), 1);
+
+		// Applied updates are not reported again
+		apply(&mut current, &updates[0]);
+		let (updates, _) = diff(&current, &new).unwrap();
+		assert!(updates.is_empty());
+	}
+
+	#[test]
+	fn no_changes() {
+		let current = RuntimeConfig::default();
+		let (updates, rejected) = diff(&current, &current.clone()).unwrap();
+		assert!(updates.is_empty());
+		assert!(rejected.is_empty());
+	}
+}
@@ -0,0 +1,353 @@
+use avail_subxt::{
+	config::substrate::DigestItem,
+	primitives::{grandpa::ConsensusLog, Header},
+};
+use codec::Decode;
+use color_eyre::{eyre::eyre, Result};
+use sp_core::ed25519;
+use tracing::{debug, info};
+
+use crate::finality::ValidatorSet;
+
+const GRANDPA_ENGINE_ID: [u8; 4] = *b"FRNK";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+	/// Change is enacted once the block at signal height + delay is finalized
+	Scheduled,
+	/// Change is enacted once the block at signal height + delay is imported,
+	/// without waiting for finality (used to recover from stalled finality)
+	Forced,
+}
+
+/// Authority set change signaled in the block header digest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthoritySetChange {
+	pub kind: ChangeKind,
+	pub delay: u32,
+	pub authorities: Vec<ed25519::Public>,
+}
+
+#[derive(Clone, Debug)]
+struct PendingChange {
+	kind: ChangeKind,
+	enact_at: u32,
+	validator_set: ValidatorSet,
+}
+
+/// Extracts GRANDPA authority set changes from the header digest logs.
+pub fn authority_set_changes(header: &Header) -> Vec<AuthoritySetChange> {
+	header
+		.digest
+		.logs
+		.iter()
+		.filter_map(|log| match log {
+			DigestItem::Consensus(GRANDPA_ENGINE_ID, data) => {
+				ConsensusLog::<u32>::decode(&mut data.as_slice()).ok()
+			},
+			_ => None,
+		})
+		.filter_map(|log| {
+			let (kind, change) = match log {
+				ConsensusLog::ScheduledChange(change) => (ChangeKind::Scheduled, change),
+				ConsensusLog::ForcedChange(_, change) => (ChangeKind::Forced, change),
+				_ => return None,
+			};
+			let authorities = change
+				.next_authorities
+				.into_iter()
+				.map(|(id, _)| ed25519::Public::from_raw(id.0 .0 .0))
+				.collect();
+			Some(AuthoritySetChange {
+				kind,
+				delay: change.delay,
+				authorities,
+			})
+		})
+		.collect()
+}
+
+/// Follows GRANDPA authority set rotations from the finalized block headers,
+/// so justifications can be verified without fetching validator set from the RPC node after each change.
+#[derive(Clone, Debug)]
+pub struct AuthoritySet {
+	current: ValidatorSet,
+	pending: Vec<PendingChange>,
+	last_imported: u32,
+}
+
+impl AuthoritySet {
+	/// Creates authority set tracker from the validator set trusted at given block number.
+	pub fn new(current: ValidatorSet, number: u32) -> Self {
+		Self {
+			current,
+			pending: vec![],
+			last_imported: number,
+		}
+	}
+
+	/// Currently active validator set.
+	pub fn current(&self) -> &ValidatorSet {
+		&self.current
+	}
+
+	/// Number of the last block imported into the tracker.
+	pub fn last_imported(&self) -> u32 {
+		self.last_imported
+	}
+
+	/// Imports the finalized header along with its skipped ancestors (in ascending order),
+	/// if the header justification is verified by the validator set derived from them.
+	/// Authority set is left unchanged if the verification fails.
+	pub fn import_finalized_header<'a>(
+		&mut self,
+		ancestors: impl IntoIterator<Item = &'a Header>,
+		header: &Header,
+		verify: impl FnOnce(&ValidatorSet) -> Result<()>,
+	) -> Result<ValidatorSet> {
+		let ancestors = ancestors
+			.into_iter()
+			.map(|ancestor| (ancestor.number, authority_set_changes(ancestor)))
+			.collect();
+		self.import_finalized(
+			ancestors,
+			header.number,
+			authority_set_changes(header),
+			verify,
+		)
+	}
+
+	/// Imports authority set changes of the finalized block and its ancestors,
+	/// only if the block finality is verified by the resulting validator set.
+	pub fn import_finalized(
+		&mut self,
+		ancestors: Vec<(u32, Vec<AuthoritySetChange>)>,
+		number: u32,
+		changes: Vec<AuthoritySetChange>,
+		verify: impl FnOnce(&ValidatorSet) -> Result<()>,
+	) -> Result<ValidatorSet> {
+		let mut imported = self.clone();
+		for (ancestor, changes) in ancestors {
+			imported.import(ancestor, changes)?;
+		}
+		let validator_set = imported.import(number, changes)?;
+		verify(&validator_set)?;
+		*self = imported;
+		Ok(validator_set)
+	}
+
+	/// Imports authority set changes signaled at given block number,
+	/// and returns validator set which is expected to sign the block justification.
+	/// Blocks are expected to be imported in ascending order.
+	pub fn import(
+		&mut self,
+		number: u32,
+		changes: Vec<AuthoritySetChange>,
+	) -> Result<ValidatorSet> {
+		if number <= self.last_imported {
+			return Err(eyre!(
+				"Block {number} is already imported (last imported block is {})",
+				self.last_imported
+			));
+		}
+
+		self.enact_pending(number);
+		self.last_imported = number;
+		let validator_set = self.current.clone();
+
+		for change in changes {
+			self.schedule(number, change)?;
+		}
+
+		Ok(validator_set)
+	}
+
+	/// Enacts pending changes whose enactment block precedes the given block.
+	/// Enactment block is the last block finalized by the previous set.
+	fn enact_pending(&mut self, number: u32) {
+		let (enacted, pending): (Vec<_>, Vec<_>) = self
+			.pending
+			.drain(..)
+			.partition(|change| change.enact_at < number);
+		self.pending = pending;
+
+		if let Some(last) = enacted.into_iter().last() {
+			info!(
+				"Authority set changed at block {}, new set_id {}",
+				last.enact_at, last.validator_set.set_id
+			);
+			self.current = last.validator_set;
+		}
+	}
+
+	fn schedule(&mut self, number: u32, change: AuthoritySetChange) -> Result<()> {
+		let enact_at = number
+			.checked_add(change.delay)
+			.ok_or_else(|| eyre!("Authority set change delay overflows at block {number}"))?;
+
+		match change.kind {
+			ChangeKind::Scheduled => {
+				if self
+					.pending
+					.iter()
+					.any(|pending| pending.kind == ChangeKind::Scheduled)
+				{
+					return Err(eyre!(
+						"Authority set change at block {number} is signaled while another one is pending"
+					));
+				}
+			},
+			// forced change overrides pending changes which are not yet enacted
+			ChangeKind::Forced => self.pending.clear(),
+		}
+
+		let set_id = self
+			.pending
+			.last()
+			.map(|pending| &pending.validator_set)
+			.unwrap_or(&self.current)
+			.set_id + 1;
+
+		debug!(
+			"{:?} authority set change signaled at block {number}, enacted at {enact_at}, set_id {set_id}",
+			change.kind
+		);
+
+		self.pending.push(PendingChange {
+			kind: change.kind,
+			enact_at,
+			validator_set: ValidatorSet {
+				set_id,
+				validator_set: change.authorities,
+			},
+		});
+		self.pending.sort_by_key(|pending| pending.enact_at);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use color_eyre::eyre::eyre;
+	use sp_core::ed25519::Public;
+
+	use super::{AuthoritySet, AuthoritySetChange, ChangeKind};
+	use crate::finality::ValidatorSet;
+
+	fn authorities(seed: u8) -> Vec<Public> {
+		vec![Public::from_raw([seed; 32])]
+	}
+
+	fn change(kind: ChangeKind, delay: u32, seed: u8) -> AuthoritySetChange {
+		AuthoritySetChange {
+			kind,
+			delay,
+			authorities: authorities(seed),
+		}
+	}
+
+	fn authority_set() -> AuthoritySet {
+		AuthoritySet::new(
+			ValidatorSet {
+				set_id: 1,
+				validator_set: authorities(1),
+			},
+			0,
+		)
+	}
+
+	#[test]
+	fn scheduled_change_without_delay() {
+		let mut set = authority_set();
+		let signed_by = set
+			.import(10, vec![change(ChangeKind::Scheduled, 0, 2)])
+			.unwrap();
+		// block which signals the change is finalized by the previous set
+		assert_eq!(signed_by.set_id, 1);
+
+		let signed_by = set.import(11, vec![]).unwrap();
+		assert_eq!(signed_by.set_id, 2);
+		assert_eq!(signed_by.validator_set, authorities(2));
+	}
+
+	#[test]
+	fn scheduled_change_with_delay() {
+		let mut set = authority_set();
+		set.import(10, vec![change(ChangeKind::Scheduled, 2, 2)])
+			.unwrap();
+		assert_eq!(set.import(11, vec![]).unwrap().set_id, 1);
+		assert_eq!(set.import(12, vec![]).unwrap().set_id, 1);
+		assert_eq!(set.import(13, vec![]).unwrap().set_id, 2);
+	}
+
+	#[test]
+	fn skipped_blocks_enact_pending_changes() {
+		let mut set = authority_set();
+		set.import(10, vec![change(ChangeKind::Scheduled, 2, 2)])
+			.unwrap();
+		assert_eq!(set.import(20, vec![]).unwrap().set_id, 2);
+		assert_eq!(set.current().set_id, 2);
+	}
+
+	#[test]
+	fn forced_change_overrides_scheduled() {
+		let mut set = authority_set();
+		set.import(10, vec![change(ChangeKind::Scheduled, 5, 2)])
+			.unwrap();
+		set.import(11, vec![change(ChangeKind::Forced, 0, 3)])
+			.unwrap();
+		let signed_by = set.import(12, vec![]).unwrap();
+		assert_eq!(signed_by.set_id, 2);
+		assert_eq!(signed_by.validator_set, authorities(3));
+	}
+
+	#[test]
+	fn second_scheduled_change_is_rejected() {
+		let mut set = authority_set();
+		set.import(10, vec![change(ChangeKind::Scheduled, 5, 2)])
+			.unwrap();
+		assert!(set
+			.import(11, vec![change(ChangeKind::Scheduled, 0, 3)])
+			.is_err());
+	}
+
+	#[test]
+	fn blocks_are_imported_in_order() {
+		let mut set = authority_set();
+		set.import(10, vec![]).unwrap();
+		assert!(set.import(10, vec![]).is_err());
+		assert!(set.import(9, vec![]).is_err());
+	}
+
+	#[test]
+	fn rejected_forced_change_leaves_set_unchanged() {
+		let mut set = authority_set();
+		let forced = vec![(10, vec![change(ChangeKind::Forced, 0, 2)])];
+		assert!(set
+			.import_finalized(forced, 11, vec![], |_| Err(eyre!("Invalid justification")))
+			.is_err());
+		assert_eq!(set.current().validator_set, authorities(1));
+		assert_eq!(set.last_imported(), 0);
+
+		let signed_by = set.import(11, vec![]).unwrap();
+		assert_eq!(signed_by.set_id, 1);
+		assert_eq!(signed_by.validator_set, authorities(1));
+	}
+
+	#[test]
+	fn verified_ancestor_changes_are_imported() {
+		let mut set = authority_set();
+		let scheduled = vec![(10, vec![change(ChangeKind::Scheduled, 0, 2)])];
+		let signed_by = set
+			.import_finalized(scheduled, 11, vec![], |signed_by| {
+				match signed_by.validator_set == authorities(2) {
+					true => Ok(()),
+					false => Err(eyre!("Invalid justification")),
+				}
+			})
+			.unwrap();
+		assert_eq!(signed_by.set_id, 2);
+		assert_eq!(set.current().set_id, 2);
+		assert_eq!(set.last_imported(), 11);
+	}
+}
@@ -1,36 +1,119 @@
 //! Column family names and other constants.
 
+use semver::{Version, VersionReq};
+
 /// Expected network Node versions. First version should be the main supported version,
 /// while all subsequent versions should be for backward compatibility/fallback/future-proofing versions.
 pub const EXPECTED_SYSTEM_VERSION: &[&str] = &["2.0"];
 pub const EXPECTED_SPEC_NAME: &str = "avail";
 
+/// Compatible node system versions by the runtime specification name, as semver requirements.
+/// Multiple ranges can be given for the same specification name, any of them has to match.
+pub const COMPATIBLE_NODE_VERSIONS: &[(&str, &str)] = &[(EXPECTED_SPEC_NAME, "~2.0")];
+
 #[derive(Clone)]
 pub struct ExpectedNodeVariant {
 	pub system_version: &'static [&'static str],
 	pub spec_name: &'static str,
+	/// Compatibility table of the specification names and system version requirements
+	pub compatible_versions: &'static [(&'static str, &'static str)],
+	/// System version requirement of the expected specification name, used instead of the compatibility table
+	pub version_requirement: Option<String>,
+	/// Nodes with incompatible versions are used, only warning event is emitted
+	pub allow_mismatch: bool,
+}
+
+/// Parses node system version (e.g. `2.0.0-6f7846b9e5b`), ignoring pre-release and build suffixes,
+/// since nodes report commit hash as a suffix. Missing minor and patch versions are set to 0.
+fn parse_system_version(system_version: &str) -> Option<Version> {
+	let core = system_version
+		.split(['-', '+'])
+		.next()
+		.unwrap_or(system_version);
+	let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+	let major = parts.next()??;
+	let minor = parts.next().unwrap_or(Some(0))?;
+	let patch = parts.next().unwrap_or(Some(0))?;
+	if parts.next().is_some() {
+		return None;
+	}
+	Some(Version::new(major, minor, patch))
 }
+
 impl ExpectedNodeVariant {
 	pub const fn new() -> Self {
 		Self {
 			system_version: EXPECTED_SYSTEM_VERSION,
 			spec_name: EXPECTED_SPEC_NAME,
+			compatible_versions: COMPATIBLE_NODE_VERSIONS,
+			version_requirement: None,
+			allow_mismatch: false,
 		}
 	}
 
-	/// Checks if any of the expected versions matches provided network version.
-	/// Since the light client uses subset of the node APIs, `matches` checks only prefix of a node version.
-	/// This means that if expected version is `1.6`, versions `1.6.x` of the node will match.
-	/// Specification name is checked for exact match.
-	/// Since runtime `spec_version` can be changed with runtime upgrade, `spec_version` is removed.
-	/// NOTE: Runtime compatibility check is currently not implemented.
+	/// Checks if node version is compatible, by any of the version requirements for the node specification name.
+	/// Since the light client uses subset of the node APIs, requirements usually allow ranges of versions.
+	/// For example, `~2.0` requirement matches `2.0.x` versions of the node.
+	/// Since runtime `spec_version` can be changed with runtime upgrade, `spec_version` is not checked.
 	pub fn matches(&self, system_version: &str, spec_name: &str) -> bool {
-		for supported_network_version in self.system_version {
-			if system_version.starts_with(supported_network_version) && self.spec_name == spec_name
-			{
-				return true;
-			}
+		let Some(version) = parse_system_version(system_version) else {
+			return false;
+		};
+		self.requirements()
+			.into_iter()
+			.filter(|(name, _)| *name == spec_name)
+			.filter_map(|(_, requirement)| VersionReq::parse(requirement).ok())
+			.any(|requirement| requirement.matches(&version))
+	}
+
+	/// Specification names and system version requirements of the compatible nodes.
+	pub fn requirements(&self) -> Vec<(&str, &str)> {
+		match &self.version_requirement {
+			Some(requirement) => vec![(self.spec_name, requirement.as_str())],
+			None => self.compatible_versions.to_vec(),
 		}
-		false
+	}
+}
+
+/// Expected Avail block time in seconds
+pub const BLOCK_TIME_SECS: u64 = 20;
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+
+	use super::ExpectedNodeVariant;
+
+	#[test_case("2.0.0", "avail" => true ; "Exact version")]
+	#[test_case("2.0.3-6f7846b9e5b", "avail" => true ; "Patch version with commit suffix")]
+	#[test_case("2.0", "avail" => true ; "Missing patch version")]
+	#[test_case("2.1.0", "avail" => false ; "Newer minor version")]
+	#[test_case("1.9.9", "avail" => false ; "Older version")]
+	#[test_case("2.0.0", "polkadot" => false ; "Other specification")]
+	#[test_case("invalid", "avail" => false ; "Invalid version")]
+	fn test_matches(system_version: &str, spec_name: &str) -> bool {
+		ExpectedNodeVariant::new().matches(system_version, spec_name)
+	}
+
+	#[test]
+	fn test_matches_any_range() {
+		let expected = ExpectedNodeVariant {
+			compatible_versions: &[("avail", "~1.9"), ("avail", ">=2.0.0, <2.2.0")],
+			..ExpectedNodeVariant::new()
+		};
+		assert!(expected.matches("1.9.1", "avail"));
+		assert!(expected.matches("2.1.5", "avail"));
+		assert!(!expected.matches("2.2.0", "avail"));
+	}
+
+	#[test]
+	fn version_requirement_replaces_compatibility_table() {
+		let expected = ExpectedNodeVariant {
+			version_requirement: Some("~2.2".to_string()),
+			..ExpectedNodeVariant::new()
+		};
+		assert!(expected.matches("2.2.1", "avail"));
+		assert!(!expected.matches("2.0.0", "avail"));
+		assert!(!expected.matches("2.2.1", "polkadot"));
 	}
 }
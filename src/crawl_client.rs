@@ -65,11 +65,15 @@ pub async fn run(
 
 	let delay = Delay(Some(Duration::from_secs(delay)));
 
-	while let Ok(rpc::Event::HeaderUpdate {
-		header,
-		received_at,
-	}) = message_rx.recv().await
-	{
+	while let Ok(event) = message_rx.recv().await {
+		let rpc::Event::HeaderUpdate {
+			header,
+			received_at,
+		} = event
+		else {
+			continue;
+		};
+
 		let block = match types::BlockVerified::try_from((header, None)) {
 			Ok(block) => block,
 			Err(error) => {
@@ -1,12 +1,29 @@
+pub use avail_light_api_types::{
+	BlockSamplingStats, CellSource, SampledCell, SamplingTimings, TransactionStatus,
+};
+use avail_subxt::primitives::Header;
 use codec::{Decode, Encode};
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Result};
 use serde::{Deserialize, Serialize};
-use sp_core::ed25519;
+use sp_core::{blake2_256, ed25519, H256};
+use std::{
+	fmt::{self, Display},
+	ops::Range,
+};
 
-pub mod rocks_db;
+use crate::types::RuntimeConfig;
 
-#[cfg(test)]
+pub mod cell_cache;
+#[cfg(target_arch = "wasm32")]
+pub mod indexed_db;
+pub mod journal;
 pub mod mem_db;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod migrations;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod rocks_db;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sled_db;
 
 pub trait Database {
 	/// Type of the database key which we can get from the custom key.
@@ -26,6 +43,213 @@ pub trait Database {
 
 	/// Deletes value from the database for the given key.
 	fn delete(&self, key: Key) -> Result<()>;
+
+	/// Writes the batch atomically, either all or none of its writes are applied.
+	fn write(&self, batch: Batch) -> Result<()>;
+
+	/// Lists keys stored in the column family. Keys of the state column family are not listed.
+	fn keys(&self, column_family: &'static str) -> Result<Vec<Key>>;
+
+	/// Compacts the database, so the space of the deleted values is reclaimed.
+	fn compact(&self) -> Result<()> {
+		Ok(())
+	}
+
+	/// Flushes pending writes to the storage, used on shutdown.
+	fn flush(&self) -> Result<()> {
+		Ok(())
+	}
+}
+
+/// Value of the batch write, encoded by the database backend.
+pub trait BatchValue {
+	fn encode_value(&self) -> Vec<u8>;
+
+	fn to_json(&self) -> Result<String>;
+}
+
+impl<T: Serialize + Encode> BatchValue for T {
+	fn encode_value(&self) -> Vec<u8> {
+		self.encode()
+	}
+
+	fn to_json(&self) -> Result<String> {
+		Ok(serde_json::to_string(self)?)
+	}
+}
+
+/// Puts and deletes written to the database at once (see [Database::write]).
+#[derive(Default)]
+pub struct Batch {
+	/// Writes in the order they are applied, value is not set for deletes
+	pub writes: Vec<(Key, Option<Box<dyn BatchValue>>)>,
+}
+
+impl Batch {
+	pub fn put<T>(&mut self, key: Key, value: T)
+	where
+		T: Serialize + Encode + 'static,
+	{
+		self.writes.push((key, Some(Box::new(value))));
+	}
+
+	pub fn delete(&mut self, key: Key) {
+		self.writes.push((key, None));
+	}
+
+	/// Puts the header of the finalized block, indexed as the canonical block at its height and by its hash.
+	pub fn put_finalized_header(&mut self, header: Header) -> H256 {
+		let block_number = header.number;
+		let block_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+		self.put(Key::BlockHeader(block_number, block_hash), header);
+		self.put(Key::CanonicalHash(block_number), block_hash);
+		self.put(Key::BlockNumber(block_hash), block_number);
+		block_hash
+	}
+}
+
+/// Storage backend used to persist the light client data.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+	#[default]
+	RocksDB,
+	/// Embedded pure-Rust database
+	Sled,
+	/// Data is not persisted, used for testing and environments without file system
+	Memory,
+	/// Browser IndexedDB database, available on the `wasm32` target only
+	IndexedDB,
+}
+
+/// Compression algorithm of the RocksDB column family.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+	None,
+	#[default]
+	Snappy,
+	Lz4,
+	Zstd,
+}
+
+/// Storage configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct StoreConfig {
+	pub backend: StorageBackend,
+	pub path: String,
+	#[cfg(not(target_arch = "wasm32"))]
+	pub rocksdb: rocks_db::RocksDBConfig,
+}
+
+impl From<&RuntimeConfig> for StoreConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		StoreConfig {
+			backend: val.storage_backend,
+			path: val.avail_path.clone(),
+			#[cfg(not(target_arch = "wasm32"))]
+			rocksdb: val.into(),
+		}
+	}
+}
+
+/// Database of the configured storage backend.
+#[derive(Clone)]
+pub enum Store {
+	#[cfg(not(target_arch = "wasm32"))]
+	RocksDB(rocks_db::RocksDB),
+	#[cfg(not(target_arch = "wasm32"))]
+	Sled(sled_db::SledDB),
+	Memory(mem_db::MemoryDB),
+	#[cfg(target_arch = "wasm32")]
+	IndexedDB(indexed_db::IndexedDB),
+}
+
+impl Store {
+	/// Opens database of the configured backend, path is not used by the in-memory backend.
+	#[cfg(not(target_arch = "wasm32"))]
+	pub fn open(cfg: &StoreConfig) -> Result<Store> {
+		let path = &cfg.path;
+		Ok(match cfg.backend {
+			StorageBackend::RocksDB => Store::RocksDB(rocks_db::RocksDB::open(path, &cfg.rocksdb)?),
+			StorageBackend::Sled => Store::Sled(sled_db::SledDB::open(path)?),
+			StorageBackend::Memory => Store::Memory(mem_db::MemoryDB::default()),
+			StorageBackend::IndexedDB => {
+				return Err(eyre!(
+					"IndexedDB storage backend is available in the browser only"
+				))
+			},
+		})
+	}
+
+	/// Opens database of the configured backend, path is used as the name of the IndexedDB database.
+	#[cfg(target_arch = "wasm32")]
+	pub async fn open(cfg: &StoreConfig) -> Result<Store> {
+		Ok(match cfg.backend {
+			StorageBackend::IndexedDB => {
+				Store::IndexedDB(indexed_db::IndexedDB::open(&cfg.path).await?)
+			},
+			StorageBackend::Memory => Store::Memory(mem_db::MemoryDB::default()),
+			backend => {
+				return Err(eyre!(
+					"Storage backend {backend:?} is not available in the browser"
+				))
+			},
+		})
+	}
+}
+
+/// Dispatches the call to the database of the configured backend.
+macro_rules! dispatch {
+	($store:expr, $db:ident => $call:expr) => {
+		match $store {
+			#[cfg(not(target_arch = "wasm32"))]
+			Store::RocksDB($db) => $call,
+			#[cfg(not(target_arch = "wasm32"))]
+			Store::Sled($db) => $call,
+			Store::Memory($db) => $call,
+			#[cfg(target_arch = "wasm32")]
+			Store::IndexedDB($db) => $call,
+		}
+	};
+}
+
+impl Database for Store {
+	type Key = Key;
+
+	fn put<T>(&self, key: Key, value: T) -> Result<()>
+	where
+		T: Serialize + Encode,
+	{
+		dispatch!(self, db => db.put(key, value))
+	}
+
+	fn get<T>(&self, key: Key) -> Result<Option<T>>
+	where
+		for<'a> T: Deserialize<'a> + Decode,
+	{
+		dispatch!(self, db => db.get(key))
+	}
+
+	fn delete(&self, key: Key) -> Result<()> {
+		dispatch!(self, db => db.delete(key))
+	}
+
+	fn write(&self, batch: Batch) -> Result<()> {
+		dispatch!(self, db => db.write(batch))
+	}
+
+	fn keys(&self, column_family: &'static str) -> Result<Vec<Key>> {
+		dispatch!(self, db => db.keys(column_family))
+	}
+
+	fn compact(&self) -> Result<()> {
+		dispatch!(self, db => db.compact())
+	}
+
+	fn flush(&self) -> Result<()> {
+		dispatch!(self, db => db.flush())
+	}
 }
 
 /// Column family for confidence factor
@@ -34,21 +258,138 @@ pub const CONFIDENCE_FACTOR_CF: &str = "avail_light_confidence_factor_cf";
 /// Column family for block header
 pub const BLOCK_HEADER_CF: &str = "avail_light_block_header_cf";
 
+/// Column family for block numbers by block hash
+pub const BLOCK_NUMBER_CF: &str = "avail_light_block_number_cf";
+
+/// Column family for hashes of the canonical blocks by block number
+pub const CANONICAL_HASH_CF: &str = "avail_light_canonical_hash_cf";
+
 /// Column family for app data
 pub const APP_DATA_CF: &str = "avail_light_app_data_cf";
 
 /// Column family for state
 pub const STATE_CF: &str = "avail_light_state_cf";
 
+/// Column family for block sampling stats
+pub const SAMPLING_STATS_CF: &str = "avail_light_sampling_stats_cf";
+
+/// Column family for submitted transactions status
+pub const TRANSACTION_STATUS_CF: &str = "avail_light_transaction_status_cf";
+
+/// Column family for historical blocks sync results
+pub const SYNC_RESULT_CF: &str = "avail_light_sync_result_cf";
+
+/// Column family for signed availability attestations
+pub const ATTESTATION_CF: &str = "avail_light_attestation_cf";
+
+/// Column family for commitment mismatches of the unavailable blocks
+pub const COMMITMENT_MISMATCH_CF: &str = "avail_light_commitment_mismatch_cf";
+
+/// Column family for failures of the block processing
+pub const BLOCK_FAILURE_CF: &str = "avail_light_block_failure_cf";
+
+/// Column family for the journal of the blocks being written
+pub const JOURNAL_CF: &str = "avail_light_journal_cf";
+
 /// Sync finality checkpoint key name
 const FINALITY_SYNC_CHECKPOINT_KEY: &str = "finality_sync_checkpoint";
 
+/// RPC node scores key name
+const RPC_NODE_SCORES_KEY: &str = "rpc_node_scores";
+
+/// Last known RPC node key name
+const RPC_LAST_NODE_KEY: &str = "rpc_last_node";
+
+/// Historical sync progress key name
+const SYNC_PROGRESS_KEY: &str = "sync_progress";
+
+/// P2P keypair key name
+const P2P_KEYPAIR_KEY: &str = "p2p_keypair";
+
+/// P2P routing table peers key name
+const P2P_PEERS_KEY: &str = "p2p_peers";
+
+/// Retention checkpoint key name
+const RETENTION_CHECKPOINT_KEY: &str = "retention_checkpoint";
+
+/// Readiness check probe key name
+const HEALTH_PROBE_KEY: &str = "health_probe";
+
+/// Database schema version key name
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
 #[derive(Clone)]
 pub enum Key {
-	AppData(u32, u32),
-	BlockHeader(u32),
-	VerifiedCellCount(u32),
+	/// Data of the application, by application ID, block number and block hash
+	AppData(u32, u32, H256),
+	/// Block header, by block number and block hash
+	BlockHeader(u32, H256),
+	/// Block number of the processed block, by block hash
+	BlockNumber(H256),
+	/// Hash of the canonical (finalized) block, by block number
+	CanonicalHash(u32),
+	/// Number of the verified cells, by block number and block hash
+	VerifiedCellCount(u32, H256),
+	BlockSamplingStats(u32),
+	TransactionStatus(H256),
+	SyncResult(u32),
+	/// Signed availability attestation of the block
+	Attestation(u32),
+	/// Mismatch between the header extension commitments and the sampled data of the block
+	CommitmentMismatch(u32),
+	/// Failure of the latest processing attempt of the block
+	BlockFailure(u32),
 	FinalitySyncCheckpoint,
+	SyncProgress,
+	RpcNodeScores,
+	/// Last successfully used RPC node
+	RpcLastNode,
+	P2PKeypair,
+	P2PPeers,
+	/// First block which is not pruned by the retention
+	RetentionCheckpoint,
+	/// Timestamp written by the readiness check, to check if the database is writable
+	HealthProbe,
+	/// Block whose verification results are being written, removed once they are written
+	Journal(u32),
+}
+
+/// Returns hash of the canonical block at the given height, if the block is stored.
+pub fn canonical_hash(db: &impl Database, block_number: u32) -> Result<Option<H256>> {
+	db.get(Key::CanonicalHash(block_number))
+}
+
+/// Gets the value of the block at the given height, stored under the key of the block number and hash.
+/// Value of the canonical block is returned, unless the hash of the block is given, e.g. of the block on another fork.
+pub fn get_block<T>(
+	db: &impl Database,
+	block_number: u32,
+	block_hash: Option<H256>,
+	key: impl FnOnce(u32, H256) -> Key,
+) -> Result<Option<T>>
+where
+	for<'a> T: Deserialize<'a> + Decode,
+{
+	let block_hash = match block_hash {
+		Some(block_hash) => block_hash,
+		None => match canonical_hash(db, block_number)? {
+			Some(block_hash) => block_hash,
+			None => return Ok(None),
+		},
+	};
+	db.get(key(block_number, block_hash))
+}
+
+/// Gets the value of the canonical block at the given height (see [get_block]).
+pub fn get_canonical<T>(
+	db: &impl Database,
+	block_number: u32,
+	key: impl FnOnce(u32, H256) -> Key,
+) -> Result<Option<T>>
+where
+	for<'a> T: Deserialize<'a> + Decode,
+{
+	get_block(db, block_number, None, key)
 }
 
 #[derive(Serialize, Deserialize, Debug, Decode, Encode)]
@@ -57,3 +398,161 @@ pub struct FinalitySyncCheckpoint {
 	pub set_id: u64,
 	pub validator_set: Vec<ed25519::Public>,
 }
+
+/// Classification of the failed block processing.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Decode, Encode)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureReason {
+	/// Block processing timed out, waiting for the RPC node or DHT
+	RpcTimeout,
+	/// Sampled cells failed the proof verification, or header extension doesn't match them
+	ProofInvalid,
+	/// Sampled cells are not found in DHT, nor fetched from RPC
+	DhtMiss,
+}
+
+impl Display for FailureReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			FailureReason::RpcTimeout => write!(f, "rpc-timeout"),
+			FailureReason::ProofInvalid => write!(f, "proof-invalid"),
+			FailureReason::DhtMiss => write!(f, "dht-miss"),
+		}
+	}
+}
+
+/// Failure of the block processing, with the number of failed attempts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Decode, Encode)]
+pub struct BlockFailure {
+	pub reason: FailureReason,
+	pub attempts: u32,
+}
+
+/// Outcome of the historical block sync.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Decode, Encode)]
+#[serde(tag = "result", rename_all = "kebab-case")]
+pub enum SyncResult {
+	/// All sampled cells are fetched and verified
+	Verified { cell_count: u32 },
+	/// Some of the sampled cells are not fetched or verified
+	Unavailable { fetched: u32, required: u32 },
+	/// Block header fetch or block processing failed
+	Failed { reason: String },
+}
+
+impl Display for SyncResult {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			SyncResult::Verified { cell_count } => write!(f, "verified {cell_count} cells"),
+			SyncResult::Unavailable { fetched, required } => {
+				write!(f, "unavailable, {fetched}/{required} cells fetched")
+			},
+			SyncResult::Failed { reason } => write!(f, "failed: {reason}"),
+		}
+	}
+}
+
+/// Progress of the historical sync, so already verified blocks are not synced again after restart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Decode, Encode)]
+pub struct SyncProgress {
+	/// First block of the sync range
+	pub start: u32,
+	/// Block after the last block of the sync range
+	pub end: u32,
+	/// Lowest verified block, such that all blocks above it are verified
+	pub lowest_verified: Option<u32>,
+	/// Bitmap of the blocks which are not verified yet, bit `n` represents block `start + n`
+	pub gaps: Vec<u8>,
+	/// Start of the current sync session, as unix timestamp in seconds
+	pub session_started_at: u64,
+	/// Number of blocks verified in the current sync session
+	pub session_verified: u32,
+}
+
+impl SyncProgress {
+	pub fn new(range: Range<u32>, now: u64) -> Self {
+		let mut gaps = vec![0xff; range.len().div_ceil(8)];
+		// Clears bits after the end of the range
+		if range.len() % 8 != 0 {
+			if let Some(last) = gaps.last_mut() {
+				*last = (1 << (range.len() % 8)) - 1;
+			}
+		}
+		SyncProgress {
+			start: range.start,
+			end: range.end,
+			lowest_verified: None,
+			gaps,
+			session_started_at: now,
+			session_verified: 0,
+		}
+	}
+
+	/// Continues progress for the new sync range. Progress is kept if the range has the same start,
+	/// with blocks finalized since the previous session added as gaps.
+	pub fn resume(self, range: Range<u32>, now: u64) -> Self {
+		if self.start != range.start || self.end > range.end {
+			return SyncProgress::new(range, now);
+		}
+
+		let mut progress = SyncProgress::new(range, now);
+		for block_number in progress.start..self.end {
+			if self.is_verified(block_number) {
+				progress.clear_gap(block_number);
+			}
+		}
+		progress.update_lowest_verified();
+		progress
+	}
+
+	fn bit(&self, block_number: u32) -> Option<(usize, u8)> {
+		if !(self.start..self.end).contains(&block_number) {
+			return None;
+		}
+		let index = (block_number - self.start) as usize;
+		Some((index / 8, 1 << (index % 8)))
+	}
+
+	fn clear_gap(&mut self, block_number: u32) -> bool {
+		let Some((byte, mask)) = self.bit(block_number) else {
+			return false;
+		};
+		let is_gap = self.gaps[byte] & mask != 0;
+		self.gaps[byte] &= !mask;
+		is_gap
+	}
+
+	fn update_lowest_verified(&mut self) {
+		let mut lowest = self.lowest_verified.unwrap_or(self.end);
+		while lowest > self.start && self.is_verified(lowest - 1) {
+			lowest -= 1;
+		}
+		self.lowest_verified = (lowest < self.end).then_some(lowest);
+	}
+
+	/// Checks if block is verified, blocks outside of the sync range are not verified.
+	pub fn is_verified(&self, block_number: u32) -> bool {
+		self.bit(block_number)
+			.map(|(byte, mask)| self.gaps[byte] & mask == 0)
+			.unwrap_or(false)
+	}
+
+	/// Marks block as verified, returning `true` if it wasn't verified before.
+	pub fn set_verified(&mut self, block_number: u32) -> bool {
+		if !self.clear_gap(block_number) {
+			return false;
+		}
+		self.session_verified += 1;
+		self.update_lowest_verified();
+		true
+	}
+
+	pub fn total(&self) -> u32 {
+		self.end.saturating_sub(self.start)
+	}
+
+	pub fn verified(&self) -> u32 {
+		let gaps: u32 = self.gaps.iter().map(|byte| byte.count_ones()).sum();
+		self.total() - gaps
+	}
+}
@@ -0,0 +1,124 @@
+//! In-memory LRU cache of the verified cells, keyed by the block number and the cell position.
+//!
+//! Cells verified during the sampling are reused by the app client, so they don't need to be fetched again from the DHT or RPC.
+
+use kate_recovery::{data::Cell, matrix::Position};
+use lru::LruCache;
+use std::{
+	num::NonZeroUsize,
+	sync::{Arc, Mutex},
+};
+
+/// Approximate memory used by a single cached cell, including the key and the LRU bookkeeping
+const ENTRY_SIZE: usize = 160;
+
+type CellKey = (u32, u32, u16);
+
+fn key(block_number: u32, position: &Position) -> CellKey {
+	(block_number, position.row, position.col)
+}
+
+/// Shared cache of the verified cells, disabled if created with the zero size.
+#[derive(Clone, Default)]
+pub struct CellCache(Option<Arc<Mutex<LruCache<CellKey, Cell>>>>);
+
+impl CellCache {
+	/// Creates cache limited to the given size in megabytes.
+	pub fn new(size_mb: usize) -> Self {
+		let capacity = NonZeroUsize::new(size_mb * 1024 * 1024 / ENTRY_SIZE);
+		CellCache(capacity.map(|capacity| Arc::new(Mutex::new(LruCache::new(capacity)))))
+	}
+
+	/// Inserts verified cells of the block, least recently used cells are evicted if cache is full.
+	pub fn insert(&self, block_number: u32, cells: &[Cell]) {
+		let Some(cache) = self.0.as_ref() else {
+			return;
+		};
+		let mut cache = cache.lock().expect("Lock can be acquired");
+		for cell in cells {
+			cache.put(key(block_number, &cell.position), cell.clone());
+		}
+	}
+
+	/// Returns cached cells on the given positions, and positions of the cells which are not cached.
+	pub fn get(&self, block_number: u32, positions: &[Position]) -> (Vec<Cell>, Vec<Position>) {
+		let Some(cache) = self.0.as_ref() else {
+			return (vec![], positions.to_vec());
+		};
+		let mut cache = cache.lock().expect("Lock can be acquired");
+		let mut cached = vec![];
+		let mut missing = vec![];
+		for position in positions {
+			match cache.get(&key(block_number, position)) {
+				Some(cell) => cached.push(cell.clone()),
+				None => missing.push(*position),
+			}
+		}
+		(cached, missing)
+	}
+
+	/// Returns number of the cached cells.
+	pub fn len(&self) -> usize {
+		self.0
+			.as_ref()
+			.map(|cache| cache.lock().expect("Lock can be acquired").len())
+			.unwrap_or(0)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use kate_recovery::{data::Cell, matrix::Position};
+
+	use super::{CellCache, ENTRY_SIZE};
+
+	fn cell(row: u32, col: u16) -> Cell {
+		Cell {
+			position: Position { row, col },
+			content: [0u8; 80],
+		}
+	}
+
+	#[test]
+	fn cached_cells_are_returned() {
+		let cache = CellCache::new(1);
+		cache.insert(1, &[cell(0, 0), cell(0, 1)]);
+
+		let positions = [Position { row: 0, col: 0 }, Position { row: 1, col: 0 }];
+		let (cached, missing) = cache.get(1, &positions);
+		assert_eq!(cached.len(), 1);
+		assert_eq!(cached[0].position, Position { row: 0, col: 0 });
+		assert_eq!(missing, vec![Position { row: 1, col: 0 }]);
+
+		let (cached, missing) = cache.get(2, &positions);
+		assert!(cached.is_empty());
+		assert_eq!(missing.len(), 2);
+	}
+
+	#[test]
+	fn least_recently_used_cells_are_evicted() {
+		let cache = CellCache::new(1);
+		let capacity = 1024 * 1024 / ENTRY_SIZE;
+		let cells = (0..capacity as u32 + 1)
+			.map(|row| cell(row, 0))
+			.collect::<Vec<_>>();
+		cache.insert(1, &cells);
+
+		assert_eq!(cache.len(), capacity);
+		let (cached, _) = cache.get(1, &[Position { row: 0, col: 0 }]);
+		assert!(cached.is_empty());
+	}
+
+	#[test]
+	fn disabled_cache() {
+		let cache = CellCache::new(0);
+		cache.insert(1, &[cell(0, 0)]);
+		assert!(cache.is_empty());
+		let (_, missing) = cache.get(1, &[Position { row: 0, col: 0 }]);
+		assert_eq!(missing.len(), 1);
+	}
+}
@@ -0,0 +1,140 @@
+//! Browser storage backend, which persists the data into the IndexedDB object store.
+
+use crate::{
+	data::{
+		mem_db::{encode_batch, HashMapKey, MemoryDB},
+		Batch, Database, Key,
+	},
+	platform,
+};
+use codec::{Decode, Encode};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Report, Result,
+};
+use rexie::{ObjectStore, Rexie, TransactionMode};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::error;
+use wasm_bindgen::JsValue;
+
+/// Object store in which the entries are stored
+const OBJECT_STORE: &str = "avail_light";
+
+type Writes = Vec<(HashMapKey, Option<String>)>;
+
+#[derive(Clone)]
+pub struct IndexedDB {
+	cache: MemoryDB,
+	writes: mpsc::UnboundedSender<Writes>,
+}
+
+fn js_error(error: rexie::Error) -> Report {
+	eyre!("{error}")
+}
+
+impl IndexedDB {
+	/// Opens the IndexedDB database with the given name, and loads its entries.
+	pub async fn open(name: &str) -> Result<IndexedDB> {
+		let rexie = Rexie::builder(name)
+			.version(1)
+			.add_object_store(ObjectStore::new(OBJECT_STORE))
+			.build()
+			.await
+			.map_err(js_error)
+			.wrap_err("Failed to open IndexedDB database")?;
+
+		let cache = MemoryDB::default();
+		cache.apply(Self::load(&rexie).await?);
+
+		let (writes, receiver) = mpsc::unbounded_channel();
+		platform::spawn(persist(rexie, receiver));
+		Ok(IndexedDB { cache, writes })
+	}
+
+	async fn load(rexie: &Rexie) -> Result<Writes> {
+		let transaction = rexie
+			.transaction(&[OBJECT_STORE], TransactionMode::ReadOnly)
+			.map_err(js_error)?;
+		let store = transaction.store(OBJECT_STORE).map_err(js_error)?;
+		let entries = store
+			.get_all(None, None, None, None)
+			.await
+			.map_err(js_error)
+			.wrap_err("Failed to load IndexedDB entries")?;
+		transaction.done().await.map_err(js_error)?;
+
+		Ok(entries
+			.into_iter()
+			.filter_map(|(key, value)| Some((HashMapKey(key.as_string()?), value.as_string())))
+			.collect())
+	}
+
+	/// Applies encoded writes to memory, and queues them for persistence.
+	fn apply(&self, writes: Writes) -> Result<()> {
+		self.cache.apply(writes.clone());
+		self.writes
+			.send(writes)
+			.map_err(|_| eyre!("IndexedDB persistence task is stopped"))
+	}
+}
+
+/// Persists the queued writes, in order.
+async fn persist(rexie: Rexie, mut receiver: mpsc::UnboundedReceiver<Writes>) {
+	while let Some(writes) = receiver.recv().await {
+		if let Err(error) = persist_writes(&rexie, writes).await {
+			error!("Cannot persist writes into IndexedDB: {error:#}");
+		}
+	}
+}
+
+async fn persist_writes(rexie: &Rexie, writes: Writes) -> Result<()> {
+	let transaction = rexie
+		.transaction(&[OBJECT_STORE], TransactionMode::ReadWrite)
+		.map_err(js_error)?;
+	let store = transaction.store(OBJECT_STORE).map_err(js_error)?;
+	for (HashMapKey(key), value) in writes {
+		let key = JsValue::from_str(&key);
+		match value {
+			Some(value) => store
+				.put(&JsValue::from_str(&value), Some(&key))
+				.await
+				.map(|_| ()),
+			None => store.delete(&key).await,
+		}
+		.map_err(js_error)?;
+	}
+	transaction.done().await.map_err(js_error)?;
+	Ok(())
+}
+
+impl Database for IndexedDB {
+	type Key = HashMapKey;
+
+	fn put<T>(&self, key: Key, value: T) -> Result<()>
+	where
+		T: Serialize + Encode,
+	{
+		self.apply(vec![(key.into(), Some(serde_json::to_string(&value)?))])
+	}
+
+	fn get<T>(&self, key: Key) -> Result<Option<T>>
+	where
+		for<'a> T: Deserialize<'a> + Decode,
+	{
+		self.cache.get(key)
+	}
+
+	fn delete(&self, key: Key) -> Result<()> {
+		self.apply(vec![(key.into(), None)])
+	}
+
+	/// Batch is persisted in a single IndexedDB transaction.
+	fn write(&self, batch: Batch) -> Result<()> {
+		self.apply(encode_batch(batch)?)
+	}
+
+	fn keys(&self, column_family: &'static str) -> Result<Vec<Key>> {
+		self.cache.keys(column_family)
+	}
+}
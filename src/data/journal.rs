@@ -0,0 +1,169 @@
+//! Journal of the block verification results, so an unclean shutdown never leaves the confidence stored without its header.
+
+use color_eyre::{eyre::WrapErr, Result};
+use std::collections::HashSet;
+
+use super::{Batch, Database, Key, BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF, JOURNAL_CF};
+
+/// Writes verification results of the block, recording the block in the journal until they are written.
+pub fn write_block(db: &impl Database, block_number: u32, mut batch: Batch) -> Result<()> {
+	db.put(Key::Journal(block_number), true)
+		.wrap_err("Failed to record block in the journal")?;
+	batch.delete(Key::Journal(block_number));
+	db.write(batch)
+}
+
+/// Outcome of the startup consistency repair.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Repair {
+	/// Blocks whose write was interrupted
+	pub interrupted: Vec<u32>,
+	/// Blocks whose confidence was stored without the header
+	pub orphaned: Vec<u32>,
+}
+
+impl Repair {
+	pub fn is_empty(&self) -> bool {
+		self.interrupted.is_empty() && self.orphaned.is_empty()
+	}
+}
+
+/// Removes verification results of the interrupted blocks, and confidence of the blocks without the header.
+pub fn repair(db: &impl Database) -> Result<Repair> {
+	let mut repair = Repair::default();
+	let mut batch = Batch::default();
+
+	for key in db.keys(JOURNAL_CF)? {
+		let Key::Journal(block_number) = key else {
+			continue;
+		};
+		batch.delete(Key::CanonicalHash(block_number));
+		batch.delete(Key::Journal(block_number));
+		repair.interrupted.push(block_number);
+	}
+
+	let mut headers = HashSet::new();
+	for key in db.keys(BLOCK_HEADER_CF)? {
+		let Key::BlockHeader(block_number, block_hash) = key else {
+			continue;
+		};
+		if repair.interrupted.contains(&block_number) {
+			batch.delete(Key::BlockNumber(block_hash));
+			batch.delete(Key::BlockHeader(block_number, block_hash));
+		} else {
+			headers.insert((block_number, block_hash));
+		}
+	}
+
+	for key in db.keys(CONFIDENCE_FACTOR_CF)? {
+		let Key::VerifiedCellCount(block_number, block_hash) = key else {
+			continue;
+		};
+		if repair.interrupted.contains(&block_number) {
+			batch.delete(Key::VerifiedCellCount(block_number, block_hash));
+			continue;
+		}
+		if !headers.contains(&(block_number, block_hash)) {
+			batch.delete(Key::VerifiedCellCount(block_number, block_hash));
+			repair.orphaned.push(block_number);
+		}
+	}
+	repair.orphaned.sort();
+
+	db.write(batch)
+		.wrap_err("Failed to repair verification results")?;
+	Ok(repair)
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3, HeaderExtension},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::Digest,
+		primitives::Header,
+	};
+	use sp_core::H256;
+
+	use super::{repair, write_block, Repair};
+	use crate::data::{get_canonical, mem_db::MemoryDB, Batch, Database, Key};
+
+	fn header(number: u32) -> Header {
+		Header {
+			parent_hash: H256::zero(),
+			number,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs: vec![] },
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 1,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+		}
+	}
+
+	#[test]
+	fn written_block_is_removed_from_journal() {
+		let db = MemoryDB::default();
+		let mut batch = Batch::default();
+		let block_hash = batch.put_finalized_header(header(1));
+		batch.put(Key::VerifiedCellCount(1, block_hash), 8u32);
+		write_block(&db, 1, batch).unwrap();
+
+		assert_eq!(
+			get_canonical::<u32>(&db, 1, Key::VerifiedCellCount).unwrap(),
+			Some(8)
+		);
+		assert_eq!(db.get::<bool>(Key::Journal(1)).unwrap(), None);
+		assert!(repair(&db).unwrap().is_empty());
+	}
+
+	#[test]
+	fn interrupted_and_orphaned_blocks_are_repaired() {
+		let db = MemoryDB::default();
+		let hash = H256::repeat_byte;
+		// Block 1 write is interrupted after the confidence is written
+		db.put(Key::Journal(1), true).unwrap();
+		db.put(Key::VerifiedCellCount(1, hash(1)), 8u32).unwrap();
+		// Block 2 confidence is stored without the header
+		db.put(Key::VerifiedCellCount(2, hash(2)), 8u32).unwrap();
+		// Block 3 is consistent
+		let mut batch = Batch::default();
+		let block_hash = batch.put_finalized_header(header(3));
+		batch.put(Key::VerifiedCellCount(3, block_hash), 8u32);
+		db.write(batch).unwrap();
+
+		let repaired = repair(&db).unwrap();
+		assert_eq!(
+			repaired,
+			Repair {
+				interrupted: vec![1],
+				orphaned: vec![2],
+			}
+		);
+		assert_eq!(
+			db.get::<u32>(Key::VerifiedCellCount(1, hash(1))).unwrap(),
+			None
+		);
+		assert_eq!(
+			db.get::<u32>(Key::VerifiedCellCount(2, hash(2))).unwrap(),
+			None
+		);
+		assert_eq!(
+			get_canonical::<u32>(&db, 3, Key::VerifiedCellCount).unwrap(),
+			Some(8)
+		);
+		assert_eq!(db.get::<bool>(Key::Journal(1)).unwrap(), None);
+	}
+}
@@ -1,5 +1,9 @@
 use crate::data::{
-	Database, Key, APP_DATA_CF, BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF, FINALITY_SYNC_CHECKPOINT_KEY,
+	Batch, Database, Key, APP_DATA_CF, ATTESTATION_CF, BLOCK_FAILURE_CF, BLOCK_HEADER_CF,
+	BLOCK_NUMBER_CF, CANONICAL_HASH_CF, COMMITMENT_MISMATCH_CF, CONFIDENCE_FACTOR_CF,
+	FINALITY_SYNC_CHECKPOINT_KEY, HEALTH_PROBE_KEY, JOURNAL_CF, P2P_KEYPAIR_KEY, P2P_PEERS_KEY,
+	RETENTION_CHECKPOINT_KEY, RPC_LAST_NODE_KEY, RPC_NODE_SCORES_KEY, SAMPLING_STATS_CF,
+	SYNC_PROGRESS_KEY, SYNC_RESULT_CF, TRANSACTION_STATUS_CF,
 };
 use color_eyre::eyre::{eyre, Result};
 use serde::{Deserialize, Serialize};
@@ -8,7 +12,7 @@ use std::{
 	sync::{Arc, RwLock},
 };
 
-#[derive(Eq, Hash, PartialEq)]
+#[derive(Clone, Eq, Hash, PartialEq)]
 pub struct HashMapKey(pub String);
 
 #[derive(Clone)]
@@ -24,6 +28,28 @@ impl Default for MemoryDB {
 	}
 }
 
+impl MemoryDB {
+	/// Applies encoded writes with a single lock, value is not set for deletes.
+	pub(crate) fn apply(&self, writes: Vec<(HashMapKey, Option<String>)>) {
+		let mut map = self.map.write().expect("Lock acquired");
+		for (key, value) in writes {
+			match value {
+				Some(value) => map.insert(key, value),
+				None => map.remove(&key),
+			};
+		}
+	}
+}
+
+/// Encodes keys and values of the batch writes.
+pub(crate) fn encode_batch(batch: Batch) -> Result<Vec<(HashMapKey, Option<String>)>> {
+	batch
+		.writes
+		.into_iter()
+		.map(|(key, value)| Ok((key.into(), value.map(|value| value.to_json()).transpose()?)))
+		.collect()
+}
+
 impl Database for MemoryDB {
 	type Key = HashMapKey;
 	fn put<T>(&self, key: Key, value: T) -> Result<()>
@@ -51,21 +77,96 @@ impl Database for MemoryDB {
 		map.remove(&key.into());
 		Ok(())
 	}
+
+	/// Values are encoded before the map is locked, so the batch is applied with a single lock.
+	fn write(&self, batch: Batch) -> Result<()> {
+		self.apply(encode_batch(batch)?);
+		Ok(())
+	}
+
+	fn keys(&self, column_family: &'static str) -> Result<Vec<Key>> {
+		let map = self.map.read().expect("Lock acquired");
+		Ok(map
+			.keys()
+			.filter_map(|HashMapKey(key)| {
+				let key = key.strip_prefix(column_family)?.strip_prefix(':')?;
+				decode_key(column_family, key)
+			})
+			.collect())
+	}
+}
+
+/// Decodes key of the column family, with the column family prefix stripped.
+fn decode_key(column_family: &str, key: &str) -> Option<Key> {
+	let block_number = || key.parse().ok();
+	let block = || {
+		let (block_number, block_hash) = key.split_once(':')?;
+		Some((block_number.parse().ok()?, block_hash.parse().ok()?))
+	};
+	match column_family {
+		APP_DATA_CF => {
+			let (app_id, block) = key.split_once(':')?;
+			let (block_number, block_hash) = block.split_once(':')?;
+			Some(Key::AppData(
+				app_id.parse().ok()?,
+				block_number.parse().ok()?,
+				block_hash.parse().ok()?,
+			))
+		},
+		BLOCK_HEADER_CF => block().map(|(number, hash)| Key::BlockHeader(number, hash)),
+		CONFIDENCE_FACTOR_CF => block().map(|(number, hash)| Key::VerifiedCellCount(number, hash)),
+		CANONICAL_HASH_CF => block_number().map(Key::CanonicalHash),
+		SAMPLING_STATS_CF => block_number().map(Key::BlockSamplingStats),
+		SYNC_RESULT_CF => block_number().map(Key::SyncResult),
+		ATTESTATION_CF => block_number().map(Key::Attestation),
+		COMMITMENT_MISMATCH_CF => block_number().map(Key::CommitmentMismatch),
+		BLOCK_FAILURE_CF => block_number().map(Key::BlockFailure),
+		JOURNAL_CF => block_number().map(Key::Journal),
+		TRANSACTION_STATUS_CF => key.parse().ok().map(Key::TransactionStatus),
+		BLOCK_NUMBER_CF => key.parse().ok().map(Key::BlockNumber),
+		_ => None,
+	}
 }
 
 impl From<Key> for HashMapKey {
 	fn from(key: Key) -> Self {
 		match key {
-			Key::AppData(app_id, block_number) => {
-				HashMapKey(format!("{APP_DATA_CF}:{app_id}:{block_number}"))
+			Key::AppData(app_id, block_number, block_hash) => HashMapKey(format!(
+				"{APP_DATA_CF}:{app_id}:{block_number}:{block_hash:?}"
+			)),
+			Key::BlockHeader(block_number, block_hash) => {
+				HashMapKey(format!("{BLOCK_HEADER_CF}:{block_number}:{block_hash:?}"))
+			},
+			Key::BlockNumber(block_hash) => HashMapKey(format!("{BLOCK_NUMBER_CF}:{block_hash:?}")),
+			Key::CanonicalHash(block_number) => {
+				HashMapKey(format!("{CANONICAL_HASH_CF}:{block_number}"))
+			},
+			Key::VerifiedCellCount(block_number, block_hash) => HashMapKey(format!(
+				"{CONFIDENCE_FACTOR_CF}:{block_number}:{block_hash:?}"
+			)),
+			Key::BlockSamplingStats(block_number) => {
+				HashMapKey(format!("{SAMPLING_STATS_CF}:{block_number}"))
+			},
+			Key::TransactionStatus(hash) => HashMapKey(format!("{TRANSACTION_STATUS_CF}:{hash:?}")),
+			Key::SyncResult(block_number) => HashMapKey(format!("{SYNC_RESULT_CF}:{block_number}")),
+			Key::Attestation(block_number) => {
+				HashMapKey(format!("{ATTESTATION_CF}:{block_number}"))
 			},
-			Key::BlockHeader(block_number) => {
-				HashMapKey(format!("{BLOCK_HEADER_CF}:{block_number}"))
+			Key::CommitmentMismatch(block_number) => {
+				HashMapKey(format!("{COMMITMENT_MISMATCH_CF}:{block_number}"))
 			},
-			Key::VerifiedCellCount(block_number) => {
-				HashMapKey(format!("{CONFIDENCE_FACTOR_CF}:{block_number}"))
+			Key::BlockFailure(block_number) => {
+				HashMapKey(format!("{BLOCK_FAILURE_CF}:{block_number}"))
 			},
 			Key::FinalitySyncCheckpoint => HashMapKey(FINALITY_SYNC_CHECKPOINT_KEY.to_string()),
+			Key::SyncProgress => HashMapKey(SYNC_PROGRESS_KEY.to_string()),
+			Key::RpcNodeScores => HashMapKey(RPC_NODE_SCORES_KEY.to_string()),
+			Key::RpcLastNode => HashMapKey(RPC_LAST_NODE_KEY.to_string()),
+			Key::P2PKeypair => HashMapKey(P2P_KEYPAIR_KEY.to_string()),
+			Key::P2PPeers => HashMapKey(P2P_PEERS_KEY.to_string()),
+			Key::RetentionCheckpoint => HashMapKey(RETENTION_CHECKPOINT_KEY.to_string()),
+			Key::HealthProbe => HashMapKey(HEALTH_PROBE_KEY.to_string()),
+			Key::Journal(block_number) => HashMapKey(format!("{JOURNAL_CF}:{block_number}")),
 		}
 	}
 }
@@ -0,0 +1,405 @@
+//! Schema versioning and migrations of the RocksDB database.
+
+use avail_subxt::primitives::Header;
+use codec::{Decode, Encode};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use rocksdb::{IteratorMode, WriteBatch, DB};
+use sp_core::{blake2_256, H256};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use super::{
+	rocks_db::block_key, APP_DATA_CF, BLOCK_HEADER_CF, BLOCK_NUMBER_CF, CANONICAL_HASH_CF,
+	CONFIDENCE_FACTOR_CF, SCHEMA_VERSION_KEY, STATE_CF,
+};
+
+/// Schema version of the current release
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Schema version of the databases without version record
+const INITIAL_SCHEMA_VERSION: u32 = 1;
+
+pub struct Migration {
+	/// Schema version after the migration is applied
+	pub version: u32,
+	pub description: &'static str,
+	pub migrate: fn(&DB) -> Result<()>,
+}
+
+/// Migrations in the ascending version order
+const MIGRATIONS: &[Migration] = &[Migration {
+	version: 2,
+	description: "key block headers, confidence and app data by block number and hash",
+	migrate: key_blocks_by_hash,
+}];
+
+pub fn stored_version(db: &DB) -> Result<Option<u32>> {
+	let cf_handle = db
+		.cf_handle(STATE_CF)
+		.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+	db.get_cf(&cf_handle, SCHEMA_VERSION_KEY)?
+		.map(|value| u32::decode(&mut &value[..]).wrap_err("Failed decoding schema version"))
+		.transpose()
+}
+
+fn store_version(db: &DB, version: u32) -> Result<()> {
+	let cf_handle = db
+		.cf_handle(STATE_CF)
+		.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+	db.put_cf(&cf_handle, SCHEMA_VERSION_KEY, version.encode())
+		.wrap_err("Failed to store schema version")
+}
+
+fn apply(db: &DB, is_new: bool, migrations: &[Migration], latest: u32) -> Result<()> {
+	let version = match stored_version(db)? {
+		Some(version) => version,
+		None if is_new => latest,
+		None => INITIAL_SCHEMA_VERSION,
+	};
+
+	if version > latest {
+		return Err(eyre!(
+			"Database schema version {version} is newer than the supported version {latest}, upgrade the light client or remove the database"
+		));
+	}
+
+	for migration in migrations.iter().filter(|m| m.version > version) {
+		info!(
+			"Migrating database to schema version {}: {}",
+			migration.version, migration.description
+		);
+		(migration.migrate)(db)
+			.wrap_err_with(|| format!("Migration to version {} failed", migration.version))?;
+		store_version(db, migration.version)?;
+	}
+
+	if stored_version(db)? != Some(latest) {
+		store_version(db, latest)?;
+	}
+	Ok(())
+}
+
+/// Upgrades the database to the latest schema version.
+/// Fails if the database is created by a newer release, since its data cannot be read.
+pub fn migrate(db: &DB, is_new: bool) -> Result<()> {
+	apply(db, is_new, MIGRATIONS, SCHEMA_VERSION)
+}
+
+/// Moves all entries of the column family to the new column family, and drops the old one.
+/// Does nothing if the old column family doesn't exist.
+pub fn rename_column_family(db: &DB, from: &str, to: &str) -> Result<()> {
+	let Some(from_handle) = db.cf_handle(from) else {
+		return Ok(());
+	};
+	if db.cf_handle(to).is_none() {
+		db.create_cf(to, &rocksdb::Options::default())?;
+	}
+	let to_handle = db
+		.cf_handle(to)
+		.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+
+	let mut batch = WriteBatch::default();
+	for entry in db.iterator_cf(&from_handle, IteratorMode::Start) {
+		let (key, value) = entry?;
+		batch.put_cf(&to_handle, key, value);
+	}
+	db.write(batch)
+		.wrap_err_with(|| format!("Failed to copy entries from {from} to {to}"))?;
+
+	drop(from_handle);
+	db.drop_cf(from)
+		.wrap_err_with(|| format!("Failed to drop column family {from}"))
+}
+
+/// Re-encodes keys of the column family. Keys for which `encode` returns `None` are kept.
+pub fn reencode_keys(
+	db: &DB,
+	column_family: &str,
+	encode: impl Fn(&[u8]) -> Option<Vec<u8>>,
+) -> Result<()> {
+	let cf_handle = db
+		.cf_handle(column_family)
+		.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+
+	let mut batch = WriteBatch::default();
+	for entry in db.iterator_cf(&cf_handle, IteratorMode::Start) {
+		let (key, value) = entry?;
+		let Some(new_key) = encode(&key) else {
+			continue;
+		};
+		if new_key[..] != key[..] {
+			batch.delete_cf(&cf_handle, &key);
+			batch.put_cf(&cf_handle, new_key, value);
+		}
+	}
+	db.write(batch)
+		.wrap_err_with(|| format!("Failed to re-encode keys of {column_family}"))
+}
+
+/// Re-keys headers, confidence and app data stored by the block number, with the block hash of the stored header.
+/// Stored blocks are finalized, so they are indexed as the canonical blocks, and their numbers are indexed by hash.
+/// Confidence and app data of the blocks without the stored header cannot be keyed, so they are removed.
+fn key_blocks_by_hash(db: &DB) -> Result<()> {
+	let cf_handle = |name: &str| {
+		db.cf_handle(name)
+			.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))
+	};
+	let (headers, canonical) = (cf_handle(BLOCK_HEADER_CF)?, cf_handle(CANONICAL_HASH_CF)?);
+	let numbers = cf_handle(BLOCK_NUMBER_CF)?;
+
+	let mut hashes = HashMap::new();
+	let mut batch = WriteBatch::default();
+	for entry in db.iterator_cf(&headers, IteratorMode::Start) {
+		let (key, value) = entry?;
+		let Ok(block_number) = <[u8; 4]>::try_from(&key[..]).map(u32::from_be_bytes) else {
+			continue;
+		};
+		let header = Header::decode(&mut &value[..]).wrap_err("Failed decoding block header")?;
+		let block_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+		batch.delete_cf(&headers, &key);
+		batch.put_cf(&headers, block_key(block_number, block_hash), value);
+		batch.put_cf(&canonical, key, block_hash.encode());
+		batch.put_cf(&numbers, block_hash.as_bytes(), block_number.encode());
+		hashes.insert(block_number, block_hash);
+	}
+
+	let mut removed = 0;
+	let confidence = cf_handle(CONFIDENCE_FACTOR_CF)?;
+	for entry in db.iterator_cf(&confidence, IteratorMode::Start) {
+		let (key, value) = entry?;
+		let Ok(block_number) = <[u8; 4]>::try_from(&key[..]).map(u32::from_be_bytes) else {
+			continue;
+		};
+		batch.delete_cf(&confidence, &key);
+		match hashes.get(&block_number) {
+			Some(block_hash) => {
+				batch.put_cf(&confidence, block_key(block_number, *block_hash), value)
+			},
+			None => removed += 1,
+		}
+	}
+
+	let app_data = cf_handle(APP_DATA_CF)?;
+	for entry in db.iterator_cf(&app_data, IteratorMode::Start) {
+		let (key, value) = entry?;
+		let Some((app_id, block_number)) = std::str::from_utf8(&key)
+			.ok()
+			.and_then(|key| key.split_once(':'))
+			.filter(|(_, block_number)| !block_number.contains(':'))
+		else {
+			continue;
+		};
+		batch.delete_cf(&app_data, &key);
+		match block_number.parse().ok().and_then(|n: u32| hashes.get(&n)) {
+			Some(block_hash) => {
+				let key = format!("{app_id}:{block_number}:{block_hash:?}");
+				batch.put_cf(&app_data, key, value)
+			},
+			None => removed += 1,
+		}
+	}
+
+	if removed > 0 {
+		warn!(
+			"Removed {removed} confidence and app data entries of the blocks without stored header"
+		);
+	}
+	db.write(batch)
+		.wrap_err("Failed to key blocks by block number and hash")
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3, HeaderExtension},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::Digest,
+		primitives::Header,
+	};
+	use codec::Encode;
+	use rocksdb::{Options, DB};
+	use sp_core::{blake2_256, H256};
+	use std::path::PathBuf;
+
+	use super::{
+		apply, key_blocks_by_hash, reencode_keys, rename_column_family, stored_version, Migration,
+	};
+	use crate::data::{
+		rocks_db::block_key, APP_DATA_CF, BLOCK_HEADER_CF, BLOCK_NUMBER_CF, CANONICAL_HASH_CF,
+		CONFIDENCE_FACTOR_CF, STATE_CF,
+	};
+
+	struct TemporaryDB {
+		db: Option<DB>,
+		path: PathBuf,
+	}
+
+	impl TemporaryDB {
+		fn open(name: &str, column_families: &[&str]) -> Self {
+			let path = std::env::temp_dir().join(format!(
+				"avail_light_migrations_{name}_{}",
+				std::process::id()
+			));
+			let mut opts = Options::default();
+			opts.create_if_missing(true);
+			opts.create_missing_column_families(true);
+			let db = DB::open_cf(&opts, &path, column_families).unwrap();
+			TemporaryDB { db: Some(db), path }
+		}
+
+		fn db(&self) -> &DB {
+			self.db.as_ref().unwrap()
+		}
+	}
+
+	impl Drop for TemporaryDB {
+		fn drop(&mut self) {
+			self.db.take();
+			let _ = DB::destroy(&Options::default(), &self.path);
+		}
+	}
+
+	fn put(db: &DB, cf: &str, key: &[u8], value: &[u8]) {
+		db.put_cf(&db.cf_handle(cf).unwrap(), key, value).unwrap();
+	}
+
+	fn get(db: &DB, cf: &str, key: &[u8]) -> Option<Vec<u8>> {
+		db.get_cf(&db.cf_handle(cf).unwrap(), key).unwrap()
+	}
+
+	const MIGRATIONS: &[Migration] = &[
+		Migration {
+			version: 2,
+			description: "rename legacy column family",
+			migrate: |db| rename_column_family(db, "legacy_cf", APP_DATA_CF),
+		},
+		Migration {
+			version: 3,
+			description: "prefix keys",
+			migrate: |db| {
+				reencode_keys(db, APP_DATA_CF, |key| {
+					(!key.starts_with(b"v3:")).then(|| [b"v3:", key].concat())
+				})
+			},
+		},
+	];
+
+	#[test]
+	fn migrate_legacy_database() {
+		let temporary = TemporaryDB::open("legacy", &[STATE_CF, "legacy_cf"]);
+		let db = temporary.db();
+		put(db, "legacy_cf", b"1:1", b"data");
+
+		apply(db, false, MIGRATIONS, 3).unwrap();
+
+		assert_eq!(stored_version(db).unwrap(), Some(3));
+		assert!(db.cf_handle("legacy_cf").is_none());
+		assert_eq!(get(db, APP_DATA_CF, b"v3:1:1"), Some(b"data".to_vec()));
+		assert_eq!(get(db, APP_DATA_CF, b"1:1"), None);
+
+		// Applied migrations are skipped
+		apply(db, false, MIGRATIONS, 3).unwrap();
+		assert_eq!(get(db, APP_DATA_CF, b"v3:1:1"), Some(b"data".to_vec()));
+	}
+
+	#[test]
+	fn new_database_is_not_migrated() {
+		let temporary = TemporaryDB::open("new", &[STATE_CF, APP_DATA_CF]);
+		let db = temporary.db();
+		put(db, APP_DATA_CF, b"1:1", b"data");
+
+		apply(db, true, MIGRATIONS, 3).unwrap();
+
+		assert_eq!(stored_version(db).unwrap(), Some(3));
+		assert_eq!(get(db, APP_DATA_CF, b"1:1"), Some(b"data".to_vec()));
+	}
+
+	#[test]
+	fn blocks_are_keyed_by_hash() {
+		let temporary = TemporaryDB::open(
+			"blocks_by_hash",
+			&[
+				BLOCK_HEADER_CF,
+				CANONICAL_HASH_CF,
+				BLOCK_NUMBER_CF,
+				CONFIDENCE_FACTOR_CF,
+				APP_DATA_CF,
+			],
+		);
+		let db = temporary.db();
+		let header = Header {
+			parent_hash: H256::zero(),
+			number: 1,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs: vec![] },
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 1,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+		};
+		let block_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+		put(db, BLOCK_HEADER_CF, &1u32.to_be_bytes(), &header.encode());
+		put(
+			db,
+			CONFIDENCE_FACTOR_CF,
+			&1u32.to_be_bytes(),
+			&8u32.encode(),
+		);
+		// Confidence of the block 2 is stored without the header
+		put(
+			db,
+			CONFIDENCE_FACTOR_CF,
+			&2u32.to_be_bytes(),
+			&8u32.encode(),
+		);
+		put(db, APP_DATA_CF, b"1:1", b"data");
+
+		key_blocks_by_hash(db).unwrap();
+
+		let key = block_key(1, block_hash);
+		assert!(get(db, BLOCK_HEADER_CF, &key).is_some());
+		assert_eq!(get(db, BLOCK_HEADER_CF, &1u32.to_be_bytes()), None);
+		assert_eq!(
+			get(db, CANONICAL_HASH_CF, &1u32.to_be_bytes()),
+			Some(block_hash.encode())
+		);
+		// Migrated block is found by its hash
+		assert_eq!(
+			get(db, BLOCK_NUMBER_CF, block_hash.as_bytes()),
+			Some(1u32.encode())
+		);
+		assert_eq!(get(db, CONFIDENCE_FACTOR_CF, &key), Some(8u32.encode()));
+		assert_eq!(get(db, CONFIDENCE_FACTOR_CF, &2u32.to_be_bytes()), None);
+		let app_data_key = format!("1:1:{block_hash:?}");
+		assert_eq!(
+			get(db, APP_DATA_CF, app_data_key.as_bytes()),
+			Some(b"data".to_vec())
+		);
+		assert_eq!(get(db, APP_DATA_CF, b"1:1"), None);
+	}
+
+	#[test]
+	fn newer_database_is_rejected() {
+		let temporary = TemporaryDB::open("newer", &[STATE_CF]);
+		let db = temporary.db();
+
+		apply(db, true, &[], 4).unwrap();
+		assert!(apply(db, false, MIGRATIONS, 3).is_err());
+		assert_eq!(stored_version(db).unwrap(), Some(4));
+	}
+}
@@ -1,11 +1,117 @@
-use crate::data::{self, Key, APP_DATA_CF, BLOCK_HEADER_CF, CONFIDENCE_FACTOR_CF, STATE_CF};
+use crate::data::{
+	self, Batch, Compression, Key, APP_DATA_CF, ATTESTATION_CF, BLOCK_FAILURE_CF, BLOCK_HEADER_CF,
+	BLOCK_NUMBER_CF, CANONICAL_HASH_CF, COMMITMENT_MISMATCH_CF, CONFIDENCE_FACTOR_CF, JOURNAL_CF,
+	SAMPLING_STATS_CF, STATE_CF, SYNC_RESULT_CF, TRANSACTION_STATUS_CF,
+};
 use codec::{Decode, Encode};
 use color_eyre::eyre::{eyre, Context, Result};
-use rocksdb::{ColumnFamilyDescriptor, Options};
+use rocksdb::{
+	BlockBasedOptions, Cache, ColumnFamilyDescriptor, DBCompressionType, IteratorMode, Options,
+	WriteBatch,
+};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use sp_core::H256;
+use std::{collections::HashMap, sync::Arc};
+use tracing::warn;
 
-use super::FINALITY_SYNC_CHECKPOINT_KEY;
+use super::{
+	migrations, FINALITY_SYNC_CHECKPOINT_KEY, HEALTH_PROBE_KEY, P2P_KEYPAIR_KEY, P2P_PEERS_KEY,
+	RETENTION_CHECKPOINT_KEY, RPC_LAST_NODE_KEY, RPC_NODE_SCORES_KEY, SYNC_PROGRESS_KEY,
+};
+use crate::types::RuntimeConfig;
+
+/// Column families of the database
+const COLUMN_FAMILIES: [&str; 13] = [
+	CONFIDENCE_FACTOR_CF,
+	BLOCK_HEADER_CF,
+	APP_DATA_CF,
+	STATE_CF,
+	SAMPLING_STATS_CF,
+	TRANSACTION_STATUS_CF,
+	SYNC_RESULT_CF,
+	BLOCK_NUMBER_CF,
+	ATTESTATION_CF,
+	COMMITMENT_MISMATCH_CF,
+	BLOCK_FAILURE_CF,
+	JOURNAL_CF,
+	CANONICAL_HASH_CF,
+];
+
+/// RocksDB tuning (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct RocksDBConfig {
+	pub compression: Compression,
+	/// Compression by the short column family name, overriding the default compression
+	pub column_family_compression: HashMap<String, Compression>,
+	/// Size of the block cache shared by the column families, in bytes
+	pub block_cache_size: usize,
+	/// Size of the memtable of each column family, in bytes
+	pub write_buffer_size: usize,
+}
+
+impl Default for RocksDBConfig {
+	fn default() -> Self {
+		(&RuntimeConfig::default()).into()
+	}
+}
+
+impl From<&RuntimeConfig> for RocksDBConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		RocksDBConfig {
+			compression: val.rocksdb_compression,
+			column_family_compression: val.rocksdb_column_family_compression.clone(),
+			block_cache_size: val.rocksdb_block_cache_size * 1024 * 1024,
+			write_buffer_size: val.rocksdb_write_buffer_size * 1024 * 1024,
+		}
+	}
+}
+
+impl From<Compression> for DBCompressionType {
+	fn from(compression: Compression) -> Self {
+		match compression {
+			Compression::None => DBCompressionType::None,
+			Compression::Snappy => DBCompressionType::Snappy,
+			Compression::Lz4 => DBCompressionType::Lz4,
+			Compression::Zstd => DBCompressionType::Zstd,
+		}
+	}
+}
+
+/// Short name of the column family (e.g. `app_data` for `avail_light_app_data_cf`), used in the configuration.
+pub fn short_name(column_family: &str) -> &str {
+	column_family
+		.trim_start_matches("avail_light_")
+		.trim_end_matches("_cf")
+}
+
+impl RocksDBConfig {
+	fn compression(&self, column_family: &str) -> Compression {
+		self.column_family_compression
+			.get(short_name(column_family))
+			.copied()
+			.unwrap_or(self.compression)
+	}
+
+	fn cf_options(&self, column_family: &str, cache: &Cache) -> Options {
+		let mut block_opts = BlockBasedOptions::default();
+		block_opts.set_block_cache(cache);
+		let mut opts = Options::default();
+		opts.set_block_based_table_factory(&block_opts);
+		opts.set_write_buffer_size(self.write_buffer_size);
+		opts.set_compression_type(self.compression(column_family).into());
+		opts
+	}
+}
+
+/// Size and number of keys of the column family.
+#[derive(Serialize, Debug, Clone)]
+pub struct ColumnFamilyStats {
+	pub name: String,
+	/// Total size of the SST files, in bytes
+	pub size: u64,
+	/// Estimated number of keys
+	pub keys: u64,
+}
 
 #[derive(Clone)]
 pub struct RocksDB {
@@ -13,47 +119,180 @@ pub struct RocksDB {
 }
 
 impl RocksDB {
-	pub fn open(path: &str) -> Result<RocksDB> {
-		let cf_opts = vec![
-			ColumnFamilyDescriptor::new(CONFIDENCE_FACTOR_CF, Options::default()),
-			ColumnFamilyDescriptor::new(BLOCK_HEADER_CF, Options::default()),
-			ColumnFamilyDescriptor::new(APP_DATA_CF, Options::default()),
-			ColumnFamilyDescriptor::new(STATE_CF, Options::default()),
-		];
-
+	/// Opens the database and migrates it to the latest schema version.
+	/// Compression is applied to the newly written data, existing data is recompressed by the compaction.
+	pub fn open(path: &str, cfg: &RocksDBConfig) -> Result<RocksDB> {
 		let mut db_opts = Options::default();
 		db_opts.create_if_missing(true);
 		db_opts.create_missing_column_families(true);
 
+		// Column families which are not used anymore are opened as well, so they can be migrated
+		let existing = rocksdb::DB::list_cf(&db_opts, path).ok();
+		let is_new = existing.is_none();
+		let mut column_families = COLUMN_FAMILIES.map(String::from).to_vec();
+		for cf in existing.unwrap_or_default() {
+			if !column_families.contains(&cf) {
+				column_families.push(cf);
+			}
+		}
+
+		for name in cfg.column_family_compression.keys() {
+			if !COLUMN_FAMILIES.iter().any(|&cf| short_name(cf) == name) {
+				warn!("Compression is set for unknown column family {name}");
+			}
+		}
+
+		let cache = Cache::new_lru_cache(cfg.block_cache_size);
+		let cf_opts = column_families
+			.into_iter()
+			.map(|cf| {
+				let opts = cfg.cf_options(&cf, &cache);
+				ColumnFamilyDescriptor::new(cf, opts)
+			})
+			.collect::<Vec<_>>();
+
 		let db = rocksdb::DB::open_cf_descriptors(&db_opts, path, cf_opts)?;
+		migrations::migrate(&db, is_new).wrap_err("Database migration failed")?;
 		Ok(RocksDB { db: Arc::new(db) })
 	}
+
+	/// Returns schema version of the database, without migrating it.
+	pub fn schema_version(path: &str) -> Result<Option<u32>> {
+		let opts = Options::default();
+		let column_families =
+			rocksdb::DB::list_cf(&opts, path).wrap_err("Failed to open RocksDB")?;
+		let db = rocksdb::DB::open_cf_for_read_only(&opts, path, column_families, false)
+			.wrap_err("Failed to open RocksDB")?;
+		migrations::stored_version(&db)
+	}
+
+	/// Returns sizes and numbers of keys of the column families, without migrating the database.
+	pub fn stats(path: &str) -> Result<Vec<ColumnFamilyStats>> {
+		let opts = Options::default();
+		let column_families =
+			rocksdb::DB::list_cf(&opts, path).wrap_err("Failed to open RocksDB")?;
+		let db = rocksdb::DB::open_cf_for_read_only(&opts, path, column_families.clone(), false)
+			.wrap_err("Failed to open RocksDB")?;
+
+		let mut stats = vec![];
+		for cf in column_families {
+			let cf_handle = db
+				.cf_handle(&cf)
+				.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+			let property = |name: &str| {
+				db.property_int_value_cf(&cf_handle, name)
+					.wrap_err("Failed to get Column Family property from RocksDB")
+					.map(Option::unwrap_or_default)
+			};
+			stats.push(ColumnFamilyStats {
+				size: property("rocksdb.total-sst-files-size")?,
+				keys: property("rocksdb.estimate-num-keys")?,
+				name: short_name(&cf).to_string(),
+			});
+		}
+		Ok(stats)
+	}
+
+	/// Repairs the database, data which cannot be recovered is lost.
+	pub fn repair(path: &str) -> Result<()> {
+		rocksdb::DB::repair(&Options::default(), path).wrap_err("Failed to repair RocksDB")
+	}
 }
 
 type RocksKey = (Option<&'static str>, Vec<u8>);
 
+/// Key of the block value, big endian block number followed by the block hash, so keys are ordered by the block number.
+pub(super) fn block_key(block_number: u32, block_hash: H256) -> Vec<u8> {
+	[&block_number.to_be_bytes()[..], block_hash.as_bytes()].concat()
+}
+
 impl From<Key> for (Option<&'static str>, Vec<u8>) {
 	fn from(key: Key) -> Self {
 		match key {
-			Key::AppData(app_id, block_number) => (
+			Key::AppData(app_id, block_number, block_hash) => (
 				Some(APP_DATA_CF),
-				format!("{app_id}:{block_number}").into_bytes(),
+				format!("{app_id}:{block_number}:{block_hash:?}").into_bytes(),
 			),
-			Key::BlockHeader(block_number) => {
-				(Some(BLOCK_HEADER_CF), block_number.to_be_bytes().to_vec())
+			Key::BlockHeader(block_number, block_hash) => {
+				(Some(BLOCK_HEADER_CF), block_key(block_number, block_hash))
 			},
-			Key::VerifiedCellCount(block_number) => (
+			Key::BlockNumber(block_hash) => (Some(BLOCK_NUMBER_CF), block_hash.as_bytes().to_vec()),
+			Key::CanonicalHash(block_number) => {
+				(Some(CANONICAL_HASH_CF), block_number.to_be_bytes().to_vec())
+			},
+			Key::VerifiedCellCount(block_number, block_hash) => (
 				Some(CONFIDENCE_FACTOR_CF),
+				block_key(block_number, block_hash),
+			),
+			Key::BlockSamplingStats(block_number) => {
+				(Some(SAMPLING_STATS_CF), block_number.to_be_bytes().to_vec())
+			},
+			Key::TransactionStatus(hash) => (Some(TRANSACTION_STATUS_CF), hash.as_bytes().to_vec()),
+			Key::SyncResult(block_number) => {
+				(Some(SYNC_RESULT_CF), block_number.to_be_bytes().to_vec())
+			},
+			Key::Attestation(block_number) => {
+				(Some(ATTESTATION_CF), block_number.to_be_bytes().to_vec())
+			},
+			Key::CommitmentMismatch(block_number) => (
+				Some(COMMITMENT_MISMATCH_CF),
 				block_number.to_be_bytes().to_vec(),
 			),
+			Key::BlockFailure(block_number) => {
+				(Some(BLOCK_FAILURE_CF), block_number.to_be_bytes().to_vec())
+			},
 			Key::FinalitySyncCheckpoint => (
 				Some(STATE_CF),
 				FINALITY_SYNC_CHECKPOINT_KEY.as_bytes().to_vec(),
 			),
+			Key::SyncProgress => (Some(STATE_CF), SYNC_PROGRESS_KEY.as_bytes().to_vec()),
+			Key::RpcNodeScores => (Some(STATE_CF), RPC_NODE_SCORES_KEY.as_bytes().to_vec()),
+			Key::RpcLastNode => (Some(STATE_CF), RPC_LAST_NODE_KEY.as_bytes().to_vec()),
+			Key::P2PKeypair => (Some(STATE_CF), P2P_KEYPAIR_KEY.as_bytes().to_vec()),
+			Key::P2PPeers => (Some(STATE_CF), P2P_PEERS_KEY.as_bytes().to_vec()),
+			Key::RetentionCheckpoint => {
+				(Some(STATE_CF), RETENTION_CHECKPOINT_KEY.as_bytes().to_vec())
+			},
+			Key::HealthProbe => (Some(STATE_CF), HEALTH_PROBE_KEY.as_bytes().to_vec()),
+			Key::Journal(block_number) => (Some(JOURNAL_CF), block_number.to_be_bytes().to_vec()),
 		}
 	}
 }
 
+/// Decodes key of the column family, stored by the RocksDB or sled database.
+pub(super) fn decode_key(column_family: &str, key: &[u8]) -> Option<Key> {
+	let block_number = || key.try_into().ok().map(u32::from_be_bytes);
+	let block = || {
+		let (block_number, block_hash) = (key.len() == 36).then(|| key.split_at(4))?;
+		let block_number = u32::from_be_bytes(block_number.try_into().ok()?);
+		Some((block_number, H256::from_slice(block_hash)))
+	};
+	match column_family {
+		APP_DATA_CF => {
+			let mut parts = std::str::from_utf8(key).ok()?.split(':');
+			Some(Key::AppData(
+				parts.next()?.parse().ok()?,
+				parts.next()?.parse().ok()?,
+				parts.next()?.parse().ok()?,
+			))
+		},
+		BLOCK_HEADER_CF => block().map(|(number, hash)| Key::BlockHeader(number, hash)),
+		CONFIDENCE_FACTOR_CF => block().map(|(number, hash)| Key::VerifiedCellCount(number, hash)),
+		CANONICAL_HASH_CF => block_number().map(Key::CanonicalHash),
+		SAMPLING_STATS_CF => block_number().map(Key::BlockSamplingStats),
+		SYNC_RESULT_CF => block_number().map(Key::SyncResult),
+		ATTESTATION_CF => block_number().map(Key::Attestation),
+		COMMITMENT_MISMATCH_CF => block_number().map(Key::CommitmentMismatch),
+		BLOCK_FAILURE_CF => block_number().map(Key::BlockFailure),
+		JOURNAL_CF => block_number().map(Key::Journal),
+		TRANSACTION_STATUS_CF => {
+			(key.len() == 32).then(|| Key::TransactionStatus(H256::from_slice(key)))
+		},
+		BLOCK_NUMBER_CF => (key.len() == 32).then(|| Key::BlockNumber(H256::from_slice(key))),
+		_ => None,
+	}
+}
+
 impl data::Database for RocksDB {
 	type Key = RocksKey;
 
@@ -126,4 +365,72 @@ impl data::Database for RocksDB {
 			.delete_cf(&cf_handle, key)
 			.wrap_err("Delete operation with Column Family failed on RocksDB")
 	}
+
+	fn write(&self, batch: Batch) -> Result<()> {
+		let mut write_batch = WriteBatch::default();
+		for (key, value) in batch.writes {
+			let (column_family, key): RocksKey = key.into();
+			let cf_handle = column_family
+				.map(|cf| {
+					self.db
+						.cf_handle(cf)
+						.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))
+				})
+				.transpose()?;
+			match (cf_handle, value) {
+				(Some(cf_handle), Some(value)) => {
+					write_batch.put_cf(&cf_handle, key, value.encode_value())
+				},
+				(Some(cf_handle), None) => write_batch.delete_cf(&cf_handle, key),
+				(None, Some(value)) => write_batch.put(key, value.encode_value()),
+				(None, None) => write_batch.delete(key),
+			}
+		}
+		self.db
+			.write(write_batch)
+			.wrap_err("Batch write failed on RocksDB")
+	}
+
+	fn keys(&self, column_family: &'static str) -> Result<Vec<Key>> {
+		let cf_handle = self
+			.db
+			.cf_handle(column_family)
+			.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+		let mut keys = vec![];
+		for entry in self.db.iterator_cf(&cf_handle, IteratorMode::Start) {
+			let (key, _) =
+				entry.wrap_err("Iterate operation with Column Family failed on RocksDB")?;
+			keys.extend(decode_key(column_family, &key));
+		}
+		Ok(keys)
+	}
+
+	fn compact(&self) -> Result<()> {
+		self.db.compact_range(None::<&[u8]>, None::<&[u8]>);
+		for cf in COLUMN_FAMILIES {
+			let cf_handle = self
+				.db
+				.cf_handle(cf)
+				.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+			self.db
+				.compact_range_cf(&cf_handle, None::<&[u8]>, None::<&[u8]>);
+		}
+		Ok(())
+	}
+
+	/// Memtables of all column families are flushed, so the write-ahead log doesn't have to be replayed on start.
+	fn flush(&self) -> Result<()> {
+		for cf in COLUMN_FAMILIES {
+			let cf_handle = self
+				.db
+				.cf_handle(cf)
+				.ok_or_else(|| eyre!("Couldn't get Column Family handle from RocksDB"))?;
+			self.db
+				.flush_cf(&cf_handle)
+				.wrap_err("Flush operation failed on RocksDB")?;
+		}
+		self.db
+			.flush_wal(true)
+			.wrap_err("Write-ahead log flush failed on RocksDB")
+	}
 }
@@ -0,0 +1,133 @@
+use crate::data::{self, rocks_db::decode_key, Batch, Key};
+use codec::{Decode, Encode};
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Embedded pure-Rust database, with a separate tree for each column family.
+#[derive(Clone)]
+pub struct SledDB {
+	db: sled::Db,
+}
+
+impl SledDB {
+	pub fn open(path: &str) -> Result<SledDB> {
+		let db = sled::open(path).wrap_err("Failed to open sled database")?;
+		Ok(SledDB { db })
+	}
+
+	fn tree(&self, column_family: Option<&'static str>) -> Result<sled::Tree> {
+		// Default tree is used if Column Family is not provided
+		let Some(cf) = column_family else {
+			return Ok((*self.db).clone());
+		};
+		self.db
+			.open_tree(cf)
+			.wrap_err("Couldn't open tree for Column Family on sled")
+	}
+}
+
+impl data::Database for SledDB {
+	type Key = (Option<&'static str>, Vec<u8>);
+
+	fn put<T>(&self, key: Key, value: T) -> Result<()>
+	where
+		T: Serialize + Encode,
+	{
+		let (column_family, key) = key.into();
+		self.tree(column_family)?
+			.insert(key, <T>::encode(&value))
+			.wrap_err("Put operation failed on sled")?;
+		Ok(())
+	}
+
+	fn get<T>(&self, key: Key) -> Result<Option<T>>
+	where
+		T: for<'a> Deserialize<'a> + Decode,
+	{
+		let (column_family, key) = key.into();
+		self.tree(column_family)?
+			.get(key)
+			.wrap_err("Get operation failed on sled")?
+			.map(|value| <T>::decode(&mut &value[..]).wrap_err("Failed decoding the value."))
+			.transpose()
+	}
+
+	fn delete(&self, key: Key) -> Result<()> {
+		let (column_family, key) = key.into();
+		self.tree(column_family)?
+			.remove(key)
+			.wrap_err("Delete operation failed on sled")?;
+		Ok(())
+	}
+
+	/// Sled batches are atomic only within the single tree, so writes are applied in order,
+	/// and the interrupted batch of the block is repaired from the journal (see [data::journal]).
+	fn write(&self, batch: Batch) -> Result<()> {
+		for (key, value) in batch.writes {
+			let (column_family, key) = key.into();
+			let tree = self.tree(column_family)?;
+			match value {
+				Some(value) => tree.insert(key, value.encode_value()).map(|_| ()),
+				None => tree.remove(key).map(|_| ()),
+			}
+			.wrap_err("Batch write failed on sled")?;
+		}
+		Ok(())
+	}
+
+	fn keys(&self, column_family: &'static str) -> Result<Vec<Key>> {
+		let mut keys = vec![];
+		for key in self.tree(Some(column_family))?.iter().keys() {
+			let key = key.wrap_err("Iterate operation failed on sled")?;
+			keys.extend(decode_key(column_family, &key));
+		}
+		Ok(keys)
+	}
+
+	/// Sled reclaims space in the background, so only pending writes are flushed.
+	fn compact(&self) -> Result<()> {
+		self.flush()
+	}
+
+	fn flush(&self) -> Result<()> {
+		self.db.flush().wrap_err("Flush operation failed on sled")?;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sp_core::H256;
+
+	use super::SledDB;
+	use crate::data::{Database, Key};
+
+	#[test]
+	fn put_get_delete() {
+		let db = SledDB {
+			db: sled::Config::new().temporary(true).open().unwrap(),
+		};
+
+		db.put(Key::VerifiedCellCount(1, H256::zero()), 8u32)
+			.unwrap();
+		db.put(Key::SyncProgress, 2u32).unwrap();
+		assert_eq!(
+			db.get::<u32>(Key::VerifiedCellCount(1, H256::zero()))
+				.unwrap(),
+			Some(8)
+		);
+		assert_eq!(
+			db.get::<u32>(Key::VerifiedCellCount(2, H256::zero()))
+				.unwrap(),
+			None
+		);
+		assert_eq!(db.get::<u32>(Key::SyncProgress).unwrap(), Some(2));
+
+		db.delete(Key::VerifiedCellCount(1, H256::zero())).unwrap();
+		assert_eq!(
+			db.get::<u32>(Key::VerifiedCellCount(1, H256::zero()))
+				.unwrap(),
+			None
+		);
+	}
+}
@@ -0,0 +1,140 @@
+//! Merkle inclusion proofs of the submitted data in the block data root.
+
+use serde::{Deserialize, Serialize};
+use sp_core::{keccak_256, H256};
+
+/// Merkle inclusion proof of the single data transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataProof {
+	pub data_root: H256,
+	/// Sibling hashes, from the leaf to the root
+	pub proof: Vec<H256>,
+	pub number_of_leaves: u32,
+	pub leaf_index: u32,
+	/// Keccak-256 hash of the submitted data
+	pub leaf: H256,
+}
+
+/// Returns the leaf hash of the submitted data.
+pub fn leaf_hash(data: &[u8]) -> H256 {
+	keccak_256(data).into()
+}
+
+fn node_hash(left: &H256, right: &H256) -> H256 {
+	keccak_256(&[left.as_bytes(), right.as_bytes()].concat()).into()
+}
+
+fn next_layer(layer: &[H256]) -> Vec<H256> {
+	layer
+		.chunks(2)
+		.map(|pair| match pair {
+			[left, right] => node_hash(left, right),
+			[promoted] => *promoted,
+			_ => unreachable!("Chunks have one or two nodes"),
+		})
+		.collect()
+}
+
+/// Returns the Merkle root of the given leaves, or zero hash if there are no leaves.
+pub fn merkle_root(leaves: &[H256]) -> H256 {
+	let mut layer = leaves.to_vec();
+	while layer.len() > 1 {
+		layer = next_layer(&layer);
+	}
+	layer.first().copied().unwrap_or_default()
+}
+
+impl DataProof {
+	/// Creates inclusion proof of the leaf on the given index, returns `None` if the index is out of range.
+	pub fn new(leaves: &[H256], leaf_index: u32) -> Option<Self> {
+		let leaf = *leaves.get(leaf_index as usize)?;
+		let mut proof = vec![];
+		let mut layer = leaves.to_vec();
+		let mut index = leaf_index as usize;
+		while layer.len() > 1 {
+			// Promoted node doesn't have a sibling
+			if let Some(sibling) = layer.get(index ^ 1) {
+				proof.push(*sibling);
+			}
+			layer = next_layer(&layer);
+			index /= 2;
+		}
+		Some(DataProof {
+			data_root: layer[0],
+			proof,
+			number_of_leaves: leaves.len() as u32,
+			leaf_index,
+			leaf,
+		})
+	}
+
+	/// Verifies that the leaf is included in the data root.
+	pub fn verify(&self) -> bool {
+		if self.leaf_index >= self.number_of_leaves {
+			return false;
+		}
+		let mut hash = self.leaf;
+		let mut siblings = self.proof.iter();
+		let (mut index, mut width) = (self.leaf_index, self.number_of_leaves);
+		while width > 1 {
+			let is_promoted = index == width - 1 && width % 2 == 1;
+			if !is_promoted {
+				let Some(sibling) = siblings.next() else {
+					return false;
+				};
+				hash = match index % 2 {
+					0 => node_hash(&hash, sibling),
+					_ => node_hash(sibling, &hash),
+				};
+			}
+			index /= 2;
+			width = (width + 1) / 2;
+		}
+		siblings.next().is_none() && hash == self.data_root
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sp_core::H256;
+	use test_case::test_case;
+
+	use super::{leaf_hash, merkle_root, node_hash, DataProof};
+
+	fn leaves(count: u8) -> Vec<H256> {
+		(0..count).map(|i| leaf_hash(&[i])).collect()
+	}
+
+	#[test]
+	fn test_merkle_root() {
+		let leaves = leaves(3);
+		let expected = node_hash(&node_hash(&leaves[0], &leaves[1]), &leaves[2]);
+		assert_eq!(merkle_root(&leaves), expected);
+		assert_eq!(merkle_root(&leaves[..1]), leaves[0]);
+		assert_eq!(merkle_root(&[]), H256::zero());
+	}
+
+	#[test_case(1 ; "Single leaf")]
+	#[test_case(2 ; "Two leaves")]
+	#[test_case(5 ; "Odd number of leaves")]
+	#[test_case(8 ; "Full tree")]
+	fn proofs_are_verified(count: u8) {
+		let leaves = leaves(count);
+		for index in 0..count as u32 {
+			let proof = DataProof::new(&leaves, index).unwrap();
+			assert_eq!(proof.data_root, merkle_root(&leaves));
+			assert!(proof.verify());
+		}
+		assert_eq!(DataProof::new(&leaves, count as u32), None);
+	}
+
+	#[test_case(|proof| proof.leaf = leaf_hash(b"other") ; "Changed leaf")]
+	#[test_case(|proof| proof.leaf_index = 1 ; "Changed index")]
+	#[test_case(|proof| proof.data_root = H256::zero() ; "Changed root")]
+	#[test_case(|proof| { proof.proof.pop(); } ; "Missing sibling")]
+	fn tampered_proofs_are_not_verified(tamper: fn(&mut DataProof)) {
+		let mut proof = DataProof::new(&leaves(5), 2).unwrap();
+		tamper(&mut proof);
+		assert!(!proof.verify());
+	}
+}
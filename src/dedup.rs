@@ -0,0 +1,142 @@
+//! Deduplication of the processed blocks, so the block received by more than one client is processed only once.
+
+use sp_core::H256;
+use std::{
+	collections::{HashSet, VecDeque},
+	fmt::{self, Display, Formatter},
+	sync::{Arc, Mutex},
+};
+
+/// Number of the latest processed blocks which are remembered
+const PROCESSED_CAPACITY: usize = 1024;
+
+/// Reason why the block is not claimed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duplicate {
+	/// Block is being processed by another client
+	InFlight,
+	/// Block is already processed
+	Processed,
+}
+
+impl Display for Duplicate {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Duplicate::InFlight => f.write_str("being processed"),
+			Duplicate::Processed => f.write_str("already processed"),
+		}
+	}
+}
+
+#[derive(Default)]
+struct Blocks {
+	in_flight: HashSet<H256>,
+	processed: HashSet<H256>,
+	/// Processed blocks in the order of completion, the oldest are forgotten first
+	order: VecDeque<H256>,
+}
+
+impl Blocks {
+	fn complete(&mut self, hash: H256) {
+		self.in_flight.remove(&hash);
+		if !self.processed.insert(hash) {
+			return;
+		}
+		self.order.push_back(hash);
+		if self.order.len() > PROCESSED_CAPACITY {
+			if let Some(oldest) = self.order.pop_front() {
+				self.processed.remove(&oldest);
+			}
+		}
+	}
+}
+
+/// Claimed block, which is in flight until dropped.
+pub struct Claim {
+	hash: H256,
+	blocks: Option<Arc<Mutex<Blocks>>>,
+}
+
+impl Claim {
+	/// Marks the block as processed, so it is not claimed again.
+	pub fn complete(mut self) {
+		if let Some(blocks) = self.blocks.take() {
+			blocks.lock().unwrap().complete(self.hash);
+		}
+	}
+}
+
+impl Drop for Claim {
+	fn drop(&mut self) {
+		if let Some(blocks) = self.blocks.take() {
+			blocks.lock().unwrap().in_flight.remove(&self.hash);
+		}
+	}
+}
+
+/// In-flight and processed blocks, shared by the light, sync and backfill clients.
+#[derive(Clone, Default)]
+pub struct BlockDedup {
+	blocks: Arc<Mutex<Blocks>>,
+}
+
+impl BlockDedup {
+	/// Claims the block with the given header hash, unless it is in flight or already processed.
+	pub fn claim(&self, hash: H256) -> Result<Claim, Duplicate> {
+		let mut blocks = self.blocks.lock().unwrap();
+		if blocks.processed.contains(&hash) {
+			return Err(Duplicate::Processed);
+		}
+		if !blocks.in_flight.insert(hash) {
+			return Err(Duplicate::InFlight);
+		}
+		Ok(Claim {
+			hash,
+			blocks: Some(self.blocks.clone()),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use sp_core::H256;
+
+	use super::{BlockDedup, Duplicate, PROCESSED_CAPACITY};
+
+	#[test]
+	fn block_is_claimed_once() {
+		let dedup = BlockDedup::default();
+		let hash = H256::repeat_byte(1);
+
+		let claim = dedup.claim(hash).unwrap();
+		assert_eq!(dedup.claim(hash).err(), Some(Duplicate::InFlight));
+		assert!(dedup.claim(H256::repeat_byte(2)).is_ok());
+		claim.complete();
+		assert_eq!(dedup.claim(hash).err(), Some(Duplicate::Processed));
+	}
+
+	#[test]
+	fn dropped_claim_is_released() {
+		let dedup = BlockDedup::default();
+		let hash = H256::repeat_byte(1);
+
+		drop(dedup.claim(hash).unwrap());
+		assert!(dedup.claim(hash).is_ok());
+	}
+
+	#[test]
+	fn oldest_processed_blocks_are_forgotten() {
+		let dedup = BlockDedup::default();
+		for number in 0..=PROCESSED_CAPACITY as u64 {
+			dedup
+				.claim(H256::from_low_u64_be(number))
+				.unwrap()
+				.complete();
+		}
+		assert!(dedup.claim(H256::from_low_u64_be(0)).is_ok());
+		assert_eq!(
+			dedup.claim(H256::from_low_u64_be(1)).err(),
+			Some(Duplicate::Processed)
+		);
+	}
+}
@@ -0,0 +1,430 @@
+//! Self-diagnostics of the light client, reported on the `/diagnostics` endpoint and by the `doctor` command.
+
+use avail_subxt::primitives::Header;
+use codec::Encode;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use serde::{Deserialize, Serialize};
+use sp_core::blake2_256;
+use std::{
+	fmt::{self, Display, Formatter},
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::timeout;
+
+use crate::{
+	api::health,
+	consts::{ExpectedNodeVariant, BLOCK_TIME_SECS},
+	data::{Database, Key, CONFIDENCE_FACTOR_CF},
+	network::{
+		p2p::{self, NatStatus, Reachability},
+		rpc,
+	},
+	types::RuntimeConfig,
+};
+
+/// Maximum duration of the RPC and P2P checks
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum age of the latest finalized block, on top of the allowed clock skew
+const FINALIZED_BLOCK_AGE: Duration = Duration::from_secs(3 * BLOCK_TIME_SECS);
+
+/// Diagnostics configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct DiagnosticsConfig {
+	pub avail_path: PathBuf,
+	pub port: u16,
+	pub version_requirement: Option<String>,
+	/// Minimum free disk space, in bytes
+	pub min_disk_space: u64,
+	pub max_clock_skew: Duration,
+}
+
+impl From<&RuntimeConfig> for DiagnosticsConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		DiagnosticsConfig {
+			avail_path: PathBuf::from(&val.avail_path),
+			port: val.port,
+			version_requirement: val.node_version_requirement.clone(),
+			min_disk_space: val.diagnostics_min_disk_space * 1024 * 1024,
+			max_clock_skew: Duration::from_secs(val.diagnostics_max_clock_skew),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+	Pass,
+	Fail,
+	/// Check cannot be performed, e.g. because the RPC node is not reachable
+	Skip,
+}
+
+/// Outcome of the single diagnostic check.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+	pub name: String,
+	pub outcome: Outcome,
+	pub message: String,
+}
+
+impl Diagnostic {
+	pub fn new(name: &str, result: Result<String>) -> Self {
+		let (outcome, message) = match result {
+			Ok(message) => (Outcome::Pass, message),
+			Err(error) => (Outcome::Fail, format!("{error:#}")),
+		};
+		Diagnostic {
+			name: name.to_string(),
+			outcome,
+			message,
+		}
+	}
+
+	pub fn skip(name: &str, message: &str) -> Self {
+		Diagnostic {
+			name: name.to_string(),
+			outcome: Outcome::Skip,
+			message: message.to_string(),
+		}
+	}
+}
+
+/// Report of the diagnostic checks, passed if none of the checks failed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+	pub passed: bool,
+	pub checks: Vec<Diagnostic>,
+}
+
+impl From<Vec<Diagnostic>> for Report {
+	fn from(checks: Vec<Diagnostic>) -> Self {
+		let passed = checks.iter().all(|check| check.outcome != Outcome::Fail);
+		Report { passed, checks }
+	}
+}
+
+impl Display for Report {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for check in &self.checks {
+			let outcome = match check.outcome {
+				Outcome::Pass => "PASS",
+				Outcome::Fail => "FAIL",
+				Outcome::Skip => "SKIP",
+			};
+			writeln!(f, "[{outcome}] {}: {}", check.name, check.message)?;
+		}
+		match self.passed {
+			true => write!(f, "All checks passed"),
+			false => write!(f, "Some checks failed"),
+		}
+	}
+}
+
+fn version_check(node: &rpc::Node, cfg: &DiagnosticsConfig) -> Result<String> {
+	let expected_node = ExpectedNodeVariant {
+		version_requirement: cfg.version_requirement.clone(),
+		..ExpectedNodeVariant::new()
+	};
+	let found = format!("{}/{}", node.system_version, node.spec_name);
+	if !expected_node.matches(&node.system_version, &node.spec_name) {
+		let expected = expected_node
+			.requirements()
+			.iter()
+			.map(|(spec_name, requirement)| format!("{requirement}/{spec_name}"))
+			.collect::<Vec<_>>()
+			.join(", ");
+		return Err(eyre!(
+			"Node version {found} is not compatible, expected: {expected}"
+		));
+	}
+	Ok(format!("Node version {found} is compatible"))
+}
+
+/// Checks if the local clock is within the maximum skew from the timestamp of the latest finalized block.
+/// Since the finalized block is produced earlier, block age is tolerated on top of the maximum skew.
+fn clock_skew_check(
+	now: Duration,
+	block_timestamp: Duration,
+	max_skew: Duration,
+) -> Result<String> {
+	if block_timestamp > now + max_skew {
+		let behind = (block_timestamp - now).as_secs();
+		return Err(eyre!(
+			"Local clock is {behind}s behind the latest finalized block, maximum skew is {}s",
+			max_skew.as_secs()
+		));
+	}
+	let ahead = now.saturating_sub(block_timestamp);
+	let message = format!(
+		"Local clock is {}s ahead of the latest finalized block",
+		ahead.as_secs()
+	);
+	if ahead > max_skew + FINALIZED_BLOCK_AGE {
+		return Err(eyre!("{message}, maximum skew is {}s", max_skew.as_secs()));
+	}
+	Ok(message)
+}
+
+async fn block_timestamp(client: &rpc::Client) -> Result<Duration> {
+	let header = client.get_chain_head_header().await?;
+	let hash = Encode::using_encoded(&header, blake2_256).into();
+	let timestamp = client.get_timestamp_at(hash).await?;
+	Ok(Duration::from_millis(timestamp))
+}
+
+async fn clock_check(client: &rpc::Client, cfg: &DiagnosticsConfig) -> Result<String> {
+	let block_timestamp = timeout(CHECK_TIMEOUT, block_timestamp(client))
+		.await
+		.wrap_err("Latest finalized block timestamp query timed out")?
+		.wrap_err("Cannot fetch timestamp of the latest finalized block")?;
+	let now = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.wrap_err("Local clock is set before the unix epoch")?;
+	clock_skew_check(now, block_timestamp, cfg.max_clock_skew)
+}
+
+fn reachability_check(reachability: &Reachability, port: u16) -> Result<String> {
+	match reachability {
+		Reachability {
+			nat_status: NatStatus::Public,
+			public_address,
+			..
+		} => {
+			let address = public_address
+				.as_ref()
+				.map_or("unknown address".to_string(), ToString::to_string);
+			Ok(format!("P2P port {port} is reachable at {address}"))
+		},
+		Reachability { relayed: true, .. } => Ok(format!(
+			"P2P port {port} is not reachable, peer is reachable through the relay"
+		)),
+		Reachability {
+			nat_status: NatStatus::Private,
+			..
+		} => Err(eyre!(
+			"P2P port {port} is not reachable from the public network"
+		)),
+		Reachability {
+			nat_status: NatStatus::Unknown,
+			..
+		} => Err(eyre!(
+			"P2P port {port} reachability is not confirmed by the AutoNAT probes yet"
+		)),
+	}
+}
+
+async fn p2p_port_check(client: &p2p::Client, port: u16) -> Result<String> {
+	let reachability = timeout(CHECK_TIMEOUT, client.get_reachability())
+		.await
+		.wrap_err("Reachability query timed out")??;
+	reachability_check(&reachability, port)
+}
+
+/// Returns free disk space available to the user, in bytes.
+#[cfg(unix)]
+fn available_space(path: &Path) -> Result<u64> {
+	use std::{ffi::CString, mem::MaybeUninit, os::unix::ffi::OsStrExt};
+
+	let c_path = CString::new(path.as_os_str().as_bytes()).wrap_err("Invalid database path")?;
+	let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+	// SAFETY: path is a valid C string, and stat is written by the successful call
+	if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+		return Err(std::io::Error::last_os_error())
+			.wrap_err_with(|| format!("Cannot get file system stats of {}", path.display()));
+	}
+	let stat = unsafe { stat.assume_init() };
+	Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn available_space(_: &Path) -> Result<u64> {
+	Err(eyre!("Disk space check is not supported on this platform"))
+}
+
+fn disk_space_check(cfg: &DiagnosticsConfig) -> Result<String> {
+	// Database directory is created on the first run, so the closest existing directory is checked
+	let path = cfg
+		.avail_path
+		.ancestors()
+		.find(|path| path.exists())
+		.filter(|path| !path.as_os_str().is_empty())
+		.unwrap_or(Path::new("."));
+	let available = available_space(path)? / 1024 / 1024;
+	let minimum = cfg.min_disk_space / 1024 / 1024;
+	let message = format!("{available} MB available on {}", path.display());
+	if available < minimum {
+		return Err(eyre!("{message}, minimum is {minimum} MB"));
+	}
+	Ok(message)
+}
+
+/// Checks if the database is writable, and if the header of each block with the confidence is stored.
+fn database_check(db: &impl Database) -> Result<String> {
+	health::database_check(db)?;
+	let blocks = db
+		.keys(CONFIDENCE_FACTOR_CF)?
+		.into_iter()
+		.filter_map(|key| match key {
+			Key::VerifiedCellCount(block_number, block_hash) => Some((block_number, block_hash)),
+			_ => None,
+		})
+		.collect::<Vec<_>>();
+	let mut missing = vec![];
+	for &(block_number, block_hash) in &blocks {
+		let header = db
+			.get::<Header>(Key::BlockHeader(block_number, block_hash))
+			.wrap_err_with(|| format!("Cannot read header of block {block_number}"))?;
+		if header.is_none() {
+			missing.push(block_number);
+		}
+	}
+	if let Some(first) = missing.iter().min() {
+		return Err(eyre!(
+			"Headers of {} blocks with the confidence are missing, first is {first}",
+			missing.len()
+		));
+	}
+	Ok(format!(
+		"Writable, confidence of {} blocks is consistent",
+		blocks.len()
+	))
+}
+
+/// Checks RPC node reachability, node version and clock skew.
+pub async fn check_node(
+	cfg: &DiagnosticsConfig,
+	client: &rpc::Client,
+	node: &rpc::Node,
+) -> Vec<Diagnostic> {
+	let rpc = health::rpc_check(client, node.host.clone()).await;
+	if rpc.is_err() {
+		let skipped = "RPC node is not reachable";
+		return vec![
+			Diagnostic::new("rpc", rpc),
+			Diagnostic::skip("version", skipped),
+			Diagnostic::skip("clock-skew", skipped),
+		];
+	}
+	vec![
+		Diagnostic::new("rpc", rpc),
+		Diagnostic::new("version", version_check(node, cfg)),
+		Diagnostic::new("clock-skew", clock_check(client, cfg).await),
+	]
+}
+
+/// Checks P2P port reachability, as observed by the running client.
+pub async fn check_p2p(cfg: &DiagnosticsConfig, client: &p2p::Client) -> Diagnostic {
+	Diagnostic::new("p2p-port", p2p_port_check(client, cfg.port).await)
+}
+
+/// Checks free disk space and database integrity.
+pub fn check_local(cfg: &DiagnosticsConfig, db: Result<&impl Database>) -> Vec<Diagnostic> {
+	vec![
+		Diagnostic::new("disk-space", disk_space_check(cfg)),
+		Diagnostic::new("database", db.and_then(database_check)),
+	]
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3, HeaderExtension},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::Digest,
+		primitives::Header,
+	};
+	use color_eyre::eyre::eyre;
+	use sp_core::H256;
+	use std::time::Duration;
+	use test_case::test_case;
+
+	use super::{
+		clock_skew_check, database_check, reachability_check, Diagnostic, Outcome, Report,
+	};
+	use crate::{
+		data::{mem_db::MemoryDB, Batch, Database, Key},
+		network::p2p::{NatStatus, Reachability},
+	};
+
+	fn header(number: u32) -> Header {
+		Header {
+			parent_hash: H256::zero(),
+			number,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs: vec![] },
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 1,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+		}
+	}
+
+	#[test_case(100, 100 => true ; "Synchronized clock")]
+	#[test_case(150, 100 => true ; "Finalized block age")]
+	#[test_case(300, 100 => false ; "Clock ahead")]
+	#[test_case(100, 140 => false ; "Clock behind")]
+	fn test_clock_skew_check(now: u64, block_timestamp: u64) -> bool {
+		let (now, block_timestamp) = (
+			Duration::from_secs(now),
+			Duration::from_secs(block_timestamp),
+		);
+		clock_skew_check(now, block_timestamp, Duration::from_secs(30)).is_ok()
+	}
+
+	#[test_case(NatStatus::Public, false => true ; "Public")]
+	#[test_case(NatStatus::Private, true => true ; "Relayed")]
+	#[test_case(NatStatus::Private, false => false ; "Private")]
+	#[test_case(NatStatus::Unknown, false => false ; "Unknown")]
+	fn test_reachability_check(nat_status: NatStatus, relayed: bool) -> bool {
+		let reachability = Reachability {
+			nat_status,
+			public_address: None,
+			relayed,
+		};
+		reachability_check(&reachability, 37000).is_ok()
+	}
+
+	#[test]
+	fn test_database_check() {
+		let db = MemoryDB::default();
+		let mut batch = Batch::default();
+		let block_hash = batch.put_finalized_header(header(1));
+		batch.put(Key::VerifiedCellCount(1, block_hash), 4u32);
+		db.write(batch).unwrap();
+		assert!(database_check(&db).is_ok());
+
+		db.put(Key::VerifiedCellCount(2, block_hash), 4u32).unwrap();
+		assert!(database_check(&db).is_err());
+	}
+
+	#[test]
+	fn report_fails_only_on_failed_checks() {
+		let skipped: Report = vec![
+			Diagnostic::new("rpc", Ok("Connected".to_string())),
+			Diagnostic::skip("p2p-port", "Skipped"),
+		]
+		.into();
+		assert!(skipped.passed);
+
+		let failed: Report = vec![Diagnostic::new("rpc", Err(eyre!("Failed")))].into();
+		assert!(!failed.passed);
+		assert_eq!(failed.checks[0].outcome, Outcome::Fail);
+	}
+}
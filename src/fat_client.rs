@@ -2,37 +2,50 @@
 //!
 //! # Flow
 //!
-//! * Fetches assigned block partition when finalized header is available and
+//! * Fetches assigned block partition or entire rows when finalized header is available,
+//! * verifies fetched cells against the commitments, unless proof verification is disabled and
 //! * inserts data rows and cells to to DHT for remote fetch.
 //!
 //! # Notes
 //!
 //! In case delay is configured, block processing is delayed for configured time.
+//! In case reseed interval is configured, cells and rows of the latest blocks are periodically inserted into the DHT again.
 
 use async_trait::async_trait;
 use avail_subxt::{primitives::Header, utils::H256};
 use codec::Encode;
 use color_eyre::{eyre::WrapErr, Result};
+use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
 use futures::future::join_all;
 use kate_recovery::{
-	data,
+	commitments, data,
 	matrix::{Dimensions, Partition, Position},
 };
 use kate_recovery::{data::Cell, matrix::RowIndex};
 use mockall::automock;
 use sp_core::blake2_256;
-use std::{sync::Arc, time::Instant};
+use std::{
+	collections::VecDeque,
+	ops::Range,
+	sync::{Arc, Mutex},
+	time::Instant,
+};
+use tokio::{
+	sync::broadcast::error::RecvError,
+	time::{self, Interval},
+};
 use tracing::{debug, error, info, warn};
 
 use crate::{
-	data::{Database, Key},
+	data::{Batch, Database},
 	network::{
 		p2p::Client as P2pClient,
 		rpc::{Client as RpcClient, Event},
 	},
+	proof::{self, Lane},
 	shutdown::Controller,
 	telemetry::{MetricCounter, MetricValue, Metrics},
-	types::{BlockVerified, ClientChannels, FatClientConfig},
+	types::{BlockVerified, ClientChannels, FatClientConfig, State},
 	utils::extract_kate,
 };
 
@@ -72,6 +85,96 @@ impl Client for FatClient {
 	}
 }
 
+/// Cells and data rows of the block, inserted into the DHT.
+#[derive(Clone)]
+pub struct Seed {
+	pub block_number: u32,
+	pub cells: Vec<Cell>,
+	pub rows: Vec<(RowIndex, Vec<u8>)>,
+}
+
+/// Maps the partition to the rows and columns of the extended matrix.
+///
+/// If there are no more partitions than rows, each partition is assigned a contiguous range of entire rows.
+/// Otherwise, partitions are spread evenly over the rows, and each row is split into the column ranges.
+/// Mapping depends only on the block dimensions, so all partitions of the same fraction
+/// cover the entire extended matrix without overlap.
+pub fn partition_ranges(dimensions: Dimensions, partition: &Partition) -> (Range<u32>, Range<u16>) {
+	let rows = dimensions.extended_rows();
+	let cols = u32::from(dimensions.cols().get());
+	let fraction = u32::from(partition.fraction.max(1));
+	let index = u32::from(partition.number.max(1) - 1).min(fraction - 1);
+
+	if fraction <= rows {
+		let start = index * rows / fraction;
+		let end = (index + 1) * rows / fraction;
+		return (start..end, 0..cols as u16);
+	}
+
+	let row = index * rows / fraction;
+	// Partitions assigned to the same row
+	let first = (row * fraction).div_ceil(rows);
+	let last = ((row + 1) * fraction).div_ceil(rows);
+	let (position, count) = (index - first, last - first);
+
+	let start = position * cols / count;
+	let end = (position + 1) * cols / count;
+	(row..row + 1, start as u16..end as u16)
+}
+
+/// Returns positions of the entire rows in the given range of the extended matrix,
+/// limited to the extended matrix rows. If range is not configured, positions of the partition are returned.
+fn positions(
+	dimensions: Dimensions,
+	rows: Option<&Range<u32>>,
+	partition: Option<&Partition>,
+) -> Vec<Position> {
+	let (rows, cols) = match (rows, partition) {
+		(Some(rows), _) => (
+			rows.start..rows.end.min(dimensions.extended_rows()),
+			0..dimensions.cols().get(),
+		),
+		(None, Some(partition)) => partition_ranges(dimensions, partition),
+		(None, None) => return vec![],
+	};
+
+	rows.flat_map(|row| cols.clone().map(move |col| Position { row, col }))
+		.collect()
+}
+
+/// Inserts cells and rows into the DHT, failures are logged.
+async fn seed(client: &impl Client, seed: Seed) {
+	let Seed {
+		block_number,
+		cells,
+		rows,
+	} = seed;
+
+	if !cells.is_empty() {
+		if let Err(e) = client.insert_cells_into_dht(block_number, cells).await {
+			debug!("Error inserting cells into DHT: {e}");
+		}
+	}
+
+	if !rows.is_empty() {
+		if let Err(e) = client.insert_rows_into_dht(block_number, rows).await {
+			debug!("Error inserting rows into DHT: {e}");
+		}
+	}
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+	match interval {
+		Some(interval) => {
+			interval.tick().await;
+		},
+		None => futures::future::pending().await,
+	}
+}
+
+/// Fetches the configured cells of the block, verifies and inserts them into the DHT.
+/// Returns inserted cells and rows, if there are any.
+#[allow(clippy::too_many_arguments)]
 pub async fn process_block(
 	client: &impl Client,
 	db: impl Database,
@@ -79,8 +182,8 @@ pub async fn process_block(
 	cfg: &FatClientConfig,
 	header: &Header,
 	received_at: Instant,
-	partition: Partition,
-) -> Result<()> {
+	pp: Arc<PublicParameters>,
+) -> Result<Option<Seed>> {
 	metrics.count(MetricCounter::SessionBlock).await;
 	metrics
 		.record(MetricValue::TotalBlockNumber(header.number))
@@ -91,18 +194,18 @@ pub async fn process_block(
 	let block_delay = received_at.elapsed().as_secs();
 	info!(block_number, block_delay, "Processing finalized block",);
 
-	let (rows, cols, _, _) = extract_kate(&header.extension);
+	let (rows, cols, _, commitment) = extract_kate(&header.extension);
 	let Some(dimensions) = Dimensions::new(rows, cols) else {
 		info!(
 			block_number,
 			"Skipping block with invalid dimensions {rows}x{cols}",
 		);
-		return Ok(());
+		return Ok(None);
 	};
 
 	if dimensions.cols().get() <= 2 {
 		error!(block_number, "More than 2 columns are required");
-		return Ok(());
+		return Ok(None);
 	}
 
 	// push latest mined block's header into column family specified
@@ -113,19 +216,34 @@ pub async fn process_block(
 	// another competing thread, which syncs all block headers
 	// in range [0, LATEST], where LATEST = latest block number
 	// when this process started
-	db.put(Key::BlockHeader(block_number), header)
+	let mut batch = Batch::default();
+	batch.put_finalized_header(header.clone());
+	db.write(batch)
 		.wrap_err("Fat Client failed to store Block Header")?;
 
 	// Fat client partition upload logic
-	let positions: Vec<Position> = dimensions
-		.iter_extended_partition_positions(&partition)
-		.collect();
-	let Partition { number, fraction } = partition;
-	info!(
-		block_number,
-		"partition_cells_requested" = positions.len(),
-		"Fetching partition ({number}/{fraction}) from RPC",
+	let positions = positions(
+		dimensions,
+		cfg.block_matrix_rows.as_ref(),
+		cfg.block_matrix_partition.as_ref(),
 	);
+	match (&cfg.block_matrix_rows, cfg.block_matrix_partition) {
+		(Some(Range { start, end }), _) => info!(
+			block_number,
+			"partition_cells_requested" = positions.len(),
+			"Fetching rows {start}..{end} from RPC",
+		),
+		(None, Some(partition)) => {
+			let Partition { number, fraction } = partition;
+			let (rows, cols) = partition_ranges(dimensions, &partition);
+			info!(
+				block_number,
+				"partition_cells_requested" = positions.len(),
+				"Fetching partition ({number}/{fraction}), rows {rows:?} and columns {cols:?} from RPC",
+			)
+		},
+		(None, None) => (),
+	};
 
 	let begin = Instant::now();
 	let mut rpc_fetched: Vec<Cell> = vec![];
@@ -141,14 +259,6 @@ pub async fn process_block(
 		for (i, result) in batch.await.into_iter().enumerate() {
 			let batch_rpc_fetched =
 				result.wrap_err(format!("Failed to fetch cells from node RPC at batch {i}"))?;
-
-			if let Err(e) = client
-				.insert_cells_into_dht(block_number, batch_rpc_fetched.clone())
-				.await
-			{
-				debug!("Error inserting cells into DHT: {e}");
-			}
-
 			rpc_fetched.extend(batch_rpc_fetched);
 		}
 	}
@@ -167,22 +277,48 @@ pub async fn process_block(
 		))
 		.await?;
 
-	if rpc_fetched.len() >= dimensions.cols().get().into() {
+	if !cfg.disable_proof_verification {
+		let commitments = commitments::from_slice(&commitment)?;
+		let (verified, unverified) = proof::verify(
+			Lane::Head,
+			block_number,
+			dimensions,
+			&rpc_fetched,
+			&commitments,
+			pp,
+		)
+		.await?;
+		if !unverified.is_empty() {
+			warn!(
+				block_number,
+				verified = verified.len(),
+				unverified = unverified.len(),
+				"Skipping cells with invalid proofs"
+			);
+		}
+		rpc_fetched.retain(|cell| !unverified.contains(&cell.position));
+	}
+
+	let rows = if rpc_fetched.len() >= dimensions.cols().get().into() {
 		let data_cells = rpc_fetched
 			.iter()
 			.filter(|cell| !cell.position.is_extended())
 			.collect::<Vec<_>>();
 
-		let data_rows = data::rows(dimensions, &data_cells);
-
-		if let Err(e) = client.insert_rows_into_dht(block_number, data_rows).await {
-			debug!("Error inserting rows into DHT: {e}");
-		}
+		data::rows(dimensions, &data_cells)
 	} else {
-		warn!("No rows has been inserted into DHT since partition size is less than one row.")
-	}
+		warn!("No rows has been inserted into DHT since partition size is less than one row.");
+		vec![]
+	};
 
-	Ok(())
+	let block_seed = Seed {
+		block_number,
+		cells: rpc_fetched,
+		rows,
+	};
+	seed(client, block_seed.clone()).await;
+
+	Ok(Some(block_seed))
 }
 
 /// Runs the fat client.
@@ -193,26 +329,48 @@ pub async fn process_block(
 /// * `cfg` - Fat client configuration
 /// * `metrics` -  Metrics registry
 /// * `channels` - Communication channels
-/// * `partition` - Assigned fat client partition
+/// * `pp` - Public parameters (i.e. SRS) needed for proof verification
 /// * `shutdown` - Shutdown controller
 pub async fn run(
 	client: impl Client,
 	db: impl Database + Clone,
 	cfg: FatClientConfig,
 	metrics: Arc<impl Metrics>,
+	state: Arc<Mutex<State>>,
 	mut channels: ClientChannels,
-	partition: Partition,
+	pp: Arc<PublicParameters>,
 	shutdown: Controller<String>,
 ) {
 	info!("Starting fat client...");
 
+	let mut reseed_interval = cfg
+		.reseed_interval
+		.map(|period| time::interval_at(time::Instant::now() + period, period));
+	let mut seeds = VecDeque::<Seed>::new();
+
 	loop {
-		let (header, received_at) = match channels.rpc_event_receiver.recv().await {
+		let event = tokio::select! {
+			event = channels.rpc_event_receiver.recv() => event,
+			_ = tick(&mut reseed_interval) => {
+				info!("Inserting {} latest blocks into DHT again", seeds.len());
+				for block_seed in seeds.iter() {
+					seed(&client, block_seed.clone()).await;
+				}
+				continue;
+			},
+		};
+
+		let (header, received_at) = match event {
 			Ok(event) => match event {
 				Event::HeaderUpdate {
 					header,
 					received_at,
 				} => (header, received_at),
+				Event::ConnectionLost { .. } | Event::ConnectionRestored { .. } => continue,
+			},
+			Err(RecvError::Lagged(skipped)) => {
+				warn!("Fat client is lagging behind, {skipped} headers are skipped");
+				continue;
 			},
 			Err(error) => {
 				error!("Cannot receive message: {error}");
@@ -230,21 +388,31 @@ pub async fn run(
 			info!("Sleeping for {seconds:?} seconds");
 			tokio::time::sleep(seconds).await;
 		}
+		cfg.confirmation_depth.wait(header.number, &state).await;
 
-		if let Err(error) = process_block(
+		match process_block(
 			&client,
 			db.clone(),
 			&metrics,
 			&cfg,
 			&header,
 			received_at,
-			partition,
+			pp.clone(),
 		)
 		.await
 		{
-			error!("Cannot process block: {error}");
-			let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
-			return;
+			Ok(Some(block_seed)) if cfg.reseed_interval.is_some() => {
+				seeds.push_back(block_seed);
+				while seeds.len() > cfg.reseed_blocks {
+					seeds.pop_front();
+				}
+			},
+			Ok(_) => (),
+			Err(error) => {
+				error!("Cannot process block: {error}");
+				let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
+				return;
+			},
 		};
 
 		let Ok(client_msg) = BlockVerified::try_from((header, None)) else {
@@ -274,6 +442,7 @@ mod tests {
 		config::substrate::Digest,
 	};
 	use hex_literal::hex;
+	use test_case::test_case;
 
 	fn default_header() -> Header {
 		Header {
@@ -363,6 +532,53 @@ mod tests {
 		}
 	}
 
+	#[test_case(Some(0..1), None => 4 ; "First row")]
+	#[test_case(Some(1..5), None => 4 ; "Rows limited to the extended matrix")]
+	#[test_case(Some(0..1), Some(entire_block()) => 4 ; "Rows take precedence over partition")]
+	#[test_case(None, Some(entire_block()) => 8 ; "Entire block partition")]
+	#[test_case(None, None => 0 ; "Nothing to fetch")]
+	fn test_positions(rows: Option<Range<u32>>, partition: Option<Partition>) -> usize {
+		let dimensions = Dimensions::new(1, 4).unwrap();
+		positions(dimensions, rows.as_ref(), partition.as_ref()).len()
+	}
+
+	#[test_case(2, 4, 1, 2 => (0..2, 0..4) ; "First half of rows")]
+	#[test_case(2, 4, 2, 2 => (2..4, 0..4) ; "Second half of rows")]
+	#[test_case(2, 4, 3, 4 => (2..3, 0..4) ; "Single row")]
+	#[test_case(1, 4, 2, 4 => (0..1, 2..4) ; "Second half of the first row")]
+	#[test_case(1, 4, 3, 3 => (1..2, 0..4) ; "Entire second row")]
+	#[test_case(1, 4, 3, 8 => (0..1, 2..3) ; "Single cell")]
+	fn test_partition_ranges(
+		rows: u16,
+		cols: u16,
+		number: u8,
+		fraction: u8,
+	) -> (Range<u32>, Range<u16>) {
+		let dimensions = Dimensions::new(rows, cols).unwrap();
+		partition_ranges(dimensions, &Partition { number, fraction })
+	}
+
+	#[test_case(1, 4, 3 ; "More partitions than rows")]
+	#[test_case(4, 8, 5 ; "Uneven rows")]
+	#[test_case(16, 32, 20 ; "Uneven rows and columns")]
+	#[test_case(2, 4, 32 ; "Partitions as many as cells")]
+	fn partitions_cover_matrix_without_overlap(rows: u16, cols: u16, fraction: u8) {
+		let dimensions = Dimensions::new(rows, cols).unwrap();
+		let mut positions = (1..=fraction)
+			.flat_map(|number| {
+				super::positions(dimensions, None, Some(&Partition { number, fraction }))
+			})
+			.collect::<Vec<_>>();
+		let count = positions.len();
+		positions.sort_by_key(|position| (position.row, position.col));
+		positions.dedup();
+		assert_eq!(positions.len(), count);
+		assert_eq!(
+			count,
+			dimensions.extended_rows() as usize * dimensions.cols().get() as usize
+		);
+	}
+
 	#[tokio::test]
 	async fn process_block_successful() {
 		let db = mem_db::MemoryDB::default();
@@ -381,16 +597,24 @@ mod tests {
 		mock_metrics.expect_count().returning(|_| ());
 		mock_metrics.expect_record().returning(|_| Ok(()));
 
-		process_block(
+		let cfg = FatClientConfig {
+			block_matrix_partition: Some(entire_block()),
+			..FatClientConfig::from(&RuntimeConfig::default())
+		};
+
+		let block_seed = process_block(
 			&mock_client,
 			db,
 			&Arc::new(mock_metrics),
-			&FatClientConfig::from(&RuntimeConfig::default()),
+			&cfg,
 			&default_header(),
 			Instant::now(),
-			entire_block(),
+			Arc::new(kate_recovery::couscous::public_params()),
 		)
 		.await
+		.unwrap()
 		.unwrap();
+
+		assert_eq!(block_seed.block_number, 57);
 	}
 }
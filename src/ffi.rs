@@ -0,0 +1,366 @@
+//! C bindings of the [`LightClient`], so the light client can be embedded into the mobile and other non-Rust applications.
+
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use serde::Serialize;
+use sp_core::H256;
+use std::{
+	cell::RefCell,
+	ffi::{c_char, c_int, c_void, CStr, CString},
+	ptr, slice,
+	time::Duration,
+};
+use tokio::{runtime::Runtime, task::JoinHandle};
+use tokio_stream::StreamExt;
+
+use crate::{
+	api::v2::types::{Base64, Transaction},
+	client::{Event, LightClient},
+	types::{IdentityConfig, RuntimeConfig},
+	verification::CommitmentMismatch,
+};
+
+/// Reason of the shutdown triggered by [`avail_light_stop`]
+const STOP_REASON: &str = "Stopped by the embedding application";
+
+thread_local! {
+	static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(error: color_eyre::Report) {
+	let message =
+		CString::new(format!("{error:#}").replace('\0', "")).expect("Null characters are removed");
+	LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Callback which receives JSON encoded client events, and the user data given on registration.
+/// Event string is valid only for the duration of the call.
+pub type EventCallback = extern "C" fn(event: *const c_char, user_data: *mut c_void);
+
+/// Light client started over the FFI, it is opaque to the callers.
+pub struct AvailLightClient {
+	runtime: Runtime,
+	client: LightClient,
+	events: Option<JoinHandle<()>>,
+}
+
+/// User data pointer given on callback registration, which is passed back to the callback.
+/// Caller is responsible for the user data being usable from the runtime threads.
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+
+/// Client event, as it is delivered to the event callback
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum EventMessage {
+	BlockVerified {
+		block_number: u32,
+		block_hash: H256,
+		confidence: Option<f64>,
+	},
+	DataVerified {
+		app_id: u32,
+		block_number: u32,
+		data: Vec<Base64>,
+	},
+	CommitmentMismatch(CommitmentMismatch),
+}
+
+impl From<Event> for EventMessage {
+	fn from(event: Event) -> Self {
+		match event {
+			Event::BlockVerified(block) => EventMessage::BlockVerified {
+				block_number: block.block_num,
+				block_hash: block.header_hash,
+				confidence: block.confidence,
+			},
+			Event::DataVerified {
+				app_id,
+				block_number,
+				data,
+			} => EventMessage::DataVerified {
+				app_id,
+				block_number,
+				data: data.into_iter().map(Base64).collect(),
+			},
+			Event::CommitmentMismatch(mismatch) => EventMessage::CommitmentMismatch(mismatch),
+		}
+	}
+}
+
+unsafe fn to_str<'a>(value: *const c_char, name: &str) -> Result<&'a str> {
+	if value.is_null() {
+		return Err(eyre!("{name} must not be null"));
+	}
+	CStr::from_ptr(value)
+		.to_str()
+		.wrap_err_with(|| format!("{name} is not valid UTF-8"))
+}
+
+fn to_c_string(value: impl Serialize) -> Result<*mut c_char> {
+	let json = serde_json::to_string(&value).wrap_err("Cannot encode response")?;
+	Ok(CString::new(json)
+		.wrap_err("Response contains null character")?
+		.into_raw())
+}
+
+/// Returns the result as a C string, or `NULL` if it failed.
+fn c_string_or_null(result: Result<*mut c_char>) -> *mut c_char {
+	result.unwrap_or_else(|error| {
+		set_last_error(error);
+		ptr::null_mut()
+	})
+}
+
+unsafe fn start(
+	config_json: *const c_char,
+	identity_path: *const c_char,
+	password: *const c_char,
+) -> Result<AvailLightClient> {
+	let cfg: RuntimeConfig =
+		serde_json::from_str(to_str(config_json, "Config")?).wrap_err("Cannot decode config")?;
+	let identity_path = to_str(identity_path, "Identity path")?;
+	let password = match password.is_null() {
+		true => None,
+		false => Some(to_str(password, "Password")?),
+	};
+	let identity_cfg = IdentityConfig::load_or_init(identity_path, password)?;
+
+	let runtime = Runtime::new().wrap_err("Cannot create tokio runtime")?;
+	let client = runtime.block_on(LightClient::builder(cfg, identity_cfg).start())?;
+	Ok(AvailLightClient {
+		runtime,
+		client,
+		events: None,
+	})
+}
+
+/// Starts the light client with the JSON encoded config, and the identity file at the given path.
+/// Identity file is created if it doesn't exist, password is optional and can be `NULL`.
+/// Returns `NULL` if the client cannot be started.
+///
+/// # Safety
+///
+/// Arguments must be `NULL` or valid null terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_start(
+	config_json: *const c_char,
+	identity_path: *const c_char,
+	password: *const c_char,
+) -> *mut AvailLightClient {
+	match start(config_json, identity_path, password) {
+		Ok(client) => Box::into_raw(Box::new(client)),
+		Err(error) => {
+			set_last_error(error);
+			ptr::null_mut()
+		},
+	}
+}
+
+/// Stops the light client and frees it. Client must not be used after it is stopped.
+///
+/// # Safety
+///
+/// Client must be `NULL` or returned by [`avail_light_start`], and not stopped yet.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_stop(client: *mut AvailLightClient) {
+	if client.is_null() {
+		return;
+	}
+	let AvailLightClient {
+		runtime,
+		client,
+		events,
+	} = *Box::from_raw(client);
+	if let Some(events) = events {
+		events.abort();
+	}
+	runtime.block_on(client.shutdown(STOP_REASON));
+	runtime.shutdown_timeout(Duration::from_secs(1));
+}
+
+/// Returns the latest finalized block number.
+///
+/// # Safety
+///
+/// Client must be returned by [`avail_light_start`], and not stopped yet.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_latest_block(client: *const AvailLightClient) -> u32 {
+	(*client).client.latest_block()
+}
+
+/// Writes the confidence of the sampled block into `confidence`.
+/// Returns `1` if the block is sampled, `0` if it is not sampled, and `-1` on error.
+///
+/// # Safety
+///
+/// Client must be returned by [`avail_light_start`], and not stopped yet. Confidence must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_confidence(
+	client: *const AvailLightClient,
+	block_number: u32,
+	confidence: *mut f64,
+) -> c_int {
+	match (*client).client.confidence(block_number) {
+		Ok(Some(value)) => {
+			*confidence = value;
+			1
+		},
+		Ok(None) => 0,
+		Err(error) => {
+			set_last_error(error);
+			-1
+		},
+	}
+}
+
+/// Returns the reconstructed data of the application as the JSON array of base64 encoded transactions,
+/// or the JSON `null` if the data is not verified. Returns `NULL` on error.
+///
+/// # Safety
+///
+/// Client must be returned by [`avail_light_start`], and not stopped yet.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_app_data(
+	client: *const AvailLightClient,
+	app_id: u32,
+	block_number: u32,
+) -> *mut c_char {
+	let result = (*client)
+		.client
+		.app_data(app_id, block_number)
+		.and_then(|data| {
+			to_c_string(data.map(|data| data.into_iter().map(Base64).collect::<Vec<_>>()))
+		});
+	c_string_or_null(result)
+}
+
+unsafe fn submit(
+	client: *const AvailLightClient,
+	bytes: *const u8,
+	len: usize,
+	transaction: fn(Base64) -> Transaction,
+) -> *mut c_char {
+	if bytes.is_null() {
+		set_last_error(eyre!("Transaction must not be null"));
+		return ptr::null_mut();
+	}
+	let client = &*client;
+	let transaction = transaction(Base64(slice::from_raw_parts(bytes, len).to_vec()));
+	let result = client
+		.runtime
+		.block_on(client.client.submit(transaction))
+		.and_then(to_c_string);
+	c_string_or_null(result)
+}
+
+/// Submits the data of the followed application, signed with the Avail account key (app mode only).
+/// Returns the JSON encoded submit response, or `NULL` on error.
+///
+/// # Safety
+///
+/// Client must be returned by [`avail_light_start`], and not stopped yet. Data must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_submit_data(
+	client: *const AvailLightClient,
+	data: *const u8,
+	len: usize,
+) -> *mut c_char {
+	submit(client, data, len, Transaction::Data)
+}
+
+/// Submits the signed extrinsic. Returns the JSON encoded submit response, or `NULL` on error.
+///
+/// # Safety
+///
+/// Client must be returned by [`avail_light_start`], and not stopped yet. Extrinsic must point to `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_submit_extrinsic(
+	client: *const AvailLightClient,
+	extrinsic: *const u8,
+	len: usize,
+) -> *mut c_char {
+	submit(client, extrinsic, len, Transaction::Extrinsic)
+}
+
+/// Registers the callback which receives the JSON encoded client events, replacing the previous one.
+/// Callback is called from the runtime threads, and `NULL` callback unregisters it.
+///
+/// # Safety
+///
+/// Client must be returned by [`avail_light_start`], and not stopped yet.
+/// User data must be usable from other threads until the callback is replaced, or the client is stopped.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_set_event_callback(
+	client: *mut AvailLightClient,
+	callback: Option<EventCallback>,
+	user_data: *mut c_void,
+) {
+	let client = &mut *client;
+	if let Some(events) = client.events.take() {
+		events.abort();
+	}
+	let Some(callback) = callback else {
+		return;
+	};
+
+	let user_data = UserData(user_data);
+	let mut events = client.client.events();
+	client.events = Some(client.runtime.spawn(async move {
+		let user_data = user_data;
+		while let Some(event) = events.next().await {
+			let Ok(event) = serde_json::to_string(&EventMessage::from(event)) else {
+				continue;
+			};
+			let Ok(event) = CString::new(event) else {
+				continue;
+			};
+			callback(event.as_ptr(), user_data.0);
+		}
+	}));
+}
+
+/// Returns the error message of the last failed call on the current thread, or `NULL` if there was no failure.
+/// Message is valid until the next failed call on the same thread, and must not be freed.
+#[no_mangle]
+pub extern "C" fn avail_light_last_error() -> *const c_char {
+	LAST_ERROR.with(|last_error| {
+		last_error
+			.borrow()
+			.as_ref()
+			.map_or(ptr::null(), |message| message.as_ptr())
+	})
+}
+
+/// Frees the string returned by the library.
+///
+/// # Safety
+///
+/// String must be `NULL` or returned by the library, and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn avail_light_free_string(value: *mut c_char) {
+	if !value.is_null() {
+		drop(CString::from_raw(value));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::ffi::{CStr, CString};
+
+	use super::{avail_light_last_error, avail_light_start};
+
+	#[test]
+	fn start_fails_on_invalid_config() {
+		let config = CString::new("not json").unwrap();
+		let identity = CString::new("identity.toml").unwrap();
+		let client =
+			unsafe { avail_light_start(config.as_ptr(), identity.as_ptr(), std::ptr::null()) };
+		assert!(client.is_null());
+		let error = unsafe { CStr::from_ptr(avail_light_last_error()) };
+		assert!(error.to_str().unwrap().starts_with("Cannot decode config"));
+	}
+}
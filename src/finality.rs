@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use codec::Encode;
 use sp_core::{
@@ -9,6 +9,7 @@ use sp_core::{
 use tracing::{info, warn};
 
 use crate::types::{GrandpaJustification, SignerMessage};
+use avail_subxt::primitives::Header;
 use color_eyre::{eyre::eyre, Result};
 
 #[derive(Clone, Debug)]
@@ -55,7 +56,8 @@ pub fn check_finality(
 					"Signature verification fails with default set_id {}, trying alternatives.",
 					validator_set.set_id
 				);
-				for set_id_m in (validator_set.set_id - 10)..(validator_set.set_id + 10) {
+				for set_id_m in validator_set.set_id.saturating_sub(10)..(validator_set.set_id + 10)
+				{
 					let s_m = Encode::encode(&(
 						&SignerMessage::PrecommitMessage(precommit.precommit.clone()),
 						&justification.round,
@@ -88,11 +90,14 @@ pub fn check_finality(
 		})
 		.collect::<Result<Vec<_>>>();
 
-	// match all the Signer addresses to the Current Validator Set
+	// match all the Signer addresses to the Current Validator Set,
+	// each validator is counted once, so duplicated precommits cannot fake the supermajority
 	let num_matched_addresses = signer_addresses?
 		.iter()
 		.filter(|x| validator_set.validator_set.iter().any(|e| e.0.eq(&x.0)))
-		.count();
+		.map(|x| x.0)
+		.collect::<HashSet<_>>()
+		.len();
 
 	info!(
 		"Number of matching signatures: {num_matched_addresses}/{} for block {}, set_id {}",
@@ -106,6 +111,26 @@ pub fn check_finality(
 		.ok_or(eyre!("Not signed by supermajority of validator set!"))
 }
 
+/// Verifies that the justification is targeting given header and that it is signed by supermajority of the validator set.
+pub fn verify_header_finality(
+	header: &Header,
+	validator_set: &ValidatorSet,
+	justification: &GrandpaJustification,
+) -> Result<()> {
+	let header_hash: H256 = Encode::using_encoded(header, blake2_256).into();
+	let commit = &justification.commit;
+	if commit.target_hash != header_hash || commit.target_number != header.number {
+		return Err(eyre!(
+			"Justification target {}/{:?} doesn't match the header {}/{header_hash:?}",
+			commit.target_number,
+			commit.target_hash,
+			header.number
+		));
+	}
+
+	check_finality(validator_set, justification)
+}
+
 fn is_signed_by_supermajority(num_signatures: usize, validator_set_size: usize) -> bool {
 	let supermajority = (validator_set_size * 2 / 3) + 1;
 	num_signatures >= supermajority
@@ -147,7 +172,8 @@ mod tests {
 	};
 	use test_case::test_case;
 
-	use crate::types::{Precommit, SignerMessage};
+	use super::{check_finality, ValidatorSet};
+	use crate::types::{Commit, GrandpaJustification, Precommit, SignedPrecommit, SignerMessage};
 	#[test_case(1, 1 => true)]
 	#[test_case(1, 2 => false)]
 	#[test_case(2, 2 => true)]
@@ -188,4 +214,64 @@ mod tests {
 
 		<ed25519::Pair as Pair>::verify(&sig, signed_message, &id)
 	}
+
+	fn signed_justification(signers: &[ed25519::Pair], set_id: u64) -> GrandpaJustification {
+		let precommit = Precommit {
+			target_hash: [1u8; 32].into(),
+			target_number: 1,
+		};
+		let round = 1u64;
+		let message = Encode::encode(&(
+			&SignerMessage::PrecommitMessage(precommit.clone()),
+			&round,
+			&set_id,
+		));
+		let precommits = signers
+			.iter()
+			.map(|pair| SignedPrecommit {
+				precommit: precommit.clone(),
+				signature: pair.sign(&message),
+				id: pair.public(),
+			})
+			.collect();
+
+		GrandpaJustification {
+			round,
+			commit: Commit {
+				target_hash: precommit.target_hash,
+				target_number: precommit.target_number,
+				precommits,
+			},
+			votes_ancestries: vec![],
+		}
+	}
+
+	fn validators() -> Vec<ed25519::Pair> {
+		(1..=4u8)
+			.map(|i| ed25519::Pair::from_seed(&[i; 32]))
+			.collect()
+	}
+
+	#[test]
+	fn check_finality_with_supermajority() {
+		let validators = validators();
+		let validator_set = ValidatorSet {
+			set_id: 2,
+			validator_set: validators.iter().map(|pair| pair.public()).collect(),
+		};
+		let justification = signed_justification(&validators[..3], 2);
+		assert!(check_finality(&validator_set, &justification).is_ok());
+	}
+
+	#[test]
+	fn check_finality_rejects_duplicated_precommits() {
+		let validators = validators();
+		let validator_set = ValidatorSet {
+			set_id: 2,
+			validator_set: validators.iter().map(|pair| pair.public()).collect(),
+		};
+		let signer = validators[0].clone();
+		let justification = signed_justification(&[signer.clone(), signer.clone(), signer], 2);
+		assert!(check_finality(&validator_set, &justification).is_err());
+	}
 }
@@ -0,0 +1,408 @@
+//! Sharing of the sampling results between light clients, over the gossipsub topic.
+
+use codec::{Decode, DecodeAll, Encode};
+use libp2p::{gossipsub::MessageAcceptance, PeerId};
+use sp_core::H256;
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::broadcast;
+use tracing::{debug, error, trace, warn};
+
+use crate::{
+	attestation::{Attestation, ATTESTATIONS_TOPIC},
+	data::{canonical_hash, BlockSamplingStats, Database, Key},
+	network::p2p::{self, GossipMessage},
+	telemetry::{MetricCounter, Metrics},
+	types::BlockVerified,
+};
+
+/// Gossipsub topic on which the sampling summaries are published
+pub const SAMPLING_SUMMARIES_TOPIC: &str = "/avail/light/sampling-summaries/1";
+
+/// Number of the latest blocks for which summaries are kept
+const MAX_BLOCKS: u32 = 64;
+
+/// Maximum number of the peer summaries kept per block which is not sampled yet
+const MAX_PENDING_PER_BLOCK: usize = 32;
+
+/// Compact sampling outcome of the block.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct SamplingSummary {
+	pub block_number: u32,
+	pub block_hash: H256,
+	/// Number of the sampled cells
+	pub sampled: u32,
+	/// Number of the fetched and verified cells
+	pub verified: u32,
+}
+
+impl SamplingSummary {
+	pub fn new(block_number: u32, block_hash: H256, stats: &BlockSamplingStats) -> Self {
+		SamplingSummary {
+			block_number,
+			block_hash,
+			sampled: stats.cells.len() as u32,
+			verified: stats.cells.iter().filter(|cell| cell.verified).count() as u32,
+		}
+	}
+
+	/// Block is available if all sampled cells are verified.
+	pub fn is_available(&self) -> bool {
+		self.sampled > 0 && self.verified == self.sampled
+	}
+}
+
+/// Outcome of the cross-check of the peer summary with the local summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrossCheck {
+	Agrees,
+	/// Peer summary refers to a different block hash
+	HashMismatch,
+	/// Peer and local client disagree on the block availability
+	AvailabilityMismatch,
+}
+
+pub fn cross_check(local: &SamplingSummary, remote: &SamplingSummary) -> CrossCheck {
+	if local.block_hash != remote.block_hash {
+		CrossCheck::HashMismatch
+	} else if local.is_available() != remote.is_available() {
+		CrossCheck::AvailabilityMismatch
+	} else {
+		CrossCheck::Agrees
+	}
+}
+
+/// Returns whether the summary is propagated, given its cross-check with the local summary
+/// and whether its block hash matches the canonical block hash.
+/// Summaries of the blocks whose hash is not known yet cannot be validated, so they are not propagated.
+fn acceptance(check: Option<CrossCheck>, is_canonical: bool) -> MessageAcceptance {
+	match check {
+		Some(CrossCheck::HashMismatch) => MessageAcceptance::Reject,
+		// Disagreement on the availability is not a protocol violation
+		Some(CrossCheck::Agrees | CrossCheck::AvailabilityMismatch) => MessageAcceptance::Accept,
+		None if is_canonical => MessageAcceptance::Accept,
+		None => MessageAcceptance::Ignore,
+	}
+}
+
+/// Local summaries of the latest sampled blocks, and peer summaries of the blocks which are not sampled yet.
+#[derive(Default)]
+struct Summaries {
+	local: BTreeMap<u32, SamplingSummary>,
+	pending: BTreeMap<u32, Vec<(PeerId, SamplingSummary)>>,
+}
+
+impl Summaries {
+	/// Inserts local summary, and returns the pending peer summaries of the block, cross-checked with it.
+	fn insert_local(
+		&mut self,
+		summary: SamplingSummary,
+	) -> Vec<(PeerId, SamplingSummary, CrossCheck)> {
+		let checked = self
+			.pending
+			.remove(&summary.block_number)
+			.unwrap_or_default()
+			.into_iter()
+			.map(|(peer_id, remote)| {
+				let check = cross_check(&summary, &remote);
+				(peer_id, remote, check)
+			})
+			.collect();
+		self.local.insert(summary.block_number, summary);
+		self.prune();
+		checked
+	}
+
+	/// Cross-checks peer summary with the local summary of the block.
+	/// If the block is not sampled yet, peer summary is kept until it is.
+	fn insert_remote(&mut self, peer_id: PeerId, remote: SamplingSummary) -> Option<CrossCheck> {
+		if let Some(local) = self.local.get(&remote.block_number) {
+			return Some(cross_check(local, &remote));
+		}
+		if remote.block_number < self.oldest() {
+			return None;
+		}
+		// Number of the pending blocks is bounded, so peers cannot exhaust memory with unknown blocks
+		if self.pending.len() >= MAX_BLOCKS as usize
+			&& !self.pending.contains_key(&remote.block_number)
+		{
+			return None;
+		}
+		let pending = self.pending.entry(remote.block_number).or_default();
+		if pending.len() < MAX_PENDING_PER_BLOCK && pending.iter().all(|(id, _)| *id != peer_id) {
+			pending.push((peer_id, remote));
+		}
+		None
+	}
+
+	/// Oldest block for which summaries are kept.
+	fn oldest(&self) -> u32 {
+		let latest = self.local.keys().next_back().copied().unwrap_or_default();
+		latest.saturating_sub(MAX_BLOCKS - 1)
+	}
+
+	fn prune(&mut self) {
+		let oldest = self.oldest();
+		self.local = self.local.split_off(&oldest);
+		self.pending = self.pending.split_off(&oldest);
+	}
+}
+
+async fn handle_cross_check(
+	p2p_client: &p2p::Client,
+	metrics: &Arc<impl Metrics>,
+	peer_id: PeerId,
+	remote: &SamplingSummary,
+	check: CrossCheck,
+) {
+	let block_number = remote.block_number;
+	match check {
+		CrossCheck::Agrees => {
+			trace!(block_number, "Sampling summary of peer {peer_id} agrees");
+		},
+		CrossCheck::HashMismatch => {
+			warn!(
+				block_number,
+				"Peer {peer_id} sampled block with different hash {:?}", remote.block_hash
+			);
+			p2p_client.report_invalid_message(peer_id);
+		},
+		CrossCheck::AvailabilityMismatch => {
+			let availability = match remote.is_available() {
+				true => "available",
+				false => "unavailable",
+			};
+			warn!(
+				block_number,
+				"Peer {peer_id} sampled block as {availability}, verified {}/{} cells",
+				remote.verified,
+				remote.sampled
+			);
+			metrics.count(MetricCounter::SamplingSummaryMismatch).await;
+		},
+	}
+}
+
+/// Returns the hash of the block, if the block is sampled or finalized.
+fn known_hash(db: &impl Database, summaries: &Summaries, block_number: u32) -> Option<H256> {
+	if let Some(local) = summaries.local.get(&block_number) {
+		return Some(local.block_hash);
+	}
+	canonical_hash(db, block_number)
+		.inspect_err(|error| debug!(block_number, "Cannot get canonical block hash: {error:#}"))
+		.ok()
+		.flatten()
+}
+
+/// Decodes and cross-checks the sampling summary received from the peer, and returns whether it is propagated.
+async fn handle_summary(
+	db: &impl Database,
+	p2p_client: &p2p::Client,
+	metrics: &Arc<impl Metrics>,
+	summaries: &mut Summaries,
+	message: &GossipMessage,
+) -> MessageAcceptance {
+	let Ok(remote) = SamplingSummary::decode_all(&mut &message.data[..]) else {
+		debug!(
+			"Invalid sampling summary received from peer {}",
+			message.source
+		);
+		p2p_client.report_invalid_message(message.source);
+		return MessageAcceptance::Reject;
+	};
+	let known_hash = known_hash(db, summaries, remote.block_number);
+	let check = match known_hash {
+		Some(hash) if hash != remote.block_hash => Some(CrossCheck::HashMismatch),
+		_ => summaries.insert_remote(message.source, remote.clone()),
+	};
+	if let Some(check) = check {
+		handle_cross_check(p2p_client, metrics, message.source, &remote, check).await;
+	}
+	acceptance(check, known_hash.is_some())
+}
+
+/// Decodes and verifies the attestation received from the peer, and returns whether it is propagated.
+fn handle_attestation(
+	db: &impl Database,
+	p2p_client: &p2p::Client,
+	summaries: &Summaries,
+	message: &GossipMessage,
+) -> MessageAcceptance {
+	let attestation = Attestation::decode_all(&mut &message.data[..])
+		.ok()
+		.filter(Attestation::verify);
+	let Some(attestation) = attestation else {
+		debug!("Invalid attestation received from peer {}", message.source);
+		p2p_client.report_invalid_message(message.source);
+		return MessageAcceptance::Reject;
+	};
+	let block_number = attestation.block_number;
+	match known_hash(db, summaries, block_number) {
+		Some(hash) if hash == attestation.block_hash => MessageAcceptance::Accept,
+		Some(_) => {
+			warn!(
+				block_number,
+				"Peer {} attested block with different hash {:?}",
+				message.source,
+				attestation.block_hash
+			);
+			p2p_client.report_invalid_message(message.source);
+			MessageAcceptance::Reject
+		},
+		None => MessageAcceptance::Ignore,
+	}
+}
+
+/// Publishes sampling summaries of the sampled blocks (if enabled), and cross-checks them with the summaries received from peers.
+/// Received gossip messages are validated before they are propagated.
+pub async fn run(
+	db: impl Database,
+	p2p_client: p2p::Client,
+	metrics: Arc<impl Metrics>,
+	publish_summaries: bool,
+	mut block_receiver: broadcast::Receiver<BlockVerified>,
+	mut gossip_receiver: broadcast::Receiver<GossipMessage>,
+) {
+	let mut summaries = Summaries::default();
+
+	loop {
+		tokio::select! {
+			block = block_receiver.recv() => {
+				let block = match block {
+					Ok(block) => block,
+					Err(broadcast::error::RecvError::Lagged(skipped)) => {
+						debug!("Sampling summaries are skipped for {skipped} blocks");
+						continue;
+					},
+					Err(error) => {
+						error!("Cannot receive verified block: {error}");
+						return;
+					},
+				};
+				let block_number = block.block_num;
+				let stats = match db.get::<BlockSamplingStats>(Key::BlockSamplingStats(block_number)) {
+					Ok(Some(stats)) => stats,
+					Ok(None) => continue,
+					Err(error) => {
+						error!(block_number, "Cannot get block sampling stats: {error:#}");
+						continue;
+					},
+				};
+
+				let summary = SamplingSummary::new(block_number, block.header_hash, &stats);
+				// Publishing fails if no peers are subscribed to the topic, which is expected on small networks
+				if publish_summaries {
+					if let Err(error) = p2p_client.publish(SAMPLING_SUMMARIES_TOPIC, summary.encode()).await {
+						debug!(block_number, "Sampling summary is not published: {error:#}");
+					}
+				}
+				for (peer_id, remote, check) in summaries.insert_local(summary) {
+					handle_cross_check(&p2p_client, &metrics, peer_id, &remote, check).await;
+				}
+			},
+			message = gossip_receiver.recv() => {
+				let message = match message {
+					Ok(message) => message,
+					Err(broadcast::error::RecvError::Lagged(skipped)) => {
+						debug!("Skipped {skipped} gossip messages");
+						continue;
+					},
+					Err(error) => {
+						error!("Cannot receive gossip message: {error}");
+						return;
+					},
+				};
+				let acceptance = match message.topic.as_str() {
+					SAMPLING_SUMMARIES_TOPIC => {
+						handle_summary(&db, &p2p_client, &metrics, &mut summaries, &message).await
+					},
+					ATTESTATIONS_TOPIC => handle_attestation(&db, &p2p_client, &summaries, &message),
+					// Messages of the topics without validator are not propagated
+					_ => MessageAcceptance::Ignore,
+				};
+				let (message_id, source) = (message.message_id, message.propagation_source);
+				p2p_client.report_gossip_validation(message_id, source, acceptance);
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::PeerId;
+	use sp_core::H256;
+	use test_case::test_case;
+
+	use libp2p::gossipsub::MessageAcceptance;
+
+	use super::{acceptance, cross_check, CrossCheck, SamplingSummary, Summaries, MAX_BLOCKS};
+
+	fn summary(block_number: u32, hash: u8, verified: u32) -> SamplingSummary {
+		SamplingSummary {
+			block_number,
+			block_hash: H256::repeat_byte(hash),
+			sampled: 10,
+			verified,
+		}
+	}
+
+	#[test_case(summary(1, 1, 10), summary(1, 1, 10) => CrossCheck::Agrees ; "Both available")]
+	#[test_case(summary(1, 1, 8), summary(1, 1, 9) => CrossCheck::Agrees ; "Both unavailable")]
+	#[test_case(summary(1, 1, 10), summary(1, 2, 10) => CrossCheck::HashMismatch ; "Different hash")]
+	#[test_case(summary(1, 1, 10), summary(1, 1, 5) => CrossCheck::AvailabilityMismatch ; "Peer reports unavailable")]
+	fn test_cross_check(local: SamplingSummary, remote: SamplingSummary) -> CrossCheck {
+		cross_check(&local, &remote)
+	}
+
+	#[test_case(Some(CrossCheck::Agrees), false => MessageAcceptance::Accept ; "Agrees")]
+	#[test_case(Some(CrossCheck::AvailabilityMismatch), true => MessageAcceptance::Accept ; "Availability mismatch")]
+	#[test_case(Some(CrossCheck::HashMismatch), true => MessageAcceptance::Reject ; "Hash mismatch")]
+	#[test_case(None, true => MessageAcceptance::Accept ; "Canonical block")]
+	#[test_case(None, false => MessageAcceptance::Ignore ; "Unknown block")]
+	fn test_acceptance(check: Option<CrossCheck>, is_canonical: bool) -> MessageAcceptance {
+		acceptance(check, is_canonical)
+	}
+
+	#[test]
+	fn pending_summaries_are_checked_when_block_is_sampled() {
+		let mut summaries = Summaries::default();
+		let (peer_id, other_peer_id) = (PeerId::random(), PeerId::random());
+
+		assert_eq!(summaries.insert_remote(peer_id, summary(5, 1, 10)), None);
+		// summary of the same peer is kept only once
+		assert_eq!(summaries.insert_remote(peer_id, summary(5, 1, 10)), None);
+		assert_eq!(
+			summaries.insert_remote(other_peer_id, summary(5, 2, 10)),
+			None
+		);
+
+		let checked = summaries.insert_local(summary(5, 1, 10));
+		let checks = checked
+			.into_iter()
+			.map(|(peer_id, _, check)| (peer_id, check))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			checks,
+			vec![
+				(peer_id, CrossCheck::Agrees),
+				(other_peer_id, CrossCheck::HashMismatch)
+			]
+		);
+
+		assert_eq!(
+			summaries.insert_remote(peer_id, summary(5, 1, 0)),
+			Some(CrossCheck::AvailabilityMismatch)
+		);
+	}
+
+	#[test]
+	fn old_summaries_are_pruned() {
+		let mut summaries = Summaries::default();
+		let peer_id = PeerId::random();
+
+		summaries.insert_local(summary(1, 1, 10));
+		summaries.insert_local(summary(MAX_BLOCKS + 1, 1, 10));
+		assert!(!summaries.local.contains_key(&1));
+		assert_eq!(summaries.insert_remote(peer_id, summary(1, 1, 10)), None);
+		assert!(summaries.pending.is_empty());
+	}
+}
@@ -1,18 +1,51 @@
 pub mod api;
 pub mod app_client;
+pub mod app_filter;
+pub mod attestation;
+pub mod backfill;
+pub mod best_heads;
+pub mod checkpoint;
+pub mod client;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod commands;
+pub mod config_overrides;
+pub mod config_reload;
+pub mod consensus;
 pub mod consts;
 #[cfg(feature = "crawl")]
 pub mod crawl_client;
 pub mod data;
+pub mod data_proof;
+pub mod dedup;
+pub mod diagnostics;
+pub mod event_bridge;
 pub mod fat_client;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod finality;
+pub mod gossip;
 pub mod light_client;
+pub mod logs;
 pub mod maintenance;
 pub mod network;
+pub mod platform;
 pub mod proof;
+pub mod replay;
+pub mod retention;
+pub mod retry;
+pub mod sampling;
+pub mod scheduler;
 pub mod shutdown;
+pub mod simulation;
+pub mod snapshot;
+pub mod supervisor;
 pub mod sync_client;
 pub mod sync_finality;
 pub mod telemetry;
 pub mod types;
 pub mod utils;
+pub mod verification;
+pub mod watchdog;
+pub mod webhooks;
+
+pub use client::{Event, LightClient, LightClientBuilder};
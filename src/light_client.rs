@@ -5,9 +5,12 @@
 //! # Flow
 //!
 //! * Connect to the Avail node WebSocket stream and start listening to finalized headers
-//! * Generate random cells for random data sampling (8 cells currently)
+//! * Skip the block if it is being processed or already processed by the sync or backfill
+//! * Wait for the sampling slot, which is given to the finalized headers before the synced blocks
+//! * Generate random cells for random data sampling (number of cells is derived from confidence, adapted to network reliability if configured)
 //! * Retrieve cell proofs from a) DHT and/or b) via RPC call from the node, in that order
 //! * Verify proof using the received cells
+//! * Check header extension commitments against the sampled cells, marking the block as unavailable on mismatch
 //! * Calculate block confidence and store it in RocksDB
 //! * Insert cells to to DHT for remote fetch
 //! * Notify the consumer (app client) a new block has been verified
@@ -15,39 +18,65 @@
 //! # Notes
 //!
 //! In case delay is configured, block processing is delayed for configured time.
+//! In case confirmation depth is configured, block processing is delayed until the configured number of blocks is finalized on top of the block.
+//! In case light client lags behind the finalized headers, skipped headers are fetched from RPC by walking the parent hashes back from the next received header,
+//! and processed in order.
+//! Skipped blocks which cannot be fetched are recorded as missed, and processed later by the backfill.
+//! In case block processing fails or times out, the failure is stored and the block is processed again by the backfill (see [`watchdog`]).
+//! In case confidence is changed on configuration reload, it is applied to the next block.
 //! In case RPC is disabled, RPC calls will be skipped.
 
 use avail_subxt::{primitives::Header, utils::H256};
 use codec::Encode;
-use color_eyre::{eyre::WrapErr, Result};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
 use kate_recovery::{commitments, matrix::Dimensions};
 use sp_core::blake2_256;
 use std::{
+	collections::VecDeque,
 	sync::{Arc, Mutex},
 	time::Instant,
 };
-use tracing::{error, info};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, info, info_span, warn};
 
 use crate::{
-	data::{Database, Key},
+	backfill,
+	config_reload::ConfigUpdate,
+	data::{journal, Batch, Database, Key},
+	dedup::BlockDedup,
 	network::{
 		self,
 		rpc::{self, Event},
 	},
+	sampling::AdaptiveSampler,
+	scheduler::{Priority, Scheduler},
 	shutdown::Controller,
 	telemetry::{MetricCounter, MetricValue, Metrics},
-	types::{self, ClientChannels, LightClientConfig, OptionBlockRange, State},
+	types::{self, BlockRange, ClientChannels, LightClientConfig, OptionBlockRange, State},
 	utils::{calculate_confidence, extract_kate},
+	verification::{self, CommitmentMismatch},
+	watchdog,
 };
 
+#[tracing::instrument(
+	name = "process_block",
+	level = "info",
+	skip_all,
+	fields(block_number = header.number, block_delay = received_at.elapsed().as_secs_f64())
+)]
 pub async fn process_block(
 	db: impl Database,
 	network_client: &impl network::Client,
 	metrics: &Arc<impl Metrics>,
 	cfg: &LightClientConfig,
+	cell_count: u32,
 	header: Header,
 	received_at: Instant,
 	state: Arc<Mutex<State>>,
+	commitment_mismatch_sender: &broadcast::Sender<CommitmentMismatch>,
 ) -> Result<Option<f64>> {
 	metrics.count(MetricCounter::SessionBlock).await;
 	metrics
@@ -77,8 +106,23 @@ pub async fn process_block(
 	}
 
 	let commitments = commitments::from_slice(&commitment)?;
-	let cell_count = rpc::cell_count_for_confidence(cfg.confidence);
-	let positions = rpc::generate_random_cells(dimensions, cell_count);
+	if let Some(reason) = verification::check_commitments(dimensions, &commitments) {
+		let mismatch = CommitmentMismatch {
+			block_number,
+			block_hash: header_hash,
+			reason,
+		};
+		verification::report(&db, metrics, commitment_mismatch_sender, mismatch).await?;
+		return Ok(None);
+	}
+
+	let positions = rpc::generate_cells(
+		dimensions,
+		cell_count,
+		cfg.sampling_seed.as_deref(),
+		header_hash,
+		cfg.sampling_strategy,
+	);
 	info!(
 		block_number,
 		"cells_requested" = positions.len(),
@@ -86,6 +130,7 @@ pub async fn process_block(
 		positions.len()
 	);
 
+	let sampling_started = Instant::now();
 	let (fetched, unfetched, fetch_stats) = network_client
 		.fetch_verified(
 			block_number,
@@ -124,18 +169,59 @@ pub async fn process_block(
 			.await?;
 	}
 
-	if positions.len() > fetched.len() {
+	metrics
+		.record(MetricValue::ProofVerificationDuration(
+			fetch_stats.proof_verification_duration,
+		))
+		.await?;
+
+	// Reconstructed cells have no verified proofs, so only the fetched cells are counted
+	let verified = fetched.len();
+	let mismatch = verification::check_sampled_cells(&fetch_stats.cells);
+
+	let sampling_stats = fetch_stats.sampling_stats(sampling_started.elapsed());
+	db.put(Key::BlockSamplingStats(block_number), sampling_stats)
+		.wrap_err("Light Client failed to store block sampling stats")?;
+
+	if let Some(reason) = mismatch {
+		let mismatch = CommitmentMismatch {
+			block_number,
+			block_hash: header_hash,
+			reason,
+		};
+		verification::report(&db, metrics, commitment_mismatch_sender, mismatch).await?;
+		return Ok(None);
+	}
+
+	if positions.len() > verified {
 		error!(block_number, "Failed to fetch {} cells", unfetched.len());
 		return Ok(None);
 	}
 
-	// write confidence factor into on-disk database
-	db.put(Key::VerifiedCellCount(block_number), fetched.len() as u32)
-		.wrap_err("Light Client failed to store Confidence Factor")?;
+	// write confidence factor into on-disk database, together with the block header,
+	// which is used later for verifying DHT stored data
+	//
+	// @note header is also written to in another competing thread,
+	// which syncs all block headers in range [0, LATEST],
+	// where LATEST = latest block number when this process started
+	let mut batch = Batch::default();
+	batch.put(
+		Key::VerifiedCellCount(block_number, header_hash),
+		verified as u32,
+	);
+	batch.put_finalized_header(header);
+	info_span!("store_confidence", block_number)
+		.in_scope(|| journal::write_block(&db, block_number, batch))
+		.wrap_err("Light Client failed to store Confidence Factor and Block Header")?;
 
-	state.lock().unwrap().confidence_achieved.set(block_number);
+	// Backfilled blocks are older than the latest processed block
+	let mut locked_state = state.lock().unwrap();
+	if locked_state.confidence_achieved.last() < Some(block_number) {
+		locked_state.confidence_achieved.set(block_number);
+	}
+	drop(locked_state);
 
-	let confidence = calculate_confidence(fetched.len() as u32);
+	let confidence = calculate_confidence(verified as u32);
 	info!(
 		block_number,
 		"confidence" = confidence,
@@ -146,16 +232,10 @@ pub async fn process_block(
 		.record(MetricValue::BlockConfidence(confidence))
 		.await?;
 
-	// push latest mined block's header into column family specified
-	// for keeping block headers, to be used
-	// later for verifying DHT stored data
-	//
-	// @note this same data store is also written to in
-	// another competing thread, which syncs all block headers
-	// in range [0, LATEST], where LATEST = latest block number
-	// when this process started
-	db.put(Key::BlockHeader(block_number), header)
-		.wrap_err("Light Client failed to store Block Header")?;
+	let latest = state.lock().unwrap().latest;
+	metrics
+		.record(MetricValue::SyncLag(latest.saturating_sub(block_number)))
+		.await?;
 
 	Ok(Some(confidence))
 }
@@ -165,36 +245,116 @@ pub async fn process_block(
 /// # Arguments
 ///
 /// * `light_client` - Light client implementation
+/// * `rpc_client` - RPC client, used to fetch headers missed while lagging behind
 /// * `cfg` - Light client configuration
 /// * `metrics` - Metrics registry
 /// * `state` - Processed blocks state
 /// * `channels` - Communication channels
+/// * `scheduler` - Sampling scheduler shared with the sync client
+/// * `dedup` - Processed blocks shared with the sync and backfill clients
 /// * `shutdown` - Shutdown controller
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
 	db: impl Database + Clone,
 	network_client: impl network::Client,
-	cfg: LightClientConfig,
+	rpc_client: rpc::Client,
+	mut cfg: LightClientConfig,
 	metrics: Arc<impl Metrics>,
 	state: Arc<Mutex<State>>,
 	mut channels: ClientChannels,
+	scheduler: Scheduler,
+	dedup: BlockDedup,
 	shutdown: Controller<String>,
 ) {
 	info!("Starting light client...");
 
+	let mut adaptive_sampler = cfg
+		.adaptive_confidence
+		.as_ref()
+		.map(|adaptive_cfg| AdaptiveSampler::new(cfg.confidence, cfg.max_cell_count, adaptive_cfg));
+
+	// Headers missed while lagging behind are processed before the next received header
+	let mut pending = VecDeque::<(Header, Instant)>::new();
+	let mut last_processed: Option<u32> = None;
+
 	loop {
-		let (header, received_at) = match channels.rpc_event_receiver.recv().await {
-			Ok(event) => match event {
-				Event::HeaderUpdate {
+		let (header, received_at) = match pending.pop_front() {
+			Some(pending) => pending,
+			None => match channels.rpc_event_receiver.recv().await {
+				Ok(Event::HeaderUpdate {
 					header,
 					received_at,
-				} => (header, received_at),
-			},
-			Err(error) => {
-				error!("Cannot receive message: {error}");
-				return;
+				}) => (header, received_at),
+				Ok(Event::ConnectionLost { .. } | Event::ConnectionRestored { .. }) => continue,
+				Err(RecvError::Lagged(skipped)) => {
+					warn!("Light client is lagging behind, {skipped} headers are skipped and fetched from RPC");
+					continue;
+				},
+				Err(RecvError::Closed) => {
+					error!("Cannot receive message: channel is closed");
+					return;
+				},
 			},
 		};
 
+		if let Some(missed) = last_processed
+			.map(|last| (last + 1)..header.number)
+			.filter(|missed| !missed.is_empty())
+		{
+			info!("Fetching missed headers {missed:?} from RPC");
+			// Missed headers are fetched by the parent hash, so they are on the chain of the received header
+			let mut parent_hash = header.parent_hash;
+			let mut fetched = Vec::with_capacity(missed.len());
+			for block_number in missed.clone().rev() {
+				let missed_header =
+					rpc_client
+						.get_header_by_hash(parent_hash)
+						.await
+						.and_then(|missed_header| match missed_header.number == block_number {
+							true => Ok(missed_header),
+							false => Err(eyre!(
+								"Parent header number is {}, expected {block_number}",
+								missed_header.number
+							)),
+						});
+				match missed_header {
+					Ok(missed_header) => {
+						parent_hash = missed_header.parent_hash;
+						fetched.push((missed_header, Instant::now()));
+					},
+					Err(error) => {
+						warn!(
+							block_number,
+							"Cannot fetch missed header, block is backfilled later: {error:#}"
+						);
+						let gap = BlockRange {
+							first: missed.start,
+							last: block_number,
+						};
+						backfill::insert(&mut state.lock().unwrap().missed, gap);
+						break;
+					},
+				}
+			}
+			pending.extend(fetched.into_iter().rev());
+		}
+		last_processed = Some(header.number);
+		if !pending.is_empty() {
+			pending.push_back((header, received_at));
+			continue;
+		}
+
+		// Confidence changed by the configuration reload is applied to the next block
+		while let Ok(update) = channels.config_update_receiver.try_recv() {
+			if let ConfigUpdate::Confidence(confidence) = update {
+				info!("Confidence threshold changed to {confidence}");
+				cfg.confidence = confidence;
+				adaptive_sampler = cfg.adaptive_confidence.as_ref().map(|adaptive_cfg| {
+					AdaptiveSampler::new(cfg.confidence, cfg.max_cell_count, adaptive_cfg)
+				});
+			}
+		}
+
 		if let Some(seconds) = cfg.block_processing_delay.sleep_duration(received_at) {
 			if let Err(error) = metrics
 				.record(MetricValue::BlockProcessingDelay(seconds.as_secs_f64()))
@@ -205,19 +365,53 @@ pub async fn run(
 			info!("Sleeping for {seconds:?} seconds");
 			tokio::time::sleep(seconds).await;
 		}
+		cfg.confirmation_depth.wait(header.number, &state).await;
+
+		let (cell_count, effective_confidence) = match adaptive_sampler.as_ref() {
+			Some(sampler) => (sampler.cell_count(), sampler.confidence()),
+			None => {
+				let cell_count = rpc::bounded_cell_count(cfg.confidence, cfg.max_cell_count);
+				(cell_count, calculate_confidence(cell_count))
+			},
+		};
+		if let Err(error) = metrics
+			.record(MetricValue::EffectiveConfidence(effective_confidence))
+			.await
+		{
+			error!("Cannot record effective confidence: {error}");
+		}
+
+		let header_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+		let claim = match dedup.claim(header_hash) {
+			Ok(claim) => claim,
+			Err(duplicate) => {
+				info!(
+					block_number = header.number,
+					"Skipping block, it is {duplicate} by another client"
+				);
+				continue;
+			},
+		};
 
-		let process_block_result = process_block(
+		let slot = scheduler.acquire(Priority::Head).await;
+		let process_block_result = watchdog::process_block(
 			db.clone(),
 			&network_client,
 			&metrics,
 			&cfg,
+			cell_count,
 			header.clone(),
 			received_at,
 			state.clone(),
+			&channels.commitment_mismatch_sender,
 		)
 		.await;
+		drop(slot);
 		let confidence = match process_block_result {
-			Ok(confidence) => confidence,
+			Ok(confidence) => {
+				claim.complete();
+				confidence
+			},
 			Err(error) => {
 				error!("Cannot process block: {error}");
 				let _ = shutdown.trigger_shutdown(format!("Cannot process block: {error:#}"));
@@ -225,6 +419,10 @@ pub async fn run(
 			},
 		};
 
+		if let Some(sampler) = adaptive_sampler.as_mut() {
+			sampler.record(confidence.is_some());
+		}
+
 		let Ok(client_msg) = types::BlockVerified::try_from((header, confidence)) else {
 			error!("Cannot create message from header");
 			continue;
@@ -246,7 +444,7 @@ mod tests {
 	use super::*;
 	use crate::{
 		data::mem_db,
-		network::rpc::{cell_count_for_confidence, CELL_COUNT_99_99},
+		network::rpc::{bounded_cell_count, CELL_COUNT_99_99},
 		telemetry,
 		types::RuntimeConfig,
 	};
@@ -271,7 +469,7 @@ mod tests {
 	#[test_case(50.0 => 1)]
 	#[test_case(50.1 => 2)]
 	fn test_cell_count_for_confidence(confidence: f64) -> u32 {
-		cell_count_for_confidence(confidence)
+		bounded_cell_count(confidence, CELL_COUNT_99_99)
 	}
 
 	#[tokio::test]
@@ -347,9 +545,11 @@ mod tests {
 			&mock_network_client,
 			&Arc::new(mock_metrics),
 			&cfg,
+			bounded_cell_count(cfg.confidence, cfg.max_cell_count),
 			header,
 			recv,
 			state,
+			&broadcast::channel(1).0,
 		)
 		.await
 		.unwrap();
@@ -0,0 +1,187 @@
+//! Log filter with the per-module log levels, which can be changed at runtime.
+
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use std::{
+	collections::BTreeMap,
+	str::FromStr,
+	sync::{Arc, Mutex},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{info, warn, Level};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+use crate::config_reload::ConfigUpdate;
+
+/// Target to which the default log level is applied
+const DEFAULT_TARGET: &str = "avail_light";
+
+pub type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Returns filter directives for the default log level and the overrides.
+fn directives(default_level: Level, overrides: &BTreeMap<String, Level>) -> String {
+	std::iter::once(format!("{DEFAULT_TARGET}={default_level}"))
+		.chain(
+			overrides
+				.iter()
+				.map(|(target, level)| format!("{target}={level}")),
+		)
+		.collect::<Vec<_>>()
+		.join(",")
+}
+
+/// Creates filter for the default log level, without overrides.
+pub fn env_filter(default_level: Level) -> EnvFilter {
+	EnvFilter::new(directives(default_level, &BTreeMap::new()))
+}
+
+fn is_valid_target(target: &str) -> bool {
+	!target.is_empty()
+		&& target
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+struct Levels {
+	default: Level,
+	overrides: BTreeMap<String, Level>,
+}
+
+pub struct LogFilter {
+	handle: FilterHandle,
+	levels: Mutex<Levels>,
+}
+
+impl LogFilter {
+	pub fn new(handle: FilterHandle, default_level: Level) -> Self {
+		LogFilter {
+			handle,
+			levels: Mutex::new(Levels {
+				default: default_level,
+				overrides: BTreeMap::new(),
+			}),
+		}
+	}
+
+	pub fn default_level(&self) -> Level {
+		self.levels.lock().expect("Lock can be acquired").default
+	}
+
+	pub fn overrides(&self) -> BTreeMap<String, Level> {
+		let levels = self.levels.lock().expect("Lock can be acquired");
+		levels.overrides.clone()
+	}
+
+	fn reload(&self, default_level: Level, overrides: &BTreeMap<String, Level>) -> Result<()> {
+		let filter = EnvFilter::try_new(directives(default_level, overrides))
+			.wrap_err("Invalid log filter")?;
+		self.handle
+			.reload(filter)
+			.wrap_err("Failed to reload log filter")
+	}
+
+	/// Sets log levels of the given targets, and removes overrides of the targets without level.
+	/// Filter is not changed if any of the targets is invalid.
+	pub fn update(&self, levels: BTreeMap<String, Option<Level>>) -> Result<()> {
+		let mut current = self.levels.lock().expect("Lock can be acquired");
+		let mut overrides = current.overrides.clone();
+		for (target, level) in levels {
+			if !is_valid_target(&target) {
+				return Err(eyre!("Invalid log target {target}"));
+			}
+			match level {
+				Some(level) => overrides.insert(target, level),
+				None => overrides.remove(&target),
+			};
+		}
+
+		self.reload(current.default, &overrides)?;
+		current.overrides = overrides;
+		Ok(())
+	}
+
+	/// Sets default log level, overrides are kept.
+	pub fn set_default_level(&self, level: Level) -> Result<()> {
+		let mut current = self.levels.lock().expect("Lock can be acquired");
+		self.reload(level, &current.overrides)?;
+		current.default = level;
+		Ok(())
+	}
+}
+
+/// Applies default log level changed on configuration reload.
+pub async fn apply_updates(
+	log_filter: Arc<LogFilter>,
+	mut receiver: broadcast::Receiver<ConfigUpdate>,
+) {
+	loop {
+		let log_level = match receiver.recv().await {
+			Ok(ConfigUpdate::LogLevel(log_level)) => log_level,
+			Ok(_) | Err(RecvError::Lagged(_)) => continue,
+			Err(RecvError::Closed) => return,
+		};
+		let result = Level::from_str(&log_level)
+			.wrap_err_with(|| format!("Invalid log level {log_level}"))
+			.and_then(|level| log_filter.set_default_level(level));
+		match result {
+			Ok(()) => info!("Log level changed to {log_level}"),
+			Err(error) => warn!("Cannot change log level: {error:#}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+	use test_case::test_case;
+	use tracing::Level;
+	use tracing_subscriber::reload;
+
+	use super::{directives, env_filter, is_valid_target, LogFilter};
+
+	#[test_case("avail_light::network::p2p" => true ; "Module path")]
+	#[test_case("libp2p_kad" => true ; "Dependency")]
+	#[test_case("" => false ; "Empty target")]
+	#[test_case("avail_light=debug" => false ; "Directive")]
+	fn test_is_valid_target(target: &str) -> bool {
+		is_valid_target(target)
+	}
+
+	#[test]
+	fn update_overrides() {
+		let (_layer, handle) = reload::Layer::new(env_filter(Level::INFO));
+		let filter = LogFilter::new(handle, Level::INFO);
+
+		let levels = BTreeMap::from([
+			("avail_light::network".to_string(), Some(Level::DEBUG)),
+			("libp2p_kad".to_string(), Some(Level::WARN)),
+		]);
+		filter.update(levels).unwrap();
+		assert_eq!(
+			directives(filter.default_level(), &filter.overrides()),
+			"avail_light=INFO,avail_light::network=DEBUG,libp2p_kad=WARN"
+		);
+
+		let levels = BTreeMap::from([("libp2p_kad".to_string(), None)]);
+		filter.update(levels).unwrap();
+		assert_eq!(
+			filter.overrides(),
+			BTreeMap::from([("avail_light::network".to_string(), Level::DEBUG)])
+		);
+
+		let levels = BTreeMap::from([
+			("avail_light::sync_client".to_string(), Some(Level::TRACE)),
+			("invalid target".to_string(), Some(Level::TRACE)),
+		]);
+		assert!(filter.update(levels).is_err());
+		assert_eq!(filter.overrides().len(), 1);
+
+		filter.set_default_level(Level::WARN).unwrap();
+		assert_eq!(
+			directives(filter.default_level(), &filter.overrides()),
+			"avail_light=WARN,avail_light::network=DEBUG"
+		);
+	}
+}
@@ -1,13 +1,14 @@
 use color_eyre::{eyre::WrapErr, Result};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-	network::p2p::Client as P2pClient,
+	data::Database,
+	network::p2p::{self, Client as P2pClient},
 	shutdown::Controller,
 	telemetry::{MetricValue, Metrics},
-	types::BlockVerified,
+	types::{BlockVerified, State},
 };
 
 #[derive(Clone, Copy)]
@@ -21,8 +22,10 @@ pub struct StaticConfigParams {
 pub async fn process_block(
 	block_number: u32,
 	p2p_client: &P2pClient,
+	db: &impl Database,
 	static_config_params: StaticConfigParams,
 	metrics: &Arc<impl Metrics>,
+	state: &Arc<Mutex<State>>,
 ) -> Result<()> {
 	if block_number % static_config_params.pruning_interval == 0 {
 		info!(block_number, "Pruning...");
@@ -58,6 +61,42 @@ pub async fn process_block(
 	let connected_peers = p2p_client.list_connected_peers().await?;
 	debug!("Connected peers: {:?}", connected_peers);
 
+	match p2p_client.get_reachability().await {
+		Ok(reachability) => {
+			debug!("Reachability: {reachability:?}");
+			state.lock().unwrap().reachability = Some(reachability);
+		},
+		Err(error) => error!(block_number, "Unable to get reachability: {error:#}"),
+	}
+
+	match p2p_client.get_peer_scores().await {
+		Ok(peer_scores) => state.lock().unwrap().peer_scores = peer_scores,
+		Err(error) => error!(block_number, "Unable to get peer scores: {error:#}"),
+	}
+
+	match p2p_client.get_peers().await {
+		Ok(peers) => state.lock().unwrap().peers = peers,
+		Err(error) => error!(block_number, "Unable to get peers: {error:#}"),
+	}
+
+	match p2p_client.get_bandwidth().await {
+		Ok(bandwidth) => {
+			if bandwidth.throttled {
+				warn!(block_number, "DHT upload cap reached, records are not put");
+			}
+			state.lock().unwrap().bandwidth = bandwidth;
+		},
+		Err(error) => error!(block_number, "Unable to get bandwidth: {error:#}"),
+	}
+
+	match p2p::store_peers(p2p_client, db).await {
+		Ok(stored) => debug!(block_number, stored, "Routing table peers stored"),
+		Err(error) => error!(
+			block_number,
+			"Storing routing table peers failed: {error:#}"
+		),
+	}
+
 	let peers_num_metric = MetricValue::ConnectedPeersNum(peers_num);
 	metrics.record(peers_num_metric).await?;
 
@@ -84,9 +123,11 @@ pub async fn process_block(
 
 pub async fn run(
 	p2p_client: P2pClient,
+	db: impl Database,
 	metrics: Arc<impl Metrics>,
 	mut block_receiver: broadcast::Receiver<BlockVerified>,
 	static_config_params: StaticConfigParams,
+	state: Arc<Mutex<State>>,
 	shutdown: Controller<String>,
 ) {
 	info!("Starting maintenance...");
@@ -94,7 +135,15 @@ pub async fn run(
 	loop {
 		let result = match block_receiver.recv().await {
 			Ok(block) => {
-				process_block(block.block_num, &p2p_client, static_config_params, &metrics).await
+				process_block(
+					block.block_num,
+					&p2p_client,
+					&db,
+					static_config_params,
+					&metrics,
+					&state,
+				)
+				.await
 			},
 			Err(error) => Err(error.into()),
 		};
@@ -1,21 +1,36 @@
 use async_trait::async_trait;
-use color_eyre::{eyre::WrapErr, Result};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
 use dusk_plonk::prelude::PublicParameters;
 use kate_recovery::{
-	config,
-	data::Cell,
+	com::reconstruct_columns,
+	config::{self, EXTENSION_FACTOR},
+	data::{Cell, DataCell},
 	matrix::{Dimensions, Position},
 };
 use mockall::automock;
 use sp_core::H256;
-use std::{sync::Arc, time::Duration};
+use std::{
+	collections::{BTreeSet, HashSet},
+	sync::Arc,
+	time::Duration,
+};
 use tokio::time::Instant;
-use tracing::{debug, info};
+use tracing::{debug, info, info_span, warn, Instrument};
 
-use crate::proof;
+use self::p2p::CellPeers;
+use crate::{
+	data::{cell_cache::CellCache, BlockSamplingStats, CellSource, SampledCell, SamplingTimings},
+	proof::{self, Lane},
+	retry::RetryPolicy,
+};
 
 pub mod p2p;
+pub mod proxy;
 pub mod rpc;
+pub mod tuning;
 
 #[async_trait]
 #[automock]
@@ -30,12 +45,82 @@ pub trait Client {
 	) -> Result<(Vec<Cell>, Vec<Position>, FetchStats)>;
 }
 
+/// Transport used to fetch cells (with proofs) of the given block.
+#[async_trait]
+#[automock]
+pub trait CellFetcher {
+	/// Fetches cells on given positions. Cells which cannot be fetched are omitted from the result.
+	/// Peers which served the cells are returned if the transport is peer to peer.
+	async fn fetch_cells(
+		&self,
+		block_number: u32,
+		block_hash: H256,
+		positions: &[Position],
+	) -> Result<(Vec<Cell>, CellPeers)>;
+
+	/// Reports peers which served the cells, once the cell proofs are verified.
+	fn report_verified_cells(&self, _peers: CellPeers, _verified: &[Position]) {}
+
+	/// Shares verified cells fetched from other transports. Transports which cannot serve cells ignore them.
+	async fn share_cells(&self, _block_number: u32, _cells: Vec<Cell>) -> Result<()> {
+		Ok(())
+	}
+}
+
+#[async_trait]
+impl CellFetcher for p2p::Client {
+	async fn fetch_cells(
+		&self,
+		block_number: u32,
+		_block_hash: H256,
+		positions: &[Position],
+	) -> Result<(Vec<Cell>, CellPeers)> {
+		let (fetched, _, peers) = self.fetch_cells_from_dht(block_number, positions).await;
+		Ok((fetched, peers))
+	}
+
+	fn report_verified_cells(&self, peers: CellPeers, verified: &[Position]) {
+		p2p::Client::report_verified_cells(self, peers, verified)
+	}
+
+	async fn share_cells(&self, block_number: u32, cells: Vec<Cell>) -> Result<()> {
+		self.insert_cells_into_dht(block_number, cells).await
+	}
+}
+
+#[async_trait]
+impl CellFetcher for rpc::Client {
+	async fn fetch_cells(
+		&self,
+		_block_number: u32,
+		block_hash: H256,
+		positions: &[Position],
+	) -> Result<(Vec<Cell>, CellPeers)> {
+		let fetched = self.request_kate_proof(block_hash, positions).await?;
+		Ok((fetched, CellPeers::default()))
+	}
+}
+
+/// Returns positions for which cells are not fetched
+fn unfetched_positions(positions: &[Position], fetched: &[Cell]) -> Vec<Position> {
+	positions
+		.iter()
+		.filter(|position| !fetched.iter().any(|cell| &cell.position == *position))
+		.cloned()
+		.collect()
+}
+
 pub struct FetchStats {
 	pub dht_fetched: f64,
 	pub dht_fetched_percentage: f64,
 	pub dht_fetch_duration: f64,
 	pub rpc_fetched: Option<f64>,
 	pub rpc_fetch_duration: Option<f64>,
+	pub proof_verification_duration: f64,
+	/// Number of the cells reconstructed from the other cells of their columns, which are not counted as verified
+	pub reconstructed: usize,
+	/// Fetch source and verification outcome of each sampled cell
+	pub cells: Vec<SampledCell>,
 }
 
 type RPCFetchStats = (usize, Duration);
@@ -53,80 +138,194 @@ impl FetchStats {
 			dht_fetch_duration: dht_fetch_duration.as_secs_f64(),
 			rpc_fetched: rpc_fetch_stats.map(|(rpc_fetched, _)| rpc_fetched as f64),
 			rpc_fetch_duration: rpc_fetch_stats.map(|(_, duration)| duration.as_secs_f64()),
+			proof_verification_duration: 0.0,
+			reconstructed: 0,
+			cells: vec![],
+		}
+	}
+
+	/// Sampling outcome of the block, with the sampled cells in the order of sampling, stored for the sampled cells API.
+	pub fn sampling_stats(&self, total: Duration) -> BlockSamplingStats {
+		let secs_to_millis = |secs: f64| (secs * 1000.0) as u64;
+		BlockSamplingStats {
+			cells: self.cells.clone(),
+			timings: SamplingTimings {
+				dht_fetch: secs_to_millis(self.dht_fetch_duration),
+				rpc_fetch: self.rpc_fetch_duration.map(secs_to_millis),
+				proof_verification: secs_to_millis(self.proof_verification_duration),
+				total: total.as_millis() as u64,
+			},
 		}
 	}
 }
 
-struct DHTWithRPCFallbackClient {
-	p2p_client: p2p::Client,
-	rpc_client: rpc::Client,
-	pp: Arc<PublicParameters>,
-	disable_rpc: bool,
+/// Cells fetched from a single source
+struct FetchedCells {
+	/// Fetched cells with verified proofs
+	verified: Vec<Cell>,
+	/// Positions of cells which are not fetched or failed the proof verification
+	unfetched: Vec<Position>,
+	/// Positions of cells which failed the proof verification
+	unverified: Vec<Position>,
+	fetch_duration: Duration,
+	verification_duration: Duration,
 }
 
-type Commitments = [[u8; config::COMMITMENT_SIZE]];
+/// Returns fetch outcome of each position, later sources override outcomes of the earlier ones.
+fn sampled_cells(
+	positions: &[Position],
+	sources: &[(CellSource, &FetchedCells)],
+) -> Vec<SampledCell> {
+	positions
+		.iter()
+		.map(|position| {
+			let mut cell = SampledCell {
+				row: position.row,
+				col: position.col,
+				source: None,
+				verified: false,
+			};
+			for (source, fetched) in sources {
+				if fetched.verified.iter().any(|c| &c.position == position) {
+					(cell.source, cell.verified) = (Some(*source), true);
+				} else if fetched.unverified.contains(position) {
+					(cell.source, cell.verified) = (Some(*source), false);
+				}
+			}
+			cell
+		})
+		.collect()
+}
 
-impl DHTWithRPCFallbackClient {
-	async fn fetch_verified_from_dht(
-		&self,
-		block_number: u32,
-		dimensions: Dimensions,
-		commitments: &Commitments,
-		positions: &[Position],
-	) -> Result<(Vec<Cell>, Vec<Position>, Duration)> {
-		let begin = Instant::now();
+/// Returns positions of the cells to fetch, so that each column of the missing cells has enough cells for reconstruction.
+/// Columns with missing cells only in the extended rows are skipped, as are the positions of the missing and verified cells.
+fn reconstruction_positions(
+	dimensions: Dimensions,
+	missing: &[Position],
+	verified: &[Cell],
+) -> Vec<Position> {
+	let rows = usize::from(dimensions.rows().get());
+	let columns = missing
+		.iter()
+		.filter(|position| position.row as usize % EXTENSION_FACTOR == 0)
+		.map(|position| position.col)
+		.collect::<BTreeSet<_>>();
 
-		let (mut dht_fetched, mut unfetched) = self
-			.p2p_client
-			.fetch_cells_from_dht(block_number, positions)
-			.await;
+	columns
+		.into_iter()
+		.flat_map(|col| {
+			let known = verified
+				.iter()
+				.map(|cell| cell.position)
+				.chain(missing.iter().cloned())
+				.filter(|position| position.col == col)
+				.collect::<Vec<_>>();
+			let available = verified
+				.iter()
+				.filter(|cell| cell.position.col == col)
+				.count();
+			(0..dimensions.extended_rows())
+				.map(move |row| Position { row, col })
+				.filter(move |position| !known.contains(position))
+				.take(rows.saturating_sub(available))
+		})
+		.collect()
+}
 
-		let fetch_elapsed = begin.elapsed();
+/// Reconstructs missing cells from the verified cells of the same columns.
+/// Columns without enough verified cells are skipped, and only cells of the original (not extended) rows
+/// can be reconstructed, since the reconstructed column is not extended.
+/// Reconstructed cells have no proofs, so they cannot be verified against the commitments.
+fn reconstruct_cells(
+	dimensions: Dimensions,
+	missing: &[Position],
+	verified: &[Cell],
+) -> Result<Vec<DataCell>> {
+	let rows = usize::from(dimensions.rows().get());
+	let columns = missing
+		.iter()
+		.map(|position| position.col)
+		.filter(|&col| {
+			verified
+				.iter()
+				.filter(|cell| cell.position.col == col)
+				.count() >= rows
+		})
+		.collect::<HashSet<_>>();
 
-		let (verified, mut unverified) = proof::verify(
-			block_number,
-			dimensions,
-			&dht_fetched,
-			commitments,
-			self.pp.clone(),
-		)
-		.await
-		.context("Failed to verify fetched cells")?;
+	let cells = verified
+		.iter()
+		.filter(|cell| columns.contains(&cell.position.col))
+		.cloned()
+		.collect::<Vec<_>>();
+	if cells.is_empty() {
+		return Ok(vec![]);
+	}
 
-		info!(
-			block_number,
-			cells_total = positions.len(),
-			cells_fetched = dht_fetched.len(),
-			cells_verified = verified.len(),
-			fetch_elapsed = ?fetch_elapsed,
-			proof_verification_elapsed = ?(begin.elapsed() - fetch_elapsed),
-			"Cells fetched from DHT"
-		);
+	let reconstructed = reconstruct_columns(dimensions, &cells)?;
+	let reconstructed_data = |position: &Position| {
+		let row = position.row as usize;
+		if row % EXTENSION_FACTOR != 0 {
+			return None;
+		}
+		reconstructed
+			.get(&position.col)
+			.and_then(|column| column.get(row / EXTENSION_FACTOR))
+	};
 
-		dht_fetched.retain(|cell| verified.contains(&cell.position));
-		unfetched.append(&mut unverified);
+	Ok(missing
+		.iter()
+		.filter_map(|&position| {
+			reconstructed_data(&position).map(|&data| DataCell { position, data })
+		})
+		.collect())
+}
 
-		Ok((dht_fetched, unfetched, fetch_elapsed))
-	}
+struct DHTWithRPCFallbackClient<D, R> {
+	dht_fetcher: D,
+	rpc_fetcher: R,
+	pp: Arc<PublicParameters>,
+	disable_rpc: bool,
+	cell_cache: CellCache,
+	retry_policy: RetryPolicy,
+	cell_reconstruction: bool,
+	lane: Lane,
+}
+
+type Commitments = [[u8; config::COMMITMENT_SIZE]];
 
-	async fn fetch_verified_from_rpc(
+impl<D: CellFetcher + Sync, R: CellFetcher + Sync> DHTWithRPCFallbackClient<D, R> {
+	async fn fetch_verified_from(
 		&self,
+		source: CellSource,
 		block_number: u32,
 		block_hash: H256,
 		dimensions: Dimensions,
 		commitments: &Commitments,
 		positions: &[Position],
-	) -> Result<(Vec<Cell>, Vec<Position>, Duration)> {
+	) -> Result<FetchedCells> {
 		let begin = Instant::now();
 
-		let mut fetched = self
-			.rpc_client
-			.request_kate_proof(block_hash, positions)
+		let fetcher: &(dyn CellFetcher + Sync) = match source {
+			CellSource::Dht => &self.dht_fetcher,
+			CellSource::Rpc => &self.rpc_fetcher,
+			CellSource::Cache | CellSource::Reconstructed => {
+				return Err(eyre!("Cells cannot be fetched from {source}"))
+			},
+		};
+		let (mut fetched, peers) = self
+			.retry_policy
+			.retry("cell_fetch", || {
+				fetcher.fetch_cells(block_number, block_hash, positions)
+			})
+			.instrument(info_span!("fetch_cells", %source, cells = positions.len()))
 			.await?;
+		let mut unfetched = unfetched_positions(positions, &fetched);
 
-		let fetch_elapsed = begin.elapsed();
+		let fetch_duration = begin.elapsed();
 
 		let (verified, unverified) = proof::verify(
+			self.lane,
 			block_number,
 			dimensions,
 			&fetched,
@@ -136,24 +335,33 @@ impl DHTWithRPCFallbackClient {
 		.await
 		.context("Failed to verify fetched cells")?;
 
+		let verification_duration = begin.elapsed() - fetch_duration;
+		fetcher.report_verified_cells(peers, &verified);
+
 		info!(
 			block_number,
 			cells_total = positions.len(),
 			cells_fetched = fetched.len(),
 			cells_verified = verified.len(),
-			fetch_elapsed = ?fetch_elapsed,
-			proof_verification_elapsed = ?(begin.elapsed() - fetch_elapsed),
-			"Cells fetched from RPC"
+			fetch_elapsed = ?fetch_duration,
+			proof_verification_elapsed = ?verification_duration,
+			"Cells fetched from {source}"
 		);
 
 		fetched.retain(|cell| verified.contains(&cell.position));
-		Ok((fetched, unverified, fetch_elapsed))
+		unfetched.extend(unverified.iter().cloned());
+
+		Ok(FetchedCells {
+			verified: fetched,
+			unfetched,
+			unverified,
+			fetch_duration,
+			verification_duration,
+		})
 	}
-}
 
-#[async_trait]
-impl Client for DHTWithRPCFallbackClient {
-	async fn fetch_verified(
+	/// Fetches the cells on the given positions, which are not cached, from DHT and then from RPC.
+	async fn fetch_verified_cells(
 		&self,
 		block_number: u32,
 		block_hash: H256,
@@ -161,44 +369,196 @@ impl Client for DHTWithRPCFallbackClient {
 		commitments: &Commitments,
 		positions: &[Position],
 	) -> Result<(Vec<Cell>, Vec<Position>, FetchStats)> {
-		let (dht_fetched, unfetched, dht_fetch_duration) = self
-			.fetch_verified_from_dht(block_number, dimensions, commitments, positions)
+		let (cached, uncached) = self.cell_cache.get(block_number, positions);
+		let cache = FetchedCells {
+			verified: cached,
+			unfetched: vec![],
+			unverified: vec![],
+			fetch_duration: Duration::ZERO,
+			verification_duration: Duration::ZERO,
+		};
+		debug!(
+			block_number,
+			"Reusing {} cached cells",
+			cache.verified.len()
+		);
+
+		let dht = self
+			.fetch_verified_from(
+				CellSource::Dht,
+				block_number,
+				block_hash,
+				dimensions,
+				commitments,
+				&uncached,
+			)
 			.await?;
+		self.cell_cache.insert(block_number, &dht.verified);
 
 		if self.disable_rpc {
-			let stats =
-				FetchStats::new(positions.len(), dht_fetched.len(), dht_fetch_duration, None);
-			return Ok((dht_fetched, unfetched, stats));
+			let mut stats = FetchStats::new(
+				positions.len(),
+				dht.verified.len(),
+				dht.fetch_duration,
+				None,
+			);
+			stats.proof_verification_duration = dht.verification_duration.as_secs_f64();
+			stats.cells = sampled_cells(
+				positions,
+				&[(CellSource::Cache, &cache), (CellSource::Dht, &dht)],
+			);
+			let mut fetched = cache.verified;
+			fetched.extend(dht.verified);
+			return Ok((fetched, dht.unfetched, stats));
 		};
 
-		let (rpc_fetched, unfetched, rpc_fetch_duration) = self
-			.fetch_verified_from_rpc(
+		let rpc = self
+			.fetch_verified_from(
+				CellSource::Rpc,
 				block_number,
 				block_hash,
 				dimensions,
 				commitments,
-				&unfetched,
+				&dht.unfetched,
 			)
 			.await?;
+		self.cell_cache.insert(block_number, &rpc.verified);
 
 		if let Err(error) = self
-			.p2p_client
-			.insert_cells_into_dht(block_number, rpc_fetched.clone())
+			.dht_fetcher
+			.share_cells(block_number, rpc.verified.clone())
 			.await
 		{
 			debug!("Error inserting cells into DHT: {error}");
 		}
 
-		let stats = FetchStats::new(
+		let mut stats = FetchStats::new(
 			positions.len(),
-			dht_fetched.len(),
-			dht_fetch_duration,
-			Some((rpc_fetched.len(), rpc_fetch_duration)),
+			dht.verified.len(),
+			dht.fetch_duration,
+			Some((rpc.verified.len(), rpc.fetch_duration)),
 		);
+		stats.proof_verification_duration =
+			(dht.verification_duration + rpc.verification_duration).as_secs_f64();
+		stats.cells = sampled_cells(
+			positions,
+			&[
+				(CellSource::Cache, &cache),
+				(CellSource::Dht, &dht),
+				(CellSource::Rpc, &rpc),
+			],
+		);
+
+		let mut fetched = cache.verified;
+		fetched.extend(dht.verified);
+		fetched.extend(rpc.verified);
 
-		let mut fetched = vec![];
-		fetched.extend(dht_fetched);
-		fetched.extend(rpc_fetched);
+		Ok((fetched, rpc.unfetched, stats))
+	}
+
+	/// Fetches enough cells of the columns of the missing cells from DHT and then from RPC,
+	/// and reconstructs the missing cells from them.
+	async fn reconstruct(
+		&self,
+		block_number: u32,
+		block_hash: H256,
+		dimensions: Dimensions,
+		commitments: &Commitments,
+		missing: &[Position],
+		verified: &[Cell],
+	) -> Result<Vec<DataCell>> {
+		let positions = reconstruction_positions(dimensions, missing, verified);
+		let mut cells = verified.to_vec();
+
+		if !positions.is_empty() {
+			let dht = self
+				.fetch_verified_from(
+					CellSource::Dht,
+					block_number,
+					block_hash,
+					dimensions,
+					commitments,
+					&positions,
+				)
+				.await?;
+			self.cell_cache.insert(block_number, &dht.verified);
+			cells.extend(dht.verified);
+
+			if !self.disable_rpc && !dht.unfetched.is_empty() {
+				let rpc = self
+					.fetch_verified_from(
+						CellSource::Rpc,
+						block_number,
+						block_hash,
+						dimensions,
+						commitments,
+						&dht.unfetched,
+					)
+					.await?;
+				self.cell_cache.insert(block_number, &rpc.verified);
+				cells.extend(rpc.verified);
+			}
+		}
+
+		let reconstructed = reconstruct_cells(dimensions, missing, &cells)?;
+		debug!(
+			block_number,
+			"Reconstructed {} of {} missing cells",
+			reconstructed.len(),
+			missing.len()
+		);
+		Ok(reconstructed)
+	}
+}
+
+#[async_trait]
+impl<D: CellFetcher + Sync, R: CellFetcher + Sync> Client for DHTWithRPCFallbackClient<D, R> {
+	async fn fetch_verified(
+		&self,
+		block_number: u32,
+		block_hash: H256,
+		dimensions: Dimensions,
+		commitments: &Commitments,
+		positions: &[Position],
+	) -> Result<(Vec<Cell>, Vec<Position>, FetchStats)> {
+		let (fetched, unfetched, mut stats) = self
+			.fetch_verified_cells(block_number, block_hash, dimensions, commitments, positions)
+			.await?;
+
+		if !self.cell_reconstruction || unfetched.is_empty() {
+			return Ok((fetched, unfetched, stats));
+		}
+
+		let reconstructed = match self
+			.reconstruct(
+				block_number,
+				block_hash,
+				dimensions,
+				commitments,
+				&unfetched,
+				&fetched,
+			)
+			.await
+		{
+			Ok(reconstructed) => reconstructed,
+			Err(error) => {
+				warn!(block_number, "Cannot reconstruct missing cells: {error:#}");
+				vec![]
+			},
+		};
+
+		// Reconstructed cells stay unfetched, since their proofs are not verified
+		let is_reconstructed =
+			|position: &Position| reconstructed.iter().any(|cell| &cell.position == position);
+		for cell in stats.cells.iter_mut() {
+			if is_reconstructed(&Position {
+				row: cell.row,
+				col: cell.col,
+			}) {
+				cell.source = Some(CellSource::Reconstructed);
+			}
+		}
+		stats.reconstructed = reconstructed.len();
 
 		Ok((fetched, unfetched, stats))
 	}
@@ -209,11 +569,75 @@ pub fn new(
 	rpc_client: rpc::Client,
 	pp: Arc<PublicParameters>,
 	disable_rpc: bool,
+	cell_cache: CellCache,
+	retry_policy: RetryPolicy,
+	cell_reconstruction: bool,
+	lane: Lane,
 ) -> impl Client {
 	DHTWithRPCFallbackClient {
-		p2p_client,
-		rpc_client,
+		dht_fetcher: p2p_client,
+		rpc_fetcher: rpc_client,
 		pp,
 		disable_rpc,
+		cell_cache,
+		retry_policy,
+		cell_reconstruction,
+		lane,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use kate_recovery::{
+		data::Cell,
+		matrix::{Dimensions, Position},
+	};
+
+	use super::{reconstruct_cells, reconstruction_positions};
+
+	fn cell(row: u32, col: u16) -> Cell {
+		Cell {
+			position: Position { row, col },
+			content: [0u8; 80],
+		}
+	}
+
+	#[test]
+	fn reconstruction_positions_complete_columns() {
+		let dimensions = Dimensions::new(2, 4).unwrap();
+		let missing = [
+			Position { row: 0, col: 0 },
+			Position { row: 2, col: 1 },
+			// missing cell of the extended row cannot be reconstructed
+			Position { row: 1, col: 2 },
+		];
+		let verified = [cell(3, 0)];
+
+		let positions = reconstruction_positions(dimensions, &missing, &verified);
+		assert_eq!(
+			positions,
+			vec![
+				Position { row: 1, col: 0 },
+				Position { row: 0, col: 1 },
+				Position { row: 1, col: 1 },
+			]
+		);
+	}
+
+	#[test]
+	fn missing_cells_are_reconstructed() {
+		let dimensions = Dimensions::new(2, 4).unwrap();
+		let missing = [
+			Position { row: 0, col: 0 },
+			Position { row: 1, col: 0 },
+			Position { row: 0, col: 1 },
+		];
+		// column 1 does not have enough verified cells
+		let verified = [cell(2, 0), cell(3, 0), cell(2, 1)];
+
+		let reconstructed = reconstruct_cells(dimensions, &missing, &verified).unwrap();
+		assert_eq!(reconstructed.len(), 1);
+		assert_eq!(reconstructed[0].position, Position { row: 0, col: 0 });
+		assert_eq!(reconstructed[0].data, [0u8; 32]);
 	}
 }
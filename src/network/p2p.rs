@@ -1,32 +1,66 @@
 use allow_block_list::BlockedPeers;
-use color_eyre::{eyre::WrapErr, Report, Result};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Report, Result,
+};
+use futures::future::Either;
 use libp2p::{
-	autonat, dcutr, identify, identity,
+	autonat,
+	core::{muxing::StreamMuxerBox, upgrade},
+	dcutr, gossipsub, identify, identity,
 	kad::{self, PeerRecord, QueryId},
-	mdns, noise, ping, relay,
-	swarm::NetworkBehaviour,
-	tcp, upnp, yamux, PeerId, Swarm, SwarmBuilder,
+	multiaddr::Protocol,
+	noise, ping, relay,
+	swarm::{behaviour::toggle::Toggle, NetworkBehaviour},
+	yamux, Multiaddr, PeerId, Swarm, SwarmBuilder, Transport,
 };
+#[cfg(target_arch = "wasm32")]
+use libp2p::{core::transport::Boxed, webrtc_websys, websocket_websys};
+#[cfg(not(target_arch = "wasm32"))]
+use libp2p::{core::transport::OptionalTransport, mdns, quic, tcp, upnp};
 use multihash::{self, Hasher};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tokio::sync::{
 	mpsc::{self},
 	oneshot,
 };
-use tracing::info;
+use tracing::{debug, info, warn};
 
 #[cfg(feature = "network-analysis")]
 pub mod analyzer;
+mod bandwidth;
+mod bootstrap;
 mod client;
 mod event_loop;
 mod kad_mem_store;
+mod peers;
+#[cfg(not(target_arch = "wasm32"))]
+mod proxy;
+mod rate_limit;
+mod record_validator;
+mod reputation;
 
-use crate::types::{LibP2PConfig, SecretKey};
-pub use client::Client;
+use crate::{
+	data::{Database, Key},
+	types::{LibP2PConfig, SecretKey},
+};
+pub use avail_light_api_types::NatStatus;
+pub use bandwidth::{BandwidthStats, Traffic, UploadCap};
+pub use bootstrap::{bootstrap_peers, retry_bootstrap};
+pub use client::{CellPeers, Client};
 pub use event_loop::EventLoop;
 pub use kad_mem_store::MemoryStoreConfig;
+pub use peers::{Direction, PeerInfo, Peers, RoutingTableStats, Transport};
+#[cfg(not(target_arch = "wasm32"))]
+use proxy::ProxyTransport;
+pub use record_validator::{forward_commitments, BlockCommitments};
+pub use reputation::{PeerEvent, PeerScore, ReputationConfig};
 
-use self::{client::BlockStat, kad_mem_store::MemoryStore};
+use self::{
+	bandwidth::Bandwidth, client::BlockStat, kad_mem_store::MemoryStore, peers::ConnectedPeers,
+	reputation::PeerScores,
+};
 use libp2p_allow_block_list as allow_block_list;
 
 #[derive(Debug)]
@@ -42,6 +76,9 @@ pub struct EventLoopEntries<'a> {
 	pending_swarm_events: &'a mut HashMap<PeerId, oneshot::Sender<Result<()>>>,
 	/// <block_num, (total_cells, result_cell_counter, time_stat)>
 	active_blocks: &'a mut HashMap<u32, BlockStat>,
+	peer_scores: &'a mut PeerScores,
+	connected_peers: &'a ConnectedPeers,
+	bandwidth: &'a mut Bandwidth,
 }
 
 impl<'a> EventLoopEntries<'a> {
@@ -50,12 +87,18 @@ impl<'a> EventLoopEntries<'a> {
 		pending_kad_queries: &'a mut HashMap<QueryId, QueryChannel>,
 		pending_swarm_events: &'a mut HashMap<PeerId, oneshot::Sender<Result<()>>>,
 		active_blocks: &'a mut HashMap<u32, BlockStat>,
+		peer_scores: &'a mut PeerScores,
+		connected_peers: &'a ConnectedPeers,
+		bandwidth: &'a mut Bandwidth,
 	) -> Self {
 		Self {
 			swarm,
 			pending_kad_queries,
 			pending_swarm_events,
 			active_blocks,
+			peer_scores,
+			connected_peers,
+			bandwidth,
 		}
 	}
 
@@ -78,6 +121,73 @@ impl<'a> EventLoopEntries<'a> {
 	pub fn swarm(&mut self) -> &mut Swarm<Behaviour> {
 		self.swarm
 	}
+
+	/// Records peer event, and bans peer if its score drops below the threshold
+	pub fn report_peer(&mut self, peer_id: PeerId, event: PeerEvent) {
+		if self
+			.peer_scores
+			.record(peer_id, event, tokio::time::Instant::now())
+		{
+			ban_peer(self.swarm, peer_id);
+		}
+	}
+}
+
+fn ban_peer(swarm: &mut Swarm<Behaviour>, peer_id: PeerId) {
+	info!("Banning peer {peer_id} due to low reputation");
+	swarm.behaviour_mut().kademlia.remove_peer(&peer_id);
+	swarm.behaviour_mut().blocked_peers.block_peer(peer_id);
+	_ = swarm.disconnect_peer_id(peer_id);
+}
+
+/// Returns `false` if the address cannot be dialed by the remote peers,
+/// i.e. unspecified address, or IPv6 link-local address, which is valid only with the scope of the local interface.
+fn is_dialable(address: &Multiaddr) -> bool {
+	match address.iter().next() {
+		Some(Protocol::Ip4(ip)) => !ip.is_unspecified(),
+		Some(Protocol::Ip6(ip)) => !ip.is_unspecified() && (ip.segments()[0] & 0xffc0) != 0xfe80,
+		_ => true,
+	}
+}
+
+/// Reachability of the local peer, as observed by the AutoNAT probes
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Reachability {
+	pub nat_status: NatStatus,
+	/// Public address confirmed by the AutoNAT servers
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub public_address: Option<Multiaddr>,
+	/// Local peer is listening on the relay circuit, which makes it dialable behind the NAT
+	pub relayed: bool,
+}
+
+impl Reachability {
+	fn new(status: autonat::NatStatus, relayed: bool) -> Self {
+		let (nat_status, public_address) = match status {
+			autonat::NatStatus::Public(address) => (NatStatus::Public, Some(address)),
+			autonat::NatStatus::Private => (NatStatus::Private, None),
+			autonat::NatStatus::Unknown => (NatStatus::Unknown, None),
+		};
+		Reachability {
+			nat_status,
+			public_address,
+			relayed,
+		}
+	}
+}
+
+/// Gossip message received from the peer, forwarded to the subscribers after the signature is verified
+/// and the rate limit of the source peer is checked.
+/// Message is propagated only after the subscriber reports it as valid (see [`Client::report_gossip_validation`]).
+#[derive(Clone, Debug)]
+pub struct GossipMessage {
+	/// Peer which published the message
+	pub source: PeerId,
+	/// Peer from which the message is received
+	pub propagation_source: PeerId,
+	pub message_id: gossipsub::MessageId,
+	pub topic: String,
+	pub data: Vec<u8>,
 }
 
 pub trait Command {
@@ -96,12 +206,13 @@ pub struct Behaviour {
 	kademlia: kad::Behaviour<MemoryStore>,
 	identify: identify::Behaviour,
 	ping: ping::Behaviour,
-	mdns: mdns::tokio::Behaviour,
+	mdns: Toggle<Mdns>,
 	auto_nat: autonat::Behaviour,
 	relay_client: relay::client::Behaviour,
 	dcutr: dcutr::Behaviour,
-	upnp: upnp::tokio::Behaviour,
+	upnp: Toggle<Upnp>,
 	blocked_peers: allow_block_list::Behaviour<BlockedPeers>,
+	gossipsub: Toggle<gossipsub::Behaviour>,
 }
 
 fn generate_config(config: libp2p::swarm::Config, cfg: &LibP2PConfig) -> libp2p::swarm::Config {
@@ -113,6 +224,80 @@ fn generate_config(config: libp2p::swarm::Config, cfg: &LibP2PConfig) -> libp2p:
 		.with_per_connection_event_buffer_size(cfg.per_connection_event_buffer_size)
 }
 
+/// Local network discovery, which is not available in the browser
+#[cfg(not(target_arch = "wasm32"))]
+type Mdns = mdns::tokio::Behaviour;
+#[cfg(target_arch = "wasm32")]
+type Mdns = libp2p::swarm::dummy::Behaviour;
+
+/// Port mapping on the gateway, which is not available in the browser
+#[cfg(not(target_arch = "wasm32"))]
+type Upnp = upnp::tokio::Behaviour;
+#[cfg(target_arch = "wasm32")]
+type Upnp = libp2p::swarm::dummy::Behaviour;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn mdns_behaviour(cfg: &LibP2PConfig, key: &identity::Keypair) -> std::io::Result<Toggle<Mdns>> {
+	let mdns = cfg
+		.mdns
+		.then(|| mdns::Behaviour::new(mdns::Config::default(), key.public().to_peer_id()))
+		.transpose()?;
+	Ok(mdns.into())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn mdns_behaviour(_: &LibP2PConfig, _: &identity::Keypair) -> std::io::Result<Toggle<Mdns>> {
+	Ok(None.into())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn upnp_behaviour(cfg: &LibP2PConfig) -> Toggle<Upnp> {
+	cfg.upnp.then(upnp::tokio::Behaviour::default).into()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn upnp_behaviour(_: &LibP2PConfig) -> Toggle<Upnp> {
+	None.into()
+}
+
+/// Creates the browser transport, which dials the peers over WebSocket (`/ws` and `/wss` addresses)
+/// and WebRTC (`/webrtc-direct` addresses).
+#[cfg(target_arch = "wasm32")]
+fn browser_transport(
+	key: &identity::Keypair,
+) -> Result<Boxed<(PeerId, StreamMuxerBox)>, noise::Error> {
+	let websocket = websocket_websys::Transport::default()
+		.upgrade(upgrade::Version::V1Lazy)
+		.authenticate(noise::Config::new(key)?)
+		.multiplex(yamux::Config::default());
+	let webrtc = webrtc_websys::Transport::new(webrtc_websys::Config::new(key));
+	Ok(websocket
+		.or_transport(webrtc)
+		.map(|output, _| match output {
+			Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+			Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+		})
+		.boxed())
+}
+
+/// Creates gossipsub behaviour with signed messages, subscribed to the given topics.
+/// Messages are validated by the subscribers before they are propagated.
+fn gossipsub_behaviour(
+	key: &identity::Keypair,
+	topics: &[String],
+) -> Result<gossipsub::Behaviour, Box<dyn std::error::Error + Send + Sync>> {
+	let config = gossipsub::ConfigBuilder::default()
+		.validation_mode(gossipsub::ValidationMode::Strict)
+		.validate_messages()
+		.build()?;
+	let mut behaviour =
+		gossipsub::Behaviour::new(gossipsub::MessageAuthenticity::Signed(key.clone()), config)?;
+	for topic in topics {
+		behaviour.subscribe(&gossipsub::IdentTopic::new(topic))?;
+	}
+	Ok(behaviour)
+}
+
 async fn build_swarm(
 	cfg: &LibP2PConfig,
 	id_keys: &libp2p::identity::Keypair,
@@ -136,10 +321,6 @@ async fn build_swarm(
 
 	// build the Swarm, connecting the lower transport logic with the
 	// higher layer network behaviour logic
-	let tokio_swarm = SwarmBuilder::with_existing_identity(id_keys.clone()).with_tokio();
-
-	let mut swarm;
-
 	let behaviour = |key: &identity::Keypair, relay_client| {
 		Ok(Behaviour {
 			ping: ping::Behaviour::new(ping::Config::new()),
@@ -148,33 +329,100 @@ async fn build_swarm(
 			dcutr: dcutr::Behaviour::new(key.public().to_peer_id()),
 			kademlia: kad::Behaviour::with_config(key.public().to_peer_id(), kad_store, cfg.into()),
 			auto_nat: autonat::Behaviour::new(key.public().to_peer_id(), autonat_cfg),
-			mdns: mdns::Behaviour::new(mdns::Config::default(), key.public().to_peer_id())?,
-			upnp: upnp::tokio::Behaviour::default(),
+			mdns: mdns_behaviour(cfg, key)?,
+			upnp: upnp_behaviour(cfg),
 			blocked_peers: allow_block_list::Behaviour::default(),
+			gossipsub: (!cfg.gossip_topics.is_empty())
+				.then(|| gossipsub_behaviour(key, &cfg.gossip_topics))
+				.transpose()?
+				.into(),
 		})
 	};
 
-	if is_ws_transport {
-		swarm = tokio_swarm
-			.with_websocket(noise::Config::new, yamux::Config::default)
-			.await?
+	#[cfg(target_arch = "wasm32")]
+	let mut swarm = {
+		// Browser dials peers over WebSocket and WebRTC only
+		_ = is_ws_transport;
+		SwarmBuilder::with_existing_identity(id_keys.clone())
+			.with_wasm_bindgen()
+			.with_other_transport(browser_transport)?
 			.with_relay_client(noise::Config::new, yamux::Config::default)?
 			.with_behaviour(behaviour)?
 			.with_swarm_config(|c| generate_config(c, cfg))
-			.build();
-	} else {
-		swarm = tokio_swarm
-			.with_tcp(
-				tcp::Config::default().port_reuse(false).nodelay(false),
-				noise::Config::new,
-				yamux::Config::default,
-			)?
-			.with_dns()?
+			.build()
+	};
+
+	#[cfg(not(target_arch = "wasm32"))]
+	let tokio_swarm = SwarmBuilder::with_existing_identity(id_keys.clone()).with_tokio();
+
+	#[cfg(not(target_arch = "wasm32"))]
+	let mut swarm = if is_ws_transport {
+		if cfg.proxy.is_some() {
+			warn!("Proxy is not supported by the WebSocket transport, peers are dialed directly");
+		}
+		tokio_swarm
+			.with_websocket(noise::Config::new, yamux::Config::default)
+			.await?
 			.with_relay_client(noise::Config::new, yamux::Config::default)?
 			.with_behaviour(behaviour)?
 			.with_swarm_config(|c| generate_config(c, cfg))
-			.build();
-	}
+			.build()
+	} else {
+		if !cfg.tcp_transport && !cfg.quic_transport {
+			return Err(eyre!(
+				"At least one of TCP or QUIC transports must be enabled"
+			));
+		}
+
+		// Disabled transports are neither used for listening nor for dialing
+		let transport = |key: &identity::Keypair| -> Result<_, noise::Error> {
+			// Proxied TCP dials take precedence, while listening is done by the TCP transport
+			let proxy_transport = match cfg.proxy.clone() {
+				Some(proxy) => OptionalTransport::some(ProxyTransport::new(proxy)),
+				None => OptionalTransport::none(),
+			};
+			let tcp_transport = match cfg.tcp_transport {
+				true => OptionalTransport::some(
+					proxy_transport
+						.or_transport(tcp::tokio::Transport::new(
+							tcp::Config::default().port_reuse(false).nodelay(false),
+						))
+						.upgrade(upgrade::Version::V1Lazy)
+						.authenticate(noise::Config::new(key)?)
+						.multiplex(yamux::Config::default()),
+				),
+				false => OptionalTransport::none(),
+			};
+			let quic_transport = match cfg.quic_transport {
+				true => {
+					OptionalTransport::some(quic::tokio::Transport::new(quic::Config::new(key)))
+				},
+				false => OptionalTransport::none(),
+			};
+			Ok(tcp_transport
+				.or_transport(quic_transport)
+				.map(|output, _| match output {
+					Either::Left((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+					Either::Right((peer_id, muxer)) => (peer_id, StreamMuxerBox::new(muxer)),
+				}))
+		};
+
+		let tokio_swarm = tokio_swarm.with_other_transport(transport)?;
+		match cfg.proxy {
+			// Host names are resolved by the proxy
+			Some(_) => tokio_swarm
+				.with_relay_client(noise::Config::new, yamux::Config::default)?
+				.with_behaviour(behaviour)?
+				.with_swarm_config(|c| generate_config(c, cfg))
+				.build(),
+			None => tokio_swarm
+				.with_dns()?
+				.with_relay_client(noise::Config::new, yamux::Config::default)?
+				.with_behaviour(behaviour)?
+				.with_swarm_config(|c| generate_config(c, cfg))
+				.build(),
+		}
+	};
 
 	info!("Local peerID: {}", swarm.local_peer_id());
 
@@ -193,7 +441,11 @@ async fn build_swarm(
 
 // Keypair function creates identity Keypair for a local node.
 // From such generated keypair it derives multihash identifier of the local peer.
-pub fn keypair(cfg: &LibP2PConfig) -> Result<(libp2p::identity::Keypair, String)> {
+// If secret key is not configured, generated keypair is persisted, so the local peer ID is kept between restarts.
+pub fn keypair(
+	cfg: &LibP2PConfig,
+	db: &impl Database,
+) -> Result<(libp2p::identity::Keypair, String)> {
 	let keypair = match cfg.secret_key.as_ref() {
 		// If seed is provided, generate secret key from seed
 		Some(SecretKey::Seed { seed }) => {
@@ -209,9 +461,97 @@ pub fn keypair(cfg: &LibP2PConfig) -> Result<(libp2p::identity::Keypair, String)
 			identity::Keypair::ed25519_from_bytes(decoded_key)
 				.wrap_err("error importing secret key")?
 		},
-		// If neither seed nor secret key provided, generate secret key from random seed
-		None => identity::Keypair::generate_ed25519(),
+		// If neither seed nor secret key provided, use stored or generate secret key from random seed
+		None => load_or_init_keypair(db)?,
 	};
 	let peer_id = PeerId::from(keypair.public()).to_string();
 	Ok((keypair, peer_id))
 }
+
+fn load_or_init_keypair(db: &impl Database) -> Result<identity::Keypair> {
+	if let Some(encoded) = db.get::<Vec<u8>>(Key::P2PKeypair)? {
+		return identity::Keypair::from_protobuf_encoding(&encoded)
+			.wrap_err("error decoding stored secret key");
+	}
+
+	let keypair = identity::Keypair::generate_ed25519();
+	let encoded = keypair
+		.to_protobuf_encoding()
+		.wrap_err("error encoding secret key")?;
+	db.put(Key::P2PKeypair, encoded)
+		.wrap_err("error storing secret key")?;
+	Ok(keypair)
+}
+
+/// Stores snapshot of the routing table peers, used to rejoin the DHT after restart.
+pub async fn store_peers(client: &Client, db: &impl Database) -> Result<usize> {
+	let peers = client
+		.get_routing_table_peers()
+		.await?
+		.into_iter()
+		.map(|(peer_id, addresses)| {
+			let addresses = addresses.iter().map(ToString::to_string).collect();
+			(peer_id.to_string(), addresses)
+		})
+		.collect::<Vec<(String, Vec<String>)>>();
+
+	let stored = peers.len();
+	db.put(Key::P2PPeers, peers)
+		.wrap_err("Failed to store routing table peers")?;
+	Ok(stored)
+}
+
+/// Adds peers from the stored routing table snapshot to the routing table.
+pub async fn restore_peers(client: &Client, db: &impl Database) -> Result<usize> {
+	let Some(peers) = db.get::<Vec<(String, Vec<String>)>>(Key::P2PPeers)? else {
+		return Ok(0);
+	};
+
+	let mut restored = 0;
+	for (peer_id, addresses) in peers {
+		let Ok(peer_id) = peer_id.parse::<PeerId>() else {
+			debug!("Skipping stored peer with invalid peer ID {peer_id}");
+			continue;
+		};
+		for address in addresses.iter().filter_map(|address| address.parse().ok()) {
+			client.add_address(peer_id, address).await?;
+		}
+		restored += 1;
+	}
+	Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::Multiaddr;
+	use test_case::test_case;
+
+	use super::{is_dialable, keypair};
+	use crate::{
+		data::mem_db::MemoryDB,
+		types::{LibP2PConfig, RuntimeConfig},
+	};
+
+	#[test_case("/ip4/192.168.1.10/tcp/37000" => true ; "IPv4")]
+	#[test_case("/ip4/0.0.0.0/tcp/37000" => false ; "Unspecified IPv4")]
+	#[test_case("/ip6/2001:db8::1/tcp/37000" => true ; "Global IPv6")]
+	#[test_case("/ip6/::1/udp/37000/quic-v1" => true ; "Loopback IPv6")]
+	#[test_case("/ip6/fe80::1/tcp/37000" => false ; "Link-local IPv6")]
+	#[test_case("/ip6/::/tcp/37000" => false ; "Unspecified IPv6")]
+	#[test_case("/dns/bootnode.example/tcp/37000" => true ; "DNS")]
+	fn test_is_dialable(address: &str) -> bool {
+		is_dialable(&address.parse::<Multiaddr>().unwrap())
+	}
+
+	#[test]
+	fn generated_keypair_is_reused() {
+		let db = MemoryDB::default();
+		let cfg = LibP2PConfig::from(&RuntimeConfig::default());
+		let (_, peer_id) = keypair(&cfg, &db).unwrap();
+		let (_, reloaded_peer_id) = keypair(&cfg, &db).unwrap();
+		assert_eq!(peer_id, reloaded_peer_id);
+
+		let (_, other_peer_id) = keypair(&cfg, &MemoryDB::default()).unwrap();
+		assert_ne!(peer_id, other_peer_id);
+	}
+}
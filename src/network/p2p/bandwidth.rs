@@ -0,0 +1,198 @@
+//! Bandwidth accounting of the application protocols, and upload caps of the DHT record puts.
+
+use libp2p::kad::Record;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+use tokio::time::Instant;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub const KADEMLIA: &str = "kad";
+pub const GOSSIPSUB: &str = "gossipsub";
+
+/// Size of the record key and value, in bytes.
+pub fn record_size(record: &Record) -> usize {
+	record.key.as_ref().len() + record.value.len()
+}
+
+/// Upload caps of the DHT record puts, in bytes
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UploadCap {
+	pub hourly: Option<u64>,
+	pub daily: Option<u64>,
+}
+
+/// Uploaded and downloaded bytes
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Traffic {
+	pub uploaded: u64,
+	pub downloaded: u64,
+}
+
+/// Bandwidth statistics, as reported by the diagnostics endpoint
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BandwidthStats {
+	/// Traffic since the client started, per protocol
+	pub protocols: BTreeMap<String, Traffic>,
+	/// Bytes uploaded by the DHT record puts in the current hourly window
+	pub hourly_upload: u64,
+	/// Bytes uploaded by the DHT record puts in the current daily window
+	pub daily_upload: u64,
+	/// Whether DHT record puts are throttled by the reached upload cap
+	pub throttled: bool,
+}
+
+/// Fixed window in which uploaded bytes are counted against the cap
+struct Window {
+	period: Duration,
+	cap: Option<u64>,
+	start: Instant,
+	bytes: u64,
+}
+
+impl Window {
+	fn new(period: Duration, cap: Option<u64>, now: Instant) -> Self {
+		Window {
+			period,
+			cap,
+			start: now,
+			bytes: 0,
+		}
+	}
+
+	fn refresh(&mut self, now: Instant) {
+		if now.duration_since(self.start) >= self.period {
+			self.start = now;
+			self.bytes = 0;
+		}
+	}
+
+	fn allows(&self, bytes: u64) -> bool {
+		self.cap.map_or(true, |cap| self.bytes + bytes <= cap)
+	}
+
+	fn is_reached(&self) -> bool {
+		self.cap.map_or(false, |cap| self.bytes >= cap)
+	}
+}
+
+pub struct Bandwidth {
+	replication_factor: u64,
+	protocols: BTreeMap<&'static str, Traffic>,
+	hourly: Window,
+	daily: Window,
+}
+
+impl Bandwidth {
+	pub fn new(cap: UploadCap, replication_factor: usize, now: Instant) -> Self {
+		Bandwidth {
+			replication_factor: replication_factor as u64,
+			protocols: BTreeMap::new(),
+			hourly: Window::new(HOUR, cap.hourly, now),
+			daily: Window::new(DAY, cap.daily, now),
+		}
+	}
+
+	pub fn uploaded(&mut self, protocol: &'static str, bytes: usize) {
+		self.protocols.entry(protocol).or_default().uploaded += bytes as u64;
+	}
+
+	pub fn downloaded(&mut self, protocol: &'static str, bytes: usize) {
+		self.protocols.entry(protocol).or_default().downloaded += bytes as u64;
+	}
+
+	/// Counts the record put as uploaded, if it fits into the upload caps.
+	/// Returns `false` if the record put would exceed the caps.
+	pub fn put_record(&mut self, record_size: usize, now: Instant) -> bool {
+		self.hourly.refresh(now);
+		self.daily.refresh(now);
+		let bytes = record_size as u64 * self.replication_factor;
+		if !self.hourly.allows(bytes) || !self.daily.allows(bytes) {
+			return false;
+		}
+		self.hourly.bytes += bytes;
+		self.daily.bytes += bytes;
+		self.uploaded(KADEMLIA, bytes as usize);
+		true
+	}
+
+	pub fn stats(&mut self, now: Instant) -> BandwidthStats {
+		self.hourly.refresh(now);
+		self.daily.refresh(now);
+		BandwidthStats {
+			protocols: self
+				.protocols
+				.iter()
+				.map(|(protocol, traffic)| (protocol.to_string(), *traffic))
+				.collect(),
+			hourly_upload: self.hourly.bytes,
+			daily_upload: self.daily.bytes,
+			throttled: self.hourly.is_reached() || self.daily.is_reached(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use tokio::time::Instant;
+
+	use super::{Bandwidth, Traffic, UploadCap, GOSSIPSUB, KADEMLIA};
+
+	#[test]
+	fn puts_are_throttled_until_window_elapses() {
+		let now = Instant::now();
+		let cap = UploadCap {
+			hourly: Some(1000),
+			daily: Some(1500),
+		};
+		let mut bandwidth = Bandwidth::new(cap, 2, now);
+
+		assert!(bandwidth.put_record(300, now));
+		assert!(!bandwidth.put_record(300, now));
+		assert!(bandwidth.put_record(200, now));
+		assert!(bandwidth.stats(now).throttled);
+
+		// Hourly window elapsed, but the daily cap is not exceeded yet
+		let now = now + Duration::from_secs(60 * 60);
+		assert!(!bandwidth.stats(now).throttled);
+		assert!(bandwidth.put_record(250, now));
+		assert!(!bandwidth.put_record(50, now));
+
+		let stats = bandwidth.stats(now);
+		assert_eq!(stats.hourly_upload, 500);
+		assert_eq!(stats.daily_upload, 1500);
+		assert!(stats.throttled);
+
+		let now = now + Duration::from_secs(24 * 60 * 60);
+		assert!(bandwidth.put_record(300, now));
+	}
+
+	#[test]
+	fn traffic_is_counted_per_protocol() {
+		let now = Instant::now();
+		let mut bandwidth = Bandwidth::new(UploadCap::default(), 20, now);
+		assert!(bandwidth.put_record(100, now));
+		bandwidth.downloaded(KADEMLIA, 80);
+		bandwidth.uploaded(GOSSIPSUB, 10);
+		bandwidth.downloaded(GOSSIPSUB, 30);
+
+		let stats = bandwidth.stats(now);
+		assert!(!stats.throttled);
+		assert_eq!(
+			stats.protocols[KADEMLIA],
+			Traffic {
+				uploaded: 2000,
+				downloaded: 80
+			}
+		);
+		assert_eq!(
+			stats.protocols[GOSSIPSUB],
+			Traffic {
+				uploaded: 10,
+				downloaded: 30
+			}
+		);
+	}
+}
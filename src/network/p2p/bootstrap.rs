@@ -0,0 +1,208 @@
+//! Bootstrap peers discovery, from the static configuration and DNS TXT records (dnsaddr).
+//!
+//! Peers discovered on the local network over mDNS are added to the routing table by the event loop.
+
+use color_eyre::{eyre::WrapErr, Result};
+use hickory_resolver::TokioAsyncResolver;
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::collections::HashSet;
+use tokio::{
+	sync::broadcast::{self, error::RecvError},
+	time::{interval_at, Instant},
+};
+use tracing::{debug, info, warn};
+
+use super::Client;
+use crate::{config_reload::ConfigUpdate, types::BootstrapConfig};
+
+/// Maximum depth of nested dnsaddr records
+const MAX_DNSADDR_DEPTH: usize = 4;
+
+/// Parses `dnsaddr=<multiaddr>` TXT record entry
+fn parse_dnsaddr_entry(entry: &str) -> Option<Multiaddr> {
+	entry.strip_prefix("dnsaddr=")?.parse().ok()
+}
+
+fn peer_id(address: &Multiaddr) -> Option<PeerId> {
+	match address.iter().last()? {
+		Protocol::P2p(peer_id) => Some(peer_id),
+		_ => None,
+	}
+}
+
+fn dnsaddr_domain(address: &Multiaddr) -> Option<String> {
+	match address.iter().next()? {
+		Protocol::Dnsaddr(domain) => Some(domain.to_string()),
+		_ => None,
+	}
+}
+
+/// Resolves bootstrap peers from the `_dnsaddr.<domain>` TXT records, following nested dnsaddr records.
+pub async fn resolve_dnsaddr(
+	resolver: &TokioAsyncResolver,
+	domain: &str,
+) -> Result<Vec<(PeerId, Multiaddr)>> {
+	let mut peers = vec![];
+	let mut domains = vec![(domain.to_string(), 0)];
+
+	while let Some((domain, depth)) = domains.pop() {
+		let records = resolver
+			.txt_lookup(format!("_dnsaddr.{domain}"))
+			.await
+			.wrap_err_with(|| format!("DNS TXT lookup for {domain} failed"))?;
+
+		for record in records.iter() {
+			let entry = record
+				.txt_data()
+				.iter()
+				.map(|data| String::from_utf8_lossy(data))
+				.collect::<String>();
+
+			let Some(address) = parse_dnsaddr_entry(&entry) else {
+				debug!("Skipping invalid dnsaddr entry {entry}");
+				continue;
+			};
+
+			match (dnsaddr_domain(&address), peer_id(&address)) {
+				(Some(nested), _) if depth < MAX_DNSADDR_DEPTH => domains.push((nested, depth + 1)),
+				(Some(nested), _) => debug!("Skipping dnsaddr {nested}, maximum depth reached"),
+				(None, Some(peer_id)) => peers.push((peer_id, address)),
+				(None, None) => debug!("Skipping dnsaddr entry {entry} without peer ID"),
+			}
+		}
+	}
+
+	Ok(peers)
+}
+
+/// Merges peers from all sources, removing duplicates while keeping the order of sources.
+fn merge(sources: Vec<Vec<(PeerId, Multiaddr)>>) -> Vec<(PeerId, Multiaddr)> {
+	let mut seen = HashSet::new();
+	sources
+		.into_iter()
+		.flatten()
+		.filter(|peer| seen.insert(peer.clone()))
+		.collect()
+}
+
+/// Returns merged list of bootstrap nodes, static peers and peers resolved via dnsaddr.
+/// Failed DNS lookups are logged, so the remaining sources can still be used.
+pub async fn bootstrap_peers(cfg: &BootstrapConfig) -> Vec<(PeerId, Multiaddr)> {
+	let mut resolved = vec![];
+	if !cfg.dnsaddrs.is_empty() {
+		match TokioAsyncResolver::tokio_from_system_conf() {
+			Ok(resolver) => {
+				for domain in &cfg.dnsaddrs {
+					match resolve_dnsaddr(&resolver, domain).await {
+						Ok(peers) => resolved.extend(peers),
+						Err(error) => warn!("Cannot resolve bootstrap peers: {error:#}"),
+					}
+				}
+			},
+			Err(error) => warn!("Cannot create DNS resolver: {error}"),
+		}
+	}
+
+	merge(vec![
+		cfg.bootstraps.clone(),
+		cfg.static_peers.clone(),
+		resolved,
+	])
+}
+
+/// Periodically re-bootstraps the DHT from all bootstrap sources, while the routing table is sparse.
+/// Bootstrap nodes changed on configuration reload are used for the immediate bootstrap, and for the next retries.
+pub async fn retry_bootstrap(
+	client: Client,
+	mut cfg: BootstrapConfig,
+	mut config_update_receiver: broadcast::Receiver<ConfigUpdate>,
+) {
+	let mut interval = interval_at(Instant::now() + cfg.retry_interval, cfg.retry_interval);
+	let mut updates_closed = false;
+	loop {
+		tokio::select! {
+			_ = interval.tick() => (),
+			update = config_update_receiver.recv(), if !updates_closed => {
+				match update {
+					Ok(ConfigUpdate::Bootstraps(bootstraps)) => {
+						cfg.bootstraps = bootstraps.iter().map(Into::into).collect();
+						info!("Bootstrap nodes changed, bootstrapping...");
+						let peers = bootstrap_peers(&cfg).await;
+						if let Err(error) = client.bootstrap_on_startup(peers).await {
+							warn!("Bootstrap with the new nodes failed: {error:#}");
+						}
+					},
+					Ok(_) | Err(RecvError::Lagged(_)) => (),
+					// Configuration is not reloaded anymore, so only retries are left
+					Err(RecvError::Closed) => updates_closed = true,
+				}
+				continue;
+			},
+		}
+
+		let routing_table_peers = match client.get_routing_table_peers().await {
+			Ok(peers) => peers.len(),
+			Err(error) => {
+				warn!("Cannot get routing table peers: {error:#}");
+				continue;
+			},
+		};
+
+		if routing_table_peers >= cfg.min_peers {
+			continue;
+		}
+
+		info!("Routing table has {routing_table_peers} peers, retrying bootstrap...");
+		let peers = bootstrap_peers(&cfg).await;
+		if let Err(error) = client.bootstrap_on_startup(peers).await {
+			warn!("Bootstrap retry failed: {error:#}");
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::{Multiaddr, PeerId};
+	use test_case::test_case;
+
+	use super::{dnsaddr_domain, merge, parse_dnsaddr_entry, peer_id};
+
+	const PEER_ID: &str = "12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz";
+
+	#[test_case("dnsaddr=/ip4/127.0.0.1/tcp/37000/p2p/12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz" => true ; "peer address")]
+	#[test_case("dnsaddr=/dnsaddr/bootstrap.avail.so" => true ; "nested dnsaddr")]
+	#[test_case("dnsaddr=invalid" => false ; "invalid address")]
+	#[test_case("v=spf1 -all" => false ; "other record")]
+	fn test_parse_dnsaddr_entry(entry: &str) -> bool {
+		parse_dnsaddr_entry(entry).is_some()
+	}
+
+	#[test]
+	fn peer_id_and_domain() {
+		let address: Multiaddr = format!("/ip4/127.0.0.1/tcp/37000/p2p/{PEER_ID}")
+			.parse()
+			.unwrap();
+		assert_eq!(peer_id(&address), Some(PEER_ID.parse().unwrap()));
+		assert_eq!(dnsaddr_domain(&address), None);
+
+		let address: Multiaddr = "/dnsaddr/bootstrap.avail.so".parse().unwrap();
+		assert_eq!(peer_id(&address), None);
+		assert_eq!(
+			dnsaddr_domain(&address),
+			Some("bootstrap.avail.so".to_string())
+		);
+	}
+
+	#[test]
+	fn merge_removes_duplicates() {
+		let peer_id: PeerId = PEER_ID.parse().unwrap();
+		let first: Multiaddr = "/ip4/127.0.0.1/tcp/37000".parse().unwrap();
+		let second: Multiaddr = "/ip4/127.0.0.2/tcp/37000".parse().unwrap();
+
+		let merged = merge(vec![
+			vec![(peer_id, first.clone())],
+			vec![(peer_id, second.clone()), (peer_id, first.clone())],
+		]);
+		assert_eq!(merged, vec![(peer_id, first), (peer_id, second)]);
+	}
+}
@@ -1,4 +1,10 @@
-use super::{Command, CommandSender, EventLoopEntries, QueryChannel, SendableCommand};
+use super::{
+	bandwidth::{self, BandwidthStats},
+	peers::{Peers, RoutingTableStats},
+	reputation::{PeerEvent, PeerScore},
+	Command, CommandSender, EventLoopEntries, QueryChannel, Reachability, SendableCommand,
+};
+use crate::{network::tuning::BatchSize, retry::RetryPolicy};
 use color_eyre::{
 	eyre::{eyre, WrapErr},
 	Report, Result,
@@ -10,7 +16,9 @@ use kate_recovery::{
 	matrix::{Dimensions, Position, RowIndex},
 };
 use libp2p::{
+	gossipsub::{self, IdentTopic},
 	kad::{PeerRecord, Quorum, Record, RecordKey},
+	multiaddr::Protocol,
 	swarm::dial_opts::DialOpts,
 	Multiaddr, PeerId,
 };
@@ -20,15 +28,23 @@ use std::{
 	time::{Duration, Instant},
 };
 use tokio::sync::oneshot;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
+
+/// Peers which served the cells fetched from the DHT
+#[derive(Debug, Default)]
+pub struct CellPeers(Vec<(Position, PeerId)>);
 
 #[derive(Clone)]
 pub struct Client {
 	command_sender: CommandSender,
 	/// Number of cells to fetch in parallel
-	dht_parallelization_limit: usize,
+	dht_parallelization: BatchSize,
 	/// Cell time to live in DHT (in seconds)
 	ttl: u64,
+	/// Number of peers which need to store the record for PUT to succeed
+	put_quorum: Quorum,
+	/// Retry policy of the DHT record queries
+	retry_policy: RetryPolicy,
 }
 
 struct DHTCell(Cell);
@@ -210,6 +226,25 @@ struct PutKadRecord {
 // `active_blocks` is a list of cell counts for each block we monitor for PUT op. results
 impl Command for PutKadRecord {
 	fn run(&mut self, mut entries: EventLoopEntries) -> Result<()> {
+		// Records which exceed the upload cap are not put, nor counted in the block stats
+		let now = tokio::time::Instant::now();
+		let total = self.records.len();
+		self.records.retain(|record| {
+			entries
+				.bandwidth
+				.put_record(bandwidth::record_size(record), now)
+		});
+		if self.records.len() < total {
+			debug!(
+				"Upload cap reached, {} of {total} records of block {} are not put",
+				total - self.records.len(),
+				self.block_num
+			);
+		}
+		if self.records.is_empty() {
+			return Ok(());
+		}
+
 		entries
 			.active_blocks
 			.entry(self.block_num)
@@ -264,6 +299,206 @@ impl Command for CountConnectedPeers {
 	}
 }
 
+struct GetReachability {
+	response_sender: Option<oneshot::Sender<Result<Reachability>>>,
+}
+
+impl Command for GetReachability {
+	fn run(&mut self, entries: EventLoopEntries) -> Result<()> {
+		let relayed = entries.swarm.listeners().any(|address| {
+			address
+				.iter()
+				.any(|protocol| protocol == Protocol::P2pCircuit)
+		});
+		let nat_status = entries.swarm.behaviour().auto_nat.nat_status();
+
+		// send result back
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Ok(Reachability::new(nat_status, relayed)))
+			.expect("GetReachability receiver dropped");
+		Ok(())
+	}
+
+	fn abort(&mut self, error: Report) {
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Err(error))
+			.expect("GetReachability receiver dropped");
+	}
+}
+
+struct GetRoutingTablePeers {
+	response_sender: Option<oneshot::Sender<Result<Vec<(PeerId, Vec<Multiaddr>)>>>>,
+}
+
+impl Command for GetRoutingTablePeers {
+	fn run(&mut self, mut entries: EventLoopEntries) -> Result<()> {
+		let mut peers = vec![];
+		for bucket in entries.behavior_mut().kademlia.kbuckets() {
+			for entry in bucket.iter() {
+				let addresses = entry.node.value.iter().cloned().collect();
+				peers.push((*entry.node.key.preimage(), addresses));
+			}
+		}
+
+		// send result back
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Ok(peers))
+			.expect("GetRoutingTablePeers receiver dropped");
+		Ok(())
+	}
+
+	fn abort(&mut self, error: Report) {
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Err(error))
+			.expect("GetRoutingTablePeers receiver dropped");
+	}
+}
+
+struct ReportPeer {
+	peer_id: PeerId,
+	event: PeerEvent,
+}
+
+impl Command for ReportPeer {
+	fn run(&mut self, mut entries: EventLoopEntries) -> Result<()> {
+		entries.report_peer(self.peer_id, self.event);
+		Ok(())
+	}
+
+	fn abort(&mut self, _: Report) {}
+}
+
+struct ReportGossipValidation {
+	message_id: gossipsub::MessageId,
+	propagation_source: PeerId,
+	acceptance: Option<gossipsub::MessageAcceptance>,
+}
+
+impl Command for ReportGossipValidation {
+	fn run(&mut self, mut entries: EventLoopEntries) -> Result<()> {
+		let gossipsub = entries
+			.behavior_mut()
+			.gossipsub
+			.as_mut()
+			.ok_or_else(|| eyre!("Gossipsub is disabled"))?;
+		if let Some(acceptance) = self.acceptance.take() {
+			_ = gossipsub.report_message_validation_result(
+				&self.message_id,
+				&self.propagation_source,
+				acceptance,
+			);
+		}
+		Ok(())
+	}
+
+	fn abort(&mut self, _: Report) {}
+}
+
+struct GetPeerScores {
+	response_sender: Option<oneshot::Sender<Result<Vec<PeerScore>>>>,
+}
+
+impl Command for GetPeerScores {
+	fn run(&mut self, entries: EventLoopEntries) -> Result<()> {
+		let scores = entries.peer_scores.table(tokio::time::Instant::now());
+
+		// send result back
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Ok(scores))
+			.expect("GetPeerScores receiver dropped");
+		Ok(())
+	}
+
+	fn abort(&mut self, error: Report) {
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Err(error))
+			.expect("GetPeerScores receiver dropped");
+	}
+}
+
+struct GetPeers {
+	response_sender: Option<oneshot::Sender<Result<Peers>>>,
+}
+
+impl Command for GetPeers {
+	fn run(&mut self, mut entries: EventLoopEntries) -> Result<()> {
+		let peers = entries.connected_peers.table(entries.peer_scores);
+		let mut routing_table = RoutingTableStats::default();
+		for bucket in entries.behavior_mut().kademlia.kbuckets() {
+			let count = bucket.num_entries();
+			routing_table.peers += count;
+			routing_table.buckets += usize::from(count > 0);
+		}
+
+		// send result back
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Ok(Peers {
+				peers,
+				routing_table,
+			}))
+			.expect("GetPeers receiver dropped");
+		Ok(())
+	}
+
+	fn abort(&mut self, error: Report) {
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Err(error))
+			.expect("GetPeers receiver dropped");
+	}
+}
+
+struct GetBandwidth {
+	response_sender: Option<oneshot::Sender<Result<BandwidthStats>>>,
+}
+
+impl Command for GetBandwidth {
+	fn run(&mut self, mut entries: EventLoopEntries) -> Result<()> {
+		let stats = entries.bandwidth.stats(tokio::time::Instant::now());
+
+		// send result back
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Ok(stats))
+			.expect("GetBandwidth receiver dropped");
+		Ok(())
+	}
+
+	fn abort(&mut self, error: Report) {
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Err(error))
+			.expect("GetBandwidth receiver dropped");
+	}
+}
+
 struct ListConnectedPeers {
 	response_sender: Option<oneshot::Sender<Result<Vec<String>>>>,
 }
@@ -485,12 +720,58 @@ impl Command for AddAutonatServer {
 	}
 }
 
+struct Publish {
+	topic: String,
+	data: Vec<u8>,
+	response_sender: Option<oneshot::Sender<Result<()>>>,
+}
+
+impl Command for Publish {
+	fn run(&mut self, mut entries: EventLoopEntries) -> Result<()> {
+		let gossipsub = entries
+			.behavior_mut()
+			.gossipsub
+			.as_mut()
+			.ok_or_else(|| eyre!("Gossipsub is disabled"))?;
+		gossipsub.publish(IdentTopic::new(&self.topic), self.data.clone())?;
+		entries
+			.bandwidth
+			.uploaded(bandwidth::GOSSIPSUB, self.data.len());
+
+		// send result back
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Ok(()))
+			.expect("Publish receiver dropped");
+		Ok(())
+	}
+
+	fn abort(&mut self, error: Report) {
+		// TODO: consider what to do if this results with None
+		self.response_sender
+			.take()
+			.unwrap()
+			.send(Err(error))
+			.expect("Publish receiver dropped");
+	}
+}
+
 impl Client {
-	pub fn new(sender: CommandSender, dht_parallelization_limit: usize, ttl: u64) -> Self {
+	pub fn new(
+		sender: CommandSender,
+		dht_parallelization: BatchSize,
+		ttl: u64,
+		put_quorum: Quorum,
+		retry_policy: RetryPolicy,
+	) -> Self {
 		Self {
 			command_sender: sender,
-			dht_parallelization_limit,
+			dht_parallelization,
 			ttl,
+			put_quorum,
+			retry_policy,
 		}
 	}
 
@@ -556,13 +837,20 @@ impl Client {
 	}
 
 	pub async fn bootstrap_on_startup(&self, nodes: Vec<(PeerId, Multiaddr)>) -> Result<()> {
-		for (peer, addr) in nodes {
-			self.dial_peer(peer, addr.clone())
-				.await
-				.wrap_err("Dialing Bootstrap peer failed.")?;
-			self.add_address(peer, addr.clone()).await?;
+		let mut dialed = 0;
+		for (peer, addr) in &nodes {
+			// Unreachable peer from one of the bootstrap sources should not prevent bootstrap
+			if let Err(error) = self.dial_peer(*peer, addr.clone()).await {
+				warn!("Dialing bootstrap peer {peer} failed: {error:#}");
+				continue;
+			}
+			dialed += 1;
+			self.add_address(*peer, addr.clone()).await?;
 
-			self.add_autonat_server(peer, addr).await?;
+			self.add_autonat_server(*peer, addr.clone()).await?;
+		}
+		if dialed == 0 && !nodes.is_empty() {
+			return Err(eyre!("Dialing bootstrap peers failed"));
 		}
 		self.bootstrap().await
 	}
@@ -601,6 +889,91 @@ impl Client {
 		.await
 	}
 
+	pub async fn get_reachability(&self) -> Result<Reachability> {
+		self.execute_sync(|response_sender| {
+			Box::new(GetReachability {
+				response_sender: Some(response_sender),
+			})
+		})
+		.await
+	}
+
+	/// Returns peers and their addresses from the Kademlia routing table
+	pub async fn get_routing_table_peers(&self) -> Result<Vec<(PeerId, Vec<Multiaddr>)>> {
+		self.execute_sync(|response_sender| {
+			Box::new(GetRoutingTablePeers {
+				response_sender: Some(response_sender),
+			})
+		})
+		.await
+	}
+
+	/// Reports peer which published gossip message which cannot be decoded or verified
+	pub fn report_invalid_message(&self, peer_id: PeerId) {
+		self.report_peer(Some(peer_id), PeerEvent::InvalidRecord);
+	}
+
+	/// Reports the validation result of the received gossip message, so valid messages are propagated
+	/// and invalid messages are penalized by gossipsub.
+	pub fn report_gossip_validation(
+		&self,
+		message_id: gossipsub::MessageId,
+		propagation_source: PeerId,
+		acceptance: gossipsub::MessageAcceptance,
+	) {
+		if let Err(error) = self.command_sender.send(Box::new(ReportGossipValidation {
+			message_id,
+			propagation_source,
+			acceptance: Some(acceptance),
+		})) {
+			debug!("Cannot report gossip message validation: {error}");
+		}
+	}
+
+	/// Reports the outcome of the interaction with the peer to the peer reputation
+	fn report_peer(&self, peer_id: Option<PeerId>, event: PeerEvent) {
+		// Records fetched from the local store have no peer
+		let Some(peer_id) = peer_id else {
+			return;
+		};
+		if let Err(error) = self
+			.command_sender
+			.send(Box::new(ReportPeer { peer_id, event }))
+		{
+			debug!("Cannot report peer {peer_id}: {error}");
+		}
+	}
+
+	/// Returns peer scores, ordered from the lowest score
+	pub async fn get_peer_scores(&self) -> Result<Vec<PeerScore>> {
+		self.execute_sync(|response_sender| {
+			Box::new(GetPeerScores {
+				response_sender: Some(response_sender),
+			})
+		})
+		.await
+	}
+
+	/// Returns connected peers, ordered by the latency, and the routing table statistics
+	pub async fn get_peers(&self) -> Result<Peers> {
+		self.execute_sync(|response_sender| {
+			Box::new(GetPeers {
+				response_sender: Some(response_sender),
+			})
+		})
+		.await
+	}
+
+	/// Returns bytes uploaded and downloaded per protocol, and the DHT upload within the cap windows
+	pub async fn get_bandwidth(&self) -> Result<BandwidthStats> {
+		self.execute_sync(|response_sender| {
+			Box::new(GetBandwidth {
+				response_sender: Some(response_sender),
+			})
+		})
+		.await
+	}
+
 	pub async fn list_connected_peers(&self) -> Result<Vec<String>> {
 		self.execute_sync(|response_sender| {
 			Box::new(ListConnectedPeers {
@@ -610,6 +983,18 @@ impl Client {
 		.await
 	}
 
+	/// Publishes message on the gossipsub topic, fails if gossipsub is disabled or no peers are subscribed to the topic.
+	pub async fn publish(&self, topic: &str, data: Vec<u8>) -> Result<()> {
+		self.execute_sync(|response_sender| {
+			Box::new(Publish {
+				topic: topic.to_string(),
+				data,
+				response_sender: Some(response_sender),
+			})
+		})
+		.await
+	}
+
 	async fn get_multiaddress(&self) -> Result<Vec<Multiaddr>> {
 		self.execute_sync(|response_sender| {
 			Box::new(GetMultiaddress {
@@ -650,13 +1035,21 @@ impl Client {
 
 	// Since callers ignores DHT errors, debug logs are used to observe DHT behavior.
 	// Return type assumes that cell is not found in case when error is present.
-	async fn fetch_cell_from_dht(&self, block_number: u32, position: Position) -> Option<Cell> {
+	async fn fetch_cell_from_dht(
+		&self,
+		block_number: u32,
+		position: Position,
+	) -> Option<(Cell, Option<PeerId>)> {
 		let reference = position.reference(block_number);
 		let record_key = RecordKey::from(reference.as_bytes().to_vec());
 
 		trace!("Getting DHT record for reference {}", reference);
 
-		match self.get_kad_record(record_key).await {
+		match self
+			.retry_policy
+			.retry("dht_get", || self.get_kad_record(record_key.clone()))
+			.await
+		{
 			Ok(peer_record) => {
 				trace!("Fetched cell {reference} from the DHT");
 
@@ -665,10 +1058,12 @@ impl Client {
 
 				let Ok(content) = try_content else {
 					debug!("Cannot convert cell {reference} into 80 bytes");
+					self.report_peer(peer_record.peer, PeerEvent::InvalidRecord);
 					return None;
 				};
 
-				Some(Cell { position, content })
+				// peer is credited once the cell proof is verified
+				Some((Cell { position, content }, peer_record.peer))
 			},
 			Err(error) => {
 				trace!("Cell {reference} not found in the DHT: {error}");
@@ -688,7 +1083,11 @@ impl Client {
 
 		trace!("Getting DHT record for reference {}", reference);
 
-		match self.get_kad_record(record_key).await {
+		match self
+			.retry_policy
+			.retry("dht_get", || self.get_kad_record(record_key.clone()))
+			.await
+		{
 			Ok(peer_record) => Some((row_index.0, peer_record.record.value)),
 			Err(error) => {
 				debug!("Row {reference} not found in the DHT: {error}");
@@ -698,7 +1097,8 @@ impl Client {
 	}
 
 	/// Fetches cells from DHT.
-	/// Returns fetched cells and unfetched positions (so we can try RPC fetch).
+	/// Returns fetched cells, unfetched positions (so we can try RPC fetch)
+	/// and peers which served the cells (so they can be reported once cells are verified).
 	///
 	/// # Arguments
 	///
@@ -708,13 +1108,19 @@ impl Client {
 		&self,
 		block_number: u32,
 		positions: &[Position],
-	) -> (Vec<Cell>, Vec<Position>) {
-		let mut cells = Vec::<Option<Cell>>::with_capacity(positions.len());
+	) -> (Vec<Cell>, Vec<Position>, CellPeers) {
+		let mut cells = Vec::<Option<(Cell, Option<PeerId>)>>::with_capacity(positions.len());
 
-		for positions in positions.chunks(self.dht_parallelization_limit) {
+		let mut remaining = positions;
+		while !remaining.is_empty() {
+			let (batch, rest) = self.next_batch(remaining);
+			let begin = Instant::now();
 			let fetch = |&position| self.fetch_cell_from_dht(block_number, position);
-			let results = join_all(positions.iter().map(fetch)).await;
+			let results = join_all(batch.iter().map(fetch)).await;
 			cells.extend(results.into_iter().collect::<Vec<_>>());
+			// records which are not found are not considered failed, only latency is tuned
+			self.dht_parallelization.record(begin.elapsed(), false);
+			remaining = rest;
 		}
 
 		let unfetched = cells
@@ -724,9 +1130,25 @@ impl Client {
 			.map(|(_, &position)| position)
 			.collect::<Vec<_>>();
 
-		let fetched = cells.into_iter().flatten().collect();
+		let (fetched, peers): (Vec<_>, Vec<_>) = cells.into_iter().flatten().unzip();
+		let peers = fetched
+			.iter()
+			.zip(peers)
+			.filter_map(|(cell, peer)| Some((cell.position, peer?)))
+			.collect();
 
-		(fetched, unfetched)
+		(fetched, unfetched, CellPeers(peers))
+	}
+
+	/// Credits peers which served the verified cells, and reports peers which served cells with invalid proofs.
+	pub fn report_verified_cells(&self, peers: CellPeers, verified: &[Position]) {
+		for (position, peer_id) in peers.0 {
+			let event = match verified.contains(&position) {
+				true => PeerEvent::RecordServed,
+				false => PeerEvent::InvalidRecord,
+			};
+			self.report_peer(Some(peer_id), event);
+		}
 	}
 
 	/// Fetches rows from DHT.
@@ -743,23 +1165,34 @@ impl Client {
 		row_indexes: &[u32],
 	) -> Vec<Option<Vec<u8>>> {
 		let mut rows = vec![None; dimensions.extended_rows() as usize];
-		for row_indexes in row_indexes.chunks(self.dht_parallelization_limit) {
+		let mut remaining = row_indexes;
+		while !remaining.is_empty() {
+			let (batch, rest) = self.next_batch(remaining);
+			let begin = Instant::now();
 			let fetch = |row| self.fetch_row_from_dht(block_number, row);
-			let fetched_rows = join_all(row_indexes.iter().cloned().map(fetch)).await;
+			let fetched_rows = join_all(batch.iter().cloned().map(fetch)).await;
 			for (row_index, row) in fetched_rows.into_iter().flatten() {
 				rows[row_index as usize] = Some(row);
 			}
+			self.dht_parallelization.record(begin.elapsed(), false);
+			remaining = rest;
 		}
 		rows
 	}
 
+	/// Splits the next batch of the parallel DHT queries, sized by the current parallelization.
+	fn next_batch<'a, T>(&self, remaining: &'a [T]) -> (&'a [T], &'a [T]) {
+		let size = self.dht_parallelization.get().min(remaining.len());
+		remaining.split_at(size)
+	}
+
 	async fn insert_into_dht(&self, records: Vec<(String, Record)>, block_num: u32) -> Result<()> {
 		if records.is_empty() {
 			return Err(eyre!("Cant send empty record list."));
 		}
 		self.put_kad_record(
 			records.into_iter().map(|e| e.1).collect(),
-			Quorum::One,
+			self.put_quorum,
 			block_num,
 		)
 		.await
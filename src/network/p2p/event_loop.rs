@@ -1,42 +1,63 @@
 use color_eyre::{eyre::eyre, Result};
+use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
 use futures::StreamExt;
 use libp2p::{
 	autonat::{self, NatStatus},
-	dcutr,
+	dcutr, gossipsub,
 	identify::{self, Info},
 	identity::Keypair,
 	kad::{
-		self, BootstrapOk, GetRecordOk, InboundRequest, QueryId, QueryResult, QueryStats, RecordKey,
+		self, store::RecordStore, BootstrapOk, GetRecordOk, InboundRequest, QueryId, QueryResult,
+		QueryStats, Record, RecordKey,
 	},
-	mdns,
 	multiaddr::Protocol,
 	ping,
 	swarm::{
 		dial_opts::{DialOpts, PeerCondition},
 		ConnectionError, SwarmEvent,
 	},
-	upnp, Multiaddr, PeerId, Swarm,
+	Multiaddr, PeerId, Swarm,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use libp2p::{mdns, upnp};
 use rand::seq::SliceRandom;
 use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 use tokio::{
-	sync::oneshot,
+	sync::{
+		broadcast,
+		mpsc::{self, UnboundedReceiver, UnboundedSender},
+		oneshot, Mutex,
+	},
 	time::{interval_at, Instant, Interval},
 };
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
+	consts::BLOCK_TIME_SECS,
 	network::p2p::kad_mem_store::MemoryStore,
+	proof::{self, Lane},
 	shutdown::Controller,
 	telemetry::{MetricCounter, MetricValue, Metrics},
 	types::{AgentVersion, IdentifyConfig, KademliaMode, LibP2PConfig, TimeToLive},
 };
 
 use super::{
-	build_swarm, client::BlockStat, Behaviour, BehaviourEvent, CommandReceiver, EventLoopEntries,
-	QueryChannel, SendableCommand,
+	ban_peer,
+	bandwidth::{self, Bandwidth},
+	build_swarm,
+	client::BlockStat,
+	is_dialable,
+	peers::ConnectedPeers,
+	rate_limit::RateLimiter,
+	record_validator::{self, BlockCommitments, Check, IncomingRecord, RecordValidator, Rejection},
+	reputation::{PeerEvent, PeerScores},
+	Behaviour, BehaviourEvent, CommandReceiver, EventLoopEntries, GossipMessage, QueryChannel,
+	SendableCommand,
 };
 
+/// Interval in which expired peer bans are lifted, and expired gossip rate limit windows are removed
+const BAN_EXPIRY_INTERVAL: Duration = Duration::from_secs(60);
+
 // RelayState keeps track of all things relay related
 struct RelayState {
 	// id of the selected Relay that needs to be connected
@@ -93,17 +114,40 @@ pub struct EventLoop {
 	bootstrap: BootstrapState,
 	/// Blocks we monitor for PUT success rate
 	active_blocks: HashMap<u32, BlockStat>,
+	peer_scores: PeerScores,
+	connected_peers: ConnectedPeers,
+	bandwidth: Bandwidth,
+	ban_expiry_timer: Interval,
+	/// Subscribers of the received gossip messages
+	gossip_sender: broadcast::Sender<GossipMessage>,
+	gossip_rate_limiter: RateLimiter,
+	/// Validator of the records put by the remote peers, if the record validation is enabled
+	record_validator: Option<RecordValidator>,
+	public_parameters: Arc<PublicParameters>,
+	/// Records validated off the event loop
+	validated_records: (
+		UnboundedSender<(IncomingRecord, std::result::Result<(), Rejection>)>,
+		UnboundedReceiver<(IncomingRecord, std::result::Result<(), Rejection>)>,
+	),
 	shutdown: Controller<String>,
 
 	event_loop_config: EventLoopConfig,
 }
 
 #[derive(PartialEq, Debug)]
-enum DHTKey {
+pub(super) enum DHTKey {
 	Cell(u32, u32, u32),
 	Row(u32, u32),
 }
 
+impl DHTKey {
+	pub(super) fn block_number(&self) -> u32 {
+		match *self {
+			DHTKey::Cell(block_number, _, _) | DHTKey::Row(block_number, _) => block_number,
+		}
+	}
+}
+
 impl TryFrom<RecordKey> for DHTKey {
 	type Error = color_eyre::Report;
 
@@ -127,15 +171,26 @@ impl EventLoop {
 		id_keys: &Keypair,
 		is_fat_client: bool,
 		is_ws_transport: bool,
+		gossip_sender: broadcast::Sender<GossipMessage>,
+		public_parameters: Arc<PublicParameters>,
 		shutdown: Controller<String>,
 	) -> Self {
 		let bootstrap_interval = cfg.bootstrap_interval;
+		let record_validator = cfg.kademlia.record_validation.then(|| {
+			let max_blocks = cfg.kademlia.kad_record_ttl.as_secs() / BLOCK_TIME_SECS;
+			RecordValidator::new(max_blocks as usize)
+		});
 		let peer_id = id_keys.public().to_peer_id();
 		let store = MemoryStore::with_config(peer_id, (&cfg).into());
 
-		let swarm = build_swarm(&cfg, id_keys, store, is_ws_transport)
+		let mut swarm = build_swarm(&cfg, id_keys, store, is_ws_transport)
 			.await
 			.expect("Unable to build swarm.");
+		// Configured external addresses are advertised without the AutoNAT confirmation
+		for address in &cfg.external_addresses {
+			info!("Advertising configured external address {address}");
+			swarm.add_external_address(address.clone());
+		}
 
 		Self {
 			swarm,
@@ -152,6 +207,22 @@ impl EventLoop {
 				timer: interval_at(Instant::now() + bootstrap_interval, bootstrap_interval),
 			},
 			active_blocks: Default::default(),
+			peer_scores: PeerScores::new(cfg.reputation),
+			connected_peers: Default::default(),
+			bandwidth: Bandwidth::new(
+				cfg.kademlia.upload_cap,
+				cfg.kademlia.record_replication_factor.get(),
+				Instant::now(),
+			),
+			ban_expiry_timer: interval_at(
+				Instant::now() + BAN_EXPIRY_INTERVAL,
+				BAN_EXPIRY_INTERVAL,
+			),
+			gossip_sender,
+			gossip_rate_limiter: RateLimiter::new(cfg.gossip_rate_limit),
+			record_validator,
+			public_parameters,
+			validated_records: mpsc::unbounded_channel(),
 			shutdown,
 			event_loop_config: EventLoopConfig {
 				identity_data: cfg.identify,
@@ -161,12 +232,26 @@ impl EventLoop {
 		}
 	}
 
-	pub async fn run(mut self, metrics: Arc<impl Metrics>, mut command_receiver: CommandReceiver) {
+	/// Starts listening on the given address.
+	pub fn listen_on(&mut self, addr: Multiaddr) -> Result<()> {
+		_ = self.swarm.listen_on(addr)?;
+		Ok(())
+	}
+
+	/// Runs the event loop, until the shutdown is triggered or the command channel is closed.
+	/// Command and commitments receivers are shared, so the event loop can be restarted with the same channels after a failure.
+	pub async fn run(
+		mut self,
+		metrics: Arc<impl Metrics>,
+		command_receiver: Arc<Mutex<CommandReceiver>>,
+		commitments_receiver: Arc<Mutex<UnboundedReceiver<BlockCommitments>>>,
+	) {
 		// shutdown will wait as long as this token is not dropped
-		let _delay_token = self
-			.shutdown
-			.delay_token()
-			.expect("There should not be any shutdowns at the begging of the P2P Event Loop");
+		let Ok(_delay_token) = self.shutdown.delay_token() else {
+			return;
+		};
+		let mut command_receiver = command_receiver.lock().await;
+		let mut commitments_receiver = commitments_receiver.lock().await;
 
 		loop {
 			tokio::select! {
@@ -179,7 +264,10 @@ impl EventLoop {
 						break;
 					},
 				},
+				Some(block) = commitments_receiver.recv() => self.handle_block_commitments(block),
+				Some((record, result)) = self.validated_records.1.recv() => self.handle_validated_record(record, result, metrics.clone()).await,
 				_ = self.bootstrap.timer.tick() => self.handle_periodic_bootstraps(),
+				_ = self.ban_expiry_timer.tick() => self.handle_expired_bans(),
 				// if the shutdown was triggered,
 				// break the loop immediately, proceed to the cleanup phase
 				_ = self.shutdown.triggered_shutdown() => {
@@ -232,20 +320,11 @@ impl EventLoop {
 						},
 						InboundRequest::PutRecord { source, record, .. } => {
 							metrics.count(MetricCounter::IncomingPutRecord).await;
-							match record {
-								Some(mut record) => {
-									let ttl = &self.event_loop_config.kad_record_ttl;
-
-									// Set TTL for all incoming records
-									// TTL will be set to a lower value between the local TTL and incoming record TTL
-									record.expires = record.expires.min(ttl.expires());
-									_ = self.swarm.behaviour_mut().kademlia.store_mut().put(record);
-								},
-								None => {
-									debug!("Received empty cell record from: {source:?}");
-									return;
-								},
-							}
+							let Some(record) = record else {
+								debug!("Received empty cell record from: {source:?}");
+								return;
+							};
+							self.handle_incoming_record(source, record, metrics).await;
 						},
 						_ => {},
 					},
@@ -258,6 +337,10 @@ impl EventLoop {
 					} => match result {
 						QueryResult::GetRecord(result) => match result {
 							Ok(GetRecordOk::FoundRecord(record)) => {
+								self.bandwidth.downloaded(
+									bandwidth::KADEMLIA,
+									bandwidth::record_size(&record.record),
+								);
 								if let Some(QueryChannel::GetRecord(ch)) =
 									self.pending_kad_queries.remove(&id)
 								{
@@ -338,6 +421,7 @@ impl EventLoop {
 					trace!(
 						"Identity Received from: {peer_id:?} on listen address: {listen_addrs:?}"
 					);
+					self.connected_peers.identified(&peer_id, &agent_version);
 					let incoming_peer_agent_version = match AgentVersion::from_str(&agent_version) {
 						Ok(agent) => agent,
 						Err(e) => {
@@ -351,7 +435,8 @@ impl EventLoop {
 							== KademliaMode::Server.to_string()
 						{
 							trace!("Adding peer {peer_id} to routing table.");
-							for addr in listen_addrs {
+							// Link-local addresses are advertised by peers listening on the unspecified IPv6 address
+							for addr in listen_addrs.into_iter().filter(is_dialable) {
 								self.swarm
 									.behaviour_mut()
 									.kademlia
@@ -375,6 +460,7 @@ impl EventLoop {
 					trace!("Identify Error event. PeerId: {peer_id:?}. Error: {error:?}");
 				},
 			},
+			#[cfg(not(target_arch = "wasm32"))]
 			SwarmEvent::Behaviour(BehaviourEvent::Mdns(event)) => match event {
 				mdns::Event::Discovered(addrs_list) => {
 					for (peer_id, multiaddr) in addrs_list {
@@ -393,8 +479,8 @@ impl EventLoop {
 							.swarm
 							.behaviour_mut()
 							.mdns
-							.discovered_nodes()
-							.any(|&p| p == peer_id)
+							.as_ref()
+							.is_some_and(|mdns| mdns.discovered_nodes().any(|&p| p == peer_id))
 						{
 							self.swarm
 								.behaviour_mut()
@@ -437,19 +523,91 @@ impl EventLoop {
 					trace!("Hole punching failed with: {remote_peer_id:#?}. Error: {err:#?}")
 				},
 			},
-			SwarmEvent::Behaviour(BehaviourEvent::Ping(ping::Event { result, .. })) => {
-				if let Ok(rtt) = result {
-					let _ = metrics
-						.record(MetricValue::PingLatency(rtt.as_millis() as f64))
-						.await;
+			SwarmEvent::Behaviour(BehaviourEvent::Ping(ping::Event { peer, result, .. })) => {
+				match result {
+					Ok(rtt) => {
+						self.connected_peers.pinged(&peer, rtt);
+						let _ = metrics
+							.record(MetricValue::PingLatency(rtt.as_millis() as f64))
+							.await;
+					},
+					Err(ping::Failure::Timeout) => {
+						if self
+							.peer_scores
+							.record(peer, PeerEvent::Timeout, Instant::now())
+						{
+							ban_peer(&mut self.swarm, peer);
+						}
+					},
+					Err(_) => {},
 				}
 			},
+			SwarmEvent::Behaviour(BehaviourEvent::Gossipsub(event)) => match event {
+				gossipsub::Event::Message {
+					propagation_source,
+					message_id,
+					message,
+				} => {
+					trace!(
+						"Gossip message received on topic {} from: {propagation_source}",
+						message.topic
+					);
+					self.bandwidth
+						.downloaded(bandwidth::GOSSIPSUB, message.data.len());
+					let acceptance = match message.source {
+						// Signatures are verified by gossipsub, so unsigned messages are not expected
+						None => gossipsub::MessageAcceptance::Reject,
+						Some(source) if !self.gossip_rate_limiter.allow(source, Instant::now()) => {
+							debug!(
+								"Gossip rate limit exceeded by peer {source}, message is ignored"
+							);
+							gossipsub::MessageAcceptance::Ignore
+						},
+						Some(source) => {
+							let message = GossipMessage {
+								source,
+								propagation_source,
+								message_id: message_id.clone(),
+								topic: message.topic.to_string(),
+								data: message.data,
+							};
+							// Message is decoded and validated by the subscriber, which reports the validation result
+							if self.gossip_sender.send(message).is_ok() {
+								return;
+							}
+							gossipsub::MessageAcceptance::Ignore
+						},
+					};
+					if let Some(gossipsub) = self.swarm.behaviour_mut().gossipsub.as_mut() {
+						_ = gossipsub.report_message_validation_result(
+							&message_id,
+							&propagation_source,
+							acceptance,
+						);
+					}
+				},
+				gossipsub::Event::Subscribed { peer_id, topic } => {
+					trace!("Peer {peer_id} subscribed to gossip topic {topic}");
+				},
+				gossipsub::Event::Unsubscribed { peer_id, topic } => {
+					trace!("Peer {peer_id} unsubscribed from gossip topic {topic}");
+				},
+				gossipsub::Event::GossipsubNotSupported { peer_id } => {
+					trace!("Peer {peer_id} does not support gossipsub");
+				},
+			},
+			// Local network discovery and port mapping are disabled in the browser
+			#[cfg(target_arch = "wasm32")]
+			SwarmEvent::Behaviour(BehaviourEvent::Mdns(event) | BehaviourEvent::Upnp(event)) => {
+				void::unreachable(event)
+			},
+			#[cfg(not(target_arch = "wasm32"))]
 			SwarmEvent::Behaviour(BehaviourEvent::Upnp(event)) => match event {
 				upnp::Event::NewExternalAddr(addr) => {
-					trace!("[UPnP] New external address: {addr}");
+					info!("[UPnP] Port mapped on the gateway, new external address: {addr}");
 				},
 				upnp::Event::GatewayNotFound => {
-					trace!("[UPnP] Gateway does not support UPnP");
+					debug!("[UPnP] Gateway does not support UPnP");
 				},
 				upnp::Event::NonRoutableGateway => {
 					trace!("[UPnP] Gateway is not exposed directly to the public Internet, i.e. it itself has a private IP address.");
@@ -471,6 +629,7 @@ impl EventLoop {
 						..
 					} => {
 						trace!("Connection closed. PeerID: {peer_id:?}. Address: {:?}. Num established: {num_established:?}. Cause: {cause:?}", endpoint.get_remote_address());
+						self.connected_peers.disconnected(&peer_id, num_established);
 
 						if let Some(ConnectionError::IO(_)) = cause {
 							// remove peer with failed connection
@@ -489,8 +648,11 @@ impl EventLoop {
 							address.to_string()
 						);
 					},
-					SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+					SwarmEvent::ConnectionEstablished {
+						peer_id, endpoint, ..
+					} => {
 						metrics.count(MetricCounter::ConnectionEstablished).await;
+						self.connected_peers.connected(peer_id, &endpoint);
 						// Notify the connections we're waiting on that we've connected successfully
 						if let Some(ch) = self.pending_swarm_events.remove(&peer_id) {
 							_ = ch.send(Ok(()));
@@ -540,11 +702,132 @@ impl EventLoop {
 			&mut self.pending_kad_queries,
 			&mut self.pending_swarm_events,
 			&mut self.active_blocks,
+			&mut self.peer_scores,
+			&self.connected_peers,
+			&mut self.bandwidth,
 		)) {
 			command.abort(eyre!(err));
 		}
 	}
 
+	/// Stores the record, setting its TTL to the lower value between the local TTL and the incoming record TTL.
+	fn store_record(&mut self, mut record: Record) {
+		let ttl = &self.event_loop_config.kad_record_ttl;
+		record.expires = record.expires.min(ttl.expires());
+		_ = self.swarm.behaviour_mut().kademlia.store_mut().put(record);
+	}
+
+	async fn handle_incoming_record(
+		&mut self,
+		source: PeerId,
+		record: Record,
+		metrics: Arc<impl Metrics>,
+	) {
+		self.bandwidth
+			.downloaded(bandwidth::KADEMLIA, bandwidth::record_size(&record));
+		if self.record_validator.is_none() || self.is_validated(&record) {
+			self.store_record(record);
+			return;
+		}
+		let key = match DHTKey::try_from(record.key.clone()) {
+			Ok(key) => key,
+			Err(error) => {
+				debug!("Rejected record from {source}: {error}");
+				metrics.count(MetricCounter::RejectedPutRecord).await;
+				self.report_invalid_record(source);
+				return;
+			},
+		};
+		let incoming = IncomingRecord {
+			source,
+			key,
+			record,
+		};
+		let Some(validator) = self.record_validator.as_mut() else {
+			return;
+		};
+		match validator.check(incoming) {
+			Check::Validate(incoming, block) => self.validate_record(incoming, block),
+			Check::Held => {},
+			Check::Rejected(incoming, rejection) => {
+				self.handle_validated_record(incoming, Err(rejection), metrics)
+					.await
+			},
+		}
+	}
+
+	/// Validates the record on the sync lane of the proof verification workers, since cell proof verification is CPU bound.
+	/// Record is dropped if the verification queue is full, so the remote peers cannot saturate the workers.
+	fn validate_record(&self, incoming: IncomingRecord, block: Arc<BlockCommitments>) {
+		let source = incoming.source;
+		let public_parameters = self.public_parameters.clone();
+		let sender = self.validated_records.0.clone();
+		let spawned = proof::try_spawn(Lane::Sync, move || {
+			let result = record_validator::validate(
+				&public_parameters,
+				&block,
+				&incoming.key,
+				&incoming.record.value,
+			);
+			_ = sender.send((incoming, result));
+		});
+		match spawned {
+			Ok(true) => {},
+			Ok(false) => debug!("Dropped record from {source}, proof verification queue is full"),
+			Err(error) => warn!("Cannot validate record from {source}: {error}"),
+		}
+	}
+
+	/// Returns `true` if the same record is already stored, since only validated records are stored.
+	fn is_validated(&mut self, record: &Record) -> bool {
+		let store = self.swarm.behaviour_mut().kademlia.store_mut();
+		store
+			.get(&record.key)
+			.is_some_and(|stored| stored.value == record.value)
+	}
+
+	fn handle_block_commitments(&mut self, block: BlockCommitments) {
+		let Some(validator) = self.record_validator.as_mut() else {
+			return;
+		};
+		let block = Arc::new(block);
+		for incoming in validator.insert(block.clone()) {
+			self.validate_record(incoming, block.clone());
+		}
+	}
+
+	async fn handle_validated_record(
+		&mut self,
+		incoming: IncomingRecord,
+		result: std::result::Result<(), Rejection>,
+		metrics: Arc<impl Metrics>,
+	) {
+		let IncomingRecord {
+			source,
+			key,
+			record,
+		} = incoming;
+		match result {
+			Ok(()) => self.store_record(record),
+			Err(rejection) => {
+				debug!("Rejected record {key:?} from {source}: {rejection}");
+				metrics.count(MetricCounter::RejectedPutRecord).await;
+				if rejection.is_invalid() {
+					self.report_invalid_record(source);
+				}
+			},
+		}
+	}
+
+	fn report_invalid_record(&mut self, source: PeerId) {
+		if self
+			.peer_scores
+			.record(source, PeerEvent::InvalidRecord, Instant::now())
+		{
+			ban_peer(&mut self.swarm, source);
+		}
+	}
+
 	fn handle_periodic_bootstraps(&mut self) {
 		// commence with periodic bootstraps,
 		// only when the initial startup bootstrap is done
@@ -553,6 +836,17 @@ impl EventLoop {
 		}
 	}
 
+	fn handle_expired_bans(&mut self) {
+		self.gossip_rate_limiter.expire(Instant::now());
+		for peer_id in self.peer_scores.expire_bans(Instant::now()) {
+			debug!("Ban expired for peer {peer_id}");
+			self.swarm
+				.behaviour_mut()
+				.blocked_peers
+				.unblock_peer(peer_id);
+		}
+	}
+
 	fn establish_relay_circuit(&mut self, peer_id: PeerId) {
 		// before we try and create a circuit with the relay
 		// we have to exchange observed addresses
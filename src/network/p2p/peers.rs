@@ -0,0 +1,197 @@
+//! Connected peers, tracked from the swarm events, as reported by the diagnostics endpoint.
+
+use libp2p::{core::ConnectedPoint, multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+
+use super::reputation::PeerScores;
+
+/// Direction of the first connection with the peer
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+	Inbound,
+	Outbound,
+}
+
+/// Transport of the first connection with the peer
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+	Tcp,
+	Websocket,
+	Quic,
+	Relayed,
+	Other,
+}
+
+impl From<&Multiaddr> for Transport {
+	fn from(address: &Multiaddr) -> Self {
+		let mut transport = Transport::Other;
+		for protocol in address.iter() {
+			transport = match protocol {
+				Protocol::P2pCircuit => return Transport::Relayed,
+				Protocol::Ws(_) | Protocol::Wss(_) => Transport::Websocket,
+				Protocol::QuicV1 | Protocol::Quic => Transport::Quic,
+				Protocol::Tcp(_) if transport == Transport::Other => Transport::Tcp,
+				_ => transport,
+			};
+		}
+		transport
+	}
+}
+
+struct Connection {
+	direction: Direction,
+	transport: Transport,
+	agent_version: Option<String>,
+	latency: Option<Duration>,
+}
+
+/// Connected peer, as reported by the diagnostics endpoint
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeerInfo {
+	pub peer_id: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub agent_version: Option<String>,
+	/// Round trip time of the latest ping, in milliseconds
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub latency: Option<u64>,
+	pub direction: Direction,
+	pub transport: Transport,
+	/// Number of valid records served by the peer
+	pub served: u32,
+	/// Number of invalid records served by the peer
+	pub invalid: u32,
+}
+
+/// Routing table statistics
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RoutingTableStats {
+	/// Number of peers in the routing table
+	pub peers: usize,
+	/// Number of non-empty buckets
+	pub buckets: usize,
+}
+
+/// Connected peers and the routing table statistics
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Peers {
+	pub peers: Vec<PeerInfo>,
+	pub routing_table: RoutingTableStats,
+}
+
+#[derive(Default)]
+pub struct ConnectedPeers {
+	peers: HashMap<PeerId, Connection>,
+}
+
+impl ConnectedPeers {
+	/// Tracks the peer on its first established connection.
+	pub fn connected(&mut self, peer_id: PeerId, endpoint: &ConnectedPoint) {
+		let (direction, address) = match endpoint {
+			ConnectedPoint::Dialer { address, .. } => (Direction::Outbound, address),
+			ConnectedPoint::Listener { send_back_addr, .. } => (Direction::Inbound, send_back_addr),
+		};
+		self.peers.entry(peer_id).or_insert_with(|| Connection {
+			direction,
+			transport: address.into(),
+			agent_version: None,
+			latency: None,
+		});
+	}
+
+	/// Stops tracking the peer when its last connection is closed.
+	pub fn disconnected(&mut self, peer_id: &PeerId, num_established: u32) {
+		if num_established == 0 {
+			self.peers.remove(peer_id);
+		}
+	}
+
+	pub fn identified(&mut self, peer_id: &PeerId, agent_version: &str) {
+		if let Some(connection) = self.peers.get_mut(peer_id) {
+			connection.agent_version = Some(agent_version.to_string());
+		}
+	}
+
+	pub fn pinged(&mut self, peer_id: &PeerId, latency: Duration) {
+		if let Some(connection) = self.peers.get_mut(peer_id) {
+			connection.latency = Some(latency);
+		}
+	}
+
+	/// Returns connected peers with their record counters, ordered by the latency.
+	pub fn table(&self, scores: &PeerScores) -> Vec<PeerInfo> {
+		let mut table = self
+			.peers
+			.iter()
+			.map(|(peer_id, connection)| {
+				let (served, invalid) = scores.counters(peer_id);
+				PeerInfo {
+					peer_id: peer_id.to_string(),
+					agent_version: connection.agent_version.clone(),
+					latency: connection.latency.map(|latency| latency.as_millis() as u64),
+					direction: connection.direction,
+					transport: connection.transport,
+					served,
+					invalid,
+				}
+			})
+			.collect::<Vec<_>>();
+		// Peers without the measured latency are listed last
+		table.sort_by_key(|peer| (peer.latency.is_none(), peer.latency));
+		table
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::{
+		core::{ConnectedPoint, Endpoint},
+		Multiaddr, PeerId,
+	};
+	use std::time::Duration;
+	use test_case::test_case;
+
+	use super::{ConnectedPeers, Direction, Transport};
+	use crate::network::p2p::reputation::{PeerScores, ReputationConfig};
+
+	#[test_case("/ip4/127.0.0.1/tcp/37000" => Transport::Tcp ; "TCP")]
+	#[test_case("/ip4/127.0.0.1/tcp/37000/ws" => Transport::Websocket ; "WebSocket")]
+	#[test_case("/ip4/127.0.0.1/udp/37000/quic-v1" => Transport::Quic ; "QUIC")]
+	#[test_case("/ip4/127.0.0.1/tcp/37000/p2p/12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz/p2p-circuit" => Transport::Relayed ; "Relayed")]
+	fn test_transport(address: &str) -> Transport {
+		Transport::from(&address.parse::<Multiaddr>().unwrap())
+	}
+
+	#[test]
+	fn peer_is_tracked_until_last_connection_is_closed() {
+		let scores = PeerScores::new(ReputationConfig {
+			ban_threshold: -100,
+			ban_duration: Duration::from_secs(60),
+		});
+		let mut peers = ConnectedPeers::default();
+		let peer_id = PeerId::random();
+		let endpoint = ConnectedPoint::Dialer {
+			address: "/ip4/127.0.0.1/tcp/37000".parse().unwrap(),
+			role_override: Endpoint::Dialer,
+		};
+
+		peers.connected(peer_id, &endpoint);
+		peers.identified(
+			&peer_id,
+			"avail-light-client/light-client/1.9.0/rust-client",
+		);
+		peers.pinged(&peer_id, Duration::from_millis(42));
+		let table = peers.table(&scores);
+		assert_eq!(table.len(), 1);
+		assert_eq!(table[0].direction, Direction::Outbound);
+		assert_eq!(table[0].transport, Transport::Tcp);
+		assert_eq!(table[0].latency, Some(42));
+
+		peers.disconnected(&peer_id, 1);
+		assert_eq!(peers.table(&scores).len(), 1);
+		peers.disconnected(&peer_id, 0);
+		assert!(peers.table(&scores).is_empty());
+	}
+}
@@ -0,0 +1,118 @@
+use futures::{
+	future::{BoxFuture, Pending},
+	FutureExt,
+};
+use libp2p::{
+	core::{
+		multiaddr::Protocol,
+		transport::{ListenerId, TransportError, TransportEvent},
+	},
+	Multiaddr, Transport,
+};
+use std::{
+	io,
+	pin::Pin,
+	task::{Context, Poll},
+};
+use tokio::net::TcpStream;
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+
+use crate::network::proxy::Proxy;
+
+/// Returns host and port of the TCP address, other transports (e.g. QUIC, WebSocket or relayed) are not supported.
+fn tcp_target(addr: &Multiaddr) -> Option<(String, u16)> {
+	let mut protocols = addr.iter();
+	let host = match protocols.next()? {
+		Protocol::Ip4(ip) => ip.to_string(),
+		Protocol::Ip6(ip) => ip.to_string(),
+		Protocol::Dns(host) | Protocol::Dns4(host) | Protocol::Dns6(host) => host.to_string(),
+		_ => return None,
+	};
+	let Protocol::Tcp(port) = protocols.next()? else {
+		return None;
+	};
+	match protocols.next() {
+		None => Some((host, port)),
+		Some(Protocol::P2p(_)) if protocols.next().is_none() => Some((host, port)),
+		_ => None,
+	}
+}
+
+/// Dials TCP connections through the proxy, listening is not supported.
+pub struct ProxyTransport {
+	proxy: Proxy,
+}
+
+impl ProxyTransport {
+	pub fn new(proxy: Proxy) -> Self {
+		Self { proxy }
+	}
+}
+
+impl Transport for ProxyTransport {
+	type Output = Compat<TcpStream>;
+	type Error = io::Error;
+	type ListenerUpgrade = Pending<Result<Self::Output, Self::Error>>;
+	type Dial = BoxFuture<'static, Result<Self::Output, Self::Error>>;
+
+	fn listen_on(
+		&mut self,
+		_id: ListenerId,
+		addr: Multiaddr,
+	) -> Result<(), TransportError<Self::Error>> {
+		Err(TransportError::MultiaddrNotSupported(addr))
+	}
+
+	fn remove_listener(&mut self, _id: ListenerId) -> bool {
+		false
+	}
+
+	fn dial(&mut self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+		let Some((host, port)) = tcp_target(&addr) else {
+			return Err(TransportError::MultiaddrNotSupported(addr));
+		};
+		let proxy = self.proxy.clone();
+		Ok(async move {
+			proxy
+				.connect(&host, port)
+				.await
+				.map(|stream| stream.compat())
+		}
+		.boxed())
+	}
+
+	fn dial_as_listener(
+		&mut self,
+		addr: Multiaddr,
+	) -> Result<Self::Dial, TransportError<Self::Error>> {
+		self.dial(addr)
+	}
+
+	fn poll(
+		self: Pin<&mut Self>,
+		_cx: &mut Context<'_>,
+	) -> Poll<TransportEvent<Self::ListenerUpgrade, Self::Error>> {
+		Poll::Pending
+	}
+
+	fn address_translation(&self, _listen: &Multiaddr, _observed: &Multiaddr) -> Option<Multiaddr> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::Multiaddr;
+	use test_case::test_case;
+
+	use super::tcp_target;
+
+	#[test_case("/ip4/127.0.0.1/tcp/37000" => Some(("127.0.0.1".to_string(), 37000)) ; "IPv4")]
+	#[test_case("/ip6/::1/tcp/37000/p2p/12D3KooWStAKPADXqJ7cngPYXd2mSANpdgh1xQ34aouufHA2xShz" => Some(("::1".to_string(), 37000)) ; "IPv6 with peer ID")]
+	#[test_case("/dns/bootnode.example.com/tcp/37000" => Some(("bootnode.example.com".to_string(), 37000)) ; "DNS")]
+	#[test_case("/ip4/127.0.0.1/udp/37000/quic-v1" => None ; "QUIC")]
+	#[test_case("/ip4/127.0.0.1/tcp/37000/ws" => None ; "WebSocket")]
+	fn test_tcp_target(addr: &str) -> Option<(String, u16)> {
+		tcp_target(&addr.parse::<Multiaddr>().unwrap())
+	}
+}
@@ -0,0 +1,78 @@
+//! Per-peer rate limit of the received gossip messages, messages above the limit are ignored and not propagated.
+
+use libp2p::PeerId;
+use std::{collections::HashMap, time::Duration};
+use tokio::time::Instant;
+
+/// Window in which the messages of the peer are counted
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+pub struct RateLimiter {
+	/// Maximum number of messages per window
+	limit: u32,
+	/// Start of the current window and number of messages in it, per peer
+	peers: HashMap<PeerId, (Instant, u32)>,
+}
+
+impl RateLimiter {
+	pub fn new(limit: u32) -> Self {
+		RateLimiter {
+			limit,
+			peers: HashMap::new(),
+		}
+	}
+
+	/// Counts message of the peer, and returns `false` if the peer exceeded the limit in the current window.
+	pub fn allow(&mut self, peer_id: PeerId, now: Instant) -> bool {
+		let (window_start, count) = self.peers.entry(peer_id).or_insert((now, 0));
+		if now.duration_since(*window_start) >= RATE_LIMIT_WINDOW {
+			*window_start = now;
+			*count = 0;
+		}
+		*count += 1;
+		*count <= self.limit
+	}
+
+	/// Removes peers whose window has expired.
+	pub fn expire(&mut self, now: Instant) {
+		self.peers
+			.retain(|_, (window_start, _)| now.duration_since(*window_start) < RATE_LIMIT_WINDOW);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::PeerId;
+	use std::time::Duration;
+	use tokio::time::Instant;
+
+	use super::RateLimiter;
+
+	#[test]
+	fn messages_above_limit_are_not_allowed() {
+		let mut limiter = RateLimiter::new(2);
+		let (peer_id, other_peer_id) = (PeerId::random(), PeerId::random());
+		let now = Instant::now();
+
+		assert!(limiter.allow(peer_id, now));
+		assert!(limiter.allow(peer_id, now + Duration::from_secs(1)));
+		assert!(!limiter.allow(peer_id, now + Duration::from_secs(2)));
+		// limit is counted per peer
+		assert!(limiter.allow(other_peer_id, now + Duration::from_secs(2)));
+		// limit is reset in the next window
+		assert!(limiter.allow(peer_id, now + Duration::from_secs(60)));
+	}
+
+	#[test]
+	fn expired_windows_are_removed() {
+		let mut limiter = RateLimiter::new(1);
+		let peer_id = PeerId::random();
+		let now = Instant::now();
+
+		assert!(limiter.allow(peer_id, now));
+		limiter.expire(now + Duration::from_secs(30));
+		assert!(!limiter.allow(peer_id, now + Duration::from_secs(30)));
+		limiter.expire(now + Duration::from_secs(60));
+		assert!(limiter.peers.is_empty());
+	}
+}
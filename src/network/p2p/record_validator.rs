@@ -0,0 +1,336 @@
+//! Validation of the DHT records put by the remote peers, so peers cannot poison the DHT with invalid cells.
+
+use avail_subxt::primitives::Header;
+use color_eyre::{eyre::eyre, Report, Result};
+use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
+use kate_recovery::{
+	commitments,
+	config::{CHUNK_SIZE, COMMITMENT_SIZE},
+	data::Cell,
+	matrix::{Dimensions, Position},
+	proof,
+};
+use libp2p::{kad::Record, PeerId};
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display},
+	sync::Arc,
+};
+use tokio::sync::{broadcast, mpsc::UnboundedSender};
+use tracing::{debug, warn};
+
+use super::event_loop::DHTKey;
+use crate::{
+	data::{self, Database, Key, CANONICAL_HASH_CF},
+	network::rpc,
+	utils::extract_kate,
+	verification,
+};
+
+/// Maximum number of records held until the commitments of their blocks are received
+const MAX_PENDING_RECORDS: usize = 16384;
+/// Maximum number of blocks ahead of the latest known block, for which records are held
+const MAX_PENDING_BLOCKS: u32 = 3;
+
+/// Commitments of the finalized block, used to validate its DHT records.
+#[derive(Clone, Debug)]
+pub struct BlockCommitments {
+	pub block_number: u32,
+	pub dimensions: Dimensions,
+	pub commitments: Vec<[u8; COMMITMENT_SIZE]>,
+}
+
+impl TryFrom<&Header> for BlockCommitments {
+	type Error = Report;
+
+	fn try_from(header: &Header) -> Result<Self> {
+		let (rows, cols, _, commitment) = extract_kate(&header.extension);
+		let dimensions =
+			Dimensions::new(rows, cols).ok_or_else(|| eyre!("Invalid dimensions {rows}x{cols}"))?;
+		let commitments = commitments::from_slice(&commitment)?;
+		if let Some(reason) = verification::check_commitments(dimensions, &commitments) {
+			return Err(eyre!("Invalid commitments: {reason}"));
+		}
+		Ok(BlockCommitments {
+			block_number: header.number,
+			dimensions,
+			commitments,
+		})
+	}
+}
+
+/// Reason of the record rejection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rejection {
+	/// Commitments of the block are not known
+	UnknownBlock,
+	/// Position of the record is outside of the block matrix
+	InvalidPosition,
+	/// Size of the record value doesn't match the cell or the row size
+	InvalidSize,
+	/// Cell proof failed verification against the row commitment
+	InvalidProof,
+}
+
+impl Rejection {
+	/// Returns `true` if the record is proven invalid, rather than it cannot be validated.
+	pub fn is_invalid(&self) -> bool {
+		!matches!(self, Rejection::UnknownBlock)
+	}
+}
+
+impl Display for Rejection {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Rejection::UnknownBlock => write!(f, "unknown block"),
+			Rejection::InvalidPosition => write!(f, "position outside of the matrix"),
+			Rejection::InvalidSize => write!(f, "invalid size"),
+			Rejection::InvalidProof => write!(f, "invalid proof"),
+		}
+	}
+}
+
+/// Record put by the remote peer.
+pub struct IncomingRecord {
+	pub source: PeerId,
+	pub key: DHTKey,
+	pub record: Record,
+}
+
+/// Outcome of the record check against the known commitments.
+pub enum Check {
+	/// Record needs to be validated against the commitments of its block
+	Validate(IncomingRecord, Arc<BlockCommitments>),
+	/// Record is held until the commitments of its block are received
+	Held,
+	Rejected(IncomingRecord, Rejection),
+}
+
+/// Validates the record value against the commitments of its block.
+pub fn validate(
+	public_parameters: &PublicParameters,
+	block: &BlockCommitments,
+	key: &DHTKey,
+	value: &[u8],
+) -> Result<(), Rejection> {
+	let dimensions = block.dimensions;
+	let cols = u32::from(dimensions.cols().get());
+	match *key {
+		DHTKey::Cell(_, row, col) => {
+			if row >= dimensions.extended_rows() || col >= cols {
+				return Err(Rejection::InvalidPosition);
+			}
+			let content = value.try_into().map_err(|_| Rejection::InvalidSize)?;
+			let cell = Cell {
+				position: Position {
+					row,
+					col: col as u16,
+				},
+				content,
+			};
+			let commitment = &block.commitments[row as usize];
+			match proof::verify(public_parameters, dimensions, commitment, &cell) {
+				Ok(true) => Ok(()),
+				Ok(false) | Err(_) => Err(Rejection::InvalidProof),
+			}
+		},
+		// Row content cannot be verified against the commitments without the app lookup,
+		// so only the position and the size of the row are checked
+		DHTKey::Row(_, row) => {
+			if row >= u32::from(dimensions.rows().get()) {
+				return Err(Rejection::InvalidPosition);
+			}
+			if value.len() != cols as usize * CHUNK_SIZE {
+				return Err(Rejection::InvalidSize);
+			}
+			Ok(())
+		},
+	}
+}
+
+/// Commitments of the latest blocks, and records held until the commitments of their blocks are received.
+pub struct RecordValidator {
+	blocks: BTreeMap<u32, Arc<BlockCommitments>>,
+	pending: BTreeMap<u32, Vec<IncomingRecord>>,
+	pending_count: usize,
+	max_blocks: usize,
+}
+
+impl RecordValidator {
+	pub fn new(max_blocks: usize) -> Self {
+		RecordValidator {
+			blocks: BTreeMap::new(),
+			pending: BTreeMap::new(),
+			pending_count: 0,
+			max_blocks: max_blocks.max(1),
+		}
+	}
+
+	/// Adds commitments of the block, evicting the oldest blocks above the limit.
+	/// Returns records of the block which were held until its commitments are received.
+	pub fn insert(&mut self, block: Arc<BlockCommitments>) -> Vec<IncomingRecord> {
+		let block_number = block.block_number;
+		self.blocks.insert(block_number, block);
+		while self.blocks.len() > self.max_blocks {
+			self.blocks.pop_first();
+		}
+
+		// Held records of the older blocks cannot be validated anymore
+		let mut pending = self.pending.split_off(&block_number);
+		let dropped = self.pending.values().map(Vec::len).sum::<usize>();
+		if dropped > 0 {
+			debug!("Dropped {dropped} held records of the blocks older than {block_number}");
+		}
+		let released = pending.remove(&block_number).unwrap_or_default();
+		self.pending = pending;
+		self.pending_count = self.pending.values().map(Vec::len).sum();
+		released
+	}
+
+	/// Checks if the commitments of the record block are known, holding the records of the upcoming blocks.
+	pub fn check(&mut self, record: IncomingRecord) -> Check {
+		let block_number = record.key.block_number();
+		if let Some(block) = self.blocks.get(&block_number) {
+			return Check::Validate(record, block.clone());
+		}
+
+		let is_upcoming = self.blocks.last_key_value().map_or(true, |(&latest, _)| {
+			block_number > latest && block_number <= latest.saturating_add(MAX_PENDING_BLOCKS)
+		});
+		if !is_upcoming || self.pending_count >= MAX_PENDING_RECORDS {
+			return Check::Rejected(record, Rejection::UnknownBlock);
+		}
+		self.pending.entry(block_number).or_default().push(record);
+		self.pending_count += 1;
+		Check::Held
+	}
+}
+
+/// Sends commitments of the latest stored headers, followed by the commitments of the finalized headers.
+///
+/// # Arguments
+///
+/// * `db` - Database with the headers of the verified blocks
+/// * `max_blocks` - Number of the latest stored blocks whose commitments are sent
+/// * `rpc_events` - Subscription to the finalized headers
+/// * `sender` - Sender of the commitments to the event loop
+pub async fn forward_commitments(
+	db: impl Database,
+	max_blocks: usize,
+	mut rpc_events: broadcast::Receiver<rpc::Event>,
+	sender: UnboundedSender<BlockCommitments>,
+) {
+	let mut stored = match db.keys(CANONICAL_HASH_CF) {
+		Ok(keys) => keys
+			.into_iter()
+			.filter_map(|key| match key {
+				Key::CanonicalHash(block_number) => Some(block_number),
+				_ => None,
+			})
+			.collect::<Vec<_>>(),
+		Err(error) => {
+			warn!("Cannot load stored headers for the record validation: {error:#}");
+			vec![]
+		},
+	};
+	stored.sort_unstable();
+	let latest = stored.len().saturating_sub(max_blocks);
+
+	let stored_headers = stored[latest..].iter().filter_map(|&block_number| {
+		data::get_canonical::<Header>(&db, block_number, Key::BlockHeader)
+			.ok()
+			.flatten()
+	});
+	for header in stored_headers {
+		match BlockCommitments::try_from(&header) {
+			Ok(block) => {
+				_ = sender.send(block);
+			},
+			Err(error) => debug!(header.number, "Skipping record validation: {error:#}"),
+		}
+	}
+
+	loop {
+		let header = match rpc_events.recv().await {
+			Ok(rpc::Event::HeaderUpdate { header, .. }) => header,
+			Ok(_) => continue,
+			Err(broadcast::error::RecvError::Lagged(skipped)) => {
+				warn!("Record validation skipped {skipped} headers");
+				continue;
+			},
+			Err(broadcast::error::RecvError::Closed) => return,
+		};
+		match BlockCommitments::try_from(&header) {
+			Ok(block) => {
+				if sender.send(block).is_err() {
+					return;
+				}
+			},
+			Err(error) => debug!(header.number, "Skipping record validation: {error:#}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use kate_recovery::matrix::Dimensions;
+	use libp2p::{
+		kad::{Record, RecordKey},
+		PeerId,
+	};
+	use std::sync::Arc;
+	use test_case::test_case;
+
+	use super::{BlockCommitments, Check, IncomingRecord, RecordValidator, Rejection};
+	use crate::network::p2p::event_loop::DHTKey;
+
+	fn block(block_number: u32) -> BlockCommitments {
+		let dimensions = Dimensions::new(1, 4).unwrap();
+		BlockCommitments {
+			block_number,
+			dimensions,
+			commitments: vec![[0; 48]; dimensions.extended_rows() as usize],
+		}
+	}
+
+	fn record(key: DHTKey, value: Vec<u8>) -> IncomingRecord {
+		IncomingRecord {
+			source: PeerId::random(),
+			key,
+			record: Record::new(RecordKey::new(&"0"), value),
+		}
+	}
+
+	#[test_case(DHTKey::Cell(1, 2, 0), vec![0; 80] => Err(Rejection::InvalidPosition) ; "Cell row outside of the matrix")]
+	#[test_case(DHTKey::Cell(1, 0, 4), vec![0; 80] => Err(Rejection::InvalidPosition) ; "Cell column outside of the matrix")]
+	#[test_case(DHTKey::Cell(1, 0, 0), vec![0; 79] => Err(Rejection::InvalidSize) ; "Cell size")]
+	#[test_case(DHTKey::Row(1, 1), vec![0; 128] => Err(Rejection::InvalidPosition) ; "Extended row")]
+	#[test_case(DHTKey::Row(1, 0), vec![0; 96] => Err(Rejection::InvalidSize) ; "Row size")]
+	#[test_case(DHTKey::Row(1, 0), vec![0; 128] => Ok(()) ; "Valid row")]
+	fn test_validate(key: DHTKey, value: Vec<u8>) -> Result<(), Rejection> {
+		let public_parameters = kate_recovery::testnet::public_params(1024);
+		super::validate(&public_parameters, &block(1), &key, &value)
+	}
+
+	#[test]
+	fn records_of_upcoming_blocks_are_held() {
+		let mut validator = RecordValidator::new(2);
+		for block_number in [1, 2, 3] {
+			assert!(validator.insert(Arc::new(block(block_number))).is_empty());
+		}
+
+		let check = validator.check(record(DHTKey::Cell(1, 0, 0), vec![]));
+		assert!(matches!(check, Check::Rejected(_, Rejection::UnknownBlock)));
+		let check = validator.check(record(DHTKey::Cell(3, 0, 0), vec![]));
+		assert!(matches!(check, Check::Validate(_, block) if block.block_number == 3));
+
+		let check = validator.check(record(DHTKey::Cell(5, 0, 0), vec![]));
+		assert!(matches!(check, Check::Held));
+		let check = validator.check(record(DHTKey::Cell(9, 0, 0), vec![]));
+		assert!(matches!(check, Check::Rejected(_, Rejection::UnknownBlock)));
+
+		let released = validator.insert(Arc::new(block(5)));
+		assert_eq!(released.len(), 1);
+		assert_eq!(released[0].key, DHTKey::Cell(5, 0, 0));
+	}
+}
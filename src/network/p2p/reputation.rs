@@ -0,0 +1,204 @@
+//! Peer reputation, scored by the outcome of interactions with the peer, peers below the threshold are banned for the cooldown period.
+
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use tokio::time::Instant;
+
+/// Maximum score, which bounds the credit peer can accumulate before misbehaving
+const MAX_SCORE: i32 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerEvent {
+	/// Peer served a valid record
+	RecordServed,
+	/// Peer served a record or published a gossip message which cannot be decoded or verified
+	InvalidRecord,
+	/// Peer failed to respond in time
+	Timeout,
+}
+
+impl PeerEvent {
+	fn score(&self) -> i32 {
+		match self {
+			PeerEvent::RecordServed => 1,
+			PeerEvent::InvalidRecord => -20,
+			PeerEvent::Timeout => -5,
+		}
+	}
+}
+
+/// Peer reputation configuration (see [crate::types::RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct ReputationConfig {
+	pub ban_threshold: i32,
+	pub ban_duration: Duration,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Reputation {
+	score: i32,
+	served: u32,
+	invalid: u32,
+	timeouts: u32,
+	banned_until: Option<Instant>,
+}
+
+/// Peer score, as reported by the diagnostics endpoint
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeerScore {
+	pub peer_id: String,
+	pub score: i32,
+	pub served: u32,
+	pub invalid: u32,
+	pub timeouts: u32,
+	/// Seconds until the ban expires, if peer is banned
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub banned_for: Option<u64>,
+}
+
+pub struct PeerScores {
+	config: ReputationConfig,
+	peers: HashMap<PeerId, Reputation>,
+}
+
+impl PeerScores {
+	pub fn new(config: ReputationConfig) -> Self {
+		PeerScores {
+			config,
+			peers: HashMap::new(),
+		}
+	}
+
+	/// Records peer event and returns `true` if the peer needs to be banned.
+	pub fn record(&mut self, peer_id: PeerId, event: PeerEvent, now: Instant) -> bool {
+		let reputation = self.peers.entry(peer_id).or_default();
+		if reputation.banned_until.is_some() {
+			return false;
+		}
+
+		reputation.score = (reputation.score + event.score()).min(MAX_SCORE);
+		match event {
+			PeerEvent::RecordServed => reputation.served += 1,
+			PeerEvent::InvalidRecord => reputation.invalid += 1,
+			PeerEvent::Timeout => reputation.timeouts += 1,
+		}
+
+		if reputation.score > self.config.ban_threshold {
+			return false;
+		}
+		reputation.banned_until = Some(now + self.config.ban_duration);
+		true
+	}
+
+	/// Removes expired bans, resets scores and returns peers which can be unbanned.
+	pub fn expire_bans(&mut self, now: Instant) -> Vec<PeerId> {
+		let expired = self
+			.peers
+			.iter()
+			.filter(|(_, reputation)| reputation.banned_until.is_some_and(|until| until <= now))
+			.map(|(peer_id, _)| *peer_id)
+			.collect::<Vec<_>>();
+
+		for peer_id in &expired {
+			self.peers.remove(peer_id);
+		}
+		expired
+	}
+
+	/// Returns the number of valid and invalid records served by the peer.
+	pub fn counters(&self, peer_id: &PeerId) -> (u32, u32) {
+		self.peers
+			.get(peer_id)
+			.map_or((0, 0), |reputation| (reputation.served, reputation.invalid))
+	}
+
+	/// Returns peer scores, ordered from the lowest score.
+	pub fn table(&self, now: Instant) -> Vec<PeerScore> {
+		let mut table = self
+			.peers
+			.iter()
+			.map(|(peer_id, reputation)| PeerScore {
+				peer_id: peer_id.to_string(),
+				score: reputation.score,
+				served: reputation.served,
+				invalid: reputation.invalid,
+				timeouts: reputation.timeouts,
+				banned_for: reputation
+					.banned_until
+					.map(|until| until.saturating_duration_since(now).as_secs()),
+			})
+			.collect::<Vec<_>>();
+		table.sort_by_key(|score| score.score);
+		table
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use libp2p::PeerId;
+	use std::time::Duration;
+	use tokio::time::Instant;
+
+	use super::{PeerEvent, PeerScores, ReputationConfig};
+
+	fn peer_scores() -> PeerScores {
+		PeerScores::new(ReputationConfig {
+			ban_threshold: -50,
+			ban_duration: Duration::from_secs(60),
+		})
+	}
+
+	#[test]
+	fn invalid_records_ban_peer() {
+		let mut scores = peer_scores();
+		let peer_id = PeerId::random();
+		let now = Instant::now();
+
+		assert!(!scores.record(peer_id, PeerEvent::RecordServed, now));
+		assert!(!scores.record(peer_id, PeerEvent::InvalidRecord, now));
+		assert!(!scores.record(peer_id, PeerEvent::InvalidRecord, now));
+		assert!(scores.record(peer_id, PeerEvent::InvalidRecord, now));
+
+		// banned peer is not reported twice
+		assert!(!scores.record(peer_id, PeerEvent::Timeout, now));
+
+		let table = scores.table(now);
+		assert_eq!(table.len(), 1);
+		assert_eq!(table[0].score, -59);
+		assert_eq!(table[0].invalid, 3);
+		assert_eq!(table[0].banned_for, Some(60));
+	}
+
+	#[test]
+	fn served_records_are_bounded() {
+		let mut scores = peer_scores();
+		let peer_id = PeerId::random();
+		let now = Instant::now();
+
+		for _ in 0..1000 {
+			scores.record(peer_id, PeerEvent::RecordServed, now);
+		}
+		assert_eq!(scores.table(now)[0].score, 100);
+
+		let banned = (0..8).any(|_| scores.record(peer_id, PeerEvent::InvalidRecord, now));
+		assert!(banned);
+	}
+
+	#[test]
+	fn bans_expire_after_cooldown() {
+		let mut scores = peer_scores();
+		let peer_id = PeerId::random();
+		let now = Instant::now();
+
+		for _ in 0..3 {
+			scores.record(peer_id, PeerEvent::InvalidRecord, now);
+		}
+		assert!(scores.expire_bans(now + Duration::from_secs(30)).is_empty());
+		assert_eq!(
+			scores.expire_bans(now + Duration::from_secs(60)),
+			vec![peer_id]
+		);
+		assert!(scores.table(now).is_empty());
+	}
+}
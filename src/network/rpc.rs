@@ -1,42 +1,160 @@
 use async_trait::async_trait;
 use avail_subxt::{primitives::Header, utils::H256};
-use codec::Decode;
-use color_eyre::{eyre::eyre, Report, Result};
+use codec::{Decode, Encode};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Report, Result,
+};
+use hickory_resolver::TokioAsyncResolver;
 use kate_recovery::matrix::{Dimensions, Position};
-use rand::{seq::SliceRandom, thread_rng, Rng};
-use serde::{de, Deserialize};
-use sp_core::bytes::from_hex;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{de, Deserialize, Serialize};
+use sp_core::{blake2_256, bytes::from_hex};
 use std::{
-	collections::HashSet,
+	collections::{HashMap, HashSet},
 	fmt::Display,
 	sync::{Arc, Mutex},
+	time::{Duration, Instant},
 };
 use tokio::{
-	sync::broadcast,
+	sync::{broadcast, OwnedSemaphorePermit},
 	time::{self, timeout},
 };
-use tracing::{debug, info};
+use tracing::{info, warn};
 
 use crate::{
-	data::Database,
+	consts::ExpectedNodeVariant,
+	data::{Database, Key},
 	network::rpc,
-	types::{GrandpaJustification, RetryConfig, State},
+	sampling::SamplingStrategy,
+	telemetry::SharedMetrics,
+	types::{
+		GrandpaJustification, HealthCheckConfig, ParallelFetchConfig, RetryConfig, RuntimeVersion,
+		State, DEV_FLAG_GENHASH,
+	},
 };
 
+mod buffer;
+mod circuit_breaker;
 mod client;
+mod error;
+mod policy;
+mod rate_limit;
+mod resolution;
 mod subscriptions;
-
+mod transport;
+
+use circuit_breaker::CircuitBreaker;
+pub use circuit_breaker::{BreakerState, CircuitBreakerConfig};
+pub use error::RpcError;
+pub use policy::NodePolicy;
+use rate_limit::RateLimiter;
+pub use rate_limit::{NodeRateLimit, RateLimitConfig};
 use subscriptions::SubscriptionLoop;
 const CELL_SIZE: usize = 32;
 const PROOF_SIZE: usize = 48;
 pub const CELL_WITH_PROOF_SIZE: usize = CELL_SIZE + PROOF_SIZE;
 pub use subscriptions::Event;
+use transport::Transport;
+pub use transport::TransportConfig;
+pub(crate) use transport::{tls_client_config, websocket};
 
 pub use client::Client;
 
 pub enum Subscription {
 	Header(Header),
 	Justification(GrandpaJustification),
+	/// Subscription stream is dropped with the given reason
+	ConnectionLost(String),
+	/// Subscription stream is recreated on the given node
+	ConnectionRestored(Node),
+	/// Runtime version of the connected node, received on subscription and on each runtime upgrade
+	RuntimeVersion(RuntimeVersion),
+}
+
+/// RPC client lifecycle events, see [`Client::subscribe`].
+#[derive(Clone, Debug)]
+pub enum RpcEvent {
+	/// Finalized header is received on the subscription stream (not yet verified)
+	HeaderReceived { number: u32, hash: H256 },
+	/// GRANDPA justification is received on the subscription stream
+	JustificationReceived {
+		target_number: u32,
+		target_hash: H256,
+	},
+	/// Client switched from the previous host to the new node
+	NodeSwitched { previous: String, node: Node },
+	/// Blocks in range [from, to) were missed by the subscription and are fetched separately
+	SubscriptionLagged { from: u32, to: u32 },
+	/// Node has unexpected system version or spec name, it is skipped unless version mismatch is allowed
+	VersionMismatch {
+		host: String,
+		expected: String,
+		found: String,
+		accepted: bool,
+	},
+	/// Runtime of the connected node is upgraded, and client metadata is refreshed
+	RuntimeUpgraded {
+		host: String,
+		previous: u32,
+		spec_version: u32,
+	},
+	/// Node is rejected because it belongs to another network
+	GenesisMismatch {
+		host: String,
+		expected: H256,
+		found: H256,
+	},
+}
+
+/// Genesis hash which connected nodes must match, nodes of other networks are rejected.
+/// If genesis hash is configured for development, genesis hash of the first connected node is pinned,
+/// so the client cannot switch to the node of another network.
+#[derive(Clone, Debug)]
+pub struct ExpectedGenesis {
+	hash: Arc<Mutex<Option<H256>>>,
+}
+
+impl ExpectedGenesis {
+	/// Parses configured genesis hash, which is either hex encoded hash, or starts with `DEV`.
+	pub fn from_config(genesis_hash: &str) -> Result<Self> {
+		let hash = if genesis_hash.starts_with(DEV_FLAG_GENHASH) {
+			warn!("Genesis hash configured for development ({genesis_hash}), genesis hash of the first connected node is used.");
+			None
+		} else {
+			let hash = from_hex(genesis_hash)
+				.ok()
+				.and_then(|hash| <[u8; 32]>::try_from(hash).ok())
+				.ok_or_else(|| {
+					eyre!("Genesis hash invalid, badly configured or missing (\"{genesis_hash}\").")
+				})?;
+			Some(H256::from(hash))
+		};
+		Ok(Self {
+			hash: Arc::new(Mutex::new(hash)),
+		})
+	}
+
+	/// Returns genesis hash which nodes must match, if it is configured or pinned.
+	pub fn hash(&self) -> Option<H256> {
+		*self.hash.lock().unwrap()
+	}
+
+	/// Checks genesis hash of the node, pinning it if genesis hash is not known yet.
+	/// Returns expected genesis hash if node genesis hash doesn't match.
+	fn check(&self, found: H256) -> Result<(), H256> {
+		let mut hash = self.hash.lock().unwrap();
+		match *hash {
+			Some(expected) if expected != found => Err(expected),
+			Some(_) => Ok(()),
+			None => {
+				info!("Pinned genesis hash {found:?} of the first connected node");
+				*hash = Some(found);
+				Ok(())
+			},
+		}
+	}
 }
 
 #[async_trait]
@@ -83,6 +201,8 @@ impl<'de> Deserialize<'de> for WrappedProof {
 #[derive(Clone, Debug)]
 pub struct Node {
 	pub host: String,
+	/// DNS name of the endpoint, if the host is its resolved address, used as the TLS server name and the `Host` header
+	pub server_name: Option<String>,
 	pub system_version: String,
 	pub spec_name: String,
 	pub spec_version: u32,
@@ -92,6 +212,7 @@ pub struct Node {
 impl Node {
 	pub fn new(
 		host: String,
+		server_name: Option<String>,
 		system_version: String,
 		spec_name: String,
 		spec_version: u32,
@@ -99,6 +220,7 @@ impl Node {
 	) -> Self {
 		Self {
 			host,
+			server_name,
 			system_version,
 			spec_name,
 			spec_version,
@@ -106,6 +228,18 @@ impl Node {
 		}
 	}
 
+	/// Creates candidate node of the configured endpoint, versions are known once the node is connected.
+	fn candidate(host: String, server_name: Option<String>) -> Self {
+		Self {
+			host,
+			server_name,
+			system_version: Default::default(),
+			spec_name: Default::default(),
+			spec_version: Default::default(),
+			genesis_hash: Default::default(),
+		}
+	}
+
 	pub fn network(&self) -> String {
 		format!(
 			"{host}/{system_version}/{spec_name}/{spec_version}",
@@ -121,6 +255,7 @@ impl Default for Node {
 	fn default() -> Self {
 		Self {
 			host: "{host}".to_string(),
+			server_name: None,
 			system_version: "{system_version}".to_string(),
 			spec_name: "data-avail".to_string(),
 			spec_version: 0,
@@ -135,83 +270,362 @@ impl Display for Node {
 	}
 }
 
+/// Last node which was successfully used, persisted so it is tried first after restart.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct LastKnownNode {
+	pub host: String,
+	pub genesis_hash: H256,
+	pub spec_version: u32,
+}
+
+impl From<&Node> for LastKnownNode {
+	fn from(node: &Node) -> Self {
+		LastKnownNode {
+			host: node.host.clone(),
+			genesis_hash: node.genesis_hash,
+			spec_version: node.spec_version,
+		}
+	}
+}
+
+/// Number of samples after which node score counters are halved,
+/// so that old failures are gradually forgotten.
+const NODE_SCORE_WINDOW: u64 = 1000;
+
+/// Weight given to the latest latency sample in the moving average (1/N).
+const NODE_LATENCY_SAMPLES: u64 = 5;
+
+/// Minimal node weight, keeps misbehaving nodes selectable as a last resort.
+const MIN_NODE_WEIGHT: f64 = 0.001;
+
+/// Health statistics of a single RPC node, used for weighted node selection.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, Encode, Decode)]
+pub struct NodeScore {
+	/// Moving average of the request latency in milliseconds
+	pub latency_ms: u64,
+	/// Number of successful requests
+	pub successes: u64,
+	/// Number of failed requests and connection attempts
+	pub errors: u64,
+	/// Number of dropped subscription streams
+	pub subscription_drops: u64,
+}
+
+impl NodeScore {
+	fn record_success(&mut self, latency: Duration) {
+		let latency_ms = latency.as_millis() as u64;
+		self.latency_ms = if self.successes == 0 {
+			latency_ms
+		} else {
+			(self.latency_ms * (NODE_LATENCY_SAMPLES - 1) + latency_ms) / NODE_LATENCY_SAMPLES
+		};
+		self.successes += 1;
+		self.decay();
+	}
+
+	fn record_error(&mut self) {
+		self.errors += 1;
+		self.decay();
+	}
+
+	fn record_subscription_drop(&mut self) {
+		self.subscription_drops += 1;
+	}
+
+	fn decay(&mut self) {
+		if self.successes + self.errors < NODE_SCORE_WINDOW {
+			return;
+		}
+		self.successes /= 2;
+		self.errors /= 2;
+		self.subscription_drops /= 2;
+	}
+
+	/// Calculates node selection weight from success rate, latency and subscription drops.
+	/// Nodes without any statistics get a neutral weight.
+	pub fn weight(&self) -> f64 {
+		// Laplace smoothing gives unknown nodes a success rate of 0.5
+		let success_rate = (self.successes + 1) as f64 / (self.successes + self.errors + 2) as f64;
+		let latency_factor = 1.0 / (1.0 + self.latency_ms as f64 / 1000.0);
+		let drops_factor = 1.0 / (1.0 + self.subscription_drops as f64);
+		(success_rate * latency_factor * drops_factor).max(MIN_NODE_WEIGHT)
+	}
+}
+
 #[derive(Clone)]
 pub struct Nodes {
-	list: Vec<Node>,
+	/// Configured endpoints, DNS names of which are resolved if DNS resolution is enabled
+	endpoints: Vec<String>,
+	/// Node candidates, either the configured endpoints or their resolved addresses
+	list: Arc<Mutex<Vec<Node>>>,
+	scores: Arc<Mutex<HashMap<String, NodeScore>>>,
+	breakers: Arc<Mutex<HashMap<String, CircuitBreaker>>>,
+	breaker_config: CircuitBreakerConfig,
+	/// Denied nodes are excluded from the resolved node candidates
+	policy: NodePolicy,
+	/// Nodes of other networks, which are never used again
+	rejected: Arc<Mutex<HashSet<String>>>,
+	/// Node used before the restart, tried first on the initial connection
+	last_known: Arc<Mutex<Option<LastKnownNode>>>,
+	limiter: RateLimiter,
 }
 
 impl Nodes {
-	pub fn new(nodes: &[String]) -> Self {
-		let candidates = nodes.to_owned();
+	pub fn new(
+		nodes: &[String],
+		policy: NodePolicy,
+		breaker_config: CircuitBreakerConfig,
+		rate_limit_config: RateLimitConfig,
+	) -> Self {
+		let candidates = nodes
+			.iter()
+			.map(|host| Node::candidate(host.to_string(), None))
+			.collect();
 		Self {
-			list: candidates
-				.iter()
-				.map(|s| Node {
-					spec_name: Default::default(),
-					genesis_hash: Default::default(),
-					spec_version: Default::default(),
-					system_version: Default::default(),
-					host: s.to_string(),
-				})
-				.collect(),
+			endpoints: nodes.to_owned(),
+			list: Arc::new(Mutex::new(candidates)),
+			scores: Default::default(),
+			breakers: Default::default(),
+			breaker_config,
+			policy,
+			rejected: Default::default(),
+			last_known: Default::default(),
+			limiter: RateLimiter::new(rate_limit_config),
 		}
 	}
 
-	/// Shuffles the list of available Nodes, excluding the host used for the current Subxt client creation.
+	/// Orders the list of available Nodes by weighted random selection, excluding the host used for the current Subxt client creation.
 	///
-	/// This method returns a new shuffled list of Nodes from the original list, excluding the Node
-	/// associated with the current Subxt client host.
-	/// The purpose of this exclusion is to prevent accidentally reconnecting to the same host in case of errors.
-	/// If there's a need to switch to a different host, the shuffled list provides a randomized order of available Nodes.
-	fn shuffle(&self, current_host: String) -> Vec<Node> {
-		if self.list.len() <= 1 {
-			return self.list.clone();
+	/// Nodes with higher score weight are more likely to be placed at the front of the list.
+	/// The current host is excluded to prevent accidentally reconnecting to the same host in case of errors.
+	/// Nodes with open circuit breaker are excluded until their backoff expires, and rejected nodes are always excluded.
+	fn weighted_shuffle(&self, current_host: &str) -> Vec<Node> {
+		let list = self.list.lock().unwrap();
+		let rejected = self.rejected.lock().unwrap();
+		if list.len() <= 1 {
+			return list
+				.iter()
+				.filter(|&Node { host, .. }| !rejected.contains(host))
+				.cloned()
+				.collect();
 		}
 
-		let mut list = self
-			.list
+		let scores = self.scores.lock().unwrap();
+		let mut breakers = self.breakers.lock().unwrap();
+		let now = Instant::now();
+		let mut rng = thread_rng();
+		// Weighted random sampling without replacement (Efraimidis-Spirakis),
+		// each node gets a key u^(1/w) and the nodes are sorted by the key, descending
+		let mut keyed = list
 			.iter()
-			.filter(|&Node { host, .. }| host != &current_host)
-			.cloned()
-			.collect::<Vec<Node>>();
-		list.shuffle(&mut thread_rng());
-		list
+			.filter(|&Node { host, .. }| host != current_host && !rejected.contains(host))
+			.filter(|&Node { host, .. }| {
+				breakers
+					.get_mut(host)
+					.map_or(true, |breaker| breaker.is_available(now))
+			})
+			.map(|node| {
+				let weight = scores.get(&node.host).cloned().unwrap_or_default().weight();
+				let key = rng.gen::<f64>().powf(1.0 / weight);
+				(key, node.clone())
+			})
+			.collect::<Vec<_>>();
+		keyed.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+		keyed.into_iter().map(|(_, node)| node).collect()
+	}
+
+	fn update_score(&self, host: &str, update: impl FnOnce(&mut NodeScore)) {
+		let mut scores = self.scores.lock().unwrap();
+		update(scores.entry(host.to_string()).or_default());
+	}
+
+	pub fn record_success(&self, host: &str, latency: Duration) {
+		self.update_score(host, |score| score.record_success(latency));
+		let mut breakers = self.breakers.lock().unwrap();
+		breakers.entry(host.to_string()).or_default().on_success();
 	}
 
-	pub fn iter(&self) -> NodesIterator {
-		NodesIterator {
-			nodes: self,
-			current_index: 0,
+	pub fn record_error(&self, host: &str) {
+		self.update_score(host, NodeScore::record_error);
+		let now = Instant::now();
+		let mut breakers = self.breakers.lock().unwrap();
+		let breaker = breakers.entry(host.to_string()).or_default();
+		let was_open = breaker.state(now) == BreakerState::Open;
+		breaker.on_failure(now, &self.breaker_config);
+		if let (false, Some(retry_in)) = (was_open, breaker.retry_in(now)) {
+			warn!(
+				host,
+				"Circuit breaker opened, node will be retried in {retry_in:?}"
+			);
 		}
 	}
-}
 
-pub struct NodesIterator<'a> {
-	nodes: &'a Nodes,
-	current_index: usize,
-}
+	/// Waits until the rate limit and the concurrency cap of the host allow sending the request.
+	/// Returned permit has to be held until the request completes.
+	pub async fn acquire(&self, host: &str) -> OwnedSemaphorePermit {
+		self.limiter.acquire(host).await
+	}
+
+	/// Rejects the node permanently, e.g. if it belongs to another network.
+	pub fn reject(&self, host: &str) {
+		self.rejected.lock().unwrap().insert(host.to_string());
+	}
+
+	/// Returns `true` if circuit breaker of the given host allows sending requests.
+	pub fn is_available(&self, host: &str) -> bool {
+		let mut breakers = self.breakers.lock().unwrap();
+		breakers
+			.get_mut(host)
+			.map_or(true, |breaker| breaker.is_available(Instant::now()))
+	}
+
+	/// Returns circuit breaker states of all configured nodes.
+	pub fn breaker_states(&self) -> Vec<(String, BreakerState)> {
+		let now = Instant::now();
+		// Node list is locked first, same as by the weighted shuffle
+		let list = self.list.lock().unwrap();
+		let mut breakers = self.breakers.lock().unwrap();
+		list.iter()
+			.map(|Node { host, .. }| {
+				let state = breakers
+					.get_mut(host)
+					.map_or(BreakerState::Closed, |breaker| breaker.state(now));
+				(host.clone(), state)
+			})
+			.collect()
+	}
 
-impl<'a> Iterator for NodesIterator<'a> {
-	type Item = &'a Node;
+	pub fn record_subscription_drop(&self, host: &str) {
+		self.update_score(host, NodeScore::record_subscription_drop);
+	}
+
+	/// Returns scores of all configured nodes.
+	pub fn scores(&self) -> Vec<(String, NodeScore)> {
+		let list = self.list.lock().unwrap();
+		let scores = self.scores.lock().unwrap();
+		list.iter()
+			.filter_map(|Node { host, .. }| {
+				scores.get(host).map(|score| (host.clone(), score.clone()))
+			})
+			.collect()
+	}
+
+	/// Loads previously persisted scores, ignoring hosts which are no longer configured.
+	pub fn load_scores(&self, persisted: Vec<(String, NodeScore)>) {
+		let list = self.list.lock().unwrap();
+		let mut scores = self.scores.lock().unwrap();
+		for (host, score) in persisted {
+			if list.iter().any(|node| node.host == host) {
+				scores.insert(host, score);
+			}
+		}
+	}
+
+	/// Loads previously persisted last known node, ignoring the host which is no longer configured.
+	pub fn load_last_known(&self, persisted: LastKnownNode) {
+		let list = self.list.lock().unwrap();
+		if list.iter().any(|node| node.host == persisted.host) {
+			*self.last_known.lock().unwrap() = Some(persisted);
+		}
+	}
 
-	fn next(&mut self) -> Option<Self::Item> {
-		let res = self.nodes.list.get(self.current_index);
-		self.current_index += 1;
-		res
+	/// Takes the last known node, so it is tried first only once.
+	pub fn take_last_known(&self) -> Option<LastKnownNode> {
+		self.last_known.lock().unwrap().take()
+	}
+
+	/// Returns DNS name of the node, if its host is the resolved address of the configured endpoint.
+	pub fn server_name(&self, host: &str) -> Option<String> {
+		let list = self.list.lock().unwrap();
+		let node = list.iter().find(|node| node.host == host)?;
+		node.server_name.clone()
+	}
+
+	/// Resolves DNS names of the configured endpoints, replacing the node candidates with the resolved addresses.
+	/// Endpoints which cannot be resolved are used by their names, and denied addresses are excluded.
+	pub async fn resolve(&self) {
+		let resolver = match TokioAsyncResolver::tokio_from_system_conf() {
+			Ok(resolver) => resolver,
+			Err(error) => {
+				warn!("Cannot create DNS resolver: {error}");
+				return;
+			},
+		};
+		let mut candidates = resolution::resolve(&resolver, &self.endpoints).await;
+		candidates.retain(|node| !self.policy.is_denied(node));
+		if candidates.is_empty() {
+			warn!("All resolved RPC node candidates are denied, keeping the previous candidates");
+			return;
+		}
+		let mut list = self.list.lock().unwrap();
+		let hosts = |list: &[Node]| {
+			list.iter()
+				.map(|node| node.host.clone())
+				.collect::<Vec<_>>()
+		};
+		if hosts(&list) != hosts(&candidates) {
+			info!("RPC node candidates: {:?}", hosts(&candidates));
+		}
+		*list = candidates;
+	}
+
+	pub fn list(&self) -> Vec<Node> {
+		self.list.lock().unwrap().clone()
 	}
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn init<T: Database>(
 	db: T,
 	state: Arc<Mutex<State>>,
 	nodes: &[String],
 	genesis_hash: &str,
+	expected_node: ExpectedNodeVariant,
 	retry_config: RetryConfig,
+	node_policy: NodePolicy,
+	breaker_config: CircuitBreakerConfig,
+	rate_limit_config: RateLimitConfig,
+	transport_config: TransportConfig,
+	parallel_fetch: ParallelFetchConfig,
+	health_check: HealthCheckConfig,
+	race_nodes: usize,
+	event_buffer_size: usize,
+	metrics: SharedMetrics,
 ) -> Result<(Client, broadcast::Sender<Event>, SubscriptionLoop<T>)> {
-	let rpc_client =
-		Client::new(state.clone(), Nodes::new(nodes), genesis_hash, retry_config).await?;
-	// create output channel for RPC Subscription Events
-	let (event_sender, _) = broadcast::channel(1000);
+	let transport =
+		Transport::new(&transport_config).wrap_err("Invalid RPC transport configuration")?;
+	let endpoints = node_policy.endpoints(nodes)?;
+	let nodes = Nodes::new(&endpoints, node_policy, breaker_config, rate_limit_config);
+	if transport_config.resolve_dns {
+		nodes.resolve().await;
+	}
+	// restore node scores, so unreliable nodes are avoided after restart
+	match db.get(Key::RpcNodeScores) {
+		Ok(Some(scores)) => nodes.load_scores(scores),
+		Ok(None) => (),
+		Err(error) => warn!("Cannot load RPC node scores: {error:#}"),
+	}
+	// restore last known node, so the node used before restart is tried first
+	match db.get(Key::RpcLastNode) {
+		Ok(Some(last_known)) => nodes.load_last_known(last_known),
+		Ok(None) => (),
+		Err(error) => warn!("Cannot load last known RPC node: {error:#}"),
+	}
+	let rpc_client = Client::new(
+		state.clone(),
+		nodes,
+		genesis_hash,
+		expected_node,
+		transport,
+		retry_config,
+		parallel_fetch,
+		health_check,
+		race_nodes,
+		metrics,
+	)
+	.await?;
+	// create output channel for RPC Subscription Events, lagging receivers skip the oldest events
+	let (event_sender, _) = broadcast::channel(event_buffer_size.max(1));
 	let subscriptions =
 		SubscriptionLoop::new(state, db, rpc_client.clone(), event_sender.clone()).await?;
 
@@ -220,22 +634,44 @@ pub async fn init<T: Database>(
 
 /// Generates random cell positions for sampling
 pub fn generate_random_cells(dimensions: Dimensions, cell_count: u32) -> Vec<Position> {
-	let max_cells = dimensions.extended_size();
-	let count = if max_cells < cell_count {
-		debug!("Max cells count {max_cells} is lesser than cell_count {cell_count}");
-		max_cells
-	} else {
-		cell_count
-	};
-	let mut rng = thread_rng();
-	let mut indices = HashSet::new();
-	while (indices.len() as u16) < count as u16 {
-		let col = rng.gen_range(0..dimensions.cols().into());
-		let row = rng.gen_range(0..dimensions.extended_rows());
-		indices.insert(Position { row, col });
-	}
+	SamplingStrategy::Uniform.sample(dimensions, cell_count, &mut thread_rng())
+}
 
-	indices.into_iter().collect::<Vec<_>>()
+/// Generates cell positions for sampling, derived from the `blake2_256(seed || block_hash)`,
+/// so the sampled cells can be reproduced for the given seed and block.
+pub fn generate_seeded_cells(
+	dimensions: Dimensions,
+	cell_count: u32,
+	seed: &[u8],
+	block_hash: H256,
+	strategy: SamplingStrategy,
+) -> Vec<Position> {
+	let rng_seed = blake2_256(&[seed, block_hash.as_bytes()].concat());
+	strategy.sample(
+		dimensions,
+		cell_count,
+		&mut ChaCha20Rng::from_seed(rng_seed),
+	)
+}
+
+/// Generates cell positions for sampling, seeded if sampling seed is given, otherwise random.
+pub fn generate_cells(
+	dimensions: Dimensions,
+	cell_count: u32,
+	seed: Option<&str>,
+	block_hash: H256,
+	strategy: SamplingStrategy,
+) -> Vec<Position> {
+	match seed {
+		Some(seed) => generate_seeded_cells(
+			dimensions,
+			cell_count,
+			seed.as_bytes(),
+			block_hash,
+			strategy,
+		),
+		None => strategy.sample(dimensions, cell_count, &mut thread_rng()),
+	}
 }
 
 /* @note: fn to take the number of cells needs to get equal to or greater than
@@ -243,8 +679,28 @@ the percentage of confidence mentioned in config file */
 
 pub const CELL_COUNT_99_99: u32 = 14;
 
-/// Calculates number of cells required to achieve given confidence
-pub fn cell_count_for_confidence(confidence: f64) -> u32 {
+/// Confidence requires more cells than the configured maximum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellCountExceeded {
+	pub confidence: f64,
+	pub required: u32,
+	pub max: u32,
+}
+
+impl Display for CellCountExceeded {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"Confidence of {} requires {} cells, which exceeds maximum of {} cells",
+			self.confidence, self.required, self.max
+		)
+	}
+}
+
+impl std::error::Error for CellCountExceeded {}
+
+/// Calculates number of cells required to achieve given confidence, without upper bound
+pub fn required_cell_count(confidence: f64) -> u32 {
 	let mut cell_count: u32;
 	if !(50.0..=100f64).contains(&confidence) {
 		//in this default of 8 cells will be taken
@@ -262,24 +718,230 @@ pub fn cell_count_for_confidence(confidence: f64) -> u32 {
 			confidence
 		);
 		cell_count = 1;
-	} else if cell_count > CELL_COUNT_99_99 {
-		info!(
-			"confidence of {} is invalid so taking confidence of 99.99",
-			confidence
-		);
-		cell_count = CELL_COUNT_99_99;
 	}
 	cell_count
 }
 
+/// Calculates number of cells required to achieve given confidence,
+/// returns an error if more than `max_cell_count` cells are required
+pub fn cell_count_for_confidence(
+	confidence: f64,
+	max_cell_count: u32,
+) -> Result<u32, CellCountExceeded> {
+	let required = required_cell_count(confidence);
+	if required > max_cell_count {
+		return Err(CellCountExceeded {
+			confidence,
+			required,
+			max: max_cell_count,
+		});
+	}
+	Ok(required)
+}
+
+/// Calculates number of cells required to achieve given confidence,
+/// bounded by the `max_cell_count` (with warning if the bound is applied)
+pub fn bounded_cell_count(confidence: f64, max_cell_count: u32) -> u32 {
+	cell_count_for_confidence(confidence, max_cell_count).unwrap_or_else(|error| {
+		warn!("{error}, sampling {} cells instead", error.max);
+		error.max
+	})
+}
+
 pub async fn wait_for_finalized_header(
 	mut rpc_events_receiver: broadcast::Receiver<Event>,
 	timeout_seconds: u64,
 ) -> Result<Header> {
 	let timeout_seconds = time::Duration::from_secs(timeout_seconds);
-	match timeout(timeout_seconds, rpc_events_receiver.recv()).await {
-		Ok(Ok(rpc::Event::HeaderUpdate { header, .. })) => Ok(header),
-		Ok(Err(error)) => Err(eyre!("Failed to receive finalized header: {error}")),
+	let first_header = async {
+		loop {
+			match rpc_events_receiver.recv().await {
+				Ok(rpc::Event::HeaderUpdate { header, .. }) => return Ok(header),
+				Ok(_) => continue,
+				Err(error) => return Err(eyre!("Failed to receive finalized header: {error}")),
+			}
+		}
+	};
+	match timeout(timeout_seconds, first_header).await {
+		Ok(result) => result,
 		Err(_) => Err(eyre!("Timeout on waiting for first finalized header")),
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn nodes(hosts: &[&str]) -> Nodes {
+		let hosts = hosts.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+		Nodes::new(
+			&hosts,
+			NodePolicy::default(),
+			CircuitBreakerConfig::default(),
+			RateLimitConfig::default(),
+		)
+	}
+
+	#[test]
+	fn test_node_score_weight() {
+		let unknown = NodeScore::default();
+
+		let mut healthy = NodeScore::default();
+		(0..10).for_each(|_| healthy.record_success(Duration::from_millis(100)));
+
+		let mut failing = NodeScore::default();
+		(0..10).for_each(|_| failing.record_error());
+
+		let mut dropping = healthy.clone();
+		(0..3).for_each(|_| dropping.record_subscription_drop());
+
+		assert!(healthy.weight() > unknown.weight());
+		assert!(unknown.weight() > failing.weight());
+		assert!(healthy.weight() > dropping.weight());
+		assert!(failing.weight() >= MIN_NODE_WEIGHT);
+	}
+
+	#[test]
+	fn test_node_score_decay() {
+		let mut score = NodeScore::default();
+		(0..NODE_SCORE_WINDOW).for_each(|_| score.record_error());
+		assert_eq!(score.errors, NODE_SCORE_WINDOW / 2);
+	}
+
+	#[test]
+	fn test_weighted_shuffle_excludes_current_host() {
+		let nodes = nodes(&["ws://a", "ws://b", "ws://c"]);
+		let shuffled = nodes.weighted_shuffle("ws://a");
+		assert_eq!(shuffled.len(), 2);
+		assert!(shuffled.iter().all(|node| node.host != "ws://a"));
+	}
+
+	#[test]
+	fn test_weighted_shuffle_prefers_healthy_nodes() {
+		let nodes = nodes(&["ws://healthy", "ws://failing"]);
+		(0..100).for_each(|_| {
+			nodes.record_success("ws://healthy", Duration::from_millis(50));
+			nodes.record_error("ws://failing");
+		});
+
+		let first_healthy = (0..100)
+			.filter(|_| nodes.weighted_shuffle("")[0].host == "ws://healthy")
+			.count();
+		assert!(first_healthy > 90);
+	}
+
+	#[test]
+	fn test_weighted_shuffle_skips_open_breakers() {
+		let nodes = nodes(&["ws://a", "ws://b", "ws://c"]);
+		(0..CircuitBreakerConfig::default().failure_threshold)
+			.for_each(|_| nodes.record_error("ws://b"));
+
+		assert!(!nodes.is_available("ws://b"));
+		let shuffled = nodes.weighted_shuffle("ws://a");
+		assert_eq!(shuffled.len(), 1);
+		assert_eq!(shuffled[0].host, "ws://c");
+
+		nodes.record_success("ws://b", Duration::from_millis(10));
+		assert!(nodes.is_available("ws://b"));
+	}
+
+	#[test]
+	fn test_weighted_shuffle_skips_rejected_nodes() {
+		let nodes = nodes(&["ws://a", "ws://b", "ws://c"]);
+		nodes.reject("ws://b");
+		let shuffled = nodes.weighted_shuffle("ws://a");
+		assert_eq!(shuffled.len(), 1);
+		assert_eq!(shuffled[0].host, "ws://c");
+
+		let single = self::nodes(&["ws://a"]);
+		single.reject("ws://a");
+		assert!(single.weighted_shuffle("").is_empty());
+	}
+
+	#[test]
+	fn test_expected_genesis() {
+		let hash = H256::repeat_byte(1);
+		let expected = ExpectedGenesis::from_config(&format!("{hash:?}")).unwrap();
+		assert_eq!(expected.check(hash), Ok(()));
+		assert_eq!(expected.check(H256::repeat_byte(2)), Err(hash));
+
+		let pinned = ExpectedGenesis::from_config("DEV123").unwrap();
+		assert_eq!(pinned.hash(), None);
+		assert_eq!(pinned.check(hash), Ok(()));
+		assert_eq!(pinned.check(H256::repeat_byte(2)), Err(hash));
+
+		assert!(ExpectedGenesis::from_config("").is_err());
+		assert!(ExpectedGenesis::from_config("0x1234").is_err());
+	}
+
+	#[test]
+	fn test_cell_count_exceeds_maximum() {
+		assert_eq!(cell_count_for_confidence(99.999, 20), Ok(17));
+		assert_eq!(
+			cell_count_for_confidence(99.999, 14),
+			Err(CellCountExceeded {
+				confidence: 99.999,
+				required: 17,
+				max: 14
+			})
+		);
+		assert_eq!(bounded_cell_count(99.999, 14), 14);
+		assert_eq!(required_cell_count(100.0), u32::MAX);
+	}
+
+	#[test]
+	fn test_seeded_cells_are_reproducible() {
+		let dimensions = Dimensions::new(16, 64).unwrap();
+		let hash = H256::repeat_byte(1);
+		let cells =
+			generate_seeded_cells(dimensions, 10, b"audit", hash, SamplingStrategy::Uniform);
+		assert_eq!(cells.len(), 10);
+		assert_eq!(
+			cells,
+			generate_seeded_cells(dimensions, 10, b"audit", hash, SamplingStrategy::Uniform)
+		);
+		assert_ne!(
+			cells,
+			generate_seeded_cells(
+				dimensions,
+				10,
+				b"audit",
+				H256::repeat_byte(2),
+				SamplingStrategy::Uniform
+			)
+		);
+		assert_ne!(
+			cells,
+			generate_seeded_cells(dimensions, 10, b"other", hash, SamplingStrategy::Uniform)
+		);
+	}
+
+	#[test]
+	fn test_load_scores_ignores_unknown_hosts() {
+		let nodes = nodes(&["ws://a"]);
+		nodes.load_scores(vec![
+			("ws://a".to_string(), NodeScore::default()),
+			("ws://removed".to_string(), NodeScore::default()),
+		]);
+		assert_eq!(
+			nodes.scores(),
+			vec![("ws://a".to_string(), NodeScore::default())]
+		);
+	}
+
+	#[test]
+	fn test_last_known_node_is_taken_once() {
+		let nodes = nodes(&["ws://a", "ws://b"]);
+		let last_known = |host: &str| LastKnownNode {
+			host: host.to_string(),
+			genesis_hash: H256::repeat_byte(1),
+			spec_version: 20,
+		};
+		nodes.load_last_known(last_known("ws://removed"));
+		assert_eq!(nodes.take_last_known(), None);
+
+		nodes.load_last_known(last_known("ws://b"));
+		assert_eq!(nodes.take_last_known(), Some(last_known("ws://b")));
+		assert_eq!(nodes.take_last_known(), None);
+	}
+}
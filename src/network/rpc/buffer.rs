@@ -0,0 +1,104 @@
+//! Reusable buffers of the cell proof responses, so the fetched cells are decoded without allocations per response.
+
+use serde::{
+	de::{SeqAccess, Visitor},
+	Deserialize, Deserializer,
+};
+use std::{fmt, ops::Deref, sync::Mutex};
+
+use super::CELL_WITH_PROOF_SIZE;
+
+/// Number of cells with proofs which fit into the new buffer
+const BUFFER_CELLS: usize = 64;
+/// Maximum number of cells with proofs in a buffer which is returned to the pool
+const MAX_POOLED_BUFFER_CELLS: usize = 1024;
+/// Maximum number of buffers kept in the pool
+const MAX_POOLED_BUFFERS: usize = 32;
+
+static POOL: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+/// Buffer of the cells with proofs, returned to the pool on drop.
+#[derive(Debug)]
+pub struct ProofBuffer(Vec<u8>);
+
+impl ProofBuffer {
+	fn take() -> Self {
+		let pooled = POOL.lock().expect("Lock should be acquired").pop();
+		ProofBuffer(
+			pooled.unwrap_or_else(|| Vec::with_capacity(BUFFER_CELLS * CELL_WITH_PROOF_SIZE)),
+		)
+	}
+}
+
+impl Deref for ProofBuffer {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		&self.0
+	}
+}
+
+impl Drop for ProofBuffer {
+	fn drop(&mut self) {
+		if self.0.capacity() > MAX_POOLED_BUFFER_CELLS * CELL_WITH_PROOF_SIZE {
+			return;
+		}
+		let mut buffer = std::mem::take(&mut self.0);
+		buffer.clear();
+		let mut pool = POOL.lock().expect("Lock should be acquired");
+		if pool.len() < MAX_POOLED_BUFFERS {
+			pool.push(buffer);
+		}
+	}
+}
+
+struct ProofBufferVisitor;
+
+impl<'de> Visitor<'de> for ProofBufferVisitor {
+	type Value = ProofBuffer;
+
+	fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+		formatter.write_str("cells with proofs bytes")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let mut buffer = ProofBuffer::take();
+		if let Some(size) = seq.size_hint() {
+			buffer.0.reserve(size);
+		}
+		while let Some(byte) = seq.next_element::<u8>()? {
+			buffer.0.push(byte);
+		}
+		Ok(buffer)
+	}
+
+	fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+		let mut buffer = ProofBuffer::take();
+		buffer.0.extend_from_slice(bytes);
+		Ok(buffer)
+	}
+}
+
+impl<'de> Deserialize<'de> for ProofBuffer {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_seq(ProofBufferVisitor)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ProofBuffer, CELL_WITH_PROOF_SIZE};
+
+	#[test]
+	fn decodes_response_into_pooled_buffer() {
+		let response = serde_json::to_string(&vec![7u8; 2 * CELL_WITH_PROOF_SIZE]).unwrap();
+		let buffer: ProofBuffer = serde_json::from_str(&response).unwrap();
+		assert_eq!(buffer.len(), 2 * CELL_WITH_PROOF_SIZE);
+		assert!(buffer.iter().all(|&byte| byte == 7));
+		drop(buffer);
+
+		let buffer: ProofBuffer = serde_json::from_str("[1, 2, 3]").unwrap();
+		assert_eq!(&*buffer, &[1, 2, 3]);
+		assert!(serde_json::from_str::<ProofBuffer>("[256]").is_err());
+	}
+}
@@ -0,0 +1,172 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+use crate::types::RuntimeConfig;
+
+/// Circuit breaker configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct CircuitBreakerConfig {
+	pub failure_threshold: u32,
+	pub backoff_base: Duration,
+	pub backoff_max: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+	fn default() -> Self {
+		(&RuntimeConfig::default()).into()
+	}
+}
+
+impl From<&RuntimeConfig> for CircuitBreakerConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		Self {
+			failure_threshold: val.rpc_breaker_failure_threshold.max(1),
+			backoff_base: Duration::from_secs(val.rpc_breaker_backoff_base),
+			backoff_max: Duration::from_secs(val.rpc_breaker_backoff_max),
+		}
+	}
+}
+
+/// State of the per host circuit breaker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BreakerState {
+	/// Requests are sent to the host
+	Closed,
+	/// Host is failing, requests are not sent until backoff expires
+	Open,
+	/// Backoff expired, next request is a trial which closes or reopens the breaker
+	HalfOpen,
+}
+
+/// Circuit breaker with exponential backoff, tracking consecutive failures of a single host.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+	state: BreakerState,
+	consecutive_failures: u32,
+	/// Number of times breaker has been opened since it was last closed
+	openings: u32,
+	opened_at: Option<Instant>,
+	backoff: Duration,
+}
+
+impl Default for CircuitBreaker {
+	fn default() -> Self {
+		Self {
+			state: BreakerState::Closed,
+			consecutive_failures: 0,
+			openings: 0,
+			opened_at: None,
+			backoff: Duration::ZERO,
+		}
+	}
+}
+
+impl CircuitBreaker {
+	/// Returns breaker state, transitioning from open to half-open once the backoff expires.
+	pub fn state(&mut self, now: Instant) -> BreakerState {
+		if self.state == BreakerState::Open
+			&& self
+				.opened_at
+				.is_some_and(|opened_at| now.duration_since(opened_at) >= self.backoff)
+		{
+			self.state = BreakerState::HalfOpen;
+		}
+		self.state
+	}
+
+	/// Returns `true` if requests can be sent to the host.
+	pub fn is_available(&mut self, now: Instant) -> bool {
+		self.state(now) != BreakerState::Open
+	}
+
+	/// Remaining time until the open breaker transitions to half-open.
+	pub fn retry_in(&self, now: Instant) -> Option<Duration> {
+		let opened_at = self.opened_at?;
+		(self.state == BreakerState::Open)
+			.then(|| self.backoff.saturating_sub(now.duration_since(opened_at)))
+	}
+
+	pub fn on_success(&mut self) {
+		*self = Self::default();
+	}
+
+	pub fn on_failure(&mut self, now: Instant, cfg: &CircuitBreakerConfig) {
+		self.consecutive_failures += 1;
+		let trial_failed = self.state(now) == BreakerState::HalfOpen;
+		if trial_failed || self.consecutive_failures >= cfg.failure_threshold {
+			self.open(now, cfg);
+		}
+	}
+
+	fn open(&mut self, now: Instant, cfg: &CircuitBreakerConfig) {
+		// backoff doubles with each opening: base, 2 * base, 4 * base, ... up to the max
+		let factor = 2u32.saturating_pow(self.openings);
+		self.backoff = cfg.backoff_base.saturating_mul(factor).min(cfg.backoff_max);
+		self.openings = self.openings.saturating_add(1);
+		self.opened_at = Some(now);
+		self.state = BreakerState::Open;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> CircuitBreakerConfig {
+		CircuitBreakerConfig {
+			failure_threshold: 3,
+			backoff_base: Duration::from_secs(1),
+			backoff_max: Duration::from_secs(5),
+		}
+	}
+
+	#[test]
+	fn test_breaker_opens_after_threshold() {
+		let cfg = config();
+		let now = Instant::now();
+		let mut breaker = CircuitBreaker::default();
+
+		breaker.on_failure(now, &cfg);
+		breaker.on_failure(now, &cfg);
+		assert_eq!(breaker.state(now), BreakerState::Closed);
+
+		breaker.on_failure(now, &cfg);
+		assert_eq!(breaker.state(now), BreakerState::Open);
+		assert!(!breaker.is_available(now));
+		assert_eq!(breaker.retry_in(now), Some(Duration::from_secs(1)));
+	}
+
+	#[test]
+	fn test_breaker_half_open_after_backoff() {
+		let cfg = config();
+		let now = Instant::now();
+		let mut breaker = CircuitBreaker::default();
+		(0..3).for_each(|_| breaker.on_failure(now, &cfg));
+
+		let later = now + Duration::from_secs(1);
+		assert_eq!(breaker.state(later), BreakerState::HalfOpen);
+		assert!(breaker.is_available(later));
+
+		breaker.on_success();
+		assert_eq!(breaker.state(later), BreakerState::Closed);
+	}
+
+	#[test]
+	fn test_breaker_backoff_is_exponential_and_capped() {
+		let cfg = config();
+		let mut now = Instant::now();
+		let mut breaker = CircuitBreaker::default();
+		(0..3).for_each(|_| breaker.on_failure(now, &cfg));
+
+		let mut backoffs = vec![];
+		for _ in 0..4 {
+			let backoff = breaker.retry_in(now).unwrap();
+			backoffs.push(backoff.as_secs());
+			now += backoff;
+			// failed trial request reopens the breaker
+			breaker.on_failure(now, &cfg);
+		}
+		assert_eq!(backoffs, vec![1, 2, 4, 5]);
+	}
+}
@@ -1,35 +1,51 @@
 use avail_subxt::{
 	api::{self, runtime_types::sp_core::crypto::KeyTypeId},
 	avail::{self, Pair},
-	build_client,
 	primitives::Header,
 	utils::H256,
 	AvailConfig,
 };
-use color_eyre::{eyre::eyre, Report, Result};
-use futures::{Stream, TryFutureExt, TryStreamExt};
+use codec::Encode;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Report, Result,
+};
+use futures::{future::join_all, stream::FuturesUnordered, Stream, TryFutureExt, TryStreamExt};
 use kate_recovery::{data::Cell, matrix::Position};
 use sp_core::{
-	bytes::from_hex,
+	blake2_256,
 	ed25519::{self, Public},
 };
-use std::sync::{Arc, Mutex};
+use std::{
+	collections::HashMap,
+	future::Future,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
 use subxt::{
 	rpc::{types::BlockNumber, RpcParams},
 	rpc_params,
 	storage::StorageKey,
-	tx::{PairSigner, SubmittableExtrinsic},
+	tx::{PairSigner, SubmittableExtrinsic, TxProgress},
 	utils::AccountId32,
 };
-use tokio::sync::RwLock;
-use tokio_retry::Retry;
+use tokio::{
+	sync::{broadcast, RwLock},
+	time::{interval, sleep, timeout, MissedTickBehavior},
+};
+use tokio_retry::{Retry, RetryIf};
 use tokio_stream::StreamExt;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
-use super::{Node, Nodes, Subscription, WrappedProof, CELL_WITH_PROOF_SIZE};
+use super::{
+	buffer::ProofBuffer, BreakerState, ExpectedGenesis, Node, NodeScore, Nodes, RpcError, RpcEvent,
+	Subscription, Transport, WrappedProof, CELL_WITH_PROOF_SIZE,
+};
 use crate::{
 	consts::ExpectedNodeVariant,
-	types::{RetryConfig, RuntimeVersion, State, DEV_FLAG_GENHASH},
+	network::tuning::BatchSize,
+	telemetry::{MetricValue, Metrics, SharedMetrics},
+	types::{HealthCheckConfig, ParallelFetchConfig, RetryConfig, RuntimeVersion, State},
 };
 
 #[derive(Clone)]
@@ -38,26 +54,51 @@ pub struct Client {
 	state: Arc<Mutex<State>>,
 	nodes: Nodes,
 	retry_config: RetryConfig,
-	expected_genesis_hash: String,
+	expected_genesis: ExpectedGenesis,
+	expected_node: ExpectedNodeVariant,
+	transport: Transport,
+	parallel_fetch: ParallelFetchConfig,
+	/// Number of cells per proof request, tuned if fetch tuning is enabled
+	cells_per_request: BatchSize,
+	health_check: HealthCheckConfig,
+	/// Additional connections used for parallel cell fetching
+	pool: Arc<RwLock<HashMap<String, avail::Client>>>,
+	events: broadcast::Sender<RpcEvent>,
+	metrics: SharedMetrics,
 }
 
+const RPC_EVENTS_CAPACITY: usize = 100;
+/// Delay before the best headers subscription is recreated
+const BEST_HEADERS_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
 impl Client {
+	#[allow(clippy::too_many_arguments)]
 	pub async fn new(
 		state: Arc<Mutex<State>>,
 		nodes: Nodes,
 		expected_genesis_hash: &str,
+		expected_node: ExpectedNodeVariant,
+		transport: Transport,
 		retry_config: RetryConfig,
+		parallel_fetch: ParallelFetchConfig,
+		health_check: HealthCheckConfig,
+		race_nodes: usize,
+		metrics: SharedMetrics,
 	) -> Result<Self> {
-		// try and connect appropriate Node from the provided list
+		let (events, _) = broadcast::channel(RPC_EVENTS_CAPACITY);
+		let expected_genesis = ExpectedGenesis::from_config(expected_genesis_hash)?;
+
+		// try and connect the fastest appropriate Node from the provided list
 		// will do retries with the provided Retry Config
-		let (client, node, _) = Retry::spawn(retry_config.clone(), || async {
-			Self::try_connect_and_execute(
-				nodes.shuffle(Default::default()),
-				ExpectedNodeVariant::new(),
-				expected_genesis_hash,
-				|_| futures::future::ok(()),
+		let (client, node) = Retry::spawn(retry_config.clone(), || {
+			Self::race_connect(
+				&nodes,
+				&events,
+				&expected_node,
+				&expected_genesis,
+				&transport,
+				race_nodes,
 			)
-			.await
 		})
 		.await?;
 
@@ -69,57 +110,88 @@ impl Client {
 			state,
 			nodes,
 			retry_config,
-			expected_genesis_hash: expected_genesis_hash.to_string(),
+			expected_genesis,
+			expected_node,
+			transport,
+			parallel_fetch,
+			cells_per_request: BatchSize::new(
+				"RPC cells per request",
+				parallel_fetch.max_cells_per_node,
+				parallel_fetch.tuning,
+			),
+			health_check,
+			pool: Default::default(),
+			events,
+			metrics,
 		})
 	}
 
 	async fn create_subxt_client(
 		host: &str,
+		nodes: &Nodes,
+		events: &broadcast::Sender<RpcEvent>,
 		expected_node: ExpectedNodeVariant,
-		expected_genesis_hash: &str,
+		expected_genesis: &ExpectedGenesis,
+		transport: &Transport,
 	) -> Result<(avail::Client, Node)> {
-		let (client, _) = build_client(host, false).await.map_err(|e| eyre!(e))?;
+		let server_name = nodes.server_name(host);
+		let client = transport
+			.connect(host, server_name.as_deref())
+			.await
+			.wrap_err(RpcError::Disconnected)?;
 
-		// check genesis hash
+		// check genesis hash, nodes of other networks are never used again
 		let genesis_hash = client.genesis_hash();
 		info!("Genesis hash: {:?}", genesis_hash);
-		if let Some(cfg_genhash) = from_hex(expected_genesis_hash)
-			.ok()
-			.and_then(|e| TryInto::<[u8; 32]>::try_into(e).ok().map(H256::from))
-		{
-			if !genesis_hash.eq(&cfg_genhash) {
-				Err(eyre!(
-					"Genesis hash doesn't match the configured one! Change the config or the node url ({}).", host
-				))?
-			}
-		} else if expected_genesis_hash.starts_with(DEV_FLAG_GENHASH) {
-			warn!("Genesis hash configured for development ({}), skipping the genesis hash check entirely.", expected_genesis_hash);
-		} else {
-			Err(eyre!(
-				"Genesis hash invalid, badly configured or missing (\"{}\").",
-				expected_genesis_hash
-			))?
-		};
+		if let Err(expected) = expected_genesis.check(genesis_hash) {
+			nodes.reject(host);
+			let _ = events.send(RpcEvent::GenesisMismatch {
+				host: host.to_string(),
+				expected,
+				found: genesis_hash,
+			});
+			return Err(RpcError::Incompatible.with_message(format!(
+				"Genesis hash {genesis_hash:?} doesn't match the expected {expected:?}! Change the config or the node url ({host}), node is rejected."
+			)));
+		}
 
 		// check system and runtime versions
-		let system_version = client.rpc().system_version().await?;
+		let system_version = client
+			.rpc()
+			.system_version()
+			.await
+			.map_err(RpcError::report)?;
 		let runtime_version: RuntimeVersion = client
 			.rpc()
 			.request("state_getRuntimeVersion", RpcParams::new())
-			.await?;
+			.await
+			.map_err(RpcError::report)?;
 
 		if !expected_node.matches(&system_version, &runtime_version.spec_name) {
-			return Err(eyre!(
-				"Expected Node system version:{:?}/{}, found: {}/{}. Skipping to another node.",
-				expected_node.system_version,
-				expected_node.spec_name,
-				system_version,
-				runtime_version.spec_name,
-			));
+			let expected = expected_node
+				.requirements()
+				.iter()
+				.map(|(spec_name, requirement)| format!("{requirement}/{spec_name}"))
+				.collect::<Vec<_>>()
+				.join(", ");
+			let found = format!("{}/{}", system_version, runtime_version.spec_name);
+			let _ = events.send(RpcEvent::VersionMismatch {
+				host: host.to_string(),
+				expected: expected.clone(),
+				found: found.clone(),
+				accepted: expected_node.allow_mismatch,
+			});
+			if !expected_node.allow_mismatch {
+				return Err(RpcError::Incompatible.with_message(format!(
+					"Expected Node system version: {expected}, found: {found}. Skipping to another node."
+				)));
+			}
+			warn!(host, "Expected Node system version: {expected}, found: {found}. Version mismatch is allowed, using the node.");
 		}
 
 		let variant = Node::new(
 			host.to_string(),
+			server_name,
 			system_version,
 			runtime_version.spec_name,
 			runtime_version.spec_version,
@@ -129,33 +201,143 @@ impl Client {
 		Ok((client, variant))
 	}
 
+	/// Connects to the weighted list of Nodes, racing the handshakes of up to `race_nodes` nodes at once.
+	/// First compatible node to complete the handshake is used, and handshakes of the slower nodes are cancelled.
+	/// Next nodes of the list are raced only if none of the raced nodes is compatible.
+	async fn race_connect(
+		nodes: &Nodes,
+		events: &broadcast::Sender<RpcEvent>,
+		expected_node: &ExpectedNodeVariant,
+		expected_genesis: &ExpectedGenesis,
+		transport: &Transport,
+		race_nodes: usize,
+	) -> Result<(avail::Client, Node)> {
+		let mut candidates = nodes.weighted_shuffle(Default::default());
+		let mut rounds = vec![];
+		// last known node of the same network is tried alone, before the other nodes are raced
+		if let Some(last_known) = nodes.take_last_known() {
+			let position = candidates
+				.iter()
+				.position(|node| node.host == last_known.host);
+			let same_network = expected_genesis
+				.hash()
+				.map_or(true, |hash| hash == last_known.genesis_hash);
+			if let (Some(position), true) = (position, same_network) {
+				info!(
+					host = %last_known.host,
+					"Trying last known node first (spec version {})", last_known.spec_version
+				);
+				rounds.push(vec![candidates.remove(position)]);
+			}
+		}
+		rounds.extend(candidates.chunks(race_nodes.max(1)).map(<[Node]>::to_vec));
+
+		let mut last_error = None;
+		for raced in &rounds {
+			let mut handshakes = raced
+				.iter()
+				.map(|Node { host, .. }| async move {
+					let begin = Instant::now();
+					let result = Self::create_subxt_client(
+						host,
+						nodes,
+						events,
+						expected_node.clone(),
+						expected_genesis,
+						transport,
+					)
+					.await;
+					(host, begin.elapsed(), result)
+				})
+				.collect::<FuturesUnordered<_>>();
+
+			while let Some((host, elapsed, result)) = handshakes.next().await {
+				match result {
+					Err(error) => {
+						nodes.record_error(host);
+						warn!(host, %error, "Skipping connection with this node");
+						last_error = Some(error);
+					},
+					Ok(connected) => {
+						nodes.record_success(host, elapsed);
+						debug!(
+							host,
+							"Connected in {elapsed:?}, fastest of {} raced nodes",
+							raced.len()
+						);
+						return Ok(connected);
+					},
+				}
+			}
+		}
+
+		Err(Self::no_working_node(last_error))
+	}
+
 	async fn try_connect_and_execute<T, F, Fut>(
-		nodes: Vec<Node>,
+		nodes: &Nodes,
+		events: &broadcast::Sender<RpcEvent>,
+		current_host: &str,
 		expected_node: ExpectedNodeVariant,
-		expected_genesis_hash: &str,
+		expected_genesis: &ExpectedGenesis,
+		transport: &Transport,
 		mut f: F,
 	) -> Result<(avail::Client, Node, T)>
 	where
 		F: FnMut(avail::Client) -> Fut + Copy,
 		Fut: std::future::Future<Output = Result<T>>,
 	{
-		// go through the provided list of Nodes to try and find and appropriate one,
+		// go through the weighted list of Nodes to try and find and appropriate one,
 		// after a successful connection, try to execute passed function call
-		for Node { host, .. } in nodes.iter() {
-			let result =
-				Self::create_subxt_client(host, expected_node.clone(), expected_genesis_hash)
-					.and_then(move |(client, node)| {
-						f(client.clone()).map_ok(|res| (client, node, res))
-					})
-					.await;
+		let mut last_error = None;
+		for Node { host, .. } in nodes.weighted_shuffle(current_host).iter() {
+			let begin = Instant::now();
+			let result = Self::create_subxt_client(
+				host,
+				nodes,
+				events,
+				expected_node.clone(),
+				expected_genesis,
+				transport,
+			)
+			.and_then(move |(client, node)| async move {
+				let _permit = nodes.acquire(host).await;
+				f(client.clone()).map_ok(|res| (client, node, res)).await
+			})
+			.await;
 
 			match result {
-				Err(error) => warn!(host, %error, "Skipping connection with this node"),
-				ok => return ok,
+				Err(error) => {
+					nodes.record_error(host);
+					warn!(host, %error, "Skipping connection with this node");
+					last_error = Some(error);
+				},
+				ok => {
+					nodes.record_success(host, begin.elapsed());
+					return ok;
+				},
 			}
 		}
 
-		Err(eyre!("Failed to connect any appropriate working node"))
+		Err(Self::no_working_node(last_error))
+	}
+
+	/// Returns the error of the last failed node, so the failure type is kept, or [RpcError::Disconnected] if no node was available.
+	fn no_working_node(last_error: Option<Report>) -> Report {
+		last_error
+			.unwrap_or_else(|| Report::new(RpcError::Disconnected))
+			.wrap_err("Failed to connect any appropriate working node")
+	}
+
+	/// Executes the request, failing with [RpcError::Timeout] if it doesn't complete in the request timeout.
+	async fn execute<T>(
+		request_timeout: Duration,
+		request: impl std::future::Future<Output = Result<T, subxt::error::Error>>,
+	) -> Result<T> {
+		match timeout(request_timeout, request).await {
+			Ok(result) => result.map_err(RpcError::report),
+			Err(_) => Err(Report::new(RpcError::Timeout(request_timeout))),
+		}
 	}
 
 	async fn with_retries<F, Fut, T>(&self, mut f: F) -> Result<T>
@@ -163,14 +345,43 @@ impl Client {
 		F: FnMut(avail::Client) -> Fut + Copy,
 		Fut: std::future::Future<Output = Result<T, subxt::error::Error>>,
 	{
-		// try and execute the passed function, use the Retry strategy if needed
-		if let Ok(result) = Retry::spawn(self.retry_config.clone(), move || async move {
-			f(self.current_client().await).await
-		})
-		.await
-		{
-			// this was successful, return early
-			return Ok(result);
+		let current_host = self.current_host();
+		let host = current_host.as_str();
+		let request_timeout = self.health_check.request_timeout;
+		// try and execute the passed function, use the Retry strategy if needed,
+		// retries are stopped as soon as the circuit breaker of the current host opens
+		if self.nodes.is_available(host) {
+			if let Ok(result) = RetryIf::spawn(
+				self.retry_config.clone(),
+				move || async move {
+					let _permit = self.nodes.acquire(host).await;
+					let begin = Instant::now();
+					let result =
+						Self::execute(request_timeout, f(self.current_client().await)).await;
+					match result {
+						Ok(_) => {
+							let elapsed = begin.elapsed();
+							self.nodes.record_success(host, elapsed);
+							let duration = MetricValue::RPCRequestDuration(elapsed.as_secs_f64());
+							if let Err(error) = self.metrics.record(duration).await {
+								debug!("Cannot record RPC request duration: {error:#}");
+							}
+						},
+						Err(_) => self.nodes.record_error(host),
+					}
+					result
+				},
+				// responses which cannot be decoded are not retried with the same node
+				|error: &Report| {
+					error.downcast_ref::<RpcError>() != Some(&RpcError::Decode)
+						&& self.nodes.is_available(host)
+				},
+			)
+			.await
+			{
+				// this was successful, return early
+				return Ok(result);
+			}
 		}
 		// if not, find another Node where this could still be done
 		let connected_node = self.state.lock().unwrap().connected_node.clone();
@@ -178,17 +389,18 @@ impl Client {
 			"Executing RPC call with host: {} failed. Trying to create a new RPC connection.",
 			connected_node.host
 		);
-		// shuffle nodes, if possible
-		let nodes = self.nodes.shuffle(connected_node.host);
-		// go through available Nodes, try to connect, Retry connecting if needed
+		// go through available Nodes ordered by their score, try to connect, Retry connecting if needed
 		let (client, node, result) = Retry::spawn(self.retry_config.clone(), move || {
-			let nodes = nodes.clone();
+			let current_host = connected_node.host.clone();
 			async move {
 				Self::try_connect_and_execute(
-					nodes,
-					ExpectedNodeVariant::new(),
-					&self.expected_genesis_hash,
-					move |client| f(client).map_err(Report::from),
+					&self.nodes,
+					&self.events,
+					&current_host,
+					self.expected_node.clone(),
+					&self.expected_genesis,
+					&self.transport,
+					move |client| Self::execute(request_timeout, f(client)),
 				)
 				.await
 			}
@@ -197,7 +409,7 @@ impl Client {
 
 		// retries gave results, update currently connected Node and created Client
 		*self.subxt_client.write().await = client;
-		self.state.lock().unwrap().connected_node = node;
+		self.switch_node(node);
 
 		Ok(result)
 	}
@@ -222,11 +434,23 @@ impl Client {
 		// map Justification subscription to the same type for later matching
 		let justifications = justification_subscription.map_ok(Subscription::Justification);
 
-		Ok(headers.merge(justifications))
+		// current runtime version is received first, followed by the runtime upgrades
+		let runtime_version_subscription = client
+			.rpc()
+			.subscribe(
+				"state_subscribeRuntimeVersion",
+				rpc_params![],
+				"state_unsubscribeRuntimeVersion",
+			)
+			.await?;
+		let runtime_versions = runtime_version_subscription.map_ok(Subscription::RuntimeVersion);
+
+		Ok(headers.merge(justifications).merge(runtime_versions))
 	}
 
 	pub async fn subscription_stream(self) -> impl Stream<Item = Result<Subscription>> {
 		async_stream::stream! {
+			let mut connection_lost = false;
 			'outer: loop{
 				let mut stream = match self.with_retries(|client| async move{
 					Self::create_subxt_subscriptions(client).await
@@ -238,29 +462,260 @@ impl Client {
 					}
 				};
 
+				if connection_lost {
+					connection_lost = false;
+					let node = self.state.lock().unwrap().connected_node.clone();
+					info!(host = %node.host, "Connection restored, subscriptions recreated");
+					yield Ok(Subscription::ConnectionRestored(node));
+				}
+
+				let mut ping = interval(self.health_check.ping_interval);
+				ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+				// first tick completes immediately
+				ping.tick().await;
+				let mut last_header_at = Instant::now();
+
 				loop {
-					// no more subscriptions left on stream, we have to try and create a new stream
-					let Some(result) = stream.next().await else {
-						warn!("No more items on Subscriptions Stream. Trying to create a new one.");
-						continue 'outer
+					let next = tokio::select! {
+						result = stream.next() => Some(result),
+						_ = ping.tick() => None,
 					};
-					match result {
-						Ok(item) => yield Ok(item),
-						// if Error was received, we need to switch to another RPC Client
-						Err(err)=> {
-							warn!(%err, "Received Error on stream. Trying to create a new one.");
-							continue 'outer
+
+					let reason = match next {
+						Some(Some(Ok(Subscription::RuntimeVersion(version)))) => {
+							match self.update_runtime(version).await {
+								Ok(false) => continue,
+								// subscriptions are recreated on the client with refreshed metadata
+								Ok(true) => continue 'outer,
+								Err(error) => {
+									let reason = format!("{error:#}");
+									warn!(%reason, "Node is not compatible after runtime upgrade. Reconnecting.");
+									if let Err(error) = self.reconnect().await {
+										yield Err(error);
+										return;
+									}
+									connection_lost = true;
+									yield Ok(Subscription::ConnectionLost(reason));
+									continue 'outer;
+								},
+							}
+						},
+						Some(Some(Ok(item))) => {
+							match &item {
+								Subscription::Header(header) => {
+									last_header_at = Instant::now();
+									self.emit(RpcEvent::HeaderReceived {
+										number: header.number,
+										hash: Encode::using_encoded(header, blake2_256).into(),
+									});
+								},
+								Subscription::Justification(justification) => {
+									self.emit(RpcEvent::JustificationReceived {
+										target_number: justification.commit.target_number,
+										target_hash: justification.commit.target_hash,
+									});
+								},
+								_ => (),
+							}
+							yield Ok(item);
+							continue;
+						},
+						// no more subscriptions left on stream, we have to try and create a new stream
+						Some(None) => "No more items on Subscriptions Stream".to_string(),
+						// if Error was received, we need to create a new stream
+						Some(Some(Err(err))) => format!("Received Error on stream: {err}"),
+						None => match self.check_health(last_header_at).await {
+							Ok(()) => continue,
+							Err(error) => {
+								let reason = format!("{error:#}");
+								warn!(%reason, "Connection health check failed. Reconnecting.");
+								if let Err(error) = self.reconnect().await {
+									yield Err(error);
+									return;
+								}
+								connection_lost = true;
+								yield Ok(Subscription::ConnectionLost(reason));
+								continue 'outer;
+							},
+						},
+					};
+
+					warn!(%reason, "Trying to create a new subscriptions stream.");
+					self.nodes.record_subscription_drop(&self.current_host());
+					connection_lost = true;
+					yield Ok(Subscription::ConnectionLost(reason));
+					continue 'outer;
+				}
+			}
+		}
+	}
+
+	/// Returns stream of the best (unfinalized) headers of the connected node.
+	/// Subscription is recreated when the stream ends or fails, e.g. after the client switched to another node.
+	pub fn best_header_stream(self) -> impl Stream<Item = Header> {
+		async_stream::stream! {
+			loop {
+				let subscription = self
+					.with_retries(|client| async move {
+						client.rpc().subscribe_best_block_headers().await
+					})
+					.await;
+				match subscription {
+					Ok(mut headers) => {
+						while let Some(result) = headers.next().await {
+							match result {
+								Ok(header) => yield header,
+								Err(error) => {
+									debug!("Received error on best headers stream: {error}");
+									break;
+								},
+							}
 						}
-					}
+					},
+					Err(error) => warn!("Cannot subscribe to best headers: {error:#}"),
 				}
+				sleep(BEST_HEADERS_RESUBSCRIBE_DELAY).await;
 			}
 		}
 	}
 
+	/// Recreates the client of the connected node if runtime is upgraded, so the metadata is refreshed,
+	/// and node compatibility is checked again. Returns `true` if the client is recreated.
+	async fn update_runtime(&self, version: RuntimeVersion) -> Result<bool> {
+		let spec_version = MetricValue::RuntimeSpecVersion(version.spec_version);
+		if let Err(error) = self.metrics.record(spec_version).await {
+			debug!("Cannot record runtime spec version: {error:#}");
+		}
+
+		let connected_node = self.state.lock().unwrap().connected_node.clone();
+		if connected_node.spec_version == version.spec_version {
+			return Ok(false);
+		}
+		info!(
+			host = connected_node.host,
+			previous = connected_node.spec_version,
+			spec_version = version.spec_version,
+			"Runtime upgrade detected, refreshing metadata"
+		);
+
+		let (client, node) = Self::create_subxt_client(
+			&connected_node.host,
+			&self.nodes,
+			&self.events,
+			self.expected_node.clone(),
+			&self.expected_genesis,
+			&self.transport,
+		)
+		.await?;
+		*self.subxt_client.write().await = client;
+		self.state.lock().unwrap().connected_node = node.clone();
+		self.emit(RpcEvent::RuntimeUpgraded {
+			host: node.host,
+			previous: connected_node.spec_version,
+			spec_version: node.spec_version,
+		});
+		Ok(true)
+	}
+
+	/// Pings the connected node and checks if finalized headers are still being received.
+	async fn check_health(&self, last_header_at: Instant) -> Result<()> {
+		let HealthCheckConfig {
+			ping_interval,
+			stall_timeout,
+			..
+		} = self.health_check;
+
+		let since_last_header = last_header_at.elapsed();
+		if since_last_header > stall_timeout {
+			return Err(eyre!(
+				"No finalized headers received in the last {since_last_header:?}"
+			));
+		}
+
+		let client = self.current_client().await;
+		match timeout(ping_interval, client.rpc().system_version()).await {
+			Ok(Ok(_)) => Ok(()),
+			Ok(Err(error)) => Err(eyre!("Ping failed: {error}")),
+			Err(_) => Err(eyre!("Ping timed out after {ping_interval:?}")),
+		}
+	}
+
+	/// Creates a new connection, preferring nodes other than the currently connected one.
+	async fn reconnect(&self) -> Result<()> {
+		let current_host = self.current_host();
+		self.nodes.record_subscription_drop(&current_host);
+
+		let (client, node, _) = Retry::spawn(self.retry_config.clone(), || async {
+			Self::try_connect_and_execute(
+				&self.nodes,
+				&self.events,
+				&current_host,
+				self.expected_node.clone(),
+				&self.expected_genesis,
+				&self.transport,
+				|_| futures::future::ok(()),
+			)
+			.await
+		})
+		.await?;
+
+		*self.subxt_client.write().await = client;
+		self.switch_node(node);
+
+		Ok(())
+	}
+
 	pub async fn current_client(&self) -> avail::Client {
 		self.subxt_client.read().await.clone()
 	}
 
+	fn current_host(&self) -> String {
+		self.state.lock().unwrap().connected_node.host.clone()
+	}
+
+	/// Updates currently connected Node in the application wide State and notifies subscribers.
+	fn switch_node(&self, node: Node) {
+		let previous =
+			std::mem::replace(&mut self.state.lock().unwrap().connected_node, node.clone());
+		self.emit(RpcEvent::NodeSwitched {
+			previous: previous.host,
+			node,
+		});
+	}
+
+	pub(super) fn emit(&self, event: RpcEvent) {
+		// sending fails only if there are no subscribers, which is fine
+		let _ = self.events.send(event);
+	}
+
+	/// Subscribes to RPC client lifecycle events.
+	pub fn subscribe(&self) -> broadcast::Receiver<RpcEvent> {
+		self.events.subscribe()
+	}
+
+	/// Returns circuit breaker states of the configured RPC nodes.
+	pub fn circuit_breakers(&self) -> Vec<(String, BreakerState)> {
+		self.nodes.breaker_states()
+	}
+
+	/// Returns health scores of the configured RPC nodes.
+	pub fn node_scores(&self) -> Vec<(String, NodeScore)> {
+		self.nodes.scores()
+	}
+
+	/// Periodically resolves DNS names of the configured nodes, so the new connections use the current addresses.
+	/// Established connections are kept, until the node fails.
+	pub async fn resolve_nodes(self, refresh_interval: Duration) {
+		let mut refresh = interval(refresh_interval);
+		refresh.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		// First tick completes immediately, while nodes are already resolved on init
+		refresh.tick().await;
+		loop {
+			refresh.tick().await;
+			self.nodes.resolve().await;
+		}
+	}
+
 	pub async fn get_block_hash(&self, block_number: u32) -> Result<H256> {
 		let hash = self
 			.with_retries(|client| async move {
@@ -284,6 +739,15 @@ impl Client {
 		Ok(header)
 	}
 
+	/// Returns block number of the block hash, or `None` if the block is unknown to the node.
+	pub async fn get_block_number(&self, block_hash: H256) -> Result<Option<u32>> {
+		let header = self
+			.with_retries(|client| async move { client.rpc().header(Some(block_hash)).await })
+			.await?;
+
+		Ok(header.map(|header| header.number))
+	}
+
 	pub async fn get_validator_set_by_hash(&self, block_hash: H256) -> Result<Vec<Public>> {
 		let res = self
 			.with_retries(|client| async move {
@@ -333,33 +797,165 @@ impl Client {
 		Ok(res)
 	}
 
+	/// Requests cell proofs, splitting the cells across multiple healthy nodes if parallel fetch is configured.
 	pub async fn request_kate_proof(
 		&self,
 		block_hash: H256,
 		positions: &[Position],
+	) -> Result<Vec<Cell>> {
+		let ParallelFetchConfig { nodes, tuning, .. } = self.parallel_fetch;
+		let cells_per_request = self.cells_per_request.get();
+
+		if positions.len() <= cells_per_request {
+			return self
+				.request_kate_proof_from_current(block_hash, positions)
+				.await;
+		}
+
+		if nodes > 1 {
+			return self
+				.request_kate_proof_parallel(block_hash, positions, nodes, cells_per_request)
+				.await;
+		}
+
+		if tuning.is_none() {
+			return self
+				.request_kate_proof_from_current(block_hash, positions)
+				.await;
+		}
+
+		// tuned batches are requested from the connected node concurrently
+		let batches = positions
+			.chunks(cells_per_request)
+			.map(|batch| self.request_kate_proof_from_current(block_hash, batch));
+		let mut cells = Vec::with_capacity(positions.len());
+		for fetched in join_all(batches).await {
+			cells.extend(fetched?);
+		}
+		Ok(cells)
+	}
+
+	async fn request_kate_proof_from_current(
+		&self,
+		block_hash: H256,
+		positions: &[Position],
 	) -> Result<Vec<Cell>> {
 		let mut params = RpcParams::new();
 		params.push(positions)?;
 		params.push(block_hash)?;
 
-		let proofs: Vec<u8> = self
+		let begin = Instant::now();
+		let proofs: Result<ProofBuffer> = self
 			.with_retries(|client| {
 				let params = params.clone();
 				async move { client.rpc().request("kate_queryProof", params).await }
 			})
-			.await?;
+			.await;
+		self.cells_per_request
+			.record(begin.elapsed(), proofs.is_err());
 
-		let i = proofs
-			.chunks_exact(CELL_WITH_PROOF_SIZE)
-			.map(|chunk| chunk.try_into().expect("chunks of 80 bytes size"));
+		Ok(cells_from_proofs(positions, &proofs?))
+	}
 
-		let proof = positions
-			.iter()
-			.zip(i)
-			.map(|(&position, &content)| Cell { position, content })
-			.collect::<Vec<_>>();
+	async fn request_kate_proof_parallel(
+		&self,
+		block_hash: H256,
+		positions: &[Position],
+		nodes: usize,
+		max_cells_per_node: usize,
+	) -> Result<Vec<Cell>> {
+		let current_host = self.current_host();
+		let mut hosts = vec![current_host.clone()];
+		hosts.extend(
+			self.nodes
+				.weighted_shuffle(&current_host)
+				.into_iter()
+				.map(|Node { host, .. }| host)
+				.filter(|host| host != &current_host)
+				.take(nodes - 1),
+		);
 
-		Ok(proof)
+		let assignments = assign_batches(positions, hosts.len(), max_cells_per_node);
+
+		debug!(
+			"Fetching {} cells from {} nodes in parallel",
+			positions.len(),
+			hosts.len()
+		);
+
+		fetch_assigned(
+			&hosts,
+			assignments,
+			|host, batch| self.query_proof_at(host, block_hash, batch),
+			|batch| self.request_kate_proof_from_current(block_hash, batch),
+		)
+		.await
+	}
+
+	/// Returns subxt client for the given host, creating and caching a new connection if needed.
+	async fn node_client(&self, host: &str) -> Result<avail::Client> {
+		if host == self.current_host() {
+			return Ok(self.current_client().await);
+		}
+
+		if let Some(client) = self.pool.read().await.get(host) {
+			return Ok(client.clone());
+		}
+
+		let (client, _) = Self::create_subxt_client(
+			host,
+			&self.nodes,
+			&self.events,
+			self.expected_node.clone(),
+			&self.expected_genesis,
+			&self.transport,
+		)
+		.await?;
+		self.pool
+			.write()
+			.await
+			.insert(host.to_string(), client.clone());
+
+		Ok(client)
+	}
+
+	async fn query_proof_at(
+		&self,
+		host: &str,
+		block_hash: H256,
+		positions: &[Position],
+	) -> Result<Vec<Cell>> {
+		let client = match self.node_client(host).await {
+			Ok(client) => client,
+			Err(error) => {
+				self.nodes.record_error(host);
+				return Err(error);
+			},
+		};
+
+		let mut params = RpcParams::new();
+		params.push(positions)?;
+		params.push(block_hash)?;
+
+		let _permit = self.nodes.acquire(host).await;
+		let begin = Instant::now();
+		let request = client
+			.rpc()
+			.request::<ProofBuffer>("kate_queryProof", params);
+		match Self::execute(self.health_check.request_timeout, request).await {
+			Ok(proofs) => {
+				self.nodes.record_success(host, begin.elapsed());
+				self.cells_per_request.record(begin.elapsed(), false);
+				Ok(cells_from_proofs(positions, &proofs))
+			},
+			Err(error) => {
+				self.nodes.record_error(host);
+				self.cells_per_request.record(begin.elapsed(), true);
+				// drop the connection, so it will be recreated on next use
+				self.pool.write().await.remove(host);
+				Err(error)
+			},
+		}
 	}
 
 	pub async fn get_system_version(&self) -> Result<String> {
@@ -400,6 +996,19 @@ impl Client {
 		Ok(res)
 	}
 
+	/// Returns timestamp of the block, in milliseconds
+	pub async fn get_timestamp_at(&self, block_hash: H256) -> Result<u64> {
+		let res = self
+			.with_retries(|client| {
+				let timestamp_key = api::storage().timestamp().now();
+				async move { client.storage().at(block_hash).fetch(&timestamp_key).await }
+			})
+			.await?
+			.ok_or_else(|| eyre!("The timestamp should exist"))?;
+
+		Ok(res)
+	}
+
 	pub async fn get_current_set_id_by_block_number(&self, block_num: u32) -> Result<u64> {
 		let hash = self.get_block_hash(block_num).await?;
 		self.fetch_set_id_at(hash).await
@@ -424,51 +1033,39 @@ impl Client {
 		Ok(res)
 	}
 
-	pub async fn submit_signed_and_wait_for_finalized<Call: subxt::tx::TxPayload>(
+	/// Signs and submits the transaction, returning the progress of the transaction through the pipeline.
+	pub async fn submit_signed_and_watch<Call: subxt::tx::TxPayload>(
 		&self,
 		call: &Call,
 		signer: &PairSigner<AvailConfig, Pair>,
 		other_params: avail_subxt::primitives::AvailExtrinsicParams,
-	) -> Result<subxt::blocks::ExtrinsicEvents<AvailConfig>> {
-		let tx_progress = self
-			.with_retries(|client| {
-				let other_params = other_params.clone();
-				async move {
-					client
-						.tx()
-						.sign_and_submit_then_watch(call, signer, other_params)
-						.await
-				}
-			})
-			.await?;
-
-		tx_progress
-			.wait_for_finalized_success()
-			.await
-			.map_err(Report::from)
+	) -> Result<TxProgress<AvailConfig, avail::Client>> {
+		self.with_retries(|client| {
+			let other_params = other_params.clone();
+			async move {
+				client
+					.tx()
+					.sign_and_submit_then_watch(call, signer, other_params)
+					.await
+			}
+		})
+		.await
 	}
 
-	pub async fn submit_from_bytes_and_wait_for_finalized(
+	/// Submits signed transaction, returning the progress of the transaction through the pipeline.
+	pub async fn submit_from_bytes_and_watch(
 		&self,
 		tx_bytes: Vec<u8>,
-	) -> Result<subxt::blocks::ExtrinsicEvents<AvailConfig>> {
-		let tx_progress = self
-			.with_retries(|client| {
-				let tx_bytes = tx_bytes.clone();
-				async move {
-					SubmittableExtrinsic::from_bytes(client, tx_bytes)
-						.submit_and_watch()
-						.await
-				}
-			})
-			.await?;
-
-		let ext = tx_progress
-			.wait_for_finalized_success()
-			.await
-			.map_err(Report::from)?;
-
-		Ok(ext)
+	) -> Result<TxProgress<AvailConfig, avail::Client>> {
+		self.with_retries(|client| {
+			let tx_bytes = tx_bytes.clone();
+			async move {
+				SubmittableExtrinsic::from_bytes(client, tx_bytes)
+					.submit_and_watch()
+					.await
+			}
+		})
+		.await
 	}
 
 	pub async fn get_paged_storage_keys(
@@ -535,9 +1132,174 @@ impl Client {
 		Ok(res)
 	}
 
+	/// Requests finality proof of the block, as the hex encoded SCALE bytes returned by the node.
+	pub async fn request_encoded_finality_proof(&self, block_number: u32) -> Result<String> {
+		let mut params = RpcParams::new();
+		params.push(block_number)?;
+
+		let res: String = self
+			.with_retries(|client| {
+				let params = params.clone();
+				async move { client.rpc().request("grandpa_proveFinality", params).await }
+			})
+			.await
+			.map_err(|e| eyre!("Request failed at Finality Proof. Error: {e}"))?;
+
+		Ok(res)
+	}
+
 	pub async fn get_genesis_hash(&self) -> Result<H256> {
 		let gen_hash = self.current_client().await.genesis_hash();
 
 		Ok(gen_hash)
 	}
 }
+
+/// Copies cells from the borrowed chunks of the proofs buffer, without allocations per cell.
+/// Assigns batches of the positions to the hosts in round robin fashion.
+fn assign_batches(
+	positions: &[Position],
+	hosts: usize,
+	max_cells_per_node: usize,
+) -> Vec<Vec<&[Position]>> {
+	let mut assignments = vec![vec![]; hosts.max(1)];
+	for (i, batch) in positions.chunks(max_cells_per_node.max(1)).enumerate() {
+		assignments[i % assignments.len()].push(batch);
+	}
+	assignments
+}
+
+/// Fetches batches assigned to each host, batches assigned to the same host are fetched sequentially.
+/// Batches which cannot be fetched from the assigned host are fetched with the fallback.
+async fn fetch_assigned<'a, F, FF, B, BF>(
+	hosts: &'a [String],
+	assignments: Vec<Vec<&'a [Position]>>,
+	fetch: F,
+	fallback: B,
+) -> Result<Vec<Cell>>
+where
+	F: Fn(&'a str, &'a [Position]) -> FF,
+	FF: Future<Output = Result<Vec<Cell>>>,
+	B: Fn(&'a [Position]) -> BF,
+	BF: Future<Output = Result<Vec<Cell>>>,
+{
+	let fetch = &fetch;
+	let requests = hosts
+		.iter()
+		.zip(assignments)
+		.map(|(host, batches)| async move {
+			let mut results = vec![];
+			for batch in batches {
+				results.push((batch, fetch(host.as_str(), batch).await));
+			}
+			results
+		});
+
+	let mut cells = vec![];
+	for (batch, result) in join_all(requests).await.into_iter().flatten() {
+		match result {
+			Ok(fetched) => cells.extend(fetched),
+			Err(error) => {
+				debug!(%error, "Parallel batch fetch failed, fetching from connected node");
+				cells.extend(fallback(batch).await?);
+			},
+		}
+	}
+	Ok(cells)
+}
+
+fn cells_from_proofs(positions: &[Position], proofs: &[u8]) -> Vec<Cell> {
+	let i = proofs
+		.chunks_exact(CELL_WITH_PROOF_SIZE)
+		.map(|chunk| chunk.try_into().expect("chunks of 80 bytes size"));
+
+	positions
+		.iter()
+		.zip(i)
+		.map(|(&position, &content)| Cell { position, content })
+		.collect::<Vec<_>>()
+}
+
+#[cfg(test)]
+mod tests {
+	use color_eyre::{eyre::eyre, Report};
+	use kate_recovery::{data::Cell, matrix::Position};
+	use std::sync::Mutex;
+
+	use super::{assign_batches, fetch_assigned, CELL_WITH_PROOF_SIZE};
+
+	fn positions(count: u16) -> Vec<Position> {
+		(0..count).map(|col| Position { row: 0, col }).collect()
+	}
+
+	fn cells(positions: &[Position]) -> Vec<Cell> {
+		positions
+			.iter()
+			.map(|&position| Cell {
+				position,
+				content: [0; CELL_WITH_PROOF_SIZE],
+			})
+			.collect()
+	}
+
+	#[test]
+	fn batches_are_assigned_round_robin() {
+		let positions = positions(10);
+		let assignments = assign_batches(&positions, 3, 3);
+		let sizes = assignments
+			.iter()
+			.map(|batches| batches.iter().map(|batch| batch.len()).collect::<Vec<_>>())
+			.collect::<Vec<_>>();
+		assert_eq!(sizes, vec![vec![3, 1], vec![3], vec![3]]);
+		assert_eq!(assignments[1][0][0], Position { row: 0, col: 3 });
+		assert_eq!(assign_batches(&positions, 0, 0).len(), 1);
+	}
+
+	#[tokio::test]
+	async fn failed_batches_are_fetched_from_fallback() {
+		let positions = positions(8);
+		let hosts = vec!["good".to_string(), "bad".to_string()];
+		let assignments = assign_batches(&positions, hosts.len(), 2);
+		let fallbacks = Mutex::new(vec![]);
+
+		let fetched = fetch_assigned(
+			&hosts,
+			assignments,
+			|host, batch| async move {
+				match host {
+					"good" => Ok(cells(batch)),
+					_ => Err(eyre!("Node is not available")),
+				}
+			},
+			|batch| {
+				fallbacks.lock().unwrap().push(batch[0].col);
+				async move { Ok::<_, Report>(cells(batch)) }
+			},
+		)
+		.await
+		.unwrap();
+
+		let mut columns = fetched
+			.iter()
+			.map(|cell| cell.position.col)
+			.collect::<Vec<_>>();
+		columns.sort();
+		assert_eq!(columns, (0..8).collect::<Vec<_>>());
+		assert_eq!(*fallbacks.lock().unwrap(), vec![2, 6]);
+	}
+
+	#[tokio::test]
+	async fn fallback_error_fails_the_fetch() {
+		let positions = positions(4);
+		let hosts = vec!["bad".to_string()];
+		let assignments = assign_batches(&positions, hosts.len(), 2);
+		let fetched = fetch_assigned(
+			&hosts,
+			assignments,
+			|_, _| async { Err::<Vec<Cell>, _>(eyre!("Node is not available")) },
+			|_| async { Err::<Vec<Cell>, _>(eyre!("Connected node is not available")) },
+		)
+		.await;
+		assert!(fetched.is_err());
+	}
+}
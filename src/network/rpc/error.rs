@@ -0,0 +1,90 @@
+//! Typed failures of the RPC requests, attached to the returned reports, so callers can branch on the failure type.
+
+use color_eyre::Report;
+use std::{fmt::Display, time::Duration};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RpcError {
+	/// Request didn't complete in the request timeout
+	Timeout(Duration),
+	/// Connection to the node is lost, or there is no available node to connect to
+	Disconnected,
+	/// Node belongs to another network, or its version is not supported
+	Incompatible,
+	/// Response of the node cannot be decoded
+	Decode,
+}
+
+impl Display for RpcError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			RpcError::Timeout(timeout) => write!(f, "RPC request timed out after {timeout:?}"),
+			RpcError::Disconnected => write!(f, "RPC node is disconnected"),
+			RpcError::Incompatible => write!(f, "RPC node is incompatible"),
+			RpcError::Decode => write!(f, "Cannot decode RPC response"),
+		}
+	}
+}
+
+impl std::error::Error for RpcError {}
+
+impl RpcError {
+	/// Classifies the subxt error, returns `None` for the errors returned by the node.
+	fn classify(error: &subxt::Error) -> Option<Self> {
+		match error {
+			subxt::Error::Io(_)
+			| subxt::Error::Rpc(subxt::error::RpcError::SubscriptionDropped) => Some(RpcError::Disconnected),
+			subxt::Error::Rpc(subxt::error::RpcError::ClientError(error)) => {
+				match error.downcast_ref::<jsonrpsee::core::Error>() {
+					Some(jsonrpsee::core::Error::RestartNeeded(_)) => Some(RpcError::Disconnected),
+					_ => None,
+				}
+			},
+			subxt::Error::Codec(_) | subxt::Error::Decode(_) | subxt::Error::Serialization(_) => {
+				Some(RpcError::Decode)
+			},
+			_ => None,
+		}
+	}
+
+	/// Converts the subxt error into the report, with the failure type attached if it is classified.
+	pub(super) fn report(error: subxt::Error) -> Report {
+		match Self::classify(&error) {
+			Some(kind) => Report::new(kind).wrap_err(error.to_string()),
+			None => Report::new(error),
+		}
+	}
+
+	/// Creates the report of the given failure type, with the message describing the failure.
+	pub(super) fn with_message(self, message: impl Display + Send + Sync + 'static) -> Report {
+		Report::new(self).wrap_err(message)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::RpcError;
+
+	#[test]
+	fn failure_type_is_kept_in_report() {
+		let io = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed");
+		let report = RpcError::report(subxt::Error::Io(io));
+		assert_eq!(
+			report.downcast_ref::<RpcError>(),
+			Some(&RpcError::Disconnected)
+		);
+
+		let report = RpcError::Timeout(Duration::from_secs(30))
+			.with_message("Request timed out")
+			.wrap_err("Cannot fetch header");
+		assert_eq!(
+			report.downcast_ref::<RpcError>(),
+			Some(&RpcError::Timeout(Duration::from_secs(30)))
+		);
+
+		let report = RpcError::report(subxt::Error::Other("Block not found".to_string()));
+		assert_eq!(report.downcast_ref::<RpcError>(), None);
+	}
+}
@@ -0,0 +1,126 @@
+//! Allowlist and denylist of the RPC nodes, for operators which may contact only the approved endpoints.
+
+use color_eyre::{eyre::eyre, Result};
+use tracing::warn;
+use url::Url;
+
+use super::Node;
+use crate::types::RuntimeConfig;
+
+/// RPC nodes policy (see [RuntimeConfig] for details)
+#[derive(Clone, Debug, Default)]
+pub struct NodePolicy {
+	pub pinned: Vec<String>,
+	pub denied: Vec<String>,
+}
+
+impl From<&RuntimeConfig> for NodePolicy {
+	fn from(val: &RuntimeConfig) -> Self {
+		NodePolicy {
+			pinned: val.rpc_pinned_nodes.clone(),
+			denied: val.rpc_denied_hosts.clone(),
+		}
+	}
+}
+
+/// Normalized host, IPv6 addresses are compared without brackets.
+fn normalize(host: &str) -> String {
+	host.trim_start_matches('[')
+		.trim_end_matches(']')
+		.to_lowercase()
+}
+
+fn url_host(endpoint: &str) -> Option<String> {
+	let url = Url::parse(endpoint).ok()?;
+	url.host_str().map(normalize)
+}
+
+impl NodePolicy {
+	fn is_denied_host(&self, host: &str) -> bool {
+		let host = normalize(host);
+		self.denied.iter().any(|denied| normalize(denied) == host)
+	}
+
+	/// Returns `true` if the host of the node, or the DNS name it is resolved from, is denied.
+	pub fn is_denied(&self, node: &Node) -> bool {
+		let host = url_host(&node.host);
+		host.iter()
+			.chain(node.server_name.iter())
+			.any(|host| self.is_denied_host(host))
+	}
+
+	/// Returns endpoints which are used, pinned nodes if set, or the configured nodes otherwise, without the denied ones.
+	pub fn endpoints(&self, configured: &[String]) -> Result<Vec<String>> {
+		let candidates = if self.pinned.is_empty() {
+			configured
+		} else {
+			&self.pinned
+		};
+		let mut endpoints = vec![];
+		for endpoint in candidates {
+			match url_host(endpoint) {
+				Some(host) if self.is_denied_host(&host) => warn!("RPC node {endpoint} is denied"),
+				_ => endpoints.push(endpoint.clone()),
+			}
+		}
+		if endpoints.is_empty() {
+			return Err(eyre!(
+				"There are no RPC nodes left, all of the configured nodes are denied"
+			));
+		}
+		Ok(endpoints)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use test_case::test_case;
+
+	use super::NodePolicy;
+	use crate::network::rpc::Node;
+
+	fn policy(pinned: &[&str], denied: &[&str]) -> NodePolicy {
+		NodePolicy {
+			pinned: pinned.iter().map(|node| node.to_string()).collect(),
+			denied: denied.iter().map(|host| host.to_string()).collect(),
+		}
+	}
+
+	fn configured() -> Vec<String> {
+		vec![
+			"wss://rpc.avail.so/ws".to_string(),
+			"ws://10.0.0.1:9944".to_string(),
+		]
+	}
+
+	#[test_case(&[], &[] => Ok(vec!["wss://rpc.avail.so/ws".to_string(), "ws://10.0.0.1:9944".to_string()]) ; "configured nodes")]
+	#[test_case(&["ws://trusted:9944"], &[] => Ok(vec!["ws://trusted:9944".to_string()]) ; "pinned nodes")]
+	#[test_case(&[], &["RPC.avail.so"] => Ok(vec!["ws://10.0.0.1:9944".to_string()]) ; "denied host")]
+	#[test_case(&["ws://trusted:9944"], &["trusted"] => Err(()) ; "denied pinned node")]
+	fn test_endpoints(pinned: &[&str], denied: &[&str]) -> Result<Vec<String>, ()> {
+		policy(pinned, denied)
+			.endpoints(&configured())
+			.map_err(|_| ())
+	}
+
+	#[test]
+	fn resolved_nodes_are_denied_by_name() {
+		let policy = policy(&[], &["rpc.avail.so", "::1"]);
+		let resolved = Node {
+			host: "wss://10.0.0.2/ws".to_string(),
+			server_name: Some("rpc.avail.so".to_string()),
+			..Default::default()
+		};
+		assert!(policy.is_denied(&resolved));
+		let ipv6 = Node {
+			host: "ws://[::1]:9944".to_string(),
+			..Default::default()
+		};
+		assert!(policy.is_denied(&ipv6));
+		let allowed = Node {
+			host: "ws://10.0.0.1:9944".to_string(),
+			..Default::default()
+		};
+		assert!(!policy.is_denied(&allowed));
+	}
+}
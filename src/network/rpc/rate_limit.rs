@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::HashMap,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::types::RuntimeConfig;
+
+/// Limits of the outbound RPC requests to a single node, unset limits are taken from the global configuration.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct NodeRateLimit {
+	/// Maximum number of requests per second
+	pub rate_limit: Option<f64>,
+	/// Maximum number of requests sent at once, after a period of inactivity
+	pub burst: Option<u32>,
+	/// Maximum number of concurrent requests
+	pub max_in_flight: Option<usize>,
+}
+
+/// RPC rate limiting configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct RateLimitConfig {
+	pub rate_limit: Option<f64>,
+	pub burst: u32,
+	pub max_in_flight: usize,
+	pub nodes: HashMap<String, NodeRateLimit>,
+}
+
+impl Default for RateLimitConfig {
+	fn default() -> Self {
+		(&RuntimeConfig::default()).into()
+	}
+}
+
+impl From<&RuntimeConfig> for RateLimitConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		Self {
+			rate_limit: val.rpc_rate_limit,
+			burst: val.rpc_rate_burst,
+			max_in_flight: val.rpc_max_in_flight,
+			nodes: val.rpc_node_limits.clone(),
+		}
+	}
+}
+
+/// Token bucket which refills at the given rate, up to the burst capacity.
+#[derive(Debug)]
+struct TokenBucket {
+	rate: f64,
+	capacity: f64,
+	tokens: f64,
+	updated_at: Instant,
+}
+
+impl TokenBucket {
+	fn new(rate: f64, burst: u32, now: Instant) -> Self {
+		let capacity = f64::from(burst.max(1));
+		Self {
+			rate,
+			capacity,
+			tokens: capacity,
+			updated_at: now,
+		}
+	}
+
+	/// Takes a token, returning the time to wait until the token is available.
+	/// Tokens are reserved ahead, so concurrent callers wait in turns.
+	fn take(&mut self, now: Instant) -> Duration {
+		let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+		self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+		self.updated_at = now;
+		self.tokens -= 1.0;
+		if self.tokens >= 0.0 {
+			return Duration::ZERO;
+		}
+		Duration::from_secs_f64(-self.tokens / self.rate)
+	}
+}
+
+struct HostLimiter {
+	bucket: Option<Mutex<TokenBucket>>,
+	in_flight: Arc<Semaphore>,
+}
+
+/// Per host rate limiter and concurrency cap of the outbound RPC requests.
+#[derive(Clone)]
+pub struct RateLimiter {
+	config: RateLimitConfig,
+	hosts: Arc<Mutex<HashMap<String, Arc<HostLimiter>>>>,
+}
+
+impl RateLimiter {
+	pub fn new(config: RateLimitConfig) -> Self {
+		Self {
+			config,
+			hosts: Default::default(),
+		}
+	}
+
+	fn host_limiter(&self, host: &str) -> Arc<HostLimiter> {
+		let mut hosts = self.hosts.lock().unwrap();
+		hosts
+			.entry(host.to_string())
+			.or_insert_with(|| {
+				let node = self.config.nodes.get(host).cloned().unwrap_or_default();
+				let rate = node
+					.rate_limit
+					.or(self.config.rate_limit)
+					.filter(|&rate| rate > 0.0);
+				let burst = node.burst.unwrap_or(self.config.burst);
+				let max_in_flight = node.max_in_flight.unwrap_or(self.config.max_in_flight);
+				Arc::new(HostLimiter {
+					bucket: rate
+						.map(|rate| Mutex::new(TokenBucket::new(rate, burst, Instant::now()))),
+					in_flight: Arc::new(Semaphore::new(max_in_flight.max(1))),
+				})
+			})
+			.clone()
+	}
+
+	/// Waits until the request to the host is allowed. Returned permit has to be held until the request completes.
+	pub async fn acquire(&self, host: &str) -> OwnedSemaphorePermit {
+		let limiter = self.host_limiter(host);
+		let permit = limiter
+			.in_flight
+			.clone()
+			.acquire_owned()
+			.await
+			.expect("Semaphore is never closed");
+		let wait = limiter
+			.bucket
+			.as_ref()
+			.map(|bucket| bucket.lock().unwrap().take(Instant::now()))
+			.unwrap_or_default();
+		if !wait.is_zero() {
+			tokio::time::sleep(wait).await;
+		}
+		permit
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		collections::HashMap,
+		time::{Duration, Instant},
+	};
+
+	use super::{NodeRateLimit, RateLimitConfig, RateLimiter, TokenBucket};
+
+	#[test]
+	fn token_bucket_refills() {
+		let now = Instant::now();
+		let mut bucket = TokenBucket::new(2.0, 2, now);
+		assert_eq!(bucket.take(now), Duration::ZERO);
+		assert_eq!(bucket.take(now), Duration::ZERO);
+		assert_eq!(bucket.take(now), Duration::from_millis(500));
+		assert_eq!(bucket.take(now), Duration::from_secs(1));
+
+		// refilled tokens cover the reserved ones first, and are capped by the burst
+		let later = now + Duration::from_secs(3);
+		assert_eq!(bucket.take(later), Duration::ZERO);
+		assert_eq!(bucket.take(later), Duration::ZERO);
+		assert_eq!(bucket.take(later), Duration::from_millis(500));
+	}
+
+	#[tokio::test]
+	async fn max_in_flight_per_node() {
+		let config = RateLimitConfig {
+			rate_limit: None,
+			burst: 1,
+			max_in_flight: 2,
+			nodes: HashMap::from([(
+				"ws://limited".to_string(),
+				NodeRateLimit {
+					max_in_flight: Some(1),
+					..Default::default()
+				},
+			)]),
+		};
+		let limiter = RateLimiter::new(config);
+
+		let _first = limiter.acquire("ws://limited").await;
+		assert_eq!(
+			limiter
+				.host_limiter("ws://limited")
+				.in_flight
+				.available_permits(),
+			0
+		);
+		let _other = limiter.acquire("ws://other").await;
+		assert_eq!(
+			limiter
+				.host_limiter("ws://other")
+				.in_flight
+				.available_permits(),
+			1
+		);
+	}
+}
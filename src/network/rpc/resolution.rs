@@ -0,0 +1,97 @@
+//! Resolution of the RPC endpoints given as DNS names into the node candidates of the resolved addresses.
+
+use hickory_resolver::TokioAsyncResolver;
+use std::net::IpAddr;
+use tracing::warn;
+use url::{Host, Url};
+
+use super::Node;
+
+/// Returns DNS name of the endpoint, or `None` if the endpoint host is an IP address.
+fn dns_name(url: &Url) -> Option<&str> {
+	match url.host()? {
+		Host::Domain(domain) => Some(domain),
+		Host::Ipv4(_) | Host::Ipv6(_) => None,
+	}
+}
+
+/// Returns node candidates of the endpoint, one for each of the resolved addresses.
+fn candidates(url: &Url, name: &str, addresses: impl IntoIterator<Item = IpAddr>) -> Vec<Node> {
+	let mut candidates: Vec<Node> = vec![];
+	for address in addresses {
+		let mut url = url.clone();
+		if url.set_ip_host(address).is_err() {
+			continue;
+		}
+		let host = url.to_string();
+		if candidates.iter().any(|node| node.host == host) {
+			continue;
+		}
+		candidates.push(Node::candidate(host, Some(name.to_string())));
+	}
+	candidates
+}
+
+/// Resolves node candidates of the configured endpoints, keeping the order of the endpoints.
+pub async fn resolve(resolver: &TokioAsyncResolver, endpoints: &[String]) -> Vec<Node> {
+	let mut nodes = vec![];
+	for endpoint in endpoints {
+		let unresolved = Node::candidate(endpoint.to_string(), None);
+		let Some(url) = Url::parse(endpoint).ok() else {
+			nodes.push(unresolved);
+			continue;
+		};
+		let Some(name) = dns_name(&url) else {
+			nodes.push(unresolved);
+			continue;
+		};
+		let resolved = match resolver.lookup_ip(name).await {
+			Ok(lookup) => candidates(&url, name, lookup.iter()),
+			Err(error) => {
+				warn!("Cannot resolve {endpoint}, using it unresolved: {error}");
+				vec![]
+			},
+		};
+		if resolved.is_empty() {
+			nodes.push(unresolved);
+		} else {
+			nodes.extend(resolved);
+		}
+	}
+	nodes
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+	use test_case::test_case;
+	use url::Url;
+
+	use super::{candidates, dns_name};
+
+	#[test_case("wss://rpc.avail.so/ws" => Some("rpc.avail.so".to_string()) ; "DNS name")]
+	#[test_case("ws://127.0.0.1:9944" => None ; "IPv4 address")]
+	#[test_case("ws://[::1]:9944" => None ; "IPv6 address")]
+	fn test_dns_name(endpoint: &str) -> Option<String> {
+		dns_name(&Url::parse(endpoint).unwrap()).map(String::from)
+	}
+
+	#[test]
+	fn candidate_for_each_address() {
+		let url = Url::parse("wss://rpc.avail.so:8443/ws").unwrap();
+		let addresses = [
+			IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+			IpAddr::V6(Ipv6Addr::LOCALHOST),
+			IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+		];
+		let candidates = candidates(&url, "rpc.avail.so", addresses);
+		let hosts = candidates
+			.iter()
+			.map(|node| node.host.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(hosts, vec!["wss://10.0.0.1:8443/ws", "wss://[::1]:8443/ws"]);
+		assert!(candidates
+			.iter()
+			.all(|node| node.server_name.as_deref() == Some("rpc.avail.so")));
+	}
+}
@@ -1,41 +1,50 @@
-use avail_subxt::primitives::{grandpa::AuthorityId, Header};
+use avail_subxt::{primitives::Header, utils::H256};
 use codec::Encode;
 use color_eyre::{eyre::eyre, Result};
-use sp_core::{
-	blake2_256,
-	ed25519::{self, Public},
-};
+use sp_core::blake2_256;
 use std::{
 	sync::{Arc, Mutex},
 	time::Instant,
 };
 use tokio::sync::broadcast::Sender;
 use tokio_stream::StreamExt;
-use tracing::{debug, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
-use super::{Client, Subscription};
+use super::{Client, LastKnownNode, Node, RpcEvent, Subscription};
 use crate::{
+	consensus::AuthoritySet,
 	data::Database,
 	data::{FinalitySyncCheckpoint, Key},
-	finality::{check_finality, ValidatorSet},
+	finality::{verify_header_finality, ValidatorSet},
 	types::{GrandpaJustification, OptionBlockRange, State},
-	utils::filter_auth_set_changes,
 };
 
+fn header_hash(header: &Header) -> H256 {
+	Encode::using_encoded(header, blake2_256).into()
+}
+
 #[derive(Clone, Debug)]
 pub enum Event {
 	HeaderUpdate {
 		header: Header,
 		received_at: Instant,
 	},
+	/// Connection to the node is lost, either because of subscription error, failed ping or stalled headers
+	ConnectionLost { reason: String },
+	/// Connection is restored and subscriptions are recreated
+	ConnectionRestored { node: Node },
 }
 
 struct BlockData {
 	justifications: Vec<GrandpaJustification>,
-	unverified_headers: Vec<(Header, Instant, ValidatorSet)>,
-	current_valset: ValidatorSet,
-	next_valset: Option<ValidatorSet>,
+	unverified_headers: Vec<(Header, Instant)>,
+	/// Authority set which follows the finalized headers
+	authority_set: AuthoritySet,
+	/// Number of the last header queued for verification
+	last_queued: u32,
 	last_finalized_block_header: Option<Header>,
+	/// Numbers and hashes of the headers which failed the finality check
+	rejected_headers: Vec<(u32, H256)>,
 }
 
 pub struct SubscriptionLoop<T: Database> {
@@ -79,12 +88,16 @@ impl<T: Database> SubscriptionLoop<T> {
 			block_data: BlockData {
 				justifications: Default::default(),
 				unverified_headers: Default::default(),
-				current_valset: ValidatorSet {
-					set_id,
-					validator_set,
-				},
-				next_valset: None,
+				authority_set: AuthoritySet::new(
+					ValidatorSet {
+						set_id,
+						validator_set,
+					},
+					last_finalized_block_header.number,
+				),
+				last_queued: last_finalized_block_header.number,
 				last_finalized_block_header: Some(last_finalized_block_header),
+				rejected_headers: Default::default(),
 			},
 		})
 	}
@@ -113,40 +126,29 @@ impl<T: Database> SubscriptionLoop<T> {
 				self.state.lock().unwrap().latest = header.clone().number;
 				info!("Header no.: {}", header.number);
 
-				// if new validator set becomes active, replace the current one
-				if self.block_data.next_valset.is_some() {
-					self.block_data.current_valset = self.block_data.next_valset.take().unwrap();
+				let last_queued = self.block_data.last_queued;
+				if header.number <= last_queued {
+					debug!("Header {} is already queued", header.number);
+					return;
 				}
 
-				// push new Unverified Header
-				self.block_data.unverified_headers.push((
-					header.clone(),
-					received_at,
-					self.block_data.current_valset.clone(),
-				));
-
-				// search the header logs for validator set change
-				let mut new_auths = filter_auth_set_changes(&header);
-				// if the event exists, send the new auths over the message channel.
-				if !new_auths.is_empty() {
-					// TODO: Handle this in a proper fashion
-					assert!(
-						new_auths.len() == 1,
-						"There should be only one valset change!"
-					);
-					let auths: Vec<(AuthorityId, u64)> = new_auths.pop().unwrap();
-					let new_valset = auths
-						.into_iter()
-						.map(|(a, _)| ed25519::Public::from_raw(a.0 .0 .0))
-						.collect::<Vec<Public>>();
-
-					self.block_data.next_valset = Some(ValidatorSet {
-						set_id: self.block_data.current_valset.set_id + 1,
-						validator_set: new_valset,
-					});
-
-					debug!("Validator set change: {:?}", self.block_data.next_valset);
+				// headers missed by the subscription can signal authority set changes,
+				// so they are queued before the received one
+				for number in (last_queued + 1)..header.number {
+					match self.rpc_client.get_header_by_block_number(number).await {
+						Ok((header, _)) => self.queue_header(header, Instant::now()),
+						// received header is not queued, so the gap is fetched again once the next header is received
+						Err(error) => {
+							warn!(
+								"Cannot fetch skipped header {number}, header {} is not queued: {error:#}",
+								header.number
+							);
+							return;
+						},
+					}
 				}
+
+				self.queue_header(header, received_at);
 			},
 			Subscription::Justification(justification) => {
 				info!(
@@ -155,11 +157,94 @@ impl<T: Database> SubscriptionLoop<T> {
 				);
 				self.block_data.justifications.push(justification);
 			},
+			Subscription::ConnectionLost(reason) => {
+				self.send_event(Event::ConnectionLost { reason });
+				return;
+			},
+			Subscription::ConnectionRestored(node) => {
+				self.send_event(Event::ConnectionRestored { node });
+				return;
+			},
+			// runtime upgrades are handled by the RPC client
+			Subscription::RuntimeVersion(_) => return,
 		}
 		// check headers
 		self.verify_and_output_block_headers().await;
 	}
 
+	/// Queues header for verification. Authority set changes of the header are imported only once it is finalized.
+	fn queue_header(&mut self, header: Header, received_at: Instant) {
+		self.block_data.last_queued = header.number;
+		self.block_data
+			.unverified_headers
+			.push((header, received_at));
+	}
+
+	/// Returns headers skipped since the last finalized header, linked to the justified header by parent hashes, in ascending order.
+	/// Walk stops at the header which cannot be fetched or was rejected, so unlinked headers are never emitted.
+	async fn linked_ancestors(&mut self, justified: &Header) -> Vec<(Header, Instant)> {
+		let Some(last_number) = self
+			.block_data
+			.last_finalized_block_header
+			.as_ref()
+			.map(|header| header.number)
+		else {
+			return vec![];
+		};
+
+		let mut ancestors = vec![];
+		let mut parent_hash = justified.parent_hash;
+		for number in ((last_number + 1)..justified.number).rev() {
+			if self
+				.block_data
+				.rejected_headers
+				.iter()
+				.any(|(_, hash)| *hash == parent_hash)
+			{
+				warn!("Skipped header {number} was rejected, older skipped headers are not sent");
+				break;
+			}
+			let unverified = self
+				.block_data
+				.unverified_headers
+				.iter()
+				.position(|(header, _)| header_hash(header) == parent_hash);
+			let (header, received_at) = match unverified {
+				Some(pos) => self.block_data.unverified_headers.swap_remove(pos),
+				None => {
+					// header was missed by the subscription
+					self.rpc_client.emit(RpcEvent::SubscriptionLagged {
+						from: number,
+						to: number + 1,
+					});
+					info!("Fetching skipped header {number} from RPC");
+					match self.rpc_client.get_header_by_hash(parent_hash).await {
+						Ok(header) => (header, Instant::now()),
+						// header is fetched again by the light client, once the next header is received
+						Err(error) => {
+							warn!("Cannot fetch skipped header {number}: {error:#}");
+							break;
+						},
+					}
+				},
+			};
+			if header.number != number || header_hash(&header) != parent_hash {
+				warn!("Skipped header {number} is not linked to the verified header");
+				break;
+			}
+			parent_hash = header.parent_hash;
+			ancestors.push((header, received_at));
+		}
+		ancestors.reverse();
+		ancestors
+	}
+
+	fn send_event(&self, event: Event) {
+		if let Err(error) = self.event_sender.send(event) {
+			debug!("Cannot send event, there are no receivers: {error}");
+		}
+	}
+
 	async fn verify_and_output_block_headers(&mut self) {
 		let mut finality_synced = false;
 		while let Some(justification) = self.block_data.justifications.pop() {
@@ -168,16 +253,36 @@ impl<T: Database> SubscriptionLoop<T> {
 				.block_data
 				.unverified_headers
 				.iter()
-				.map(|(h, _, _)| Encode::using_encoded(h, blake2_256).into())
+				.map(|(h, _)| header_hash(h))
 				.position(|hash| justification.commit.target_hash == hash)
 			{
 				// basically, pop it out of the collection
-				let (header, received_at, valset) =
-					self.block_data.unverified_headers.swap_remove(pos);
-
-				let is_final = check_finality(&valset, &justification);
+				let (header, received_at) = self.block_data.unverified_headers.swap_remove(pos);
 
-				is_final.expect("Finality check failed");
+				// skipped headers can signal authority set changes, so their changes are imported along with the header,
+				// and only if the header has valid finality proof
+				let ancestors = self.linked_ancestors(&header).await;
+				let result = self.block_data.authority_set.import_finalized_header(
+					ancestors.iter().map(|(ancestor, _)| ancestor),
+					&header,
+					|valset| verify_header_finality(&header, valset, &justification),
+				);
+				// refuse to process headers without valid finality proof
+				let valset = match result {
+					Ok(valset) => valset,
+					Err(error) => {
+						error!(
+							"Finality check failed for block {}, header is dropped: {error:#}",
+							header.number
+						);
+						self.block_data
+							.rejected_headers
+							.push((header.number, justification.commit.target_hash));
+						// skipped headers can still be finalized by another justification
+						self.block_data.unverified_headers.extend(ancestors);
+						continue;
+					},
+				};
 
 				// To avoid locking the global state all the time, after finality is synced, it will not be necessary to read the state
 				if !finality_synced {
@@ -190,48 +295,44 @@ impl<T: Database> SubscriptionLoop<T> {
 						.put(
 							Key::FinalitySyncCheckpoint,
 							FinalitySyncCheckpoint {
-								set_id: self.block_data.current_valset.set_id,
+								set_id: valset.set_id,
 								number: header.number,
-								validator_set: self.block_data.current_valset.validator_set.clone(),
+								validator_set: valset.validator_set.clone(),
 							},
 						)
 						.unwrap();
 				}
 
-				// try and get get all the skipped blocks, if they exist
-				if let Some(last_header) = self.block_data.last_finalized_block_header.as_ref() {
-					for bl_num in (last_header.number + 1)..header.number {
-						info!("Sending skipped block {bl_num}");
-						let (header, received_at) = match self
-							.block_data
-							.unverified_headers
-							.iter()
-							.position(|(h, _, _)| h.number == bl_num)
-						{
-							Some(pos) => {
-								info!("Fetching header from unverified headers");
-								let p = self.block_data.unverified_headers.swap_remove(pos);
-								(p.0, p.1)
-							},
-							None => {
-								info!("Fetching header from RPC");
-								let a = self
-									.rpc_client
-									.get_header_by_block_number(bl_num)
-									.await
-									.unwrap()
-									.0;
-								(a, Instant::now())
-							},
-						};
-						// send as output event
-						self.event_sender
-							.send(Event::HeaderUpdate {
-								header,
-								received_at,
-							})
-							.unwrap();
-					}
+				// skipped blocks are emitted only if they are ancestors of the verified header
+				for (header, received_at) in ancestors {
+					info!("Sending skipped block {}", header.number);
+					self.send_event(Event::HeaderUpdate {
+						header,
+						received_at,
+					});
+				}
+				// headers at or below the verified one are either emitted or not canonical
+				self.block_data
+					.unverified_headers
+					.retain(|(unverified, _)| unverified.number > header.number);
+				self.block_data
+					.rejected_headers
+					.retain(|(number, _)| *number > header.number);
+
+				// persist RPC node scores, so they survive restarts
+				if let Err(error) = self
+					.db
+					.put(Key::RpcNodeScores, self.rpc_client.node_scores())
+				{
+					warn!("Cannot store RPC node scores: {error:#}");
+				}
+				// persist the connected node, which is tried first after restart
+				let connected_node = self.state.lock().unwrap().connected_node.clone();
+				if let Err(error) = self
+					.db
+					.put(Key::RpcLastNode, LastKnownNode::from(&connected_node))
+				{
+					warn!("Cannot store last known RPC node: {error:#}");
 				}
 
 				info!("Sending finalized block {}", header.number);
@@ -244,12 +345,10 @@ impl<T: Database> SubscriptionLoop<T> {
 					.unwrap()
 					.header_verified
 					.set(header.number);
-				self.event_sender
-					.send(Event::HeaderUpdate {
-						header,
-						received_at,
-					})
-					.unwrap();
+				self.send_event(Event::HeaderUpdate {
+					header,
+					received_at,
+				});
 			} else {
 				trace!("Matched pair of header/justification not found.");
 				self.block_data.justifications.push(justification);
@@ -0,0 +1,320 @@
+//! Transport of the connections to the RPC nodes, with the custom TLS parameters and the proxy (if configured).
+
+use async_trait::async_trait;
+use avail_subxt::{avail, build_client};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use jsonrpsee::core::client::{
+	ClientBuilder, ReceivedMessage, TransportReceiverT, TransportSenderT,
+};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerName};
+use rustls_pemfile::Item;
+use soketto::{
+	connection,
+	handshake::client::{Client as HandshakeClient, ServerResponse},
+	Data, Incoming,
+};
+use std::{
+	fs::File,
+	io::BufReader,
+	path::{Path, PathBuf},
+	sync::Arc,
+};
+use tokio::net::TcpStream;
+use tokio_rustls::{client::TlsStream, TlsConnector};
+use tokio_util::{
+	compat::{Compat, TokioAsyncReadCompatExt},
+	either::Either,
+};
+use tracing::debug;
+use url::{Host, Url};
+
+use crate::{network::proxy::Proxy, types::RuntimeConfig};
+
+/// Transport configuration of the RPC connections (see [RuntimeConfig] for details)
+#[derive(Clone, Debug, Default)]
+pub struct TransportConfig {
+	pub ca_bundle: Option<PathBuf>,
+	pub client_cert: Option<PathBuf>,
+	pub client_key: Option<PathBuf>,
+	pub proxy: Option<Proxy>,
+	pub resolve_dns: bool,
+}
+
+impl From<&RuntimeConfig> for TransportConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		Self {
+			ca_bundle: val.rpc_tls_ca_bundle.as_ref().map(PathBuf::from),
+			client_cert: val.rpc_tls_client_cert.as_ref().map(PathBuf::from),
+			client_key: val.rpc_tls_client_key.as_ref().map(PathBuf::from),
+			proxy: val.proxy.clone(),
+			resolve_dns: val.full_node_dns_resolution_enable,
+		}
+	}
+}
+
+impl TransportConfig {
+	fn has_tls_parameters(&self) -> bool {
+		self.ca_bundle.is_some() || self.client_cert.is_some() || self.client_key.is_some()
+	}
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+	let file = File::open(path).wrap_err_with(|| format!("Cannot open {}", path.display()))?;
+	Ok(BufReader::new(file))
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<Certificate>> {
+	let certificates = rustls_pemfile::certs(&mut open(path)?)
+		.wrap_err_with(|| format!("Cannot read certificates from {}", path.display()))?;
+	if certificates.is_empty() {
+		return Err(eyre!("There are no certificates in {}", path.display()));
+	}
+	Ok(certificates.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKey> {
+	rustls_pemfile::read_all(&mut open(path)?)
+		.wrap_err_with(|| format!("Cannot read private key from {}", path.display()))?
+		.into_iter()
+		.find_map(|item| match item {
+			Item::RSAKey(key) | Item::PKCS8Key(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+			_ => None,
+		})
+		.ok_or_else(|| eyre!("There is no private key in {}", path.display()))
+}
+
+/// TLS configuration with the native and configured root certificates, and the client certificate if configured.
+pub(crate) fn tls_client_config(config: &TransportConfig) -> Result<ClientConfig> {
+	let mut roots = RootCertStore::empty();
+	let native = rustls_native_certs::load_native_certs()
+		.wrap_err("Cannot load native root certificates")?;
+	// Invalid native certificates are skipped, same as by the default transport
+	let (_, ignored) =
+		roots.add_parsable_certificates(&native.into_iter().map(|cert| cert.0).collect::<Vec<_>>());
+	if ignored > 0 {
+		debug!("Ignored {ignored} invalid native root certificates");
+	}
+	if let Some(path) = &config.ca_bundle {
+		for certificate in load_certificates(path)? {
+			roots
+				.add(&certificate)
+				.wrap_err_with(|| format!("Invalid root certificate in {}", path.display()))?;
+		}
+	}
+
+	let builder = ClientConfig::builder()
+		.with_safe_defaults()
+		.with_root_certificates(roots);
+	match (&config.client_cert, &config.client_key) {
+		(Some(cert), Some(key)) => builder
+			.with_client_auth_cert(load_certificates(cert)?, load_private_key(key)?)
+			.wrap_err("Invalid client certificate or key"),
+		(None, None) => Ok(builder.with_no_client_auth()),
+		_ => Err(eyre!(
+			"Both client certificate and key have to be configured"
+		)),
+	}
+}
+
+/// Creates subxt clients connected to the RPC nodes.
+#[derive(Clone, Default)]
+pub struct Transport {
+	tls: Option<Arc<ClientConfig>>,
+	proxy: Option<Proxy>,
+}
+
+impl Transport {
+	/// Creates transport with the configured TLS parameters and proxy, certificates and key are loaded immediately.
+	pub fn new(config: &TransportConfig) -> Result<Self> {
+		if !config.has_tls_parameters() && config.proxy.is_none() && !config.resolve_dns {
+			return Ok(Self::default());
+		}
+		Ok(Self {
+			tls: Some(Arc::new(tls_client_config(config)?)),
+			proxy: config.proxy.clone(),
+		})
+	}
+
+	/// Connects to the node, using the configured TLS parameters for `wss://` nodes, and the configured proxy.
+	/// Server name is the DNS name of the node, if the host is its resolved address.
+	pub async fn connect(&self, host: &str, server_name: Option<&str>) -> Result<avail::Client> {
+		let url = Url::parse(host).wrap_err_with(|| format!("Invalid node URL {host}"))?;
+		match &self.tls {
+			Some(tls) if url.scheme() == "wss" || self.proxy.is_some() || server_name.is_some() => {
+				connect(&url, server_name, tls.clone(), self.proxy.as_ref()).await
+			},
+			_ => {
+				let (client, _) = build_client(host, false).await.map_err(|e| eyre!(e))?;
+				Ok(client)
+			},
+		}
+	}
+}
+
+pub(crate) type Stream = Compat<Either<TlsStream<TcpStream>, TcpStream>>;
+
+struct Sender(connection::Sender<Stream>);
+
+struct Receiver(connection::Receiver<Stream>);
+
+#[async_trait]
+impl TransportSenderT for Sender {
+	type Error = connection::Error;
+
+	async fn send(&mut self, message: String) -> Result<(), Self::Error> {
+		self.0.send_text(message).await?;
+		self.0.flush().await
+	}
+}
+
+#[async_trait]
+impl TransportReceiverT for Receiver {
+	type Error = connection::Error;
+
+	async fn receive(&mut self) -> Result<ReceivedMessage, Self::Error> {
+		loop {
+			let mut message = Vec::new();
+			match self.0.receive(&mut message).await? {
+				Incoming::Data(Data::Text(_)) => {
+					return String::from_utf8(message)
+						.map(ReceivedMessage::Text)
+						.map_err(|error| connection::Error::Utf8(error.utf8_error()))
+				},
+				Incoming::Data(Data::Binary(_)) => return Ok(ReceivedMessage::Bytes(message)),
+				Incoming::Pong(_) => continue,
+				Incoming::Closed(_) => return Err(connection::Error::Closed),
+			}
+		}
+	}
+}
+
+/// Opens WebSocket connection to the `ws://` or `wss://` URL, through the proxy if configured.
+/// If server name is given, it is used instead of the URL host for the TLS handshake and the `Host` header.
+pub(crate) async fn websocket(
+	url: &Url,
+	server_name: Option<&str>,
+	tls: Arc<ClientConfig>,
+	proxy: Option<&Proxy>,
+) -> Result<(connection::Sender<Stream>, connection::Receiver<Stream>)> {
+	// IPv6 addresses are used without brackets
+	let host = match url.host() {
+		Some(Host::Ipv6(ip)) => ip.to_string(),
+		Some(host) => host.to_string(),
+		None => return Err(eyre!("There is no host in the node URL {url}")),
+	};
+	let port = url.port_or_known_default().unwrap_or(443);
+
+	let stream = match proxy {
+		Some(proxy) => proxy
+			.connect(&host, port)
+			.await
+			.wrap_err_with(|| format!("Cannot connect to {host}:{port} through proxy {proxy}"))?,
+		None => TcpStream::connect((host.as_str(), port))
+			.await
+			.wrap_err_with(|| format!("Cannot connect to {host}:{port}"))?,
+	};
+	let stream = match url.scheme() {
+		"wss" => {
+			let server_name = ServerName::try_from(server_name.unwrap_or(host.as_str()))
+				.wrap_err("Invalid server name")?;
+			let stream = TlsConnector::from(tls)
+				.connect(server_name, stream)
+				.await
+				.wrap_err("TLS handshake failed")?;
+			Either::Left(stream)
+		},
+		_ => Either::Right(stream),
+	};
+
+	let host = server_name.or(url.host_str()).unwrap_or_default();
+	let host_header = match url.port() {
+		Some(port) => format!("{host}:{port}"),
+		None => host.to_string(),
+	};
+	let resource = match url.query() {
+		Some(query) => format!("{}?{query}", url.path()),
+		None => url.path().to_string(),
+	};
+	let mut client = HandshakeClient::new(stream.compat(), &host_header, &resource);
+	match client
+		.handshake()
+		.await
+		.wrap_err("WebSocket handshake failed")?
+	{
+		ServerResponse::Accepted { .. } => (),
+		ServerResponse::Rejected { status_code } => {
+			return Err(eyre!(
+				"WebSocket handshake rejected with status {status_code}"
+			))
+		},
+		ServerResponse::Redirect { status_code, .. } => {
+			return Err(eyre!(
+				"WebSocket handshake redirected with status {status_code}, redirects are not supported"
+			))
+		},
+	}
+	Ok(client.into_builder().finish())
+}
+
+async fn connect(
+	url: &Url,
+	server_name: Option<&str>,
+	tls: Arc<ClientConfig>,
+	proxy: Option<&Proxy>,
+) -> Result<avail::Client> {
+	let (sender, receiver) = websocket(url, server_name, tls, proxy).await?;
+	let rpc_client = ClientBuilder::default().build_with_tokio(Sender(sender), Receiver(receiver));
+	avail::Client::from_rpc_client(Arc::new(rpc_client))
+		.await
+		.wrap_err("Cannot create client")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, path::PathBuf};
+
+	use super::{Transport, TransportConfig};
+
+	fn temp_file(name: &str, content: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("avail-light-{}-{name}", std::process::id()));
+		fs::write(&path, content).unwrap();
+		path
+	}
+
+	#[test]
+	fn default_transport_without_tls_config() {
+		let transport = Transport::new(&TransportConfig::default()).unwrap();
+		assert!(transport.tls.is_none());
+
+		let config = TransportConfig {
+			proxy: Some("socks5://127.0.0.1:9050".parse().unwrap()),
+			..Default::default()
+		};
+		let transport = Transport::new(&config).unwrap();
+		assert!(transport.tls.is_some() && transport.proxy.is_some());
+	}
+
+	#[test]
+	fn invalid_tls_config() {
+		let config = TransportConfig {
+			ca_bundle: Some(PathBuf::from("/nonexistent/ca.pem")),
+			..Default::default()
+		};
+		assert!(Transport::new(&config).is_err());
+
+		let config = TransportConfig {
+			ca_bundle: Some(temp_file("empty-ca.pem", "not a certificate")),
+			..Default::default()
+		};
+		assert!(Transport::new(&config).is_err());
+
+		let config = TransportConfig {
+			client_cert: Some(temp_file("client.pem", "")),
+			..Default::default()
+		};
+		assert!(Transport::new(&config).is_err());
+	}
+}
@@ -0,0 +1,115 @@
+//! Auto-tuning of the fetch batch sizes (additive increase, multiplicative decrease), based on the observed latency and errors.
+
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+use tracing::debug;
+
+/// Batch size auto-tuning configuration (see [RuntimeConfig](crate::types::RuntimeConfig) for details)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TuningConfig {
+	pub min: usize,
+	pub max: usize,
+	pub latency_target: Duration,
+}
+
+/// Batch size shared by the fetches, adjusted by AIMD if tuning is configured.
+#[derive(Clone, Debug)]
+pub struct BatchSize {
+	name: &'static str,
+	value: Arc<AtomicUsize>,
+	tuning: Option<TuningConfig>,
+}
+
+impl BatchSize {
+	pub fn new(name: &'static str, initial: usize, tuning: Option<TuningConfig>) -> Self {
+		let value = match tuning {
+			Some(TuningConfig { min, max, .. }) => initial.clamp(min.max(1), max.max(min).max(1)),
+			None => initial.max(1),
+		};
+		BatchSize {
+			name,
+			value: Arc::new(AtomicUsize::new(value)),
+			tuning,
+		}
+	}
+
+	pub fn get(&self) -> usize {
+		self.value.load(Ordering::Relaxed)
+	}
+
+	/// Records the outcome of the batch, adjusting the batch size if tuning is configured.
+	pub fn record(&self, latency: Duration, failed: bool) {
+		let Some(TuningConfig {
+			min,
+			max,
+			latency_target,
+		}) = self.tuning
+		else {
+			return;
+		};
+		let (min, max) = (min.max(1), max.max(min).max(1));
+		let decrease = failed || latency > latency_target;
+		let update = |value: usize| {
+			let tuned = if decrease { value / 2 } else { value + 1 };
+			Some(tuned.clamp(min, max))
+		};
+		if let Ok(previous) = self
+			.value
+			.fetch_update(Ordering::Relaxed, Ordering::Relaxed, update)
+		{
+			if decrease && previous > min {
+				debug!(
+					"Decreased {} to {} (latency {latency:?}, failed: {failed})",
+					self.name,
+					self.get()
+				);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::{BatchSize, TuningConfig};
+
+	const TARGET: Duration = Duration::from_millis(500);
+
+	fn tuned(initial: usize) -> BatchSize {
+		let config = TuningConfig {
+			min: 4,
+			max: 10,
+			latency_target: TARGET,
+		};
+		BatchSize::new("batch size", initial, Some(config))
+	}
+
+	#[test]
+	fn additive_increase_multiplicative_decrease() {
+		let batch = tuned(8);
+		batch.record(Duration::from_millis(100), false);
+		batch.record(Duration::from_millis(100), false);
+		assert_eq!(batch.get(), 10);
+		batch.record(Duration::from_millis(100), false);
+		assert_eq!(batch.get(), 10);
+
+		batch.record(TARGET * 2, false);
+		assert_eq!(batch.get(), 5);
+		batch.record(Duration::from_millis(100), true);
+		assert_eq!(batch.get(), 4);
+	}
+
+	#[test]
+	fn fixed_batch_size_is_not_tuned() {
+		assert_eq!(tuned(100).get(), 10);
+		let fixed = BatchSize::new("batch size", 30, None);
+		fixed.record(TARGET * 2, true);
+		assert_eq!(fixed.get(), 30);
+	}
+}
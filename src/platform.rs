@@ -0,0 +1,113 @@
+//! Platform specific runtime, so the client tasks can run on the native `tokio` runtime, or on the browser event loop.
+
+use std::{future::Future, time::Duration};
+
+/// Marker of the futures which can be spawned on the platform runtime, it is `Send` on the native targets only.
+#[cfg(not(target_arch = "wasm32"))]
+pub trait MaybeSend: Send {}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send> MaybeSend for T {}
+
+/// Marker of the futures which can be spawned on the platform runtime, it is `Send` on the native targets only.
+#[cfg(target_arch = "wasm32")]
+pub trait MaybeSend {}
+
+#[cfg(target_arch = "wasm32")]
+impl<T> MaybeSend for T {}
+
+/// Runtime on which the tasks are spawned, and the timers are run.
+pub trait Runtime {
+	type Sleep: Future<Output = ()>;
+
+	/// Spawns the task which runs in the background until it completes, its output is dropped.
+	fn spawn<F>(future: F)
+	where
+		F: Future + MaybeSend + 'static,
+		F::Output: MaybeSend + 'static;
+
+	/// Returns the future which completes after the given duration.
+	fn sleep(duration: Duration) -> Self::Sleep;
+}
+
+/// Multi-threaded `tokio` runtime of the native targets
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Tokio;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Runtime for Tokio {
+	type Sleep = tokio::time::Sleep;
+
+	fn spawn<F>(future: F)
+	where
+		F: Future + MaybeSend + 'static,
+		F::Output: MaybeSend + 'static,
+	{
+		tokio::spawn(future);
+	}
+
+	fn sleep(duration: Duration) -> Self::Sleep {
+		tokio::time::sleep(duration)
+	}
+}
+
+/// Browser event loop of the `wasm32` target
+#[cfg(target_arch = "wasm32")]
+pub struct Browser;
+
+#[cfg(target_arch = "wasm32")]
+impl Runtime for Browser {
+	type Sleep = gloo_timers::future::TimeoutFuture;
+
+	fn spawn<F>(future: F)
+	where
+		F: Future + MaybeSend + 'static,
+		F::Output: MaybeSend + 'static,
+	{
+		wasm_bindgen_futures::spawn_local(async move {
+			future.await;
+		});
+	}
+
+	fn sleep(duration: Duration) -> Self::Sleep {
+		gloo_timers::future::sleep(duration)
+	}
+}
+
+/// Runtime of the target platform
+#[cfg(not(target_arch = "wasm32"))]
+pub type Platform = Tokio;
+
+/// Runtime of the target platform
+#[cfg(target_arch = "wasm32")]
+pub type Platform = Browser;
+
+/// Spawns the task on the runtime of the target platform.
+pub fn spawn<F>(future: F)
+where
+	F: Future + MaybeSend + 'static,
+	F::Output: MaybeSend + 'static,
+{
+	Platform::spawn(future)
+}
+
+/// Returns the future which completes after the given duration, on the runtime of the target platform.
+pub fn sleep(duration: Duration) -> <Platform as Runtime>::Sleep {
+	Platform::sleep(duration)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use tokio::sync::oneshot;
+
+	#[tokio::test]
+	async fn spawned_task_runs_in_background() {
+		let (sender, receiver) = oneshot::channel();
+		super::spawn(async move {
+			super::sleep(Duration::from_millis(10)).await;
+			sender.send(42).unwrap();
+		});
+		assert_eq!(receiver.await.unwrap(), 42);
+	}
+}
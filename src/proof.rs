@@ -1,6 +1,6 @@
-//! Parallelized proof verification
+//! Parallelized proof verification on the dedicated worker pool, with the latest blocks verified before the synced blocks.
 
-use color_eyre::eyre;
+use color_eyre::eyre::{self, eyre};
 use dusk_plonk::commitment_scheme::kzg10::PublicParameters;
 use itertools::{Either, Itertools};
 use kate_recovery::{
@@ -8,22 +8,244 @@ use kate_recovery::{
 	matrix::{Dimensions, Position},
 	proof,
 };
-use std::sync::Arc;
-use tokio::{task::JoinSet, time::Instant};
-use tracing::debug;
+use std::{
+	collections::VecDeque,
+	panic::{self, AssertUnwindSafe},
+	sync::{Arc, Condvar, Mutex, OnceLock},
+	thread,
+};
+use tokio::{
+	sync::{oneshot, OwnedSemaphorePermit, Semaphore},
+	time::Instant,
+};
+use tracing::{debug, info, warn};
 
-async fn verify_proof(
-	public_parameters: Arc<PublicParameters>,
+use crate::types::RuntimeConfig;
+
+/// Number of cells verified in a single chunk on one core, each cell proof is verified separately
+const VERIFICATION_CHUNK_SIZE: usize = 16;
+
+/// Priority lane of the verification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lane {
+	/// Latest blocks, verified first
+	Head,
+	/// Blocks being synced or backfilled
+	Sync,
+}
+
+/// Verification worker pool configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Copy, Debug)]
+pub struct VerifierConfig {
+	pub threads: usize,
+	pub queue_size: usize,
+}
+
+impl Default for VerifierConfig {
+	fn default() -> Self {
+		(&RuntimeConfig::default()).into()
+	}
+}
+
+impl From<&RuntimeConfig> for VerifierConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		let threads = match val.proof_verification_threads {
+			0 => thread::available_parallelism().map_or(1, |threads| threads.get()),
+			threads => threads,
+		};
+		VerifierConfig {
+			threads,
+			queue_size: val.proof_verification_queue_size.max(1),
+		}
+	}
+}
+
+type ChunkResult = eyre::Result<Vec<(Position, bool)>>;
+
+/// Task run by the worker, with the slot of the lane queue, released once the task is run
+struct Job {
+	task: Box<dyn FnOnce() + Send>,
+	_slot: OwnedSemaphorePermit,
+}
+
+struct Queues<T> {
+	head: VecDeque<T>,
+	sync: VecDeque<T>,
+}
+
+impl<T> Default for Queues<T> {
+	fn default() -> Self {
+		Queues {
+			head: VecDeque::new(),
+			sync: VecDeque::new(),
+		}
+	}
+}
+
+impl<T> Queues<T> {
+	fn push(&mut self, lane: Lane, item: T) {
+		match lane {
+			Lane::Head => self.head.push_back(item),
+			Lane::Sync => self.sync.push_back(item),
+		}
+	}
+
+	/// Takes the oldest item of the head lane, or of the sync lane if the head lane is empty.
+	fn pop(&mut self) -> Option<T> {
+		self.head.pop_front().or_else(|| self.sync.pop_front())
+	}
+}
+
+struct WorkerPool {
+	queues: Arc<(Mutex<Queues<Job>>, Condvar)>,
+	head_slots: Arc<Semaphore>,
+	sync_slots: Arc<Semaphore>,
+}
+
+/// Started worker pool, or the error if no worker could be started
+static POOL: OnceLock<Result<WorkerPool, String>> = OnceLock::new();
+
+impl WorkerPool {
+	fn start(config: VerifierConfig) -> Result<Self, String> {
+		let queues = Arc::new((Mutex::new(Queues::default()), Condvar::new()));
+		let mut threads = 0;
+		for index in 0..config.threads.max(1) {
+			let queues = queues.clone();
+			let spawned = thread::Builder::new()
+				.name(format!("proof-verifier-{index}"))
+				.spawn(move || work(&queues));
+			match spawned {
+				Ok(_) => threads += 1,
+				Err(error) => warn!("Cannot start proof verification worker: {error}"),
+			}
+		}
+		if threads == 0 {
+			return Err("Cannot start any proof verification worker".to_string());
+		}
+		info!(
+			"Started {threads} proof verification workers, with {} chunks queued per lane",
+			config.queue_size
+		);
+		Ok(WorkerPool {
+			queues,
+			head_slots: Arc::new(Semaphore::new(config.queue_size)),
+			sync_slots: Arc::new(Semaphore::new(config.queue_size)),
+		})
+	}
+
+	fn slots(&self, lane: Lane) -> &Arc<Semaphore> {
+		match lane {
+			Lane::Head => &self.head_slots,
+			Lane::Sync => &self.sync_slots,
+		}
+	}
+
+	fn push(&self, lane: Lane, job: Job) {
+		let (queues, available) = &*self.queues;
+		let mut queues = queues.lock().expect("Lock should be acquired");
+		queues.push(lane, job);
+		available.notify_one();
+	}
+
+	/// Submits the task to the lane queue, waiting for the free slot if the queue is full.
+	async fn submit(&self, lane: Lane, task: impl FnOnce() + Send + 'static) -> eyre::Result<()> {
+		let slot = self
+			.slots(lane)
+			.clone()
+			.acquire_owned()
+			.await
+			.map_err(|_| eyre!("Proof verification queue is closed"))?;
+		self.push(
+			lane,
+			Job {
+				task: Box::new(task),
+				_slot: slot,
+			},
+		);
+		Ok(())
+	}
+
+	/// Submits the task to the lane queue, or returns `false` if the queue is full.
+	fn try_submit(&self, lane: Lane, task: impl FnOnce() + Send + 'static) -> bool {
+		let Ok(slot) = self.slots(lane).clone().try_acquire_owned() else {
+			return false;
+		};
+		self.push(
+			lane,
+			Job {
+				task: Box::new(task),
+				_slot: slot,
+			},
+		);
+		true
+	}
+}
+
+fn work(queues: &(Mutex<Queues<Job>>, Condvar)) {
+	let (queues, available) = queues;
+	loop {
+		let job = {
+			let mut queues = queues.lock().expect("Lock should be acquired");
+			loop {
+				if let Some(job) = queues.pop() {
+					break job;
+				}
+				queues = available.wait(queues).expect("Lock should be acquired");
+			}
+		};
+		// Worker keeps running if the task panics
+		if panic::catch_unwind(AssertUnwindSafe(job.task)).is_err() {
+			warn!("Proof verification task panicked");
+		}
+	}
+}
+
+/// Returns the worker pool, starting it with the given configuration on the first call.
+fn pool(config: impl FnOnce() -> VerifierConfig) -> eyre::Result<&'static WorkerPool> {
+	POOL.get_or_init(|| WorkerPool::start(config()))
+		.as_ref()
+		.map_err(|error| eyre!("{error}"))
+}
+
+/// Starts the verification worker pool with the given configuration, unless it is already started.
+/// Fails if no worker thread can be started.
+pub fn init(config: VerifierConfig) -> eyre::Result<()> {
+	if POOL.get().is_some() {
+		warn!("Proof verification workers are already started");
+	}
+	pool(|| config).map(|_| ())
+}
+
+/// Runs the CPU bound verification task on the worker pool in the given lane, unless the queue of the lane is full.
+/// Returns `false` if the task is dropped, since the queue of the lane is full.
+pub fn try_spawn(lane: Lane, task: impl FnOnce() + Send + 'static) -> eyre::Result<bool> {
+	Ok(pool(VerifierConfig::default)?.try_submit(lane, task))
+}
+
+/// Verifies cell proofs of the chunk one by one, since batched opening verification is not exposed by `kate_recovery`.
+fn verify_chunk(
+	public_parameters: &PublicParameters,
 	dimensions: Dimensions,
-	commitment: [u8; 48],
-	cell: Cell,
-) -> Result<(Position, bool), proof::Error> {
-	proof::verify(&public_parameters, dimensions, &commitment, &cell)
-		.map(|verified| (cell.position, verified))
+	commitments: &[[u8; 48]],
+	cells: &[Cell],
+) -> ChunkResult {
+	cells
+		.iter()
+		.map(|cell| {
+			let row = cell.position.row;
+			let commitment = commitments
+				.get(row as usize)
+				.ok_or_else(|| eyre!("Commitment of the row {row} is missing"))?;
+			let verified = proof::verify(public_parameters, dimensions, commitment, cell)?;
+			Ok((cell.position, verified))
+		})
+		.collect()
 }
 
-/// Verifies proofs for given block, cells and commitments
+/// Verifies proofs for given block, cells and commitments, in the given priority lane
+#[tracing::instrument(name = "verify_proofs", level = "info", skip_all, fields(block_num, cells = cells.len()))]
 pub async fn verify(
+	lane: Lane,
 	block_num: u32,
 	dimensions: Dimensions,
 	cells: &[Cell],
@@ -36,23 +258,33 @@ pub async fn verify(
 
 	let start_time = Instant::now();
 
-	let mut tasks = JoinSet::new();
-
-	for cell in cells {
-		tasks.spawn(verify_proof(
-			public_parameters.clone(),
-			dimensions,
-			commitments[cell.position.row as usize],
-			cell.clone(),
-		));
+	// CPU bound verification is moved off the async runtime
+	let pool = pool(VerifierConfig::default)?;
+	let commitments = Arc::new(commitments.to_vec());
+	let mut receivers = Vec::with_capacity(cells.len() / VERIFICATION_CHUNK_SIZE + 1);
+	for chunk in cells.chunks(VERIFICATION_CHUNK_SIZE) {
+		let (sender, receiver) = oneshot::channel();
+		let public_parameters = public_parameters.clone();
+		let commitments = commitments.clone();
+		let chunk = chunk.to_vec();
+		pool.submit(lane, move || {
+			// Panics of the verification are returned as errors of the chunk
+			let result = panic::catch_unwind(AssertUnwindSafe(|| {
+				verify_chunk(&public_parameters, dimensions, &commitments, &chunk)
+			}))
+			.unwrap_or_else(|_| Err(eyre!("Proof verification panicked")));
+			let _ = sender.send(result);
+		})
+		.await?;
+		receivers.push(receiver);
 	}
 
 	let mut results = Vec::with_capacity(cells.len());
-	while let Some(result) = tasks.join_next().await {
-		results.push(result??)
+	for receiver in receivers {
+		results.extend(receiver.await??);
 	}
 
-	debug!(block_num, duration = ?start_time.elapsed(), "Proof verification completed");
+	debug!(block_num, ?lane, duration = ?start_time.elapsed(), "Proof verification completed");
 
 	Ok(results
 		.into_iter()
@@ -61,3 +293,50 @@ pub async fn verify(
 			false => Either::Right(position),
 		}))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{verify_chunk, Lane, Queues, VerifierConfig, WorkerPool};
+	use kate_recovery::{
+		data::Cell,
+		matrix::{Dimensions, Position},
+		testnet,
+	};
+
+	#[test]
+	fn head_lane_is_taken_first() {
+		let mut queues = Queues::default();
+		queues.push(Lane::Sync, 1);
+		queues.push(Lane::Head, 2);
+		queues.push(Lane::Sync, 3);
+		queues.push(Lane::Head, 4);
+		let taken = std::iter::from_fn(|| queues.pop()).collect::<Vec<_>>();
+		assert_eq!(taken, vec![2, 4, 1, 3]);
+	}
+
+	#[test]
+	fn full_queue_drops_the_task() {
+		let config = VerifierConfig {
+			threads: 1,
+			queue_size: 1,
+		};
+		let pool = WorkerPool::start(config).unwrap();
+		let (release, blocked) = std::sync::mpsc::channel::<()>();
+		assert!(pool.try_submit(Lane::Sync, move || _ = blocked.recv()));
+		assert!(!pool.try_submit(Lane::Sync, || {}));
+		assert!(pool.try_submit(Lane::Head, || {}));
+		release.send(()).unwrap();
+	}
+
+	#[test]
+	fn missing_commitment_is_an_error() {
+		let public_parameters = testnet::public_params(1024);
+		let dimensions = Dimensions::new(2, 4).unwrap();
+		let cell = Cell {
+			position: Position { row: 1, col: 0 },
+			content: [0u8; 80],
+		};
+		let result = verify_chunk(&public_parameters, dimensions, &[[0u8; 48]], &[cell]);
+		assert!(result.is_err());
+	}
+}
@@ -0,0 +1,447 @@
+//! Recording of the blocks fetched from the node, and their deterministic replay by the light client pipeline.
+
+use async_trait::async_trait;
+use avail_subxt::primitives::Header;
+use codec::Encode;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use dusk_plonk::prelude::PublicParameters;
+use kate_recovery::{
+	config::COMMITMENT_SIZE,
+	data::Cell,
+	matrix::{Dimensions, Position},
+};
+use serde::{Deserialize, Serialize};
+use sp_core::{blake2_256, H256};
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{BufReader, BufWriter},
+	ops::RangeInclusive,
+	path::Path,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::{
+	data::{mem_db::MemoryDB, CellSource, Database, Key, SampledCell},
+	light_client,
+	network::{self, rpc, FetchStats},
+	proof::{self, Lane},
+	sampling::SamplingStrategy,
+	telemetry,
+	types::{LightClientConfig, State},
+	utils::extract_kate,
+	verification::{CommitmentMismatch, MismatchReason},
+};
+
+/// Version of the recording format
+pub const RECORDING_VERSION: u32 = 1;
+
+/// Cell with the proof, as fetched from the node.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCell {
+	pub row: u32,
+	pub col: u16,
+	#[serde(with = "sp_core::bytes")]
+	pub content: Vec<u8>,
+}
+
+impl From<&Cell> for RecordedCell {
+	fn from(cell: &Cell) -> Self {
+		RecordedCell {
+			row: cell.position.row,
+			col: cell.position.col,
+			content: cell.content.to_vec(),
+		}
+	}
+}
+
+impl TryFrom<&RecordedCell> for Cell {
+	type Error = color_eyre::Report;
+
+	fn try_from(cell: &RecordedCell) -> Result<Self> {
+		let content = cell.content.as_slice().try_into().map_err(|_| {
+			eyre!(
+				"Invalid content length {} of the recorded cell {}:{}",
+				cell.content.len(),
+				cell.row,
+				cell.col
+			)
+		})?;
+		Ok(Cell {
+			position: Position {
+				row: cell.row,
+				col: cell.col,
+			},
+			content,
+		})
+	}
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedBlock {
+	pub header: Header,
+	/// Hex encoded finality proof, omitted if the node didn't return it
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub finality_proof: Option<String>,
+	pub cells: Vec<RecordedCell>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Recording {
+	pub version: u32,
+	pub genesis_hash: String,
+	pub sampling_seed: String,
+	pub sampling_strategy: SamplingStrategy,
+	pub cell_count: u32,
+	/// Blocks in the ascending order
+	pub blocks: Vec<RecordedBlock>,
+}
+
+/// Outcome of the replayed block.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReplayedBlock {
+	pub block_number: u32,
+	pub block_hash: H256,
+	/// Confidence, if the block achieved confidence
+	pub confidence: Option<f64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub mismatch: Option<MismatchReason>,
+	/// Whether the recorded finality proof can be decoded, omitted if it is not recorded
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub finality_proof_decoded: Option<bool>,
+}
+
+/// Records blocks in the range from the node, sampling cells with the configured sampling parameters.
+pub async fn record(
+	rpc_client: &rpc::Client,
+	cfg: &LightClientConfig,
+	block_range: RangeInclusive<u32>,
+) -> Result<Recording> {
+	let sampling_seed = cfg
+		.sampling_seed
+		.clone()
+		.unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+	let cell_count = rpc::bounded_cell_count(cfg.confidence, cfg.max_cell_count);
+	let genesis_hash = rpc_client.get_genesis_hash().await?;
+
+	let mut blocks = vec![];
+	for block_number in block_range {
+		let (header, _) = rpc_client
+			.get_header_by_block_number(block_number)
+			.await
+			.wrap_err_with(|| format!("Failed to get header of the block {block_number}"))?;
+		// Hash is computed in the same way as by the light client pipeline
+		let block_hash: H256 = Encode::using_encoded(&header, blake2_256).into();
+
+		let (rows, cols, _, _) = extract_kate(&header.extension);
+		let cells = match Dimensions::new(rows, cols) {
+			Some(dimensions) => {
+				let positions = rpc::generate_cells(
+					dimensions,
+					cell_count,
+					Some(&sampling_seed),
+					block_hash,
+					cfg.sampling_strategy,
+				);
+				rpc_client
+					.request_kate_proof(block_hash, &positions)
+					.await
+					.wrap_err_with(|| format!("Failed to get cells of the block {block_number}"))?
+			},
+			None => vec![],
+		};
+
+		let finality_proof = match rpc_client
+			.request_encoded_finality_proof(block_number)
+			.await
+		{
+			Ok(finality_proof) => Some(finality_proof),
+			Err(error) => {
+				warn!(block_number, "Finality proof is not recorded: {error:#}");
+				None
+			},
+		};
+
+		info!(block_number, cells = cells.len(), "Block recorded");
+		blocks.push(RecordedBlock {
+			header,
+			finality_proof,
+			cells: cells.iter().map(RecordedCell::from).collect(),
+		});
+	}
+
+	Ok(Recording {
+		version: RECORDING_VERSION,
+		genesis_hash: format!("{genesis_hash:?}"),
+		sampling_seed,
+		sampling_strategy: cfg.sampling_strategy,
+		cell_count,
+		blocks,
+	})
+}
+
+/// Writes recording into the file.
+pub fn write(recording: &Recording, path: &Path) -> Result<()> {
+	let file = File::create(path)
+		.wrap_err_with(|| format!("Failed to create recording file {}", path.display()))?;
+	serde_json::to_writer(BufWriter::new(file), recording).wrap_err("Failed to write recording")
+}
+
+/// Reads recording from the file.
+pub fn read(path: &Path) -> Result<Recording> {
+	let file = File::open(path)
+		.wrap_err_with(|| format!("Failed to open recording file {}", path.display()))?;
+	let recording: Recording =
+		serde_json::from_reader(BufReader::new(file)).wrap_err("Failed to read recording")?;
+	if recording.version != RECORDING_VERSION {
+		return Err(eyre!(
+			"Unsupported recording version {}, expected {RECORDING_VERSION}",
+			recording.version
+		));
+	}
+	Ok(recording)
+}
+
+/// Network which serves the recorded cells, verifying their proofs as they were fetched from RPC.
+struct ReplayNetwork {
+	cells: HashMap<H256, Vec<Cell>>,
+	pp: Arc<PublicParameters>,
+}
+
+#[async_trait]
+impl network::Client for ReplayNetwork {
+	async fn fetch_verified(
+		&self,
+		block_number: u32,
+		block_hash: H256,
+		dimensions: Dimensions,
+		commitments: &[[u8; COMMITMENT_SIZE]],
+		positions: &[Position],
+	) -> Result<(Vec<Cell>, Vec<Position>, FetchStats)> {
+		let begin = Instant::now();
+		let recorded = self.cells.get(&block_hash).map(Vec::as_slice);
+		let fetched = recorded
+			.unwrap_or_default()
+			.iter()
+			.filter(|cell| positions.contains(&cell.position))
+			.cloned()
+			.collect::<Vec<_>>();
+		let fetch_duration = begin.elapsed();
+
+		let (verified, _) = proof::verify(
+			Lane::Sync,
+			block_number,
+			dimensions,
+			&fetched,
+			commitments,
+			self.pp.clone(),
+		)
+		.await
+		.context("Failed to verify recorded cells")?;
+
+		let mut stats = FetchStats::new(
+			positions.len(),
+			0,
+			Duration::ZERO,
+			Some((verified.len(), fetch_duration)),
+		);
+		stats.proof_verification_duration = (begin.elapsed() - fetch_duration).as_secs_f64();
+		stats.cells = positions
+			.iter()
+			.map(|position| SampledCell {
+				row: position.row,
+				col: position.col,
+				source: fetched
+					.iter()
+					.any(|cell| &cell.position == position)
+					.then_some(CellSource::Rpc),
+				verified: verified.contains(position),
+			})
+			.collect();
+
+		let unfetched = positions
+			.iter()
+			.filter(|position| !verified.contains(position))
+			.cloned()
+			.collect();
+		let cells = fetched
+			.into_iter()
+			.filter(|cell| verified.contains(&cell.position))
+			.collect();
+		Ok((cells, unfetched, stats))
+	}
+}
+
+/// Replays recorded blocks by the light client pipeline, with the recorded sampling parameters.
+pub async fn replay(
+	mut cfg: LightClientConfig,
+	recording: Recording,
+	pp: Arc<PublicParameters>,
+) -> Result<Vec<ReplayedBlock>> {
+	cfg.sampling_seed = Some(recording.sampling_seed);
+	cfg.sampling_strategy = recording.sampling_strategy;
+
+	let mut cells = HashMap::new();
+	for block in &recording.blocks {
+		let block_hash: H256 = Encode::using_encoded(&block.header, blake2_256).into();
+		let block_cells = block
+			.cells
+			.iter()
+			.map(Cell::try_from)
+			.collect::<Result<Vec<_>>>()?;
+		cells.insert(block_hash, block_cells);
+	}
+
+	let db = MemoryDB::default();
+	let network_client = ReplayNetwork { cells, pp };
+	let metrics = Arc::new(telemetry::Registry::default());
+	let state = Arc::new(Mutex::new(State::default()));
+	let (commitment_mismatch_sender, _) = broadcast::channel(1);
+
+	let mut replayed = vec![];
+	for block in recording.blocks {
+		let block_number = block.header.number;
+		let block_hash: H256 = Encode::using_encoded(&block.header, blake2_256).into();
+		let finality_proof_decoded = block.finality_proof.map(|finality_proof| {
+			serde_json::from_value::<rpc::WrappedProof>(serde_json::Value::String(finality_proof))
+				.is_ok()
+		});
+
+		let confidence = light_client::process_block(
+			db.clone(),
+			&network_client,
+			&metrics,
+			&cfg,
+			recording.cell_count,
+			block.header,
+			Instant::now(),
+			state.clone(),
+			&commitment_mismatch_sender,
+		)
+		.await?;
+		let mismatch = db
+			.get::<CommitmentMismatch>(Key::CommitmentMismatch(block_number))?
+			.map(|mismatch| mismatch.reason);
+
+		info!(
+			block_number,
+			"Recorded block replayed, confidence: {confidence:?}"
+		);
+		replayed.push(ReplayedBlock {
+			block_number,
+			block_hash,
+			confidence,
+			mismatch,
+			finality_proof_decoded,
+		});
+	}
+	Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3, HeaderExtension},
+			kate_commitment::v3::KateCommitment,
+		},
+		primitives::Header,
+		utils::H256,
+	};
+	use kate_recovery::{data::Cell, matrix::Position};
+	use std::sync::Arc;
+	use subxt::config::substrate::Digest;
+
+	use super::{replay, RecordedBlock, RecordedCell, Recording, RECORDING_VERSION};
+	use crate::{
+		sampling::SamplingStrategy,
+		types::{LightClientConfig, RuntimeConfig},
+		verification::MismatchReason,
+	};
+
+	fn header(number: u32, commitments: usize) -> Header {
+		Header {
+			parent_hash: H256::default(),
+			number,
+			state_root: H256::default(),
+			extrinsics_root: H256::default(),
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 4,
+					data_root: H256::default(),
+					commitment: vec![0u8; commitments * 48],
+				},
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+			digest: Digest { logs: vec![] },
+		}
+	}
+
+	#[test]
+	fn recorded_cell_roundtrip() {
+		let cell = Cell {
+			position: Position { row: 1, col: 2 },
+			content: [7u8; 80],
+		};
+		let recorded = RecordedCell::from(&cell);
+		let restored = Cell::try_from(&recorded).unwrap();
+		assert_eq!(RecordedCell::from(&restored), recorded);
+
+		let invalid = RecordedCell {
+			content: vec![0u8; 10],
+			..recorded
+		};
+		assert!(Cell::try_from(&invalid).is_err());
+	}
+
+	#[tokio::test]
+	async fn recorded_blocks_are_replayed() {
+		let recording = Recording {
+			version: RECORDING_VERSION,
+			genesis_hash: "DEV".to_string(),
+			sampling_seed: "seed".to_string(),
+			sampling_strategy: SamplingStrategy::default(),
+			cell_count: 2,
+			blocks: vec![
+				RecordedBlock {
+					header: header(1, 2),
+					finality_proof: Some("0x00".to_string()),
+					cells: vec![],
+				},
+				RecordedBlock {
+					header: header(2, 1),
+					finality_proof: None,
+					cells: vec![],
+				},
+			],
+		};
+		let cfg = LightClientConfig::from(&RuntimeConfig::default());
+		let pp = Arc::new(kate_recovery::couscous::public_params());
+		let replayed = replay(cfg, recording, pp).await.unwrap();
+
+		assert_eq!(replayed.len(), 2);
+		// Recorded cells are missing, so the block doesn't achieve confidence
+		assert_eq!(replayed[0].confidence, None);
+		assert_eq!(replayed[0].mismatch, None);
+		assert_eq!(replayed[0].finality_proof_decoded, Some(false));
+		assert_eq!(
+			replayed[1].mismatch,
+			Some(MismatchReason::CommitmentCount {
+				expected: 2,
+				actual: 1
+			})
+		);
+		assert_eq!(replayed[1].finality_proof_decoded, None);
+	}
+}
@@ -0,0 +1,281 @@
+//! Retention of the block data stored in the database.
+
+use color_eyre::{eyre::WrapErr, Result};
+use std::{
+	collections::HashSet,
+	fs,
+	ops::Range,
+	path::Path,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::time;
+use tracing::{debug, error, info};
+
+use crate::{
+	app_client::Follow,
+	data::{self, Database, Key, BLOCK_HEADER_CF},
+	types::State,
+};
+
+#[derive(Clone)]
+pub struct RetentionConfig {
+	/// Number of latest blocks kept in the database
+	pub blocks: u32,
+	pub interval: Duration,
+	/// Data directory, used to report the reclaimed space
+	pub avail_path: String,
+}
+
+/// Deletes data of the blocks in the given range, including the blocks of the other forks.
+pub fn prune(db: &impl Database, blocks: Range<u32>, app_ids: &[u32]) -> Result<()> {
+	let mut stored = HashSet::new();
+	for key in db.keys(BLOCK_HEADER_CF)? {
+		if let Key::BlockHeader(block_number, block_hash) = key {
+			if blocks.contains(&block_number) {
+				stored.insert((block_number, block_hash));
+			}
+		}
+	}
+	for block_number in blocks.clone() {
+		if let Some(block_hash) = data::canonical_hash(db, block_number)? {
+			stored.insert((block_number, block_hash));
+		}
+	}
+
+	for (block_number, block_hash) in stored {
+		db.delete(Key::VerifiedCellCount(block_number, block_hash))?;
+		db.delete(Key::BlockHeader(block_number, block_hash))?;
+		db.delete(Key::BlockNumber(block_hash))?;
+		for &app_id in app_ids {
+			db.delete(Key::AppData(app_id, block_number, block_hash))?;
+		}
+	}
+	for block_number in blocks {
+		db.delete(Key::CanonicalHash(block_number))?;
+		db.delete(Key::BlockSamplingStats(block_number))?;
+		db.delete(Key::SyncResult(block_number))?;
+		db.delete(Key::Attestation(block_number))?;
+		db.delete(Key::CommitmentMismatch(block_number))?;
+		db.delete(Key::BlockFailure(block_number))?;
+	}
+	Ok(())
+}
+
+/// Returns the lowest block number of the stored headers.
+fn lowest_stored_block(db: &impl Database) -> Result<Option<u32>> {
+	Ok(db
+		.keys(BLOCK_HEADER_CF)?
+		.into_iter()
+		.filter_map(|key| match key {
+			Key::BlockHeader(block_number, _) => Some(block_number),
+			_ => None,
+		})
+		.min())
+}
+
+/// Prunes blocks older than the retention period, starting from the retention checkpoint,
+/// or from the lowest stored header (the first block if none is stored) if there is no checkpoint,
+/// so blocks stored by the previous sessions are pruned too. Returns range of the pruned blocks.
+pub fn prune_expired(
+	db: &impl Database,
+	retention_blocks: u32,
+	latest: u32,
+	app_ids: &[u32],
+) -> Result<Option<Range<u32>>> {
+	let checkpoint = db
+		.get::<u32>(Key::RetentionCheckpoint)
+		.wrap_err("Failed to get retention checkpoint")?;
+	let start = match checkpoint {
+		Some(checkpoint) => checkpoint,
+		None => lowest_stored_block(db)
+			.wrap_err("Failed to get lowest stored block")?
+			.unwrap_or(0),
+	};
+	let end = (latest + 1).saturating_sub(retention_blocks);
+	if start >= end {
+		return Ok(None);
+	}
+
+	prune(db, start..end, app_ids).wrap_err("Failed to prune blocks")?;
+	db.put(Key::RetentionCheckpoint, end)
+		.wrap_err("Failed to store retention checkpoint")?;
+	Ok(Some(start..end))
+}
+
+/// Returns total size of the files in the directory, in bytes.
+fn directory_size(path: &Path) -> u64 {
+	let Ok(entries) = fs::read_dir(path) else {
+		return 0;
+	};
+	entries
+		.filter_map(|entry| entry.ok())
+		.map(|entry| match entry.metadata() {
+			Ok(metadata) if metadata.is_dir() => directory_size(&entry.path()),
+			Ok(metadata) => metadata.len(),
+			Err(_) => 0,
+		})
+		.sum()
+}
+
+/// Runs pruning and compaction of the database on the configured interval.
+pub async fn run(
+	db: impl Database,
+	cfg: RetentionConfig,
+	state: Arc<Mutex<State>>,
+	app_clients: Arc<impl Follow>,
+) {
+	info!("Starting retention of {} latest blocks...", cfg.blocks);
+
+	let mut interval = time::interval(cfg.interval);
+	loop {
+		interval.tick().await;
+
+		let latest = state.lock().expect("State lock can be acquired").latest;
+		let pruned = match prune_expired(&db, cfg.blocks, latest, &app_clients.app_ids()) {
+			Ok(Some(pruned)) => pruned,
+			Ok(None) => {
+				debug!(latest, "No blocks to prune");
+				continue;
+			},
+			Err(error) => {
+				error!("Pruning failed: {error:#}");
+				continue;
+			},
+		};
+
+		let path = Path::new(&cfg.avail_path);
+		let size_before = directory_size(path);
+		if let Err(error) = db.compact() {
+			error!("Compaction failed: {error:#}");
+		}
+		let size_after = directory_size(path);
+
+		info!(
+			pruned_from = pruned.start,
+			pruned_to = pruned.end,
+			size = size_after,
+			reclaimed = size_before.saturating_sub(size_after),
+			"Pruned {} blocks from the database",
+			pruned.len()
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3, HeaderExtension},
+			kate_commitment::v3::KateCommitment,
+		},
+		config::substrate::Digest,
+		primitives::Header,
+	};
+	use sp_core::H256;
+
+	use super::prune_expired;
+	use crate::data::{mem_db::MemoryDB, Batch, Database, Key};
+
+	fn hash(block_number: u32) -> H256 {
+		H256::from_low_u64_be(block_number.into())
+	}
+
+	fn header(number: u32) -> Header {
+		Header {
+			parent_hash: H256::zero(),
+			number,
+			state_root: H256::zero(),
+			extrinsics_root: H256::zero(),
+			digest: Digest { logs: vec![] },
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment {
+					rows: 1,
+					cols: 1,
+					data_root: H256::zero(),
+					commitment: vec![],
+				},
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+		}
+	}
+
+	#[test]
+	fn prune_expired_blocks() {
+		let db = MemoryDB::default();
+		for block_number in 0..10 {
+			let block_hash = hash(block_number);
+			db.put(Key::CanonicalHash(block_number), block_hash)
+				.unwrap();
+			db.put(Key::VerifiedCellCount(block_number, block_hash), 8u32)
+				.unwrap();
+			db.put(Key::AppData(1, block_number, block_hash), vec![vec![1u8]])
+				.unwrap();
+		}
+
+		// Without stored headers, pruning starts from the first block
+		assert_eq!(prune_expired(&db, 4, 9, &[1]).unwrap(), Some(0..6));
+		for block_number in 0..6 {
+			assert!(db
+				.get::<u32>(Key::VerifiedCellCount(block_number, hash(block_number)))
+				.unwrap()
+				.is_none());
+			assert!(db
+				.get::<Vec<Vec<u8>>>(Key::AppData(1, block_number, hash(block_number)))
+				.unwrap()
+				.is_none());
+		}
+		for block_number in [6, 9] {
+			assert!(db
+				.get::<u32>(Key::VerifiedCellCount(block_number, hash(block_number)))
+				.unwrap()
+				.is_some());
+		}
+
+		// Pruning continues from the checkpoint
+		assert_eq!(prune_expired(&db, 4, 9, &[1]).unwrap(), None);
+		assert_eq!(prune_expired(&db, 4, 11, &[1]).unwrap(), Some(6..8));
+	}
+
+	#[test]
+	fn prune_blocks_of_previous_session() {
+		// Blocks stored by the previous session, without the retention checkpoint
+		let db = MemoryDB::default();
+		let mut batch = Batch::default();
+		let hashes = (3..10)
+			.map(|block_number| {
+				let block_hash = batch.put_finalized_header(header(block_number));
+				batch.put(Key::VerifiedCellCount(block_number, block_hash), 8u32);
+				(block_number, block_hash)
+			})
+			.collect::<Vec<_>>();
+		db.write(batch).unwrap();
+
+		assert_eq!(prune_expired(&db, 4, 9, &[]).unwrap(), Some(3..6));
+		for (block_number, block_hash) in hashes {
+			let is_stored = db
+				.get::<u32>(Key::VerifiedCellCount(block_number, block_hash))
+				.unwrap()
+				.is_some();
+			assert_eq!(is_stored, block_number >= 6);
+			assert_eq!(
+				db.get::<Header>(Key::BlockHeader(block_number, block_hash))
+					.unwrap()
+					.is_some(),
+				block_number >= 6
+			);
+		}
+		assert_eq!(db.get::<u32>(Key::RetentionCheckpoint).unwrap(), Some(6));
+	}
+
+	#[test]
+	fn nothing_to_prune() {
+		let db = MemoryDB::default();
+		assert_eq!(prune_expired(&db, 20, 9, &[]).unwrap(), None);
+		assert!(db.get::<u32>(Key::RetentionCheckpoint).unwrap().is_none());
+	}
+}
@@ -0,0 +1,227 @@
+//! Retry policies of the network operations, with the exponential backoff and jitter.
+
+use color_eyre::Report;
+use libp2p::kad;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, future::Future, time::Duration};
+use tracing::debug;
+
+use crate::network::rpc::RpcError;
+
+/// Retry policy of a single class of the network operations.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicy {
+	/// Maximum number of attempts, including the first one
+	pub max_attempts: u32,
+	/// Delay before the first retry, in milliseconds
+	pub base_delay: u64,
+	/// Maximum delay between the attempts, in milliseconds
+	pub max_delay: u64,
+	/// Maximum fraction of the delay which is randomly subtracted, between 0 and 1
+	pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts: 3,
+			base_delay: 500,
+			max_delay: 5000,
+			jitter: 0.5,
+		}
+	}
+}
+
+/// Retry policies of the network operations, by the operation class.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RetryPolicies {
+	/// Cell fetches from the RPC nodes and DHT
+	pub cell_fetch: RetryPolicy,
+	/// Block hash and header queries
+	pub header_query: RetryPolicy,
+	/// DHT record queries
+	pub dht: RetryPolicy,
+	/// Webhook deliveries
+	pub webhook: RetryPolicy,
+}
+
+impl Default for RetryPolicies {
+	fn default() -> Self {
+		Self {
+			cell_fetch: RetryPolicy {
+				max_attempts: 2,
+				max_delay: 2000,
+				..Default::default()
+			},
+			header_query: RetryPolicy::default(),
+			dht: RetryPolicy {
+				max_attempts: 2,
+				base_delay: 100,
+				max_delay: 1000,
+				..Default::default()
+			},
+			webhook: RetryPolicy {
+				max_attempts: 5,
+				base_delay: 1000,
+				max_delay: 30000,
+				..Default::default()
+			},
+		}
+	}
+}
+
+/// Classification of the errors which can succeed on retry.
+pub trait Retryable {
+	fn is_retryable(&self) -> bool;
+}
+
+impl Retryable for Report {
+	fn is_retryable(&self) -> bool {
+		if let Some(error) = self.downcast_ref::<kad::GetRecordError>() {
+			// Record which is not found by the DHT query is not going to be found by the next one,
+			// while timeouts and failed quorums can succeed with other peers
+			return !matches!(error, kad::GetRecordError::NotFound { .. });
+		}
+		if let Some(error) = self.downcast_ref::<RpcError>() {
+			// Incompatible nodes and undecodable responses are not going to change on retry
+			return matches!(error, RpcError::Timeout(_) | RpcError::Disconnected);
+		}
+		if let Some(error) = self.downcast_ref::<subxt::Error>() {
+			return matches!(error, subxt::Error::Io(_) | subxt::Error::Rpc(_));
+		}
+		true
+	}
+}
+
+impl RetryPolicy {
+	/// Returns delay before the given retry (starting from 1), with random jitter applied.
+	pub fn delay(&self, retry: u32) -> Duration {
+		let factor = 2u64.saturating_pow(retry.saturating_sub(1));
+		let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+		let jitter = self.jitter.clamp(0.0, 1.0) * rand::thread_rng().gen::<f64>();
+		Duration::from_millis(delay).mul_f64(1.0 - jitter)
+	}
+
+	/// Executes the operation, retrying it on retryable errors until the maximum number of attempts is reached.
+	/// Operation name is used for logging only.
+	pub async fn retry<T, E, F, Fut>(&self, operation: &str, mut f: F) -> Result<T, E>
+	where
+		E: Retryable + Display,
+		F: FnMut() -> Fut,
+		Fut: Future<Output = Result<T, E>>,
+	{
+		let mut attempt = 1;
+		loop {
+			match f().await {
+				Ok(value) => return Ok(value),
+				Err(error) if attempt < self.max_attempts && error.is_retryable() => {
+					let delay = self.delay(attempt);
+					debug!(
+						operation,
+						attempt,
+						?delay,
+						"Retrying failed operation: {error}"
+					);
+					tokio::time::sleep(delay).await;
+					attempt += 1;
+				},
+				Err(error) => return Err(error),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use color_eyre::{eyre::eyre, Report, Result};
+	use libp2p::kad::{self, RecordKey};
+	use std::{
+		sync::atomic::{AtomicU32, Ordering},
+		time::Duration,
+	};
+
+	use super::{RetryPolicy, Retryable};
+
+	fn policy(max_attempts: u32) -> RetryPolicy {
+		RetryPolicy {
+			max_attempts,
+			base_delay: 0,
+			max_delay: 0,
+			jitter: 0.0,
+		}
+	}
+
+	#[test]
+	fn delays_are_capped_and_jittered() {
+		let policy = RetryPolicy {
+			max_attempts: 10,
+			base_delay: 100,
+			max_delay: 1000,
+			jitter: 0.5,
+		};
+		for (retry, max) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (9, 1000)] {
+			let delay = policy.delay(retry);
+			assert!(delay <= Duration::from_millis(max));
+			assert!(delay >= Duration::from_millis(max / 2));
+		}
+	}
+
+	#[test]
+	fn classifies_dht_errors() {
+		let key = RecordKey::new(&"key");
+		let not_found: Report = kad::GetRecordError::NotFound {
+			key: key.clone(),
+			closest_peers: vec![],
+		}
+		.into();
+		assert!(!not_found.is_retryable());
+
+		let timeout: Report = kad::GetRecordError::Timeout { key }.into();
+		assert!(timeout.wrap_err("Cannot get record").is_retryable());
+		assert!(eyre!("Connection reset").is_retryable());
+	}
+
+	#[tokio::test]
+	async fn retries_until_max_attempts() {
+		let attempts = AtomicU32::new(0);
+		let result: Result<()> = policy(3)
+			.retry("test", || async {
+				attempts.fetch_add(1, Ordering::SeqCst);
+				Err(eyre!("Failed"))
+			})
+			.await;
+		assert!(result.is_err());
+		assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+		let attempts = AtomicU32::new(0);
+		let result = policy(3)
+			.retry("test", || async {
+				match attempts.fetch_add(1, Ordering::SeqCst) {
+					0 => Err(eyre!("Failed")),
+					attempt => Ok(attempt),
+				}
+			})
+			.await;
+		assert_eq!(result.unwrap(), 1);
+	}
+
+	#[tokio::test]
+	async fn non_retryable_errors_are_returned() {
+		let attempts = AtomicU32::new(0);
+		let result: Result<()> = policy(3)
+			.retry("test", || async {
+				attempts.fetch_add(1, Ordering::SeqCst);
+				Err(kad::GetRecordError::NotFound {
+					key: RecordKey::new(&"key"),
+					closest_peers: vec![],
+				}
+				.into())
+			})
+			.await;
+		assert!(result.is_err());
+		assert_eq!(attempts.load(Ordering::SeqCst), 1);
+	}
+}
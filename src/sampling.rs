@@ -0,0 +1,269 @@
+//! Cell sampling strategies and sampling parameters, adapted to the observed network reliability.
+
+use kate_recovery::matrix::{Dimensions, Position};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, ops::Range};
+use tracing::{debug, info};
+
+use crate::{
+	network::rpc::bounded_cell_count, types::AdaptiveConfidenceConfig, utils::calculate_confidence,
+};
+
+/// Number of attempts to sample a cell within the stratum, before the cell is sampled uniformly
+const STRATUM_ATTEMPTS: usize = 8;
+
+/// Strategy for choosing positions of the sampled cells in the extended data matrix.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SamplingStrategy {
+	/// Positions are sampled uniformly across the matrix
+	#[default]
+	Uniform,
+	/// Matrix rows and columns are split into strata, and each row and column stratum is sampled once,
+	/// which prevents sampled cells from clustering
+	Stratified,
+}
+
+impl SamplingStrategy {
+	/// Samples distinct cell positions, up to the size of the extended matrix.
+	/// Positions are returned in the order of generation, which makes seeded sampling reproducible.
+	pub fn sample(
+		&self,
+		dimensions: Dimensions,
+		cell_count: u32,
+		rng: &mut impl Rng,
+	) -> Vec<Position> {
+		let max_cells = dimensions.extended_size();
+		let count = if max_cells < cell_count {
+			debug!("Max cells count {max_cells} is lesser than cell_count {cell_count}");
+			max_cells
+		} else {
+			cell_count
+		};
+
+		let mut sampled = Sampled::default();
+		if *self == SamplingStrategy::Stratified {
+			stratified_cells(dimensions, count, rng, &mut sampled);
+		}
+		while (sampled.positions.len() as u32) < count {
+			let col = rng.gen_range(0..dimensions.cols().into());
+			let row = rng.gen_range(0..dimensions.extended_rows());
+			sampled.insert(row, col);
+		}
+		sampled.positions
+	}
+}
+
+#[derive(Default)]
+struct Sampled {
+	indices: HashSet<(u32, u16)>,
+	positions: Vec<Position>,
+}
+
+impl Sampled {
+	fn insert(&mut self, row: u32, col: u16) -> bool {
+		let inserted = self.indices.insert((row, col));
+		if inserted {
+			self.positions.push(Position { row, col });
+		}
+		inserted
+	}
+}
+
+/// Range of lines in the given stratum, strata wrap around if there are more strata than lines.
+fn stratum(index: u32, strata: u32, lines: u32) -> Range<u32> {
+	if strata > lines {
+		let line = index % lines;
+		return line..line + 1;
+	}
+	let start = (index as u64 * lines as u64 / strata as u64) as u32;
+	let end = ((index as u64 + 1) * lines as u64 / strata as u64) as u32;
+	start..end
+}
+
+/// Samples one cell per row stratum, with column strata randomly assigned to row strata,
+/// so each row and column stratum is covered once (latin hypercube sampling).
+fn stratified_cells(dimensions: Dimensions, count: u32, rng: &mut impl Rng, sampled: &mut Sampled) {
+	let rows = dimensions.extended_rows();
+	let cols: u32 = dimensions.cols().get().into();
+
+	let mut col_strata = (0..count).collect::<Vec<_>>();
+	col_strata.shuffle(rng);
+
+	for (row_stratum, col_stratum) in (0..count).zip(col_strata) {
+		let row_range = stratum(row_stratum, count, rows);
+		let col_range = stratum(col_stratum, count, cols);
+		for _ in 0..STRATUM_ATTEMPTS {
+			let row = rng.gen_range(row_range.clone());
+			let col = rng.gen_range(col_range.clone()) as u16;
+			if sampled.insert(row, col) {
+				break;
+			}
+		}
+	}
+}
+
+/// Adjusts number of sampled cells based on the outcome of recent blocks.
+///
+/// Cell count is raised after each block with failed fetch or verification,
+/// and lowered back after configured number of consecutive healthy blocks,
+/// bounded by the cell counts required for configured min and max confidence.
+pub struct AdaptiveSampler {
+	min_cell_count: u32,
+	max_cell_count: u32,
+	healthy_blocks: u32,
+	cell_count: u32,
+	consecutive_healthy: u32,
+}
+
+impl AdaptiveSampler {
+	pub fn new(confidence: f64, max_cell_count: u32, cfg: &AdaptiveConfidenceConfig) -> Self {
+		let min_cell_count = bounded_cell_count(cfg.min_confidence, max_cell_count);
+		let max_cell_count =
+			bounded_cell_count(cfg.max_confidence, max_cell_count).max(min_cell_count);
+		let cell_count =
+			bounded_cell_count(confidence, max_cell_count).clamp(min_cell_count, max_cell_count);
+
+		AdaptiveSampler {
+			min_cell_count,
+			max_cell_count,
+			healthy_blocks: cfg.healthy_blocks.max(1),
+			cell_count,
+			consecutive_healthy: 0,
+		}
+	}
+
+	/// Number of cells to sample for the next block.
+	pub fn cell_count(&self) -> u32 {
+		self.cell_count
+	}
+
+	/// Confidence achieved when all of the sampled cells are verified.
+	pub fn confidence(&self) -> f64 {
+		calculate_confidence(self.cell_count)
+	}
+
+	/// Records outcome of the block sampling.
+	/// Block is healthy if all of the sampled cells are fetched and verified.
+	pub fn record(&mut self, healthy: bool) {
+		let previous = self.cell_count;
+		if healthy {
+			self.consecutive_healthy += 1;
+			if self.consecutive_healthy >= self.healthy_blocks {
+				self.consecutive_healthy = 0;
+				self.cell_count = self.cell_count.saturating_sub(1).max(self.min_cell_count);
+			}
+		} else {
+			self.consecutive_healthy = 0;
+			self.cell_count = (self.cell_count + 1).min(self.max_cell_count);
+		}
+
+		if previous != self.cell_count {
+			info!(
+				"Adaptive sampling changed cell count from {previous} to {}, effective confidence is {}",
+				self.cell_count,
+				self.confidence()
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use kate_recovery::matrix::Dimensions;
+	use rand::thread_rng;
+	use std::collections::HashSet;
+	use test_case::test_case;
+
+	use super::{stratum, AdaptiveSampler, SamplingStrategy};
+	use crate::types::AdaptiveConfidenceConfig;
+
+	#[test_case(0, 4, 16 => 0..4)]
+	#[test_case(3, 4, 16 => 12..16)]
+	#[test_case(1, 3, 10 => 3..6)]
+	#[test_case(5, 8, 4 => 1..2)]
+	fn test_stratum(index: u32, strata: u32, lines: u32) -> std::ops::Range<u32> {
+		stratum(index, strata, lines)
+	}
+
+	#[test]
+	fn stratified_sampling_covers_rows_and_columns() {
+		let dimensions = Dimensions::new(8, 16).unwrap();
+		let cells = SamplingStrategy::Stratified.sample(dimensions, 16, &mut thread_rng());
+		assert_eq!(cells.len(), 16);
+
+		let rows = cells.iter().map(|cell| cell.row).collect::<HashSet<_>>();
+		let cols = cells.iter().map(|cell| cell.col).collect::<HashSet<_>>();
+		assert_eq!(rows.len(), 16);
+		assert_eq!(cols.len(), 16);
+	}
+
+	#[test_case(SamplingStrategy::Uniform ; "uniform")]
+	#[test_case(SamplingStrategy::Stratified ; "stratified")]
+	fn sampling_is_bounded_by_matrix_size(strategy: SamplingStrategy) {
+		let dimensions = Dimensions::new(1, 4).unwrap();
+		let cells = strategy.sample(dimensions, 20, &mut thread_rng());
+		assert_eq!(cells.len(), 8);
+		let distinct = cells
+			.iter()
+			.map(|cell| (cell.row, cell.col))
+			.collect::<HashSet<_>>();
+		assert_eq!(distinct.len(), 8);
+	}
+
+	fn config() -> AdaptiveConfidenceConfig {
+		AdaptiveConfidenceConfig {
+			min_confidence: 99.0,
+			max_confidence: 99.99,
+			healthy_blocks: 3,
+		}
+	}
+
+	#[test]
+	fn failures_raise_cell_count_up_to_max() {
+		let mut sampler = AdaptiveSampler::new(99.9, 20, &config());
+		assert_eq!(sampler.cell_count(), 10);
+
+		sampler.record(false);
+		assert_eq!(sampler.cell_count(), 11);
+
+		(0..10).for_each(|_| sampler.record(false));
+		assert_eq!(sampler.cell_count(), 14);
+	}
+
+	#[test]
+	fn healthy_blocks_lower_cell_count_down_to_min() {
+		let mut sampler = AdaptiveSampler::new(99.9, 20, &config());
+
+		(0..2).for_each(|_| sampler.record(true));
+		assert_eq!(sampler.cell_count(), 10);
+		sampler.record(true);
+		assert_eq!(sampler.cell_count(), 9);
+
+		(0..30).for_each(|_| sampler.record(true));
+		assert_eq!(sampler.cell_count(), 7);
+	}
+
+	#[test]
+	fn failure_resets_healthy_streak() {
+		let mut sampler = AdaptiveSampler::new(99.9, 20, &config());
+		(0..2).for_each(|_| sampler.record(true));
+		sampler.record(false);
+		(0..2).for_each(|_| sampler.record(true));
+		assert_eq!(sampler.cell_count(), 11);
+	}
+
+	#[test]
+	fn max_confidence_is_bounded_by_max_cell_count() {
+		let mut sampler = AdaptiveSampler::new(99.9, 12, &config());
+		(0..10).for_each(|_| sampler.record(false));
+		assert_eq!(sampler.cell_count(), 12);
+	}
+
+	#[test]
+	fn initial_cell_count_is_bounded() {
+		let sampler = AdaptiveSampler::new(60.0, 20, &config());
+		assert_eq!(sampler.cell_count(), 7);
+	}
+}
@@ -0,0 +1,247 @@
+//! Scheduling of the block sampling between the latest finalized blocks and the historical sync.
+
+use std::{
+	collections::VecDeque,
+	sync::{Arc, Mutex},
+};
+use tokio::sync::{oneshot, Semaphore};
+use tracing::debug;
+
+use crate::types::RuntimeConfig;
+
+/// Priority of the sampled block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+	/// Latest finalized block
+	Head,
+	/// Historical block sampled by the sync
+	Sync,
+}
+
+/// Scheduler configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct SchedulerConfig {
+	pub slots: usize,
+	pub head_queue_size: usize,
+	pub sync_queue_size: usize,
+	pub max_head_streak: u32,
+}
+
+impl From<&RuntimeConfig> for SchedulerConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		SchedulerConfig {
+			slots: val.sampling_slots.max(1),
+			head_queue_size: val.head_queue_size.max(1),
+			sync_queue_size: val.sync_queue_size.max(1),
+			max_head_streak: val.max_head_streak.max(1),
+		}
+	}
+}
+
+struct Queues {
+	available: usize,
+	head: VecDeque<oneshot::Sender<Slot>>,
+	sync: VecDeque<oneshot::Sender<Slot>>,
+	/// Number of head blocks which got the slot in a row while sync blocks were waiting
+	head_streak: u32,
+}
+
+impl Queues {
+	fn grant(&mut self, priority: Priority) {
+		self.head_streak = match priority {
+			Priority::Head if !self.sync.is_empty() => self.head_streak + 1,
+			Priority::Head | Priority::Sync => 0,
+		};
+	}
+
+	/// Returns the next waiting client, or makes the slot available if there are none.
+	fn next(&mut self, max_head_streak: u32) -> Option<(Priority, oneshot::Sender<Slot>)> {
+		self.head.retain(|waiter| !waiter.is_closed());
+		self.sync.retain(|waiter| !waiter.is_closed());
+
+		let sync_starved = !self.sync.is_empty() && self.head_streak >= max_head_streak;
+		let head = (!sync_starved)
+			.then(|| self.head.pop_front())
+			.flatten()
+			.map(|waiter| (Priority::Head, waiter));
+		let next = head.or_else(|| self.sync.pop_front().map(|waiter| (Priority::Sync, waiter)));
+		match next {
+			Some((priority, waiter)) => {
+				self.grant(priority);
+				Some((priority, waiter))
+			},
+			None => {
+				self.available += 1;
+				None
+			},
+		}
+	}
+}
+
+/// Sampling slot, released on drop.
+pub struct Slot {
+	scheduler: Option<Scheduler>,
+}
+
+impl Drop for Slot {
+	fn drop(&mut self) {
+		if let Some(scheduler) = self.scheduler.take() {
+			scheduler.release();
+		}
+	}
+}
+
+/// Scheduler of the sampling slots, shared by the light and sync clients.
+#[derive(Clone)]
+pub struct Scheduler {
+	queues: Arc<Mutex<Queues>>,
+	head_queue: Arc<Semaphore>,
+	sync_queue: Arc<Semaphore>,
+	max_head_streak: u32,
+}
+
+impl Scheduler {
+	pub fn new(cfg: &SchedulerConfig) -> Self {
+		Scheduler {
+			queues: Arc::new(Mutex::new(Queues {
+				available: cfg.slots,
+				head: VecDeque::new(),
+				sync: VecDeque::new(),
+				head_streak: 0,
+			})),
+			head_queue: Arc::new(Semaphore::new(cfg.head_queue_size)),
+			sync_queue: Arc::new(Semaphore::new(cfg.sync_queue_size)),
+			max_head_streak: cfg.max_head_streak,
+		}
+	}
+
+	/// Waits for the sampling slot in the queue of the given priority.
+	pub async fn acquire(&self, priority: Priority) -> Slot {
+		let queue = match priority {
+			Priority::Head => &self.head_queue,
+			Priority::Sync => &self.sync_queue,
+		};
+		let _queued = queue.acquire().await.expect("Queue is never closed");
+
+		let receiver = {
+			let mut queues = self.queues.lock().unwrap();
+			if queues.available > 0 {
+				queues.available -= 1;
+				queues.grant(priority);
+				return Slot {
+					scheduler: Some(self.clone()),
+				};
+			}
+			let (sender, receiver) = oneshot::channel();
+			match priority {
+				Priority::Head => queues.head.push_back(sender),
+				Priority::Sync => queues.sync.push_back(sender),
+			}
+			receiver
+		};
+		receiver.await.expect("Waiter is removed only when closed")
+	}
+
+	fn release(&self) {
+		loop {
+			let Some((priority, waiter)) = self.queues.lock().unwrap().next(self.max_head_streak)
+			else {
+				return;
+			};
+			let slot = Slot {
+				scheduler: Some(self.clone()),
+			};
+			match waiter.send(slot) {
+				Ok(()) => {
+					debug!("Sampling slot is given to the {priority:?} block");
+					return;
+				},
+				// Waiter is cancelled in the meantime, so the slot is given to the next one
+				Err(mut slot) => slot.scheduler = None,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{collections::VecDeque, time::Duration};
+	use tokio::sync::oneshot;
+
+	use super::{Priority, Queues, Scheduler, SchedulerConfig, Slot};
+
+	fn config(slots: usize) -> SchedulerConfig {
+		SchedulerConfig {
+			slots,
+			head_queue_size: 8,
+			sync_queue_size: 8,
+			max_head_streak: 2,
+		}
+	}
+
+	#[test]
+	fn head_is_scheduled_first_without_sync_starvation() {
+		let mut queues = Queues {
+			available: 0,
+			head: VecDeque::new(),
+			sync: VecDeque::new(),
+			head_streak: 0,
+		};
+		let mut receivers: Vec<oneshot::Receiver<Slot>> = vec![];
+		for _ in 0..5 {
+			let (sender, receiver) = oneshot::channel();
+			queues.head.push_back(sender);
+			receivers.push(receiver);
+		}
+		for _ in 0..2 {
+			let (sender, receiver) = oneshot::channel();
+			queues.sync.push_back(sender);
+			receivers.push(receiver);
+		}
+
+		let scheduled =
+			std::iter::from_fn(|| queues.next(2).map(|(priority, _)| priority)).collect::<Vec<_>>();
+		use Priority::*;
+		assert_eq!(scheduled, vec![Head, Head, Sync, Head, Head, Sync, Head]);
+		assert_eq!(queues.available, 1);
+	}
+
+	#[tokio::test]
+	async fn released_slot_is_given_to_head() {
+		let scheduler = Scheduler::new(&config(1));
+		let slot = scheduler.acquire(Priority::Sync).await;
+
+		let sync = tokio::spawn({
+			let scheduler = scheduler.clone();
+			async move { scheduler.acquire(Priority::Sync).await }
+		});
+		tokio::time::sleep(Duration::from_millis(10)).await;
+		let head = tokio::spawn({
+			let scheduler = scheduler.clone();
+			async move { scheduler.acquire(Priority::Head).await }
+		});
+		tokio::time::sleep(Duration::from_millis(10)).await;
+
+		drop(slot);
+		let head_slot = head.await.unwrap();
+		assert!(!sync.is_finished());
+		drop(head_slot);
+		sync.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn cancelled_waiter_releases_slot() {
+		let scheduler = Scheduler::new(&config(1));
+		let slot = scheduler.acquire(Priority::Head).await;
+		let cancelled =
+			tokio::time::timeout(Duration::from_millis(10), scheduler.acquire(Priority::Sync))
+				.await;
+		assert!(cancelled.is_err());
+
+		drop(slot);
+		let acquired =
+			tokio::time::timeout(Duration::from_millis(10), scheduler.acquire(Priority::Sync))
+				.await;
+		assert!(acquired.is_ok());
+	}
+}
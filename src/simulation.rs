@@ -0,0 +1,254 @@
+//! Simulation of the light client sampling on the synthetic blocks, without connecting to the network.
+//! Synthetic matrices are not committed to, so fetched cells are reported as verified DHT cells.
+
+use async_trait::async_trait;
+use avail_subxt::{
+	api::runtime_types::avail_core::{
+		data_lookup::compact::CompactDataLookup,
+		header::extension::{v3, HeaderExtension},
+		kate_commitment::v3::KateCommitment,
+	},
+	config::substrate::Digest,
+	primitives::Header,
+};
+use codec::Encode;
+use color_eyre::{eyre::eyre, Result};
+use kate_recovery::{
+	config::{COMMITMENT_SIZE, EXTENSION_FACTOR},
+	data::Cell,
+	matrix::{Dimensions, Position},
+};
+use rand::Rng;
+use serde::Serialize;
+use sp_core::{blake2_256, H256};
+use std::{
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+use tokio::sync::broadcast;
+use tracing::info;
+
+use crate::{
+	data::{mem_db::MemoryDB, BlockSamplingStats, CellSource, Database, Key, SampledCell},
+	light_client,
+	network::{self, rpc, FetchStats},
+	telemetry,
+	types::{LightClientConfig, SimulateOpts, State},
+};
+
+/// Parameters of the simulated blocks and network.
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+	pub blocks: u32,
+	pub rows: u16,
+	pub cols: u16,
+	/// Percentage of the sampled cells which are fetched
+	pub availability: f64,
+	pub interval: Duration,
+}
+
+impl From<&SimulateOpts> for SimulationConfig {
+	fn from(opts: &SimulateOpts) -> Self {
+		SimulationConfig {
+			blocks: opts.blocks,
+			rows: opts.rows,
+			cols: opts.cols,
+			availability: opts.availability,
+			interval: Duration::from_millis(opts.interval),
+		}
+	}
+}
+
+/// Outcome of the simulation.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SimulationSummary {
+	pub blocks: u32,
+	/// Number of blocks which achieved confidence
+	pub available: u32,
+	pub cells_sampled: u32,
+	/// Average processing time of the block, in milliseconds
+	pub average_processing_time: u64,
+}
+
+/// Network which serves zero cells, each fetched with the given probability.
+struct SimulatedNetwork {
+	availability: f64,
+}
+
+#[async_trait]
+impl network::Client for SimulatedNetwork {
+	async fn fetch_verified(
+		&self,
+		_: u32,
+		_: H256,
+		_: Dimensions,
+		_: &[[u8; COMMITMENT_SIZE]],
+		positions: &[Position],
+	) -> Result<(Vec<Cell>, Vec<Position>, FetchStats)> {
+		let begin = Instant::now();
+		let mut rng = rand::thread_rng();
+		let (fetched, unfetched): (Vec<Position>, Vec<Position>) = positions
+			.iter()
+			.cloned()
+			.partition(|_| rng.gen_bool(self.availability / 100.0));
+
+		let mut stats = FetchStats::new(positions.len(), fetched.len(), begin.elapsed(), None);
+		stats.cells = positions
+			.iter()
+			.map(|position| {
+				let is_fetched = fetched.contains(position);
+				SampledCell {
+					row: position.row,
+					col: position.col,
+					source: is_fetched.then_some(CellSource::Dht),
+					verified: is_fetched,
+				}
+			})
+			.collect();
+
+		let cells = fetched
+			.into_iter()
+			.map(|position| Cell {
+				position,
+				content: [0u8; 80],
+			})
+			.collect();
+		Ok((cells, unfetched, stats))
+	}
+}
+
+/// Generates synthetic header with the commitment for each extended row.
+fn header(number: u32, parent_hash: H256, rows: u16, cols: u16) -> Header {
+	let extended_rows = rows as usize * EXTENSION_FACTOR as usize;
+	Header {
+		parent_hash,
+		number,
+		state_root: H256::zero(),
+		extrinsics_root: H256::zero(),
+		digest: Digest { logs: vec![] },
+		extension: HeaderExtension::V3(v3::HeaderExtension {
+			commitment: KateCommitment {
+				rows,
+				cols,
+				data_root: H256::zero(),
+				commitment: vec![0u8; extended_rows * COMMITMENT_SIZE],
+			},
+			// Synthetic blocks carry no application data
+			app_lookup: CompactDataLookup {
+				size: 0,
+				index: vec![],
+			},
+		}),
+	}
+}
+
+/// Processes synthetic blocks by the light client pipeline, and returns the summary.
+pub async fn run(
+	cfg: LightClientConfig,
+	simulation: SimulationConfig,
+) -> Result<SimulationSummary> {
+	if Dimensions::new(simulation.rows, simulation.cols).is_none() {
+		return Err(eyre!(
+			"Invalid matrix dimensions {}x{}",
+			simulation.rows,
+			simulation.cols
+		));
+	}
+	if !(0.0..=100.0).contains(&simulation.availability) {
+		return Err(eyre!("Availability must be a percentage between 0 and 100"));
+	}
+
+	let db = MemoryDB::default();
+	let network_client = SimulatedNetwork {
+		availability: simulation.availability,
+	};
+	let metrics = Arc::new(telemetry::Registry::default());
+	let state = Arc::new(Mutex::new(State::default()));
+	let (commitment_mismatch_sender, _) = broadcast::channel(1);
+	let cell_count = rpc::bounded_cell_count(cfg.confidence, cfg.max_cell_count);
+
+	let mut summary = SimulationSummary {
+		blocks: simulation.blocks,
+		available: 0,
+		cells_sampled: 0,
+		average_processing_time: 0,
+	};
+	let mut processing_time = Duration::ZERO;
+	let mut parent_hash = H256::zero();
+
+	for block_number in 1..=simulation.blocks {
+		let header = header(block_number, parent_hash, simulation.rows, simulation.cols);
+		parent_hash = Encode::using_encoded(&header, blake2_256).into();
+
+		let received_at = Instant::now();
+		let confidence = light_client::process_block(
+			db.clone(),
+			&network_client,
+			&metrics,
+			&cfg,
+			cell_count,
+			header,
+			received_at,
+			state.clone(),
+			&commitment_mismatch_sender,
+		)
+		.await?;
+		processing_time += received_at.elapsed();
+
+		let stats = db.get::<BlockSamplingStats>(Key::BlockSamplingStats(block_number))?;
+		summary.cells_sampled += stats.map_or(0, |stats| stats.cells.len() as u32);
+		if confidence.is_some() {
+			summary.available += 1;
+		}
+		info!(
+			block_number,
+			"Simulated block processed, confidence: {confidence:?}"
+		);
+
+		if block_number < simulation.blocks {
+			tokio::time::sleep(simulation.interval).await;
+		}
+	}
+
+	if simulation.blocks > 0 {
+		summary.average_processing_time =
+			(processing_time.as_millis() / simulation.blocks as u128) as u64;
+	}
+	Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use test_case::test_case;
+
+	use super::{run, SimulationConfig};
+	use crate::types::{LightClientConfig, RuntimeConfig};
+
+	fn simulation(availability: f64) -> SimulationConfig {
+		SimulationConfig {
+			blocks: 3,
+			rows: 4,
+			cols: 4,
+			availability,
+			interval: Duration::ZERO,
+		}
+	}
+
+	#[test_case(100.0, 3 ; "All cells fetched")]
+	#[test_case(0.0, 0 ; "No cells fetched")]
+	#[tokio::test]
+	async fn simulated_blocks_are_processed(availability: f64, available: u32) {
+		let cfg = LightClientConfig::from(&RuntimeConfig::default());
+		let summary = run(cfg, simulation(availability)).await.unwrap();
+		assert_eq!(summary.blocks, 3);
+		assert_eq!(summary.available, available);
+		assert!(summary.cells_sampled > 0);
+	}
+
+	#[tokio::test]
+	async fn invalid_availability_is_rejected() {
+		let cfg = LightClientConfig::from(&RuntimeConfig::default());
+		assert!(run(cfg, simulation(101.0)).await.is_err());
+	}
+}
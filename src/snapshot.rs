@@ -0,0 +1,280 @@
+//! Export and import of the client state snapshots, serialized as JSON so they can be imported into any storage backend.
+
+use avail_subxt::primitives::Header;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fs::File,
+	io::{BufReader, BufWriter},
+	path::Path,
+};
+
+use crate::{
+	data::{
+		self, Batch, Database, FinalitySyncCheckpoint, Key, SyncProgress, APP_DATA_CF,
+		BLOCK_HEADER_CF, CANONICAL_HASH_CF, CONFIDENCE_FACTOR_CF,
+	},
+	types::DEV_FLAG_GENHASH,
+};
+use tracing::warn;
+
+/// Version of the snapshot format
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct BlockSnapshot {
+	pub block_number: u32,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub header: Option<Header>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub verified_cell_count: Option<u32>,
+	/// Data of the followed applications, by the application ID
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub app_data: BTreeMap<u32, Vec<Vec<u8>>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Snapshot {
+	pub version: u32,
+	/// Genesis hash of the network, snapshot cannot be imported into the client of the other network
+	pub genesis_hash: String,
+	pub sync_progress: Option<SyncProgress>,
+	pub finality_sync_checkpoint: Option<FinalitySyncCheckpoint>,
+	/// Blocks in the ascending order
+	pub blocks: Vec<BlockSnapshot>,
+}
+
+/// Creates snapshot of the state stored in the database, blocks of the other forks are not included.
+pub fn create(db: &impl Database, genesis_hash: &str) -> Result<Snapshot> {
+	let mut canonical = HashMap::new();
+	for key in db.keys(CANONICAL_HASH_CF)? {
+		let Key::CanonicalHash(block_number) = key else {
+			continue;
+		};
+		if let Some(block_hash) = data::canonical_hash(db, block_number)? {
+			canonical.insert(block_number, block_hash);
+		}
+	}
+	let is_canonical = |block_number, block_hash| canonical.get(&block_number) == Some(&block_hash);
+
+	let mut snapshot_blocks = BTreeMap::<u32, BlockSnapshot>::new();
+	for key in db.keys(BLOCK_HEADER_CF)? {
+		let Key::BlockHeader(block_number, block_hash) = key else {
+			continue;
+		};
+		if !is_canonical(block_number, block_hash) {
+			continue;
+		}
+		let header = db
+			.get::<Header>(key)
+			.wrap_err_with(|| format!("Failed to get header of the block {block_number}"))?;
+		block_entry(&mut snapshot_blocks, block_number).header = header;
+	}
+	for key in db.keys(CONFIDENCE_FACTOR_CF)? {
+		let Key::VerifiedCellCount(block_number, block_hash) = key else {
+			continue;
+		};
+		if !is_canonical(block_number, block_hash) {
+			continue;
+		}
+		let count = db
+			.get::<u32>(key)
+			.wrap_err_with(|| format!("Failed to get confidence of the block {block_number}"))?;
+		block_entry(&mut snapshot_blocks, block_number).verified_cell_count = count;
+	}
+	for key in db.keys(APP_DATA_CF)? {
+		let Key::AppData(app_id, block_number, block_hash) = key else {
+			continue;
+		};
+		if !is_canonical(block_number, block_hash) {
+			continue;
+		}
+		let Some(data) = db.get::<Vec<Vec<u8>>>(key).wrap_err_with(|| {
+			format!("Failed to get app {app_id} data of the block {block_number}")
+		})?
+		else {
+			continue;
+		};
+		block_entry(&mut snapshot_blocks, block_number)
+			.app_data
+			.insert(app_id, data);
+	}
+
+	Ok(Snapshot {
+		version: SNAPSHOT_VERSION,
+		genesis_hash: genesis_hash.to_string(),
+		sync_progress: db.get(Key::SyncProgress)?,
+		finality_sync_checkpoint: db.get(Key::FinalitySyncCheckpoint)?,
+		blocks: snapshot_blocks.into_values().collect(),
+	})
+}
+
+fn block_entry(blocks: &mut BTreeMap<u32, BlockSnapshot>, block_number: u32) -> &mut BlockSnapshot {
+	blocks.entry(block_number).or_insert_with(|| BlockSnapshot {
+		block_number,
+		..Default::default()
+	})
+}
+
+/// Stores snapshot into the database, returning number of the restored blocks.
+/// Existing values of the restored blocks are overwritten, and restored blocks are indexed as canonical.
+pub fn restore(db: &impl Database, snapshot: Snapshot, genesis_hash: &str) -> Result<usize> {
+	if snapshot.version != SNAPSHOT_VERSION {
+		return Err(eyre!(
+			"Unsupported snapshot version {}, expected {SNAPSHOT_VERSION}",
+			snapshot.version
+		));
+	}
+	let is_dev = genesis_hash == DEV_FLAG_GENHASH || snapshot.genesis_hash == DEV_FLAG_GENHASH;
+	if !is_dev && snapshot.genesis_hash != genesis_hash {
+		return Err(eyre!(
+			"Snapshot genesis hash {} doesn't match configured genesis hash {genesis_hash}",
+			snapshot.genesis_hash
+		));
+	}
+
+	let mut restored = 0;
+	for block in snapshot.blocks {
+		let block_number = block.block_number;
+		// Values are keyed by the block hash, so blocks without the header cannot be restored
+		let Some(header) = block.header else {
+			warn!(block_number, "Skipping block without the header");
+			continue;
+		};
+		let mut batch = Batch::default();
+		let block_hash = batch.put_finalized_header(header);
+		if let Some(count) = block.verified_cell_count {
+			batch.put(Key::VerifiedCellCount(block_number, block_hash), count);
+		}
+		for (app_id, data) in block.app_data {
+			batch.put(Key::AppData(app_id, block_number, block_hash), data);
+		}
+		db.write(batch)?;
+		restored += 1;
+	}
+	if let Some(progress) = snapshot.sync_progress {
+		db.put(Key::SyncProgress, progress)?;
+	}
+	if let Some(checkpoint) = snapshot.finality_sync_checkpoint {
+		db.put(Key::FinalitySyncCheckpoint, checkpoint)?;
+	}
+	Ok(restored)
+}
+
+/// Exports state snapshot into the file, returning number of the exported blocks.
+pub fn export(db: &impl Database, genesis_hash: &str, path: &Path) -> Result<usize> {
+	let snapshot = create(db, genesis_hash)?;
+	let exported = snapshot.blocks.len();
+	let file = File::create(path)
+		.wrap_err_with(|| format!("Failed to create snapshot file {}", path.display()))?;
+	serde_json::to_writer(BufWriter::new(file), &snapshot).wrap_err("Failed to write snapshot")?;
+	Ok(exported)
+}
+
+/// Imports state snapshot from the file, returning number of the imported blocks.
+pub fn import(db: &impl Database, genesis_hash: &str, path: &Path) -> Result<usize> {
+	let file = File::open(path)
+		.wrap_err_with(|| format!("Failed to open snapshot file {}", path.display()))?;
+	let snapshot: Snapshot =
+		serde_json::from_reader(BufReader::new(file)).wrap_err("Failed to read snapshot")?;
+	restore(db, snapshot, genesis_hash)
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_subxt::{
+		api::runtime_types::avail_core::{
+			data_lookup::compact::CompactDataLookup,
+			header::extension::{v3, HeaderExtension},
+			kate_commitment::v3::KateCommitment,
+		},
+		primitives::Header,
+		utils::H256,
+	};
+	use subxt::config::substrate::Digest;
+
+	use super::{create, restore};
+	use crate::data::{self, mem_db::MemoryDB, Batch, Database, Key, SyncProgress};
+
+	fn header(number: u32) -> Header {
+		Header {
+			parent_hash: H256::default(),
+			number,
+			state_root: H256::default(),
+			extrinsics_root: H256::default(),
+			extension: HeaderExtension::V3(v3::HeaderExtension {
+				commitment: KateCommitment::default(),
+				app_lookup: CompactDataLookup {
+					size: 0,
+					index: vec![],
+				},
+			}),
+			digest: Digest { logs: vec![] },
+		}
+	}
+
+	#[test]
+	fn export_import_roundtrip() {
+		let db = MemoryDB::default();
+		let mut batch = Batch::default();
+		let mut hashes = vec![H256::zero()];
+		for block_number in 1..4 {
+			let block_hash = batch.put_finalized_header(header(block_number));
+			batch.put(Key::VerifiedCellCount(block_number, block_hash), 8u32);
+			hashes.push(block_hash);
+		}
+		batch.put(Key::AppData(1, 2, hashes[2]), vec![vec![1u8, 2]]);
+		// Blocks of the other forks are not exported
+		batch.put(Key::VerifiedCellCount(3, H256::repeat_byte(1)), 4u32);
+		db.write(batch).unwrap();
+		db.put(Key::SyncProgress, SyncProgress::new(1..4, 0))
+			.unwrap();
+
+		let snapshot = create(&db, "DEV").unwrap();
+		let numbers = snapshot
+			.blocks
+			.iter()
+			.map(|block| block.block_number)
+			.collect::<Vec<_>>();
+		assert_eq!(numbers, vec![1, 2, 3]);
+		let json = serde_json::to_string(&snapshot).unwrap();
+
+		let imported = MemoryDB::default();
+		let restored = restore(&imported, serde_json::from_str(&json).unwrap(), "DEV").unwrap();
+		assert_eq!(restored, 3);
+		assert_eq!(
+			imported
+				.get::<u32>(Key::VerifiedCellCount(3, hashes[3]))
+				.unwrap(),
+			Some(8)
+		);
+		assert_eq!(
+			imported
+				.get::<Header>(Key::BlockHeader(2, hashes[2]))
+				.unwrap()
+				.map(|header| header.number),
+			Some(2)
+		);
+		assert_eq!(
+			imported
+				.get::<Vec<Vec<u8>>>(Key::AppData(1, 2, hashes[2]))
+				.unwrap(),
+			Some(vec![vec![1, 2]])
+		);
+		assert_eq!(data::canonical_hash(&imported, 2).unwrap(), Some(hashes[2]));
+		assert_eq!(
+			imported.get::<SyncProgress>(Key::SyncProgress).unwrap(),
+			Some(SyncProgress::new(1..4, 0))
+		);
+	}
+
+	#[test]
+	fn import_other_network_fails() {
+		let snapshot = create(&MemoryDB::default(), "0x01").unwrap();
+		assert!(restore(&MemoryDB::default(), snapshot, "0x02").is_err());
+	}
+}
@@ -0,0 +1,160 @@
+//! Supervision of the long running subsystems, which are restarted with the backoff if they panic or fail.
+
+use color_eyre::Result;
+use std::{
+	any::Any,
+	future::Future,
+	time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::{
+	retry::RetryPolicy,
+	shutdown::Controller,
+	telemetry::{MetricCounter, SharedMetrics},
+};
+
+tokio::task_local! {
+	static SUBSYSTEM: &'static str;
+}
+
+/// Returns name of the supervised subsystem which runs the current task.
+/// Panic hook uses it to restart the panicked subsystem, instead of triggering the shutdown.
+pub fn current_subsystem() -> Option<&'static str> {
+	SUBSYSTEM.try_with(|name| *name).ok()
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+	match payload.downcast::<String>() {
+		Ok(message) => *message,
+		Err(payload) => payload
+			.downcast_ref::<&str>()
+			.map(|message| message.to_string())
+			.unwrap_or_else(|| "unknown panic".to_string()),
+	}
+}
+
+/// Runs the subsystem created by the `start` function, and restarts it on failure.
+/// Subsystem which runs longer than the maximum restart delay is considered recovered,
+/// so its failures are counted from the start again.
+pub async fn supervise<F, Fut>(
+	name: &'static str,
+	policy: RetryPolicy,
+	shutdown: Controller<String>,
+	metrics: SharedMetrics,
+	mut start: F,
+) where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = Result<()>> + Send + 'static,
+{
+	let mut failures = 0;
+	loop {
+		let started_at = Instant::now();
+		let result = tokio::spawn(SUBSYSTEM.scope(name, start())).await;
+		if shutdown.is_shutdown_triggered() {
+			return;
+		}
+
+		let error = match result {
+			Ok(Ok(())) => {
+				info!(subsystem = name, "Subsystem completed");
+				return;
+			},
+			Ok(Err(error)) => format!("{error:#}"),
+			Err(error) if error.is_panic() => {
+				format!("Panicked: {}", panic_message(error.into_panic()))
+			},
+			Err(error) => format!("{error}"),
+		};
+
+		if started_at.elapsed() > Duration::from_millis(policy.max_delay) {
+			failures = 0;
+		}
+		failures += 1;
+		if failures >= policy.max_attempts {
+			let _ = shutdown.trigger_shutdown(format!(
+				"Subsystem {name} failed {failures} times in a row: {error}"
+			));
+			return;
+		}
+
+		metrics.count(MetricCounter::SubsystemRestart).await;
+		let delay = policy.delay(failures);
+		warn!(
+			subsystem = name,
+			failures,
+			?delay,
+			"Subsystem failed, restarting: {error}"
+		);
+		tokio::select! {
+			_ = tokio::time::sleep(delay) => (),
+			_ = shutdown.triggered_shutdown() => return,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use color_eyre::eyre::eyre;
+	use std::sync::{
+		atomic::{AtomicU32, Ordering},
+		Arc,
+	};
+
+	use super::{current_subsystem, supervise};
+	use crate::{
+		retry::RetryPolicy, shutdown::Controller, telemetry::MetricCounter, telemetry::MockMetrics,
+	};
+
+	fn policy(max_attempts: u32) -> RetryPolicy {
+		RetryPolicy {
+			max_attempts,
+			base_delay: 0,
+			max_delay: 1000,
+			jitter: 0.0,
+		}
+	}
+
+	fn metrics(restarts: usize) -> Arc<MockMetrics> {
+		let mut metrics = MockMetrics::new();
+		metrics
+			.expect_count()
+			.withf(|counter| matches!(counter, MetricCounter::SubsystemRestart))
+			.times(restarts)
+			.returning(|_| ());
+		Arc::new(metrics)
+	}
+
+	#[tokio::test]
+	async fn restarts_failed_subsystem() {
+		let shutdown = Controller::<String>::new();
+		let starts = Arc::new(AtomicU32::new(0));
+		let counter = starts.clone();
+		supervise("test", policy(5), shutdown.clone(), metrics(2), move || {
+			let starts = counter.clone();
+			async move {
+				assert_eq!(current_subsystem(), Some("test"));
+				match starts.fetch_add(1, Ordering::SeqCst) {
+					0 => Err(eyre!("Failed")),
+					1 => panic!("Panicked"),
+					_ => Ok(()),
+				}
+			}
+		})
+		.await;
+		assert_eq!(starts.load(Ordering::SeqCst), 3);
+		assert!(!shutdown.is_shutdown_triggered());
+	}
+
+	#[tokio::test]
+	async fn triggers_shutdown_after_max_attempts() {
+		let shutdown = Controller::<String>::new();
+		supervise("test", policy(3), shutdown.clone(), metrics(2), || async {
+			Err(eyre!("Failed"))
+		})
+		.await;
+		let reason = shutdown.shutdown_reason().unwrap();
+		assert_eq!(reason, "Subsystem test failed 3 times in a row: Failed");
+		assert_eq!(current_subsystem(), None);
+	}
+}
@@ -1,28 +1,39 @@
 //! Light (sync) client sampling and verification for blocks before latest finalized.
 //!
 //! Fetches and verifies previous blocks up to configured sync depth.
+//! Blocks are synced from the latest finalized block towards the sync start block,
+//! with the bounded number of blocks processed in parallel.
 //!
 //! # Flow
 //!
-//! * For each block, fetches block header from RPC and stores it into database
+//! * For each block, skips it if confidence is already stored (e.g. before restart)
+//! * Fetches block header from RPC and stores it into database
+//! * Skips the block if it is being processed or already processed by the light client or backfill
+//! * Waits for the sampling slot, which is given to the latest finalized blocks first
 //! * Generate random cells for random data sampling
 //! * Retrieve cell proofs from a) DHT and/or b) via RPC call from the node, in that order
 //! * Verify proof using the received cells
 //! * Calculate block confidence and store it in RocksDB
 //! * Insert cells to to DHT for remote fetch
+//! * Store block sync result
+//! * Store sync progress, so verified blocks are skipped without database lookup after restart
 //!
 //! # Notes
 //!
 //! In case RPC is disabled, RPC calls will be skipped.
+//! On shutdown, sync stops before the next block result is processed, and resumes from the stored progress after restart.
 
 use crate::{
-	data::{Database, Key},
+	data::{self, Batch, BlockSamplingStats, Database, Key, SyncProgress, SyncResult},
+	dedup::BlockDedup,
 	network::{
 		self,
 		rpc::{self, Client as RpcClient},
 	},
+	scheduler::{Priority, Scheduler},
+	shutdown::Controller,
 	types::{BlockVerified, OptionBlockRange, State, SyncClientConfig},
-	utils::{calculate_confidence, extract_app_lookup, extract_kate},
+	utils::{calculate_confidence, extract_app_lookup, extract_kate, unix_timestamp},
 };
 
 use async_trait::async_trait;
@@ -32,6 +43,7 @@ use color_eyre::{
 	eyre::{eyre, WrapErr},
 	Result,
 };
+use futures::{stream, StreamExt};
 use kate_recovery::{commitments, matrix::Dimensions};
 use mockall::automock;
 use sp_core::blake2_256;
@@ -48,7 +60,12 @@ use tracing::{error, info, warn};
 pub trait Client {
 	async fn get_header_by_block_number(&self, block_number: u32) -> Result<(DaHeader, H256)>;
 	fn is_confidence_stored(&self, block_number: u32) -> Result<bool>;
-	fn store_confidence(&self, count: u32, block_number: u32) -> Result<()>;
+	fn store_confidence(&self, count: u32, block_number: u32, block_hash: H256) -> Result<()>;
+	fn store_sampling_stats(&self, block_number: u32, stats: BlockSamplingStats) -> Result<()>;
+	fn get_sync_result(&self, block_number: u32) -> Result<Option<SyncResult>>;
+	fn store_sync_result(&self, block_number: u32, result: SyncResult) -> Result<()>;
+	fn get_sync_progress(&self) -> Result<Option<SyncProgress>>;
+	fn store_sync_progress(&self, progress: &SyncProgress) -> Result<()>;
 }
 
 #[derive(Clone)]
@@ -66,9 +83,7 @@ impl<T: Database + Sync> SyncClient<T> {
 #[async_trait]
 impl<T: Database + Sync> Client for SyncClient<T> {
 	async fn get_header_by_block_number(&self, block_number: u32) -> Result<(DaHeader, H256)> {
-		if let Some(header) = self
-			.db
-			.get(Key::BlockHeader(block_number))
+		if let Some(header) = data::get_canonical(&self.db, block_number, Key::BlockHeader)
 			.wrap_err("Sync Client failed to get Block Header from the storage")?
 		{
 			let hash: H256 = Encode::using_encoded(&header, blake2_256).into();
@@ -88,25 +103,56 @@ impl<T: Database + Sync> Client for SyncClient<T> {
 			Err(error) => return Err(error),
 		};
 
+		let mut batch = Batch::default();
+		batch.put_finalized_header(header.clone());
 		self.db
-			.put(Key::BlockHeader(block_number), header.clone())
+			.write(batch)
 			.wrap_err("Sync Client failed to store Block Header")?;
 
 		Ok((header, hash))
 	}
 
 	fn is_confidence_stored(&self, block_number: u32) -> Result<bool> {
-		self.db
-			.get(Key::VerifiedCellCount(block_number))
+		data::get_canonical(&self.db, block_number, Key::VerifiedCellCount)
 			.wrap_err("Sync Client failed to check if Confidence Factor is stored")
 			.map(|c: Option<u32>| c.is_some())
 	}
 
-	fn store_confidence(&self, count: u32, block_number: u32) -> Result<()> {
+	fn store_confidence(&self, count: u32, block_number: u32, block_hash: H256) -> Result<()> {
 		self.db
-			.put(Key::VerifiedCellCount(block_number), count)
+			.put(Key::VerifiedCellCount(block_number, block_hash), count)
 			.wrap_err("Sync Client failed to store Confidence Factor")
 	}
+
+	fn store_sampling_stats(&self, block_number: u32, stats: BlockSamplingStats) -> Result<()> {
+		self.db
+			.put(Key::BlockSamplingStats(block_number), stats)
+			.wrap_err("Sync Client failed to store block sampling stats")
+	}
+
+	fn get_sync_result(&self, block_number: u32) -> Result<Option<SyncResult>> {
+		self.db
+			.get(Key::SyncResult(block_number))
+			.wrap_err("Sync Client failed to get Sync Result")
+	}
+
+	fn store_sync_result(&self, block_number: u32, result: SyncResult) -> Result<()> {
+		self.db
+			.put(Key::SyncResult(block_number), result)
+			.wrap_err("Sync Client failed to store Sync Result")
+	}
+
+	fn get_sync_progress(&self) -> Result<Option<SyncProgress>> {
+		self.db
+			.get(Key::SyncProgress)
+			.wrap_err("Sync Client failed to get Sync Progress")
+	}
+
+	fn store_sync_progress(&self, progress: &SyncProgress) -> Result<()> {
+		self.db
+			.put(Key::SyncProgress, progress)
+			.wrap_err("Sync Client failed to store Sync Progress")
+	}
 }
 
 async fn process_block(
@@ -116,7 +162,7 @@ async fn process_block(
 	header_hash: H256,
 	cfg: &SyncClientConfig,
 	block_verified_sender: broadcast::Sender<BlockVerified>,
-) -> Result<()> {
+) -> Result<SyncResult> {
 	let block_number = header.number;
 	let begin = Instant::now();
 
@@ -131,10 +177,17 @@ async fn process_block(
 	let commitments = commitments::from_slice(&commitment)?;
 
 	// now this is in `u64`
-	let cell_count = rpc::cell_count_for_confidence(cfg.confidence);
-	let positions = rpc::generate_random_cells(dimensions, cell_count);
-
-	let (fetched, unfetched, _fetch_stats) = network_client
+	let cell_count = rpc::bounded_cell_count(cfg.confidence, cfg.max_cell_count);
+	let positions = rpc::generate_cells(
+		dimensions,
+		cell_count,
+		cfg.sampling_seed.as_deref(),
+		header_hash,
+		cfg.sampling_strategy,
+	);
+
+	let sampling_started = Instant::now();
+	let (fetched, unfetched, fetch_stats) = network_client
 		.fetch_verified(
 			block_number,
 			header_hash,
@@ -143,16 +196,27 @@ async fn process_block(
 			&positions,
 		)
 		.await?;
+	client.store_sampling_stats(
+		block_number,
+		fetch_stats.sampling_stats(sampling_started.elapsed()),
+	)?;
+
+	// Reconstructed cells have no verified proofs, so only the fetched cells are counted
+	let verified = fetched.len();
 
-	if positions.len() > fetched.len() {
+	if positions.len() > verified {
 		error!(block_number, "Failed to fetch {} cells", unfetched.len());
-		return Ok(());
+		return Ok(SyncResult::Unavailable {
+			fetched: verified.try_into()?,
+			required: positions.len().try_into()?,
+		});
 	}
 
 	// write confidence factor into on-disk database
-	client.store_confidence(fetched.len().try_into()?, block_number)?;
+	let cell_count = verified.try_into()?;
+	client.store_confidence(cell_count, block_number, header_hash)?;
 
-	let confidence = Some(calculate_confidence(fetched.len() as u32));
+	let confidence = Some(calculate_confidence(verified as u32));
 	let client_msg =
 		BlockVerified::try_from((header, confidence)).wrap_err("converting to message failed")?;
 
@@ -160,7 +224,66 @@ async fn process_block(
 		error!("Cannot send block verified message: {error}");
 	}
 
-	Ok(())
+	Ok(SyncResult::Verified { cell_count })
+}
+
+/// Syncs single block, returning `None` if the block is already verified, or processed by another client.
+#[allow(clippy::too_many_arguments)]
+async fn sync_block(
+	client: &impl Client,
+	network_client: &impl network::Client,
+	cfg: &SyncClientConfig,
+	block_number: u32,
+	block_verified_sender: broadcast::Sender<BlockVerified>,
+	state: &Mutex<State>,
+	scheduler: &Scheduler,
+	dedup: &BlockDedup,
+) -> Result<Option<SyncResult>> {
+	// TODO: This is still an ambiguous check since data fetch can fail.
+	if client.is_confidence_stored(block_number)? {
+		return Ok(None);
+	}
+
+	if let Some(result) = client.get_sync_result(block_number)? {
+		info!(
+			block_number,
+			"Retrying block sync, previous result: {result}"
+		);
+	}
+
+	// Blocks close to the latest finalized block at the startup are not confirmed yet
+	cfg.confirmation_depth.wait(block_number, state).await;
+	state.lock().unwrap().sync_latest.replace(block_number);
+	let (header, header_hash) = cfg
+		.header_retry_policy
+		.retry("header_query", || {
+			client.get_header_by_block_number(block_number)
+		})
+		.await?;
+
+	let claim = match dedup.claim(header_hash) {
+		Ok(claim) => claim,
+		Err(duplicate) => {
+			info!(
+				block_number,
+				"Skipping block sync, block is {duplicate} by another client"
+			);
+			return Ok(None);
+		},
+	};
+
+	let _slot = scheduler.acquire(Priority::Sync).await;
+	let result = process_block(
+		client,
+		network_client,
+		header,
+		header_hash,
+		cfg,
+		block_verified_sender,
+	)
+	.await?;
+	claim.complete();
+	Ok(Some(result))
 }
 
 /// Runs sync client.
@@ -171,6 +294,9 @@ async fn process_block(
 /// * `start_block` - Sync start block
 /// * `end_block` - Sync end block
 /// * `block_verified_sender` - Optional channel to send verified blocks
+/// * `scheduler` - Sampling scheduler shared with the light client
+/// * `dedup` - Processed blocks shared with the light and backfill clients
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
 	client: impl Client,
 	network_client: impl network::Client,
@@ -178,6 +304,9 @@ pub async fn run(
 	sync_range: Range<u32>,
 	block_verified_sender: broadcast::Sender<BlockVerified>,
 	state: Arc<Mutex<State>>,
+	scheduler: Scheduler,
+	dedup: BlockDedup,
+	shutdown: Controller<String>,
 ) {
 	if sync_range.is_empty() {
 		warn!("There are no blocks to sync for range {sync_range:?}");
@@ -188,51 +317,102 @@ pub async fn run(
 		warn!("In order to process {sync_blocks_depth} blocks behind latest block, connected nodes needs to be archive nodes!");
 	}
 
-	info!("Syncing block headers for {sync_range:?}");
-	for block_number in sync_range {
-		// TODO: This is still an ambiguous check since data fetch can fail.
-		// We should write block status in DB explicitly.
-		match client.is_confidence_stored(block_number) {
-			Ok(false) => (),
-			Ok(true) => continue,
-			Err(error) => {
-				// TODO: Is it valid to have skipped block?
-				error!(block_number, "Cannot process block: {error:#}");
-				continue;
+	let now = unix_timestamp();
+	let mut progress = match client.get_sync_progress() {
+		Ok(Some(progress)) => progress.resume(sync_range.clone(), now),
+		Ok(None) => SyncProgress::new(sync_range.clone(), now),
+		Err(error) => {
+			warn!("Cannot load sync progress, syncing all blocks: {error:#}");
+			SyncProgress::new(sync_range.clone(), now)
+		},
+	};
+
+	let parallelism = cfg.parallelism;
+	info!(
+		verified = progress.verified(),
+		total = progress.total(),
+		"Syncing blocks {sync_range:?} from the latest, {parallelism} blocks in parallel"
+	);
+
+	let blocks = sync_range
+		.rev()
+		.map(|block_number| (block_number, progress.is_verified(block_number)))
+		.collect::<Vec<_>>();
+
+	// Results are buffered in order, so synced blocks are extending state ranges downwards
+	let mut results = stream::iter(blocks)
+		.map(|(block_number, is_verified)| {
+			let (client, network_client, cfg, state, scheduler, dedup) =
+				(&client, &network_client, &cfg, &state, &scheduler, &dedup);
+			let block_verified_sender = block_verified_sender.clone();
+			async move {
+				if is_verified {
+					return (block_number, Ok(None));
+				}
+				let result = sync_block(
+					client,
+					network_client,
+					cfg,
+					block_number,
+					block_verified_sender,
+					state,
+					scheduler,
+					dedup,
+				)
+				.await;
+				(block_number, result)
+			}
+		})
+		.buffered(parallelism);
+
+	// shutdown will wait until the result of the last processed block is stored
+	let Ok(_delay_token) = shutdown.delay_token() else {
+		return;
+	};
+
+	loop {
+		let (block_number, result) = tokio::select! {
+			biased;
+			_ = shutdown.triggered_shutdown() => {
+				info!(verified = progress.verified(), "Shutdown triggered, stopping the sync");
+				return;
+			},
+			next = results.next() => match next {
+				Some(next) => next,
+				None => break,
 			},
 		};
-
-		let (header, header_hash) = match client.get_header_by_block_number(block_number).await {
-			Ok(value) => value,
+		let result = match result {
+			Ok(None) => None,
+			Ok(Some(result)) => Some(result),
 			Err(error) => {
+				// TODO: Should we handle unprocessed blocks differently?
 				error!(block_number, "Cannot process block: {error:#}");
-				continue;
+				let reason = format!("{error:#}");
+				Some(SyncResult::Failed { reason })
 			},
 		};
 
-		{
+		let is_verified = matches!(result, None | Some(SyncResult::Verified { .. }));
+		if !matches!(result, Some(SyncResult::Failed { .. })) {
 			let mut state = state.lock().unwrap();
-			state.sync_latest.replace(block_number);
 			// TODO: Add proper header verification on sync
-			state.sync_header_verified.set(block_number);
+			state.sync_header_verified.extend(block_number);
+			if is_verified {
+				state.sync_confidence_achieved.extend(block_number);
+			}
 		}
 
-		// TODO: Should we handle unprocessed blocks differently?
-		let block_verified_sender = block_verified_sender.clone();
-		if let Err(error) = process_block(
-			&client,
-			&network_client,
-			header,
-			header_hash,
-			&cfg,
-			block_verified_sender,
-		)
-		.await
-		{
-			error!(block_number, "Cannot process block: {error:#}");
-		} else {
-			let mut state = state.lock().unwrap();
-			state.sync_confidence_achieved.set(block_number);
+		if let Some(result) = result {
+			if let Err(error) = client.store_sync_result(block_number, result) {
+				error!(block_number, "Cannot store sync result: {error:#}");
+			}
+		}
+
+		if is_verified && progress.set_verified(block_number) {
+			if let Err(error) = client.store_sync_progress(&progress) {
+				error!(block_number, "Cannot store sync progress: {error:#}");
+			}
 		}
 	}
 
@@ -372,8 +552,12 @@ mod tests {
 			.returning(|_| Ok(true));
 		mock_client
 			.expect_store_confidence()
-			.withf(move |_, block_number| *block_number == 2)
-			.returning(move |_, _| Ok(()));
+			.withf(move |_, block_number, _| *block_number == 2)
+			.returning(move |_, _, _| Ok(()));
+		mock_client
+			.expect_store_sampling_stats()
+			.withf(|block_number, _| *block_number == 2)
+			.returning(|_, _| Ok(()));
 		process_block(
 			&mock_client,
 			&mock_network_client,
@@ -458,8 +642,12 @@ mod tests {
 
 		mock_client
 			.expect_store_confidence()
-			.withf(move |_, block_number| *block_number == 2)
-			.returning(move |_, _| Ok(()));
+			.withf(move |_, block_number, _| *block_number == 2)
+			.returning(move |_, _, _| Ok(()));
+		mock_client
+			.expect_store_sampling_stats()
+			.withf(|block_number, _| *block_number == 2)
+			.returning(|_, _| Ok(()));
 		process_block(
 			&mock_client,
 			&mock_network_client,
@@ -471,4 +659,128 @@ mod tests {
 		.await
 		.unwrap();
 	}
+
+	#[tokio::test]
+	pub async fn test_run_skips_verified_blocks() {
+		let (block_tx, _) = broadcast::channel::<types::BlockVerified>(10);
+		let mut cfg = SyncClientConfig::from(&RuntimeConfig::default());
+		// Failed header query is not retried, so the block sync fails on the first attempt
+		cfg.header_retry_policy.max_attempts = 1;
+		let mut mock_client = MockClient::new();
+		mock_client
+			.expect_is_confidence_stored()
+			.returning(|block_number| Ok(block_number != 3));
+		mock_client
+			.expect_get_sync_result()
+			.with(eq(3))
+			.returning(|_| Ok(None));
+		mock_client
+			.expect_get_header_by_block_number()
+			.with(eq(3))
+			.times(1)
+			.returning(|_| Box::pin(async { Err(eyre!("Header not found")) }));
+		mock_client
+			.expect_store_sync_result()
+			.withf(|block_number, result| {
+				*block_number == 3 && matches!(result, SyncResult::Failed { .. })
+			})
+			.times(1)
+			.returning(|_, _| Ok(()));
+		mock_client
+			.expect_get_sync_progress()
+			.returning(|| Ok(None));
+		mock_client
+			.expect_store_sync_progress()
+			.times(3)
+			.returning(|_| Ok(()));
+
+		let state = Arc::new(Mutex::new(State::default()));
+		run(
+			mock_client,
+			network::MockClient::new(),
+			cfg,
+			1..5,
+			block_tx,
+			state.clone(),
+			Scheduler::new(&(&RuntimeConfig::default()).into()),
+			BlockDedup::default(),
+			Controller::new(),
+		)
+		.await;
+
+		let state = state.lock().unwrap();
+		assert_eq!(state.sync_confidence_achieved.first(), Some(1));
+		assert_eq!(state.sync_confidence_achieved.last(), Some(4));
+		assert_eq!(state.synced, Some(true));
+	}
+
+	#[tokio::test]
+	pub async fn test_run_resumes_sync_progress() {
+		let (block_tx, _) = broadcast::channel::<types::BlockVerified>(10);
+		let cfg = SyncClientConfig::from(&RuntimeConfig::default());
+		let mut mock_client = MockClient::new();
+		mock_client.expect_get_sync_progress().returning(|| {
+			let mut progress = SyncProgress::new(1..4, 0);
+			for block_number in 1..4 {
+				progress.set_verified(block_number);
+			}
+			Ok(Some(progress))
+		});
+		mock_client
+			.expect_is_confidence_stored()
+			.with(eq(4))
+			.times(1)
+			.returning(|_| Ok(true));
+		mock_client
+			.expect_store_sync_progress()
+			.withf(|progress| progress.verified() == 4 && progress.lowest_verified == Some(1))
+			.times(1)
+			.returning(|_| Ok(()));
+
+		let state = Arc::new(Mutex::new(State::default()));
+		run(
+			mock_client,
+			network::MockClient::new(),
+			cfg,
+			1..5,
+			block_tx,
+			state.clone(),
+			Scheduler::new(&(&RuntimeConfig::default()).into()),
+			BlockDedup::default(),
+			Controller::new(),
+		)
+		.await;
+
+		let state = state.lock().unwrap();
+		assert_eq!(state.sync_confidence_achieved.first(), Some(1));
+		assert_eq!(state.sync_confidence_achieved.last(), Some(4));
+	}
+
+	#[test]
+	fn test_sync_progress() {
+		let mut progress = SyncProgress::new(10..20, 0);
+		assert_eq!(progress.total(), 10);
+		assert_eq!(progress.verified(), 0);
+		assert!(progress.set_verified(19));
+		assert!(progress.set_verified(17));
+		assert!(!progress.set_verified(19));
+		assert!(!progress.set_verified(20));
+		assert_eq!(progress.lowest_verified, Some(19));
+		assert!(progress.set_verified(18));
+		assert_eq!(progress.lowest_verified, Some(17));
+		assert_eq!(progress.verified(), 3);
+		assert_eq!(progress.session_verified, 3);
+
+		let progress = progress.resume(10..25, 100);
+		assert_eq!(progress.total(), 15);
+		assert_eq!(progress.verified(), 3);
+		assert_eq!(progress.lowest_verified, None);
+		assert_eq!(progress.session_verified, 0);
+		assert_eq!(progress.session_started_at, 100);
+		assert!(progress.is_verified(17));
+		assert!(!progress.is_verified(20));
+
+		let progress = progress.resume(5..25, 200);
+		assert_eq!(progress.verified(), 0);
+	}
 }
@@ -19,9 +19,11 @@ use subxt::{storage::StorageKey, utils::AccountId32};
 use tracing::{error, info, trace};
 
 use crate::{
-	data::{Database, FinalitySyncCheckpoint, Key},
+	checkpoint::TrustedCheckpoint,
+	data::{Batch, Database, FinalitySyncCheckpoint, Key},
 	finality::{check_finality, ValidatorSet},
 	network::rpc::{self, WrappedProof},
+	retry::RetryPolicy,
 	shutdown::Controller,
 	types::State,
 	utils::filter_auth_set_changes,
@@ -29,7 +31,7 @@ use crate::{
 
 #[async_trait]
 pub trait Client {
-	fn store_block_header(&self, block_number: u32, header: Header) -> Result<()>;
+	fn store_block_header(&self, header: Header) -> Result<()>;
 	fn get_checkpoint(&self) -> Result<Option<FinalitySyncCheckpoint>>;
 	fn store_checkpoint(&self, checkpoint: FinalitySyncCheckpoint) -> Result<()>;
 	async fn get_paged_storage_keys(
@@ -133,9 +135,11 @@ impl<T: Database + Sync> Client for SyncFinality<T> {
 			.wrap_err("Finality Sync Client failed to request Finality Proof")
 	}
 
-	fn store_block_header(&self, block_number: u32, header: Header) -> Result<()> {
+	fn store_block_header(&self, header: Header) -> Result<()> {
+		let mut batch = Batch::default();
+		batch.put_finalized_header(header);
 		self.db
-			.put(Key::BlockHeader(block_number), header)
+			.write(batch)
 			.wrap_err("Finality Sync Client failed to store Block Header")
 	}
 
@@ -212,13 +216,49 @@ async fn get_valset_at_genesis(
 	Ok(validator_set)
 }
 
+/// Stores trusted checkpoint as a starting point of the finality sync,
+/// unless finality is already synced beyond the checkpoint.
+pub async fn import_checkpoint(client: &impl Client, checkpoint: TrustedCheckpoint) -> Result<()> {
+	if let Some(stored) = client.get_checkpoint()? {
+		if stored.number >= checkpoint.number {
+			info!(
+				"Finality is synced up to block {}, skipping trusted checkpoint at block {}",
+				stored.number, checkpoint.number
+			);
+			return Ok(());
+		}
+	}
+
+	let block_hash = client
+		.get_block_hash(checkpoint.number)
+		.await
+		.wrap_err(format!(
+			"Couldn't get hash for checkpoint block no. {}",
+			checkpoint.number
+		))?;
+	if block_hash != checkpoint.block_hash {
+		return Err(eyre!(
+			"Checkpoint block hash {:?} doesn't match the hash {block_hash:?} of block no. {}",
+			checkpoint.block_hash,
+			checkpoint.number
+		));
+	}
+
+	info!(
+		"Importing trusted checkpoint at block no. {}, set_id {}",
+		checkpoint.number, checkpoint.set_id
+	);
+	client.store_checkpoint(checkpoint.into())
+}
+
 pub async fn run(
 	client: impl Client,
 	shutdown: Controller<String>,
 	state: Arc<Mutex<State>>,
 	from_header: Header,
+	retry_policy: RetryPolicy,
 ) {
-	if let Err(error) = sync(client, state, from_header).await {
+	if let Err(error) = sync(client, state, from_header, retry_policy).await {
 		error!("Cannot sync finality {error}");
 		let _ = shutdown.trigger_shutdown(format!("Cannot sync finality {error:#}"));
 	};
@@ -228,6 +268,7 @@ pub async fn sync(
 	client: impl Client,
 	state: Arc<Mutex<State>>,
 	mut from_header: Header,
+	retry_policy: RetryPolicy,
 ) -> Result<()> {
 	let gen_hash = client.get_genesis_hash().await?;
 
@@ -257,8 +298,10 @@ pub async fn sync(
 
 	info!("Syncing finality from {curr_block_num} up to block no. {last_block_num}");
 
-	let mut prev_hash = client
-		.get_block_hash(curr_block_num - 1)
+	let mut prev_hash = retry_policy
+		.retry("block_hash_query", || {
+			client.get_block_hash(curr_block_num - 1)
+		})
 		.await
 		.wrap_err("Hash doesn't exist?")?;
 	loop {
@@ -266,18 +309,18 @@ pub async fn sync(
 			info!("Finished verifying finality up to block no. {last_block_num}!");
 			break;
 		}
-		let hash = client
-			.get_block_hash(curr_block_num)
+		let hash = retry_policy
+			.retry("block_hash_query", || client.get_block_hash(curr_block_num))
 			.await
 			.wrap_err(format!(
 				"Couldn't get hash for block no. {}",
 				curr_block_num
 			))?;
-		from_header = client
-			.get_header_by_hash(hash)
+		from_header = retry_policy
+			.retry("header_query", || client.get_header_by_hash(hash))
 			.await
 			.wrap_err(format!("Couldn't get header for {}", hash))?;
-		client.store_block_header(curr_block_num, from_header.clone())?;
+		client.store_block_header(from_header.clone())?;
 
 		assert_eq!(
 			from_header.parent_hash, prev_hash,
@@ -299,8 +342,10 @@ pub async fn sync(
 				curr_block_num
 			))?;
 		let proof_block_hash = proof.0.block;
-		let p_h = client
-			.get_header_by_hash(proof_block_hash)
+		let p_h = retry_policy
+			.retry("header_query", || {
+				client.get_header_by_hash(proof_block_hash)
+			})
 			.await
 			.wrap_err(format!("Couldn't get header for {}", proof_block_hash))?;
 
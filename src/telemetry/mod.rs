@@ -1,6 +1,7 @@
 use std::{
 	collections::HashMap,
 	fmt::{self, Display, Formatter},
+	sync::Arc,
 };
 
 use async_trait::async_trait;
@@ -9,7 +10,11 @@ use mockall::automock;
 use opentelemetry_api::metrics::{Counter, Meter};
 
 pub mod otlp;
+pub mod prometheus;
+pub mod push;
+pub mod reporter;
 
+#[derive(Clone, Copy)]
 pub enum MetricCounter {
 	SessionBlock,
 	OutgoingConnectionError,
@@ -17,7 +22,11 @@ pub enum MetricCounter {
 	IncomingConnection,
 	ConnectionEstablished,
 	IncomingPutRecord,
+	RejectedPutRecord,
 	IncomingGetRecord,
+	SubsystemRestart,
+	SamplingSummaryMismatch,
+	CommitmentMismatch,
 }
 
 impl Display for MetricCounter {
@@ -29,23 +38,35 @@ impl Display for MetricCounter {
 			MetricCounter::IncomingConnection => write!(f, "incoming_connections"),
 			MetricCounter::ConnectionEstablished => write!(f, "established_connections"),
 			MetricCounter::IncomingPutRecord => write!(f, "incoming_put_record_counter"),
+			MetricCounter::RejectedPutRecord => write!(f, "rejected_put_record_counter"),
 			MetricCounter::IncomingGetRecord => write!(f, "incoming_get_record_counter"),
+			MetricCounter::SubsystemRestart => write!(f, "subsystem_restarts"),
+			MetricCounter::SamplingSummaryMismatch => write!(f, "sampling_summary_mismatches"),
+			MetricCounter::CommitmentMismatch => write!(f, "commitment_mismatches"),
 		}
 	}
 }
 
 impl MetricCounter {
-	fn init_counters(meter: Meter) -> HashMap<String, Counter<u64>> {
-		let mut counter_map: HashMap<String, Counter<u64>> = Default::default();
-		for counter in [
+	fn all() -> [MetricCounter; 11] {
+		[
 			MetricCounter::SessionBlock,
 			MetricCounter::OutgoingConnectionError,
 			MetricCounter::IncomingConnectionError,
 			MetricCounter::IncomingConnection,
 			MetricCounter::ConnectionEstablished,
 			MetricCounter::IncomingPutRecord,
+			MetricCounter::RejectedPutRecord,
 			MetricCounter::IncomingGetRecord,
-		] {
+			MetricCounter::SubsystemRestart,
+			MetricCounter::SamplingSummaryMismatch,
+			MetricCounter::CommitmentMismatch,
+		]
+	}
+
+	fn init_counters(meter: Meter) -> HashMap<String, Counter<u64>> {
+		let mut counter_map: HashMap<String, Counter<u64>> = Default::default();
+		for counter in MetricCounter::all() {
 			counter_map.insert(
 				counter.to_string(),
 				meter.u64_counter(counter.to_string()).init(),
@@ -55,6 +76,7 @@ impl MetricCounter {
 	}
 }
 
+#[derive(Clone, Copy)]
 pub enum MetricValue {
 	TotalBlockNumber(u32),
 	DHTFetched(f64),
@@ -62,17 +84,25 @@ pub enum MetricValue {
 	DHTFetchDuration(f64),
 	NodeRPCFetched(f64),
 	NodeRPCFetchDuration(f64),
+	ProofVerificationDuration(f64),
 	BlockConfidence(f64),
 	BlockConfidenceTreshold(f64),
+	EffectiveConfidence(f64),
 	RPCCallDuration(f64),
+	/// Duration of the single RPC request, in seconds
+	RPCRequestDuration(f64),
 	DHTPutDuration(f64),
 	DHTPutSuccess(f64),
 	ConnectedPeersNum(usize),
 	HealthCheck(),
 	BlockProcessingDelay(f64),
+	/// Number of blocks between the latest finalized block and the processed block
+	SyncLag(u32),
 	PingLatency(f64),
 	ReplicationFactor(u16),
 	QueryTimeout(u32),
+	/// Runtime specification version of the connected node
+	RuntimeSpecVersion(u32),
 	#[cfg(feature = "crawl")]
 	CrawlCellsSuccessRate(f64),
 	#[cfg(feature = "crawl")]
@@ -88,3 +118,89 @@ pub trait Metrics {
 	async fn record(&self, value: MetricValue) -> Result<()>;
 	async fn set_multiaddress(&self, multiaddr: String);
 }
+
+/// Metrics backend which is shared across modules
+pub type SharedMetrics = Arc<dyn Metrics + Send + Sync>;
+
+/// Metrics registry, which forwards recorded metrics to all of the configured backends.
+/// Registry without backends discards recorded metrics.
+#[derive(Clone, Default)]
+pub struct Registry {
+	backends: Vec<SharedMetrics>,
+}
+
+impl Registry {
+	pub fn with_backend(mut self, backend: SharedMetrics) -> Self {
+		self.backends.push(backend);
+		self
+	}
+}
+
+#[async_trait]
+impl Metrics for Registry {
+	async fn count(&self, counter: MetricCounter) {
+		for backend in &self.backends {
+			backend.count(counter).await;
+		}
+	}
+
+	/// Records value to all backends, returning the last error if recording fails on any of them.
+	async fn record(&self, value: MetricValue) -> Result<()> {
+		let mut result = Ok(());
+		for backend in &self.backends {
+			if let Err(error) = backend.record(value).await {
+				result = Err(error);
+			}
+		}
+		result
+	}
+
+	async fn set_multiaddress(&self, multiaddr: String) {
+		for backend in &self.backends {
+			backend.set_multiaddress(multiaddr.clone()).await;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use color_eyre::eyre::eyre;
+	use std::sync::Arc;
+
+	use super::{MetricCounter, MetricValue, Metrics, MockMetrics, Registry};
+
+	#[tokio::test]
+	async fn registry_forwards_to_all_backends() {
+		let mut first = MockMetrics::new();
+		first.expect_count().times(1).returning(|_| ());
+		first.expect_record().times(2).returning(|_| Ok(()));
+
+		let mut second = MockMetrics::new();
+		second.expect_count().times(1).returning(|_| ());
+		second
+			.expect_record()
+			.times(2)
+			.returning(|value| match value {
+				MetricValue::SyncLag(_) => Err(eyre!("Cannot record")),
+				_ => Ok(()),
+			});
+
+		let registry = Registry::default()
+			.with_backend(Arc::new(first))
+			.with_backend(Arc::new(second));
+
+		registry.count(MetricCounter::SessionBlock).await;
+		assert!(registry
+			.record(MetricValue::BlockConfidence(99.))
+			.await
+			.is_ok());
+		assert!(registry.record(MetricValue::SyncLag(1)).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn empty_registry_discards_metrics() {
+		let registry = Registry::default();
+		registry.count(MetricCounter::SessionBlock).await;
+		assert!(registry.record(MetricValue::SyncLag(1)).await.is_ok());
+	}
+}
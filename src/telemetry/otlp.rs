@@ -1,11 +1,15 @@
 use async_trait::async_trait;
-use color_eyre::Result;
+use color_eyre::{eyre::WrapErr, Result};
 use opentelemetry_api::{
 	global,
 	metrics::{Counter, Meter},
 	KeyValue,
 };
 use opentelemetry_otlp::{ExportConfig, Protocol, WithExportConfig};
+use opentelemetry_sdk::{
+	trace::{self, Tracer},
+	Resource,
+};
 use std::{collections::HashMap, time::Duration};
 use tokio::sync::RwLock;
 
@@ -59,6 +63,11 @@ impl Metrics {
 		Ok(())
 	}
 
+	async fn record_histogram(&self, name: &'static str, value: f64) {
+		let instrument = self.meter.f64_histogram(name).init();
+		instrument.record(value, &self.attributes().await);
+	}
+
 	async fn record_f64(&self, name: &'static str, value: f64) -> Result<()> {
 		let instrument = self.meter.f64_observable_gauge(name).try_init()?;
 		let attributes = self.attributes().await;
@@ -101,15 +110,26 @@ impl super::Metrics for Metrics {
 			super::MetricValue::NodeRPCFetchDuration(number) => {
 				self.record_f64("node_rpc_fetch_duration", number).await?;
 			},
+			super::MetricValue::ProofVerificationDuration(number) => {
+				self.record_f64("proof_verification_duration", number)
+					.await?;
+			},
 			super::MetricValue::BlockConfidence(number) => {
 				self.record_f64("block_confidence", number).await?;
 			},
 			super::MetricValue::BlockConfidenceTreshold(number) => {
 				self.record_f64("block_confidence_treshold", number).await?;
 			},
+			super::MetricValue::EffectiveConfidence(number) => {
+				self.record_f64("effective_confidence", number).await?;
+			},
 			super::MetricValue::RPCCallDuration(number) => {
 				self.record_f64("rpc_call_duration", number).await?;
 			},
+			super::MetricValue::RPCRequestDuration(number) => {
+				// recorded on each request, so it is exported as histogram instead of gauge
+				self.record_histogram("rpc_request_duration", number).await;
+			},
 			super::MetricValue::DHTPutDuration(number) => {
 				self.record_f64("dht_put_duration", number).await?;
 			},
@@ -126,6 +146,9 @@ impl super::Metrics for Metrics {
 			super::MetricValue::BlockProcessingDelay(number) => {
 				self.record_f64("block_processing_delay", number).await?;
 			},
+			super::MetricValue::SyncLag(number) => {
+				self.record_u64("sync_lag", number.into()).await?;
+			},
 			super::MetricValue::ReplicationFactor(number) => {
 				self.record_f64("replication_factor", number as f64).await?;
 			},
@@ -135,6 +158,10 @@ impl super::Metrics for Metrics {
 			super::MetricValue::PingLatency(number) => {
 				self.record_f64("ping_latency", number).await?;
 			},
+			super::MetricValue::RuntimeSpecVersion(number) => {
+				self.record_u64("runtime_spec_version", number.into())
+					.await?;
+			},
 			#[cfg(feature = "crawl")]
 			super::MetricValue::CrawlCellsSuccessRate(number) => {
 				self.record_f64("crawl_cells_success_rate", number).await?;
@@ -183,3 +210,28 @@ pub fn initialize(endpoint: String, attributes: MetricAttributes) -> Result<Metr
 		counters: initialized_counters,
 	})
 }
+
+/// Initializes tracer which exports spans to the OTLP collector in batches.
+pub fn initialize_tracer(endpoint: String) -> Result<Tracer> {
+	let exporter = opentelemetry_otlp::new_exporter()
+		.tonic()
+		.with_endpoint(endpoint)
+		.with_timeout(Duration::from_secs(10));
+
+	let resource = Resource::new([
+		KeyValue::new("service.name", "avail-light"),
+		KeyValue::new("service.version", clap::crate_version!()),
+	]);
+
+	opentelemetry_otlp::new_pipeline()
+		.tracing()
+		.with_exporter(exporter)
+		.with_trace_config(trace::config().with_resource(resource))
+		.install_batch(opentelemetry_sdk::runtime::Tokio)
+		.wrap_err("Unable to initialize OpenTelemetry tracer")
+}
+
+/// Exports remaining spans and shuts down the tracer.
+pub fn shutdown_tracer() {
+	global::shutdown_tracer_provider();
+}
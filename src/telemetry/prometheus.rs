@@ -0,0 +1,329 @@
+//! Prometheus metrics backend, exported in the OpenMetrics text format on the `/metrics` endpoint.
+
+use async_trait::async_trait;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use prometheus_client::{
+	encoding::{text::encode, EncodeLabelSet},
+	metrics::{
+		counter::Counter,
+		family::Family,
+		gauge::Gauge,
+		histogram::{exponential_buckets, Histogram},
+	},
+	registry::Registry,
+};
+use std::{collections::HashMap, sync::atomic::AtomicU64};
+
+use super::{MetricCounter, MetricValue};
+
+const PREFIX: &str = "avail_light";
+
+/// Histogram buckets, in seconds (from 5ms to ~41s)
+const DURATION_BUCKETS: (f64, f64, u16) = (0.005, 2.0, 14);
+
+const GAUGES: &[(&str, &str)] = &[
+	("total_block_number", "Latest processed block number"),
+	(
+		"dht_get_success_ratio",
+		"Ratio of the sampled cells fetched from the DHT",
+	),
+	(
+		"block_confidence",
+		"Confidence of the latest processed block",
+	),
+	(
+		"block_confidence_threshold",
+		"Configured confidence threshold",
+	),
+	(
+		"effective_confidence",
+		"Confidence achieved when all sampled cells are verified",
+	),
+	(
+		"dht_put_success_ratio",
+		"Ratio of the successfully stored DHT records",
+	),
+	("connected_peers", "Number of connected peers"),
+	("up", "Light client health check"),
+	(
+		"replication_factor",
+		"Configured Kademlia replication factor",
+	),
+	(
+		"query_timeout",
+		"Configured Kademlia query timeout, in seconds",
+	),
+	(
+		"sync_lag",
+		"Number of blocks between the latest finalized and the processed block",
+	),
+	#[cfg(feature = "crawl")]
+	("crawl_cells_success_rate", "Ratio of the crawled cells"),
+	#[cfg(feature = "crawl")]
+	("crawl_rows_success_rate", "Ratio of the crawled rows"),
+	#[cfg(feature = "crawl")]
+	("crawl_block_delay", "Crawl block delay, in seconds"),
+];
+
+const HISTOGRAMS: &[(&str, &str)] = &[
+	(
+		"dht_fetch_duration_seconds",
+		"Duration of the cells fetch from the DHT",
+	),
+	(
+		"rpc_fetch_duration_seconds",
+		"Duration of the cells fetch from the RPC",
+	),
+	(
+		"proof_verification_duration_seconds",
+		"Duration of the cell proofs verification",
+	),
+	(
+		"rpc_call_duration_seconds",
+		"Duration of the partition fetch from the RPC",
+	),
+	(
+		"rpc_request_duration_seconds",
+		"Duration of the single RPC request",
+	),
+	(
+		"dht_put_duration_seconds",
+		"Duration of the DHT records upload",
+	),
+	(
+		"block_processing_delay_seconds",
+		"Configured delay before the block is processed",
+	),
+	(
+		"ping_latency_seconds",
+		"Round trip time of the ping to the peer",
+	),
+];
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct SourceLabels {
+	source: String,
+}
+
+enum Instrument {
+	Gauge(&'static str),
+	Histogram(&'static str),
+	CellsFetched(&'static str),
+}
+
+fn instrument(value: MetricValue) -> (Instrument, f64) {
+	use Instrument::*;
+	match value {
+		MetricValue::TotalBlockNumber(number) => (Gauge("total_block_number"), number.into()),
+		MetricValue::DHTFetched(number) => (CellsFetched("dht"), number),
+		MetricValue::DHTFetchedPercentage(number) => (Gauge("dht_get_success_ratio"), number),
+		MetricValue::DHTFetchDuration(number) => (Histogram("dht_fetch_duration_seconds"), number),
+		MetricValue::NodeRPCFetched(number) => (CellsFetched("rpc"), number),
+		MetricValue::NodeRPCFetchDuration(number) => {
+			(Histogram("rpc_fetch_duration_seconds"), number)
+		},
+		MetricValue::ProofVerificationDuration(number) => {
+			(Histogram("proof_verification_duration_seconds"), number)
+		},
+		MetricValue::BlockConfidence(number) => (Gauge("block_confidence"), number),
+		MetricValue::BlockConfidenceTreshold(number) => {
+			(Gauge("block_confidence_threshold"), number)
+		},
+		MetricValue::EffectiveConfidence(number) => (Gauge("effective_confidence"), number),
+		MetricValue::RPCCallDuration(number) => (Histogram("rpc_call_duration_seconds"), number),
+		MetricValue::RPCRequestDuration(number) => {
+			(Histogram("rpc_request_duration_seconds"), number)
+		},
+		MetricValue::DHTPutDuration(number) => (Histogram("dht_put_duration_seconds"), number),
+		MetricValue::DHTPutSuccess(number) => (Gauge("dht_put_success_ratio"), number),
+		MetricValue::ConnectedPeersNum(number) => (Gauge("connected_peers"), number as f64),
+		MetricValue::HealthCheck() => (Gauge("up"), 1.0),
+		MetricValue::BlockProcessingDelay(number) => {
+			(Histogram("block_processing_delay_seconds"), number)
+		},
+		// ping latency is recorded in milliseconds
+		MetricValue::PingLatency(number) => (Histogram("ping_latency_seconds"), number / 1000.0),
+		MetricValue::ReplicationFactor(number) => (Gauge("replication_factor"), number.into()),
+		MetricValue::QueryTimeout(number) => (Gauge("query_timeout"), number.into()),
+		MetricValue::SyncLag(number) => (Gauge("sync_lag"), number.into()),
+		MetricValue::RuntimeSpecVersion(number) => (Gauge("runtime_spec_version"), number.into()),
+		#[cfg(feature = "crawl")]
+		MetricValue::CrawlCellsSuccessRate(number) => (Gauge("crawl_cells_success_rate"), number),
+		#[cfg(feature = "crawl")]
+		MetricValue::CrawlRowsSuccessRate(number) => (Gauge("crawl_rows_success_rate"), number),
+		#[cfg(feature = "crawl")]
+		MetricValue::CrawlBlockDelay(number) => (Gauge("crawl_block_delay"), number),
+	}
+}
+
+pub struct Metrics {
+	registry: Registry,
+	counters: HashMap<String, Counter>,
+	gauges: HashMap<&'static str, Gauge<f64, AtomicU64>>,
+	histograms: HashMap<&'static str, Histogram>,
+	cells_fetched: Family<SourceLabels, Counter>,
+}
+
+impl Default for Metrics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Metrics {
+	pub fn new() -> Self {
+		let mut registry = Registry::with_prefix(PREFIX);
+
+		let mut counters = HashMap::new();
+		for counter in MetricCounter::all() {
+			let metric = Counter::default();
+			let name = counter.to_string();
+			registry.register(&name, format!("Number of {name} events"), metric.clone());
+			counters.insert(name, metric);
+		}
+
+		let mut gauges = HashMap::new();
+		for &(name, help) in GAUGES {
+			let metric = Gauge::<f64, AtomicU64>::default();
+			registry.register(name, help, metric.clone());
+			gauges.insert(name, metric);
+		}
+
+		let mut histograms = HashMap::new();
+		for &(name, help) in HISTOGRAMS {
+			let (start, factor, length) = DURATION_BUCKETS;
+			let metric = Histogram::new(exponential_buckets(start, factor, length));
+			registry.register(name, help, metric.clone());
+			histograms.insert(name, metric);
+		}
+
+		let cells_fetched = Family::<SourceLabels, Counter>::default();
+		registry.register(
+			"cells_fetched",
+			"Number of sampled cells fetched per source",
+			cells_fetched.clone(),
+		);
+
+		Metrics {
+			registry,
+			counters,
+			gauges,
+			histograms,
+			cells_fetched,
+		}
+	}
+
+	/// Encodes metrics in the OpenMetrics text format.
+	pub fn encode(&self) -> Result<String> {
+		let mut buffer = String::new();
+		encode(&mut buffer, &self.registry).wrap_err("Cannot encode metrics")?;
+		Ok(buffer)
+	}
+}
+
+#[async_trait]
+impl super::Metrics for Metrics {
+	async fn count(&self, counter: MetricCounter) {
+		if let Some(counter) = self.counters.get(&counter.to_string()) {
+			counter.inc();
+		}
+	}
+
+	async fn record(&self, value: MetricValue) -> Result<()> {
+		match instrument(value) {
+			(Instrument::Gauge(name), value) => {
+				let gauge = self
+					.gauges
+					.get(name)
+					.ok_or_else(|| eyre!("Gauge {name} is not registered"))?;
+				gauge.set(value);
+			},
+			(Instrument::Histogram(name), value) => {
+				let histogram = self
+					.histograms
+					.get(name)
+					.ok_or_else(|| eyre!("Histogram {name} is not registered"))?;
+				histogram.observe(value);
+			},
+			(Instrument::CellsFetched(source), value) => {
+				let labels = SourceLabels {
+					source: source.to_string(),
+				};
+				self.cells_fetched
+					.get_or_create(&labels)
+					.inc_by(value as u64);
+			},
+		};
+		Ok(())
+	}
+
+	/// Attributes are not exported, since they are the labels of the scrape target.
+	async fn set_multiaddress(&self, _: String) {}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Metrics;
+	use crate::telemetry::{MetricCounter, MetricValue, Metrics as _};
+
+	#[tokio::test]
+	async fn all_values_are_registered() {
+		let metrics = Metrics::new();
+		for value in [
+			MetricValue::TotalBlockNumber(1),
+			MetricValue::DHTFetched(1.0),
+			MetricValue::DHTFetchedPercentage(1.0),
+			MetricValue::DHTFetchDuration(1.0),
+			MetricValue::NodeRPCFetched(1.0),
+			MetricValue::NodeRPCFetchDuration(1.0),
+			MetricValue::ProofVerificationDuration(1.0),
+			MetricValue::BlockConfidence(1.0),
+			MetricValue::BlockConfidenceTreshold(1.0),
+			MetricValue::EffectiveConfidence(1.0),
+			MetricValue::RPCCallDuration(1.0),
+			MetricValue::RPCRequestDuration(1.0),
+			MetricValue::DHTPutDuration(1.0),
+			MetricValue::DHTPutSuccess(1.0),
+			MetricValue::ConnectedPeersNum(1),
+			MetricValue::HealthCheck(),
+			MetricValue::BlockProcessingDelay(1.0),
+			MetricValue::PingLatency(1.0),
+			MetricValue::ReplicationFactor(1),
+			MetricValue::QueryTimeout(1),
+			MetricValue::SyncLag(1),
+			MetricValue::RuntimeSpecVersion(1),
+		] {
+			assert!(metrics.record(value).await.is_ok());
+		}
+	}
+
+	#[tokio::test]
+	async fn encode_metrics() {
+		let metrics = Metrics::new();
+		metrics.count(MetricCounter::SessionBlock).await;
+		metrics.record(MetricValue::DHTFetched(8.0)).await.unwrap();
+		metrics
+			.record(MetricValue::NodeRPCFetched(2.0))
+			.await
+			.unwrap();
+		metrics.record(MetricValue::DHTFetched(4.0)).await.unwrap();
+		metrics
+			.record(MetricValue::BlockConfidence(93.75))
+			.await
+			.unwrap();
+		metrics
+			.record(MetricValue::RPCRequestDuration(0.01))
+			.await
+			.unwrap();
+
+		let encoded = metrics.encode().unwrap();
+		assert!(encoded.contains("avail_light_session_block_counter_total 1"));
+		assert!(encoded.contains(r#"avail_light_cells_fetched_total{source="dht"} 12"#));
+		assert!(encoded.contains(r#"avail_light_cells_fetched_total{source="rpc"} 2"#));
+		assert!(encoded.contains("avail_light_block_confidence 93.75"));
+		assert!(encoded.contains("avail_light_rpc_request_duration_seconds_count 1"));
+	}
+}
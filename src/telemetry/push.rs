@@ -0,0 +1,139 @@
+//! Push of the Prometheus metrics to the Pushgateway, for clients which cannot be scraped (e.g. behind NAT).
+
+use base64::{engine::general_purpose, Engine};
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use hyper::{header, Body, Client, Method, Request, Uri};
+use std::{sync::Arc, time::Duration};
+use tracing::{debug, info, warn};
+
+use super::prometheus::Metrics;
+use crate::types::RuntimeConfig;
+
+const JOB: &str = "avail_light";
+
+/// Metrics push configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct PushConfig {
+	pub endpoint: Option<String>,
+	pub interval: Duration,
+	pub username: Option<String>,
+	pub password: Option<String>,
+}
+
+impl From<&RuntimeConfig> for PushConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		PushConfig {
+			endpoint: val.prometheus_push_endpoint.clone(),
+			interval: Duration::from_secs(val.prometheus_push_interval.max(1)),
+			username: val.prometheus_push_username.clone(),
+			password: val.prometheus_push_password.clone(),
+		}
+	}
+}
+
+/// Labels of the pushed metrics group.
+#[derive(Clone, Debug)]
+pub struct PushLabels {
+	pub node_id: String,
+	pub network: String,
+	pub role: String,
+}
+
+/// Pushgateway URL of the metrics group.
+fn url(endpoint: &str, labels: &PushLabels) -> String {
+	format!(
+		"{endpoint}/metrics/job/{JOB}/node_id/{}/network/{}/role/{}",
+		labels.node_id,
+		labels.network,
+		labels.role,
+		endpoint = endpoint.trim_end_matches('/'),
+	)
+}
+
+/// Value of the basic auth `Authorization` header.
+fn authorization(username: &str, password: Option<&str>) -> String {
+	let credentials = format!("{username}:{}", password.unwrap_or_default());
+	format!("Basic {}", general_purpose::STANDARD.encode(credentials))
+}
+
+async fn push(
+	client: &Client<hyper::client::HttpConnector>,
+	uri: &Uri,
+	cfg: &PushConfig,
+	metrics: &Metrics,
+) -> Result<()> {
+	let mut request = Request::builder()
+		.method(Method::PUT)
+		.uri(uri)
+		.header(header::CONTENT_TYPE, "text/plain; version=0.0.4");
+	if let Some(username) = &cfg.username {
+		let authorization = authorization(username, cfg.password.as_deref());
+		request = request.header(header::AUTHORIZATION, authorization);
+	}
+	let request = request
+		.body(Body::from(metrics.encode()?))
+		.wrap_err("Cannot create metrics push request")?;
+
+	let response = client
+		.request(request)
+		.await
+		.wrap_err("Metrics push request failed")?;
+	if !response.status().is_success() {
+		return Err(eyre!(
+			"Pushgateway responded with status {}",
+			response.status()
+		));
+	}
+	Ok(())
+}
+
+/// Pushes metrics to the Pushgateway on each interval.
+pub async fn run(metrics: Arc<Metrics>, cfg: PushConfig, labels: PushLabels) -> Result<()> {
+	let Some(endpoint) = cfg.endpoint.as_deref() else {
+		return Ok(());
+	};
+	let uri: Uri = url(endpoint, &labels)
+		.parse()
+		.wrap_err("Invalid metrics push endpoint")?;
+	info!("Pushing metrics to {endpoint} every {:?}", cfg.interval);
+
+	let client = Client::new();
+	let mut interval = tokio::time::interval(cfg.interval);
+	loop {
+		interval.tick().await;
+		match push(&client, &uri, &cfg, &metrics).await {
+			Ok(()) => debug!("Metrics pushed to {endpoint}"),
+			Err(error) => warn!("Cannot push metrics: {error:#}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{authorization, url, PushLabels};
+
+	#[test]
+	fn push_url_contains_labels() {
+		let labels = PushLabels {
+			node_id: "12D3KooW".to_string(),
+			network: "d3d24b".to_string(),
+			role: "lightnode".to_string(),
+		};
+		assert_eq!(
+			url("http://127.0.0.1:9091/", &labels),
+			"http://127.0.0.1:9091/metrics/job/avail_light/node_id/12D3KooW/network/d3d24b/role/lightnode"
+		);
+	}
+
+	#[test]
+	fn basic_auth_is_encoded() {
+		assert_eq!(
+			authorization("user", Some("secret")),
+			"Basic dXNlcjpzZWNyZXQ="
+		);
+		assert_eq!(authorization("user", None), "Basic dXNlcjo=");
+	}
+}
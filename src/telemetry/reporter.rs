@@ -0,0 +1,344 @@
+//! Reporting of the anonymized client statistics to the telemetry backend, similar to the full node telemetry.
+
+use async_trait::async_trait;
+use chrono::Utc;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Result,
+};
+use futures::{AsyncRead, AsyncWrite};
+use serde::Serialize;
+use soketto::connection;
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tracing::{debug, info, warn};
+use url::Url;
+use uuid::Uuid;
+
+use super::{MetricCounter, MetricValue};
+use crate::{
+	network::{
+		proxy::Proxy,
+		rpc::{self, TransportConfig},
+	},
+	types::RuntimeConfig,
+};
+
+const IMPLEMENTATION: &str = "avail-light";
+
+/// Telemetry reporter configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct ReporterConfig {
+	pub endpoint: Option<String>,
+	pub interval: Duration,
+	pub proxy: Option<Proxy>,
+}
+
+impl From<&RuntimeConfig> for ReporterConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		ReporterConfig {
+			endpoint: val.telemetry_endpoint.clone(),
+			interval: Duration::from_secs(val.telemetry_interval.max(1)),
+			proxy: val.proxy.clone(),
+		}
+	}
+}
+
+/// Client reported on connect.
+#[derive(Clone, Debug, Serialize)]
+pub struct ClientInfo {
+	pub version: String,
+	pub role: String,
+	/// Genesis hash of the network
+	pub genesis_hash: String,
+}
+
+/// Statistics reported on each interval.
+#[derive(Clone, Debug, Default, Serialize, PartialEq)]
+pub struct IntervalStats {
+	/// Latest processed block number
+	pub height: Option<u32>,
+	/// Number of blocks processed since the previous report
+	pub blocks: u32,
+	/// Ratio of the processed blocks which achieved the confidence threshold
+	pub confidence_rate: Option<f64>,
+	/// Average ratio of the sampled cells fetched from the DHT
+	pub dht_get_success_ratio: Option<f64>,
+	/// Average ratio of the successfully stored DHT records
+	pub dht_put_success_ratio: Option<f64>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "msg")]
+enum Payload<'a> {
+	#[serde(rename = "system.connected")]
+	Connected {
+		implementation: &'static str,
+		#[serde(flatten)]
+		client: &'a ClientInfo,
+	},
+	#[serde(rename = "system.interval")]
+	Interval(&'a IntervalStats),
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+	id: Uuid,
+	ts: String,
+	payload: Payload<'a>,
+}
+
+fn encode(session_id: Uuid, payload: Payload) -> Result<String> {
+	let message = Message {
+		id: session_id,
+		ts: Utc::now().to_rfc3339(),
+		payload,
+	};
+	serde_json::to_string(&message).wrap_err("Cannot encode telemetry message")
+}
+
+/// Sum and count of the recorded ratios, averaged on report
+#[derive(Default)]
+struct Average {
+	sum: f64,
+	count: u32,
+}
+
+impl Average {
+	fn add(&mut self, value: f64) {
+		self.sum += value;
+		self.count += 1;
+	}
+
+	fn value(&self) -> Option<f64> {
+		(self.count > 0).then(|| self.sum / self.count as f64)
+	}
+}
+
+#[derive(Default)]
+struct Collected {
+	height: Option<u32>,
+	blocks: u32,
+	confident_blocks: u32,
+	dht_get: Average,
+	dht_put: Average,
+}
+
+/// Metrics backend which collects statistics reported to the telemetry backend.
+pub struct Stats {
+	confidence_threshold: f64,
+	collected: Mutex<Collected>,
+}
+
+impl Stats {
+	pub fn new(confidence_threshold: f64) -> Self {
+		Stats {
+			confidence_threshold,
+			collected: Mutex::new(Collected::default()),
+		}
+	}
+
+	/// Returns statistics collected since the previous call, only the height is kept.
+	pub fn take(&self) -> IntervalStats {
+		let mut collected = self.collected.lock().expect("Lock should be acquired");
+		let height = collected.height;
+		let collected = std::mem::replace(
+			&mut *collected,
+			Collected {
+				height,
+				..Default::default()
+			},
+		);
+		IntervalStats {
+			height: collected.height,
+			blocks: collected.blocks,
+			confidence_rate: (collected.blocks > 0)
+				.then(|| collected.confident_blocks as f64 / collected.blocks as f64),
+			dht_get_success_ratio: collected.dht_get.value(),
+			dht_put_success_ratio: collected.dht_put.value(),
+		}
+	}
+}
+
+#[async_trait]
+impl super::Metrics for Stats {
+	async fn count(&self, counter: MetricCounter) {
+		if let MetricCounter::SessionBlock = counter {
+			self.collected
+				.lock()
+				.expect("Lock should be acquired")
+				.blocks += 1;
+		}
+	}
+
+	async fn record(&self, value: MetricValue) -> Result<()> {
+		let mut collected = self.collected.lock().expect("Lock should be acquired");
+		match value {
+			MetricValue::TotalBlockNumber(number) => {
+				collected.height = collected.height.max(Some(number));
+			},
+			MetricValue::BlockConfidence(confidence) if confidence >= self.confidence_threshold => {
+				collected.confident_blocks += 1;
+			},
+			MetricValue::DHTFetchedPercentage(ratio) => collected.dht_get.add(ratio),
+			MetricValue::DHTPutSuccess(ratio) => collected.dht_put.add(ratio),
+			_ => (),
+		}
+		Ok(())
+	}
+
+	async fn set_multiaddress(&self, _: String) {}
+}
+
+async fn send<T>(sender: &mut connection::Sender<T>, message: &str) -> Result<(), connection::Error>
+where
+	T: AsyncRead + AsyncWrite + Unpin,
+{
+	sender.send_text(message).await?;
+	sender.flush().await
+}
+
+/// Reports collected statistics to the telemetry backend on each interval.
+pub async fn run(stats: Arc<Stats>, cfg: ReporterConfig, client: ClientInfo) -> Result<()> {
+	let Some(endpoint) = cfg.endpoint.as_deref() else {
+		return Ok(());
+	};
+	let url = Url::parse(endpoint).wrap_err("Invalid telemetry endpoint")?;
+	if !matches!(url.scheme(), "ws" | "wss") {
+		return Err(eyre!(
+			"Telemetry endpoint must be a WebSocket URL, got {endpoint}"
+		));
+	}
+	// Client certificates of the RPC nodes are not presented to the telemetry backend
+	let transport_cfg = TransportConfig {
+		proxy: cfg.proxy.clone(),
+		..Default::default()
+	};
+	let tls = Arc::new(rpc::tls_client_config(&transport_cfg)?);
+	info!("Reporting telemetry to {endpoint} every {:?}", cfg.interval);
+
+	let session_id = Uuid::new_v4();
+	let connected = encode(
+		session_id,
+		Payload::Connected {
+			implementation: IMPLEMENTATION,
+			client: &client,
+		},
+	)?;
+
+	let mut connection = None;
+	let mut interval = tokio::time::interval(cfg.interval);
+	loop {
+		interval.tick().await;
+		if connection.is_none() {
+			match rpc::websocket(&url, None, tls.clone(), cfg.proxy.as_ref()).await {
+				Ok((mut sender, _)) => match send(&mut sender, &connected).await {
+					Ok(()) => {
+						// Statistics collected while disconnected are discarded
+						stats.take();
+						connection = Some(sender);
+					},
+					Err(error) => warn!("Cannot send telemetry: {error}"),
+				},
+				Err(error) => warn!("Cannot connect to telemetry endpoint: {error:#}"),
+			}
+		}
+		let Some(sender) = connection.as_mut() else {
+			continue;
+		};
+
+		let message = encode(session_id, Payload::Interval(&stats.take()))?;
+		match send(sender, &message).await {
+			Ok(()) => debug!("Telemetry reported to {endpoint}"),
+			Err(error) => {
+				warn!("Cannot send telemetry: {error}");
+				connection = None;
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::{json, Value};
+	use uuid::Uuid;
+
+	use super::{encode, ClientInfo, IntervalStats, Payload, Stats};
+	use crate::telemetry::{MetricCounter, MetricValue, Metrics};
+
+	#[tokio::test]
+	async fn stats_are_collected_per_interval() {
+		let stats = Stats::new(99.9);
+		for (number, confidence) in [(10, 99.95), (11, 93.75)] {
+			stats.count(MetricCounter::SessionBlock).await;
+			stats
+				.record(MetricValue::TotalBlockNumber(number))
+				.await
+				.unwrap();
+			stats
+				.record(MetricValue::BlockConfidence(confidence))
+				.await
+				.unwrap();
+		}
+		for ratio in [1.0, 0.5] {
+			stats
+				.record(MetricValue::DHTFetchedPercentage(ratio))
+				.await
+				.unwrap();
+		}
+
+		assert_eq!(
+			stats.take(),
+			IntervalStats {
+				height: Some(11),
+				blocks: 2,
+				confidence_rate: Some(0.5),
+				dht_get_success_ratio: Some(0.75),
+				dht_put_success_ratio: None,
+			}
+		);
+		assert_eq!(
+			stats.take(),
+			IntervalStats {
+				height: Some(11),
+				..Default::default()
+			}
+		);
+	}
+
+	#[test]
+	fn messages_are_tagged() {
+		let client = ClientInfo {
+			version: "1.8.0".to_string(),
+			role: "lightnode".to_string(),
+			genesis_hash: "0x01".to_string(),
+		};
+		let message = encode(
+			Uuid::nil(),
+			Payload::Connected {
+				implementation: "avail-light",
+				client: &client,
+			},
+		)
+		.unwrap();
+		let message: Value = serde_json::from_str(&message).unwrap();
+		assert_eq!(message["id"], json!(Uuid::nil()));
+		assert_eq!(
+			message["payload"],
+			json!({
+				"msg": "system.connected",
+				"implementation": "avail-light",
+				"version": "1.8.0",
+				"role": "lightnode",
+				"genesis_hash": "0x01",
+			})
+		);
+
+		let message = encode(Uuid::nil(), Payload::Interval(&IntervalStats::default())).unwrap();
+		let message: Value = serde_json::from_str(&message).unwrap();
+		assert_eq!(message["payload"]["msg"], "system.interval");
+		assert_eq!(message["payload"]["blocks"], 0);
+	}
+}
@@ -20,6 +20,7 @@ use kate_recovery::{
 	data::Cell,
 	matrix::{Dimensions, Position},
 };
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn decode_app_data(data: &[u8]) -> Result<Option<Vec<u8>>> {
 	let extrisic: AppUncheckedExtrinsic =
@@ -31,6 +32,14 @@ pub fn decode_app_data(data: &[u8]) -> Result<Option<Vec<u8>>> {
 	}
 }
 
+/// Returns current unix timestamp, in seconds
+pub(crate) fn unix_timestamp() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|duration| duration.as_secs())
+		.unwrap_or_default()
+}
+
 /// Calculates confidence from given number of verified cells
 pub fn calculate_confidence(count: u32) -> f64 {
 	100f64 * (1f64 - 1f64 / 2u32.pow(count) as f64)
@@ -68,6 +77,15 @@ pub(crate) fn extract_app_lookup(
 	DataLookup::try_from(compact)
 }
 
+/// Returns IDs of the applications with data in the block, in the order of their data in the matrix.
+pub(crate) fn extract_app_ids(extension: &HeaderExtension) -> Vec<u32> {
+	match &extension {
+		HeaderExtension::V3(v3::HeaderExtension { app_lookup, .. }) => {
+			app_lookup.index.iter().map(|item| item.app_id.0).collect()
+		},
+	}
+}
+
 pub fn filter_auth_set_changes(header: &DaHeader) -> Vec<Vec<(AuthorityId, u64)>> {
 	let new_auths = header
 		.digest
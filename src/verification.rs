@@ -0,0 +1,126 @@
+//! Verification of the header extension commitments against the sampled data.
+
+use codec::{Decode, Encode};
+use color_eyre::{eyre::WrapErr, Result};
+use kate_recovery::{config::COMMITMENT_SIZE, matrix::Dimensions};
+use serde::{Deserialize, Serialize};
+use sp_core::H256;
+use std::{
+	fmt::{self, Display},
+	sync::Arc,
+};
+use tokio::sync::broadcast;
+use tracing::{debug, warn};
+
+use crate::{
+	data::{CellSource, Database, Key, SampledCell},
+	telemetry::{MetricCounter, Metrics},
+};
+
+/// Reason of the mismatch between the header extension and the sampled data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum MismatchReason {
+	/// Number of the commitments differs from the number of the extended matrix rows
+	CommitmentCount { expected: u32, actual: u32 },
+	/// Cells fetched from RPC failed the proof verification against the commitments
+	UnverifiedCells { cells: u32 },
+}
+
+impl Display for MismatchReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MismatchReason::CommitmentCount { expected, actual } => {
+				write!(f, "expected {expected} commitments, found {actual}")
+			},
+			MismatchReason::UnverifiedCells { cells } => {
+				write!(f, "{cells} cells fetched from RPC failed verification")
+			},
+		}
+	}
+}
+
+/// Event emitted when the header extension of the block does not match the sampled data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct CommitmentMismatch {
+	pub block_number: u32,
+	pub block_hash: H256,
+	#[serde(flatten)]
+	pub reason: MismatchReason,
+}
+
+/// Checks that the header extension contains commitment for each row of the extended matrix.
+pub fn check_commitments(
+	dimensions: Dimensions,
+	commitments: &[[u8; COMMITMENT_SIZE]],
+) -> Option<MismatchReason> {
+	let expected = dimensions.extended_rows();
+	let actual = commitments.len() as u32;
+	(actual != expected).then_some(MismatchReason::CommitmentCount { expected, actual })
+}
+
+/// Checks that none of the cells fetched from RPC failed the proof verification against the commitments.
+pub fn check_sampled_cells(cells: &[SampledCell]) -> Option<MismatchReason> {
+	let unverified = cells
+		.iter()
+		.filter(|cell| cell.source == Some(CellSource::Rpc) && !cell.verified)
+		.count() as u32;
+	(unverified > 0).then_some(MismatchReason::UnverifiedCells { cells: unverified })
+}
+
+/// Stores, counts and publishes the commitment mismatch of the block.
+pub async fn report(
+	db: &impl Database,
+	metrics: &Arc<impl Metrics>,
+	sender: &broadcast::Sender<CommitmentMismatch>,
+	mismatch: CommitmentMismatch,
+) -> Result<()> {
+	let block_number = mismatch.block_number;
+	warn!(
+		block_number,
+		"Header extension does not match sampled data, block is unavailable: {}", mismatch.reason
+	);
+	metrics.count(MetricCounter::CommitmentMismatch).await;
+	db.put(Key::CommitmentMismatch(block_number), mismatch.clone())
+		.wrap_err("Failed to store commitment mismatch")?;
+	// Sending fails if there are no subscribers, which is expected if the API is not used
+	if let Err(error) = sender.send(mismatch) {
+		debug!(
+			block_number,
+			"Commitment mismatch is not published: {error}"
+		);
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use kate_recovery::matrix::Dimensions;
+	use test_case::test_case;
+
+	use super::{check_commitments, check_sampled_cells, MismatchReason};
+	use crate::data::{CellSource, SampledCell};
+
+	fn cell(source: Option<CellSource>, verified: bool) -> SampledCell {
+		SampledCell {
+			row: 0,
+			col: 0,
+			source,
+			verified,
+		}
+	}
+
+	#[test_case(4 => None ; "Commitment for each extended row")]
+	#[test_case(2 => Some(MismatchReason::CommitmentCount { expected: 4, actual: 2 }) ; "Missing commitments")]
+	fn test_check_commitments(count: usize) -> Option<MismatchReason> {
+		let dimensions = Dimensions::new(2, 4).unwrap();
+		check_commitments(dimensions, &vec![[0u8; 48]; count])
+	}
+
+	#[test_case(vec![cell(Some(CellSource::Dht), true), cell(None, false)] => None ; "Unfetched cell")]
+	#[test_case(vec![cell(Some(CellSource::Dht), false)] => None ; "Unverified DHT cell")]
+	#[test_case(vec![cell(Some(CellSource::Rpc), false), cell(Some(CellSource::Rpc), false)] => Some(MismatchReason::UnverifiedCells { cells: 2 }) ; "Unverified RPC cells")]
+	fn test_check_sampled_cells(cells: Vec<SampledCell>) -> Option<MismatchReason> {
+		check_sampled_cells(&cells)
+	}
+}
@@ -0,0 +1,180 @@
+//! Watchdog of the block processing, for the blocks processed by the light client and the backfill.
+
+use avail_subxt::primitives::Header;
+use color_eyre::{eyre::WrapErr, Result};
+use std::{
+	sync::{Arc, Mutex},
+	time::Instant,
+};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+use crate::{
+	backfill,
+	data::{BlockFailure, BlockSamplingStats, Database, FailureReason, Key},
+	light_client, network,
+	telemetry::Metrics,
+	types::{BlockRange, LightClientConfig, State},
+	verification::CommitmentMismatch,
+};
+
+/// Classifies the processing of the block which didn't achieve confidence, from its stored sampling outcome.
+/// Returns `None` if the block is skipped (e.g. because of invalid dimensions).
+pub fn classify(db: &impl Database, block_number: u32) -> Result<Option<FailureReason>> {
+	if db
+		.get::<CommitmentMismatch>(Key::CommitmentMismatch(block_number))?
+		.is_some()
+	{
+		return Ok(Some(FailureReason::ProofInvalid));
+	}
+	let Some(stats) = db.get::<BlockSamplingStats>(Key::BlockSamplingStats(block_number))? else {
+		return Ok(None);
+	};
+	if stats.cells.iter().any(|cell| cell.source.is_none()) {
+		return Ok(Some(FailureReason::DhtMiss));
+	}
+	let is_invalid = stats.cells.iter().any(|cell| !cell.verified);
+	Ok(is_invalid.then_some(FailureReason::ProofInvalid))
+}
+
+/// Stores the failure of the block, and schedules it for the backfill if attempts are left.
+fn fail(
+	db: &impl Database,
+	cfg: &LightClientConfig,
+	state: &Mutex<State>,
+	block_number: u32,
+	reason: FailureReason,
+) -> Result<()> {
+	let attempts = db
+		.get::<BlockFailure>(Key::BlockFailure(block_number))?
+		.map_or(0, |failure| failure.attempts)
+		+ 1;
+	db.put(
+		Key::BlockFailure(block_number),
+		BlockFailure { reason, attempts },
+	)
+	.wrap_err("Failed to store block failure")?;
+
+	if attempts >= cfg.max_processing_attempts {
+		warn!(
+			block_number,
+			"Block processing failed ({reason}), after {attempts} attempts"
+		);
+		return Ok(());
+	}
+	let max_attempts = cfg.max_processing_attempts;
+	info!(
+		block_number,
+		"Block processing failed ({reason}), attempt {attempts} of {max_attempts}, block is processed again later"
+	);
+	let gap = BlockRange {
+		first: block_number,
+		last: block_number,
+	};
+	backfill::insert(&mut state.lock().unwrap().missed, gap);
+	Ok(())
+}
+
+/// Processes the block within the processing timeout, storing and classifying the failure.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_block(
+	db: impl Database + Clone,
+	network_client: &impl network::Client,
+	metrics: &Arc<impl Metrics>,
+	cfg: &LightClientConfig,
+	cell_count: u32,
+	header: Header,
+	received_at: Instant,
+	state: Arc<Mutex<State>>,
+	commitment_mismatch_sender: &broadcast::Sender<CommitmentMismatch>,
+) -> Result<Option<f64>> {
+	let block_number = header.number;
+	let processing = light_client::process_block(
+		db.clone(),
+		network_client,
+		metrics,
+		cfg,
+		cell_count,
+		header,
+		received_at,
+		state.clone(),
+		commitment_mismatch_sender,
+	);
+
+	let reason = match tokio::time::timeout(cfg.processing_timeout, processing).await {
+		Err(_) => Some(FailureReason::RpcTimeout),
+		Ok(Ok(Some(confidence))) => {
+			db.delete(Key::BlockFailure(block_number))?;
+			return Ok(Some(confidence));
+		},
+		Ok(Ok(None)) => classify(&db, block_number)?,
+		Ok(Err(error)) => return Err(error),
+	};
+
+	if let Some(reason) = reason {
+		fail(&db, cfg, &state, block_number, reason)?;
+	}
+	Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Mutex;
+	use test_case::test_case;
+
+	use super::{classify, fail};
+	use crate::{
+		data::{
+			mem_db::MemoryDB, BlockFailure, BlockSamplingStats, CellSource, Database,
+			FailureReason, Key, SampledCell, SamplingTimings,
+		},
+		types::{LightClientConfig, RuntimeConfig, State},
+	};
+
+	fn cell(source: Option<CellSource>, verified: bool) -> SampledCell {
+		SampledCell {
+			row: 0,
+			col: 0,
+			source,
+			verified,
+		}
+	}
+
+	#[test_case(vec![cell(Some(CellSource::Dht), true), cell(None, false)] => Some(FailureReason::DhtMiss) ; "Unfetched cell")]
+	#[test_case(vec![cell(Some(CellSource::Rpc), false)] => Some(FailureReason::ProofInvalid) ; "Unverified cell")]
+	#[test_case(vec![cell(Some(CellSource::Dht), true)] => None ; "Verified cells")]
+	fn test_classify(cells: Vec<SampledCell>) -> Option<FailureReason> {
+		let db = MemoryDB::default();
+		let stats = BlockSamplingStats {
+			cells,
+			timings: SamplingTimings::default(),
+		};
+		db.put(Key::BlockSamplingStats(1), stats).unwrap();
+		classify(&db, 1).unwrap()
+	}
+
+	#[test]
+	fn failed_block_is_attempted_again_until_limit() {
+		let db = MemoryDB::default();
+		let cfg = LightClientConfig {
+			max_processing_attempts: 2,
+			..LightClientConfig::from(&RuntimeConfig::default())
+		};
+		let state = Mutex::new(State::default());
+
+		fail(&db, &cfg, &state, 5, FailureReason::DhtMiss).unwrap();
+		assert_eq!(state.lock().unwrap().missed.len(), 1);
+		state.lock().unwrap().missed.clear();
+
+		fail(&db, &cfg, &state, 5, FailureReason::RpcTimeout).unwrap();
+		assert!(state.lock().unwrap().missed.is_empty());
+		let failure = db.get::<BlockFailure>(Key::BlockFailure(5)).unwrap();
+		assert_eq!(
+			failure,
+			Some(BlockFailure {
+				reason: FailureReason::RpcTimeout,
+				attempts: 2
+			})
+		);
+	}
+}
@@ -0,0 +1,369 @@
+//! Webhook notifications of the achieved confidence and the stored application data, so external systems don't need to poll the API.
+
+use avail_core::AppId;
+use color_eyre::{
+	eyre::{eyre, WrapErr},
+	Report, Result,
+};
+use futures::{future::join_all, StreamExt};
+use hmac::{Hmac, Mac};
+use hyper::{client::HttpConnector, header, Body, Client, Method, Request, StatusCode, Uri};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use kate_recovery::com::AppData;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{fmt::Display, time::Duration};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+	api::v2::types::{PublishMessage, Topic},
+	retry::{RetryPolicy, Retryable},
+	types::{BlockVerified, RuntimeConfig},
+	utils::unix_timestamp,
+};
+
+/// Header with the unix timestamp of the delivery, in seconds
+pub const TIMESTAMP_HEADER: &str = "x-avail-timestamp";
+/// Header with the `sha256={hex}` HMAC of the `{timestamp}.{payload}` message
+pub const SIGNATURE_HEADER: &str = "x-avail-signature";
+
+const TOPICS: [Topic; 2] = [Topic::ConfidenceAchieved, Topic::DataVerified];
+
+type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+/// Configured webhook (see [RuntimeConfig] for details)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+	/// URL to which the events are posted
+	pub url: String,
+	/// Topics of the posted events, all supported topics if empty
+	#[serde(default)]
+	pub topics: Vec<Topic>,
+	/// Applications whose stored data is posted, all followed applications if empty
+	#[serde(default)]
+	pub app_ids: Vec<u32>,
+}
+
+/// Webhooks configuration (see [RuntimeConfig] for details)
+#[derive(Clone, Debug)]
+pub struct WebhookConfig {
+	pub webhooks: Vec<Webhook>,
+	pub secret: Option<String>,
+	pub timeout: Duration,
+	pub retry_policy: RetryPolicy,
+}
+
+impl From<&RuntimeConfig> for WebhookConfig {
+	fn from(val: &RuntimeConfig) -> Self {
+		WebhookConfig {
+			webhooks: val.webhooks.clone(),
+			secret: val.webhook_secret.clone(),
+			timeout: Duration::from_secs(val.webhook_timeout.max(1)),
+			retry_policy: val.retry_policies.webhook.clone(),
+		}
+	}
+}
+
+/// Checks configured webhooks, so the invalid webhooks are rejected once the configuration is loaded.
+pub fn check(webhooks: &[Webhook]) -> Result<()> {
+	for webhook in webhooks {
+		let uri: Uri = webhook
+			.url
+			.parse()
+			.wrap_err_with(|| format!("Invalid webhook URL {}", webhook.url))?;
+		if !matches!(uri.scheme_str(), Some("http" | "https")) {
+			return Err(eyre!(
+				"Webhook URL {} is not supported, only HTTP and HTTPS endpoints are supported",
+				webhook.url
+			));
+		}
+		if let Some(topic) = webhook.topics.iter().find(|topic| !TOPICS.contains(topic)) {
+			return Err(eyre!(
+				"Webhook topic {topic:?} is not supported, supported topics are {TOPICS:?}"
+			));
+		}
+	}
+	Ok(())
+}
+
+#[derive(Clone, Debug)]
+enum Event {
+	ConfidenceAchieved(BlockVerified),
+	DataVerified(AppId, u32, AppData),
+}
+
+impl Event {
+	fn topic(&self) -> Topic {
+		match self {
+			Event::ConfidenceAchieved(_) => Topic::ConfidenceAchieved,
+			Event::DataVerified(..) => Topic::DataVerified,
+		}
+	}
+}
+
+impl TryFrom<Event> for PublishMessage {
+	type Error = Report;
+
+	fn try_from(value: Event) -> Result<Self, Self::Error> {
+		match value {
+			Event::ConfidenceAchieved(block) => block.try_into(),
+			Event::DataVerified(app_id, block_number, data) => {
+				(app_id, block_number, data).try_into()
+			},
+		}
+	}
+}
+
+impl Webhook {
+	fn matches(&self, event: &Event) -> bool {
+		let topic = event.topic();
+		let app_matches = match event {
+			Event::DataVerified(app_id, ..) => {
+				self.app_ids.is_empty() || self.app_ids.contains(&app_id.0)
+			},
+			Event::ConfidenceAchieved(_) => true,
+		};
+		(self.topics.is_empty() || self.topics.contains(&topic)) && app_matches
+	}
+}
+
+/// HMAC-SHA256 of the `{timestamp}.{payload}` message.
+fn mac(secret: &str, timestamp: u64, payload: &[u8]) -> Hmac<Sha256> {
+	let mut mac =
+		Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+	mac.update(format!("{timestamp}.").as_bytes());
+	mac.update(payload);
+	mac
+}
+
+/// Value of the signature header, signed message includes the timestamp, so the deliveries cannot be replayed later.
+fn signature(secret: &str, timestamp: u64, payload: &[u8]) -> String {
+	let signature = mac(secret, timestamp, payload).finalize().into_bytes();
+	format!("sha256={}", hex::encode(signature))
+}
+
+/// Checks the value of the signature header of the delivery, in constant time.
+pub fn verify_signature(secret: &str, timestamp: u64, payload: &[u8], signature: &str) -> bool {
+	let Some(signature) = signature
+		.strip_prefix("sha256=")
+		.and_then(|signature| hex::decode(signature).ok())
+	else {
+		return false;
+	};
+	mac(secret, timestamp, payload)
+		.verify_slice(&signature)
+		.is_ok()
+}
+
+#[derive(Debug)]
+enum DeliveryError {
+	/// Webhook rejected the payload, delivery would be rejected again
+	Rejected(StatusCode),
+	Failed(Report),
+}
+
+impl Display for DeliveryError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			DeliveryError::Rejected(status) => {
+				write!(f, "Webhook rejected the event with status {status}")
+			},
+			DeliveryError::Failed(error) => write!(f, "{error:#}"),
+		}
+	}
+}
+
+impl Retryable for DeliveryError {
+	fn is_retryable(&self) -> bool {
+		matches!(self, DeliveryError::Failed(_))
+	}
+}
+
+async fn deliver(
+	client: &HttpsClient,
+	uri: &Uri,
+	cfg: &WebhookConfig,
+	topic: &Topic,
+	payload: &[u8],
+) -> Result<(), DeliveryError> {
+	let timestamp = unix_timestamp();
+	let mut request = Request::builder()
+		.method(Method::POST)
+		.uri(uri)
+		.header(header::CONTENT_TYPE, "application/json")
+		.header(TIMESTAMP_HEADER, timestamp);
+	if let Some(secret) = &cfg.secret {
+		request = request.header(SIGNATURE_HEADER, signature(secret, timestamp, payload));
+	}
+	let request = request
+		.body(Body::from(payload.to_vec()))
+		.map_err(|error| DeliveryError::Failed(Report::new(error)))?;
+
+	debug!(%uri, ?topic, "Posting event to the webhook");
+	let response = tokio::time::timeout(cfg.timeout, client.request(request))
+		.await
+		.map_err(|_| DeliveryError::Failed(eyre!("Webhook request timed out")))?
+		.map_err(|error| DeliveryError::Failed(Report::new(error)))?;
+
+	match response.status() {
+		status if status.is_success() => Ok(()),
+		status @ (StatusCode::REQUEST_TIMEOUT | StatusCode::TOO_MANY_REQUESTS) => Err(
+			DeliveryError::Failed(eyre!("Webhook responded with status {status}")),
+		),
+		status if status.is_client_error() => Err(DeliveryError::Rejected(status)),
+		status => Err(DeliveryError::Failed(eyre!(
+			"Webhook responded with status {status}"
+		))),
+	}
+}
+
+async fn serve(
+	webhook: Webhook,
+	cfg: &WebhookConfig,
+	client: &HttpsClient,
+	blocks: broadcast::Receiver<BlockVerified>,
+	data: broadcast::Receiver<(AppId, u32, AppData)>,
+) {
+	let uri: Uri = match webhook.url.parse() {
+		Ok(uri) => uri,
+		Err(error) => {
+			error!("Invalid webhook URL {}: {error}", webhook.url);
+			return;
+		},
+	};
+
+	let blocks = BroadcastStream::new(blocks).map(|block| block.map(Event::ConfidenceAchieved));
+	let data = BroadcastStream::new(data).map(|data| {
+		data.map(|(app_id, block_number, data)| Event::DataVerified(app_id, block_number, data))
+	});
+	let mut events = futures::stream::select(blocks, data);
+
+	while let Some(event) = events.next().await {
+		let event = match event {
+			Ok(event) if webhook.matches(&event) => event,
+			Ok(_) => continue,
+			Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+				warn!(%uri, "Webhook is lagging behind, {skipped} events are dropped");
+				continue;
+			},
+		};
+
+		let topic = event.topic();
+		let payload = PublishMessage::try_from(event)
+			.and_then(|message| serde_json::to_vec(&message).map_err(Report::new));
+		let payload = match payload {
+			Ok(payload) => payload,
+			Err(error) => {
+				error!(%uri, ?topic, "Cannot create webhook payload: {error:#}");
+				continue;
+			},
+		};
+
+		let delivery = cfg
+			.retry_policy
+			.retry("webhook", || deliver(client, &uri, cfg, &topic, &payload))
+			.await;
+		if let Err(error) = delivery {
+			// Dead letter contains the whole payload, so the event can be delivered manually
+			error!(
+				%uri,
+				?topic,
+				payload = %String::from_utf8_lossy(&payload),
+				"Webhook delivery failed, event is dropped: {error}"
+			);
+		}
+	}
+}
+
+/// Posts events to the configured webhooks, until the event channels are closed.
+pub async fn run(
+	cfg: WebhookConfig,
+	block_sender: broadcast::Sender<BlockVerified>,
+	data_sender: broadcast::Sender<(AppId, u32, AppData)>,
+) {
+	if cfg.webhooks.is_empty() {
+		return;
+	}
+	info!("Posting events to {} webhooks", cfg.webhooks.len());
+
+	let connector = HttpsConnectorBuilder::new()
+		.with_webpki_roots()
+		.https_or_http()
+		.enable_http1()
+		.build();
+	let client = Client::builder().build(connector);
+	let webhooks = cfg.webhooks.iter().cloned().map(|webhook| {
+		let (blocks, data) = (block_sender.subscribe(), data_sender.subscribe());
+		serve(webhook, &cfg, &client, blocks, data)
+	});
+	join_all(webhooks).await;
+}
+
+#[cfg(test)]
+mod tests {
+	use avail_core::AppId;
+	use test_case::test_case;
+
+	use super::{check, signature, verify_signature, Event, Webhook};
+	use crate::api::v2::types::Topic;
+
+	fn webhook(url: &str, topics: Vec<Topic>, app_ids: Vec<u32>) -> Webhook {
+		Webhook {
+			url: url.to_string(),
+			topics,
+			app_ids,
+		}
+	}
+
+	#[test_case("http://127.0.0.1:8080/events", vec![] => true ; "all topics")]
+	#[test_case("http://127.0.0.1:8080/events", vec![Topic::DataVerified] => true ; "supported topic")]
+	#[test_case("http://127.0.0.1:8080/events", vec![Topic::HeaderVerified] => false ; "unsupported topic")]
+	#[test_case("https://example.com/events", vec![] => true ; "https")]
+	#[test_case("ws://example.com/events", vec![] => false ; "unsupported scheme")]
+	#[test_case("not a url", vec![] => false ; "invalid url")]
+	fn test_check(url: &str, topics: Vec<Topic>) -> bool {
+		check(&[webhook(url, topics, vec![])]).is_ok()
+	}
+
+	#[test]
+	fn payload_is_signed() {
+		let signed = signature("secret", 1700000000, br#"{"topic":"data-verified"}"#);
+		assert!(signed.starts_with("sha256="));
+		assert_eq!(signed.len(), "sha256=".len() + 64);
+		assert_ne!(
+			signed,
+			signature("secret", 1700000001, br#"{"topic":"data-verified"}"#)
+		);
+
+		let payload = br#"{"topic":"data-verified"}"#;
+		assert!(verify_signature("secret", 1700000000, payload, &signed));
+		assert!(!verify_signature("secret", 1700000001, payload, &signed));
+		assert!(!verify_signature("other", 1700000000, payload, &signed));
+		assert!(!verify_signature(
+			"secret",
+			1700000000,
+			payload,
+			"sha256=00"
+		));
+	}
+
+	#[test]
+	fn events_are_filtered_by_topic_and_app() {
+		let data_verified = |app_id| Event::DataVerified(AppId(app_id), 1, vec![]);
+		let all = webhook("http://127.0.0.1:8080", vec![], vec![]);
+		assert!(all.matches(&data_verified(1)));
+
+		let app = webhook("http://127.0.0.1:8080", vec![Topic::DataVerified], vec![2]);
+		assert!(app.matches(&data_verified(2)));
+		assert!(!app.matches(&data_verified(1)));
+
+		let confidence = webhook(
+			"http://127.0.0.1:8080",
+			vec![Topic::ConfidenceAchieved],
+			vec![],
+		);
+		assert!(!confidence.matches(&data_verified(1)));
+	}
+}